@@ -0,0 +1,10 @@
+#![no_main]
+
+use debian::package::ControlFile;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(cf) = ControlFile::from_reader(data) {
+        let _ = cf.to_string();
+    }
+});
@@ -0,0 +1,12 @@
+#![no_main]
+
+use debian::package::parse_dep_list;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &str| {
+    if let Ok(deps) = parse_dep_list(data) {
+        for d in deps {
+            let _ = d.to_string();
+        }
+    }
+});
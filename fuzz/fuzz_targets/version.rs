@@ -0,0 +1,21 @@
+#![no_main]
+
+use debian::version::{compare, Version};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &str| {
+    let strict = Version::parse_strict(data);
+    if let Ok(v) = Version::parse(data) {
+        // strictly valid versions must round-trip losslessly
+        if strict.is_ok() {
+            assert_eq!(v.to_string(), data);
+        }
+        let _ = v.increment_revision();
+        let _ = v.nmu();
+        let _ = v.binnmu(1);
+        let _ = v.stable_update(12);
+        let _ = v.backport("12", Some(&v));
+        let other = Version::parse("1.0-1").unwrap();
+        assert_eq!(compare(data, "1.0-1").ok(), Some(v.cmp(&other)));
+    }
+});
@@ -0,0 +1,271 @@
+//! Parser and writer for the update-alternatives database
+//!
+//! update-alternatives keeps one file per link group in its
+//! administrative directory, usually `/var/lib/dpkg/alternatives`,
+//! recording the mode of the group, its master link and slave links,
+//! and all alternatives with their priorities and slave paths:
+//!
+//! ```text
+//! auto
+//! /usr/bin/editor
+//! editor.1.gz
+//! /usr/share/man/man1/editor.1.gz
+//!
+//! /usr/bin/vim.basic
+//! 30
+//! /usr/share/man/man1/vim.1.gz
+//! /bin/nano
+//! 40
+//! /usr/share/man/man1/nano.1.gz
+//!
+//! ```
+//!
+//! The alternative selected is the target of the symbolic link named
+//! after the group in `/etc/alternatives`. In automatic mode, it is the
+//! one with the highest priority.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::alternatives::{AlternativeMode, LinkGroup};
+//!
+//! let text = "auto\n/usr/bin/editor\n\n/bin/nano\n40\n/bin/ed\n-100\n\n";
+//! let group = LinkGroup::parse("editor", text).unwrap();
+//! assert_eq!(group.mode, AlternativeMode::Auto);
+//! assert_eq!(group.best().unwrap().path, "/bin/nano");
+//! assert!(group.needs_update(Some("/bin/ed")));
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Default location of the administrative directory.
+pub const ALTERNATIVES_ADMIN_DIR: &str = "/var/lib/dpkg/alternatives";
+
+/// Default location of the symbolic links to the selected alternatives.
+pub const ALTERNATIVES_DIR: &str = "/etc/alternatives";
+
+/// Whether the alternative of a link group is selected automatically.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AlternativeMode {
+    /// the alternative with the highest priority is selected
+    Auto,
+    /// the alternative was selected by the administrator
+    Manual,
+}
+
+/// A link following the master link of a group, like a manual page.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SlaveLink {
+    /// the name, also used for its link in `/etc/alternatives`
+    pub name: String,
+    /// the path of the link, like `/usr/share/man/man1/editor.1.gz`
+    pub link: String,
+}
+
+/// A single alternative of a link group.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Alternative {
+    /// the path the master link points to, like `/bin/nano`
+    pub path: String,
+    pub priority: i32,
+    /// the paths of the slave links, by their name, for those provided
+    pub slaves: Vec<(String, String)>,
+}
+
+/// A link group, as recorded in the administrative directory.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LinkGroup {
+    /// the name of the group, like `editor`
+    pub name: String,
+    pub mode: AlternativeMode,
+    /// the path of the master link, like `/usr/bin/editor`
+    pub link: String,
+    pub slaves: Vec<SlaveLink>,
+    pub alternatives: Vec<Alternative>,
+}
+
+impl FromStr for AlternativeMode {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(AlternativeMode::Auto),
+            "manual" => Ok(AlternativeMode::Manual),
+            _ => Err("invalid mode"),
+        }
+    }
+}
+
+impl fmt::Display for AlternativeMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AlternativeMode::Auto => write!(f, "auto"),
+            AlternativeMode::Manual => write!(f, "manual"),
+        }
+    }
+}
+
+impl Alternative {
+    /// Get the path of a slave link for this alternative.
+    pub fn get_slave(&self, name: &str) -> Option<&str> {
+        self.slaves
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, p)| p.as_str())
+    }
+}
+
+impl LinkGroup {
+    /// Reads the file of a link group from the administrative
+    /// directory, taking the name of the group from the file name.
+    pub fn from_file(in_file: &Path) -> io::Result<LinkGroup> {
+        let name = in_file
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        LinkGroup::parse(&name, &fs::read_to_string(in_file)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Serializes this link group to its file in an administrative
+    /// directory.
+    pub fn to_dir(&self, admin_dir: &Path) -> io::Result<()> {
+        fs::write(admin_dir.join(&self.name), self.to_string())
+    }
+
+    /// Parses the contents of the file of a link group.
+    pub fn parse(name: &str, text: &str) -> Result<LinkGroup, &'static str> {
+        let mut lines = text.lines();
+        let mut next = || lines.next().ok_or("unexpected end of file");
+        let mode = next()?.parse()?;
+        let link = next()?.to_string();
+        if link.is_empty() {
+            return Err("missing master link");
+        }
+        let mut slaves = vec![];
+        loop {
+            let name = next()?;
+            if name.is_empty() {
+                break;
+            }
+            let link = next()?;
+            if link.is_empty() {
+                return Err("missing slave link");
+            }
+            slaves.push(SlaveLink {
+                name: name.to_string(),
+                link: link.to_string(),
+            });
+        }
+        let mut alternatives = vec![];
+        loop {
+            let path = next()?;
+            if path.is_empty() {
+                break;
+            }
+            let priority = next()?.parse().map_err(|_| "invalid priority")?;
+            let mut alternative = Alternative {
+                path: path.to_string(),
+                priority,
+                slaves: vec![],
+            };
+            for slave in &slaves {
+                match next()? {
+                    "" => {}
+                    p => {
+                        alternative.slaves.push((slave.name.clone(), p.into()))
+                    }
+                }
+            }
+            alternatives.push(alternative);
+        }
+        Ok(LinkGroup {
+            name: name.to_string(),
+            mode,
+            link,
+            slaves,
+            alternatives,
+        })
+    }
+
+    /// Look up an alternative by its path.
+    pub fn get(&self, path: &str) -> Option<&Alternative> {
+        self.alternatives.iter().find(|a| a.path == path)
+    }
+
+    /// The alternative with the highest priority, the first one listed
+    /// if several share it.
+    pub fn best(&self) -> Option<&Alternative> {
+        self.alternatives.iter().fold(None, |best, a| match best {
+            Some(b) if b.priority >= a.priority => Some(b),
+            _ => Some(a),
+        })
+    }
+
+    /// Reads the path of the alternative currently selected from the
+    /// symbolic link of the group in the given directory, usually
+    /// `/etc/alternatives`.
+    pub fn current(
+        &self,
+        alternatives_dir: &Path,
+    ) -> io::Result<Option<String>> {
+        match fs::read_link(alternatives_dir.join(&self.name)) {
+            Ok(target) => Ok(Some(target.to_string_lossy().into_owned())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Whether the selection differs from what update-alternatives
+    /// would configure, given the path currently selected: in automatic
+    /// mode, the best alternative should be selected, in manual mode,
+    /// any of the alternatives, falling back to the best one.
+    pub fn needs_update(&self, current: Option<&str>) -> bool {
+        let expected = match self.mode {
+            AlternativeMode::Auto => self.best(),
+            AlternativeMode::Manual => {
+                current.and_then(|c| self.get(c)).or_else(|| self.best())
+            }
+        };
+        expected.map(|a| a.path.as_str()) != current
+    }
+}
+
+impl fmt::Display for LinkGroup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.mode)?;
+        writeln!(f, "{}", self.link)?;
+        for slave in &self.slaves {
+            writeln!(f, "{}", slave.name)?;
+            writeln!(f, "{}", slave.link)?;
+        }
+        writeln!(f)?;
+        for alternative in &self.alternatives {
+            writeln!(f, "{}", alternative.path)?;
+            writeln!(f, "{}", alternative.priority)?;
+            for slave in &self.slaves {
+                let path = alternative.get_slave(&slave.name).unwrap_or("");
+                writeln!(f, "{path}")?;
+            }
+        }
+        writeln!(f)
+    }
+}
+
+/// Reads all link groups of an administrative directory, sorted by
+/// name.
+pub fn read_admin_dir(admin_dir: &Path) -> io::Result<Vec<LinkGroup>> {
+    let mut result = vec![];
+    for entry in fs::read_dir(admin_dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            result.push(LinkGroup::from_file(&entry.path())?);
+        }
+    }
+    result.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(result)
+}
@@ -0,0 +1,452 @@
+//! Parser and writer for apt's External Dependency Solver Protocol
+//!
+//! apt can leave dependency resolution to an external solver, like
+//! `apt install --solver foo`, running `/usr/lib/apt/solvers/foo` with
+//! a scenario on its standard input and reading the answer from its
+//! standard output. Both are in the deb822 format.
+//!
+//! A scenario starts with a request paragraph, listing the packages to
+//! install or remove, followed by one paragraph per package known to
+//! apt, identified by its `APT-ID`. The answer lists the packages to
+//! install or remove by their ids, or reports an error. Progress
+//! information may be sent before it.
+//!
+//! # Examples
+//!
+//! A minimal external solver, using the resolver of this crate:
+//!
+//! ```no_run
+//! use debian::apt::edsp::{write_progress, EdspAnswer, EdspScenario};
+//! use std::io;
+//!
+//! let scenario = EdspScenario::from_reader(io::stdin().lock()).unwrap();
+//! write_progress(&mut io::stdout(), 0, "Resolving").unwrap();
+//! let resolver = scenario.resolver().unwrap();
+//! let requests = scenario.request.install_dependencies();
+//! let answer = match resolver.resolve(&requests) {
+//!     Ok(set) => EdspAnswer::from_installation_set(&set).unwrap(),
+//!     Err(err) => EdspAnswer::Error("resolver".into(), err.to_string()),
+//! };
+//! print!("{answer}");
+//! ```
+
+use std::fmt;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+use chrono::Utc;
+
+use crate::package::{
+    join_multiline, split_multiline, ControlFile, ControlParagraph, Dependency,
+    SingleDependency,
+};
+use crate::resolver::{Candidate, InstallationSet, Resolver};
+
+/// The version of the protocol supported.
+pub const EDSP_VERSION: &str = "0.5";
+
+/// The request paragraph of a scenario.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct EdspRequest {
+    /// the protocol version, like `0.5`
+    pub version: String,
+    /// the native architecture
+    pub architecture: Option<String>,
+    /// all architectures configured, including the native one
+    pub architectures: Vec<String>,
+    /// the packages to install, like `foo:amd64`
+    pub install: Vec<String>,
+    /// the packages to remove
+    pub remove: Vec<String>,
+    /// whether all installed packages should be upgraded
+    pub upgrade_all: bool,
+    /// whether packages no longer needed should be removed
+    pub autoremove: bool,
+    /// whether only the candidate versions chosen by apt may be
+    /// installed
+    pub strict_pinning: bool,
+    /// whether packages not installed may not be installed
+    pub forbid_new_install: bool,
+    /// whether installed packages may not be removed
+    pub forbid_remove: bool,
+    /// the name of the solver asked
+    pub solver: Option<String>,
+}
+
+/// A scenario given to an external solver.
+#[derive(Debug, Clone)]
+pub struct EdspScenario {
+    pub request: EdspRequest,
+    /// the packages known to apt, installed or available
+    pub packages: Vec<ControlParagraph>,
+}
+
+/// The kinds of actions of a solution.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EdspActionKind {
+    Install,
+    Remove,
+    /// remove a package no longer needed
+    Autoremove,
+}
+
+/// A single action of a solution.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct EdspAction {
+    pub kind: EdspActionKind,
+    /// the `APT-ID` of the package
+    pub id: String,
+    /// the name, version and architecture of the package, informative
+    /// only
+    pub package: Option<String>,
+    pub version: Option<String>,
+    pub architecture: Option<String>,
+}
+
+/// The answer of an external solver.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum EdspAnswer {
+    /// the actions to take
+    Solution(Vec<EdspAction>),
+    /// an error, given by an id and a message, which may span multiple
+    /// lines
+    Error(String, String),
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn parse_bool(para: &ControlParagraph, key: &str) -> io::Result<Option<bool>> {
+    match para.get_entry(key) {
+        Some("yes") => Ok(Some(true)),
+        Some("no") => Ok(Some(false)),
+        Some(_) => Err(invalid(&format!("invalid {key} value"))),
+        None => Ok(None),
+    }
+}
+
+fn split_list(para: &ControlParagraph, key: &str) -> Vec<String> {
+    let value = para.get_entry(key).unwrap_or("");
+    value.split_whitespace().map(str::to_string).collect()
+}
+
+/// Whether a package paragraph of a scenario is installed.
+fn is_installed(para: &ControlParagraph) -> bool {
+    para.get_entry("Installed") == Some("yes")
+}
+
+/// Writes a progress paragraph, with a percentage from 0 to 100.
+pub fn write_progress<W: Write>(
+    out: &mut W,
+    percentage: u8,
+    message: &str,
+) -> io::Result<()> {
+    writeln!(out, "Progress: {}", Utc::now().to_rfc2822())?;
+    writeln!(out, "Percentage: {percentage}")?;
+    writeln!(out, "Message: {message}")?;
+    writeln!(out)?;
+    out.flush()
+}
+
+impl Default for EdspRequest {
+    fn default() -> Self {
+        EdspRequest {
+            version: EDSP_VERSION.to_string(),
+            architecture: None,
+            architectures: vec![],
+            install: vec![],
+            remove: vec![],
+            upgrade_all: false,
+            autoremove: false,
+            strict_pinning: true,
+            forbid_new_install: false,
+            forbid_remove: false,
+            solver: None,
+        }
+    }
+}
+
+impl EdspRequest {
+    /// Parses a request paragraph.
+    pub fn from_paragraph(para: &ControlParagraph) -> io::Result<EdspRequest> {
+        let version = para
+            .get_entry("Request")
+            .and_then(|r| r.strip_prefix("EDSP "))
+            .ok_or_else(|| invalid("not an EDSP request"))?;
+        let mut request = EdspRequest {
+            version: version.trim().to_string(),
+            architecture: para.get_entry("Architecture").map(str::to_string),
+            architectures: split_list(para, "Architectures"),
+            install: split_list(para, "Install"),
+            remove: split_list(para, "Remove"),
+            solver: para.get_entry("Solver").map(str::to_string),
+            ..Default::default()
+        };
+        // deprecated variants of Upgrade-All
+        if parse_bool(para, "Upgrade")? == Some(true) {
+            request.upgrade_all = true;
+            request.forbid_new_install = true;
+            request.forbid_remove = true;
+        }
+        if parse_bool(para, "Dist-Upgrade")? == Some(true) {
+            request.upgrade_all = true;
+        }
+        let flags = [
+            ("Upgrade-All", &mut request.upgrade_all),
+            ("Autoremove", &mut request.autoremove),
+            ("Strict-Pinning", &mut request.strict_pinning),
+            ("Forbid-New-Install", &mut request.forbid_new_install),
+            ("Forbid-Remove", &mut request.forbid_remove),
+        ];
+        for (key, flag) in flags {
+            if let Some(value) = parse_bool(para, key)? {
+                *flag = value;
+            }
+        }
+        Ok(request)
+    }
+
+    /// The packages to install as dependencies, for the resolver.
+    pub fn install_dependencies(&self) -> Vec<Dependency> {
+        self.install
+            .iter()
+            .map(|package| Dependency {
+                alternatives: vec![SingleDependency {
+                    package: package.clone(),
+                    version: None,
+                    arch: None,
+                    condition: None,
+                    extra_conditions: vec![],
+                }],
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for EdspRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Request: EDSP {}", self.version)?;
+        if let Some(ref arch) = self.architecture {
+            writeln!(f, "Architecture: {arch}")?;
+        }
+        for (key, list) in [
+            ("Architectures", &self.architectures),
+            ("Install", &self.install),
+            ("Remove", &self.remove),
+        ] {
+            if !list.is_empty() {
+                writeln!(f, "{key}: {}", list.join(" "))?;
+            }
+        }
+        for (key, value, default) in [
+            ("Upgrade-All", self.upgrade_all, false),
+            ("Autoremove", self.autoremove, false),
+            ("Strict-Pinning", self.strict_pinning, true),
+            ("Forbid-New-Install", self.forbid_new_install, false),
+            ("Forbid-Remove", self.forbid_remove, false),
+        ] {
+            if value != default {
+                writeln!(f, "{key}: {}", if value { "yes" } else { "no" })?;
+            }
+        }
+        if let Some(ref solver) = self.solver {
+            writeln!(f, "Solver: {solver}")?;
+        }
+        Ok(())
+    }
+}
+
+impl EdspScenario {
+    /// Reads and parses a scenario from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<EdspScenario> {
+        let file = std::fs::File::open(in_file)?;
+        EdspScenario::from_reader(BufReader::new(file))
+    }
+
+    /// Parses a scenario from any buffered reader, like the standard
+    /// input of a solver.
+    pub fn from_reader<R: BufRead>(buf: R) -> io::Result<EdspScenario> {
+        let cf = ControlFile::from_reader(buf)?;
+        let (first, packages) = cf
+            .get_paragraphs()
+            .split_first()
+            .ok_or_else(|| invalid("empty EDSP scenario"))?;
+        let request = EdspRequest::from_paragraph(first)?;
+        for para in packages {
+            for field in ["Package", "Version", "Architecture", "APT-ID"] {
+                if !para.has_entry(field) {
+                    return Err(invalid(&format!("missing {field} field")));
+                }
+            }
+        }
+        Ok(EdspScenario {
+            request,
+            packages: packages.to_vec(),
+        })
+    }
+
+    /// The package with the given `APT-ID`.
+    pub fn package(&self, id: &str) -> Option<&ControlParagraph> {
+        self.packages
+            .iter()
+            .find(|p| p.get_entry("APT-ID") == Some(id))
+    }
+
+    /// The packages installed.
+    pub fn installed(&self) -> impl Iterator<Item = &ControlParagraph> {
+        self.packages.iter().filter(|p| is_installed(p))
+    }
+
+    /// Creates a resolver knowing all packages of the scenario, with
+    /// the installed ones satisfying dependencies.
+    pub fn resolver(&self) -> Result<Resolver, &'static str> {
+        let mut resolver = Resolver::default();
+        for para in &self.packages {
+            let candidate = Candidate::from_paragraph(para.clone())?;
+            if is_installed(para) {
+                resolver.add_installed(candidate.clone());
+            }
+            resolver.add_candidate(candidate);
+        }
+        Ok(resolver)
+    }
+}
+
+impl fmt::Display for EdspScenario {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.request)?;
+        for para in &self.packages {
+            write!(f, "\n{para}")?;
+        }
+        Ok(())
+    }
+}
+
+impl EdspActionKind {
+    /// The field naming the action in an answer.
+    pub fn field(&self) -> &'static str {
+        match *self {
+            EdspActionKind::Install => "Install",
+            EdspActionKind::Remove => "Remove",
+            EdspActionKind::Autoremove => "Autoremove",
+        }
+    }
+}
+
+impl EdspAction {
+    /// Creates an action for a package paragraph of a scenario.
+    pub fn new(
+        kind: EdspActionKind,
+        para: &ControlParagraph,
+    ) -> Result<EdspAction, &'static str> {
+        let id = para.get_entry("APT-ID").ok_or("missing APT-ID field")?;
+        let field = |key| para.get_entry(key).map(str::to_string);
+        Ok(EdspAction {
+            kind,
+            id: id.to_string(),
+            package: field("Package"),
+            version: field("Version"),
+            architecture: field("Architecture"),
+        })
+    }
+}
+
+impl fmt::Display for EdspAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}: {}", self.kind.field(), self.id)?;
+        for (key, value) in [
+            ("Package", &self.package),
+            ("Version", &self.version),
+            ("Architecture", &self.architecture),
+        ] {
+            if let Some(value) = value {
+                writeln!(f, "{key}: {value}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl EdspAnswer {
+    /// Reads and parses an answer from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<EdspAnswer> {
+        let file = std::fs::File::open(in_file)?;
+        EdspAnswer::from_reader(BufReader::new(file))
+    }
+
+    /// Parses an answer from any buffered reader, skipping progress
+    /// information.
+    pub fn from_reader<R: BufRead>(buf: R) -> io::Result<EdspAnswer> {
+        let cf = ControlFile::from_reader(buf)?;
+        let mut actions = vec![];
+        for para in cf.get_paragraphs() {
+            if para.has_entry("Progress") {
+                continue;
+            }
+            if let Some(id) = para.get_entry("Error") {
+                let (first, rest) =
+                    split_multiline(para.get_entry("Message").unwrap_or(""));
+                let mut message = first.to_string();
+                for line in rest {
+                    message.push('\n');
+                    message.push_str(line);
+                }
+                return Ok(EdspAnswer::Error(id.to_string(), message));
+            }
+            let kinds = [
+                EdspActionKind::Install,
+                EdspActionKind::Remove,
+                EdspActionKind::Autoremove,
+            ];
+            let kind = kinds
+                .into_iter()
+                .find(|k| para.has_entry(k.field()))
+                .ok_or_else(|| invalid("unknown paragraph in EDSP answer"))?;
+            let field = |key| para.get_entry(key).map(str::to_string);
+            actions.push(EdspAction {
+                kind,
+                id: field(kind.field()).unwrap(),
+                package: field("Package"),
+                version: field("Version"),
+                architecture: field("Architecture"),
+            });
+        }
+        Ok(EdspAnswer::Solution(actions))
+    }
+
+    /// Creates a solution installing the packages of an installation
+    /// set computed from a scenario.
+    pub fn from_installation_set(
+        set: &InstallationSet,
+    ) -> Result<EdspAnswer, &'static str> {
+        set.packages()
+            .map(|c| EdspAction::new(EdspActionKind::Install, c.paragraph()))
+            .collect::<Result<_, _>>()
+            .map(EdspAnswer::Solution)
+    }
+}
+
+impl fmt::Display for EdspAnswer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EdspAnswer::Solution(ref actions) => {
+                for (i, action) in actions.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{action}")?;
+                }
+                Ok(())
+            }
+            EdspAnswer::Error(ref id, ref message) => {
+                let mut lines = message.lines();
+                let first = lines.next().unwrap_or("");
+                let rest: Vec<&str> = lines.collect();
+                writeln!(f, "Error: {id}")?;
+                writeln!(f, "Message: {}", join_multiline(first, &rest))
+            }
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! Parser for apt's extended states
+//!
+//! Apt records which packages have been installed automatically (i.e.
+//! only to satisfy dependencies) in `/var/lib/apt/extended_states`.
+//! Together with the dpkg status database, this allows determining
+//! which packages are no longer needed.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use debian::apt::extended_states::ExtendedStates;
+//! use debian::status::DpkgStatus;
+//! use std::path::Path;
+//!
+//! let states = ExtendedStates::from_file(Path::new(
+//!     "/var/lib/apt/extended_states",
+//! ))
+//! .unwrap();
+//! let status = DpkgStatus::from_file(Path::new("/var/lib/dpkg/status"))
+//!     .unwrap();
+//! for entry in states.auto_removable(&status) {
+//!     println!("{} is no longer needed", entry.package);
+//! }
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::io::BufRead;
+use std::path::Path;
+
+use crate::package::{parse_dep_list, ControlFile};
+use crate::status::{DpkgStatus, StatusEntry};
+
+/// Default location of apt's extended states file.
+pub const EXTENDED_STATES_PATH: &str = "/var/lib/apt/extended_states";
+
+/// Extended state information for a single package.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ExtendedState {
+    /// the package has been installed to satisfy a dependency
+    pub auto_installed: bool,
+}
+
+/// All extended states, keyed by package name and architecture.
+#[derive(Debug, Default)]
+pub struct ExtendedStates {
+    states: HashMap<(String, String), ExtendedState>,
+}
+
+impl ExtendedStates {
+    /// Reads and parses an extended states file from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<ExtendedStates> {
+        ExtendedStates::from_control(ControlFile::from_file(in_file)?)
+    }
+
+    /// Parses extended states from any buffered reader.
+    pub fn from_reader<R: BufRead>(buf: R) -> io::Result<ExtendedStates> {
+        ExtendedStates::from_control(ControlFile::from_reader(buf)?)
+    }
+
+    fn from_control(cf: ControlFile) -> io::Result<ExtendedStates> {
+        let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let mut result = ExtendedStates::default();
+        for para in cf.get_paragraphs() {
+            let package = para
+                .get_entry("Package")
+                .ok_or_else(|| invalid("missing Package field"))?;
+            let arch = para
+                .get_entry("Architecture")
+                .ok_or_else(|| invalid("missing Architecture field"))?;
+            let auto_installed = match para.get_entry("Auto-Installed") {
+                Some("1") => true,
+                Some("0") | None => false,
+                Some(_) => return Err(invalid("invalid Auto-Installed value")),
+            };
+            result.set(package, arch, ExtendedState { auto_installed });
+        }
+        Ok(result)
+    }
+
+    /// Set the extended state of a package.
+    pub fn set(&mut self, package: &str, arch: &str, state: ExtendedState) {
+        self.states
+            .insert((package.to_string(), arch.to_string()), state);
+    }
+
+    /// Get the extended state of a package, if recorded.
+    pub fn get(&self, package: &str, arch: &str) -> Option<&ExtendedState> {
+        self.states.get(&(package.to_string(), arch.to_string()))
+    }
+
+    /// Check whether a package has been installed automatically.
+    pub fn is_auto_installed(&self, package: &str, arch: &str) -> bool {
+        self.get(package, arch).map_or(false, |s| s.auto_installed)
+    }
+
+    /// Iterate over all recorded states.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str, &ExtendedState)> {
+        self.states
+            .iter()
+            .map(|((p, a), s)| (p.as_str(), a.as_str(), s))
+    }
+
+    fn is_auto(&self, entry: &StatusEntry) -> bool {
+        match entry.architecture {
+            Some(ref arch) => self.is_auto_installed(&entry.package, arch),
+            None => false,
+        }
+    }
+
+    /// Determine installed packages that have been installed
+    /// automatically but are no longer required by any manually
+    /// installed package.
+    ///
+    /// Just like apt, this follows `Pre-Depends`, `Depends` and
+    /// `Recommends`, considering any installed alternative or provider
+    /// as required. Essential packages are never removable.
+    pub fn auto_removable<'a>(
+        &self,
+        status: &'a DpkgStatus,
+    ) -> Vec<&'a StatusEntry> {
+        let mut providers: HashMap<String, Vec<&str>> = HashMap::new();
+        for entry in status.installed() {
+            providers
+                .entry(entry.package.clone())
+                .or_default()
+                .push(entry.package.as_str());
+            let provides = entry.get_field("Provides").unwrap_or("");
+            for dep in parse_dep_list(provides).unwrap_or_default() {
+                for alt in dep.alternatives {
+                    let name = strip_arch_qualifier(&alt.package);
+                    providers
+                        .entry(name.to_string())
+                        .or_default()
+                        .push(entry.package.as_str());
+                }
+            }
+        }
+
+        let mut required: HashSet<&str> = HashSet::new();
+        let mut todo: Vec<&StatusEntry> = status
+            .installed()
+            .filter(|e| {
+                !self.is_auto(e) || e.get_field("Essential") == Some("yes")
+            })
+            .collect();
+        while let Some(entry) = todo.pop() {
+            if !required.insert(entry.package.as_str()) {
+                continue;
+            }
+            for field in ["Pre-Depends", "Depends", "Recommends"] {
+                let value = match entry.get_field(field) {
+                    Some(v) => v,
+                    None => continue,
+                };
+                for dep in parse_dep_list(value).unwrap_or_default() {
+                    for alt in dep.alternatives {
+                        let name = strip_arch_qualifier(&alt.package);
+                        for p in providers.get(name).into_iter().flatten() {
+                            todo.extend(status.get_all(p));
+                        }
+                    }
+                }
+            }
+        }
+
+        status
+            .installed()
+            .filter(|e| !required.contains(e.package.as_str()))
+            .collect()
+    }
+}
+
+/// Strips an architecture qualifier like `:any` from a package name.
+fn strip_arch_qualifier(name: &str) -> &str {
+    name.split(':').next().unwrap_or(name)
+}
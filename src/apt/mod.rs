@@ -0,0 +1,11 @@
+//! Tools for interacting with apt
+//!
+//! This module contains parsers for the state and configuration files
+//! of apt and for the protocol apt uses to talk to external solvers, as
+//! well as apt's selection of candidate versions.
+
+pub mod edsp;
+pub mod extended_states;
+pub mod policy;
+pub mod preferences;
+pub mod sources;
@@ -0,0 +1,231 @@
+//! Candidate version selection like apt's policy
+//!
+//! Out of the versions of a package available from the origins of a
+//! `PackageUniverse` and the one installed, apt chooses a candidate to
+//! install or upgrade to. Each version gets the highest of the
+//! priorities of the origins providing it, as determined by the pin
+//! stanzas of the preferences or the default priority of the release,
+//! which takes `NotAutomatic` and `ButAutomaticUpgrades` into account.
+//! The installed version has at least priority 100.
+//!
+//! Going from the newest version to older ones, the first version with
+//! the highest priority wins. Versions older than the installed one
+//! only win with a priority of 1000 or more, as they are downgrades.
+//! Versions with a priority of zero or less never win.
+//!
+//! `Policy::versions` lists all versions with their priorities, much
+//! like `apt-cache policy`, to tell why a version is chosen.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::apt::policy::Policy;
+//! use debian::apt::preferences::{Preferences, ReleaseInfo};
+//! use debian::package::ControlFile;
+//! use debian::universe::{Origin, PackageUniverse};
+//! use debian::Version;
+//!
+//! let mut universe = PackageUniverse::default();
+//! let stable = universe.add_origin(Origin::new(
+//!     "bookworm",
+//!     ReleaseInfo {
+//!         archive: Some("stable".to_string()),
+//!         ..Default::default()
+//!     },
+//! ));
+//! let backports = universe.add_origin(Origin::new(
+//!     "bookworm-backports",
+//!     ReleaseInfo {
+//!         archive: Some("stable-backports".to_string()),
+//!         not_automatic: true,
+//!         but_automatic_upgrades: true,
+//!         ..Default::default()
+//!     },
+//! ));
+//! let available = [(stable, "1.0-1"), (backports, "2.0-1~bpo12+1")];
+//! for (origin, version) in available {
+//!     let text = format!("Package: foo\nVersion: {version}\n");
+//!     let index = ControlFile::from_reader(text.as_bytes()).unwrap();
+//!     universe.add_packages(origin, &index).unwrap();
+//! }
+//!
+//! let prefs = Preferences::default();
+//! let mut policy = Policy::new(&universe, &prefs);
+//! let candidate = policy.candidate_version("foo").unwrap();
+//! assert_eq!(candidate.to_string(), "1.0-1");
+//!
+//! // once installed from backports, upgrades are taken from there
+//! policy.add_installed("foo", Version::parse("1.9-1~bpo12+1").unwrap());
+//! let candidate = policy.candidate_version("foo").unwrap();
+//! assert_eq!(candidate.to_string(), "2.0-1~bpo12+1");
+//! ```
+
+use std::collections::HashMap;
+
+use crate::apt::preferences::{default_priority, Preferences, ReleaseInfo};
+use crate::status::DpkgStatus;
+use crate::universe::{Entry, PackageUniverse};
+use crate::Version;
+
+/// The priority from which on a version is installed even if that
+/// means a downgrade.
+pub const DOWNGRADE_PRIORITY: i32 = 1000;
+
+/// Computes candidate versions considering the preferences.
+#[derive(Debug, Clone)]
+pub struct Policy<'a> {
+    universe: &'a PackageUniverse,
+    preferences: &'a Preferences,
+    /// installed versions by package name
+    installed: HashMap<String, Version>,
+}
+
+/// A version of a package with the priority assigned to it.
+#[derive(Debug, Clone)]
+pub struct VersionPriority<'a> {
+    pub version: Version,
+    /// the highest priority of the origins providing the version
+    pub priority: i32,
+    /// the entries of the universe providing this version, with the
+    /// priority of each
+    pub entries: Vec<(&'a Entry, i32)>,
+    /// whether this is the installed version
+    pub installed: bool,
+}
+
+/// The name of the source package of an entry.
+fn source_name(entry: &Entry) -> &str {
+    match entry.candidate.get_field("Source") {
+        Some(source) => source.split_whitespace().next().unwrap_or(source),
+        None => &entry.candidate.package,
+    }
+}
+
+impl<'a> Policy<'a> {
+    /// Creates a policy for the packages of a universe, without any
+    /// package installed.
+    pub fn new(
+        universe: &'a PackageUniverse,
+        preferences: &'a Preferences,
+    ) -> Policy<'a> {
+        Policy {
+            universe,
+            preferences,
+            installed: HashMap::new(),
+        }
+    }
+
+    /// Considers the packages installed according to a status database.
+    pub fn set_status(&mut self, status: &DpkgStatus) {
+        self.installed.clear();
+        for entry in status.installed() {
+            if let Some(ref version) = entry.version {
+                self.add_installed(&entry.package, version.clone());
+            }
+        }
+    }
+
+    /// Records a version of a package as installed, replacing any other
+    /// version recorded.
+    pub fn add_installed(&mut self, package: &str, version: Version) {
+        self.installed.insert(package.to_string(), version);
+    }
+
+    /// The installed version of a package, if any.
+    pub fn installed(&self, package: &str) -> Option<&Version> {
+        self.installed.get(package)
+    }
+
+    /// The priority of a version available from an origin.
+    pub fn priority(&self, entry: &Entry) -> i32 {
+        let origin = self.universe.origin(entry.origin);
+        let candidate = &entry.candidate;
+        self.preferences
+            .matching_stanza(
+                &candidate.package,
+                Some(source_name(entry)),
+                &candidate.version,
+                &origin.release,
+            )
+            .map_or(origin.priority, |stanza| stanza.priority)
+    }
+
+    /// All versions of a package, newest first, with their priorities.
+    pub fn versions(&self, package: &str) -> Vec<VersionPriority<'a>> {
+        let mut result: Vec<VersionPriority> = vec![];
+        for entry in self.universe.candidates(package) {
+            let priority = self.priority(entry);
+            let version = &entry.candidate.version;
+            match result.iter_mut().find(|v| &v.version == version) {
+                Some(v) => {
+                    v.priority = v.priority.max(priority);
+                    v.entries.push((entry, priority));
+                }
+                None => result.push(VersionPriority {
+                    version: version.clone(),
+                    priority,
+                    entries: vec![(entry, priority)],
+                    installed: false,
+                }),
+            }
+        }
+        if let Some(installed) = self.installed(package) {
+            let status = ReleaseInfo {
+                is_installed: true,
+                ..Default::default()
+            };
+            let priority = self
+                .preferences
+                .matching_stanza(package, None, installed, &status)
+                .map_or(default_priority(&status), |stanza| stanza.priority);
+            match result.iter_mut().find(|v| &v.version == installed) {
+                Some(v) => {
+                    v.priority = v.priority.max(priority);
+                    v.installed = true;
+                }
+                None => result.push(VersionPriority {
+                    version: installed.clone(),
+                    priority,
+                    entries: vec![],
+                    installed: true,
+                }),
+            }
+        }
+        result.sort_by(|a, b| b.version.cmp(&a.version));
+        result
+    }
+
+    /// The version apt would install or upgrade to, if any. This may
+    /// be the installed version, which is not necessarily available
+    /// from any origin.
+    pub fn candidate(&self, package: &str) -> Option<VersionPriority<'a>> {
+        let mut max = 0;
+        let mut result = None;
+        for version in self.versions(package) {
+            let installed = version.installed;
+            if version.priority > max {
+                max = version.priority;
+                result = Some(version);
+            }
+            if installed {
+                // anything older is a downgrade
+                max = max.max(DOWNGRADE_PRIORITY - 1);
+            }
+        }
+        result
+    }
+
+    /// The candidate version of a package, if any.
+    pub fn candidate_version(&self, package: &str) -> Option<Version> {
+        self.candidate(package).map(|v| v.version)
+    }
+
+    /// Whether the candidate version is newer than the installed one,
+    /// i.e. apt would upgrade the package.
+    pub fn is_upgradable(&self, package: &str) -> bool {
+        match (self.installed(package), self.candidate_version(package)) {
+            (Some(installed), Some(candidate)) => &candidate > installed,
+            _ => false,
+        }
+    }
+}
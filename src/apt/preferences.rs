@@ -0,0 +1,599 @@
+//! Parser for apt preferences and pin priority computation
+//!
+//! Apt allows pinning packages to certain releases, origins or versions
+//! via `/etc/apt/preferences` and the files in `/etc/apt/preferences.d`.
+//! This module parses these pin stanzas and computes the resulting pin
+//! priority for a candidate version, following the rules described in
+//! apt_preferences(5).
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::apt::preferences::{Preferences, ReleaseInfo};
+//! use debian::Version;
+//!
+//! let prefs: Preferences = "Package: perl*\n\
+//!                           Pin: release a=unstable\n\
+//!                           Pin-Priority: 600\n"
+//!     .parse()
+//!     .unwrap();
+//! let release = ReleaseInfo {
+//!     archive: Some("unstable".to_string()),
+//!     ..Default::default()
+//! };
+//! let version = Version::parse("5.36.0-9").unwrap();
+//! assert_eq!(prefs.pin_priority("perl-base", &version, &release), 600);
+//! assert_eq!(prefs.pin_priority("bash", &version, &release), 500);
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::path::Path;
+use std::str::FromStr;
+
+use regex::Regex;
+
+use crate::archive::release::Release;
+use crate::package::{ControlFile, ControlParagraph};
+use crate::Version;
+
+/// Priority of versions from the default target release.
+pub const TARGET_RELEASE_PRIORITY: i32 = 990;
+/// Default priority of any available version.
+pub const DEFAULT_PRIORITY: i32 = 500;
+/// Priority of the currently installed version.
+pub const INSTALLED_PRIORITY: i32 = 100;
+/// Priority of versions from releases marked `NotAutomatic`.
+pub const NOT_AUTOMATIC_PRIORITY: i32 = 1;
+
+/// A pattern matching names, as used for package names and release
+/// properties.
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// An exact name
+    Exact(String),
+    /// A glob pattern using `*`, `?` and `[...]`
+    Glob(String),
+    /// A regular expression, written as `/regex/`
+    Regex(Regex),
+}
+
+/// A pattern matching package names, possibly source package names
+/// (written with a `src:` prefix).
+#[derive(Debug, Clone)]
+pub struct PackagePattern {
+    /// match the source package name instead of the binary one
+    pub source: bool,
+    pub pattern: Pattern,
+}
+
+/// Properties of a release a pin can refer to, as used in `Pin:
+/// release` lines.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ReleaseKey {
+    /// `a=`, the archive or suite, like `stable`
+    Archive,
+    /// `n=`, the codename, like `bookworm`
+    Codename,
+    /// `v=`, the release version, like `12.4`
+    Version,
+    /// `o=`, the origin, like `Debian`
+    Origin,
+    /// `l=`, the label, like `Debian-Security`
+    Label,
+    /// `c=`, the component, like `main`
+    Component,
+    /// `b=`, the architecture, like `amd64`
+    Architecture,
+}
+
+/// The target of a pin.
+#[derive(Debug, Clone)]
+pub enum Pin {
+    /// Pin to releases with matching properties.
+    Release(Vec<(ReleaseKey, Pattern)>),
+    /// Pin to versions matching a glob pattern.
+    Version(Pattern),
+    /// Pin to a site or hostname, the empty string denotes local
+    /// repositories.
+    Origin(String),
+}
+
+/// A single stanza of an apt preferences file.
+#[derive(Debug, Clone)]
+pub struct PinStanza {
+    /// packages this stanza applies to, an empty list means `*`
+    pub packages: Vec<PackagePattern>,
+    pub pin: Pin,
+    pub priority: i32,
+    /// free form explanation, if any
+    pub explanation: Option<String>,
+}
+
+/// Metadata about the release providing a candidate version, as found
+/// in its Release file.
+#[derive(Debug, Clone, Default)]
+pub struct ReleaseInfo {
+    pub archive: Option<String>,
+    pub codename: Option<String>,
+    pub version: Option<String>,
+    pub origin: Option<String>,
+    pub label: Option<String>,
+    pub component: Option<String>,
+    pub architecture: Option<String>,
+    /// hostname the release has been downloaded from, empty for local
+    /// repositories
+    pub site: Option<String>,
+    /// the release sets `NotAutomatic: yes`
+    pub not_automatic: bool,
+    /// the release sets `ButAutomaticUpgrades: yes`
+    pub but_automatic_upgrades: bool,
+    /// the release matches the configured default target release
+    pub is_target_release: bool,
+    /// the candidate version is the one currently installed
+    pub is_installed: bool,
+}
+
+/// A complete set of pin stanzas, in order of precedence.
+#[derive(Debug, Clone, Default)]
+pub struct Preferences {
+    stanzas: Vec<PinStanza>,
+}
+
+/// Matches `s` against a glob pattern supporting `*`, `?` and
+/// bracketed character classes.
+pub fn glob_match(pattern: &str, s: &str) -> bool {
+    fn class_match(class: &[char], c: char) -> bool {
+        let (negate, class) = match class.first() {
+            Some('!') | Some('^') => (true, &class[1..]),
+            _ => (false, class),
+        };
+        let mut i = 0;
+        let mut found = false;
+        while i < class.len() {
+            if i + 2 < class.len() && class[i + 1] == '-' {
+                found |= class[i] <= c && c <= class[i + 2];
+                i += 3;
+            } else {
+                found |= class[i] == c;
+                i += 1;
+            }
+        }
+        found != negate
+    }
+
+    fn rec(p: &[char], s: &[char]) -> bool {
+        match p.first() {
+            None => s.is_empty(),
+            Some('*') => (0..=s.len()).any(|i| rec(&p[1..], &s[i..])),
+            Some('?') => !s.is_empty() && rec(&p[1..], &s[1..]),
+            Some('[') => match p.iter().skip(1).position(|&c| c == ']') {
+                Some(end) if !s.is_empty() => {
+                    class_match(&p[1..end + 1], s[0])
+                        && rec(&p[end + 2..], &s[1..])
+                }
+                Some(_) => false,
+                None => s.first() == Some(&'[') && rec(&p[1..], &s[1..]),
+            },
+            Some(c) => s.first() == Some(c) && rec(&p[1..], &s[1..]),
+        }
+    }
+
+    let p = pattern.chars().collect::<Vec<char>>();
+    let s = s.chars().collect::<Vec<char>>();
+    rec(&p, &s)
+}
+
+impl Pattern {
+    /// Check whether the given name matches this pattern.
+    pub fn matches(&self, s: &str) -> bool {
+        match *self {
+            Pattern::Exact(ref p) => p == s,
+            Pattern::Glob(ref p) => glob_match(p, s),
+            Pattern::Regex(ref r) => r.is_match(s),
+        }
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() >= 2 && s.starts_with('/') && s.ends_with('/') {
+            match Regex::new(&s[1..s.len() - 1]) {
+                Ok(r) => Ok(Pattern::Regex(r)),
+                Err(_) => Err("invalid regular expression"),
+            }
+        } else if s.contains(['*', '?', '[']) {
+            Ok(Pattern::Glob(s.to_string()))
+        } else {
+            Ok(Pattern::Exact(s.to_string()))
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Pattern::Exact(ref p) | Pattern::Glob(ref p) => write!(f, "{p}"),
+            Pattern::Regex(ref r) => write!(f, "/{}/", r.as_str()),
+        }
+    }
+}
+
+impl FromStr for PackagePattern {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix("src:") {
+            Some(name) => Ok(PackagePattern {
+                source: true,
+                pattern: name.parse()?,
+            }),
+            None => Ok(PackagePattern {
+                source: false,
+                pattern: s.parse()?,
+            }),
+        }
+    }
+}
+
+impl fmt::Display for PackagePattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.source {
+            write!(f, "src:")?;
+        }
+        write!(f, "{}", self.pattern)
+    }
+}
+
+impl ReleaseKey {
+    fn from_abbrev(s: &str) -> Result<ReleaseKey, &'static str> {
+        match s {
+            "a" => Ok(ReleaseKey::Archive),
+            "n" => Ok(ReleaseKey::Codename),
+            "v" => Ok(ReleaseKey::Version),
+            "o" => Ok(ReleaseKey::Origin),
+            "l" => Ok(ReleaseKey::Label),
+            "c" => Ok(ReleaseKey::Component),
+            "b" => Ok(ReleaseKey::Architecture),
+            _ => Err("unknown release property"),
+        }
+    }
+
+    fn abbrev(&self) -> &'static str {
+        match *self {
+            ReleaseKey::Archive => "a",
+            ReleaseKey::Codename => "n",
+            ReleaseKey::Version => "v",
+            ReleaseKey::Origin => "o",
+            ReleaseKey::Label => "l",
+            ReleaseKey::Component => "c",
+            ReleaseKey::Architecture => "b",
+        }
+    }
+}
+
+impl FromStr for Pin {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (kind, rest) = match s.split_once(char::is_whitespace) {
+            Some((k, r)) => (k, r.trim()),
+            None => (s, ""),
+        };
+        match kind {
+            "release" => {
+                let mut props = vec![];
+                for prop in rest.split(',').map(|x| x.trim()) {
+                    if prop.is_empty() {
+                        continue;
+                    }
+                    match prop.split_once('=') {
+                        Some((k, v)) => props.push((
+                            ReleaseKey::from_abbrev(k.trim())?,
+                            v.trim().parse()?,
+                        )),
+                        // a plain value is a release version
+                        None => {
+                            props.push((ReleaseKey::Version, prop.parse()?))
+                        }
+                    }
+                }
+                Ok(Pin::Release(props))
+            }
+            "version" if !rest.is_empty() => {
+                Ok(Pin::Version(Pattern::Glob(rest.to_string())))
+            }
+            "origin" => Ok(Pin::Origin(rest.trim_matches('"').to_string())),
+            _ => Err("invalid pin"),
+        }
+    }
+}
+
+impl fmt::Display for Pin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Pin::Release(ref props) => {
+                let props = props
+                    .iter()
+                    .map(|(k, v)| format!("{}={}", k.abbrev(), v))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "release {props}")
+            }
+            Pin::Version(ref p) => write!(f, "version {p}"),
+            Pin::Origin(ref o) => write!(f, "origin \"{o}\""),
+        }
+    }
+}
+
+impl Pin {
+    /// Check whether a version from the given release matches this pin.
+    pub fn matches(&self, version: &Version, release: &ReleaseInfo) -> bool {
+        match *self {
+            Pin::Release(ref props) => props.iter().all(|(k, p)| {
+                let value = match *k {
+                    ReleaseKey::Archive => &release.archive,
+                    ReleaseKey::Codename => &release.codename,
+                    ReleaseKey::Version => &release.version,
+                    ReleaseKey::Origin => &release.origin,
+                    ReleaseKey::Label => &release.label,
+                    ReleaseKey::Component => &release.component,
+                    ReleaseKey::Architecture => &release.architecture,
+                };
+                value.as_deref().map_or(false, |v| p.matches(v))
+            }),
+            Pin::Version(ref p) => p.matches(&version.to_string()),
+            Pin::Origin(ref o) => release.site.as_deref() == Some(o.as_str()),
+        }
+    }
+}
+
+impl PinStanza {
+    /// Creates a `PinStanza` from a paragraph of a preferences file.
+    pub fn from_paragraph(
+        para: &ControlParagraph,
+    ) -> Result<PinStanza, &'static str> {
+        let packages = match para.get_entry("Package") {
+            Some(p) => p
+                .split_whitespace()
+                .filter(|x| *x != "*")
+                .map(|x| x.parse())
+                .collect::<Result<Vec<PackagePattern>, &'static str>>()?,
+            None => return Err("missing Package field"),
+        };
+        let pin = match para.get_entry("Pin") {
+            Some(p) => p.parse()?,
+            None => return Err("missing Pin field"),
+        };
+        let priority = match para.get_entry("Pin-Priority") {
+            Some(p) => match i32::from_str(p.trim()) {
+                Ok(p) => p,
+                Err(_) => return Err("invalid Pin-Priority"),
+            },
+            None => return Err("missing Pin-Priority field"),
+        };
+        Ok(PinStanza {
+            packages,
+            pin,
+            priority,
+            explanation: para.get_entry("Explanation").map(|x| x.to_string()),
+        })
+    }
+
+    /// Whether this is a general stanza applying to all packages.
+    pub fn is_general(&self) -> bool {
+        self.packages.is_empty()
+    }
+
+    /// Check whether this stanza applies to the given package.
+    pub fn applies_to(&self, package: &str, source: Option<&str>) -> bool {
+        self.is_general()
+            || self.packages.iter().any(|p| match (p.source, source) {
+                (false, _) => p.pattern.matches(package),
+                (true, Some(src)) => p.pattern.matches(src),
+                (true, None) => false,
+            })
+    }
+
+    fn serialize(&self) -> String {
+        let mut s = String::new();
+        if let Some(ref e) = self.explanation {
+            s += &format!("Explanation: {e}\n");
+        }
+        let packages = match self.packages.is_empty() {
+            true => "*".to_string(),
+            false => self
+                .packages
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<String>>()
+                .join(" "),
+        };
+        s + &format!(
+            "Package: {}\nPin: {}\nPin-Priority: {}\n",
+            packages, self.pin, self.priority
+        )
+    }
+}
+
+impl ReleaseInfo {
+    /// Takes the properties of a release from its Release file. The
+    /// component, architecture and site are not part of it and remain
+    /// unset.
+    pub fn from_release(release: &Release) -> ReleaseInfo {
+        let field = |key| release.get_entry(key).map(|v| v.to_string());
+        let flag = |key| release.get_entry(key) == Some("yes");
+        ReleaseInfo {
+            archive: field("Suite"),
+            codename: field("Codename"),
+            version: field("Version"),
+            origin: field("Origin"),
+            label: field("Label"),
+            not_automatic: flag("NotAutomatic"),
+            but_automatic_upgrades: flag("ButAutomaticUpgrades"),
+            ..Default::default()
+        }
+    }
+}
+
+/// Computes the priority apt assigns to a version if no pin applies.
+pub fn default_priority(release: &ReleaseInfo) -> i32 {
+    if release.is_target_release {
+        TARGET_RELEASE_PRIORITY
+    } else if release.not_automatic && release.but_automatic_upgrades {
+        INSTALLED_PRIORITY
+    } else if release.not_automatic {
+        NOT_AUTOMATIC_PRIORITY
+    } else if release.is_installed {
+        INSTALLED_PRIORITY
+    } else {
+        DEFAULT_PRIORITY
+    }
+}
+
+impl Preferences {
+    /// Reads and parses a single preferences file from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<Preferences> {
+        Preferences::from_control(&ControlFile::from_file(in_file)?)
+    }
+
+    /// Parses preferences from any buffered reader.
+    pub fn from_reader<R: BufRead>(buf: R) -> io::Result<Preferences> {
+        Preferences::from_control(&ControlFile::from_reader(buf)?)
+    }
+
+    /// Reads the complete preferences of an apt configuration
+    /// directory like `/etc/apt`, i.e. the `preferences` file followed
+    /// by the files in `preferences.d` in alphabetical order.
+    ///
+    /// Just like apt, only files without extension or with a `.pref`
+    /// extension are considered in the `preferences.d` directory.
+    pub fn from_dir(apt_dir: &Path) -> io::Result<Preferences> {
+        let mut result = Preferences::default();
+        let main = apt_dir.join("preferences");
+        if main.is_file() {
+            result
+                .stanzas
+                .extend(Preferences::from_file(&main)?.stanzas);
+        }
+        let parts_dir = apt_dir.join("preferences.d");
+        if parts_dir.is_dir() {
+            let mut parts = vec![];
+            for entry in fs::read_dir(&parts_dir)? {
+                let path = entry?.path();
+                let name = match path.file_name().and_then(|n| n.to_str()) {
+                    Some(n) => n,
+                    None => continue,
+                };
+                let valid = name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "_-.".contains(c));
+                let ext = path.extension().and_then(|e| e.to_str());
+                if path.is_file() && valid && matches!(ext, None | Some("pref"))
+                {
+                    parts.push(path);
+                }
+            }
+            parts.sort();
+            for path in parts {
+                result
+                    .stanzas
+                    .extend(Preferences::from_file(&path)?.stanzas);
+            }
+        }
+        Ok(result)
+    }
+
+    fn from_control(cf: &ControlFile) -> io::Result<Preferences> {
+        let mut stanzas = vec![];
+        for para in cf.get_paragraphs() {
+            let stanza = PinStanza::from_paragraph(para)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            stanzas.push(stanza);
+        }
+        Ok(Preferences { stanzas })
+    }
+
+    /// Append a stanza, with lowest precedence.
+    pub fn add_stanza(&mut self, stanza: PinStanza) {
+        self.stanzas.push(stanza);
+    }
+
+    /// All stanzas in order of precedence.
+    pub fn get_stanzas(&self) -> &Vec<PinStanza> {
+        &self.stanzas
+    }
+
+    /// Find the stanza determining the priority of a version, if any.
+    ///
+    /// Package-specific stanzas take precedence over general ones;
+    /// within each kind, the first matching stanza wins.
+    pub fn matching_stanza(
+        &self,
+        package: &str,
+        source: Option<&str>,
+        version: &Version,
+        release: &ReleaseInfo,
+    ) -> Option<&PinStanza> {
+        let matches = |s: &&PinStanza| {
+            s.applies_to(package, source) && s.pin.matches(version, release)
+        };
+        self.stanzas
+            .iter()
+            .filter(|s| !s.is_general())
+            .find(matches)
+            .or_else(|| {
+                self.stanzas.iter().filter(|s| s.is_general()).find(matches)
+            })
+    }
+
+    /// Compute the effective pin priority of a version of a package.
+    pub fn pin_priority(
+        &self,
+        package: &str,
+        version: &Version,
+        release: &ReleaseInfo,
+    ) -> i32 {
+        self.pin_priority_with_source(package, None, version, release)
+    }
+
+    /// Compute the effective pin priority of a version of a package,
+    /// also considering stanzas referring to its source package.
+    pub fn pin_priority_with_source(
+        &self,
+        package: &str,
+        source: Option<&str>,
+        version: &Version,
+        release: &ReleaseInfo,
+    ) -> i32 {
+        match self.matching_stanza(package, source, version, release) {
+            Some(stanza) => stanza.priority,
+            None => default_priority(release),
+        }
+    }
+}
+
+impl FromStr for Preferences {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Preferences::from_reader(s.as_bytes())
+    }
+}
+
+impl fmt::Display for Preferences {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let stanzas = self
+            .stanzas
+            .iter()
+            .map(|s| s.serialize())
+            .collect::<Vec<String>>()
+            .join("\n");
+        write!(f, "{stanzas}")
+    }
+}
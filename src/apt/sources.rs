@@ -0,0 +1,417 @@
+//! Parser and writer for apt sources
+//!
+//! Apt reads its list of repositories from one-line style
+//! `sources.list` files as well as from deb822 style `.sources` files.
+//! Both are represented by the same `SourceEntry` type, allowing
+//! conversion between the two formats.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::apt::sources::SourcesList;
+//!
+//! let list = SourcesList::parse_list(
+//!     "deb [arch=amd64] http://deb.debian.org/debian bookworm main\n\
+//!      deb-src http://deb.debian.org/debian bookworm main\n",
+//! )
+//! .unwrap();
+//! assert_eq!(list.entries().len(), 2);
+//! assert!(list.to_deb822().contains("Architectures: amd64"));
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::package::{ControlFile, ControlParagraph};
+
+/// The type of a repository, binary or source.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SourceType {
+    /// `deb`, binary packages
+    Deb,
+    /// `deb-src`, source packages
+    DebSrc,
+}
+
+/// A single repository entry, possibly covering multiple types, URIs
+/// and suites.
+///
+/// One-line style entries always have exactly one type, URI and suite,
+/// while deb822 style entries may list multiple of them.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SourceEntry {
+    pub types: Vec<SourceType>,
+    pub uris: Vec<String>,
+    pub suites: Vec<String>,
+    /// components, empty for flat repositories
+    pub components: Vec<String>,
+    /// further options, using the deb822 field names, like
+    /// `Architectures` or `Signed-By`
+    pub options: Vec<(String, String)>,
+    /// whether the entry is enabled
+    pub enabled: bool,
+}
+
+/// A list of repository entries, as read from one or more files.
+#[derive(Debug, Default, Clone)]
+pub struct SourcesList {
+    entries: Vec<SourceEntry>,
+}
+
+/// Mapping between one-line option names and deb822 field names.
+const OPTION_NAMES: &[(&str, &str)] = &[
+    ("arch", "Architectures"),
+    ("lang", "Languages"),
+    ("target", "Targets"),
+    ("pdiffs", "PDiffs"),
+    ("by-hash", "By-Hash"),
+    ("allow-insecure", "Allow-Insecure"),
+    ("allow-weak", "Allow-Weak"),
+    ("allow-downgrade-to-insecure", "Allow-Downgrade-To-Insecure"),
+    ("trusted", "Trusted"),
+    ("signed-by", "Signed-By"),
+    ("check-valid-until", "Check-Valid-Until"),
+    ("valid-until-min", "Valid-Until-Min"),
+    ("valid-until-max", "Valid-Until-Max"),
+    ("check-date", "Check-Date"),
+    ("date-max-future", "Date-Max-Future"),
+    ("inrelease-path", "InRelease-Path"),
+    ("snapshot", "Snapshot"),
+];
+
+/// Options whose values are lists, comma separated in one-line style
+/// and whitespace separated in deb822 style.
+const LIST_OPTIONS: &[&str] = &["Architectures", "Languages", "Targets"];
+
+fn option_to_deb822(name: &str) -> String {
+    for (short, long) in OPTION_NAMES {
+        if *short == name {
+            return long.to_string();
+        }
+    }
+    name.to_string()
+}
+
+fn option_to_one_line(name: &str) -> String {
+    for (short, long) in OPTION_NAMES {
+        if long.eq_ignore_ascii_case(name) {
+            return short.to_string();
+        }
+    }
+    name.to_lowercase()
+}
+
+impl FromStr for SourceType {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "deb" => Ok(SourceType::Deb),
+            "deb-src" => Ok(SourceType::DebSrc),
+            _ => Err("unknown source type"),
+        }
+    }
+}
+
+impl fmt::Display for SourceType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SourceType::Deb => write!(f, "deb"),
+            SourceType::DebSrc => write!(f, "deb-src"),
+        }
+    }
+}
+
+impl SourceEntry {
+    /// Parse a single line of a one-line style `sources.list` file.
+    pub fn parse_line(line: &str) -> Result<SourceEntry, &'static str> {
+        let line = line.trim();
+        let (stype, rest) = match line.split_once(char::is_whitespace) {
+            Some((t, r)) => (t.parse::<SourceType>()?, r.trim_start()),
+            None => return Err("incomplete source entry"),
+        };
+
+        let mut options = vec![];
+        let rest = match rest.strip_prefix('[') {
+            Some(r) => {
+                let (opts, r) = match r.split_once(']') {
+                    Some(x) => x,
+                    None => return Err("unterminated options"),
+                };
+                for opt in opts.split_whitespace() {
+                    let (k, v) = match opt.split_once('=') {
+                        Some(x) => x,
+                        None => return Err("option without value"),
+                    };
+                    let name = option_to_deb822(k);
+                    let value = match LIST_OPTIONS.contains(&name.as_str()) {
+                        true => v.replace(',', " "),
+                        false => v.to_string(),
+                    };
+                    options.push((name, value));
+                }
+                r
+            }
+            None => rest,
+        };
+
+        let mut words = rest.split_whitespace();
+        let (uri, suite) = match (words.next(), words.next()) {
+            (Some(u), Some(s)) => (u.to_string(), s.to_string()),
+            _ => return Err("missing URI or suite"),
+        };
+        let components = words.map(|x| x.to_string()).collect::<Vec<_>>();
+        if components.is_empty() && !suite.ends_with('/') {
+            return Err("missing components");
+        }
+        Ok(SourceEntry {
+            types: vec![stype],
+            uris: vec![uri],
+            suites: vec![suite],
+            components,
+            options,
+            enabled: true,
+        })
+    }
+
+    /// Creates a `SourceEntry` from a paragraph of a deb822 style
+    /// `.sources` file.
+    pub fn from_paragraph(
+        para: &ControlParagraph,
+    ) -> Result<SourceEntry, &'static str> {
+        let mut entry = SourceEntry {
+            types: vec![],
+            uris: vec![],
+            suites: vec![],
+            components: vec![],
+            options: vec![],
+            enabled: true,
+        };
+        let split = |v: &str| {
+            v.split_whitespace()
+                .map(|x| x.to_string())
+                .collect::<Vec<String>>()
+        };
+        for e in para.entries() {
+            let value = e.value().as_str();
+            match e.key() {
+                "Types" => {
+                    for t in value.split_whitespace() {
+                        entry.types.push(t.parse()?);
+                    }
+                }
+                "URIs" => entry.uris = split(value),
+                "Suites" => entry.suites = split(value),
+                "Components" => entry.components = split(value),
+                "Enabled" => entry.enabled = value != "no",
+                key => entry.options.push((key.to_string(), value.to_string())),
+            }
+        }
+        if entry.types.is_empty() {
+            return Err("missing Types field");
+        } else if entry.uris.is_empty() {
+            return Err("missing URIs field");
+        } else if entry.suites.is_empty() {
+            return Err("missing Suites field");
+        }
+        let flat = entry.suites.iter().all(|s| s.ends_with('/'));
+        if entry.components.is_empty() && !flat {
+            return Err("missing Components field");
+        }
+        Ok(entry)
+    }
+
+    /// Get the value of an option, given its deb822 name.
+    pub fn get_option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Converts this entry to a paragraph of a deb822 style `.sources`
+    /// file.
+    pub fn to_paragraph(&self) -> ControlParagraph {
+        let join = |v: &Vec<String>| v.join(" ");
+        let mut para = ControlParagraph::default();
+        let types = self
+            .types
+            .iter()
+            .map(|t| t.to_string())
+            .collect::<Vec<String>>();
+        para.add_entry("Types", join(&types));
+        para.add_entry("URIs", join(&self.uris));
+        para.add_entry("Suites", join(&self.suites));
+        if !self.components.is_empty() {
+            para.add_entry("Components", join(&self.components));
+        }
+        if !self.enabled {
+            para.add_entry("Enabled", "no".to_string());
+        }
+        for (k, v) in &self.options {
+            para.add_entry(k, v.clone());
+        }
+        para
+    }
+
+    /// Converts this entry to one or more lines in one-line style.
+    ///
+    /// Fails if an option cannot be expressed in one-line style, for
+    /// example an embedded `Signed-By` key.
+    pub fn to_lines(&self) -> Result<Vec<String>, &'static str> {
+        let mut opts = vec![];
+        for (k, v) in &self.options {
+            if v.contains(char::is_whitespace)
+                && !LIST_OPTIONS.contains(&k.as_str())
+            {
+                return Err("option not representable in one-line style");
+            }
+            let v = v.split_whitespace().collect::<Vec<&str>>().join(",");
+            opts.push(format!("{}={}", option_to_one_line(k), v));
+        }
+        let opts = match opts.is_empty() {
+            true => "".to_string(),
+            false => format!("[{}] ", opts.join(" ")),
+        };
+        let comment = if self.enabled { "" } else { "# " };
+
+        let mut lines = vec![];
+        for t in &self.types {
+            for uri in &self.uris {
+                for suite in &self.suites {
+                    let mut line = format!("{comment}{t} {opts}{uri} {suite}");
+                    for c in &self.components {
+                        line += " ";
+                        line += c;
+                    }
+                    lines.push(line);
+                }
+            }
+        }
+        Ok(lines)
+    }
+}
+
+impl FromStr for SourceEntry {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SourceEntry::parse_line(s)
+    }
+}
+
+impl SourcesList {
+    /// Parses the contents of a one-line style `sources.list` file.
+    ///
+    /// Comments and empty lines are skipped.
+    pub fn parse_list(s: &str) -> Result<SourcesList, &'static str> {
+        let mut entries = vec![];
+        for line in s.lines() {
+            let line = match line.find('#') {
+                Some(i) => &line[..i],
+                None => line,
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            entries.push(SourceEntry::parse_line(line)?);
+        }
+        Ok(SourcesList { entries })
+    }
+
+    /// Parses the contents of a deb822 style `.sources` file.
+    pub fn parse_deb822(s: &str) -> io::Result<SourcesList> {
+        let cf = ControlFile::from_reader(s.as_bytes())?;
+        let mut entries = vec![];
+        for para in cf.get_paragraphs() {
+            entries
+                .push(SourceEntry::from_paragraph(para).map_err(invalid_data)?);
+        }
+        Ok(SourcesList { entries })
+    }
+
+    /// Reads a sources file from disk, choosing the format by the
+    /// extension of the file: deb822 style for `.sources`, one-line
+    /// style otherwise.
+    pub fn from_file(in_file: &Path) -> io::Result<SourcesList> {
+        let content = fs::read_to_string(in_file)?;
+        match in_file.extension().and_then(|e| e.to_str()) {
+            Some("sources") => SourcesList::parse_deb822(&content),
+            _ => SourcesList::parse_list(&content).map_err(invalid_data),
+        }
+    }
+
+    /// Reads all sources of an apt configuration directory like
+    /// `/etc/apt`, i.e. `sources.list` followed by the `.list` and
+    /// `.sources` files in `sources.list.d` in alphabetical order.
+    pub fn from_dir(apt_dir: &Path) -> io::Result<SourcesList> {
+        let mut result = SourcesList::default();
+        let main = apt_dir.join("sources.list");
+        if main.is_file() {
+            result
+                .entries
+                .extend(SourcesList::from_file(&main)?.entries);
+        }
+        let parts_dir = apt_dir.join("sources.list.d");
+        if parts_dir.is_dir() {
+            let mut parts = vec![];
+            for entry in fs::read_dir(&parts_dir)? {
+                let path = entry?.path();
+                let ext = path.extension().and_then(|e| e.to_str());
+                if path.is_file() && matches!(ext, Some("list" | "sources")) {
+                    parts.push(path);
+                }
+            }
+            parts.sort();
+            for path in parts {
+                result
+                    .entries
+                    .extend(SourcesList::from_file(&path)?.entries);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Append an entry.
+    pub fn add_entry(&mut self, entry: SourceEntry) {
+        self.entries.push(entry);
+    }
+
+    /// All entries, in order.
+    pub fn entries(&self) -> &Vec<SourceEntry> {
+        &self.entries
+    }
+
+    /// Mutable access to the entries, for rewriting sources.
+    pub fn entries_mut(&mut self) -> &mut Vec<SourceEntry> {
+        &mut self.entries
+    }
+
+    /// Emits all entries in one-line style.
+    pub fn to_list(&self) -> Result<String, &'static str> {
+        let mut result = String::new();
+        for entry in &self.entries {
+            for line in entry.to_lines()? {
+                result += &line;
+                result += "\n";
+            }
+        }
+        Ok(result)
+    }
+
+    /// Emits all entries in deb822 style.
+    pub fn to_deb822(&self) -> String {
+        let mut cf = ControlFile::default();
+        for entry in &self.entries {
+            cf.add_paragraph(entry.to_paragraph());
+        }
+        cf.to_string()
+    }
+}
+
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
@@ -0,0 +1,461 @@
+//! Debian architectures
+//!
+//! dpkg identifies each architecture by a tuple of ABI, libc, operating
+//! system and CPU, like `base-gnu-linux-amd64` for `amd64` or
+//! `eabihf-musl-linux-arm` for `musl-linux-armhf`. The tuples allow
+//! matching architectures against wildcards, like `linux-any` or
+//! `any-arm`, and converting them to GNU triplets as used by compilers,
+//! like `x86_64-linux-gnu`.
+//!
+//! The tables used follow those of dpkg, covering all architectures
+//! known to it, whether official or not.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::arch::DebianArchitecture;
+//!
+//! let arch: DebianArchitecture = "musl-linux-armhf".parse().unwrap();
+//! assert_eq!(arch.tuple(), ("eabihf", "musl", "linux", "arm"));
+//! assert_eq!(arch.gnu_triplet(), "arm-linux-musleabihf");
+//! assert!(arch.matches("linux-any"));
+//! assert!(arch.matches("any-arm"));
+//! assert!(!arch.matches("armhf"));
+//!
+//! let arch = DebianArchitecture::from_gnu_triplet("i686-gnu").unwrap();
+//! assert_eq!(arch.to_string(), "hurd-i386");
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use self::Endianness::{Big, Little};
+
+/// Byte order of a CPU.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+/// An architecture, as identified by its dpkg tuple.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub struct DebianArchitecture {
+    abi: &'static str,
+    libc: &'static str,
+    os: &'static str,
+    cpu: &'static str,
+}
+
+/// A CPU: the Debian name, the GNU name, patterns matching GNU names,
+/// in which `*` matches anything, bits and endianness.
+type Cpu = (
+    &'static str,
+    &'static str,
+    &'static [&'static str],
+    u8,
+    Endianness,
+);
+
+/// Known CPUs.
+const CPUS: &[Cpu] = &[
+    (
+        "i386",
+        "i686",
+        &["i386", "i486", "i586", "i686", "i786", "pentium"],
+        32,
+        Little,
+    ),
+    ("ia64", "ia64", &["ia64"], 64, Little),
+    ("alpha", "alpha", &["alpha*"], 64, Little),
+    ("amd64", "x86_64", &["amd64", "x86_64"], 64, Little),
+    ("armeb", "armeb", &["arm*b"], 32, Big),
+    ("arm", "arm", &["arm*"], 32, Little),
+    ("arm64", "aarch64", &["aarch64"], 64, Little),
+    ("avr32", "avr32", &["avr32"], 32, Big),
+    ("hppa", "hppa", &["hppa*"], 32, Big),
+    ("loong64", "loongarch64", &["loongarch64"], 64, Little),
+    ("m32r", "m32r", &["m32r"], 32, Big),
+    ("m68k", "m68k", &["m68k"], 32, Big),
+    ("mips", "mips", &["mips", "mipseb"], 32, Big),
+    ("mipsel", "mipsel", &["mipsel"], 32, Little),
+    ("mipsr6", "mipsisa32r6", &["mipsisa32r6"], 32, Big),
+    ("mipsr6el", "mipsisa32r6el", &["mipsisa32r6el"], 32, Little),
+    ("mips64", "mips64", &["mips64"], 64, Big),
+    ("mips64el", "mips64el", &["mips64el"], 64, Little),
+    ("mips64r6", "mipsisa64r6", &["mipsisa64r6"], 64, Big),
+    (
+        "mips64r6el",
+        "mipsisa64r6el",
+        &["mipsisa64r6el"],
+        64,
+        Little,
+    ),
+    ("nios2", "nios2", &["nios2"], 32, Little),
+    ("or1k", "or1k", &["or1k"], 32, Big),
+    ("powerpc", "powerpc", &["powerpc", "ppc"], 32, Big),
+    ("powerpcel", "powerpcle", &["powerpcle"], 32, Little),
+    ("ppc64", "powerpc64", &["powerpc64", "ppc64"], 64, Big),
+    ("ppc64el", "powerpc64le", &["powerpc64le"], 64, Little),
+    ("riscv64", "riscv64", &["riscv64"], 64, Little),
+    ("s390", "s390", &["s390"], 32, Big),
+    ("s390x", "s390x", &["s390x"], 64, Big),
+    ("sh3", "sh3", &["sh3"], 32, Little),
+    ("sh3eb", "sh3eb", &["sh3eb"], 32, Big),
+    ("sh4", "sh4", &["sh4"], 32, Little),
+    ("sh4eb", "sh4eb", &["sh4eb"], 32, Big),
+    ("sparc", "sparc", &["sparc"], 32, Big),
+    ("sparc64", "sparc64", &["sparc64"], 64, Big),
+];
+
+/// Known operating systems, as ABI-libc-OS triples, with the system
+/// part of their GNU triplets.
+const SYSTEMS: &[(&str, &str)] = &[
+    ("eabi-uclibc-linux", "linux-uclibceabi"),
+    ("base-uclibc-linux", "linux-uclibc"),
+    ("eabihf-musl-linux", "linux-musleabihf"),
+    ("base-musl-linux", "linux-musl"),
+    ("eabihf-gnu-linux", "linux-gnueabihf"),
+    ("eabi-gnu-linux", "linux-gnueabi"),
+    ("abin32-gnu-linux", "linux-gnuabin32"),
+    ("abi64-gnu-linux", "linux-gnuabi64"),
+    ("spe-gnu-linux", "linux-gnuspe"),
+    ("x32-gnu-linux", "linux-gnux32"),
+    ("ilp32-gnu-linux", "linux-gnu_ilp32"),
+    ("base-gnu-linux", "linux-gnu"),
+    ("eabihf-gnu-kfreebsd", "kfreebsd-gnueabihf"),
+    ("base-gnu-kfreebsd", "kfreebsd-gnu"),
+    ("base-gnu-knetbsd", "knetbsd-gnu"),
+    ("base-gnu-kopensolaris", "kopensolaris-gnu"),
+    ("base-gnu-hurd", "gnu"),
+    ("base-bsd-dragonflybsd", "dragonflybsd"),
+    ("base-bsd-freebsd", "freebsd"),
+    ("base-bsd-openbsd", "openbsd"),
+    ("base-bsd-netbsd", "netbsd"),
+    ("base-bsd-darwin", "darwin"),
+    ("base-sysv-aix", "aix"),
+    ("base-sysv-solaris", "solaris"),
+];
+
+/// The tuple table, mapping tuples to architecture names, where `<cpu>`
+/// stands for any CPU. The first entry matching wins.
+const TUPLES: &[(&str, &str)] = &[
+    ("eabihf-musl-linux-arm", "musl-linux-armhf"),
+    ("base-musl-linux-<cpu>", "musl-linux-<cpu>"),
+    ("ilp32-gnu-linux-arm64", "arm64ilp32"),
+    ("eabihf-gnu-linux-arm", "armhf"),
+    ("eabi-gnu-linux-arm", "armel"),
+    ("abin32-gnu-linux-mips64r6el", "mipsn32r6el"),
+    ("abin32-gnu-linux-mips64r6", "mipsn32r6"),
+    ("abin32-gnu-linux-mips64el", "mipsn32el"),
+    ("abin32-gnu-linux-mips64", "mipsn32"),
+    ("abi64-gnu-linux-mips64r6el", "mips64r6el"),
+    ("abi64-gnu-linux-mips64r6", "mips64r6"),
+    ("abi64-gnu-linux-mips64el", "mips64el"),
+    ("abi64-gnu-linux-mips64", "mips64"),
+    ("spe-gnu-linux-powerpc", "powerpcspe"),
+    ("x32-gnu-linux-amd64", "x32"),
+    ("base-gnu-linux-<cpu>", "<cpu>"),
+    ("eabihf-gnu-kfreebsd-arm", "kfreebsd-armhf"),
+    ("base-gnu-kfreebsd-<cpu>", "kfreebsd-<cpu>"),
+    ("base-gnu-knetbsd-<cpu>", "knetbsd-<cpu>"),
+    ("base-gnu-kopensolaris-<cpu>", "kopensolaris-<cpu>"),
+    ("base-gnu-hurd-<cpu>", "hurd-<cpu>"),
+    ("base-bsd-dragonflybsd-<cpu>", "dragonflybsd-<cpu>"),
+    ("base-bsd-freebsd-<cpu>", "freebsd-<cpu>"),
+    ("base-bsd-openbsd-<cpu>", "openbsd-<cpu>"),
+    ("base-bsd-netbsd-<cpu>", "netbsd-<cpu>"),
+    ("base-bsd-darwin-<cpu>", "darwin-<cpu>"),
+    ("base-sysv-aix-<cpu>", "aix-<cpu>"),
+    ("base-sysv-solaris-<cpu>", "solaris-<cpu>"),
+    ("eabi-uclibc-linux-arm", "uclibc-linux-armel"),
+    ("base-uclibc-linux-<cpu>", "uclibc-linux-<cpu>"),
+];
+
+fn cpu_entry(cpu: &str) -> Option<&'static Cpu> {
+    CPUS.iter().find(|c| c.0 == cpu)
+}
+
+/// Whether a GNU CPU name matches a pattern of the CPU table.
+fn gnu_cpu_matches(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        Some((prefix, suffix)) => {
+            name.len() >= prefix.len() + suffix.len()
+                && name.starts_with(prefix)
+                && name.ends_with(suffix)
+        }
+        None => pattern == name,
+    }
+}
+
+/// Splits a tuple from the tables into its parts, using the given CPU
+/// where the tuple has none or `<cpu>`.
+fn split_tuple(tuple: &'static str, cpu: &'static str) -> DebianArchitecture {
+    let mut parts = tuple.splitn(4, '-');
+    let mut next = || parts.next().unwrap_or("");
+    let (abi, libc, os) = (next(), next(), next());
+    let cpu = match next() {
+        "" | "<cpu>" => cpu,
+        other => other,
+    };
+    DebianArchitecture { abi, libc, os, cpu }
+}
+
+/// Whether the parts of a wildcard tuple match those of a tuple.
+fn tuple_matches(wildcard: &[&str; 4], tuple: &[&str; 4]) -> bool {
+    wildcard
+        .iter()
+        .zip(tuple.iter())
+        .all(|(w, t)| *w == "any" || w == t)
+}
+
+impl DebianArchitecture {
+    /// Looks up an architecture by its Debian name, like `amd64` or
+    /// `hurd-i386`.
+    pub fn parse(name: &str) -> Result<DebianArchitecture, &'static str> {
+        for &(tuple, arch) in TUPLES {
+            match arch.split_once("<cpu>") {
+                None if arch == name => return Ok(split_tuple(tuple, "")),
+                None => (),
+                Some((prefix, suffix)) => {
+                    let cpu = name
+                        .strip_prefix(prefix)
+                        .and_then(|n| n.strip_suffix(suffix))
+                        .and_then(cpu_entry);
+                    if let Some(cpu) = cpu {
+                        return Ok(split_tuple(tuple, cpu.0));
+                    }
+                }
+            }
+        }
+        Err("unknown architecture")
+    }
+
+    /// Looks up an architecture by its dpkg tuple, given as
+    /// `abi-libc-os-cpu`.
+    pub fn from_tuple(tuple: &str) -> Result<DebianArchitecture, &'static str> {
+        let (system, cpu) = tuple.rsplit_once('-').ok_or("invalid tuple")?;
+        let cpu = cpu_entry(cpu).ok_or("unknown CPU")?.0;
+        let arch = SYSTEMS
+            .iter()
+            .find(|s| s.0 == system)
+            .map(|s| split_tuple(s.0, cpu))
+            .ok_or("unknown system")?;
+        match arch.lookup_name() {
+            Some(_) => Ok(arch),
+            None => Err("unknown architecture"),
+        }
+    }
+
+    /// Looks up an architecture by its GNU triplet, like
+    /// `x86_64-linux-gnu` or `arm-linux-gnueabihf`. A vendor part, like
+    /// in `x86_64-pc-linux-gnu`, is ignored.
+    pub fn from_gnu_triplet(
+        triplet: &str,
+    ) -> Result<DebianArchitecture, &'static str> {
+        let (gnu_cpu, system) =
+            triplet.split_once('-').ok_or("invalid GNU triplet")?;
+        let cpu = CPUS
+            .iter()
+            .find(|c| c.2.iter().any(|p| gnu_cpu_matches(p, gnu_cpu)))
+            .ok_or("unknown CPU")?;
+        let without_vendor = system.split_once('-').map(|(_, s)| s);
+        let system = SYSTEMS
+            .iter()
+            .find(|s| s.1 == system)
+            .or_else(|| SYSTEMS.iter().find(|s| Some(s.1) == without_vendor))
+            .ok_or("unknown system")?;
+        let arch = split_tuple(system.0, cpu.0);
+        // the GNU names of 64 bit MIPS do not tell the ABI
+        let arch = match (arch.cpu, arch.abi) {
+            (c, "base") if c.starts_with("mips64") => DebianArchitecture {
+                abi: "abi64",
+                ..arch
+            },
+            _ => arch,
+        };
+        match arch.lookup_name() {
+            Some(_) => Ok(arch),
+            None => Err("unknown architecture"),
+        }
+    }
+
+    /// The name of the architecture in the tuple table, if any.
+    fn lookup_name(&self) -> Option<String> {
+        let system = [self.abi, self.libc, self.os].join("-");
+        let tuple = format!("{system}-{}", self.cpu);
+        let generic = format!("{system}-<cpu>");
+        TUPLES.iter().find_map(|&(t, arch)| {
+            if t == tuple {
+                Some(arch.to_string())
+            } else if t == generic {
+                Some(arch.replace("<cpu>", self.cpu))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// The Debian name of the architecture, like `armhf`.
+    pub fn name(&self) -> String {
+        self.lookup_name().unwrap_or_default()
+    }
+
+    /// The dpkg tuple, as ABI, libc, operating system and CPU.
+    pub fn tuple(
+        &self,
+    ) -> (&'static str, &'static str, &'static str, &'static str) {
+        (self.abi, self.libc, self.os, self.cpu)
+    }
+
+    pub fn abi(&self) -> &'static str {
+        self.abi
+    }
+
+    pub fn libc(&self) -> &'static str {
+        self.libc
+    }
+
+    /// The operating system, like `linux` or `hurd`.
+    pub fn os(&self) -> &'static str {
+        self.os
+    }
+
+    /// The Debian name of the CPU, like `amd64` or `arm`.
+    pub fn cpu(&self) -> &'static str {
+        self.cpu
+    }
+
+    /// The number of bits of the CPU.
+    pub fn bits(&self) -> u8 {
+        match (self.abi, self.cpu) {
+            ("x32" | "abin32" | "ilp32", _) => 32,
+            _ => cpu_entry(self.cpu).map_or(0, |c| c.3),
+        }
+    }
+
+    pub fn endianness(&self) -> Endianness {
+        cpu_entry(self.cpu).map_or(Little, |c| c.4)
+    }
+
+    /// The GNU name of the CPU, like `x86_64`.
+    pub fn gnu_cpu(&self) -> &'static str {
+        cpu_entry(self.cpu).map_or("", |c| c.1)
+    }
+
+    /// The GNU system type, like `linux-gnueabihf`.
+    pub fn gnu_system(&self) -> &'static str {
+        let system = [self.abi, self.libc, self.os].join("-");
+        SYSTEMS.iter().find(|s| s.0 == system).map_or("", |s| s.1)
+    }
+
+    /// The GNU triplet, like `aarch64-linux-gnu`, as used for the names
+    /// of multiarch directories and cross compilers.
+    pub fn gnu_triplet(&self) -> String {
+        format!("{}-{}", self.gnu_cpu(), self.gnu_system())
+    }
+
+    /// The multiarch tuple, naming library directories like
+    /// `/usr/lib/i386-linux-gnu`. It only differs from the GNU triplet
+    /// for i386, which keeps the historic `i386` CPU name.
+    pub fn multiarch(&self) -> String {
+        match self.cpu {
+            "i386" => format!("i386-{}", self.gnu_system()),
+            _ => self.gnu_triplet(),
+        }
+    }
+
+    /// Whether the architecture matches an architecture name or a
+    /// wildcard as used in architecture restrictions and the
+    /// `Architecture` field of source packages, like `any`, `linux-any`,
+    /// `any-amd64` or `musl-any-any`. Missing parts of a wildcard are
+    /// taken as `any` from the left.
+    pub fn matches(&self, wildcard: &str) -> bool {
+        if wildcard == "any" {
+            return true;
+        }
+        let parts: Vec<&str> = wildcard.split('-').collect();
+        if !parts.contains(&"any") {
+            return DebianArchitecture::parse(wildcard).as_ref() == Ok(self);
+        }
+        let pattern = match parts[..] {
+            [os, cpu] => ["any", "any", os, cpu],
+            [libc, os, cpu] => ["any", libc, os, cpu],
+            [abi, libc, os, cpu] => [abi, libc, os, cpu],
+            _ => return false,
+        };
+        tuple_matches(&pattern, &[self.abi, self.libc, self.os, self.cpu])
+    }
+
+    /// All architectures known, in the order of the tuple table.
+    pub fn all() -> Vec<DebianArchitecture> {
+        let mut result = vec![];
+        for &(tuple, _) in TUPLES {
+            if tuple.ends_with("<cpu>") {
+                for cpu in CPUS {
+                    let arch = split_tuple(tuple, cpu.0);
+                    if !result.contains(&arch)
+                        && arch.lookup_name().map_or(false, |n| {
+                            DebianArchitecture::parse(&n) == Ok(arch)
+                        })
+                    {
+                        result.push(arch);
+                    }
+                }
+            } else {
+                let arch = split_tuple(tuple, "");
+                if !result.contains(&arch) {
+                    result.push(arch);
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Whether an architecture name matches a name or wildcard. Unknown
+/// architectures only match themselves and `any`.
+pub fn arch_matches(arch: &str, wildcard: &str) -> bool {
+    if arch == wildcard || wildcard == "any" {
+        return true;
+    }
+    match DebianArchitecture::parse(arch) {
+        Ok(arch) => arch.matches(wildcard),
+        Err(_) => false,
+    }
+}
+
+impl fmt::Display for DebianArchitecture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for DebianArchitecture {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DebianArchitecture::parse(s)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for DebianArchitecture {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.name())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for DebianArchitecture {
+    fn deserialize<D>(deserializer: D) -> Result<DebianArchitecture, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        DebianArchitecture::parse(&String::deserialize(deserializer)?)
+            .map_err(serde::de::Error::custom)
+    }
+}
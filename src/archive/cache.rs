@@ -0,0 +1,209 @@
+//! Persistent cache of parsed index files
+//!
+//! Parsing large Packages or Sources indices takes a noticeable amount
+//! of time, which tools running repeatedly over the same archive state
+//! should not need to spend every time. An `IndexCache` keeps parsed
+//! indices in a directory, in a compact binary form, keyed by the
+//! SHA256 digest of the index file they were parsed from.
+//!
+//! As Release files list the digest of every index, a cached index is
+//! used exactly as long as the Release file still refers to the same
+//! contents, and entries no longer listed by any Release file can be
+//! pruned. Unreadable or corrupt cache entries are ignored and
+//! replaced.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::archive::cache::IndexCache;
+//!
+//! let dir = tempfile::tempdir().unwrap();
+//! let cache = IndexCache::new(dir.path());
+//! let data = b"Package: foo\nVersion: 1.0\n";
+//! let index = cache.load(data).unwrap();
+//! assert_eq!(index.get_paragraphs()[0].get_entry("Package"), Some("foo"));
+//! // the second time, the index is read from the cache
+//! assert_eq!(cache.load(data).unwrap().get_paragraphs().len(), 1);
+//! ```
+
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use super::release::Release;
+use crate::package::{ControlFile, ControlParagraph};
+
+/// Identifies cache entries and the version of their format.
+const MAGIC: &[u8] = b"debian-index-cache 1\n";
+
+/// A directory of parsed index files.
+#[derive(Debug, Clone)]
+pub struct IndexCache {
+    dir: PathBuf,
+}
+
+/// Whether a string is a hex encoded SHA256 digest, and thus safe to
+/// use as a file name.
+fn is_sha256(digest: &str) -> bool {
+    digest.len() == 64
+        && digest
+            .bytes()
+            .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(&(s.len() as u32).to_le_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn encode(index: &ControlFile) -> Vec<u8> {
+    let mut out = MAGIC.to_vec();
+    let paragraphs = index.get_paragraphs();
+    out.extend_from_slice(&(paragraphs.len() as u32).to_le_bytes());
+    for para in paragraphs {
+        out.extend_from_slice(&(para.entries().count() as u32).to_le_bytes());
+        for entry in para.entries() {
+            write_str(&mut out, entry.key());
+            write_str(&mut out, entry.value().as_str());
+        }
+    }
+    out
+}
+
+fn read_u32(data: &mut &[u8]) -> Option<u32> {
+    let (head, rest) = (data.get(..4)?, &data[4..]);
+    *data = rest;
+    Some(u32::from_le_bytes(head.try_into().ok()?))
+}
+
+fn read_str(data: &mut &[u8]) -> Option<String> {
+    let len = read_u32(data)? as usize;
+    let (head, rest) = (data.get(..len)?, &data[len..]);
+    *data = rest;
+    String::from_utf8(head.to_vec()).ok()
+}
+
+/// Decodes a cache entry, returning `None` if it is corrupt.
+fn decode(data: &[u8]) -> Option<ControlFile> {
+    let mut data = data.strip_prefix(MAGIC)?;
+    let mut index = ControlFile::default();
+    for _ in 0..read_u32(&mut data)? {
+        let mut para = ControlParagraph::default();
+        for _ in 0..read_u32(&mut data)? {
+            let key = read_str(&mut data)?;
+            para.add_entry(&key, read_str(&mut data)?);
+        }
+        index.add_paragraph(para);
+    }
+    match data.is_empty() {
+        true => Some(index),
+        false => None,
+    }
+}
+
+impl IndexCache {
+    /// Creates a cache keeping its entries in the given directory,
+    /// which is created if needed.
+    pub fn new(dir: &Path) -> IndexCache {
+        IndexCache {
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    /// The directory of the cache.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn entry_path(&self, sha256: &str) -> io::Result<PathBuf> {
+        if !is_sha256(sha256) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid SHA256 digest {sha256}"),
+            ));
+        }
+        Ok(self.dir.join(sha256))
+    }
+
+    /// Looks up the parsed index of the file with the given SHA256
+    /// digest. Corrupt entries are treated as missing.
+    pub fn get(&self, sha256: &str) -> io::Result<Option<ControlFile>> {
+        let path = self.entry_path(sha256)?;
+        match fs::read(path) {
+            Ok(data) => Ok(decode(&data)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Stores the parsed index of the file with the given SHA256
+    /// digest. The entry is replaced atomically, so concurrent readers
+    /// never see a partial entry.
+    pub fn insert(&self, sha256: &str, index: &ControlFile) -> io::Result<()> {
+        let path = self.entry_path(sha256)?;
+        fs::create_dir_all(&self.dir)?;
+        let mut file = tempfile::NamedTempFile::new_in(&self.dir)?;
+        file.write_all(&encode(index))?;
+        file.persist(path).map_err(|e| e.error)?;
+        Ok(())
+    }
+
+    /// Returns the cached index of the file with the given SHA256
+    /// digest, or parses it with the given function and stores the
+    /// result.
+    pub fn get_or_parse<F>(
+        &self,
+        sha256: &str,
+        parse: F,
+    ) -> io::Result<ControlFile>
+    where
+        F: FnOnce() -> io::Result<ControlFile>,
+    {
+        if let Some(index) = self.get(sha256)? {
+            return Ok(index);
+        }
+        let index = parse()?;
+        self.insert(sha256, &index)?;
+        Ok(index)
+    }
+
+    /// Parses the contents of an uncompressed index file, using the
+    /// cache keyed by the digest of the data.
+    pub fn load(&self, data: &[u8]) -> io::Result<ControlFile> {
+        let sha256 = format!("{:x}", Sha256::digest(data));
+        self.get_or_parse(&sha256, || ControlFile::from_reader(data))
+    }
+
+    /// Removes all entries for files not listed in any of the given
+    /// Release files, returning the number of entries removed. As the
+    /// cache may be shared between distributions, the Release files of
+    /// all of them should be passed.
+    pub fn prune(&self, releases: &[&Release]) -> io::Result<usize> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+        let mut removed = 0;
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = match name.to_str() {
+                Some(name) if is_sha256(name) => name,
+                _ => continue,
+            };
+            let listed = releases
+                .iter()
+                .flat_map(|r| r.indices())
+                .any(|index| index.sha256 == name);
+            if !listed {
+                fs::remove_file(entry.path())?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
@@ -0,0 +1,303 @@
+//! Parser and generator for Contents indices
+//!
+//! The `Contents-<arch>` files of an archive map each path installed
+//! by any package, without a leading slash, to a comma separated list
+//! of the packages shipping it, each qualified by its section, like
+//! `usr/bin/foo  utils/foo,contrib/net/bar`. Older files start with a
+//! free form header, ending in a `FILE  LOCATION` line.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::archive::contents::Contents;
+//!
+//! let mut contents = Contents::default();
+//! contents.add_package("utils/foo", ["/usr/bin/foo", "/usr/bin/bar"]);
+//! contents.add_package("admin/bar", ["/usr/bin/bar"]);
+//! assert_eq!(
+//!     contents.packages("/usr/bin/bar"),
+//!     Some(&vec!["admin/bar".to_string(), "utils/foo".to_string()])
+//! );
+//! let parsed: Contents = contents.to_string().parse().unwrap();
+//! assert_eq!(parsed, contents);
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Width of the path column in generated files, as used by dak.
+const PATH_WIDTH: usize = 55;
+
+/// A single line of a Contents index.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ContentsEntry {
+    /// the path, without a leading slash, like `usr/bin/foo`
+    pub path: String,
+    /// the packages shipping the path, qualified by their section, like
+    /// `utils/foo`
+    pub packages: Vec<String>,
+}
+
+/// A streaming parser for Contents indices, yielding one entry per
+/// line.
+///
+/// A header is only recognized if the first line starts with `This
+/// file maps`, as written by older versions of dak and apt-ftparchive.
+#[derive(Debug)]
+pub struct ContentsReader<R> {
+    lines: io::Lines<R>,
+    first: bool,
+}
+
+/// A Contents index, mapping paths to the packages shipping them.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Contents {
+    paths: BTreeMap<String, Vec<String>>,
+}
+
+/// Strips the section from a qualified package name, like `utils/foo`.
+pub fn package_name(qualified: &str) -> &str {
+    qualified.rsplit('/').next().unwrap_or(qualified)
+}
+
+fn relative_path(path: &str) -> &str {
+    path.trim_start_matches("./").trim_start_matches('/')
+}
+
+fn is_header_end(line: &str) -> bool {
+    line.split_whitespace().eq(["FILE", "LOCATION"])
+}
+
+impl ContentsEntry {
+    /// The package names, without sections.
+    pub fn package_names(&self) -> impl Iterator<Item = &str> {
+        self.packages.iter().map(|p| package_name(p))
+    }
+}
+
+impl FromStr for ContentsEntry {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // paths may contain spaces, package lists may not
+        let (path, packages) = s
+            .trim_end()
+            .rsplit_once(char::is_whitespace)
+            .ok_or("missing package list in Contents")?;
+        let path = relative_path(path.trim());
+        if path.is_empty() {
+            return Err("missing path in Contents");
+        }
+        let packages: Vec<String> =
+            packages.split(',').map(str::to_string).collect();
+        if packages.iter().any(|p| p.is_empty()) {
+            return Err("empty package in Contents");
+        }
+        Ok(ContentsEntry {
+            path: path.to_string(),
+            packages,
+        })
+    }
+}
+
+impl fmt::Display for ContentsEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:<width$} {}",
+            self.path,
+            self.packages.join(","),
+            width = PATH_WIDTH
+        )
+    }
+}
+
+impl<R: BufRead> ContentsReader<R> {
+    /// Creates a parser reading from any buffered reader.
+    pub fn new(buf: R) -> ContentsReader<R> {
+        ContentsReader {
+            lines: buf.lines(),
+            first: true,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for ContentsReader<R> {
+    type Item = io::Result<ContentsEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e)),
+            };
+            if std::mem::take(&mut self.first)
+                && line.starts_with("This file maps")
+            {
+                for line in self.lines.by_ref() {
+                    match line {
+                        Ok(l) if is_header_end(&l) => break,
+                        Ok(_) => (),
+                        Err(e) => return Some(Err(e)),
+                    }
+                }
+                continue;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            return Some(
+                line.parse()
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            );
+        }
+    }
+}
+
+impl Contents {
+    /// Reads and parses a Contents index from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<Contents> {
+        Contents::from_reader(BufReader::new(File::open(in_file)?))
+    }
+
+    /// Parses a Contents index from any buffered reader.
+    pub fn from_reader<R: BufRead>(buf: R) -> io::Result<Contents> {
+        let mut result = Contents::default();
+        for entry in ContentsReader::new(buf) {
+            let entry = entry?;
+            for package in &entry.packages {
+                result.add(&entry.path, package);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Records that a package, qualified by its section, ships a path.
+    pub fn add(&mut self, path: &str, package: &str) {
+        let packages = self
+            .paths
+            .entry(relative_path(path).to_string())
+            .or_default();
+        if let Err(pos) = packages.binary_search_by(|p| p.as_str().cmp(package))
+        {
+            packages.insert(pos, package.to_string());
+        }
+    }
+
+    /// Adds all paths shipped by a package, qualified by its section.
+    /// Directories should not be included.
+    pub fn add_package<I, S>(&mut self, package: &str, paths: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for path in paths {
+            self.add(path.as_ref(), package);
+        }
+    }
+
+    /// Adds all non-directory entries of a binary package, qualified by
+    /// the section given in its control file, if any.
+    #[cfg(feature = "deb")]
+    pub fn add_deb(&mut self, deb: &crate::deb::DebFile) -> io::Result<()> {
+        use crate::deb::DataEntryType;
+
+        let control = deb.control()?;
+        let name = control.get_entry("Package").ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "missing Package field")
+        })?;
+        let package = match control.get_entry("Section") {
+            Some(section) => format!("{section}/{name}"),
+            None => name.to_string(),
+        };
+        let entries = deb.data_entries()?;
+        self.add_package(
+            &package,
+            entries
+                .iter()
+                .filter(|e| e.entry_type != DataEntryType::Dir)
+                .map(|e| e.path.as_str()),
+        );
+        Ok(())
+    }
+
+    /// The number of paths listed.
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    /// Whether no path is listed.
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// The packages shipping a path, qualified by their sections.
+    pub fn packages(&self, path: &str) -> Option<&Vec<String>> {
+        self.paths.get(relative_path(path))
+    }
+
+    /// All entries, sorted by path.
+    pub fn entries(&self) -> impl Iterator<Item = ContentsEntry> + '_ {
+        self.paths.iter().map(|(path, packages)| ContentsEntry {
+            path: path.clone(),
+            packages: packages.clone(),
+        })
+    }
+
+    /// Paths shipped by a package, given with or without its section.
+    pub fn paths_of<'a>(
+        &'a self,
+        package: &'a str,
+    ) -> impl Iterator<Item = &'a str> + 'a {
+        self.paths
+            .iter()
+            .filter(move |(_, packages)| {
+                packages
+                    .iter()
+                    .any(|p| p == package || package_name(p) == package)
+            })
+            .map(|(path, _)| path.as_str())
+    }
+
+    /// Entries whose path contains the given string, like `apt-file
+    /// search`.
+    pub fn search<'a>(
+        &'a self,
+        pattern: &'a str,
+    ) -> impl Iterator<Item = ContentsEntry> + 'a {
+        self.entries().filter(move |e| e.path.contains(pattern))
+    }
+}
+
+impl FromStr for Contents {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines();
+        if s.starts_with("This file maps") {
+            lines.find(|l| is_header_end(l));
+        }
+        let mut result = Contents::default();
+        for line in lines.filter(|l| !l.trim().is_empty()) {
+            let entry: ContentsEntry = line.parse()?;
+            for package in &entry.packages {
+                result.add(&entry.path, package);
+            }
+        }
+        Ok(result)
+    }
+}
+
+impl fmt::Display for Contents {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for entry in self.entries() {
+            writeln!(f, "{entry}")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,375 @@
+//! Parser for DEP-11 AppStream metadata
+//!
+//! Next to the Packages indices, archives ship AppStream metadata on
+//! the software components contained in binary packages, like desktop
+//! applications, fonts or codecs, in `dep11/Components-<arch>.yml`
+//! files per component. These are YAML streams: the first document is
+//! a header naming the origin of the data, each following one
+//! describes a component with its ID, the package shipping it,
+//! localized names and descriptions, icons and more.
+//!
+//! Components refer to their package by name, so they can be linked
+//! back to the entries of the Packages index of the same component and
+//! architecture. This module requires the `yaml` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::archive::dep11::Dep11File;
+//!
+//! let file: Dep11File = "\
+//! File: DEP-11
+//! Version: '0.12'
+//! Origin: debian-bookworm-main
+//! ---
+//! Type: desktop-application
+//! ID: org.gnome.gedit.desktop
+//! Package: gedit
+//! Name:
+//!   C: gedit
+//! Summary:
+//!   C: Text editor
+//!   de: Texteditor
+//! "
+//! .parse()
+//! .unwrap();
+//! assert_eq!(file.header.origin, "debian-bookworm-main");
+//! let gedit = &file.components[0];
+//! assert_eq!(gedit.package.as_deref(), Some("gedit"));
+//! assert_eq!(gedit.summary("de_AT"), Some("Texteditor"));
+//! assert_eq!(gedit.summary("fr"), Some("Text editor"));
+//! ```
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde_yaml::{Mapping, Value};
+
+use crate::package::{ControlFile, ControlParagraph};
+use crate::version::Version;
+
+/// The language key of untranslated texts.
+const UNTRANSLATED: &str = "C";
+
+/// The header document of a DEP-11 file.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Dep11Header {
+    /// the version of the format, like `0.12`
+    pub version: String,
+    /// the origin of the data, like `debian-bookworm-main`
+    pub origin: String,
+    /// the URL the paths of icons and screenshots are relative to
+    pub media_base_url: Option<String>,
+    pub priority: Option<i64>,
+    pub time: Option<String>,
+}
+
+/// An icon of a component.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Icon {
+    /// how the icon is provided: `stock`, `cached` or `remote`
+    pub kind: String,
+    /// the icon name, file name or URL, depending on the kind
+    pub name: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+/// A software component described by AppStream metadata.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct AppComponent {
+    /// the type, like `desktop-application` or `font`
+    pub kind: String,
+    /// the AppStream ID, like `org.gnome.gedit.desktop`
+    pub id: String,
+    /// the name of the binary package shipping the component
+    pub package: Option<String>,
+    /// the localized names, by language
+    pub names: Vec<(String, String)>,
+    /// the localized summaries, by language
+    pub summaries: Vec<(String, String)>,
+    /// the localized descriptions, by language, as AppStream markup
+    pub descriptions: Vec<(String, String)>,
+    pub categories: Vec<String>,
+    /// the untranslated keywords
+    pub keywords: Vec<String>,
+    pub icons: Vec<Icon>,
+    /// the SPDX license expression of the project
+    pub project_license: Option<String>,
+    /// URLs by type, like `homepage` or `bugtracker`
+    pub urls: Vec<(String, String)>,
+    /// items provided by type, like `mediatypes` or `binaries`
+    pub provides: Vec<(String, String)>,
+}
+
+/// A parsed DEP-11 file.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Dep11File {
+    pub header: Dep11Header,
+    pub components: Vec<AppComponent>,
+}
+
+fn scalar(value: &Value) -> Option<String> {
+    match *value {
+        Value::String(ref s) => Some(s.clone()),
+        Value::Number(ref n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn strings(value: &Value) -> Vec<String> {
+    match *value {
+        Value::Sequence(ref s) => s.iter().filter_map(scalar).collect(),
+        _ => scalar(value).into_iter().collect(),
+    }
+}
+
+fn pairs(value: &Value) -> Vec<(String, String)> {
+    match value.as_mapping() {
+        Some(map) => map
+            .iter()
+            .filter_map(|(k, v)| Some((scalar(k)?, scalar(v)?)))
+            .collect(),
+        None => vec![],
+    }
+}
+
+/// Flattens a mapping of lists, like `Provides`, into pairs.
+fn flat_pairs(value: &Value) -> Vec<(String, String)> {
+    let mut result = vec![];
+    if let Some(map) = value.as_mapping() {
+        for (k, v) in map {
+            if let Some(key) = scalar(k) {
+                result.extend(strings(v).into_iter().map(|s| (key.clone(), s)));
+            }
+        }
+    }
+    result
+}
+
+fn icons(value: &Value) -> Vec<Icon> {
+    let mut result = vec![];
+    for (kind, v) in value.as_mapping().into_iter().flatten() {
+        let kind = match scalar(kind) {
+            Some(kind) => kind,
+            None => continue,
+        };
+        match *v {
+            Value::Sequence(ref entries) => {
+                for entry in entries {
+                    let get = |key: &str| entry.get(key).and_then(scalar);
+                    let size =
+                        |key: &str| get(key).and_then(|s| s.parse().ok());
+                    if let Some(name) = get("name").or_else(|| get("url")) {
+                        result.push(Icon {
+                            kind: kind.clone(),
+                            name,
+                            width: size("width"),
+                            height: size("height"),
+                        });
+                    }
+                }
+            }
+            _ => {
+                if let Some(name) = scalar(v) {
+                    result.push(Icon {
+                        kind,
+                        name,
+                        width: None,
+                        height: None,
+                    });
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Looks up a localized text for a locale, like `pt_BR.UTF-8`, falling
+/// back to the language without country, then to the untranslated
+/// text.
+fn localized<'a>(texts: &'a [(String, String)], lang: &str) -> Option<&'a str> {
+    let get = |l: &str| texts.iter().find(|(k, _)| k == l).map(|(_, v)| v);
+    let lang = lang.split(['.', '@']).next().unwrap_or(lang);
+    let base = lang.split('_').next().unwrap_or(lang);
+    get(lang)
+        .or_else(|| get(base))
+        .or_else(|| get(UNTRANSLATED))
+        .map(String::as_str)
+}
+
+/// Splits a YAML stream into its documents.
+fn documents(s: &str) -> Vec<String> {
+    let mut result = vec![String::new()];
+    for line in s.lines() {
+        if line.trim_end() == "---" {
+            result.push(String::new());
+        } else {
+            let doc = result.last_mut().unwrap();
+            doc.push_str(line);
+            doc.push('\n');
+        }
+    }
+    result.retain(|d| !d.trim().is_empty());
+    result
+}
+
+impl Dep11Header {
+    fn from_mapping(map: &Mapping) -> Result<Dep11Header, &'static str> {
+        let get = |key: &str| map.get(key).and_then(scalar);
+        if get("File").as_deref() != Some("DEP-11") {
+            return Err("not a DEP-11 file");
+        }
+        Ok(Dep11Header {
+            version: get("Version").ok_or("missing Version")?,
+            origin: get("Origin").ok_or("missing Origin")?,
+            media_base_url: get("MediaBaseUrl"),
+            priority: get("Priority").and_then(|p| p.parse().ok()),
+            time: get("Time"),
+        })
+    }
+}
+
+impl AppComponent {
+    fn from_mapping(map: &Mapping) -> Result<AppComponent, &'static str> {
+        let get = |key: &str| map.get(key).and_then(scalar);
+        let field = |key: &str| map.get(key).unwrap_or(&Value::Null);
+        Ok(AppComponent {
+            kind: get("Type").ok_or("missing Type")?,
+            id: get("ID").ok_or("missing ID")?,
+            package: get("Package"),
+            names: pairs(field("Name")),
+            summaries: pairs(field("Summary")),
+            descriptions: pairs(field("Description")),
+            categories: strings(field("Categories")),
+            keywords: field("Keywords")
+                .get(UNTRANSLATED)
+                .map(strings)
+                .unwrap_or_default(),
+            icons: icons(field("Icon")),
+            project_license: get("ProjectLicense"),
+            urls: pairs(field("Url")),
+            provides: flat_pairs(field("Provides")),
+        })
+    }
+
+    /// The name of the component in a language, like `de_CH`, falling
+    /// back to the untranslated one.
+    pub fn name(&self, lang: &str) -> Option<&str> {
+        localized(&self.names, lang)
+    }
+
+    /// The summary of the component in a language.
+    pub fn summary(&self, lang: &str) -> Option<&str> {
+        localized(&self.summaries, lang)
+    }
+
+    /// The description of the component in a language.
+    pub fn description(&self, lang: &str) -> Option<&str> {
+        localized(&self.descriptions, lang)
+    }
+
+    /// Look up a URL by its type, like `homepage`.
+    pub fn url(&self, kind: &str) -> Option<&str> {
+        self.urls
+            .iter()
+            .find(|(k, _)| k == kind)
+            .map(|(_, u)| u.as_str())
+    }
+
+    /// The items provided of a type, like `mediatypes`.
+    pub fn provided(&self, kind: &str) -> Vec<&str> {
+        self.provides
+            .iter()
+            .filter(|(k, _)| k == kind)
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
+}
+
+impl Dep11File {
+    /// Reads and parses a DEP-11 file from disk, uncompressed.
+    pub fn from_file(in_file: &Path) -> io::Result<Dep11File> {
+        fs::read_to_string(in_file)?.parse()
+    }
+
+    /// Look up a component by its ID.
+    pub fn get(&self, id: &str) -> Option<&AppComponent> {
+        self.components.iter().find(|c| c.id == id)
+    }
+
+    /// Iterate over the components shipped by a package.
+    pub fn by_package<'a>(
+        &'a self,
+        package: &'a str,
+    ) -> impl Iterator<Item = &'a AppComponent> {
+        self.components
+            .iter()
+            .filter(move |c| c.package.as_deref() == Some(package))
+    }
+
+    /// Links each component to the entry of its package in a Packages
+    /// index, the one with the highest version if there are several.
+    /// Components without an entry are paired with `None`.
+    pub fn link<'a>(
+        &'a self,
+        packages: &'a ControlFile,
+    ) -> Vec<(&'a AppComponent, Option<&'a ControlParagraph>)> {
+        let version = |p: &ControlParagraph| {
+            p.get_entry("Version").and_then(|v| Version::parse(v).ok())
+        };
+        let mut by_name: HashMap<&str, &ControlParagraph> = HashMap::new();
+        for para in packages.get_paragraphs() {
+            let name = match para.get_entry("Package") {
+                Some(name) => name,
+                None => continue,
+            };
+            match by_name.get(name) {
+                Some(other) if version(other) >= version(para) => {}
+                _ => {
+                    by_name.insert(name, para);
+                }
+            }
+        }
+        self.components
+            .iter()
+            .map(|c| {
+                let para = c.package.as_deref().and_then(|p| by_name.get(p));
+                (c, para.copied())
+            })
+            .collect()
+    }
+}
+
+impl FromStr for Dep11File {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |e: &str| io::Error::new(io::ErrorKind::InvalidData, e);
+        let mut docs = documents(s).into_iter();
+        let mut next = || -> io::Result<Option<Mapping>> {
+            let doc = match docs.next() {
+                Some(doc) => doc,
+                None => return Ok(None),
+            };
+            match serde_yaml::from_str(&doc) {
+                Ok(Value::Mapping(map)) => Ok(Some(map)),
+                Ok(_) => Err(invalid("document is not a mapping")),
+                Err(e) => Err(invalid(&e.to_string())),
+            }
+        };
+        let header = next()?.ok_or_else(|| invalid("missing header"))?;
+        let header = Dep11Header::from_mapping(&header).map_err(invalid)?;
+        let mut components = vec![];
+        while let Some(map) = next()? {
+            let component =
+                AppComponent::from_mapping(&map).map_err(invalid)?;
+            components.push(component);
+        }
+        Ok(Dep11File { header, components })
+    }
+}
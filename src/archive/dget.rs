@@ -0,0 +1,285 @@
+//! Downloader for source packages
+//!
+//! Like dget, fetches the `.dsc` file of a source package together with
+//! all files it refers to, either given the URL of the `.dsc` or the
+//! name of the source package and a repository to find it in. The size
+//! and checksums of every file are verified against those listed in the
+//! `.dsc` or the Sources index.
+//!
+//! Files already present in the destination directory are kept if they
+//! match, while partial downloads are resumed with range requests.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use debian::archive::dget::SourceDownloader;
+//! use debian::openpgp::Keyring;
+//! use std::path::Path;
+//!
+//! let mut dget = SourceDownloader::new(Path::new("."));
+//! dget.set_verifier(Keyring::from_file(Path::new("uploaders.gpg"))?);
+//! let url = "http://deb.debian.org/debian/pool/main/f/foo/foo_1.2-1.dsc";
+//! let files = dget.download_dsc(url).unwrap();
+//! println!("fetched {} files", files.len());
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use super::fetch::{request_error, RepositoryFetcher, Verifier};
+use crate::md5sums::md5_hex;
+use crate::package::{ControlFile, ControlParagraph};
+use crate::Version;
+
+/// A file of a source package, as listed in a `.dsc` file or a Sources
+/// index.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SourceFile {
+    pub name: String,
+    pub size: u64,
+    pub md5: Option<String>,
+    pub sha1: Option<String>,
+    pub sha256: Option<String>,
+}
+
+/// Downloads source packages into a directory.
+#[derive(Debug)]
+pub struct SourceDownloader {
+    agent: ureq::Agent,
+    dest_dir: PathBuf,
+    verifier: Option<Box<dyn Verifier>>,
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Whether a file name is safe to write into the destination directory.
+fn is_plain_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('.') && !name.contains('/')
+}
+
+/// Lists the files referred to by a `.dsc` file or a paragraph of a
+/// Sources index, merging its `Files` and `Checksums-*` fields.
+pub fn source_files(para: &ControlParagraph) -> io::Result<Vec<SourceFile>> {
+    let mut files: Vec<SourceFile> = vec![];
+    for field in ["Files", "Checksums-Sha1", "Checksums-Sha256"] {
+        let value = para.get_entry(field).unwrap_or_default();
+        for line in value.lines().filter(|l| !l.trim().is_empty()) {
+            let (digest, size, name) =
+                match line.split_whitespace().collect::<Vec<_>>()[..] {
+                    [digest, size, name] => (digest, size, name),
+                    _ => return Err(invalid(format!("invalid line {line}"))),
+                };
+            if !is_plain_name(name) {
+                return Err(invalid(format!("invalid file name {name}")));
+            }
+            let size = size
+                .parse()
+                .map_err(|_| invalid(format!("invalid size of {name}")))?;
+            let pos = match files.iter().position(|f| f.name == name) {
+                Some(pos) if files[pos].size == size => pos,
+                Some(_) => {
+                    return Err(invalid(format!("conflicting sizes of {name}")))
+                }
+                None => {
+                    files.push(SourceFile {
+                        name: name.to_string(),
+                        size,
+                        md5: None,
+                        sha1: None,
+                        sha256: None,
+                    });
+                    files.len() - 1
+                }
+            };
+            let digest = Some(digest.to_string());
+            match field {
+                "Files" => files[pos].md5 = digest,
+                "Checksums-Sha1" => files[pos].sha1 = digest,
+                _ => files[pos].sha256 = digest,
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Computes the digests of a file.
+fn digests(path: &Path) -> io::Result<(String, String, String)> {
+    let data = fs::read(path)?;
+    Ok((
+        md5_hex(&data),
+        format!("{:x}", Sha1::digest(&data)),
+        format!("{:x}", Sha256::digest(&data)),
+    ))
+}
+
+impl SourceFile {
+    /// Checks whether a file on disk matches the size and all
+    /// checksums listed.
+    pub fn matches(&self, path: &Path) -> io::Result<bool> {
+        if fs::metadata(path)?.len() != self.size {
+            return Ok(false);
+        }
+        let (md5, sha1, sha256) = digests(path)?;
+        let ok = |expected: &Option<String>, actual: &str| {
+            expected.as_deref().map_or(true, |e| e == actual)
+        };
+        Ok(ok(&self.md5, &md5)
+            && ok(&self.sha1, &sha1)
+            && ok(&self.sha256, &sha256))
+    }
+}
+
+impl SourceDownloader {
+    /// Creates a downloader storing files in the given directory, which
+    /// is created if needed.
+    pub fn new(dest_dir: &Path) -> SourceDownloader {
+        SourceDownloader {
+            agent: ureq::Agent::new(),
+            dest_dir: dest_dir.to_path_buf(),
+            verifier: None,
+        }
+    }
+
+    /// Sets a verifier for the signatures of `.dsc` files downloaded by
+    /// URL. Without one, signatures are not checked, like with
+    /// `dget --allow-unauthenticated`.
+    pub fn set_verifier<V: Verifier + 'static>(&mut self, verifier: V) {
+        self.verifier = Some(Box::new(verifier));
+    }
+
+    /// Downloads a file to the destination directory, resuming a
+    /// partial download if possible, and verifies it.
+    fn fetch(&self, url: &str, file: &SourceFile) -> io::Result<PathBuf> {
+        let path = self.dest_dir.join(&file.name);
+        let offset = match fs::metadata(&path) {
+            Ok(_) if file.matches(&path)? => return Ok(path),
+            Ok(meta) if meta.len() < file.size => meta.len(),
+            _ => 0,
+        };
+        let mut request = self.agent.get(url);
+        if offset > 0 {
+            request = request.set("Range", &format!("bytes={offset}-"));
+        }
+        let response = request.call().map_err(request_error)?;
+        let mut out = match response.status() {
+            206 => OpenOptions::new().append(true).open(&path)?,
+            _ => File::create(&path)?,
+        };
+        let limit = file.size + 1;
+        io::copy(&mut response.into_reader().take(limit), &mut out)?;
+        drop(out);
+        if !file.matches(&path)? {
+            fs::remove_file(&path)?;
+            return Err(invalid(format!(
+                "checksum mismatch for {}",
+                file.name
+            )));
+        }
+        Ok(path)
+    }
+
+    /// Downloads the files listed for a source package from a base URL,
+    /// returning their paths.
+    fn fetch_files(
+        &self,
+        base_url: &str,
+        files: &[SourceFile],
+    ) -> io::Result<Vec<PathBuf>> {
+        files
+            .iter()
+            .map(|f| self.fetch(&format!("{base_url}/{}", f.name), f))
+            .collect()
+    }
+
+    /// Downloads a `.dsc` file and all files it refers to, which are
+    /// expected next to it, returning their paths with the `.dsc` file
+    /// first. The signature of the `.dsc` is verified if a verifier is
+    /// set.
+    pub fn download_dsc(&self, url: &str) -> io::Result<Vec<PathBuf>> {
+        let (base_url, name) = url
+            .rsplit_once('/')
+            .filter(|(_, name)| is_plain_name(name) && name.ends_with(".dsc"))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("not the URL of a .dsc file: {url}"),
+                )
+            })?;
+        fs::create_dir_all(&self.dest_dir)?;
+        let response = self.agent.get(url).call().map_err(request_error)?;
+        let mut signed = vec![];
+        io::copy(&mut response.into_reader(), &mut signed)?;
+        let text = match self.verifier {
+            Some(ref verifier) => verifier.verify(&signed)?,
+            None => signed.clone(),
+        };
+        let text = String::from_utf8(text)
+            .map_err(|_| invalid(format!("{name} is not valid UTF-8")))?;
+        let cf = ControlFile::from_reader(text.as_bytes())?;
+        let para = match cf.get_paragraphs().as_slice() {
+            [para] => para.clone(),
+            _ => return Err(invalid(format!("invalid {name}"))),
+        };
+        let dsc = self.dest_dir.join(name);
+        fs::write(&dsc, &signed)?;
+        let mut paths = vec![dsc];
+        paths.extend(self.fetch_files(base_url, &source_files(&para)?)?);
+        Ok(paths)
+    }
+
+    /// Downloads a source package from a repository, given its name
+    /// and the component to look in, with the `.dsc` file first. The
+    /// latest version is fetched unless a version is given.
+    ///
+    /// The fetcher needs to be updated before. The files are verified
+    /// against the Sources index, which is covered by the signature of
+    /// the Release file.
+    pub fn download_source(
+        &self,
+        fetcher: &RepositoryFetcher,
+        component: &str,
+        source: &str,
+        version: Option<&Version>,
+    ) -> io::Result<Vec<PathBuf>> {
+        let sources = fetcher.fetch_sources(component)?;
+        let para = sources
+            .get_paragraphs()
+            .iter()
+            .filter(|p| p.get_entry("Package") == Some(source))
+            .filter_map(|p| {
+                let v = Version::parse(p.get_entry("Version")?).ok()?;
+                Some((v, p))
+            })
+            .filter(|(v, _)| version.map_or(true, |version| v == version))
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, p)| p)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("source package {source} not found"),
+                )
+            })?;
+        let directory = para
+            .get_entry("Directory")
+            .ok_or_else(|| invalid("missing Directory field".to_string()))?;
+        let mut files = source_files(para)?;
+        // fetch the .dsc first
+        files.sort_by_key(|f| !f.name.ends_with(".dsc"));
+        fs::create_dir_all(&self.dest_dir)?;
+        let base_url = format!(
+            "{}/{}",
+            fetcher.archive_url(),
+            directory.trim_matches('/')
+        );
+        self.fetch_files(&base_url, &files)
+    }
+}
@@ -0,0 +1,462 @@
+//! Fetcher for the index files of remote archives
+//!
+//! Downloads the `InRelease` file of a distribution over HTTP, verifies
+//! its signature and then fetches index files like `Packages`, checking
+//! their sizes and SHA256 digests against the Release file, much like
+//! `apt update` does. Index files are fetched by their digest where the
+//! archive supports it.
+//!
+//! Signatures are verified against a [`Keyring`] of the `openpgp`
+//! module. With the `gpgv` feature, the `gpgv` program can be used
+//! instead, which supports more kinds of keys.
+//!
+//! With a cache directory set, the InRelease file is only downloaded if
+//! it changed, using a conditional request, and index files are only
+//! downloaded if the cached copy does not match the Release file.
+//! Parsed indices can additionally be kept in an
+//! [`IndexCache`](super::cache::IndexCache), which skips both download
+//! and parsing of indices that did not change. Uncompressed index
+//! files kept elsewhere can be brought up to date by fetching only the
+//! patches leading to the current version, see
+//! [`pdiff`](super::pdiff).
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use debian::archive::fetch::RepositoryFetcher;
+//! use debian::openpgp::Keyring;
+//! use std::path::Path;
+//!
+//! let keyring = Keyring::from_file(Path::new("archive-keyring.gpg"))?;
+//! let mut fetcher = RepositoryFetcher::new(
+//!     "http://example.org/debian",
+//!     "bookworm",
+//!     keyring,
+//! );
+//! fetcher.set_cache_dir(Path::new("/var/cache/example"));
+//! fetcher.update().unwrap();
+//! let packages = fetcher.fetch_packages("main", "amd64").unwrap();
+//! println!("{} packages", packages.get_paragraphs().len());
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::Read;
+#[cfg(feature = "gpgv")]
+use std::io::Write;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "gpgv")]
+use std::process::Command;
+
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+
+use super::cache::IndexCache;
+#[cfg(feature = "yaml")]
+use super::dep11::Dep11File;
+use super::pdiff::{PdiffIndex, PdiffPatch, PDIFF_INDEX};
+use super::release::{Release, ReleaseIndex};
+use super::translation::Translation;
+use crate::deb::Compression;
+use crate::openpgp::Keyring;
+use crate::package::ControlFile;
+
+/// Compressions tried for index files, in order of preference.
+const INDEX_COMPRESSIONS: &[Compression] =
+    &[Compression::Xz, Compression::Gzip, Compression::None];
+
+/// Verifies the signature of InRelease files.
+pub trait Verifier: fmt::Debug {
+    /// Checks the signature of a clearsigned file and returns the
+    /// signed data.
+    fn verify(&self, signed: &[u8]) -> io::Result<Vec<u8>>;
+}
+
+/// Verifies signatures with `gpgv` against a set of keyrings, as apt
+/// does. Needs the `gpgv` program to be installed.
+#[cfg(feature = "gpgv")]
+#[derive(Debug, Clone)]
+pub struct Gpgv {
+    keyrings: Vec<PathBuf>,
+}
+
+/// Fetches and verifies the index files of a distribution of a remote
+/// archive.
+#[derive(Debug)]
+pub struct RepositoryFetcher {
+    agent: ureq::Agent,
+    archive_url: String,
+    dist_url: String,
+    /// the distribution, which the Release file has to name
+    dist: String,
+    verifier: Box<dyn Verifier>,
+    cache_dir: Option<PathBuf>,
+    index_cache: Option<IndexCache>,
+    release: Option<Release>,
+}
+
+#[cfg(feature = "gpgv")]
+impl Gpgv {
+    /// Creates a verifier trusting the keys of the given keyring.
+    pub fn new(keyring: &Path) -> Gpgv {
+        Gpgv {
+            keyrings: vec![keyring.to_path_buf()],
+        }
+    }
+
+    /// Additionally trusts the keys of another keyring.
+    pub fn add_keyring(&mut self, keyring: &Path) {
+        self.keyrings.push(keyring.to_path_buf());
+    }
+}
+
+impl Verifier for Keyring {
+    fn verify(&self, signed: &[u8]) -> io::Result<Vec<u8>> {
+        let text = std::str::from_utf8(signed).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidData, "non-UTF-8 signed data")
+        })?;
+        Ok(self.verify_clearsigned(text)?.into_bytes())
+    }
+}
+
+#[cfg(feature = "gpgv")]
+impl Verifier for Gpgv {
+    fn verify(&self, signed: &[u8]) -> io::Result<Vec<u8>> {
+        let mut file = tempfile::NamedTempFile::new()?;
+        file.write_all(signed)?;
+        file.flush()?;
+        let mut cmd = Command::new("gpgv");
+        cmd.arg("--quiet");
+        for keyring in &self.keyrings {
+            cmd.arg("--keyring").arg(keyring);
+        }
+        let output = cmd.arg("--output").arg("-").arg(file.path()).output()?;
+        if !output.status.success() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "signature verification failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            ));
+        }
+        Ok(output.stdout)
+    }
+}
+
+/// Converts a failed request into an I/O error.
+pub(crate) fn request_error(err: ureq::Error) -> io::Error {
+    let kind = match err {
+        ureq::Error::Status(404, _) => io::ErrorKind::NotFound,
+        _ => io::ErrorKind::Other,
+    };
+    io::Error::new(kind, err.to_string())
+}
+
+fn read_body(response: ureq::Response) -> io::Result<Vec<u8>> {
+    let mut body = vec![];
+    response.into_reader().read_to_end(&mut body)?;
+    Ok(body)
+}
+
+/// Whether data matches the size and digest listed for an index file.
+fn matches_index(data: &[u8], index: &ReleaseIndex) -> bool {
+    data.len() as u64 == index.size
+        && format!("{:x}", Sha256::digest(data)) == index.sha256
+}
+
+impl RepositoryFetcher {
+    /// Creates a fetcher for a distribution, like `bookworm`, of the
+    /// archive at the given URL, like `http://deb.debian.org/debian`.
+    pub fn new<V: Verifier + 'static>(
+        archive_url: &str,
+        dist: &str,
+        verifier: V,
+    ) -> RepositoryFetcher {
+        let archive_url = archive_url.trim_end_matches('/');
+        let dist = dist.trim_matches('/');
+        RepositoryFetcher {
+            agent: ureq::Agent::new(),
+            archive_url: archive_url.to_string(),
+            dist_url: format!("{archive_url}/dists/{dist}"),
+            dist: dist.to_string(),
+            verifier: Box::new(verifier),
+            cache_dir: None,
+            index_cache: None,
+            release: None,
+        }
+    }
+
+    /// The URL of the archive, without trailing slash.
+    pub fn archive_url(&self) -> &str {
+        &self.archive_url
+    }
+
+    /// Sets a directory to keep fetched files in, which is created if
+    /// needed. It should not be shared with other distributions.
+    pub fn set_cache_dir(&mut self, dir: &Path) {
+        self.cache_dir = Some(dir.to_path_buf());
+    }
+
+    /// Sets a cache for parsed indices, used by `fetch_packages` and
+    /// `fetch_sources`. Unlike the cache directory, it may be shared
+    /// between distributions.
+    pub fn set_index_cache(&mut self, cache: IndexCache) {
+        self.index_cache = Some(cache);
+    }
+
+    fn cache_path(&self, name: &str) -> Option<PathBuf> {
+        let dir = self.cache_dir.as_ref()?;
+        Some(dir.join(name.replace('/', "_")))
+    }
+
+    fn write_cache(&self, name: &str, data: &[u8]) -> io::Result<()> {
+        if let Some(path) = self.cache_path(name) {
+            fs::create_dir_all(path.parent().unwrap())?;
+            fs::write(path, data)?;
+        }
+        Ok(())
+    }
+
+    /// Fetches and verifies the InRelease file, returning whether it
+    /// changed since it was last fetched into the cache directory.
+    ///
+    /// Fails if the signature is invalid, if the Release file names
+    /// neither the distribution requested as its suite nor as its
+    /// codename, so a validly signed file of another distribution is
+    /// not accepted in its place, or if it has expired according to its
+    /// Valid-Until field.
+    pub fn update(&mut self) -> io::Result<bool> {
+        let cached = match self.cache_path("InRelease") {
+            Some(path) if path.exists() => Some(fs::read(path)?),
+            _ => None,
+        };
+        let last_modified = match self.cache_path("InRelease.last-modified") {
+            Some(path) if cached.is_some() && path.exists() => {
+                Some(fs::read_to_string(path)?)
+            }
+            _ => None,
+        };
+
+        let mut request =
+            self.agent.get(&format!("{}/InRelease", self.dist_url));
+        if let Some(date) = last_modified.as_deref().map(str::trim) {
+            if !date.is_empty() {
+                request = request.set("If-Modified-Since", date);
+            }
+        }
+        let response = request.call().map_err(request_error)?;
+        let (signed, date) = match (response.status(), cached) {
+            (304, Some(cached)) => (cached, None),
+            _ => {
+                let date = response.header("Last-Modified").map(str::to_string);
+                (read_body(response)?, Some(date))
+            }
+        };
+
+        let data = self.verifier.verify(&signed)?;
+        let release = Release::from_reader(data.as_slice())?;
+        if release.suite() != Some(self.dist.as_str())
+            && release.codename() != Some(self.dist.as_str())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Release file is not for {}", self.dist),
+            ));
+        }
+        if release.valid_until().map_or(false, |d| d < Utc::now()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Release file has expired",
+            ));
+        }
+        let changed = date.is_some();
+        if let Some(date) = date {
+            self.write_cache("InRelease", &signed)?;
+            self.write_cache(
+                "InRelease.last-modified",
+                date.unwrap_or_default().as_bytes(),
+            )?;
+        }
+        self.release = Some(release);
+        Ok(changed)
+    }
+
+    /// The Release file, once fetched by `update`.
+    pub fn release(&self) -> Option<&Release> {
+        self.release.as_ref()
+    }
+
+    /// Fetches an index file, given by its path relative to the
+    /// distribution's directory, like `main/binary-amd64/Packages.xz`.
+    /// Its size and digest are checked against the Release file, so
+    /// only files listed there can be fetched.
+    pub fn fetch_index(&self, path: &str) -> io::Result<Vec<u8>> {
+        let release = self.release.as_ref().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Other, "no Release file fetched")
+        })?;
+        let index = release.index(path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{path} not listed in Release file"),
+            )
+        })?;
+        if let Some(cached) = self.cache_path(path) {
+            if let Ok(data) = fs::read(cached) {
+                if matches_index(&data, index) {
+                    return Ok(data);
+                }
+            }
+        }
+
+        let fetch_path = release.fetch_path(path).unwrap();
+        let url = format!("{}/{}", self.dist_url, fetch_path);
+        let data =
+            read_body(self.agent.get(&url).call().map_err(request_error)?)?;
+        if !matches_index(&data, index) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("hash sum mismatch for {path}"),
+            ));
+        }
+        self.write_cache(path, &data)?;
+        Ok(data)
+    }
+
+    /// Brings an uncompressed index file, like
+    /// `main/binary-amd64/Packages`, up to date from an older version of
+    /// it, fetching only the patches from the `Packages.diff` directory
+    /// next to it. The index of patches is checked against the Release
+    /// file, the patches and the result against the index of patches.
+    ///
+    /// Fails with `NotFound` if the archive offers no patches for the
+    /// given version, in which case the index file has to be fetched in
+    /// full.
+    pub fn fetch_pdiff(&self, path: &str, old: &[u8]) -> io::Result<Vec<u8>> {
+        let diff_dir = format!("{path}.diff");
+        let data = self.fetch_index(&format!("{diff_dir}/{PDIFF_INDEX}"))?;
+        let index = PdiffIndex::from_reader(data.as_slice())?;
+        let patches = index.patches_for(old).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no patches for this version of {path}"),
+            )
+        })?;
+
+        let mut scripts = vec![];
+        for patch in &patches {
+            let (size, sha256) =
+                match (patch.download_size, &patch.download_sha256) {
+                    (Some(size), Some(sha256)) => (size, sha256),
+                    _ => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("no digest for download of {}", patch.name),
+                        ))
+                    }
+                };
+            let url = format!("{}/{diff_dir}/{}.gz", self.dist_url, patch.name);
+            let data =
+                read_body(self.agent.get(&url).call().map_err(request_error)?)?;
+            if data.len() as u64 != size
+                || format!("{:x}", Sha256::digest(&data)) != *sha256
+            {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("hash sum mismatch for {}.gz", patch.name),
+                ));
+            }
+            let mut script = vec![];
+            Compression::Gzip
+                .decoder(data.as_slice())?
+                .read_to_end(&mut script)?;
+            scripts.push(script);
+        }
+        let patches: Vec<(&PdiffPatch, &[u8])> = patches
+            .into_iter()
+            .zip(scripts.iter().map(Vec::as_slice))
+            .collect();
+        index.apply(old, &patches)
+    }
+
+    /// Finds the first index file listed in the Release file of the
+    /// variants of a path compressed differently.
+    fn find_compressed(
+        &self,
+        path: &str,
+    ) -> io::Result<(String, Compression, &ReleaseIndex)> {
+        let release = self.release.as_ref().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Other, "no Release file fetched")
+        })?;
+        for compression in INDEX_COMPRESSIONS {
+            let name = format!("{path}{}", compression.extension());
+            if let Some(index) = release.index(&name) {
+                return Ok((name, *compression, index));
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{path} not listed in Release file"),
+        ))
+    }
+
+    /// Fetches the first index file listed in the Release file of the
+    /// variants of a path compressed differently, and decompresses it.
+    fn fetch_decompressed(&self, path: &str) -> io::Result<ControlFile> {
+        let (name, compression, index) = self.find_compressed(path)?;
+        let parse = || {
+            let data = self.fetch_index(&name)?;
+            let reader = compression.decoder(data.as_slice())?;
+            ControlFile::from_reader(io::BufReader::new(reader))
+        };
+        match self.index_cache {
+            Some(ref cache) => cache.get_or_parse(&index.sha256, parse),
+            None => parse(),
+        }
+    }
+
+    /// Fetches the Packages index of a component and architecture.
+    pub fn fetch_packages(
+        &self,
+        component: &str,
+        arch: &str,
+    ) -> io::Result<ControlFile> {
+        self.fetch_decompressed(&format!("{component}/binary-{arch}/Packages"))
+    }
+
+    /// Fetches the Sources index of a component.
+    pub fn fetch_sources(&self, component: &str) -> io::Result<ControlFile> {
+        self.fetch_decompressed(&format!("{component}/source/Sources"))
+    }
+
+    /// Fetches the Translation index of a component for a language,
+    /// like `de`.
+    pub fn fetch_translation(
+        &self,
+        component: &str,
+        lang: &str,
+    ) -> io::Result<Translation> {
+        let path = format!("{component}/i18n/Translation-{lang}");
+        Translation::from_control_file(lang, &self.fetch_decompressed(&path)?)
+    }
+
+    /// Fetches the DEP-11 AppStream metadata of a component and
+    /// architecture. Requires the `yaml` feature.
+    #[cfg(feature = "yaml")]
+    pub fn fetch_dep11(
+        &self,
+        component: &str,
+        arch: &str,
+    ) -> io::Result<Dep11File> {
+        let path = format!("{component}/dep11/Components-{arch}.yml");
+        let (name, compression, _) = self.find_compressed(&path)?;
+        let data = self.fetch_index(&name)?;
+        let mut text = String::new();
+        compression
+            .decoder(data.as_slice())?
+            .read_to_string(&mut text)?;
+        text.parse()
+    }
+}
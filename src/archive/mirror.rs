@@ -0,0 +1,298 @@
+//! Planner for synchronizing mirrors of archives
+//!
+//! A mirror keeps a copy of the index files of some distributions of
+//! a remote archive, and of the packages in its pool they refer to.
+//! Given the Release files and indices of the remote archive, the
+//! planner compares them against the files of a local copy and
+//! computes the files to fetch and the ones to delete, along with
+//! their total sizes. Only files not matching the remote ones are
+//! fetched, and each only once, even if stored under several names.
+//!
+//! With `Acquire-By-Hash: yes`, index files are fetched from their
+//! by-hash location and stored both there and under their name.
+//! Older files in the by-hash directories are left alone, so clients
+//! of the mirror still using a previous Release file can fetch them.
+//!
+//! Index files are compared by size and SHA256 digest, files in the
+//! pool only by size, as their contents never change.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use debian::archive::mirror::MirrorPlanner;
+//! use debian::archive::release::Release;
+//! use std::path::Path;
+//!
+//! let release = Release::from_file(Path::new("/tmp/Release")).unwrap();
+//! let mut planner = MirrorPlanner::new(Path::new("/srv/mirror"));
+//! planner.add_release("bookworm", &release, |path| {
+//!     path.starts_with("main/binary-amd64/")
+//! });
+//! let plan = planner.plan().unwrap();
+//! println!(
+//!     "{} files to fetch, {} bytes",
+//!     plan.fetch.len(),
+//!     plan.fetch_size
+//! );
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use super::release::{parse_checksums, Release, RELEASE_FILES};
+use crate::package::ControlFile;
+
+/// Name of the directory holding the packages of an archive.
+const POOL_DIR: &str = "pool";
+
+/// A file to fetch from the remote archive.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MirrorFetch {
+    /// the path to fetch, relative to the archive root, like
+    /// `dists/bookworm/main/binary-amd64/by-hash/SHA256/<digest>`
+    pub source: String,
+    /// the paths to store the file at, relative to the archive root
+    pub targets: Vec<String>,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// The changes needed to bring a mirror up to date.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct MirrorPlan {
+    /// the files to fetch, sorted by source
+    pub fetch: Vec<MirrorFetch>,
+    /// the files to delete, relative to the archive root, sorted
+    pub delete: Vec<String>,
+    /// the total size of the files to fetch
+    pub fetch_size: u64,
+    /// the total size of the files to delete
+    pub delete_size: u64,
+}
+
+/// A file the mirror should hold.
+#[derive(Debug, Clone)]
+struct WantedFile {
+    source: String,
+    size: u64,
+    sha256: String,
+    /// whether to compare the digest of a local copy, not just its size
+    verify: bool,
+}
+
+/// Collects the files a mirror should hold and plans the changes to a
+/// local copy.
+#[derive(Debug, Clone)]
+pub struct MirrorPlanner {
+    root: PathBuf,
+    files: BTreeMap<String, WantedFile>,
+    /// the directories to look for files to delete in
+    dirs: BTreeSet<String>,
+    /// files never deleted, like the Release files
+    keep: HashSet<String>,
+}
+
+impl MirrorPlan {
+    /// Whether the mirror is up to date.
+    pub fn is_empty(&self) -> bool {
+        self.fetch.is_empty() && self.delete.is_empty()
+    }
+}
+
+/// Whether a path lies within a by-hash directory.
+fn is_by_hash(path: &str) -> bool {
+    path.split('/').any(|c| c == "by-hash")
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+impl MirrorPlanner {
+    /// Creates a planner for a local copy of an archive, given its
+    /// root directory, which need not exist yet.
+    pub fn new(root: &Path) -> MirrorPlanner {
+        MirrorPlanner {
+            root: root.to_path_buf(),
+            files: BTreeMap::new(),
+            dirs: BTreeSet::new(),
+            keep: HashSet::new(),
+        }
+    }
+
+    fn want(&mut self, path: String, file: WantedFile) {
+        self.files.insert(path, file);
+    }
+
+    /// Adds the index files of a distribution, like `bookworm`, listed
+    /// in its Release file, for which `wanted` returns true given their
+    /// path relative to the distribution's directory. The Release files
+    /// themselves are expected to be fetched by the caller.
+    pub fn add_release<F: Fn(&str) -> bool>(
+        &mut self,
+        dist: &str,
+        release: &Release,
+        wanted: F,
+    ) {
+        let dist_dir = format!("dists/{}", dist.trim_matches('/'));
+        for name in RELEASE_FILES {
+            self.keep.insert(format!("{dist_dir}/{name}"));
+        }
+        for index in release.indices() {
+            if !wanted(&index.path) {
+                continue;
+            }
+            let fetch_path = release.fetch_path(&index.path).unwrap();
+            let file = WantedFile {
+                source: format!("{dist_dir}/{fetch_path}"),
+                size: index.size,
+                sha256: index.sha256.clone(),
+                verify: true,
+            };
+            if fetch_path != index.path {
+                self.want(file.source.clone(), file.clone());
+            }
+            self.want(format!("{dist_dir}/{}", index.path), file);
+        }
+        self.dirs.insert(dist_dir);
+    }
+
+    /// Adds the packages listed in a Packages index.
+    ///
+    /// Fails with `InvalidData` if an entry lacks its file name, size
+    /// or SHA256 digest.
+    pub fn add_packages(&mut self, packages: &ControlFile) -> io::Result<()> {
+        for para in packages.get_paragraphs() {
+            let (path, size, sha256) = match (
+                para.get_entry("Filename"),
+                para.get_entry("Size").and_then(|s| s.parse().ok()),
+                para.get_entry("SHA256"),
+            ) {
+                (Some(path), Some(size), Some(sha256)) => (path, size, sha256),
+                _ => return Err(invalid("incomplete entry in Packages")),
+            };
+            self.want(
+                path.to_string(),
+                WantedFile {
+                    source: path.to_string(),
+                    size,
+                    sha256: sha256.to_string(),
+                    verify: false,
+                },
+            );
+        }
+        self.dirs.insert(POOL_DIR.to_string());
+        Ok(())
+    }
+
+    /// Adds the files of the source packages listed in a Sources index.
+    ///
+    /// Fails with `InvalidData` if an entry lacks its directory or
+    /// SHA256 digests.
+    pub fn add_sources(&mut self, sources: &ControlFile) -> io::Result<()> {
+        for para in sources.get_paragraphs() {
+            let (dir, files) = match (
+                para.get_entry("Directory"),
+                para.get_entry("Checksums-Sha256"),
+            ) {
+                (Some(dir), Some(files)) => (dir.trim_end_matches('/'), files),
+                _ => return Err(invalid("incomplete entry in Sources")),
+            };
+            for line in parse_checksums(files) {
+                let (sha256, size, name) = line?;
+                let path = format!("{dir}/{name}");
+                self.want(
+                    path.clone(),
+                    WantedFile {
+                        source: path,
+                        size,
+                        sha256: sha256.to_string(),
+                        verify: false,
+                    },
+                );
+            }
+        }
+        self.dirs.insert(POOL_DIR.to_string());
+        Ok(())
+    }
+
+    /// Whether the local copy of a file matches the remote one.
+    fn is_current(&self, path: &str, file: &WantedFile) -> io::Result<bool> {
+        let local = self.root.join(path);
+        match fs::metadata(&local) {
+            Ok(m) if m.is_file() && m.len() == file.size => {}
+            Ok(_) => return Ok(false),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        }
+        if !file.verify {
+            return Ok(true);
+        }
+        let mut hasher = Sha256::new();
+        io::copy(&mut fs::File::open(&local)?, &mut hasher)?;
+        Ok(format!("{:x}", hasher.finalize()) == file.sha256)
+    }
+
+    /// Collects the files below a directory of the local copy not
+    /// wanted anymore, recursively.
+    fn find_obsolete(
+        &self,
+        dir: &str,
+        plan: &mut MirrorPlan,
+    ) -> io::Result<()> {
+        let mut entries = match fs::read_dir(self.root.join(dir)) {
+            Ok(entries) => entries.collect::<io::Result<Vec<_>>>()?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        entries.sort_by_key(|e| e.file_name());
+        for entry in entries {
+            let path = format!("{dir}/{}", entry.file_name().to_string_lossy());
+            let meta = fs::symlink_metadata(entry.path())?;
+            if meta.is_dir() {
+                self.find_obsolete(&path, plan)?;
+            } else if !self.files.contains_key(&path)
+                && !self.keep.contains(&path)
+                && !is_by_hash(&path)
+            {
+                plan.delete_size += meta.len();
+                plan.delete.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Compares the files added against the local copy.
+    pub fn plan(&self) -> io::Result<MirrorPlan> {
+        let mut fetch: BTreeMap<&str, MirrorFetch> = BTreeMap::new();
+        for (path, file) in &self.files {
+            if self.is_current(path, file)? {
+                continue;
+            }
+            fetch
+                .entry(&file.source)
+                .or_insert_with(|| MirrorFetch {
+                    source: file.source.clone(),
+                    targets: vec![],
+                    size: file.size,
+                    sha256: file.sha256.clone(),
+                })
+                .targets
+                .push(path.clone());
+        }
+        let mut plan = MirrorPlan {
+            fetch: fetch.into_values().collect(),
+            ..Default::default()
+        };
+        plan.fetch_size = plan.fetch.iter().map(|f| f.size).sum();
+        for dir in &self.dirs {
+            self.find_obsolete(dir, &mut plan)?;
+        }
+        plan.delete.sort();
+        Ok(plan)
+    }
+}
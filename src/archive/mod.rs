@@ -0,0 +1,20 @@
+//! Tools for Debian archives
+//!
+//! This module contains parsers and generators for the index files of
+//! a Debian archive, as served to apt.
+
+pub mod cache;
+pub mod contents;
+#[cfg(feature = "yaml")]
+pub mod dep11;
+#[cfg(feature = "http")]
+pub mod dget;
+#[cfg(feature = "http")]
+pub mod fetch;
+pub mod mirror;
+pub mod overrides;
+#[cfg(feature = "deb")]
+pub mod packages;
+pub mod pdiff;
+pub mod release;
+pub mod translation;
@@ -0,0 +1,221 @@
+//! Override files
+//!
+//! Archives managed by dak or apt-ftparchive decide about the priority
+//! and section of binary packages centrally, in override files, rather
+//! than trusting the values given by the packages. An override file
+//! lists one package per line, with its priority, section and
+//! optionally a new maintainer:
+//!
+//! ```text
+//! foo optional utils
+//! bar extra devel Jane Doe <jane@example.org>
+//! baz optional misc Old Maintainer <old@example.org> => New <new@example.org>
+//! ```
+//!
+//! The last form only replaces the maintainer if it is one of those
+//! given before `=>`, separated by `//`.
+//!
+//! Extra override files set arbitrary fields, with one field per line:
+//!
+//! ```text
+//! foo Task gnome-desktop, kde-desktop
+//! ```
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::archive::overrides::Overrides;
+//! use debian::package::ControlParagraph;
+//!
+//! let mut overrides: Overrides = "foo optional utils\n".parse().unwrap();
+//! overrides.parse_extra("foo Task minimal\n").unwrap();
+//!
+//! let mut para = ControlParagraph::default();
+//! para.add_entry("Package", "foo".to_string());
+//! para.add_entry("Priority", "extra".to_string());
+//! assert!(overrides.apply(&mut para));
+//! assert_eq!(para.get_entry("Priority"), Some("optional"));
+//! assert_eq!(para.get_entry("Section"), Some("utils"));
+//! assert_eq!(para.get_entry("Task"), Some("minimal"));
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::package::ControlParagraph;
+
+/// How an override changes the maintainer of a package.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum MaintainerOverride {
+    /// Replaces any maintainer.
+    Set(String),
+    /// Replaces the maintainer only if it is one of the old ones.
+    Replace { old: Vec<String>, new: String },
+}
+
+/// The override of a single binary package.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct OverrideEntry {
+    pub package: String,
+    pub priority: String,
+    pub section: String,
+    pub maintainer: Option<MaintainerOverride>,
+}
+
+/// The contents of an override file, possibly along with extra
+/// overrides.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Overrides {
+    entries: BTreeMap<String, OverrideEntry>,
+    extra: BTreeMap<String, Vec<(String, String)>>,
+}
+
+/// Strips a comment and surrounding whitespace from a line.
+fn strip_comment(line: &str) -> &str {
+    match line.find('#') {
+        Some(pos) => line[..pos].trim(),
+        None => line.trim(),
+    }
+}
+
+impl MaintainerOverride {
+    fn parse(s: &str) -> MaintainerOverride {
+        match s.split_once("=>") {
+            Some((old, new)) => MaintainerOverride::Replace {
+                old: old.split("//").map(|o| o.trim().to_string()).collect(),
+                new: new.trim().to_string(),
+            },
+            None => MaintainerOverride::Set(s.to_string()),
+        }
+    }
+
+    /// The maintainer to use instead of the given one, if any.
+    pub fn apply(&self, maintainer: Option<&str>) -> Option<&str> {
+        match *self {
+            MaintainerOverride::Set(ref new) => Some(new),
+            MaintainerOverride::Replace { ref old, ref new } => maintainer
+                .filter(|m| old.iter().any(|o| o == m))
+                .map(|_| new.as_str()),
+        }
+    }
+}
+
+impl FromStr for OverrideEntry {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let package = words.next().ok_or("missing package name")?;
+        let priority = words.next().ok_or("missing priority")?;
+        let section = words.next().ok_or("missing section")?;
+        let maintainer = words.collect::<Vec<&str>>().join(" ");
+        Ok(OverrideEntry {
+            package: package.to_string(),
+            priority: priority.to_string(),
+            section: section.to_string(),
+            maintainer: match maintainer.as_str() {
+                "" => None,
+                m => Some(MaintainerOverride::parse(m)),
+            },
+        })
+    }
+}
+
+impl Overrides {
+    /// Reads and parses an override file from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<Overrides> {
+        fs::read_to_string(in_file)?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Adds the entries of an override file, replacing earlier entries
+    /// of the same packages.
+    pub fn parse_overrides(&mut self, s: &str) -> Result<(), &'static str> {
+        for line in s.lines().map(strip_comment).filter(|l| !l.is_empty()) {
+            let entry: OverrideEntry = line.parse()?;
+            self.entries.insert(entry.package.clone(), entry);
+        }
+        Ok(())
+    }
+
+    /// Adds the entries of an extra override file.
+    pub fn parse_extra(&mut self, s: &str) -> Result<(), &'static str> {
+        for line in s.lines().map(strip_comment).filter(|l| !l.is_empty()) {
+            let (package, rest) = line
+                .split_once(char::is_whitespace)
+                .ok_or("missing field name")?;
+            let rest = rest.trim_start();
+            let (field, value) = rest
+                .split_once(char::is_whitespace)
+                .ok_or("missing field value")?;
+            let fields = self.extra.entry(package.to_string()).or_default();
+            let value = value.trim().to_string();
+            match fields.iter_mut().find(|(f, _)| f == field) {
+                Some((_, v)) => *v = value,
+                None => fields.push((field.to_string(), value)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads and adds an extra override file from disk.
+    pub fn load_extra_file(&mut self, in_file: &Path) -> io::Result<()> {
+        self.parse_extra(&fs::read_to_string(in_file)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// The override of a package, if any.
+    pub fn get(&self, package: &str) -> Option<&OverrideEntry> {
+        self.entries.get(package)
+    }
+
+    /// The extra fields to set for a package.
+    pub fn extra(&self, package: &str) -> &[(String, String)] {
+        self.extra.get(package).map_or(&[], |f| f.as_slice())
+    }
+
+    /// All overrides, sorted by package name.
+    pub fn entries(&self) -> impl Iterator<Item = &OverrideEntry> {
+        self.entries.values()
+    }
+
+    /// Applies the overrides to the paragraph of a binary package,
+    /// returning true if the override file has an entry for it. Extra
+    /// overrides are applied in any case.
+    pub fn apply(&self, para: &mut ControlParagraph) -> bool {
+        let package = match para.get_entry("Package") {
+            Some(package) => package.to_string(),
+            None => return false,
+        };
+        for (field, value) in self.extra(&package) {
+            para.update_entry(field, value.clone());
+        }
+        let entry = match self.entries.get(&package) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        para.update_entry("Priority", entry.priority.clone());
+        para.update_entry("Section", entry.section.clone());
+        if let Some(ref maintainer) = entry.maintainer {
+            let current = para.get_entry("Maintainer");
+            if let Some(new) = maintainer.apply(current) {
+                para.update_entry("Maintainer", new.to_string());
+            }
+        }
+        true
+    }
+}
+
+impl FromStr for Overrides {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut result = Overrides::default();
+        result.parse_overrides(s)?;
+        Ok(result)
+    }
+}
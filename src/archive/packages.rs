@@ -0,0 +1,202 @@
+//! Generator for Packages indices
+//!
+//! A `Packages` index lists the control paragraphs of all binary
+//! packages of a component and architecture, each extended by the
+//! location of the `.deb` relative to the archive root and its size
+//! and checksums. Like `apt-ftparchive packages`, the generator scans a
+//! directory for `.deb` files to build the index from. Priorities and
+//! sections may be overridden by override files.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use debian::archive::overrides::Overrides;
+//! use debian::archive::packages::PackagesIndex;
+//! use debian::deb::Compression;
+//! use std::path::Path;
+//!
+//! let root = Path::new("/srv/repo");
+//! let mut index =
+//!     PackagesIndex::scan(root, &root.join("pool/main")).unwrap();
+//! let overrides =
+//!     Overrides::from_file(&root.join("indices/override.stable.main"))
+//!         .unwrap();
+//! index.apply_overrides(&overrides);
+//! index
+//!     .to_dir(
+//!         &root.join("dists/stable/main/binary-amd64"),
+//!         &[Compression::None, Compression::Xz],
+//!     )
+//!     .unwrap();
+//! ```
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use super::overrides::Overrides;
+use crate::deb::{Compression, DebFile};
+use crate::md5sums::md5_hex;
+use crate::package::ControlParagraph;
+use crate::version::Version;
+
+/// A Packages index, with paragraphs sorted by package name, version
+/// and architecture.
+#[derive(Debug, Clone, Default)]
+pub struct PackagesIndex {
+    paragraphs: Vec<ControlParagraph>,
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Compares two paragraphs by package name, version and architecture.
+fn compare_paragraphs(a: &ControlParagraph, b: &ControlParagraph) -> Ordering {
+    let version = |p: &ControlParagraph| {
+        p.get_entry("Version").and_then(|v| Version::parse(v).ok())
+    };
+    a.get_entry("Package")
+        .cmp(&b.get_entry("Package"))
+        .then_with(|| version(a).cmp(&version(b)))
+        .then_with(|| {
+            a.get_entry("Architecture")
+                .cmp(&b.get_entry("Architecture"))
+        })
+}
+
+/// Collects the `.deb` files below a directory, recursively.
+fn find_debs(dir: &Path, debs: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let path = entry.path();
+        if path.is_dir() {
+            find_debs(&path, debs)?;
+        } else if path.extension().map_or(false, |e| e == "deb") {
+            debs.push(path);
+        }
+    }
+    Ok(())
+}
+
+impl PackagesIndex {
+    /// Scans a directory below the archive root for `.deb` files and
+    /// creates an index of all of them.
+    pub fn scan(root: &Path, dir: &Path) -> io::Result<PackagesIndex> {
+        let mut debs = vec![];
+        find_debs(dir, &mut debs)?;
+        let mut index = PackagesIndex::default();
+        for deb in debs {
+            let rel = deb
+                .strip_prefix(root)
+                .map_err(|_| invalid("package not below the archive root"))?;
+            let filename = rel
+                .iter()
+                .map(|c| c.to_str().ok_or_else(|| invalid("non-UTF-8 path")))
+                .collect::<io::Result<Vec<_>>>()?
+                .join("/");
+            index.add_deb(&deb, &filename)?;
+        }
+        Ok(index)
+    }
+
+    /// Adds a binary package, given the path of its `.deb` and the
+    /// `Filename` to record, relative to the archive root.
+    pub fn add_deb(
+        &mut self,
+        deb_file: &Path,
+        filename: &str,
+    ) -> io::Result<()> {
+        let contents = fs::read(deb_file)?;
+        let deb = DebFile::from_reader(contents.as_slice())?;
+        let mut para = deb.control()?;
+        for field in ["Package", "Version", "Architecture"] {
+            if !para.has_entry(field) {
+                return Err(invalid(&format!("missing {field} field")));
+            }
+        }
+        para.update_entry("Filename", filename.to_string());
+        para.update_entry("Size", contents.len().to_string());
+        para.update_entry("MD5sum", md5_hex(&contents));
+        let sha256 = Sha256::digest(&contents);
+        para.update_entry("SHA256", format!("{sha256:x}"));
+        self.add_paragraph(para);
+        Ok(())
+    }
+
+    /// Adds a paragraph, keeping the index sorted.
+    pub fn add_paragraph(&mut self, para: ControlParagraph) {
+        let pos = self.paragraphs.partition_point(|p| {
+            compare_paragraphs(p, &para) != Ordering::Greater
+        });
+        self.paragraphs.insert(pos, para);
+    }
+
+    /// Applies the priority, section and maintainer given by override
+    /// files to all packages, as well as extra overrides. Returns the
+    /// names of the packages without an entry in the override file,
+    /// which apt-ftparchive warns about.
+    pub fn apply_overrides(&mut self, overrides: &Overrides) -> Vec<String> {
+        let mut missing: Vec<String> = vec![];
+        for para in &mut self.paragraphs {
+            if !overrides.apply(para) {
+                let package = para.get_entry("Package").unwrap_or_default();
+                if !missing.iter().any(|m| m == package) {
+                    missing.push(package.to_string());
+                }
+            }
+        }
+        missing
+    }
+
+    /// All paragraphs, in order.
+    pub fn paragraphs(&self) -> &Vec<ControlParagraph> {
+        &self.paragraphs
+    }
+
+    /// Writes the index to disk, compressed as given.
+    pub fn to_file(
+        &self,
+        out_file: &Path,
+        compression: Compression,
+    ) -> io::Result<()> {
+        let contents = compression.compress(self.to_string().as_bytes())?;
+        fs::write(out_file, contents)
+    }
+
+    /// Writes the index to `Packages` files in a directory, one per
+    /// compression, returning their paths. The directory is created if
+    /// needed.
+    pub fn to_dir(
+        &self,
+        dir: &Path,
+        compressions: &[Compression],
+    ) -> io::Result<Vec<PathBuf>> {
+        fs::create_dir_all(dir)?;
+        let mut paths = vec![];
+        for compression in compressions {
+            let name = format!("Packages{}", compression.extension());
+            let path = dir.join(name);
+            self.to_file(&path, *compression)?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+}
+
+impl fmt::Display for PackagesIndex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, para) in self.paragraphs.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{para}")?;
+        }
+        Ok(())
+    }
+}
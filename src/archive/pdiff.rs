@@ -0,0 +1,269 @@
+//! Incremental updates of index files
+//!
+//! Archives may offer the changes to index files like `Packages` as a
+//! series of patches in the directory next to them, like
+//! `main/binary-amd64/Packages.diff/`, so clients holding an older
+//! version only need to download the changes. The `Index` file there
+//! lists the digest of the current index file, the digests of older
+//! versions, and the patches leading from each of them towards the
+//! current one. With `X-Patch-Precedence: merged`, each patch leads
+//! straight to the current version, otherwise they have to be applied
+//! in order.
+//!
+//! The patches are ed scripts, as generated by `diff --ed`, with the
+//! changes to the end of a file first, and are compressed with gzip.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::archive::pdiff::{apply_ed_script, PdiffIndex};
+//!
+//! let old = "Package: foo\nVersion: 1.0\n";
+//! let script = "2c\nVersion: 1.1\n.\n";
+//! let new = apply_ed_script(old, script).unwrap();
+//! assert_eq!(new, "Package: foo\nVersion: 1.1\n");
+//! ```
+
+use std::io;
+use std::io::BufRead;
+
+use sha2::{Digest, Sha256};
+
+use super::release::parse_checksums;
+use crate::package::ControlFile;
+
+/// The name of the index of patches within the directory of an index
+/// file, like `Packages.diff/Index`.
+pub const PDIFF_INDEX: &str = "Index";
+
+/// A patch listed in the index of patches.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PdiffPatch {
+    /// the name, like `T-2024-01-01-0200.00-F-2023-12-31-2000.00`
+    pub name: String,
+    /// the size of the version of the index file the patch applies to
+    pub from_size: u64,
+    /// the SHA256 digest of that version
+    pub from_sha256: String,
+    /// the size of the patch, uncompressed
+    pub size: u64,
+    pub sha256: String,
+    /// the size of the compressed patch, `<name>.gz`, if listed
+    pub download_size: Option<u64>,
+    /// the SHA256 digest of the compressed patch, if listed
+    pub download_sha256: Option<String>,
+}
+
+/// A parsed index of patches.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PdiffIndex {
+    /// the size of the current version of the index file
+    pub current_size: u64,
+    /// the SHA256 digest of the current version
+    pub current_sha256: String,
+    /// the patches, from the oldest version to the newest
+    pub patches: Vec<PdiffPatch>,
+    /// whether each patch leads straight to the current version
+    pub merged: bool,
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Parses an address of an ed command, like `12` or `3,5`, into the
+/// first and last line, counting from 1.
+fn parse_address(s: &str) -> Option<(usize, usize)> {
+    match s.split_once(',') {
+        Some((first, last)) => Some((first.parse().ok()?, last.parse().ok()?)),
+        None => {
+            let line = s.parse().ok()?;
+            Some((line, line))
+        }
+    }
+}
+
+/// Applies an ed script, as generated by `diff --ed`, to a text.
+/// Supports the commands to append (`a`), change (`c`) and delete
+/// (`d`) lines, as well as `s/.//`, which diff uses to restore lines
+/// consisting of a single dot.
+///
+/// Fails with `InvalidData` for unsupported commands and addresses
+/// beyond the end of the text.
+pub fn apply_ed_script(text: &str, script: &str) -> io::Result<String> {
+    let mut lines: Vec<String> =
+        text.split_inclusive('\n').map(|l| l.to_string()).collect();
+    let mut commands = script.lines();
+    // the index of the current line, following the last one changed
+    let mut current: usize = 0;
+    while let Some(command) = commands.next() {
+        if command == "s/.//" {
+            match current.checked_sub(1).and_then(|i| lines.get_mut(i)) {
+                Some(line) if line.starts_with('.') => {
+                    line.remove(0);
+                }
+                _ => return Err(invalid(format!("invalid {command}"))),
+            }
+            continue;
+        }
+        let op_len = command.chars().last().map_or(0, |c| c.len_utf8());
+        let (address, op) = command.split_at(command.len() - op_len);
+        let (first, last) = match address {
+            "" => (current, current),
+            address => parse_address(address).ok_or_else(|| {
+                invalid(format!("invalid ed command {command}"))
+            })?,
+        };
+        if first > last || last > lines.len() {
+            return Err(invalid(format!("invalid address in {command}")));
+        }
+        let mut insert_at = match op {
+            // appending after line zero inserts at the start
+            "a" => last,
+            "c" | "d" => {
+                let first = first.max(1);
+                lines.drain(first - 1..last);
+                first - 1
+            }
+            _ => return Err(invalid(format!("unsupported command {command}"))),
+        };
+        if op != "d" {
+            for line in commands.by_ref() {
+                if line == "." {
+                    break;
+                }
+                lines.insert(insert_at, format!("{line}\n"));
+                insert_at += 1;
+            }
+        }
+        current = insert_at;
+    }
+    Ok(lines.concat())
+}
+
+impl PdiffIndex {
+    /// Parses an index of patches from any buffered reader.
+    ///
+    /// Fails with `InvalidData` if it lacks SHA256 digests.
+    pub fn from_reader<R: BufRead>(buf: R) -> io::Result<PdiffIndex> {
+        let cf = ControlFile::from_reader(buf)?;
+        let fields = match cf.get_paragraphs().as_slice() {
+            [para] => para,
+            _ => return Err(invalid("expected a single paragraph".into())),
+        };
+        let current = fields.get_entry("SHA256-Current").ok_or_else(|| {
+            invalid("missing SHA256-Current field".to_string())
+        })?;
+        let (current_sha256, current_size) =
+            match current.split_whitespace().collect::<Vec<&str>>()[..] {
+                [sha256, size] => (
+                    sha256.to_string(),
+                    size.parse().map_err(|_| {
+                        invalid("invalid size in SHA256-Current".to_string())
+                    })?,
+                ),
+                _ => return Err(invalid("invalid SHA256-Current".into())),
+            };
+
+        let table = |key: &str| -> io::Result<Vec<(String, u64, String)>> {
+            parse_checksums(fields.get_entry(key).unwrap_or_default())
+                .map(|line| {
+                    let (sha256, size, name) = line?;
+                    Ok((sha256.to_string(), size, name.to_string()))
+                })
+                .collect()
+        };
+        let history = table("SHA256-History")?;
+        let downloads = table("SHA256-Download")?;
+        let mut patches = vec![];
+        for (sha256, size, name) in table("SHA256-Patches")? {
+            // older versions may be listed without their patches, but
+            // not the other way around
+            let from = history.iter().find(|(_, _, n)| *n == name);
+            let (from_sha256, from_size, _) = from.ok_or_else(|| {
+                invalid(format!("no history entry for patch {name}"))
+            })?;
+            let download_name = format!("{name}.gz");
+            let download =
+                downloads.iter().find(|(_, _, n)| *n == download_name);
+            patches.push(PdiffPatch {
+                from_size: *from_size,
+                from_sha256: from_sha256.clone(),
+                size,
+                sha256,
+                download_size: download.map(|d| d.1),
+                download_sha256: download.map(|d| d.0.clone()),
+                name,
+            });
+        }
+        Ok(PdiffIndex {
+            current_size,
+            current_sha256,
+            patches,
+            merged: fields.get_entry("X-Patch-Precedence") == Some("merged"),
+        })
+    }
+
+    /// Whether data matches the current version of the index file.
+    pub fn is_current(&self, data: &[u8]) -> bool {
+        data.len() as u64 == self.current_size
+            && sha256_hex(data) == self.current_sha256
+    }
+
+    /// The patches to apply, in order, to get from the given version of
+    /// the index file to the current one. Returns `None` if there are
+    /// no patches for the version, so the index file needs to be
+    /// downloaded in full.
+    pub fn patches_for(&self, data: &[u8]) -> Option<Vec<&PdiffPatch>> {
+        if self.is_current(data) {
+            return Some(vec![]);
+        }
+        let sha256 = sha256_hex(data);
+        let size = data.len() as u64;
+        let start = self
+            .patches
+            .iter()
+            .position(|p| p.from_sha256 == sha256 && p.from_size == size)?;
+        match self.merged {
+            true => Some(vec![&self.patches[start]]),
+            false => Some(self.patches[start..].iter().collect()),
+        }
+    }
+
+    /// Applies patches to a version of the index file, as returned by
+    /// `patches_for`, given their uncompressed contents, checking the
+    /// digests of the patches and of the result.
+    ///
+    /// Fails with `InvalidData` on any mismatch.
+    pub fn apply(
+        &self,
+        data: &[u8],
+        patches: &[(&PdiffPatch, &[u8])],
+    ) -> io::Result<Vec<u8>> {
+        let text = std::str::from_utf8(data)
+            .map_err(|_| invalid("index file is not UTF-8".to_string()))?;
+        let mut text = text.to_string();
+        for (patch, script) in patches {
+            if script.len() as u64 != patch.size
+                || sha256_hex(script) != patch.sha256
+            {
+                return Err(invalid(format!(
+                    "hash sum mismatch for patch {}",
+                    patch.name
+                )));
+            }
+            let script = std::str::from_utf8(script).map_err(|_| {
+                invalid(format!("patch {} is not UTF-8", patch.name))
+            })?;
+            text = apply_ed_script(&text, script)?;
+        }
+        if !self.is_current(text.as_bytes()) {
+            return Err(invalid("hash sum mismatch after patching".into()));
+        }
+        Ok(text.into_bytes())
+    }
+}
@@ -0,0 +1,543 @@
+//! Parser and generator for Release files
+//!
+//! The `Release` file at the top of each distribution of an archive,
+//! like `dists/stable/Release`, describes the distribution and lists
+//! the size and checksums of all its index files, so apt can verify
+//! them once the Release file itself is signed.
+//!
+//! With `Acquire-By-Hash: yes`, each index file is also available
+//! under its digest, like `main/binary-amd64/by-hash/SHA256/<digest>`.
+//! Fetching those avoids hash sum mismatches while a mirror is being
+//! updated, provided the archive keeps some older versions around.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::archive::release::ReleaseBuilder;
+//! use chrono::{TimeZone, Utc};
+//!
+//! let mut release = ReleaseBuilder::new().unwrap();
+//! release.set_origin("Example");
+//! release.set_suite("stable");
+//! release.set_codename("bookworm");
+//! release.add_component("main");
+//! release.add_architecture("amd64");
+//! release.set_date(Utc.timestamp_opt(1700000000, 0).unwrap());
+//! release.add_index("main/binary-amd64/Packages", b"");
+//!
+//! let mut text = vec![];
+//! release.write(&mut text).unwrap();
+//! assert!(String::from_utf8(text)
+//!     .unwrap()
+//!     .starts_with("Origin: Example\nSuite: stable\nCodename: bookworm\n\
+//!                   Date: Tue, 14 Nov 2023 22:13:20 UTC\n"));
+//! ```
+
+use std::collections::HashSet;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, TimeZone, Utc};
+use sha2::{Digest, Sha256};
+
+use crate::buildopts::source_date_epoch;
+use crate::md5sums::md5_hex;
+use crate::package::{ControlFile, ControlParagraph};
+
+/// Files at the top of a distribution that are not index files.
+pub(crate) const RELEASE_FILES: &[&str] =
+    &["Release", "InRelease", "Release.gpg"];
+
+/// Name of the directories holding index files by their digest.
+const BY_HASH_DIR: &str = "by-hash";
+
+/// An index file listed in a Release file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ReleaseIndex {
+    /// the path relative to the distribution's directory, like
+    /// `main/binary-amd64/Packages.xz`
+    pub path: String,
+    pub size: u64,
+    /// the MD5 digest, which recent Release files may omit
+    pub md5: Option<String>,
+    pub sha256: String,
+}
+
+/// A parsed Release file.
+#[derive(Debug, Clone)]
+pub struct Release {
+    fields: ControlParagraph,
+    indices: Vec<ReleaseIndex>,
+}
+
+/// Builder for Release files.
+#[derive(Debug, Clone)]
+pub struct ReleaseBuilder {
+    fields: Vec<(&'static str, String)>,
+    components: Vec<String>,
+    architectures: Vec<String>,
+    date: DateTime<Utc>,
+    valid_until: Option<DateTime<Utc>>,
+    acquire_by_hash: bool,
+    indices: Vec<ReleaseIndex>,
+}
+
+/// Formats a date as used in Release files.
+fn format_date(date: &DateTime<Utc>) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S UTC").to_string()
+}
+
+/// Parses a date as used in Release files, which name the UTC time
+/// zone, unlike RFC 2822.
+fn parse_date(s: &str) -> Option<DateTime<Utc>> {
+    let s = s.trim();
+    let s = match s.strip_suffix(" UTC") {
+        Some(s) => format!("{s} +0000"),
+        None => s.to_string(),
+    };
+    DateTime::parse_from_rfc2822(&s)
+        .ok()
+        .map(|d| d.with_timezone(&Utc))
+}
+
+/// The by-hash location of an index file, relative to the
+/// distribution's directory.
+fn by_hash_path(index: &ReleaseIndex) -> String {
+    let name = format!("{BY_HASH_DIR}/SHA256/{}", index.sha256);
+    match index.path.rsplit_once('/') {
+        Some((dir, _)) => format!("{dir}/{name}"),
+        None => name,
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Parses a checksum table of a Release file, yielding the digest,
+/// size and path of each line.
+pub(crate) fn parse_checksums(
+    value: &str,
+) -> impl Iterator<Item = io::Result<(&str, u64, &str)>> {
+    value.lines().filter(|l| !l.trim().is_empty()).map(|l| {
+        let mut parts = l.split_whitespace();
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(digest), Some(size), Some(path), None) => {
+                let size =
+                    size.parse().map_err(|_| invalid("invalid index size"))?;
+                Ok((digest, size, path))
+            }
+            _ => Err(invalid("invalid checksum line")),
+        }
+    })
+}
+
+impl ReleaseIndex {
+    /// Computes size and checksums of the contents of an index file.
+    pub fn new(path: &str, contents: &[u8]) -> ReleaseIndex {
+        ReleaseIndex {
+            path: path.to_string(),
+            size: contents.len() as u64,
+            md5: Some(md5_hex(contents)),
+            sha256: format!("{:x}", Sha256::digest(contents)),
+        }
+    }
+}
+
+impl ReleaseBuilder {
+    /// Creates an empty builder, dated `SOURCE_DATE_EPOCH` if set, or
+    /// the current time otherwise. Fails if `SOURCE_DATE_EPOCH` is set
+    /// but not a valid number of seconds.
+    pub fn new() -> io::Result<ReleaseBuilder> {
+        let date = match source_date_epoch()? {
+            Some(epoch) => i64::try_from(epoch)
+                .ok()
+                .and_then(|t| Utc.timestamp_opt(t, 0).single())
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "SOURCE_DATE_EPOCH out of range",
+                    )
+                })?,
+            None => Utc::now(),
+        };
+        Ok(ReleaseBuilder {
+            fields: vec![],
+            components: vec![],
+            architectures: vec![],
+            date,
+            valid_until: None,
+            acquire_by_hash: false,
+            indices: vec![],
+        })
+    }
+
+    fn set_field(&mut self, key: &'static str, value: &str) {
+        self.fields.retain(|(k, _)| *k != key);
+        self.fields.push((key, value.to_string()));
+    }
+
+    /// Sets the Origin field, naming who provides the archive.
+    pub fn set_origin(&mut self, origin: &str) {
+        self.set_field("Origin", origin);
+    }
+
+    /// Sets the Label field.
+    pub fn set_label(&mut self, label: &str) {
+        self.set_field("Label", label);
+    }
+
+    /// Sets the Suite field, like `stable`.
+    pub fn set_suite(&mut self, suite: &str) {
+        self.set_field("Suite", suite);
+    }
+
+    /// Sets the Version field, like `12.4`.
+    pub fn set_version(&mut self, version: &str) {
+        self.set_field("Version", version);
+    }
+
+    /// Sets the Codename field, like `bookworm`.
+    pub fn set_codename(&mut self, codename: &str) {
+        self.set_field("Codename", codename);
+    }
+
+    /// Sets the Description field.
+    pub fn set_description(&mut self, description: &str) {
+        self.set_field("Description", description);
+    }
+
+    /// Adds a component, like `main`.
+    pub fn add_component(&mut self, component: &str) {
+        if !self.components.iter().any(|c| c == component) {
+            self.components.push(component.to_string());
+        }
+    }
+
+    /// Adds an architecture, like `amd64` or `all`.
+    pub fn add_architecture(&mut self, arch: &str) {
+        if !self.architectures.iter().any(|a| a == arch) {
+            self.architectures.push(arch.to_string());
+        }
+    }
+
+    /// Sets the date the Release file was generated.
+    pub fn set_date(&mut self, date: DateTime<Utc>) {
+        self.date = date;
+    }
+
+    /// Sets the date after which apt considers the Release file
+    /// outdated.
+    pub fn set_valid_until(&mut self, valid_until: Option<DateTime<Utc>>) {
+        self.valid_until = valid_until;
+    }
+
+    /// Sets whether apt should fetch index files by their digest. The
+    /// files need to be placed with `write_by_hash`.
+    pub fn set_acquire_by_hash(&mut self, acquire_by_hash: bool) {
+        self.acquire_by_hash = acquire_by_hash;
+    }
+
+    /// Adds an index file, given its path relative to the
+    /// distribution's directory and its contents, replacing any
+    /// previous one for the same path.
+    pub fn add_index(&mut self, path: &str, contents: &[u8]) {
+        self.indices.retain(|i| i.path != path);
+        self.indices.push(ReleaseIndex::new(path, contents));
+    }
+
+    /// Adds all files below a distribution's directory, like
+    /// `dists/stable`, except for the Release files themselves and
+    /// by-hash directories.
+    pub fn add_index_files(&mut self, dist_dir: &Path) -> io::Result<()> {
+        self.add_index_dir(dist_dir, "")
+    }
+
+    fn add_index_dir(&mut self, dir: &Path, prefix: &str) -> io::Result<()> {
+        let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+        entries.sort_by_key(|e| e.file_name());
+        for entry in entries {
+            let name = entry.file_name().into_string().map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, "non-UTF-8 path")
+            })?;
+            if prefix.is_empty() && RELEASE_FILES.contains(&name.as_str()) {
+                continue;
+            }
+            let path = format!("{prefix}{name}");
+            if entry.path().is_dir() && name == BY_HASH_DIR {
+                continue;
+            } else if entry.path().is_dir() {
+                self.add_index_dir(&entry.path(), &format!("{path}/"))?;
+            } else {
+                self.add_index(&path, &fs::read(entry.path())?);
+            }
+        }
+        Ok(())
+    }
+
+    /// The index files added, sorted by path.
+    pub fn indices(&self) -> Vec<&ReleaseIndex> {
+        let mut indices: Vec<&ReleaseIndex> = self.indices.iter().collect();
+        indices.sort_by(|a, b| a.path.cmp(&b.path));
+        indices
+    }
+
+    /// Writes the Release file. Fails if neither suite nor codename is
+    /// set.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let field = |key| self.fields.iter().find(|(k, _)| *k == key);
+        if field("Suite").is_none() && field("Codename").is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "missing Suite or Codename field",
+            ));
+        }
+        for key in ["Origin", "Label", "Suite", "Version", "Codename"] {
+            if let Some((k, v)) = field(key) {
+                writeln!(writer, "{k}: {v}")?;
+            }
+        }
+        writeln!(writer, "Date: {}", format_date(&self.date))?;
+        if let Some(ref valid_until) = self.valid_until {
+            writeln!(writer, "Valid-Until: {}", format_date(valid_until))?;
+        }
+        if self.acquire_by_hash {
+            writeln!(writer, "Acquire-By-Hash: yes")?;
+        }
+        if !self.architectures.is_empty() {
+            writeln!(
+                writer,
+                "Architectures: {}",
+                self.architectures.join(" ")
+            )?;
+        }
+        if !self.components.is_empty() {
+            writeln!(writer, "Components: {}", self.components.join(" "))?;
+        }
+        if let Some((k, v)) = field("Description") {
+            writeln!(writer, "{k}: {v}")?;
+        }
+        let indices = self.indices();
+        writeln!(writer, "MD5Sum:")?;
+        for i in &indices {
+            let md5 = i.md5.as_deref().unwrap_or_default();
+            writeln!(writer, " {} {:>16} {}", md5, i.size, i.path)?;
+        }
+        writeln!(writer, "SHA256:")?;
+        for i in &indices {
+            writeln!(writer, " {} {:>16} {}", i.sha256, i.size, i.path)?;
+        }
+        writer.flush()
+    }
+
+    /// Writes the Release file to disk.
+    pub fn to_file(&self, out_file: &Path) -> io::Result<()> {
+        self.write(BufWriter::new(File::create(out_file)?))
+    }
+
+    /// Moves each index file below a distribution's directory into the
+    /// by-hash directory next to it and replaces it with a symlink to
+    /// its by-hash file, so each index is stored once. Existing by-hash
+    /// files are kept.
+    ///
+    /// As the index files are links afterwards, newer versions have to
+    /// replace them, like by renaming a new file over them, rather than
+    /// be written in place, which would alter the by-hash file.
+    pub fn write_by_hash(&self, dist_dir: &Path) -> io::Result<()> {
+        for index in &self.indices {
+            let source = dist_dir.join(&index.path);
+            let target = dist_dir.join(by_hash_path(index));
+            let dir = target
+                .parent()
+                .ok_or_else(|| invalid("invalid index path"))?;
+            fs::create_dir_all(dir)?;
+            let link =
+                Path::new(BY_HASH_DIR).join("SHA256").join(&index.sha256);
+            if fs::read_link(&source).map_or(false, |l| l == link) {
+                continue;
+            }
+            if target.exists() {
+                fs::remove_file(&source)?;
+            } else if fs::symlink_metadata(&source)?.file_type().is_symlink() {
+                fs::copy(&source, &target)?;
+                fs::remove_file(&source)?;
+            } else {
+                fs::rename(&source, &target)?;
+            }
+            link_index(&link, &target, &source)?;
+        }
+        Ok(())
+    }
+
+    /// Removes older files from the by-hash directories below a
+    /// distribution's directory, returning their paths. Files of the
+    /// index files added are always kept, as are the `keep` most
+    /// recently modified other ones in each directory, so clients still
+    /// using a previous Release file can fetch them.
+    pub fn prune_by_hash(
+        &self,
+        dist_dir: &Path,
+        keep: usize,
+    ) -> io::Result<Vec<PathBuf>> {
+        let current: HashSet<PathBuf> = self
+            .indices
+            .iter()
+            .map(|i| dist_dir.join(by_hash_path(i)))
+            .collect();
+        let mut removed = vec![];
+        prune_dir(dist_dir, &current, keep, &mut removed)?;
+        Ok(removed)
+    }
+}
+
+/// Links an index file to its by-hash file, given relative to the
+/// index file as `link`. Without symlinks, a hard link is made instead.
+#[cfg(unix)]
+fn link_index(link: &Path, _target: &Path, path: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(link, path)
+}
+
+#[cfg(not(unix))]
+fn link_index(_link: &Path, target: &Path, path: &Path) -> io::Result<()> {
+    fs::hard_link(target, path)
+}
+
+/// Prunes the by-hash directories below a directory, recursively.
+fn prune_dir(
+    dir: &Path,
+    current: &HashSet<PathBuf>,
+    keep: usize,
+    removed: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        } else if entry.file_name() != BY_HASH_DIR {
+            prune_dir(&entry.path(), current, keep, removed)?;
+            continue;
+        }
+        for hash_dir in fs::read_dir(entry.path())? {
+            let mut old = vec![];
+            for file in fs::read_dir(hash_dir?.path())? {
+                let path = file?.path();
+                if !current.contains(&path) {
+                    old.push((fs::symlink_metadata(&path)?.modified()?, path));
+                }
+            }
+            old.sort();
+            let excess = old.len().saturating_sub(keep);
+            for (_, path) in old.into_iter().take(excess) {
+                fs::remove_file(&path)?;
+                removed.push(path);
+            }
+        }
+    }
+    Ok(())
+}
+
+impl Release {
+    /// Reads and parses a Release file from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<Release> {
+        Release::from_reader(BufReader::new(File::open(in_file)?))
+    }
+
+    /// Parses a Release file from any buffered reader.
+    pub fn from_reader<R: BufRead>(buf: R) -> io::Result<Release> {
+        let cf = ControlFile::from_reader(buf)?;
+        let fields = match cf.get_paragraphs().as_slice() {
+            [para] => para.clone(),
+            _ => return Err(invalid("expected a single paragraph")),
+        };
+        let mut indices: Vec<ReleaseIndex> = vec![];
+        if let Some(value) = fields.get_entry("SHA256") {
+            for line in parse_checksums(value) {
+                let (sha256, size, path) = line?;
+                indices.push(ReleaseIndex {
+                    path: path.to_string(),
+                    size,
+                    md5: None,
+                    sha256: sha256.to_string(),
+                });
+            }
+        }
+        if let Some(value) = fields.get_entry("MD5Sum") {
+            for line in parse_checksums(value) {
+                let (md5, _, path) = line?;
+                if let Some(index) = indices.iter_mut().find(|i| i.path == path)
+                {
+                    index.md5 = Some(md5.to_string());
+                }
+            }
+        }
+        Ok(Release { fields, indices })
+    }
+
+    /// Get the value of a field.
+    pub fn get_entry(&self, key: &str) -> Option<&str> {
+        self.fields.get_entry(key)
+    }
+
+    /// The suite, like `stable`.
+    pub fn suite(&self) -> Option<&str> {
+        self.get_entry("Suite")
+    }
+
+    /// The codename, like `bookworm`.
+    pub fn codename(&self) -> Option<&str> {
+        self.get_entry("Codename")
+    }
+
+    /// The components, like `main`.
+    pub fn components(&self) -> Vec<&str> {
+        let value = self.get_entry("Components").unwrap_or_default();
+        value.split_whitespace().collect()
+    }
+
+    /// The architectures.
+    pub fn architectures(&self) -> Vec<&str> {
+        let value = self.get_entry("Architectures").unwrap_or_default();
+        value.split_whitespace().collect()
+    }
+
+    /// The date the Release file was generated.
+    pub fn date(&self) -> Option<DateTime<Utc>> {
+        self.get_entry("Date").and_then(parse_date)
+    }
+
+    /// The date after which the Release file is outdated, if any.
+    pub fn valid_until(&self) -> Option<DateTime<Utc>> {
+        self.get_entry("Valid-Until").and_then(parse_date)
+    }
+
+    /// Whether index files can be fetched by their digest.
+    pub fn acquire_by_hash(&self) -> bool {
+        self.get_entry("Acquire-By-Hash") == Some("yes")
+    }
+
+    /// The index files listed with a SHA256 digest, in order.
+    pub fn indices(&self) -> &Vec<ReleaseIndex> {
+        &self.indices
+    }
+
+    /// Look up an index file by its path relative to the
+    /// distribution's directory.
+    pub fn index(&self, path: &str) -> Option<&ReleaseIndex> {
+        self.indices.iter().find(|i| i.path == path)
+    }
+
+    /// The path to fetch an index file from, relative to the
+    /// distribution's directory. That is its by-hash location if
+    /// supported, or the path itself otherwise. Returns `None` for
+    /// files not listed.
+    pub fn fetch_path(&self, path: &str) -> Option<String> {
+        let index = self.index(path)?;
+        match self.acquire_by_hash() {
+            true => Some(by_hash_path(index)),
+            false => Some(index.path.clone()),
+        }
+    }
+}
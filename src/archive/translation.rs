@@ -0,0 +1,210 @@
+//! Parser for Translation indices
+//!
+//! Archives ship the descriptions of binary packages in separate
+//! `i18n/Translation-<lang>` indices per component, like
+//! `main/i18n/Translation-de`, with one paragraph per description
+//! holding the package name, the MD5 digest of the original English
+//! description and its translation in a `Description-<lang>` field.
+//! Entries of a Packages index refer to their description with a
+//! `Description-md5` field, or the digest is computed from their
+//! `Description` field.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::archive::translation::{
+//!     description_md5, TranslatedDescription, Translation,
+//! };
+//! use debian::package::ControlFile;
+//!
+//! let packages = ControlFile::from_reader(
+//!     &b"Package: foo\nDescription: tool\n frobnicates\n"[..],
+//! )
+//! .unwrap();
+//! let mut translation = Translation::new("de");
+//! translation.add(TranslatedDescription {
+//!     package: "foo".to_string(),
+//!     md5: description_md5("tool\n frobnicates"),
+//!     description: "Werkzeug\n frobniziert".to_string(),
+//! });
+//! let merged = translation.merge(&packages);
+//! let foo = &merged.get_paragraphs()[0];
+//! let description = foo.get_entry("Description-de").unwrap();
+//! assert_eq!(description, "Werkzeug\n frobniziert");
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::io::BufRead;
+use std::path::Path;
+
+use crate::md5sums::md5_hex;
+use crate::package::{ControlFile, ControlParagraph};
+
+/// The prefix of the file names of Translation indices.
+const TRANSLATION_PREFIX: &str = "Translation-";
+
+/// A single translated description.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TranslatedDescription {
+    pub package: String,
+    /// the MD5 digest of the original description
+    pub md5: String,
+    /// the translation, with the synopsis on the first line
+    pub description: String,
+}
+
+/// A parsed Translation index of a single language.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Translation {
+    /// the language code, like `de` or `pt_BR`
+    pub lang: String,
+    descriptions: Vec<TranslatedDescription>,
+    by_md5: HashMap<String, usize>,
+}
+
+/// The MD5 digest of the value of a `Description` field, as used for
+/// the `Description-md5` field.
+pub fn description_md5(description: &str) -> String {
+    md5_hex(format!("{description}\n").as_bytes())
+}
+
+impl Translation {
+    /// Creates an empty Translation index for a language.
+    pub fn new(lang: &str) -> Translation {
+        Translation {
+            lang: lang.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// The name of the field holding translated descriptions, like
+    /// `Description-de`.
+    pub fn field(&self) -> String {
+        format!("Description-{}", self.lang)
+    }
+
+    /// Reads a Translation index from disk, taking the language from
+    /// the file name, like `Translation-de`.
+    pub fn from_file(in_file: &Path) -> io::Result<Translation> {
+        let lang = in_file
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_prefix(TRANSLATION_PREFIX))
+            .filter(|l| !l.is_empty())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "not a Translation index",
+                )
+            })?;
+        Translation::from_control_file(lang, &ControlFile::from_file(in_file)?)
+    }
+
+    /// Parses a Translation index of a language from any buffered
+    /// reader.
+    pub fn from_reader<R: BufRead>(
+        lang: &str,
+        buf: R,
+    ) -> io::Result<Translation> {
+        Translation::from_control_file(lang, &ControlFile::from_reader(buf)?)
+    }
+
+    /// Collects the translated descriptions from the paragraphs of a
+    /// Translation index, like one fetched from an archive.
+    ///
+    /// Fails with `InvalidData` if a paragraph lacks any of the
+    /// fields.
+    pub fn from_control_file(
+        lang: &str,
+        cf: &ControlFile,
+    ) -> io::Result<Translation> {
+        let mut translation = Translation::new(lang);
+        let field = translation.field();
+        for para in cf.get_paragraphs() {
+            let get = |key: &str| {
+                para.get_entry(key).map(str::to_string).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("missing {key} field"),
+                    )
+                })
+            };
+            translation.add(TranslatedDescription {
+                package: get("Package")?,
+                md5: get("Description-md5")?,
+                description: get(&field)?,
+            });
+        }
+        Ok(translation)
+    }
+
+    /// Adds a translated description, replacing any for the same
+    /// original description.
+    pub fn add(&mut self, description: TranslatedDescription) {
+        match self.by_md5.get(&description.md5) {
+            Some(&i) => self.descriptions[i] = description,
+            None => {
+                self.by_md5
+                    .insert(description.md5.clone(), self.descriptions.len());
+                self.descriptions.push(description);
+            }
+        }
+    }
+
+    /// All translated descriptions, in order.
+    pub fn descriptions(&self) -> &[TranslatedDescription] {
+        &self.descriptions
+    }
+
+    /// Look up a translation by the MD5 digest of the original
+    /// description.
+    pub fn get(&self, md5: &str) -> Option<&TranslatedDescription> {
+        self.by_md5.get(md5).map(|&i| &self.descriptions[i])
+    }
+
+    /// Look up the translation of the description of an entry of a
+    /// Packages index, by its `Description-md5` field or else by the
+    /// digest of its `Description` field.
+    pub fn translate(
+        &self,
+        para: &ControlParagraph,
+    ) -> Option<&TranslatedDescription> {
+        let md5 = match para.get_entry("Description-md5") {
+            Some(md5) => md5.to_string(),
+            None => description_md5(para.get_entry("Description")?),
+        };
+        self.get(&md5)
+    }
+
+    /// Copies the entries of a Packages index, adding a
+    /// `Description-<lang>` field to those with a translation.
+    pub fn merge(&self, packages: &ControlFile) -> ControlFile {
+        let field = self.field();
+        let mut result = ControlFile::default();
+        for para in packages.get_paragraphs() {
+            let mut para = para.clone();
+            if let Some(t) = self.translate(&para) {
+                para.update_entry(&field, t.description.clone());
+            }
+            result.add_paragraph(para);
+        }
+        result
+    }
+}
+
+impl fmt::Display for Translation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let field = self.field();
+        for (i, d) in self.descriptions.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "Package: {}", d.package)?;
+            writeln!(f, "Description-md5: {}", d.md5)?;
+            writeln!(f, "{field}: {}", d.description)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+//! References to bug reports
+//!
+//! Changelog entries and commit messages refer to bugs they fix as in
+//! `Closes: #123, #456` for bugs in the Debian bug tracking system, or
+//! `LP: #98765` for Launchpad. dpkg collects the Debian bugs of a
+//! changelog entry into the `Closes` field of the `.changes` file, as
+//! numbers separated by spaces, and Launchpad does the same for its own
+//! bug references in `Launchpad-Bugs-Fixed`.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::bugs::{format_closes, parse_bug_refs, BugRef};
+//!
+//! let text = "  * Fix crash on startup. Closes: #123, #456 (LP: #98765)";
+//! let bugs = parse_bug_refs(text);
+//! assert_eq!(
+//!     bugs,
+//!     [BugRef::Debian(123), BugRef::Debian(456), BugRef::Launchpad(98765)]
+//! );
+//! assert_eq!(format_closes(&[123, 456]), "Closes: #123, #456");
+//! assert_eq!(bugs[2].url(), "https://bugs.launchpad.net/bugs/98765");
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use regex::Regex;
+
+/// A reference to a bug report.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub enum BugRef {
+    /// a bug in the Debian bug tracking system
+    Debian(u32),
+    /// a bug on Launchpad
+    Launchpad(u32),
+}
+
+/// The pattern dpkg uses to find bugs closed in changelog entries.
+const CLOSES_PATTERN: &str =
+    r"(?i)closes:\s*(?:bug)?\#?\s?\d+(?:,\s*(?:bug)?\#?\s?\d+)*";
+
+/// The pattern of references to Launchpad bugs.
+const LAUNCHPAD_PATTERN: &str = r"(?i)lp:\s+\#\d+(?:,\s*\#\d+)*";
+
+fn numbers(s: &str) -> impl Iterator<Item = u32> + '_ {
+    s.split(|c: char| !c.is_ascii_digit())
+        .filter_map(|n| n.parse().ok())
+}
+
+/// Collects the numbers of all references matching a pattern, in
+/// order and without duplicates, with their positions.
+fn find_refs(text: &str, pattern: &str) -> Vec<(usize, u32)> {
+    let re = Regex::new(pattern).unwrap();
+    let mut result: Vec<(usize, u32)> = vec![];
+    for m in re.find_iter(text) {
+        // skip the "lp" or "closes" prefix, which has no digits
+        for n in numbers(m.as_str()) {
+            if !result.iter().any(|&(_, b)| b == n) {
+                result.push((m.start(), n));
+            }
+        }
+    }
+    result
+}
+
+/// Finds the Debian bugs closed in a text, like a changelog entry, by
+/// references like `Closes: #123, #456`, in order and without
+/// duplicates.
+pub fn parse_closes(text: &str) -> Vec<u32> {
+    find_refs(text, CLOSES_PATTERN)
+        .into_iter()
+        .map(|(_, n)| n)
+        .collect()
+}
+
+/// Finds the Launchpad bugs referred to in a text, by references like
+/// `LP: #98765`, in order and without duplicates.
+pub fn parse_launchpad(text: &str) -> Vec<u32> {
+    find_refs(text, LAUNCHPAD_PATTERN)
+        .into_iter()
+        .map(|(_, n)| n)
+        .collect()
+}
+
+/// Finds all bug references in a text, in the order they appear.
+pub fn parse_bug_refs(text: &str) -> Vec<BugRef> {
+    let mut refs: Vec<(usize, BugRef)> = find_refs(text, CLOSES_PATTERN)
+        .into_iter()
+        .map(|(pos, n)| (pos, BugRef::Debian(n)))
+        .chain(
+            find_refs(text, LAUNCHPAD_PATTERN)
+                .into_iter()
+                .map(|(pos, n)| (pos, BugRef::Launchpad(n))),
+        )
+        .collect();
+    refs.sort_by_key(|&(pos, _)| pos);
+    refs.into_iter().map(|(_, b)| b).collect()
+}
+
+fn format_refs(prefix: &str, bugs: &[u32]) -> String {
+    let bugs: Vec<String> = bugs.iter().map(|b| format!("#{b}")).collect();
+    format!("{prefix}: {}", bugs.join(", "))
+}
+
+/// Formats Debian bugs as closed in a changelog entry, like
+/// `Closes: #123, #456`.
+pub fn format_closes(bugs: &[u32]) -> String {
+    format_refs("Closes", bugs)
+}
+
+/// Formats references to Launchpad bugs, like `LP: #123, #456`.
+pub fn format_launchpad(bugs: &[u32]) -> String {
+    format_refs("LP", bugs)
+}
+
+/// Parses a field listing bug numbers separated by whitespace, like the
+/// `Closes` field of a `.changes` file.
+pub fn parse_bug_field(value: &str) -> Result<Vec<u32>, &'static str> {
+    value
+        .split_whitespace()
+        .map(|n| n.parse().map_err(|_| "invalid bug number"))
+        .collect()
+}
+
+/// Formats bug numbers for a field like `Closes`, sorted and without
+/// duplicates as dpkg does.
+pub fn format_bug_field(bugs: &[u32]) -> String {
+    let mut bugs = bugs.to_vec();
+    bugs.sort_unstable();
+    bugs.dedup();
+    let bugs: Vec<String> = bugs.iter().map(|b| b.to_string()).collect();
+    bugs.join(" ")
+}
+
+impl BugRef {
+    /// The number of the bug in its tracker.
+    pub fn number(&self) -> u32 {
+        match *self {
+            BugRef::Debian(n) | BugRef::Launchpad(n) => n,
+        }
+    }
+
+    /// The web page of the bug report.
+    pub fn url(&self) -> String {
+        match *self {
+            BugRef::Debian(n) => format!("https://bugs.debian.org/{n}"),
+            BugRef::Launchpad(n) => {
+                format!("https://bugs.launchpad.net/bugs/{n}")
+            }
+        }
+    }
+}
+
+impl fmt::Display for BugRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BugRef::Debian(n) => write!(f, "#{n}"),
+            BugRef::Launchpad(n) => write!(f, "LP: #{n}"),
+        }
+    }
+}
+
+impl FromStr for BugRef {
+    type Err = &'static str;
+
+    /// Parses a single reference, like `#123`, `123` or `LP: #123`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (launchpad, number) = match s.get(..3) {
+            Some(p) if p.eq_ignore_ascii_case("lp:") => (true, &s[3..]),
+            _ => (false, s),
+        };
+        let number = number.trim_start().trim_start_matches('#');
+        let number = number.parse().map_err(|_| "invalid bug number")?;
+        Ok(match launchpad {
+            true => BugRef::Launchpad(number),
+            false => BugRef::Debian(number),
+        })
+    }
+}
@@ -0,0 +1,197 @@
+//! Satisfiability checks of build dependencies
+//!
+//! In the spirit of dose-builddebcheck, this checks whether the build
+//! dependencies of a source package, given in the `Build-Depends`,
+//! `Build-Depends-Arch` and `Build-Depends-Indep` fields of its
+//! `debian/control` paragraph or `.dsc` file, can be installed from a
+//! set of Packages indices for a given architecture and set of build
+//! profiles.
+//!
+//! Build dependencies are first reduced to the alternatives applying to
+//! the architecture and profiles, dropping dependencies without any.
+//! Each remaining alternative is checked on its own, and all
+//! dependencies together. `Build-Conflicts` are not considered, and the
+//! build-essential packages have to be part of the indices like any
+//! other package, but are not required implicitly.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::builddep::builddep_check;
+//! use debian::package::ControlFile;
+//! use debian::resolver::Resolver;
+//!
+//! let source = ControlFile::from_reader(
+//!     "Source: foo\nBuild-Depends: debhelper-compat (= 13), \
+//!      libfoo-dev [linux-any], check <!nocheck>\n"
+//!         .as_bytes(),
+//! )
+//! .unwrap();
+//! let packages = ControlFile::from_reader(
+//!     "Package: debhelper\nVersion: 13.11\n\
+//!      Provides: debhelper-compat (= 13)\n"
+//!         .as_bytes(),
+//! )
+//! .unwrap();
+//! let mut resolver = Resolver::default();
+//! resolver.add_index(&packages).unwrap();
+//!
+//! let source = &source.get_paragraphs()[0];
+//! let report =
+//!     builddep_check(source, &resolver, "hurd-i386", &["nocheck"]).unwrap();
+//! assert!(report.is_installable());
+//! let report = builddep_check(source, &resolver, "amd64", &[]).unwrap();
+//! assert!(!report.is_installable());
+//! ```
+
+use crate::arch::arch_matches;
+use crate::package::{
+    parse_dep_list, ControlParagraph, Dependency, SingleDependency,
+};
+use crate::resolver::{InstallationSet, ResolveError, Resolver};
+
+/// The fields holding build dependencies, in order.
+pub const BUILD_DEPENDS_FIELDS: [&str; 3] =
+    ["Build-Depends", "Build-Depends-Arch", "Build-Depends-Indep"];
+
+/// The result of checking a single build dependency.
+#[derive(Debug, Clone)]
+pub struct BuildDepStatus {
+    /// the dependency, reduced to the alternatives applying
+    pub dependency: Dependency,
+    /// for each alternative, why it cannot be installed, if it cannot
+    pub alternatives: Vec<(SingleDependency, Option<ResolveError>)>,
+}
+
+/// The result of checking all build dependencies of a source package.
+#[derive(Debug, Clone)]
+pub struct BuildDepReport<'a> {
+    /// the build dependencies applying, in order
+    pub dependencies: Vec<BuildDepStatus>,
+    /// the packages to install, or why the build dependencies cannot be
+    /// installed together
+    pub result: Result<InstallationSet<'a>, ResolveError>,
+}
+
+/// Evaluates an architecture restriction list, like `amd64 i386` or
+/// `!hurd-any !kfreebsd-any`.
+pub fn arch_restriction_matches(restriction: &str, arch: &str) -> bool {
+    let mut terms = restriction.split_whitespace().peekable();
+    match terms.peek() {
+        Some(t) if t.starts_with('!') => {
+            terms.all(|t| !arch_matches(arch, t.trim_start_matches('!')))
+        }
+        _ => terms.any(|t| arch_matches(arch, t)),
+    }
+}
+
+/// Evaluates build profile restriction lists, like `!nocheck !cross`
+/// for `<!nocheck !cross>`, given the active build profiles. All terms
+/// of a list have to hold, while any of the lists has to hold. Without
+/// any lists, there is no restriction.
+pub fn profile_restriction_matches<S: AsRef<str>>(
+    lists: &[S],
+    profiles: &[&str],
+) -> bool {
+    lists.is_empty()
+        || lists.iter().any(|list| {
+            list.as_ref().split_whitespace().all(|term| {
+                match term.strip_prefix('!') {
+                    Some(profile) => !profiles.contains(&profile),
+                    None => profiles.contains(&term),
+                }
+            })
+        })
+}
+
+/// Reduces a list of build dependencies to the alternatives applying to
+/// an architecture and set of build profiles, dropping dependencies
+/// without any alternatives left.
+pub fn reduce_build_depends(
+    deps: Vec<Dependency>,
+    arch: &str,
+    profiles: &[&str],
+) -> Vec<Dependency> {
+    deps.into_iter()
+        .filter_map(|dep| {
+            let alternatives: Vec<SingleDependency> =
+                dep.alternatives
+                    .into_iter()
+                    .filter(|alt| {
+                        !alt.package.is_empty()
+                            && alt.arch.as_ref().map_or(true, |r| {
+                                arch_restriction_matches(r, arch)
+                            })
+                            && profile_restriction_matches(
+                                &alt.conditions(),
+                                profiles,
+                            )
+                    })
+                    .collect();
+            match alternatives.is_empty() {
+                true => None,
+                false => Some(Dependency { alternatives }),
+            }
+        })
+        .collect()
+}
+
+impl BuildDepStatus {
+    /// Whether any alternative can be installed.
+    pub fn is_satisfiable(&self) -> bool {
+        self.alternatives.iter().any(|(_, error)| error.is_none())
+    }
+}
+
+impl<'a> BuildDepReport<'a> {
+    /// Whether all build dependencies can be installed together.
+    pub fn is_installable(&self) -> bool {
+        self.result.is_ok()
+    }
+
+    /// The build dependencies none of whose alternatives can be
+    /// installed.
+    pub fn unsatisfiable(&self) -> impl Iterator<Item = &BuildDepStatus> {
+        self.dependencies.iter().filter(|d| !d.is_satisfiable())
+    }
+}
+
+/// Checks the build dependencies of a source package, given by its
+/// paragraph from `debian/control` or a `.dsc` file, against the
+/// packages known to a resolver, for the given host architecture and
+/// active build profiles.
+pub fn builddep_check<'a>(
+    source: &ControlParagraph,
+    resolver: &'a Resolver,
+    arch: &str,
+    profiles: &[&str],
+) -> Result<BuildDepReport<'a>, &'static str> {
+    let mut deps = vec![];
+    for field in BUILD_DEPENDS_FIELDS {
+        if let Some(value) = source.get_entry(field) {
+            deps.extend(parse_dep_list(value)?);
+        }
+    }
+    let deps = reduce_build_depends(deps, arch, profiles);
+
+    let dependencies = deps
+        .iter()
+        .map(|dep| BuildDepStatus {
+            dependency: dep.clone(),
+            alternatives: dep
+                .alternatives
+                .iter()
+                .map(|alt| {
+                    let single = Dependency {
+                        alternatives: vec![alt.clone()],
+                    };
+                    (alt.clone(), resolver.resolve(&[single]).err())
+                })
+                .collect(),
+        })
+        .collect();
+    Ok(BuildDepReport {
+        dependencies,
+        result: resolver.resolve(&deps),
+    })
+}
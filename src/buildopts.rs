@@ -0,0 +1,281 @@
+//! Build options and build profiles
+//!
+//! The `DEB_BUILD_OPTIONS` environment variable tweaks how packages are
+//! built, like `nocheck` to skip tests or `parallel=4` to build with
+//! four jobs. Some options, like `hardening=+all,-pie`, enable or
+//! disable features of an area. `DEB_BUILD_PROFILES` lists the active
+//! build profiles, which select the build dependencies and binary
+//! packages applying by restriction formulas like `<!nocheck>`.
+//! `SOURCE_DATE_EPOCH` gives the time to use for timestamps in
+//! reproducible builds.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::buildopts::{BuildOptions, BuildProfiles};
+//!
+//! let options: BuildOptions =
+//!     "nocheck parallel=4 hardening=+all,-pie".parse().unwrap();
+//! assert!(options.nocheck());
+//! assert_eq!(options.parallel(), Some(4));
+//! assert_eq!(options.feature("hardening", "pie"), Some(false));
+//! assert_eq!(options.feature("hardening", "bindnow"), Some(true));
+//!
+//! let profiles: BuildProfiles = "nocheck cross".parse().unwrap();
+//! assert!(profiles.matches("<!nodoc> <stage1>"));
+//! assert!(!profiles.matches("<!nocheck>"));
+//! ```
+
+use std::convert::Infallible;
+use std::env;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+use crate::builddep::profile_restriction_matches;
+
+/// The environment variable holding build options.
+pub const DEB_BUILD_OPTIONS: &str = "DEB_BUILD_OPTIONS";
+
+/// The environment variable holding the active build profiles.
+pub const DEB_BUILD_PROFILES: &str = "DEB_BUILD_PROFILES";
+
+/// The environment variable holding the time for timestamps of
+/// reproducible builds, in seconds since the epoch.
+pub const SOURCE_DATE_EPOCH: &str = "SOURCE_DATE_EPOCH";
+
+/// Parsed `DEB_BUILD_OPTIONS`, in the order given.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct BuildOptions {
+    options: Vec<(String, Option<String>)>,
+}
+
+/// Parsed `DEB_BUILD_PROFILES`.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct BuildProfiles {
+    profiles: Vec<String>,
+}
+
+fn read_env(name: &str) -> String {
+    env::var(name).unwrap_or_default()
+}
+
+/// Reads `SOURCE_DATE_EPOCH` from the environment, if set. Fails if it
+/// is not a number of seconds, rather than silently building with
+/// another timestamp.
+pub fn source_date_epoch() -> io::Result<Option<u64>> {
+    match env::var(SOURCE_DATE_EPOCH) {
+        Ok(epoch) => epoch.trim().parse().map(Some).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid {SOURCE_DATE_EPOCH} '{epoch}'"),
+            )
+        }),
+        Err(_) => Ok(None),
+    }
+}
+
+impl BuildOptions {
+    /// Parses build options separated by whitespace. Options given
+    /// again override earlier ones.
+    pub fn parse(s: &str) -> BuildOptions {
+        let mut options = BuildOptions::default();
+        for option in s.split_whitespace() {
+            match option.split_once('=') {
+                Some((name, value)) => options.set(name, Some(value)),
+                None => options.set(option, None),
+            }
+        }
+        options
+    }
+
+    /// Reads the build options from the environment.
+    pub fn from_env() -> BuildOptions {
+        BuildOptions::parse(&read_env(DEB_BUILD_OPTIONS))
+    }
+
+    /// Whether an option is set, with or without a value.
+    pub fn has(&self, name: &str) -> bool {
+        self.options.iter().any(|(n, _)| n == name)
+    }
+
+    /// The value of an option, if set with one.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(n, _)| n == name)
+            .and_then(|(_, v)| v.as_deref())
+    }
+
+    /// Sets an option, replacing any previous value.
+    pub fn set(&mut self, name: &str, value: Option<&str>) {
+        let value = value.map(|v| v.to_string());
+        match self.options.iter_mut().find(|(n, _)| n == name) {
+            Some(option) => option.1 = value,
+            None => self.options.push((name.to_string(), value)),
+        }
+    }
+
+    /// Removes an option.
+    pub fn unset(&mut self, name: &str) {
+        self.options.retain(|(n, _)| n != name);
+    }
+
+    /// All options with their values, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Option<&str>)> {
+        self.options.iter().map(|(n, v)| (n.as_str(), v.as_deref()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.options.is_empty()
+    }
+
+    /// Whether tests should be skipped.
+    pub fn nocheck(&self) -> bool {
+        self.has("nocheck")
+    }
+
+    /// Whether binaries should not be stripped.
+    pub fn nostrip(&self) -> bool {
+        self.has("nostrip")
+    }
+
+    /// Whether building documentation should be skipped.
+    pub fn nodoc(&self) -> bool {
+        self.has("nodoc")
+    }
+
+    /// Whether to compile without optimization.
+    pub fn noopt(&self) -> bool {
+        self.has("noopt")
+    }
+
+    /// Whether build logs should be less verbose.
+    pub fn terse(&self) -> bool {
+        self.has("terse")
+    }
+
+    /// The number of jobs to build with, if given and valid.
+    pub fn parallel(&self) -> Option<usize> {
+        self.get("parallel")
+            .and_then(|v| v.parse().ok())
+            .filter(|&n| n > 0)
+    }
+
+    /// The features of an area, like `hardening` or `reproducible`,
+    /// given as `+feature` to enable or `-feature` to disable them,
+    /// separated by commas. Features without a sign are enabled. The
+    /// pseudo-feature `all` stands for all features of the area.
+    pub fn features(&self, area: &str) -> Vec<(&str, bool)> {
+        self.get(area)
+            .unwrap_or("")
+            .split(',')
+            .filter(|f| !f.is_empty())
+            .map(|f| match f.strip_prefix('-') {
+                Some(f) => (f, false),
+                None => (f.trim_start_matches('+'), true),
+            })
+            .collect()
+    }
+
+    /// Whether a feature of an area is enabled or disabled, taking
+    /// `all` into account, or None if neither.
+    pub fn feature(&self, area: &str, feature: &str) -> Option<bool> {
+        self.features(area)
+            .iter()
+            .rev()
+            .find(|(f, _)| *f == feature || *f == "all")
+            .map(|&(_, enabled)| enabled)
+    }
+}
+
+impl fmt::Display for BuildOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let options: Vec<String> = self
+            .options
+            .iter()
+            .map(|(name, value)| match value {
+                Some(value) => format!("{name}={value}"),
+                None => name.clone(),
+            })
+            .collect();
+        write!(f, "{}", options.join(" "))
+    }
+}
+
+impl FromStr for BuildOptions {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(BuildOptions::parse(s))
+    }
+}
+
+impl BuildProfiles {
+    /// Parses build profiles separated by whitespace.
+    pub fn parse(s: &str) -> BuildProfiles {
+        let mut profiles = BuildProfiles::default();
+        for profile in s.split_whitespace() {
+            profiles.add(profile);
+        }
+        profiles
+    }
+
+    /// Reads the active build profiles from the environment.
+    pub fn from_env() -> BuildProfiles {
+        BuildProfiles::parse(&read_env(DEB_BUILD_PROFILES))
+    }
+
+    /// Activates a profile.
+    pub fn add(&mut self, profile: &str) {
+        if !self.contains(profile) {
+            self.profiles.push(profile.to_string());
+        }
+    }
+
+    /// Deactivates a profile.
+    pub fn remove(&mut self, profile: &str) {
+        self.profiles.retain(|p| p != profile);
+    }
+
+    pub fn contains(&self, profile: &str) -> bool {
+        self.profiles.iter().any(|p| p == profile)
+    }
+
+    /// The active profiles, in order, as expected by
+    /// `builddep::builddep_check`.
+    pub fn names(&self) -> Vec<&str> {
+        self.profiles.iter().map(|p| p.as_str()).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.profiles.is_empty()
+    }
+
+    /// Evaluates a restriction formula, like `<!nocheck> <stage1 cross>`,
+    /// as given in `Build-Depends` or the `Build-Profiles` field of
+    /// binary packages. Any of the lists in angle brackets has to hold,
+    /// and all terms within a list. An empty formula always holds.
+    pub fn matches(&self, formula: &str) -> bool {
+        let lists: Vec<&str> = formula
+            .split('<')
+            .map(|l| l.trim().trim_end_matches('>'))
+            .filter(|l| !l.is_empty())
+            .collect();
+        profile_restriction_matches(&lists, &self.names())
+    }
+}
+
+impl fmt::Display for BuildProfiles {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.profiles.join(" "))
+    }
+}
+
+impl FromStr for BuildProfiles {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(BuildProfiles::parse(s))
+    }
+}
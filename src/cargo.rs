@@ -0,0 +1,664 @@
+//! Debian packaging of Rust crates
+//!
+//! Maps the metadata of a crate, as given in its `Cargo.toml`, to a
+//! draft `debian/control` following the conventions of the Debian Rust
+//! team, as implemented by debcargo:
+//!
+//! - the source package of crate `foo_bar` is `rust-foo-bar`,
+//! - its code is shipped in `librust-foo-bar-dev`, which also provides
+//!   versioned names like `librust-foo-bar-1-dev`, `-1.2-dev` and
+//!   `-1.2.3-dev`,
+//! - features pulling in additional dependencies get a package of
+//!   their own, like `librust-foo-bar+serde-dev`, while the others are
+//!   provided by the main package,
+//! - dependencies on other crates are expressed on the package of the
+//!   semver compatible version range, like `librust-serde-1+derive-dev`,
+//!   with semver versions mangled to Debian versions, so `1.0.0-rc.1`
+//!   becomes `1.0.0~rc.1`.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::cargo::CrateMetadata;
+//!
+//! let manifest = r#"
+//! [package]
+//! name = "foo_bar"
+//! version = "1.2.3"
+//!
+//! [dependencies]
+//! serde = { version = "1.0.100", optional = true }
+//! "#;
+//! let metadata = CrateMetadata::from_manifest(manifest).unwrap();
+//! assert_eq!(metadata.source_name(), "rust-foo-bar");
+//! assert_eq!(
+//!     metadata.binary_name(Some("serde")),
+//!     "librust-foo-bar+serde-dev"
+//! );
+//!
+//! let control = metadata.control("Jane Doe <jane@example.org>");
+//! let feature = &control.get_paragraphs()[2];
+//! assert_eq!(
+//!     feature.get_entry("Depends"),
+//!     Some(concat!(
+//!         "librust-foo-bar-dev (= ${binary:Version}),\n",
+//!         " librust-serde-1+default-dev (>= 1.0.100-~~),\n",
+//!         " ${misc:Depends}"
+//!     ))
+//! );
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use semver::{Comparator, Op, VersionReq};
+
+use crate::package::{join_multiline, ControlFile, ControlParagraph};
+use crate::scaffold::{DEBHELPER_COMPAT, STANDARDS_VERSION};
+use crate::Version;
+
+/// A dependency of a crate, for building or using it.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CrateDependency {
+    /// the name the dependency is referred to by in the crate
+    pub name: String,
+    /// the name of the crate depended on, which differs from `name`
+    /// for renamed dependencies
+    pub package: String,
+    pub req: VersionReq,
+    pub optional: bool,
+    pub default_features: bool,
+    pub features: Vec<String>,
+}
+
+/// The metadata of a crate relevant to packaging it.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CrateMetadata {
+    pub name: String,
+    pub version: semver::Version,
+    pub description: Option<String>,
+    pub homepage: Option<String>,
+    /// the features of the crate, as declared, with what they enable
+    pub features: BTreeMap<String, Vec<String>>,
+    /// the normal and build dependencies, for all targets
+    pub dependencies: Vec<CrateDependency>,
+}
+
+/// What a feature enables.
+#[derive(Debug, PartialEq, Eq)]
+enum FeatureItem<'a> {
+    /// another feature of the crate
+    Feature(&'a str),
+    /// a dependency, with one of its features
+    Dependency(&'a CrateDependency, Option<&'a str>),
+    /// a feature of an optional dependency, if enabled otherwise
+    Weak,
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Converts the name of a crate or feature into the form used in
+/// package names, lowercase and with dashes instead of underscores.
+pub fn debian_name(name: &str) -> String {
+    name.to_lowercase().replace('_', "-")
+}
+
+/// Formats the version of a comparator, mangled like versions of
+/// crates are.
+fn comparator_version(c: &Comparator) -> String {
+    let mut s = c.major.to_string();
+    for part in [c.minor, c.patch].into_iter().flatten() {
+        s += &format!(".{part}");
+    }
+    if !c.pre.is_empty() {
+        s += &format!("~{}", c.pre.as_str().replace('-', "."));
+    }
+    s
+}
+
+/// The lowest version above those matching the version of a comparator
+/// exactly, like `1.3` for `=1.2`.
+fn next_version(c: &Comparator) -> String {
+    match (c.minor, c.patch) {
+        (Some(minor), Some(patch)) => {
+            format!("{}.{minor}.{}", c.major, patch + 1)
+        }
+        (Some(minor), None) => format!("{}.{}", c.major, minor + 1),
+        _ => (c.major + 1).to_string(),
+    }
+}
+
+/// The semver compatible version range of a comparator, like `1` for
+/// `1.2.3` and `0.3` for `0.3.1`.
+fn compat_version(c: &Comparator) -> String {
+    match (c.major, c.minor, c.patch) {
+        (0, Some(0), Some(patch)) => format!("0.0.{patch}"),
+        (0, Some(minor), _) => format!("0.{minor}"),
+        (major, _, _) => major.to_string(),
+    }
+}
+
+/// The lowest version above the semver compatible range of a
+/// comparator, like `2` for `1.2.3` and `0.4` for `0.3.1`.
+fn compat_upper(c: &Comparator) -> String {
+    match (c.major, c.minor, c.patch) {
+        (0, Some(0), Some(patch)) => format!("0.0.{}", patch + 1),
+        (0, Some(minor), _) => format!("0.{}", minor + 1),
+        (major, _, _) => (major + 1).to_string(),
+    }
+}
+
+/// The bounds a comparator puts on Debian versions, as relations and
+/// upstream versions.
+fn comparator_bounds(c: &Comparator) -> Vec<(&'static str, String)> {
+    let version = comparator_version(c);
+    match c.op {
+        Op::Exact | Op::Wildcard => {
+            vec![(">=", version), ("<<", next_version(c))]
+        }
+        Op::Greater => vec![(">=", next_version(c))],
+        Op::GreaterEq => vec![(">=", version)],
+        Op::Less => vec![("<<", version)],
+        Op::LessEq => vec![("<<", next_version(c))],
+        Op::Tilde => {
+            let upper = match c.minor {
+                Some(minor) => format!("{}.{}", c.major, minor + 1),
+                None => (c.major + 1).to_string(),
+            };
+            vec![(">=", version), ("<<", upper)]
+        }
+        _ => vec![(">=", version), ("<<", compat_upper(c))],
+    }
+}
+
+impl CrateDependency {
+    /// The semver compatible version range the dependency is on, if
+    /// its requirement starts with a caret, tilde, exact or wildcard
+    /// comparator.
+    pub fn compat_version(&self) -> Option<String> {
+        let c = self.req.comparators.first()?;
+        match c.op {
+            Op::Greater | Op::GreaterEq | Op::Less | Op::LessEq => None,
+            _ => Some(compat_version(c)),
+        }
+    }
+
+    /// The relations on the packages of the dependency with its
+    /// features and any given additional ones, in the form used in
+    /// control files, like `librust-serde-1+derive-dev (>= 1.0.100-~~)`.
+    pub fn relations(&self, features: &[&str]) -> Vec<String> {
+        let compat = self.compat_version();
+        let mut bounds = vec![];
+        for c in &self.req.comparators {
+            bounds.extend(comparator_bounds(c));
+        }
+        // bounds implied by the package name are left out
+        if let Some(first) = self.req.comparators.first() {
+            if let Some(ref compat) = compat {
+                let upper = compat_upper(first);
+                bounds.retain(|(rel, version)| match *rel {
+                    ">=" => version != compat,
+                    _ => *version != upper,
+                });
+            }
+        }
+
+        let mut all_features = vec![];
+        if self.default_features {
+            all_features.push("default");
+        }
+        all_features.extend(self.features.iter().map(|f| f.as_str()));
+        all_features.extend(features);
+        let mut names = vec![];
+        for feature in all_features {
+            let name = format!("+{}", debian_name(feature));
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        if names.is_empty() {
+            names.push(String::new());
+        }
+
+        let base = match compat {
+            Some(compat) => format!("{}-{compat}", debian_name(&self.package)),
+            None => debian_name(&self.package),
+        };
+        let mut result = vec![];
+        for name in names {
+            let package = format!("librust-{base}{name}-dev");
+            if bounds.is_empty() {
+                result.push(package.clone());
+            }
+            for (rel, version) in &bounds {
+                result.push(format!("{package} ({rel} {version}-~~)"));
+            }
+        }
+        result
+    }
+
+    fn from_toml(
+        name: &str,
+        value: &toml::Value,
+    ) -> io::Result<CrateDependency> {
+        let invalid =
+            || invalid_data(format!("invalid dependency specification {name}"));
+        let (req, table) = match value {
+            toml::Value::String(req) => (Some(req.as_str()), None),
+            toml::Value::Table(table) => {
+                let req = match table.get("version") {
+                    Some(v) => Some(v.as_str().ok_or_else(invalid)?),
+                    None => None,
+                };
+                (req, Some(table))
+            }
+            _ => return Err(invalid()),
+        };
+        let req = match req {
+            Some(req) => VersionReq::parse(req).map_err(|e| {
+                invalid_data(format!("invalid version of {name}: {e}"))
+            })?,
+            None => VersionReq::STAR,
+        };
+        let get_bool = |key: &str, default: bool| -> io::Result<bool> {
+            match table.and_then(|t| t.get(key)) {
+                Some(v) => v.as_bool().ok_or_else(invalid),
+                None => Ok(default),
+            }
+        };
+        let default_features = match table {
+            Some(t) if t.contains_key("default_features") => {
+                get_bool("default_features", true)?
+            }
+            _ => get_bool("default-features", true)?,
+        };
+        let features = match table.and_then(|t| t.get("features")) {
+            Some(toml::Value::Array(features)) => features
+                .iter()
+                .map(|f| f.as_str().map(|f| f.to_string()).ok_or_else(invalid))
+                .collect::<io::Result<Vec<String>>>()?,
+            Some(_) => return Err(invalid()),
+            None => vec![],
+        };
+        let package = match table.and_then(|t| t.get("package")) {
+            Some(p) => p.as_str().ok_or_else(invalid)?,
+            None => name,
+        };
+        Ok(CrateDependency {
+            name: name.to_string(),
+            package: package.to_string(),
+            req,
+            optional: get_bool("optional", false)?,
+            default_features,
+            features,
+        })
+    }
+}
+
+/// Adds the dependencies of a table like `[dependencies]`.
+fn add_dependencies(
+    deps: &mut Vec<CrateDependency>,
+    table: Option<&toml::Value>,
+) -> io::Result<()> {
+    let table = match table {
+        Some(toml::Value::Table(table)) => table,
+        Some(_) => return Err(invalid_data("invalid dependencies".into())),
+        None => return Ok(()),
+    };
+    for (name, value) in table {
+        let dep = CrateDependency::from_toml(name, value)?;
+        // the same crate may be a normal and a build dependency
+        if !deps.contains(&dep) {
+            deps.push(dep);
+        }
+    }
+    Ok(())
+}
+
+impl CrateMetadata {
+    /// Parses the metadata from the contents of a `Cargo.toml`. Normal
+    /// and build dependencies are included, also those for specific
+    /// targets, while development dependencies are not.
+    pub fn from_manifest(manifest: &str) -> io::Result<CrateMetadata> {
+        let doc: toml::Table = manifest
+            .parse()
+            .map_err(|e: toml::de::Error| invalid_data(e.to_string()))?;
+        let package = doc
+            .get("package")
+            .and_then(|p| p.as_table())
+            .ok_or_else(|| invalid_data("missing package section".into()))?;
+        let get_str = |key: &str| {
+            package
+                .get(key)
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string())
+        };
+        let name = get_str("name")
+            .ok_or_else(|| invalid_data("missing crate name".into()))?;
+        let version = get_str("version")
+            .ok_or_else(|| invalid_data("missing crate version".into()))?;
+        let version = semver::Version::parse(&version)
+            .map_err(|e| invalid_data(format!("invalid crate version: {e}")))?;
+
+        let mut dependencies = vec![];
+        let mut tables = vec![&doc];
+        if let Some(targets) = doc.get("target").and_then(|t| t.as_table()) {
+            tables.extend(targets.values().filter_map(|t| t.as_table()));
+        }
+        for table in tables {
+            add_dependencies(&mut dependencies, table.get("dependencies"))?;
+            add_dependencies(
+                &mut dependencies,
+                table.get("build-dependencies"),
+            )?;
+        }
+
+        let mut features = BTreeMap::new();
+        if let Some(table) = doc.get("features") {
+            let invalid = || invalid_data("invalid features".into());
+            for (name, value) in table.as_table().ok_or_else(invalid)? {
+                let items = value
+                    .as_array()
+                    .ok_or_else(invalid)?
+                    .iter()
+                    .map(|v| v.as_str().map(|s| s.to_string()))
+                    .collect::<Option<Vec<String>>>()
+                    .ok_or_else(invalid)?;
+                features.insert(name.clone(), items);
+            }
+        }
+
+        let metadata = CrateMetadata {
+            name,
+            version,
+            description: get_str("description"),
+            homepage: get_str("homepage").or_else(|| get_str("repository")),
+            features,
+            dependencies,
+        };
+        let features = metadata.all_features();
+        for (feature, items) in &features {
+            for item in items {
+                if metadata.feature_item(&features, feature, item).is_none() {
+                    return Err(invalid_data(format!(
+                        "feature {feature} enables unknown {item}"
+                    )));
+                }
+            }
+        }
+        Ok(metadata)
+    }
+
+    /// Reads and parses a `Cargo.toml` from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<CrateMetadata> {
+        CrateMetadata::from_manifest(&fs::read_to_string(in_file)?)
+    }
+
+    /// The name of the source package, like `rust-foo-bar`.
+    pub fn source_name(&self) -> String {
+        format!("rust-{}", debian_name(&self.name))
+    }
+
+    /// The name of the binary package of the crate or one of its
+    /// features, like `librust-foo-bar-dev` or
+    /// `librust-foo-bar+serde-dev`.
+    pub fn binary_name(&self, feature: Option<&str>) -> String {
+        self.versioned_name(None, feature)
+    }
+
+    fn versioned_name(
+        &self,
+        version: Option<&str>,
+        feature: Option<&str>,
+    ) -> String {
+        let mut name = format!("librust-{}", debian_name(&self.name));
+        if let Some(version) = version {
+            name += &format!("-{version}");
+        }
+        if let Some(feature) = feature {
+            name += &format!("+{}", debian_name(feature));
+        }
+        name + "-dev"
+    }
+
+    /// The upstream version of the package.
+    pub fn upstream_version(&self) -> Version {
+        Version::from(self.version.clone())
+    }
+
+    /// The features of the crate, including the implicit features of
+    /// optional dependencies not referred to by `dep:`.
+    pub fn all_features(&self) -> BTreeMap<String, Vec<String>> {
+        let mut result = self.features.clone();
+        for dep in self.dependencies.iter().filter(|d| d.optional) {
+            let explicit = format!("dep:{}", dep.name);
+            let referred =
+                self.features.values().flatten().any(|i| *i == explicit);
+            if !referred && !result.contains_key(&dep.name) {
+                result.insert(dep.name.clone(), vec![explicit]);
+            }
+        }
+        result
+    }
+
+    fn dependency(&self, name: &str) -> Option<&CrateDependency> {
+        self.dependencies.iter().find(|d| d.name == name)
+    }
+
+    /// Interprets an item of the list of what a feature enables, like
+    /// `dep:foo`, `foo/bar` or `foo?/bar`.
+    fn feature_item<'a>(
+        &'a self,
+        features: &BTreeMap<String, Vec<String>>,
+        feature: &str,
+        item: &'a str,
+    ) -> Option<FeatureItem<'a>> {
+        if let Some(dep) = item.strip_prefix("dep:") {
+            let dep = self.dependency(dep).filter(|d| d.optional)?;
+            return Some(FeatureItem::Dependency(dep, None));
+        }
+        if let Some((dep, dep_feature)) = item.split_once('/') {
+            let (dep, weak) = match dep.strip_suffix('?') {
+                Some(dep) => (dep, true),
+                None => (dep, false),
+            };
+            let dep = self.dependency(dep)?;
+            return match weak && dep.optional {
+                true => Some(FeatureItem::Weak),
+                false => Some(FeatureItem::Dependency(dep, Some(dep_feature))),
+            };
+        }
+        if item != feature && features.contains_key(item) {
+            return Some(FeatureItem::Feature(item));
+        }
+        let dep = self.dependency(item).filter(|d| d.optional)?;
+        Some(FeatureItem::Dependency(dep, None))
+    }
+
+    /// The features that need no dependencies besides those of the
+    /// crate itself, and are thus provided by its main package.
+    pub fn provided_features(&self) -> BTreeSet<String> {
+        let features = self.all_features();
+        let mut result = BTreeSet::new();
+        loop {
+            let before = result.len();
+            for (feature, items) in &features {
+                let trivial = items.iter().all(|item| {
+                    match self.feature_item(&features, feature, item) {
+                        Some(FeatureItem::Feature(f)) => result.contains(f),
+                        Some(FeatureItem::Weak) => true,
+                        _ => false,
+                    }
+                });
+                if trivial {
+                    result.insert(feature.clone());
+                }
+            }
+            if result.len() == before {
+                return result;
+            }
+        }
+    }
+
+    /// The versioned names provided by the package of the crate or one
+    /// of its features.
+    fn versioned_names(&self, feature: Option<&str>) -> Vec<String> {
+        let v = &self.version;
+        [
+            v.major.to_string(),
+            format!("{}.{}", v.major, v.minor),
+            format!("{}.{}.{}", v.major, v.minor, v.patch),
+        ]
+        .iter()
+        .map(|version| self.versioned_name(Some(version), feature))
+        .collect()
+    }
+
+    fn synopsis(&self) -> String {
+        let first = self
+            .description
+            .as_deref()
+            .and_then(|d| d.lines().map(|l| l.trim()).find(|l| !l.is_empty()));
+        match first {
+            Some(line) => line.trim_end_matches('.').to_string(),
+            None => format!("Rust crate \"{}\"", self.name),
+        }
+    }
+
+    /// The relations of the main package on the dependencies of the
+    /// crate.
+    fn main_relations(&self) -> Vec<String> {
+        let mut result = vec![];
+        for dep in self.dependencies.iter().filter(|d| !d.optional) {
+            push_unique(&mut result, dep.relations(&[]));
+        }
+        result
+    }
+
+    /// Generates a draft `debian/control` with the given maintainer.
+    pub fn control(&self, maintainer: &str) -> ControlFile {
+        let mut cf = ControlFile::default();
+        let main_relations = self.main_relations();
+
+        let mut source = ControlParagraph::default();
+        source.add_entry("Source", self.source_name());
+        source.add_entry("Section", "rust".to_string());
+        source.add_entry("Priority", "optional".to_string());
+        let mut build_depends = vec![
+            format!("debhelper-compat (= {DEBHELPER_COMPAT})"),
+            "dh-sequence-cargo".to_string(),
+            "cargo:native <!nocheck>".to_string(),
+            "rustc:native <!nocheck>".to_string(),
+            "libstd-rust-dev <!nocheck>".to_string(),
+        ];
+        build_depends
+            .extend(main_relations.iter().map(|r| format!("{r} <!nocheck>")));
+        source.add_entry("Build-Depends", build_depends.join(",\n "));
+        source.add_entry("Maintainer", maintainer.to_string());
+        source.add_entry("Standards-Version", STANDARDS_VERSION.to_string());
+        if let Some(ref homepage) = self.homepage {
+            source.add_entry("Homepage", homepage.clone());
+        }
+        source.add_entry("X-Cargo-Crate", self.name.clone());
+        source.add_entry("Rules-Requires-Root", "no".to_string());
+        cf.add_paragraph(source);
+
+        let synopsis = self.synopsis();
+        let provided = self.provided_features();
+        let mut provides = self.versioned_names(None);
+        for feature in &provided {
+            provides.push(self.binary_name(Some(feature)));
+            provides.extend(self.versioned_names(Some(feature)));
+        }
+        let mut depends = main_relations;
+        depends.push("${misc:Depends}".to_string());
+        let description = join_multiline(
+            &format!("{synopsis} - Rust source code"),
+            &[format!(
+                "Source code for Debianized Rust crate \"{}\"",
+                self.name
+            )],
+        );
+        cf.add_paragraph(self.binary_paragraph(
+            None,
+            depends,
+            provides,
+            description,
+        ));
+
+        let main =
+            format!("{} (= ${{binary:Version}})", self.binary_name(None));
+        let features = self.all_features();
+        for (feature, items) in &features {
+            if provided.contains(feature) {
+                continue;
+            }
+            let mut depends = vec![main.clone()];
+            for item in items {
+                match self.feature_item(&features, feature, item) {
+                    Some(FeatureItem::Feature(f)) if !provided.contains(f) => {
+                        let name = self.binary_name(Some(f));
+                        depends.push(format!("{name} (= ${{binary:Version}})"));
+                    }
+                    Some(FeatureItem::Dependency(dep, dep_feature)) => {
+                        let extra: Vec<&str> =
+                            dep_feature.into_iter().collect();
+                        push_unique(&mut depends, dep.relations(&extra));
+                    }
+                    _ => {}
+                }
+            }
+            depends.push("${misc:Depends}".to_string());
+            let description = join_multiline(
+                &format!("{synopsis} - feature \"{feature}\""),
+                &[format!(
+                    "This metapackage enables feature \"{feature}\" for the \
+                     Rust {} crate, by pulling in any additional \
+                     dependencies needed by that feature.",
+                    self.name
+                )],
+            );
+            let provides = self.versioned_names(Some(feature));
+            cf.add_paragraph(self.binary_paragraph(
+                Some(feature),
+                depends,
+                provides,
+                description,
+            ));
+        }
+        cf
+    }
+
+    fn binary_paragraph(
+        &self,
+        feature: Option<&str>,
+        depends: Vec<String>,
+        provides: Vec<String>,
+        description: String,
+    ) -> ControlParagraph {
+        let mut para = ControlParagraph::default();
+        para.add_entry("Package", self.binary_name(feature));
+        para.add_entry("Architecture", "any".to_string());
+        para.add_entry("Multi-Arch", "same".to_string());
+        para.add_entry("Depends", depends.join(",\n "));
+        let provides: Vec<String> = provides
+            .iter()
+            .map(|p| format!("{p} (= ${{binary:Version}})"))
+            .collect();
+        para.add_entry("Provides", provides.join(",\n "));
+        para.add_entry("Description", description);
+        para
+    }
+}
+
+fn push_unique(list: &mut Vec<String>, items: Vec<String>) {
+    for item in items {
+        if !list.contains(&item) {
+            list.push(item);
+        }
+    }
+}
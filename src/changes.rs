@@ -0,0 +1,529 @@
+//! Parser and generator for `.changes` files
+//!
+//! An upload to the archive is described by a `.changes` file, listing
+//! the files uploaded together with their sizes, checksums and the
+//! section and priority of the packages they belong to, as well as the
+//! changelog entry of the version uploaded. Like dpkg-genchanges, the
+//! builder collects that information from the changelog entry, the
+//! `debian/control` file and the `.dsc` and `.deb` files built.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use debian::changes::ChangesBuilder;
+//! use debian::package::{Changelog, ControlFile};
+//! use std::path::Path;
+//!
+//! let changelog = Changelog::from_file(Path::new("debian/changelog"))?;
+//! let control = ControlFile::from_file(Path::new("debian/control"))?;
+//! let mut changes = ChangesBuilder::new(&changelog.entries()[0], &control)?;
+//! changes.add_dsc(Path::new("../foo_1.2-1.dsc"), true)?;
+//! changes.to_file(Path::new("../foo_1.2-1_source.changes"))?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufWriter, Write};
+use std::path::Path;
+
+use chrono::{DateTime, FixedOffset};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::bugs::{format_bug_field, parse_closes};
+use crate::deb822::Deb822Reader;
+use crate::filename::{ArtifactKind, ArtifactName};
+use crate::md5sums::md5_hex;
+use crate::package::{
+    format_date, split_multiline, ChangelogEntry, ControlFile, ControlParagraph,
+};
+
+/// The format of the `.changes` files generated.
+pub const CHANGES_FORMAT: &str = "1.8";
+
+/// A file listed in a `.changes` file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ChangesFile {
+    /// the file name, without any directory
+    pub name: String,
+    pub size: u64,
+    pub md5: String,
+    pub sha1: String,
+    pub sha256: String,
+    /// the section, like `utils`, or `-` if unknown
+    pub section: String,
+    /// the priority, like `optional`, or `-` if unknown
+    pub priority: String,
+}
+
+/// A parsed `.changes` file.
+#[derive(Debug, Clone)]
+pub struct Changes {
+    fields: ControlParagraph,
+    files: Vec<ChangesFile>,
+    signed: bool,
+}
+
+/// A binary package included in an upload.
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Binary {
+    package: String,
+    synopsis: String,
+}
+
+/// Builder for `.changes` files.
+#[derive(Debug, Clone)]
+pub struct ChangesBuilder {
+    source: String,
+    version: String,
+    distribution: String,
+    urgency: String,
+    maintainer: String,
+    changed_by: String,
+    date: DateTime<FixedOffset>,
+    changes: String,
+    closes: Vec<u32>,
+    control: Vec<ControlParagraph>,
+    binaries: Vec<Binary>,
+    architectures: Vec<String>,
+    files: Vec<ChangesFile>,
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Parses the lines of a checksum field, yielding the digest, size and
+/// name of each file.
+fn parse_checksums(value: &str) -> io::Result<Vec<(&str, u64, &str)>> {
+    let mut result = vec![];
+    for line in value.lines().filter(|l| !l.trim().is_empty()) {
+        match line.split_whitespace().collect::<Vec<_>>()[..] {
+            [digest, size, name] => {
+                let size = size
+                    .parse()
+                    .map_err(|_| invalid(format!("invalid size of {name}")))?;
+                result.push((digest, size, name));
+            }
+            _ => return Err(invalid(format!("invalid checksum line {line}"))),
+        }
+    }
+    Ok(result)
+}
+
+fn file_name(path: &Path) -> io::Result<String> {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .map(|n| n.to_string())
+        .ok_or_else(|| invalid(format!("invalid file name {}", path.display())))
+}
+
+impl ChangesFile {
+    /// Computes size and checksums of the contents of a file.
+    pub fn new(
+        name: &str,
+        contents: &[u8],
+        section: &str,
+        priority: &str,
+    ) -> ChangesFile {
+        ChangesFile {
+            name: name.to_string(),
+            size: contents.len() as u64,
+            md5: md5_hex(contents),
+            sha1: format!("{:x}", Sha1::digest(contents)),
+            sha256: format!("{:x}", Sha256::digest(contents)),
+            section: section.to_string(),
+            priority: priority.to_string(),
+        }
+    }
+}
+
+impl Changes {
+    /// Reads and parses a `.changes` file from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<Changes> {
+        Changes::from_reader(io::BufReader::new(File::open(in_file)?))
+    }
+
+    /// Parses a `.changes` file, possibly signed, from any buffered
+    /// reader. The signature is not verified.
+    pub fn from_reader<R: BufRead>(buf: R) -> io::Result<Changes> {
+        let mut reader = Deb822Reader::new(buf);
+        let paragraphs = reader.by_ref().collect::<io::Result<Vec<_>>>()?;
+        let fields = match paragraphs.as_slice() {
+            [para] => ControlParagraph::try_from(para)
+                .map_err(|e| invalid(e.to_string()))?,
+            _ => {
+                return Err(invalid("expected a single paragraph".to_string()))
+            }
+        };
+        let listed = fields
+            .get_entry("Files")
+            .ok_or_else(|| invalid("missing Files field".to_string()))?;
+        let mut files = vec![];
+        for line in listed.lines().filter(|l| !l.trim().is_empty()) {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let (md5, size, section, priority, name) = match parts[..] {
+                [md5, size, section, priority, name] => {
+                    (md5, size, section, priority, name)
+                }
+                _ => return Err(invalid(format!("invalid Files line {line}"))),
+            };
+            files.push(ChangesFile {
+                name: name.to_string(),
+                size: size
+                    .parse()
+                    .map_err(|_| invalid(format!("invalid size of {name}")))?,
+                md5: md5.to_string(),
+                sha1: String::new(),
+                sha256: String::new(),
+                section: section.to_string(),
+                priority: priority.to_string(),
+            });
+        }
+        for field in ["Checksums-Sha1", "Checksums-Sha256"] {
+            let value = fields.get_entry(field).unwrap_or_default();
+            for (digest, size, name) in parse_checksums(value)? {
+                let file = files
+                    .iter_mut()
+                    .find(|f| f.name == name && f.size == size)
+                    .ok_or_else(|| {
+                        invalid(format!("{name} is missing in Files"))
+                    })?;
+                match field {
+                    "Checksums-Sha1" => file.sha1 = digest.to_string(),
+                    _ => file.sha256 = digest.to_string(),
+                }
+            }
+        }
+        Ok(Changes {
+            fields,
+            files,
+            signed: reader.is_signed(),
+        })
+    }
+
+    /// Get the value of a field.
+    pub fn get_entry(&self, key: &str) -> Option<&str> {
+        self.fields.get_entry(key)
+    }
+
+    /// The name of the source package.
+    pub fn source(&self) -> Option<&str> {
+        self.get_entry("Source")
+    }
+
+    /// The version uploaded.
+    pub fn version(&self) -> Option<&str> {
+        self.get_entry("Version")
+    }
+
+    /// The distribution targeted, like `unstable`.
+    pub fn distribution(&self) -> Option<&str> {
+        self.get_entry("Distribution")
+    }
+
+    /// The architectures uploaded, including `source` for source
+    /// packages.
+    pub fn architectures(&self) -> Vec<&str> {
+        self.get_entry("Architecture")
+            .unwrap_or_default()
+            .split_whitespace()
+            .collect()
+    }
+
+    /// Whether the file is clearsigned.
+    pub fn is_signed(&self) -> bool {
+        self.signed
+    }
+
+    /// The files listed, in order.
+    pub fn files(&self) -> &Vec<ChangesFile> {
+        &self.files
+    }
+
+    /// Checks that all files listed are present in a directory, with
+    /// the sizes and checksums given.
+    pub fn verify_files(&self, dir: &Path) -> io::Result<()> {
+        for file in &self.files {
+            let contents = fs::read(dir.join(&file.name))?;
+            let actual = ChangesFile::new(&file.name, &contents, "-", "-");
+            if actual.size != file.size
+                || actual.md5 != file.md5
+                || (!file.sha1.is_empty() && actual.sha1 != file.sha1)
+                || (!file.sha256.is_empty() && actual.sha256 != file.sha256)
+            {
+                return Err(invalid(format!(
+                    "checksum mismatch for {}",
+                    file.name
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ChangesBuilder {
+    /// Creates a builder for an upload of the version described by a
+    /// changelog entry, given the `debian/control` file of the package.
+    /// Fails if the control file lacks the source paragraph or its
+    /// Maintainer field.
+    pub fn new(
+        entry: &ChangelogEntry,
+        control: &ControlFile,
+    ) -> io::Result<ChangesBuilder> {
+        let control = control.get_paragraphs().clone();
+        let maintainer = control
+            .first()
+            .filter(|p| p.has_entry("Source"))
+            .ok_or_else(|| invalid("missing source paragraph".to_string()))?
+            .get_entry("Maintainer")
+            .ok_or_else(|| invalid("missing Maintainer field".to_string()))?
+            .to_string();
+        Ok(ChangesBuilder {
+            source: entry.package().to_string(),
+            version: entry.version().to_string(),
+            distribution: entry.distributions().join(" "),
+            urgency: entry.urgency().to_string(),
+            maintainer,
+            changed_by: format!(
+                "{} <{}>",
+                entry.maintainer_name(),
+                entry.maintainer_email()
+            ),
+            date: *entry.timestamp(),
+            changes: format!(
+                "{} ({}) {}; urgency={}\n\n{}",
+                entry.package(),
+                entry.version(),
+                entry.distributions().join(" "),
+                entry.urgency(),
+                entry.detail()
+            ),
+            closes: parse_closes(entry.detail()),
+            control,
+            binaries: vec![],
+            architectures: vec![],
+            files: vec![],
+        })
+    }
+
+    fn source_paragraph(&self) -> &ControlParagraph {
+        &self.control[0]
+    }
+
+    fn add_architecture(&mut self, arch: &str) {
+        if !self.architectures.iter().any(|a| a == arch) {
+            self.architectures.push(arch.to_string());
+        }
+    }
+
+    fn push_file(&mut self, file: ChangesFile) {
+        match self.files.iter_mut().find(|f| f.name == file.name) {
+            Some(f) => *f = file,
+            None => self.files.push(file),
+        }
+    }
+
+    /// Adds a file with the given section and priority, replacing any
+    /// previous one of the same name.
+    pub fn add_file(
+        &mut self,
+        path: &Path,
+        section: &str,
+        priority: &str,
+    ) -> io::Result<()> {
+        let name = file_name(path)?;
+        let contents = fs::read(path)?;
+        self.push_file(ChangesFile::new(&name, &contents, section, priority));
+        Ok(())
+    }
+
+    /// Adds a source package, given its `.dsc` file, together with the
+    /// files it lists, which need to be next to it. The orig tarballs
+    /// and their signatures are only included if `include_orig` is
+    /// set, as needed for the first upload of an upstream version.
+    ///
+    /// The files listed are checked against their size and MD5 digest
+    /// in the `.dsc`.
+    pub fn add_dsc(
+        &mut self,
+        dsc: &Path,
+        include_orig: bool,
+    ) -> io::Result<()> {
+        let cf = ControlFile::from_file(dsc)?;
+        let para = match cf.get_paragraphs().as_slice() {
+            [para] => para,
+            _ => return Err(invalid(format!("invalid {}", dsc.display()))),
+        };
+        let section = self.source_paragraph().get_entry("Section");
+        let section = section.unwrap_or("-").to_string();
+        let priority = self.source_paragraph().get_entry("Priority");
+        let priority = priority.unwrap_or("-").to_string();
+        self.add_file(dsc, &section, &priority)?;
+        let dir = dsc.parent().unwrap_or_else(|| Path::new(""));
+        let listed = para.get_entry("Files").unwrap_or_default();
+        for (md5, size, name) in parse_checksums(listed)? {
+            let is_orig = matches!(
+                ArtifactName::parse(name).map(|n| n.kind),
+                Ok(ArtifactKind::OrigTarball { .. })
+            );
+            if is_orig && !include_orig {
+                continue;
+            }
+            self.add_file(&dir.join(name), &section, &priority)?;
+            let added = self.files.iter().find(|f| f.name == name).unwrap();
+            if added.md5 != md5 || added.size != size {
+                return Err(invalid(format!("checksum mismatch for {name}")));
+            }
+        }
+        self.add_architecture("source");
+        Ok(())
+    }
+
+    /// Adds a binary package, given its `.deb` file. Its section and
+    /// priority are taken from the package, falling back to those of
+    /// the paragraphs in `debian/control`.
+    #[cfg(feature = "deb")]
+    pub fn add_deb(&mut self, deb: &Path) -> io::Result<()> {
+        let contents = fs::read(deb)?;
+        let para =
+            crate::deb::DebFile::from_reader(contents.as_slice())?.control()?;
+        let package = para
+            .get_entry("Package")
+            .ok_or_else(|| invalid("missing Package field".to_string()))?;
+        let arch = para
+            .get_entry("Architecture")
+            .ok_or_else(|| invalid("missing Architecture field".to_string()))?;
+        let lookup = |field: &str| {
+            para.get_entry(field)
+                .or_else(|| {
+                    self.control[1..]
+                        .iter()
+                        .find(|p| p.get_entry("Package") == Some(package))
+                        .and_then(|p| p.get_entry(field))
+                })
+                .or_else(|| self.source_paragraph().get_entry(field))
+                .unwrap_or("-")
+                .to_string()
+        };
+        let (section, priority) = (lookup("Section"), lookup("Priority"));
+        let name = file_name(deb)?;
+        self.push_file(ChangesFile::new(&name, &contents, &section, &priority));
+        let synopsis =
+            split_multiline(para.get_entry("Description").unwrap_or(""))
+                .0
+                .to_string();
+        let binary = Binary {
+            package: package.to_string(),
+            synopsis,
+        };
+        if !self.binaries.contains(&binary) {
+            self.binaries.push(binary);
+        }
+        self.add_architecture(arch);
+        Ok(())
+    }
+
+    /// Sets the distribution targeted, overriding the one of the
+    /// changelog entry.
+    pub fn set_distribution(&mut self, distribution: &str) {
+        self.distribution = distribution.to_string();
+    }
+
+    /// Sets the date of the upload, which defaults to the one of the
+    /// changelog entry.
+    pub fn set_date(&mut self, date: DateTime<FixedOffset>) {
+        self.date = date;
+    }
+
+    /// The files added, in order.
+    pub fn files(&self) -> &Vec<ChangesFile> {
+        &self.files
+    }
+
+    /// The binary packages described, which are those added or, for
+    /// source-only uploads, all packages in `debian/control`.
+    fn binaries(&self) -> Vec<Binary> {
+        if !self.binaries.is_empty() {
+            return self.binaries.clone();
+        }
+        self.control[1..]
+            .iter()
+            .filter_map(|p| {
+                Some(Binary {
+                    package: p.get_entry("Package")?.to_string(),
+                    synopsis: split_multiline(
+                        p.get_entry("Description").unwrap_or(""),
+                    )
+                    .0
+                    .to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// Writes the `.changes` file. Fails if no files were added.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        if self.files.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no files to upload",
+            ));
+        }
+        let binaries = self.binaries();
+        writeln!(writer, "Format: {CHANGES_FORMAT}")?;
+        writeln!(writer, "Date: {}", format_date(&self.date))?;
+        writeln!(writer, "Source: {}", self.source)?;
+        if !binaries.is_empty() {
+            let names: Vec<&str> =
+                binaries.iter().map(|b| b.package.as_str()).collect();
+            writeln!(writer, "Binary: {}", names.join(" "))?;
+        }
+        writeln!(writer, "Architecture: {}", self.architectures.join(" "))?;
+        writeln!(writer, "Version: {}", self.version)?;
+        writeln!(writer, "Distribution: {}", self.distribution)?;
+        writeln!(writer, "Urgency: {}", self.urgency)?;
+        writeln!(writer, "Maintainer: {}", self.maintainer)?;
+        writeln!(writer, "Changed-By: {}", self.changed_by)?;
+        if !binaries.is_empty() {
+            writeln!(writer, "Description:")?;
+            for b in &binaries {
+                writeln!(writer, " {:<10} - {}", b.package, b.synopsis)?;
+            }
+        }
+        if !self.closes.is_empty() {
+            writeln!(writer, "Closes: {}", format_bug_field(&self.closes))?;
+        }
+        writeln!(writer, "Changes:")?;
+        for line in self.changes.lines() {
+            match line.trim_end() {
+                "" => writeln!(writer, " .")?,
+                line => writeln!(writer, " {line}")?,
+            }
+        }
+        writeln!(writer, "Checksums-Sha1:")?;
+        for f in &self.files {
+            writeln!(writer, " {} {} {}", f.sha1, f.size, f.name)?;
+        }
+        writeln!(writer, "Checksums-Sha256:")?;
+        for f in &self.files {
+            writeln!(writer, " {} {} {}", f.sha256, f.size, f.name)?;
+        }
+        writeln!(writer, "Files:")?;
+        for f in &self.files {
+            writeln!(
+                writer,
+                " {} {} {} {} {}",
+                f.md5, f.size, f.section, f.priority, f.name
+            )?;
+        }
+        writer.flush()
+    }
+
+    /// Writes the `.changes` file to disk.
+    pub fn to_file(&self, out_file: &Path) -> io::Result<()> {
+        self.write(BufWriter::new(File::create(out_file)?))
+    }
+}
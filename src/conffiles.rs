@@ -0,0 +1,182 @@
+//! Parser and writer for conffiles control files
+//!
+//! The `conffiles` control file of a binary package lists the
+//! configuration files dpkg preserves local changes to. Since dpkg
+//! 1.20.1, an entry may be prefixed with the `remove-on-upgrade` flag,
+//! telling dpkg to remove a conffile no longer shipped on upgrade.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::conffiles::Conffiles;
+//!
+//! let old: Conffiles = "/etc/foo.conf\n/etc/foo/old.conf\n".parse().unwrap();
+//! let new: Conffiles = "/etc/foo.conf\n\
+//!                       remove-on-upgrade /etc/foo/old.conf\n\
+//!                       /etc/foo/new.conf\n"
+//!     .parse()
+//!     .unwrap();
+//! let diff = old.diff(&new);
+//! assert_eq!(diff.added, vec!["/etc/foo/new.conf"]);
+//! assert!(diff.dropped.is_empty());
+//! assert_eq!(diff.removed_on_upgrade, vec!["/etc/foo/old.conf"]);
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A single entry of a conffiles file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Conffile {
+    /// the absolute path of the conffile
+    pub path: String,
+    /// whether dpkg should remove the file on upgrade, rather than it
+    /// being shipped by the package
+    pub remove_on_upgrade: bool,
+}
+
+/// The contents of a conffiles file.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Conffiles {
+    conffiles: Vec<Conffile>,
+}
+
+/// Changes to the conffiles between two versions of a package.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ConffilesDiff {
+    /// conffiles shipped by the new version only
+    pub added: Vec<String>,
+    /// conffiles no longer listed at all, which dpkg leaves behind
+    pub dropped: Vec<String>,
+    /// conffiles the new version asks dpkg to remove
+    pub removed_on_upgrade: Vec<String>,
+}
+
+impl Conffile {
+    /// Creates an entry for a conffile shipped by the package.
+    pub fn new(path: &str) -> Conffile {
+        Conffile {
+            path: path.to_string(),
+            remove_on_upgrade: false,
+        }
+    }
+
+    /// Whether the package ships the file, i.e. it is not flagged to
+    /// be removed.
+    pub fn is_shipped(&self) -> bool {
+        !self.remove_on_upgrade
+    }
+}
+
+impl FromStr for Conffile {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (remove_on_upgrade, path) = match s.starts_with('/') {
+            true => (false, s),
+            false => match s.split_once(' ') {
+                Some(("remove-on-upgrade", path)) => (true, path.trim_start()),
+                Some(_) => return Err("unknown conffile flag"),
+                None => return Err("conffile path must be absolute"),
+            },
+        };
+        if !path.starts_with('/') {
+            return Err("conffile path must be absolute");
+        }
+        Ok(Conffile {
+            path: path.to_string(),
+            remove_on_upgrade,
+        })
+    }
+}
+
+impl fmt::Display for Conffile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.remove_on_upgrade {
+            write!(f, "remove-on-upgrade ")?;
+        }
+        write!(f, "{}", self.path)
+    }
+}
+
+impl Conffiles {
+    /// Reads and parses a conffiles file from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<Conffiles> {
+        fs::read_to_string(in_file)?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// All entries, in order.
+    pub fn conffiles(&self) -> &Vec<Conffile> {
+        &self.conffiles
+    }
+
+    /// Append an entry, replacing any previous one for the same path.
+    pub fn add_conffile(&mut self, conffile: Conffile) {
+        self.conffiles.retain(|c| c.path != conffile.path);
+        self.conffiles.push(conffile);
+    }
+
+    /// Look up the entry for a path.
+    pub fn get(&self, path: &str) -> Option<&Conffile> {
+        self.conffiles.iter().find(|c| c.path == path)
+    }
+
+    /// Paths of the conffiles shipped by the package.
+    pub fn shipped(&self) -> impl Iterator<Item = &str> {
+        self.conffiles
+            .iter()
+            .filter(|c| c.is_shipped())
+            .map(|c| c.path.as_str())
+    }
+
+    /// Whether the package ships the given conffile.
+    pub fn ships(&self, path: &str) -> bool {
+        self.get(path).map_or(false, Conffile::is_shipped)
+    }
+
+    /// Compares the conffiles of this version of a package with the
+    /// ones of a newer version.
+    pub fn diff(&self, newer: &Conffiles) -> ConffilesDiff {
+        let mut diff = ConffilesDiff::default();
+        for c in &newer.conffiles {
+            if c.remove_on_upgrade {
+                diff.removed_on_upgrade.push(c.path.clone());
+            } else if !self.ships(&c.path) {
+                diff.added.push(c.path.clone());
+            }
+        }
+        for path in self.shipped() {
+            if newer.get(path).is_none() {
+                diff.dropped.push(path.to_string());
+            }
+        }
+        diff
+    }
+}
+
+impl FromStr for Conffiles {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut result = Conffiles::default();
+        for line in s.lines().filter(|l| !l.trim().is_empty()) {
+            result.add_conffile(line.parse()?);
+        }
+        Ok(result)
+    }
+}
+
+impl fmt::Display for Conffiles {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in &self.conffiles {
+            writeln!(f, "{c}")?;
+        }
+        Ok(())
+    }
+}
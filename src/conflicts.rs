@@ -0,0 +1,257 @@
+//! Consistency checks of sets of binary packages
+//!
+//! Before a selection of packages is installed together, like the
+//! packages of an image to build, it should be checked that none of
+//! them conflicts with or breaks another and that no file is shipped by
+//! two packages, unless one of them declares to replace the other.
+//! Otherwise dpkg refuses to install them.
+//!
+//! The packages can be given individually, from an installation set
+//! computed by the resolver or from the dpkg status database. To detect
+//! files overwritten, the files of each package need to be added, too,
+//! which are not part of Packages indices or the status database.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::conflicts::{ConflictAnalyzer, ConflictIssue};
+//! use debian::package::ControlFile;
+//!
+//! let packages = ControlFile::from_reader(
+//!     "Package: foo\nVersion: 1.0\nConflicts: bar\n\n\
+//!      Package: bar\nVersion: 2.0\n"
+//!         .as_bytes(),
+//! )
+//! .unwrap();
+//! let mut analyzer = ConflictAnalyzer::default();
+//! analyzer.add_index(&packages).unwrap();
+//! let issues = analyzer.check();
+//! assert!(matches!(issues[..], [ConflictIssue::Conflicts(..)]));
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::package::{parse_dep_list, ControlFile, SingleDependency};
+use crate::resolver::{Candidate, InstallationSet};
+use crate::status::DpkgStatus;
+
+/// Checks a set of packages for conflicts and files overwritten.
+#[derive(Debug, Clone, Default)]
+pub struct ConflictAnalyzer {
+    packages: Vec<Candidate>,
+    /// the paths shipped by each package, by name
+    files: HashMap<String, Vec<String>>,
+}
+
+/// A problem preventing packages from being installed together.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ConflictIssue {
+    /// The first package conflicts with the second one, as declared by
+    /// the given relation.
+    Conflicts(String, String, SingleDependency),
+    /// The first package breaks the second one, as declared by the given
+    /// relation.
+    Breaks(String, String, SingleDependency),
+    /// The path is shipped by both packages, but neither replaces the
+    /// other.
+    FileOverwrite(String, String, String),
+}
+
+fn relative_path(path: &str) -> &str {
+    path.trim_start_matches("./").trim_start_matches('/')
+}
+
+/// Parses a relationship field of a package, ignoring alternatives,
+/// which are meaningless for negative relations.
+fn relations(candidate: &Candidate, field: &str) -> Vec<SingleDependency> {
+    let value = candidate.get_field(field).unwrap_or("");
+    parse_dep_list(value)
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|d| d.alternatives)
+        .collect()
+}
+
+impl fmt::Display for ConflictIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConflictIssue::Conflicts(ref package, ref other, ref rel) => {
+                write!(f, "{package} conflicts with {other} ({rel})")
+            }
+            ConflictIssue::Breaks(ref package, ref other, ref rel) => {
+                write!(f, "{package} breaks {other} ({rel})")
+            }
+            ConflictIssue::FileOverwrite(ref path, ref a, ref b) => {
+                write!(f, "/{path} is shipped by both {a} and {b}")
+            }
+        }
+    }
+}
+
+impl ConflictAnalyzer {
+    /// Adds a package, replacing any other version of it for the same
+    /// architecture, like upgrading it would.
+    pub fn add_package(&mut self, candidate: Candidate) {
+        let same = |c: &Candidate| {
+            c.package == candidate.package
+                && c.architecture == candidate.architecture
+        };
+        match self.packages.iter_mut().find(|c| same(c)) {
+            Some(existing) => *existing = candidate,
+            None => self.packages.push(candidate),
+        }
+    }
+
+    /// Adds all packages of an index.
+    pub fn add_index(
+        &mut self,
+        index: &ControlFile,
+    ) -> Result<(), &'static str> {
+        for para in index.get_paragraphs() {
+            self.add_package(Candidate::from_paragraph(para.clone())?);
+        }
+        Ok(())
+    }
+
+    /// Adds the packages installed according to a status database.
+    pub fn add_status(
+        &mut self,
+        status: &DpkgStatus,
+    ) -> Result<(), &'static str> {
+        for entry in status.installed() {
+            self.add_package(Candidate::from_paragraph(
+                entry.paragraph().clone(),
+            )?);
+        }
+        Ok(())
+    }
+
+    /// Adds the packages of an installation set. Added after the status
+    /// database, they replace the versions installed.
+    pub fn add_installation_set(&mut self, set: &InstallationSet) {
+        for candidate in set.packages() {
+            self.add_package(candidate.clone());
+        }
+    }
+
+    /// Records the paths shipped by a package. Directories, which may
+    /// be shared, should not be included.
+    pub fn add_files<I, S>(&mut self, package: &str, paths: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let files = self.files.entry(package.to_string()).or_default();
+        files.extend(
+            paths
+                .into_iter()
+                .map(|p| relative_path(p.as_ref()).to_string()),
+        );
+    }
+
+    /// Adds a binary package together with its non-directory entries.
+    #[cfg(feature = "deb")]
+    pub fn add_deb(
+        &mut self,
+        deb: &crate::deb::DebFile,
+    ) -> std::io::Result<()> {
+        use crate::deb::DataEntryType;
+        use std::io;
+
+        let candidate = Candidate::from_paragraph(deb.control()?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let entries = deb.data_entries()?;
+        self.add_files(
+            &candidate.package,
+            entries
+                .iter()
+                .filter(|e| e.entry_type != DataEntryType::Dir)
+                .map(|e| e.path.as_str()),
+        );
+        self.add_package(candidate);
+        Ok(())
+    }
+
+    /// All packages, in the order added.
+    pub fn packages(&self) -> &[Candidate] {
+        &self.packages
+    }
+
+    /// Whether a package declares to replace another one.
+    fn replaces(&self, package: &str, other: &str) -> bool {
+        self.packages
+            .iter()
+            .filter(|c| c.package == package)
+            .flat_map(|c| relations(c, "Replaces"))
+            .any(|rel| {
+                self.packages
+                    .iter()
+                    .any(|o| o.package == other && o.satisfies(&rel))
+            })
+    }
+
+    /// Checks for violated Conflicts and Breaks relations, including
+    /// those on virtual packages, and for paths shipped by more than one
+    /// package without a Replaces relation between them.
+    ///
+    /// As dpkg allows, a package may conflict with a virtual package it
+    /// provides itself, and instances of a package for different
+    /// architectures may ship the same paths.
+    pub fn check(&self) -> Vec<ConflictIssue> {
+        let mut issues = vec![];
+        for candidate in &self.packages {
+            for (field, breaks) in [("Conflicts", false), ("Breaks", true)] {
+                for rel in relations(candidate, field) {
+                    let violating = self.packages.iter().filter(|o| {
+                        o.package != candidate.package && o.satisfies(&rel)
+                    });
+                    for other in violating {
+                        let package = candidate.package.clone();
+                        let other = other.package.clone();
+                        issues.push(match breaks {
+                            true => ConflictIssue::Breaks(
+                                package,
+                                other,
+                                rel.clone(),
+                            ),
+                            false => ConflictIssue::Conflicts(
+                                package,
+                                other,
+                                rel.clone(),
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+
+        let mut shipped: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for (package, files) in &self.files {
+            if !self.packages.iter().any(|c| &c.package == package) {
+                continue;
+            }
+            for path in files {
+                let packages = shipped.entry(path).or_default();
+                if !packages.contains(&package.as_str()) {
+                    packages.push(package);
+                }
+            }
+        }
+        for (path, mut packages) in shipped {
+            packages.sort();
+            for (i, a) in packages.iter().enumerate() {
+                for b in &packages[i + 1..] {
+                    if !self.replaces(a, b) && !self.replaces(b, a) {
+                        issues.push(ConflictIssue::FileOverwrite(
+                            path.to_string(),
+                            a.to_string(),
+                            b.to_string(),
+                        ));
+                    }
+                }
+            }
+        }
+        issues
+    }
+}
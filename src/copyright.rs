@@ -0,0 +1,397 @@
+//! Parser and writer for machine-readable debian/copyright files
+//!
+//! Implements the DEP-5 copyright format: a header paragraph, followed
+//! by `Files` paragraphs assigning copyright and license to sets of
+//! files and stand-alone `License` paragraphs carrying license texts.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use debian::copyright::Copyright;
+//! use std::path::Path;
+//!
+//! let copyright = Copyright::from_file(Path::new("debian/copyright"))
+//!     .unwrap();
+//! if let Some(license) = copyright.license_for_path("src/main.c") {
+//!     println!("src/main.c is licensed under {}", license.name);
+//! }
+//! ```
+
+use std::fmt;
+use std::io;
+use std::io::BufRead;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::package::{
+    join_multiline, split_multiline, ControlFile, ControlParagraph,
+};
+
+/// The URI identifying version 1.0 of the format.
+pub const FORMAT_1_0: &str =
+    "https://www.debian.org/doc/packaging-manuals/copyright-format/1.0/";
+
+/// A license, given by its short name and optionally its full text.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct License {
+    /// short name or expression, like `GPL-2+ or Artistic`
+    pub name: String,
+    /// license text or additional information, if given
+    pub text: Option<String>,
+}
+
+/// The header paragraph of a copyright file.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Header {
+    pub format: String,
+    pub upstream_name: Option<String>,
+    pub upstream_contact: Vec<String>,
+    pub source: Option<String>,
+    pub disclaimer: Option<String>,
+    pub comment: Option<String>,
+    pub license: Option<License>,
+    pub copyright: Vec<String>,
+    pub files_excluded: Vec<String>,
+    /// any other fields, in order
+    pub extra: Vec<(String, String)>,
+}
+
+/// A `Files` paragraph, covering a set of files.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FilesParagraph {
+    /// glob patterns matching the files covered
+    pub files: Vec<String>,
+    /// copyright statements, one per line
+    pub copyright: Vec<String>,
+    pub license: License,
+    pub comment: Option<String>,
+}
+
+/// A stand-alone `License` paragraph.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LicenseParagraph {
+    pub license: License,
+    pub comment: Option<String>,
+}
+
+/// A complete machine-readable copyright file.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Copyright {
+    pub header: Header,
+    pub files: Vec<FilesParagraph>,
+    pub licenses: Vec<LicenseParagraph>,
+}
+
+/// Matches a path against a DEP-5 `Files` pattern.
+///
+/// In these patterns, `*` matches any sequence of characters including
+/// slashes, `?` matches a single character and a backslash escapes
+/// either of them or itself. Patterns are relative to the root of the
+/// source tree.
+pub fn files_pattern_match(pattern: &str, path: &str) -> bool {
+    fn rec(p: &[char], s: &[char]) -> bool {
+        match p.first() {
+            None => s.is_empty(),
+            Some('*') => (0..=s.len()).any(|i| rec(&p[1..], &s[i..])),
+            Some('?') => !s.is_empty() && rec(&p[1..], &s[1..]),
+            Some('\\') if p.len() > 1 => {
+                s.first() == Some(&p[1]) && rec(&p[2..], &s[1..])
+            }
+            Some(c) => s.first() == Some(c) && rec(&p[1..], &s[1..]),
+        }
+    }
+
+    let normalize = |x: &str| x.trim_start_matches("./").to_string();
+    let p = normalize(pattern).chars().collect::<Vec<char>>();
+    let s = normalize(path).chars().collect::<Vec<char>>();
+    rec(&p, &s)
+}
+
+impl License {
+    fn parse(value: &str) -> License {
+        let (name, lines) = split_multiline(value);
+        License {
+            name: name.to_string(),
+            text: match lines.is_empty() {
+                true => None,
+                false => Some(lines.join("\n")),
+            },
+        }
+    }
+
+    fn to_value(&self) -> String {
+        match self.text {
+            Some(ref t) => {
+                join_multiline(&self.name, &t.lines().collect::<Vec<&str>>())
+            }
+            None => self.name.clone(),
+        }
+    }
+}
+
+fn lines(value: &str) -> Vec<String> {
+    value
+        .lines()
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty())
+        .collect()
+}
+
+fn words(value: &str) -> Vec<String> {
+    value.split_whitespace().map(|x| x.to_string()).collect()
+}
+
+fn text(value: &str) -> String {
+    let (first, rest) = split_multiline(value);
+    let mut all = vec![first];
+    all.extend(rest);
+    all.join("\n").trim().to_string()
+}
+
+fn text_value(text: &str) -> String {
+    let mut lines = text.lines();
+    let first = lines.next().unwrap_or("");
+    join_multiline(first, &lines.collect::<Vec<&str>>())
+}
+
+fn lines_value(lines: &[String]) -> String {
+    match lines.split_first() {
+        Some((first, rest)) => join_multiline(first, rest),
+        None => "".to_string(),
+    }
+}
+
+impl Header {
+    /// Creates a `Header` from the first paragraph of a copyright file.
+    pub fn from_paragraph(
+        para: &ControlParagraph,
+    ) -> Result<Header, &'static str> {
+        let mut header = Header::default();
+        let mut has_format = false;
+        for e in para.entries() {
+            let v = e.value().as_str();
+            match e.key() {
+                "Format" | "Format-Specification" => {
+                    has_format = true;
+                    header.format = v.to_string();
+                }
+                "Upstream-Name" => header.upstream_name = Some(v.to_string()),
+                "Upstream-Contact" => header.upstream_contact = lines(v),
+                "Source" => header.source = Some(text(v)),
+                "Disclaimer" => header.disclaimer = Some(text(v)),
+                "Comment" => header.comment = Some(text(v)),
+                "License" => header.license = Some(License::parse(v)),
+                "Copyright" => header.copyright = lines(v),
+                "Files-Excluded" => header.files_excluded = words(v),
+                key => header.extra.push((key.to_string(), v.to_string())),
+            }
+        }
+        match has_format {
+            true => Ok(header),
+            false => Err("missing Format field in header"),
+        }
+    }
+
+    /// Converts the header back into a paragraph.
+    pub fn to_paragraph(&self) -> ControlParagraph {
+        let mut para = ControlParagraph::default();
+        para.add_entry("Format", self.format.clone());
+        if let Some(ref v) = self.upstream_name {
+            para.add_entry("Upstream-Name", v.clone());
+        }
+        if !self.upstream_contact.is_empty() {
+            para.add_entry(
+                "Upstream-Contact",
+                lines_value(&self.upstream_contact),
+            );
+        }
+        if let Some(ref v) = self.source {
+            para.add_entry("Source", text_value(v));
+        }
+        if let Some(ref v) = self.disclaimer {
+            para.add_entry("Disclaimer", text_value(v));
+        }
+        if let Some(ref v) = self.comment {
+            para.add_entry("Comment", text_value(v));
+        }
+        if let Some(ref v) = self.license {
+            para.add_entry("License", v.to_value());
+        }
+        if !self.copyright.is_empty() {
+            para.add_entry("Copyright", lines_value(&self.copyright));
+        }
+        if !self.files_excluded.is_empty() {
+            let value = lines_value(&self.files_excluded);
+            para.add_entry("Files-Excluded", value);
+        }
+        for (k, v) in &self.extra {
+            para.add_entry(k, v.clone());
+        }
+        para
+    }
+}
+
+impl FilesParagraph {
+    /// Creates a `FilesParagraph` from a paragraph of a copyright file.
+    pub fn from_paragraph(
+        para: &ControlParagraph,
+    ) -> Result<FilesParagraph, &'static str> {
+        let files = match para.get_entry("Files") {
+            Some(v) => words(v),
+            None => return Err("missing Files field"),
+        };
+        let copyright = match para.get_entry("Copyright") {
+            Some(v) => lines(v),
+            None => return Err("missing Copyright field"),
+        };
+        let license = match para.get_entry("License") {
+            Some(v) => License::parse(v),
+            None => return Err("missing License field"),
+        };
+        Ok(FilesParagraph {
+            files,
+            copyright,
+            license,
+            comment: para.get_entry("Comment").map(text),
+        })
+    }
+
+    /// Check whether this paragraph covers the given path.
+    pub fn matches(&self, path: &str) -> bool {
+        self.files.iter().any(|p| files_pattern_match(p, path))
+    }
+
+    /// Converts this paragraph back into a generic paragraph.
+    pub fn to_paragraph(&self) -> ControlParagraph {
+        let mut para = ControlParagraph::default();
+        para.add_entry("Files", lines_value(&self.files));
+        para.add_entry("Copyright", lines_value(&self.copyright));
+        para.add_entry("License", self.license.to_value());
+        if let Some(ref v) = self.comment {
+            para.add_entry("Comment", text_value(v));
+        }
+        para
+    }
+}
+
+impl LicenseParagraph {
+    /// Creates a `LicenseParagraph` from a paragraph of a copyright
+    /// file.
+    pub fn from_paragraph(
+        para: &ControlParagraph,
+    ) -> Result<LicenseParagraph, &'static str> {
+        let license = match para.get_entry("License") {
+            Some(v) => License::parse(v),
+            None => return Err("missing License field"),
+        };
+        Ok(LicenseParagraph {
+            license,
+            comment: para.get_entry("Comment").map(text),
+        })
+    }
+
+    /// Converts this paragraph back into a generic paragraph.
+    pub fn to_paragraph(&self) -> ControlParagraph {
+        let mut para = ControlParagraph::default();
+        para.add_entry("License", self.license.to_value());
+        if let Some(ref v) = self.comment {
+            para.add_entry("Comment", text_value(v));
+        }
+        para
+    }
+}
+
+impl Copyright {
+    /// Reads and parses a copyright file from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<Copyright> {
+        Copyright::from_control(&ControlFile::from_file(in_file)?)
+    }
+
+    /// Parses a copyright file from any buffered reader.
+    pub fn from_reader<R: BufRead>(buf: R) -> io::Result<Copyright> {
+        Copyright::from_control(&ControlFile::from_reader(buf)?)
+    }
+
+    fn from_control(cf: &ControlFile) -> io::Result<Copyright> {
+        let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+        let mut paragraphs = cf.get_paragraphs().iter();
+        let header = match paragraphs.next() {
+            Some(p) => Header::from_paragraph(p).map_err(invalid)?,
+            None => return Err(invalid("empty copyright file")),
+        };
+        let mut result = Copyright {
+            header,
+            files: vec![],
+            licenses: vec![],
+        };
+        for para in paragraphs {
+            if para.has_entry("Files") {
+                let p =
+                    FilesParagraph::from_paragraph(para).map_err(invalid)?;
+                result.files.push(p);
+            } else {
+                let p =
+                    LicenseParagraph::from_paragraph(para).map_err(invalid)?;
+                result.licenses.push(p);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Find the `Files` paragraph covering a path.
+    ///
+    /// As mandated by DEP-5, the last paragraph matching the path
+    /// takes precedence.
+    pub fn files_paragraph_for_path(
+        &self,
+        path: &str,
+    ) -> Option<&FilesParagraph> {
+        self.files.iter().rev().find(|p| p.matches(path))
+    }
+
+    /// Determine the license of a path.
+    pub fn license_for_path(&self, path: &str) -> Option<&License> {
+        self.files_paragraph_for_path(path).map(|p| &p.license)
+    }
+
+    /// Look up the full text of a license by its short name, either
+    /// from a stand-alone `License` paragraph or from a `Files`
+    /// paragraph carrying the text inline.
+    pub fn license_text(&self, name: &str) -> Option<&str> {
+        let from_files = self.files.iter().map(|p| &p.license);
+        self.licenses
+            .iter()
+            .map(|p| &p.license)
+            .chain(from_files)
+            .chain(self.header.license.iter())
+            .filter(|l| l.name == name)
+            .find_map(|l| l.text.as_deref())
+    }
+
+    /// Serializes this copyright file to disk.
+    pub fn to_file(&self, out_file: &Path) -> io::Result<()> {
+        std::fs::write(out_file, self.to_string())
+    }
+}
+
+impl FromStr for Copyright {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Copyright::from_reader(s.as_bytes())
+    }
+}
+
+impl fmt::Display for Copyright {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut cf = ControlFile::default();
+        cf.add_paragraph(self.header.to_paragraph());
+        for p in &self.files {
+            cf.add_paragraph(p.to_paragraph());
+        }
+        for p in &self.licenses {
+            cf.add_paragraph(p.to_paragraph());
+        }
+        write!(f, "{cf}")
+    }
+}
@@ -0,0 +1,461 @@
+//! Writer for binary packages
+//!
+//! Assembles a `.deb` from a control paragraph, maintainer scripts and
+//! the files to install, generating `md5sums` unless given. All
+//! timestamps are set to the same value, taken from `SOURCE_DATE_EPOCH`
+//! if set, and entries are written in sorted order, so building the
+//! same package twice yields identical files.
+
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use super::Compression;
+use crate::buildopts::source_date_epoch;
+use crate::conffiles::Conffiles;
+use crate::maintainer_scripts::ScriptKind;
+use crate::md5sums::{md5_hex, Md5Sums};
+use crate::package::ControlParagraph;
+use crate::substvars::Substvars;
+
+/// Fields dpkg-deb requires in the control paragraph.
+const REQUIRED_FIELDS: &[&str] = &[
+    "Package",
+    "Version",
+    "Architecture",
+    "Maintainer",
+    "Description",
+];
+
+#[derive(Debug, Clone)]
+enum EntryKind {
+    File(Vec<u8>),
+    Dir,
+    Symlink(String),
+}
+
+#[derive(Debug, Clone)]
+struct DataEntry {
+    /// the path relative to the root directory, without leading `./`
+    path: String,
+    kind: EntryKind,
+    mode: u32,
+    user: String,
+    uid: u64,
+    group: String,
+    gid: u64,
+}
+
+/// Builder for binary packages.
+///
+/// # Examples
+///
+/// ```
+/// use debian::deb::{DebBuilder, DebFile};
+/// use debian::package::ControlParagraph;
+///
+/// let mut control = ControlParagraph::default();
+/// control.add_entry("Package", "foo".to_string());
+/// control.add_entry("Version", "1.0-1".to_string());
+/// control.add_entry("Architecture", "all".to_string());
+/// control.add_entry("Maintainer", "Jane Doe <jane@example.org>".to_string());
+/// control.add_entry("Description", "example package".to_string());
+///
+/// let mut builder = DebBuilder::new(control).unwrap();
+/// builder
+///     .add_file("/usr/bin/foo", b"#!/bin/sh\n".to_vec(), 0o755)
+///     .unwrap();
+/// let mut deb = vec![];
+/// builder.write(&mut deb).unwrap();
+///
+/// let package = DebFile::from_reader(deb.as_slice()).unwrap();
+/// assert_eq!(package.control().unwrap().get_entry("Package"), Some("foo"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct DebBuilder {
+    control: ControlParagraph,
+    control_files: Vec<(String, Vec<u8>, u32)>,
+    entries: Vec<DataEntry>,
+    compression: Compression,
+    mtime: u64,
+}
+
+/// Normalizes a path within the package to be relative to the root
+/// directory, rejecting ones that would escape it.
+fn normalize_path(path: &str) -> Result<String, &'static str> {
+    let components: Vec<&str> = path
+        .split('/')
+        .filter(|c| !c.is_empty() && *c != ".")
+        .collect();
+    if components.is_empty() {
+        return Err("empty path");
+    }
+    if components.contains(&"..") {
+        return Err("path must not contain ..");
+    }
+    Ok(components.join("/"))
+}
+
+/// Copies a name into a header field, truncating it if it needs a GNU
+/// long name entry.
+fn copy_name(field: &mut [u8; 100], name: &str) {
+    let n = name.len().min(field.len());
+    field[..n].copy_from_slice(&name.as_bytes()[..n]);
+}
+
+impl DebBuilder {
+    /// Creates a builder for a package with the given control
+    /// paragraph, compressing its members with xz. Fails if
+    /// `SOURCE_DATE_EPOCH` is set but not a number of seconds.
+    pub fn new(control: ControlParagraph) -> io::Result<DebBuilder> {
+        let mtime = source_date_epoch()?.unwrap_or(0);
+        Ok(DebBuilder {
+            control,
+            control_files: vec![],
+            entries: vec![],
+            compression: Compression::Xz,
+            mtime,
+        })
+    }
+
+    /// Sets the compression of the control and data members.
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.compression = compression;
+    }
+
+    /// Sets the modification time of all entries, in seconds since the
+    /// epoch, overriding `SOURCE_DATE_EPOCH`.
+    pub fn set_mtime(&mut self, mtime: u64) {
+        self.mtime = mtime;
+    }
+
+    /// Adds a maintainer script, replacing any previous one.
+    pub fn add_maintainer_script(&mut self, kind: ScriptKind, contents: &str) {
+        let contents = contents.as_bytes().to_vec();
+        self.control_files.retain(|(n, _, _)| n != kind.name());
+        self.control_files
+            .push((kind.name().to_string(), contents, 0o755));
+    }
+
+    /// Adds another file to the control member, like `conffiles` or
+    /// `triggers`. The `control` file itself is generated from the
+    /// paragraph.
+    pub fn add_control_file(
+        &mut self,
+        name: &str,
+        contents: Vec<u8>,
+        mode: u32,
+    ) -> Result<(), &'static str> {
+        if name.is_empty() || name.contains('/') || name == "control" {
+            return Err("invalid control file name");
+        }
+        self.control_files.retain(|(n, _, _)| n != name);
+        self.control_files.push((name.to_string(), contents, mode));
+        Ok(())
+    }
+
+    fn add_entry(
+        &mut self,
+        path: &str,
+        kind: EntryKind,
+        mode: u32,
+    ) -> Result<(), &'static str> {
+        let path = normalize_path(path)?;
+        let mut parent = path.as_str();
+        while let Some((p, _)) = parent.rsplit_once('/') {
+            parent = p;
+            match self.entries.iter().find(|e| e.path == parent) {
+                Some(e) if !matches!(e.kind, EntryKind::Dir) => {
+                    return Err("parent of path is not a directory");
+                }
+                Some(_) => {}
+                None => {
+                    self.push_entry(parent.to_string(), EntryKind::Dir, 0o755)
+                }
+            }
+        }
+        self.entries.retain(|e| e.path != path);
+        self.push_entry(path, kind, mode);
+        Ok(())
+    }
+
+    fn push_entry(&mut self, path: String, kind: EntryKind, mode: u32) {
+        self.entries.push(DataEntry {
+            path,
+            kind,
+            mode,
+            user: "root".to_string(),
+            uid: 0,
+            group: "root".to_string(),
+            gid: 0,
+        });
+    }
+
+    /// Adds a regular file to install, creating missing parent
+    /// directories. Entries are owned by root unless changed with
+    /// `set_owner`.
+    pub fn add_file(
+        &mut self,
+        path: &str,
+        contents: Vec<u8>,
+        mode: u32,
+    ) -> Result<(), &'static str> {
+        self.add_entry(path, EntryKind::File(contents), mode)
+    }
+
+    /// Adds a directory, creating missing parent directories.
+    pub fn add_dir(
+        &mut self,
+        path: &str,
+        mode: u32,
+    ) -> Result<(), &'static str> {
+        self.add_entry(path, EntryKind::Dir, mode)
+    }
+
+    /// Adds a symbolic link, creating missing parent directories.
+    pub fn add_symlink(
+        &mut self,
+        path: &str,
+        target: &str,
+    ) -> Result<(), &'static str> {
+        self.add_entry(path, EntryKind::Symlink(target.to_string()), 0o777)
+    }
+
+    /// Changes the owner of an entry added before. Returns false if
+    /// there is no such entry.
+    pub fn set_owner(
+        &mut self,
+        path: &str,
+        user: &str,
+        uid: u64,
+        group: &str,
+        gid: u64,
+    ) -> bool {
+        let path = match normalize_path(path) {
+            Ok(p) => p,
+            Err(_) => return false,
+        };
+        match self.entries.iter_mut().find(|e| e.path == path) {
+            Some(e) => {
+                e.user = user.to_string();
+                e.uid = uid;
+                e.group = group.to_string();
+                e.gid = gid;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The installed size in KiB, counting each file rounded up to a
+    /// full KiB and each other entry as one, like dpkg-gencontrol.
+    pub fn installed_size(&self) -> u64 {
+        self.entries
+            .iter()
+            .map(|e| match e.kind {
+                EntryKind::File(ref c) => (c.len() as u64 + 1023) / 1024,
+                _ => 1,
+            })
+            .sum()
+    }
+
+    /// Expands the substitution variables in the control paragraph,
+    /// like dpkg-gencontrol. Unless defined, `Installed-Size` is set to
+    /// the installed size of the entries added so far.
+    pub fn substitute(&mut self, substvars: &mut Substvars) -> io::Result<()> {
+        if substvars.get("Installed-Size").is_none() {
+            let size = self.installed_size().to_string();
+            substvars.set_optional("Installed-Size", &size);
+        }
+        self.control = substvars.substitute_paragraph(&self.control)?;
+        Ok(())
+    }
+
+    /// The digests of the regular files in the package, except for
+    /// conffiles, like dh_md5sums generates them.
+    pub fn md5sums(&self) -> io::Result<Md5Sums> {
+        let conffiles =
+            match self.control_files.iter().find(|f| f.0 == "conffiles") {
+                Some((_, contents, _)) => {
+                    String::from_utf8_lossy(contents).parse().map_err(|e| {
+                        io::Error::new(io::ErrorKind::InvalidData, e)
+                    })?
+                }
+                None => Conffiles::default(),
+            };
+        let mut entries: Vec<&DataEntry> = self.entries.iter().collect();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        let mut md5sums = Md5Sums::default();
+        for e in entries {
+            if let EntryKind::File(ref contents) = e.kind {
+                if !conffiles.ships(&format!("/{}", e.path)) {
+                    md5sums.add_file(&e.path, &md5_hex(contents));
+                }
+            }
+        }
+        Ok(md5sums)
+    }
+
+    fn header(&self, entry_type: tar::EntryType, mode: u32) -> tar::Header {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(entry_type);
+        header.set_mode(mode);
+        header.set_mtime(self.mtime);
+        header.set_size(0);
+        header.set_uid(0);
+        header.set_gid(0);
+        // root names always fit into the header
+        let _ = header.set_username("root");
+        let _ = header.set_groupname("root");
+        header
+    }
+
+    /// Appends an entry to a tarball, keeping its path as given, like
+    /// `./usr/`, and using GNU extensions for long names, as dpkg-deb
+    /// does.
+    fn append(
+        &self,
+        tar: &mut tar::Builder<Vec<u8>>,
+        mut header: tar::Header,
+        path: &str,
+        link: Option<&str>,
+        contents: &[u8],
+    ) -> io::Result<()> {
+        if let Some(link) = link {
+            if link.len() > 100 {
+                self.append_long_name(tar, tar::EntryType::GNULongLink, link)?;
+            }
+            copy_name(&mut header.as_old_mut().linkname, link);
+        }
+        if path.len() > 100 {
+            self.append_long_name(tar, tar::EntryType::GNULongName, path)?;
+        }
+        copy_name(&mut header.as_old_mut().name, path);
+        header.set_cksum();
+        tar.append(&header, contents)
+    }
+
+    fn append_long_name(
+        &self,
+        tar: &mut tar::Builder<Vec<u8>>,
+        entry_type: tar::EntryType,
+        name: &str,
+    ) -> io::Result<()> {
+        let mut header = self.header(entry_type, 0o644);
+        copy_name(&mut header.as_old_mut().name, "././@LongLink");
+        let mut data = name.as_bytes().to_vec();
+        data.push(0);
+        header.set_size(data.len() as u64);
+        header.set_cksum();
+        tar.append(&header, data.as_slice())
+    }
+
+    fn control_tar(&self) -> io::Result<Vec<u8>> {
+        let mut control = self.control.clone();
+        if !control.has_entry("Installed-Size") {
+            control
+                .add_entry("Installed-Size", self.installed_size().to_string());
+        }
+        let mut files = vec![(
+            "control".to_string(),
+            format!("{control}").into_bytes(),
+            0o644,
+        )];
+        files.extend(self.control_files.iter().cloned());
+        if !files.iter().any(|(name, _, _)| name == "md5sums") {
+            let md5sums = self.md5sums()?;
+            if !md5sums.files().is_empty() {
+                let contents = md5sums.to_string().into_bytes();
+                files.push(("md5sums".to_string(), contents, 0o644));
+            }
+        }
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut tar = tar::Builder::new(vec![]);
+        let header = self.header(tar::EntryType::Directory, 0o755);
+        self.append(&mut tar, header, "./", None, &[])?;
+        for (name, contents, mode) in files {
+            let mut header = self.header(tar::EntryType::Regular, mode);
+            header.set_size(contents.len() as u64);
+            let path = format!("./{name}");
+            self.append(&mut tar, header, &path, None, &contents)?;
+        }
+        tar.into_inner()
+    }
+
+    fn data_tar(&self) -> io::Result<Vec<u8>> {
+        let mut entries: Vec<&DataEntry> = self.entries.iter().collect();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let mut tar = tar::Builder::new(vec![]);
+        let header = self.header(tar::EntryType::Directory, 0o755);
+        self.append(&mut tar, header, "./", None, &[])?;
+        for e in entries {
+            let entry_type = match e.kind {
+                EntryKind::File(_) => tar::EntryType::Regular,
+                EntryKind::Dir => tar::EntryType::Directory,
+                EntryKind::Symlink(_) => tar::EntryType::Symlink,
+            };
+            let mut header = self.header(entry_type, e.mode);
+            header.set_uid(e.uid);
+            header.set_gid(e.gid);
+            header.set_username(&e.user)?;
+            header.set_groupname(&e.group)?;
+            match e.kind {
+                EntryKind::File(ref contents) => {
+                    header.set_size(contents.len() as u64);
+                    let path = format!("./{}", e.path);
+                    self.append(&mut tar, header, &path, None, contents)?;
+                }
+                EntryKind::Dir => {
+                    let path = format!("./{}/", e.path);
+                    self.append(&mut tar, header, &path, None, &[])?;
+                }
+                EntryKind::Symlink(ref target) => {
+                    let path = format!("./{}", e.path);
+                    self.append(&mut tar, header, &path, Some(target), &[])?;
+                }
+            }
+        }
+        tar.into_inner()
+    }
+
+    /// Writes the package.
+    pub fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+        for field in REQUIRED_FIELDS {
+            if !self.control.has_entry(field) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("missing {field} field"),
+                ));
+            }
+        }
+        let ext = self.compression.extension();
+        let members = [
+            ("debian-binary".to_string(), b"2.0\n".to_vec()),
+            (
+                format!("control.tar{ext}"),
+                self.compression.compress(&self.control_tar()?)?,
+            ),
+            (
+                format!("data.tar{ext}"),
+                self.compression.compress(&self.data_tar()?)?,
+            ),
+        ];
+        let mut ar = ar::Builder::new(writer);
+        for (name, contents) in members {
+            let mut header =
+                ar::Header::new(name.into_bytes(), contents.len() as u64);
+            header.set_mtime(self.mtime);
+            header.set_mode(0o100644);
+            ar.append(&header, contents.as_slice())?;
+        }
+        ar.into_inner()?.flush()
+    }
+
+    /// Writes the package to disk.
+    pub fn to_file(&self, out_file: &Path) -> io::Result<()> {
+        self.write(BufWriter::new(File::create(out_file)?))
+    }
+}
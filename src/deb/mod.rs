@@ -0,0 +1,429 @@
+//! Reader for binary packages
+//!
+//! A `.deb` file is an ar archive holding the `debian-binary` format
+//! version followed by two tarballs: `control.tar` with the control
+//! paragraph, maintainer scripts and other metadata, and `data.tar`
+//! with the files to install. Either may be compressed with gzip, xz
+//! or zstd.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use debian::deb::DebFile;
+//! use std::path::Path;
+//!
+//! let deb = DebFile::from_file(Path::new("foo_1.0-1_all.deb")).unwrap();
+//! let control = deb.control().unwrap();
+//! println!("{}", control.get_entry("Package").unwrap());
+//! for conffile in deb.conffiles().unwrap().shipped() {
+//!     println!("conffile: {conffile}");
+//! }
+//! ```
+
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, Read, Write};
+use std::path::Path;
+
+use crate::conffiles::Conffiles;
+use crate::maintainer_scripts::{MaintainerScript, ScriptKind};
+use crate::md5sums::Md5Sums;
+use crate::package::{ControlFile, ControlParagraph};
+
+mod builder;
+
+pub use self::builder::DebBuilder;
+
+/// The compression of a tarball within a package.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Compression {
+    None,
+    Gzip,
+    Xz,
+    Zstd,
+}
+
+/// The type of an entry of the data tarball.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DataEntryType {
+    File,
+    Dir,
+    Symlink,
+    HardLink,
+    CharDevice,
+    BlockDevice,
+    Fifo,
+    Other,
+}
+
+/// An entry of the data tarball, i.e. a path the package installs.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DataEntry {
+    /// the absolute path, like `/usr/bin/foo`
+    pub path: String,
+    pub entry_type: DataEntryType,
+    pub mode: u32,
+    pub user: String,
+    pub uid: u64,
+    pub group: String,
+    pub gid: u64,
+    pub size: u64,
+    pub mtime: u64,
+    /// the target of a symbolic or hard link
+    pub link_target: Option<String>,
+}
+
+/// A binary package, read into memory.
+///
+/// The files of the control member are kept decompressed, while the
+/// data member is only decompressed on access.
+#[derive(Debug, Clone)]
+pub struct DebFile {
+    format_version: String,
+    control_compression: Compression,
+    control_files: Vec<(String, Vec<u8>)>,
+    data_compression: Compression,
+    data: Vec<u8>,
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+impl Compression {
+    /// Determines the compression from the extension of a member name
+    /// following `.tar`, like `.xz` for `data.tar.xz`.
+    pub fn from_extension(ext: &str) -> Option<Compression> {
+        match ext {
+            "" => Some(Compression::None),
+            ".gz" => Some(Compression::Gzip),
+            ".xz" => Some(Compression::Xz),
+            ".zst" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    /// The extension to append to `.tar`, if any.
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            Compression::None => "",
+            Compression::Gzip => ".gz",
+            Compression::Xz => ".xz",
+            Compression::Zstd => ".zst",
+        }
+    }
+
+    /// Wraps a reader, decompressing its contents.
+    pub fn decoder<'a, R: Read + 'a>(
+        &self,
+        reader: R,
+    ) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(match *self {
+            Compression::None => Box::new(reader),
+            Compression::Gzip => Box::new(flate2::read::GzDecoder::new(reader)),
+            Compression::Xz => Box::new(xz2::read::XzDecoder::new(reader)),
+            Compression::Zstd => Box::new(zstd::Decoder::new(reader)?),
+        })
+    }
+
+    /// Compresses data at the default level of dpkg-deb.
+    pub fn compress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match *self {
+            Compression::None => Ok(data.to_vec()),
+            Compression::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(
+                    vec![],
+                    flate2::Compression::best(),
+                );
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Compression::Xz => {
+                let mut encoder = xz2::write::XzEncoder::new(vec![], 6);
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Compression::Zstd => zstd::encode_all(data, 3),
+        }
+    }
+}
+
+/// Normalizes a path of the data tarball, like `./usr/bin/`, to an
+/// absolute one, like `/usr/bin`.
+fn absolute_path(path: &str) -> String {
+    let path = path.trim_start_matches('.').trim_matches('/');
+    format!("/{path}")
+}
+
+impl DataEntry {
+    pub(crate) fn from_tar<R: Read>(
+        entry: &tar::Entry<R>,
+    ) -> io::Result<DataEntry> {
+        let header = entry.header();
+        let entry_type = match header.entry_type() {
+            tar::EntryType::Regular | tar::EntryType::Continuous => {
+                DataEntryType::File
+            }
+            tar::EntryType::Directory => DataEntryType::Dir,
+            tar::EntryType::Symlink => DataEntryType::Symlink,
+            tar::EntryType::Link => DataEntryType::HardLink,
+            tar::EntryType::Char => DataEntryType::CharDevice,
+            tar::EntryType::Block => DataEntryType::BlockDevice,
+            tar::EntryType::Fifo => DataEntryType::Fifo,
+            _ => DataEntryType::Other,
+        };
+        let name = |n: Option<&[u8]>| {
+            String::from_utf8_lossy(n.unwrap_or_default()).to_string()
+        };
+        let link_target = entry
+            .link_name()?
+            .map(|l| l.to_string_lossy().to_string())
+            .map(|l| match entry_type {
+                DataEntryType::HardLink => absolute_path(&l),
+                _ => l,
+            });
+        Ok(DataEntry {
+            path: absolute_path(&entry.path()?.to_string_lossy()),
+            entry_type,
+            mode: header.mode()?,
+            user: name(header.username_bytes()),
+            uid: header.uid()?,
+            group: name(header.groupname_bytes()),
+            gid: header.gid()?,
+            size: header.size()?,
+            mtime: header.mtime()?,
+            link_target,
+        })
+    }
+}
+
+fn member_compression(name: &str, prefix: &str) -> io::Result<Compression> {
+    Compression::from_extension(&name[prefix.len()..])
+        .ok_or_else(|| invalid("unsupported compression of package member"))
+}
+
+fn read_control_tar<R: Read>(reader: R) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let mut result = vec![];
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?.to_string_lossy().to_string();
+        let name = path.trim_start_matches("./").to_string();
+        let mut contents = vec![];
+        entry.read_to_end(&mut contents)?;
+        result.push((name, contents));
+    }
+    Ok(result)
+}
+
+impl DebFile {
+    /// Reads a package from disk.
+    pub fn from_file(path: &Path) -> io::Result<DebFile> {
+        DebFile::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Reads a package from any reader.
+    ///
+    /// Members with names starting with an underscore are ignored, as
+    /// dpkg does.
+    pub fn from_reader<R: Read>(reader: R) -> io::Result<DebFile> {
+        let mut archive = ar::Archive::new(reader);
+        let mut format_version = None;
+        let mut control = None;
+        let mut data = None;
+        while let Some(entry) = archive.next_entry() {
+            let mut entry = entry?;
+            let name = String::from_utf8_lossy(entry.header().identifier())
+                .to_string();
+            if format_version.is_none() {
+                if name != "debian-binary" {
+                    return Err(invalid("missing debian-binary member"));
+                }
+                let mut version = String::new();
+                entry.read_to_string(&mut version)?;
+                let version = version.trim_end().to_string();
+                if !version.starts_with("2.") {
+                    return Err(invalid("unsupported package format version"));
+                }
+                format_version = Some(version);
+            } else if name.starts_with("control.tar") {
+                if control.is_some() || data.is_some() {
+                    return Err(invalid("unexpected control member"));
+                }
+                let compression = member_compression(&name, "control.tar")?;
+                let files = read_control_tar(compression.decoder(&mut entry)?)?;
+                control = Some((compression, files));
+            } else if name.starts_with("data.tar") {
+                if control.is_none() || data.is_some() {
+                    return Err(invalid("unexpected data member"));
+                }
+                let compression = member_compression(&name, "data.tar")?;
+                let mut contents = vec![];
+                entry.read_to_end(&mut contents)?;
+                data = Some((compression, contents));
+            } else if !name.starts_with('_') {
+                return Err(invalid("unknown member in package"));
+            }
+        }
+        match (format_version, control, data) {
+            (
+                Some(format_version),
+                Some((control_compression, control_files)),
+                Some((data_compression, data)),
+            ) => Ok(DebFile {
+                format_version,
+                control_compression,
+                control_files,
+                data_compression,
+                data,
+            }),
+            (None, _, _) => Err(invalid("missing debian-binary member")),
+            (_, None, _) => Err(invalid("missing control member")),
+            (_, _, None) => Err(invalid("missing data member")),
+        }
+    }
+
+    /// The format version given by `debian-binary`, usually `2.0`.
+    pub fn format_version(&self) -> &str {
+        &self.format_version
+    }
+
+    /// The compression of the control member.
+    pub fn control_compression(&self) -> Compression {
+        self.control_compression
+    }
+
+    /// The compression of the data member.
+    pub fn data_compression(&self) -> Compression {
+        self.data_compression
+    }
+
+    /// Names of all files in the control member, like `control` or
+    /// `postinst`.
+    pub fn control_file_names(&self) -> impl Iterator<Item = &str> {
+        self.control_files.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Get the contents of a file in the control member.
+    pub fn control_file(&self, name: &str) -> Option<&[u8]> {
+        self.control_files
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, contents)| contents.as_slice())
+    }
+
+    fn control_text(&self, name: &str) -> io::Result<Option<&str>> {
+        match self.control_file(name) {
+            Some(contents) => std::str::from_utf8(contents)
+                .map(Some)
+                .map_err(|_| invalid("control file is not valid UTF-8")),
+            None => Ok(None),
+        }
+    }
+
+    /// Parses the control paragraph of the package.
+    pub fn control(&self) -> io::Result<ControlParagraph> {
+        let text = self
+            .control_text("control")?
+            .ok_or_else(|| invalid("missing control file"))?;
+        let cf = ControlFile::from_reader(text.as_bytes())?;
+        match cf.get_paragraphs().as_slice() {
+            [para] => Ok(para.clone()),
+            _ => Err(invalid("expected a single control paragraph")),
+        }
+    }
+
+    /// The package's conffiles, as listed in `conffiles`.
+    pub fn conffiles(&self) -> io::Result<Conffiles> {
+        match self.control_text("conffiles")? {
+            Some(text) => text.parse().map_err(invalid),
+            None => Ok(Conffiles::default()),
+        }
+    }
+
+    /// The MD5 digests of the installed files, as listed in `md5sums`.
+    pub fn md5sums(&self) -> io::Result<Md5Sums> {
+        match self.control_text("md5sums")? {
+            Some(text) => text.parse().map_err(invalid),
+            None => Ok(Md5Sums::default()),
+        }
+    }
+
+    /// Get a maintainer script, if the package has it.
+    pub fn maintainer_script(
+        &self,
+        kind: ScriptKind,
+    ) -> Option<MaintainerScript> {
+        let contents = self.control_file(kind.name())?;
+        Some(MaintainerScript::new(
+            kind,
+            &String::from_utf8_lossy(contents),
+        ))
+    }
+
+    /// All maintainer scripts of the package.
+    pub fn maintainer_scripts(&self) -> Vec<MaintainerScript> {
+        ScriptKind::ALL
+            .iter()
+            .filter_map(|&kind| self.maintainer_script(kind))
+            .collect()
+    }
+
+    /// A reader for the decompressed data tarball.
+    pub fn data_reader(&self) -> io::Result<Box<dyn Read + '_>> {
+        self.data_compression.decoder(self.data.as_slice())
+    }
+
+    /// The data tarball, holding the files to install.
+    pub fn data_tar(&self) -> io::Result<tar::Archive<Box<dyn Read + '_>>> {
+        Ok(tar::Archive::new(self.data_reader()?))
+    }
+
+    /// Lists the entries of the data tarball, in order.
+    pub fn data_entries(&self) -> io::Result<Vec<DataEntry>> {
+        let mut archive = self.data_tar()?;
+        let entries = archive.entries()?;
+        entries.map(|e| DataEntry::from_tar(&e?)).collect()
+    }
+
+    /// Reads the contents of a regular file the package installs, given
+    /// by its path, like `/usr/bin/foo`. Returns `None` if there is no
+    /// such file.
+    pub fn read_data_file(&self, path: &str) -> io::Result<Option<Vec<u8>>> {
+        let path = absolute_path(path);
+        let mut archive = self.data_tar()?;
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let info = DataEntry::from_tar(&entry)?;
+            if info.path == path && info.entry_type == DataEntryType::File {
+                let mut contents = vec![];
+                entry.read_to_end(&mut contents)?;
+                return Ok(Some(contents));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Extracts the entries of the data tarball selected by the filter
+    /// into a directory, returning the number of entries extracted.
+    ///
+    /// Missing parent directories are created. Entries that would end
+    /// up outside of the directory are skipped.
+    pub fn extract_data<F>(&self, dest: &Path, filter: F) -> io::Result<usize>
+    where
+        F: Fn(&DataEntry) -> bool,
+    {
+        let mut count = 0;
+        let mut archive = self.data_tar()?;
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if filter(&DataEntry::from_tar(&entry)?) && entry.unpack_in(dest)? {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+}
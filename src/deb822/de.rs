@@ -0,0 +1,427 @@
+//! Deserialization of deb822 data with serde
+
+use std::io;
+use std::io::BufRead;
+use std::str::FromStr;
+
+use serde::de::value::StrDeserializer;
+use serde::de::{
+    DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess,
+    Visitor,
+};
+use serde::forward_to_deserialize_any;
+
+use super::{Deb822Reader, Error, Paragraph};
+
+impl serde::de::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Error {
+        Error {
+            msg: msg.to_string(),
+            line: None,
+        }
+    }
+}
+
+/// Deserializes deb822 data from a string.
+pub fn from_str<T: DeserializeOwned>(s: &str) -> Result<T, Error> {
+    from_reader(s.as_bytes())
+}
+
+/// Deserializes deb822 data from any buffered reader.
+pub fn from_reader<R: BufRead, T: DeserializeOwned>(
+    buf: R,
+) -> Result<T, Error> {
+    let paragraphs = Deb822Reader::new(buf).collect::<io::Result<Vec<_>>>()?;
+    T::deserialize(Deserializer {
+        paragraphs: &paragraphs,
+    })
+}
+
+/// Compares a field name to the name of a struct member, ignoring case
+/// and treating dashes and underscores alike.
+fn name_matches(field: &str, member: &str) -> bool {
+    let normalize = |c: u8| match c {
+        b'_' => b'-',
+        c => c.to_ascii_lowercase(),
+    };
+    field.len() == member.len()
+        && field
+            .bytes()
+            .zip(member.bytes())
+            .all(|(a, b)| normalize(a) == normalize(b))
+}
+
+/// Splits the value of a field into the elements of a sequence.
+fn split_list(value: &str) -> Vec<&str> {
+    let items: Vec<&str> = if value.contains(',') {
+        value.split(',').collect()
+    } else {
+        value.lines().collect()
+    };
+    items
+        .into_iter()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Deserializes all paragraphs, either as a sequence or as a single
+/// struct or map.
+struct Deserializer<'a> {
+    paragraphs: &'a [Paragraph],
+}
+
+/// Deserializes a single paragraph as a struct or map.
+struct ParagraphDeserializer<'a> {
+    para: &'a Paragraph,
+}
+
+/// Deserializes the value of a field or an element of it.
+struct ValueDeserializer<'a> {
+    value: &'a str,
+    line: usize,
+}
+
+struct ParagraphSeq<'a> {
+    iter: std::slice::Iter<'a, Paragraph>,
+}
+
+struct FieldMap<'a> {
+    para: &'a Paragraph,
+    /// the struct members to match field names against, if any
+    members: &'static [&'static str],
+    pos: usize,
+    value: String,
+}
+
+struct ValueSeq<'a> {
+    iter: std::vec::IntoIter<&'a str>,
+    line: usize,
+}
+
+impl<'de, 'a> serde::Deserializer<'de> for Deserializer<'a> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        self.deserialize_seq(v)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        v.visit_seq(ParagraphSeq {
+            iter: self.paragraphs.iter(),
+        })
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(
+        self,
+        v: V,
+    ) -> Result<V::Value, Error> {
+        if self.paragraphs.is_empty() {
+            v.visit_none()
+        } else {
+            v.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        v: V,
+    ) -> Result<V::Value, Error> {
+        v.visit_newtype_struct(self)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        self.single()?.deserialize_map(v)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        v: V,
+    ) -> Result<V::Value, Error> {
+        self.single()?.deserialize_struct(name, fields, v)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct tuple tuple_struct enum identifier
+        ignored_any
+    }
+}
+
+impl<'a> Deserializer<'a> {
+    fn single(self) -> Result<ParagraphDeserializer<'a>, Error> {
+        match self.paragraphs {
+            [para] => Ok(ParagraphDeserializer { para }),
+            _ => Err(Error {
+                msg: format!(
+                    "expected a single paragraph, found {}",
+                    self.paragraphs.len()
+                ),
+                line: self.paragraphs.get(1).map(|p| p.span.line),
+            }),
+        }
+    }
+}
+
+impl<'de, 'a> SeqAccess<'de> for ParagraphSeq<'a> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        match self.iter.next() {
+            Some(para) => seed
+                .deserialize(ParagraphDeserializer { para })
+                .map(Some)
+                .map_err(|e| e.at_line(para.span.line)),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+impl<'de, 'a> serde::Deserializer<'de> for ParagraphDeserializer<'a> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        self.deserialize_map(v)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        v.visit_map(FieldMap {
+            para: self.para,
+            members: &[],
+            pos: 0,
+            value: String::new(),
+        })
+        .map_err(|e| e.at_line(self.para.span.line))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        v: V,
+    ) -> Result<V::Value, Error> {
+        v.visit_map(FieldMap {
+            para: self.para,
+            members: fields,
+            pos: 0,
+            value: String::new(),
+        })
+        .map_err(|e| e.at_line(self.para.span.line))
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(
+        self,
+        v: V,
+    ) -> Result<V::Value, Error> {
+        v.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        v: V,
+    ) -> Result<V::Value, Error> {
+        v.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct enum identifier
+        ignored_any
+    }
+}
+
+impl<'de, 'a> MapAccess<'de> for FieldMap<'a> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        let field = match self.para.fields.get(self.pos) {
+            Some(field) => field,
+            None => return Ok(None),
+        };
+        self.pos += 1;
+        self.value = field.full_value();
+        let name = self
+            .members
+            .iter()
+            .find(|m| name_matches(&field.name, m))
+            .copied()
+            .unwrap_or(&field.name);
+        let key: StrDeserializer<Error> = name.into_deserializer();
+        seed.deserialize(key)
+            .map(Some)
+            .map_err(|e| e.at_line(field.span.line))
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Error> {
+        let line = self.para.fields[self.pos - 1].span.line;
+        seed.deserialize(ValueDeserializer {
+            value: &self.value,
+            line,
+        })
+        .map_err(|e| e.at_line(line))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.para.fields.len() - self.pos)
+    }
+}
+
+impl<'a> ValueDeserializer<'a> {
+    fn parse<T: FromStr>(&self, what: &str) -> Result<T, Error> {
+        self.value.parse().map_err(|_| Error {
+            msg: format!("invalid {}: {}", what, self.value),
+            line: Some(self.line),
+        })
+    }
+
+    fn seq(self, items: Vec<&'a str>) -> ValueSeq<'a> {
+        ValueSeq {
+            iter: items.into_iter(),
+            line: self.line,
+        }
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(
+                self,
+                v: V,
+            ) -> Result<V::Value, Error> {
+                v.$visit(self.parse(stringify!($method))?)
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> serde::Deserializer<'de> for ValueDeserializer<'a> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        v.visit_str(self.value)
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(
+        self,
+        v: V,
+    ) -> Result<V::Value, Error> {
+        match self.value.to_ascii_lowercase().as_str() {
+            "yes" => v.visit_bool(true),
+            "no" => v.visit_bool(false),
+            _ => Err(Error {
+                msg: format!("expected yes or no, found {}", self.value),
+                line: Some(self.line),
+            }),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(
+        self,
+        v: V,
+    ) -> Result<V::Value, Error> {
+        v.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        v: V,
+    ) -> Result<V::Value, Error> {
+        v.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        let items = split_list(self.value);
+        v.visit_seq(self.seq(items))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        v: V,
+    ) -> Result<V::Value, Error> {
+        let items = self.value.split_whitespace().collect();
+        v.visit_seq(self.seq(items))
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        v: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_tuple(len, v)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        v: V,
+    ) -> Result<V::Value, Error> {
+        let value: StrDeserializer<Error> = self.value.into_deserializer();
+        v.visit_enum(value)
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct map struct identifier
+        ignored_any
+    }
+}
+
+impl<'de, 'a> SeqAccess<'de> for ValueSeq<'a> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        match self.iter.next() {
+            Some(value) => seed
+                .deserialize(ValueDeserializer {
+                    value,
+                    line: self.line,
+                })
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
@@ -0,0 +1,171 @@
+//! Handling of data which is not valid UTF-8
+
+use std::io;
+use std::io::{BufRead, Read};
+
+use super::Span;
+
+/// How to treat lines which are not valid UTF-8.
+///
+/// Policy requires control data to be UTF-8, but real archives contain
+/// violations, mostly in Latin-1.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Encoding {
+    /// fail with an error pointing at the line
+    #[default]
+    Strict,
+    /// replace invalid sequences with U+FFFD and record a warning
+    Lossy,
+    /// decode the line as Latin-1 instead and record a warning
+    Latin1Fallback,
+}
+
+/// A line which is not valid UTF-8 and has been decoded according to
+/// the `Encoding`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct EncodingWarning {
+    /// the line as found in the original data
+    pub span: Span,
+    /// the byte offset of the first invalid byte
+    pub offset: usize,
+}
+
+/// An adapter decoding any buffered reader according to an `Encoding`,
+/// so that all parsers taking a `BufRead` can read data which is not
+/// valid UTF-8.
+///
+/// # Examples
+///
+/// ```
+/// use debian::deb822::{Decoder, Encoding};
+/// use debian::package::ControlFile;
+///
+/// let data = b"Package: foo\nMaintainer: J\xf6rg <j@example.org>\n";
+/// assert!(ControlFile::from_reader(&data[..]).is_err());
+///
+/// let mut decoder = Decoder::new(&data[..], Encoding::Latin1Fallback);
+/// let cf = ControlFile::from_reader(&mut decoder).unwrap();
+/// let para = &cf.get_paragraphs()[0];
+/// assert_eq!(para.get_entry("Maintainer"), Some("Jörg <j@example.org>"));
+/// assert_eq!(decoder.warnings()[0].span.line, 2);
+/// ```
+#[derive(Debug)]
+pub struct Decoder<R> {
+    buf: R,
+    encoding: Encoding,
+    /// the decoded current line and the position within it
+    line: Vec<u8>,
+    pos: usize,
+    /// the byte offset of the next line in the original data
+    offset: usize,
+    /// the number of lines read
+    count: usize,
+    warnings: Vec<EncodingWarning>,
+}
+
+impl Encoding {
+    /// Decodes a line, returning the text and, unless valid UTF-8, the
+    /// position of the first invalid byte.
+    pub(crate) fn decode(
+        self,
+        bytes: Vec<u8>,
+        line: usize,
+    ) -> io::Result<(String, Option<usize>)> {
+        let err = match String::from_utf8(bytes) {
+            Ok(text) => return Ok((text, None)),
+            Err(err) => err,
+        };
+        let invalid = err.utf8_error().valid_up_to();
+        let text = match self {
+            Encoding::Strict => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid UTF-8 in line {line} at byte {invalid}"),
+                ))
+            }
+            Encoding::Lossy => {
+                String::from_utf8_lossy(err.as_bytes()).into_owned()
+            }
+            Encoding::Latin1Fallback => {
+                err.as_bytes().iter().map(|&b| char::from(b)).collect()
+            }
+        };
+        Ok((text, Some(invalid)))
+    }
+}
+
+impl<R: BufRead> Decoder<R> {
+    /// Creates a decoder reading from any buffered reader.
+    pub fn new(buf: R, encoding: Encoding) -> Decoder<R> {
+        Decoder {
+            buf,
+            encoding,
+            line: vec![],
+            pos: 0,
+            offset: 0,
+            count: 0,
+            warnings: vec![],
+        }
+    }
+
+    /// The lines read so far which are not valid UTF-8.
+    pub fn warnings(&self) -> &[EncodingWarning] {
+        &self.warnings
+    }
+
+    /// Reads and decodes the next line, including its line break.
+    fn next_line(&mut self) -> io::Result<()> {
+        let mut bytes = vec![];
+        let len = self.buf.read_until(b'\n', &mut bytes)?;
+        self.line.clear();
+        self.pos = 0;
+        if len == 0 {
+            return Ok(());
+        }
+        let start = self.offset;
+        self.offset += len;
+        self.count += 1;
+        let line_break = bytes
+            .iter()
+            .rev()
+            .take_while(|&&b| b == b'\n' || b == b'\r');
+        let end = start + len - line_break.count();
+        let (text, invalid) = self.encoding.decode(bytes, self.count)?;
+        if let Some(invalid) = invalid {
+            self.warnings.push(EncodingWarning {
+                span: Span {
+                    start,
+                    end,
+                    line: self.count,
+                    end_line: self.count,
+                },
+                offset: start + invalid,
+            });
+        }
+        self.line = text.into_bytes();
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for Decoder<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(out.len());
+        out[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl<R: BufRead> BufRead for Decoder<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos >= self.line.len() {
+            self.next_line()?;
+        }
+        Ok(&self.line[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.line.len());
+    }
+}
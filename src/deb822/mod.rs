@@ -0,0 +1,395 @@
+//! Tokenizer for the deb822 format with source locations
+//!
+//! Control files, `.dsc` and `.changes` files, Release files, Packages
+//! and Sources indices and many others share the format described in
+//! deb822(5): paragraphs separated by blank lines, each consisting of
+//! fields like `Name: value`. Values may continue on the following
+//! lines, which start with a space or a tab. Lines starting with `#`
+//! are comments.
+//!
+//! The tokenizer splits text into paragraphs and fields, recording
+//! byte offsets and line numbers of every paragraph, field and
+//! continuation line, so that tools like linters can point at the
+//! exact source of a problem. `ControlFile` and the formats built on
+//! it use this module for parsing.
+//!
+//! Clearsigned text is accepted: the armor headers are skipped and the
+//! data ends at the signature.
+//!
+//! Data is expected to be UTF-8. Lines violating that cause an error,
+//! unless another `Encoding` is chosen with
+//! `Deb822Reader::with_encoding`. The `Decoder` adapter applies the
+//! same choice to any other parser reading from a `BufRead`.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::deb822::parse_str;
+//!
+//! let paragraphs =
+//!     parse_str("Source: foo\n\nPackage: foo\nDescription: bar\n baz\n");
+//! assert_eq!(paragraphs.len(), 2);
+//! let desc = paragraphs[1].get("description").unwrap();
+//! assert_eq!(desc.value, "bar");
+//! assert_eq!(desc.span.line, 4);
+//! assert_eq!(desc.continuations[0].span.line, 5);
+//! assert_eq!(desc.full_value(), "bar\n baz");
+//! ```
+//!
+//! With the `serde` feature, `from_str`, `from_reader` and `to_string`
+//! convert between deb822 data and types implementing `Deserialize` or
+//! `Serialize`:
+//!
+//! - A single paragraph maps to a struct or a map, multiple
+//!   paragraphs to a sequence of those.
+//! - Field names are matched ignoring case and with dashes matching
+//!   underscores, so a `build_depends` member picks up the
+//!   `Build-Depends` field. When serializing, member names without
+//!   upper case letters are written like `Build-Depends`.
+//! - Missing fields turn into `None` for `Option` members.
+//! - Sequences are separated by commas, like relationship lists, or
+//!   given one per line for multiline values without commas. Tuples
+//!   are separated by whitespace, so a `Vec<(String, u64, String)>`
+//!   reads the lines of a `Files` field. Other whitespace separated
+//!   lists, like `Architecture`, are best read as a `String`.
+//! - Booleans are written as `yes` and `no`.
+//!
+//! ```
+//! # #[cfg(feature = "serde")]
+//! # {
+//! use debian::deb822;
+//!
+//! #[derive(serde::Deserialize, serde::Serialize)]
+//! struct Package {
+//!     package: String,
+//!     version: debian::Version,
+//!     depends: Option<Vec<String>>,
+//!     essential: Option<bool>,
+//! }
+//!
+//! let packages: Vec<Package> = deb822::from_str(
+//!     "Package: foo\nVersion: 1.0-1\nDepends: bar (>= 2), baz\n\n\
+//!      Package: bar\nVersion: 2.0-1\nEssential: yes\n",
+//! )
+//! .unwrap();
+//! assert_eq!(packages[0].depends.as_ref().unwrap()[0], "bar (>= 2)");
+//! assert_eq!(packages[1].essential, Some(true));
+//! assert_eq!(
+//!     deb822::to_string(&packages[1]).unwrap(),
+//!     "Package: bar\nVersion: 2.0-1\nEssential: yes\n"
+//! );
+//! # }
+//! ```
+
+use std::fmt;
+use std::io;
+use std::io::BufRead;
+
+#[cfg(feature = "serde")]
+mod de;
+mod encoding;
+#[cfg(feature = "serde")]
+mod ser;
+
+#[cfg(feature = "serde")]
+pub use self::de::{from_reader, from_str};
+pub use self::encoding::{Decoder, Encoding, EncodingWarning};
+#[cfg(feature = "serde")]
+pub use self::ser::{to_string, to_writer};
+
+const SIGNED_MESSAGE_HEADER: &str = "-----BEGIN PGP SIGNED MESSAGE-----";
+const SIGNATURE_HEADER: &str = "-----BEGIN PGP SIGNATURE-----";
+
+/// A location in the source text.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Span {
+    /// the byte offset of the start
+    pub start: usize,
+    /// the byte offset just past the end, excluding any line break
+    pub end: usize,
+    /// the line of the start, counting from 1
+    pub line: usize,
+    /// the line of the end, counting from 1
+    pub end_line: usize,
+}
+
+/// A single line of text, without its line break.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Line {
+    pub text: String,
+    pub span: Span,
+}
+
+/// A field of a paragraph.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Field {
+    /// the name, as given before the colon
+    pub name: String,
+    /// the value given on the first line, trimmed
+    pub value: String,
+    /// the continuation lines, including their leading whitespace
+    pub continuations: Vec<Line>,
+    /// the whole field, including all continuation lines
+    pub span: Span,
+    pub name_span: Span,
+    /// the value given on the first line, which may be empty
+    pub value_span: Span,
+}
+
+/// A paragraph of fields.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Paragraph {
+    pub fields: Vec<Field>,
+    /// lines neither starting nor continuing a field, which are
+    /// otherwise skipped
+    pub invalid: Vec<Line>,
+    /// the lines from the first to the last of the paragraph
+    pub span: Span,
+}
+
+/// An error converting between deb822 data and other types.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Error {
+    pub msg: String,
+    /// the line of the paragraph or field causing the error, if known
+    pub line: Option<usize>,
+}
+
+/// A streaming tokenizer for deb822 data, yielding one paragraph at a
+/// time.
+#[derive(Debug)]
+pub struct Deb822Reader<R> {
+    buf: R,
+    /// the byte offset of the next line
+    offset: usize,
+    /// the number of lines read
+    line: usize,
+    /// whether the armor headers of a clearsigned message are read
+    in_armor: bool,
+    signed: bool,
+    done: bool,
+    encoding: Encoding,
+    warnings: Vec<EncodingWarning>,
+}
+
+impl Error {
+    /// Records the line causing the error, unless already known.
+    #[cfg(feature = "serde")]
+    fn at_line(mut self, line: usize) -> Error {
+        self.line.get_or_insert(line);
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.msg),
+            None => write!(f, "{}", self.msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error {
+            msg: err.to_string(),
+            line: None,
+        }
+    }
+}
+
+impl Span {
+    fn extend_to(&mut self, other: Span) {
+        self.end = other.end;
+        self.end_line = other.end_line;
+    }
+}
+
+impl Field {
+    /// The complete value, with continuation lines following the first
+    /// one on lines of their own, trimmed. This is the value stored by
+    /// `ControlParagraph`.
+    pub fn full_value(&self) -> String {
+        let mut result = self.value.clone();
+        for line in &self.continuations {
+            result.push('\n');
+            result.push_str(&line.text);
+        }
+        result.trim().to_string()
+    }
+
+    /// Parses the first line of a field, if it has a colon.
+    fn parse(line: &Line) -> Option<Field> {
+        let colon = line.text.find(':')?;
+        let rest = &line.text[colon + 1..];
+        let value = rest.trim();
+        let value_start = line.span.start
+            + colon
+            + 1
+            + (rest.len() - rest.trim_start().len());
+        Some(Field {
+            name: line.text[..colon].to_string(),
+            value: value.to_string(),
+            continuations: vec![],
+            span: line.span,
+            name_span: Span {
+                end: line.span.start + colon,
+                ..line.span
+            },
+            value_span: Span {
+                start: value_start,
+                end: value_start + value.len(),
+                ..line.span
+            },
+        })
+    }
+}
+
+impl Paragraph {
+    /// Look up a field by its name, ignoring case.
+    pub fn get(&self, name: &str) -> Option<&Field> {
+        self.fields
+            .iter()
+            .find(|f| f.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Adds a line to the paragraph.
+    fn push(&mut self, line: Line) {
+        self.span.extend_to(line.span);
+        if line.text.starts_with([' ', '\t']) {
+            if let Some(field) = self.fields.last_mut() {
+                field.span.extend_to(line.span);
+                field.continuations.push(line);
+                return;
+            }
+        } else if let Some(field) = Field::parse(&line) {
+            self.fields.push(field);
+            return;
+        }
+        self.invalid.push(line);
+    }
+}
+
+impl<R: BufRead> Deb822Reader<R> {
+    /// Creates a tokenizer reading from any buffered reader.
+    pub fn new(buf: R) -> Deb822Reader<R> {
+        Deb822Reader {
+            buf,
+            offset: 0,
+            line: 0,
+            in_armor: false,
+            signed: false,
+            done: false,
+            encoding: Encoding::default(),
+            warnings: vec![],
+        }
+    }
+
+    /// Sets how to treat lines which are not valid UTF-8, failing by
+    /// default. Offsets within such lines, like the start of a field
+    /// value, refer to the decoded text.
+    pub fn with_encoding(mut self, encoding: Encoding) -> Deb822Reader<R> {
+        self.encoding = encoding;
+        self
+    }
+
+    /// The lines read so far which are not valid UTF-8, with their
+    /// location in the original data.
+    pub fn warnings(&self) -> &[EncodingWarning] {
+        &self.warnings
+    }
+
+    /// Whether the data read so far started as a clearsigned message.
+    pub fn is_signed(&self) -> bool {
+        self.signed
+    }
+
+    /// Reads the next line, or `None` at the end of the data.
+    fn read_line(&mut self) -> io::Result<Option<Line>> {
+        let mut bytes = vec![];
+        let len = self.buf.read_until(b'\n', &mut bytes)?;
+        if len == 0 {
+            return Ok(None);
+        }
+        let start = self.offset;
+        self.offset += len;
+        self.line += 1;
+        if bytes.ends_with(b"\n") {
+            bytes.pop();
+            if bytes.ends_with(b"\r") {
+                bytes.pop();
+            }
+        }
+        let span = Span {
+            start,
+            end: start + bytes.len(),
+            line: self.line,
+            end_line: self.line,
+        };
+        let (text, invalid) = self.encoding.decode(bytes, self.line)?;
+        if let Some(invalid) = invalid {
+            self.warnings.push(EncodingWarning {
+                span,
+                offset: start + invalid,
+            });
+        }
+        Ok(Some(Line { text, span }))
+    }
+}
+
+impl<R: BufRead> Iterator for Deb822Reader<R> {
+    type Item = io::Result<Paragraph>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut para: Option<Paragraph> = None;
+        while !self.done {
+            let line = match self.read_line() {
+                Ok(Some(line)) => line,
+                Ok(None) => break,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            };
+            if line.span.line == 1 && line.text == SIGNED_MESSAGE_HEADER {
+                self.in_armor = true;
+                self.signed = true;
+                continue;
+            }
+            if self.in_armor {
+                self.in_armor = !line.text.trim().is_empty();
+                continue;
+            }
+            if self.signed && line.text.starts_with(SIGNATURE_HEADER) {
+                break;
+            }
+            if line.text.starts_with('#') {
+                continue;
+            }
+            if line.text.trim().is_empty() {
+                if para.is_some() {
+                    return para.map(Ok);
+                }
+                continue;
+            }
+            para.get_or_insert_with(|| Paragraph {
+                fields: vec![],
+                invalid: vec![],
+                span: line.span,
+            })
+            .push(line);
+        }
+        self.done = true;
+        para.map(Ok)
+    }
+}
+
+/// Splits text into paragraphs.
+pub fn parse_str(text: &str) -> Vec<Paragraph> {
+    Deb822Reader::new(text.as_bytes())
+        .map(|p| p.expect("reading valid UTF-8 from memory"))
+        .collect()
+}
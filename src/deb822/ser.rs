@@ -0,0 +1,594 @@
+//! Serialization to deb822 data with serde
+
+use std::fmt::Display;
+use std::io::Write;
+
+use serde::ser::{
+    Impossible, Serialize, SerializeMap, SerializeSeq, SerializeStruct,
+    SerializeTuple, SerializeTupleStruct,
+};
+
+use super::Error;
+
+impl serde::ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Error {
+        Error {
+            msg: msg.to_string(),
+            line: None,
+        }
+    }
+}
+
+/// Serializes a struct or map as a single paragraph, or a sequence of
+/// those as paragraphs separated by blank lines.
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, Error> {
+    let mut out = String::new();
+    value.serialize(Serializer { out: &mut out })?;
+    Ok(out)
+}
+
+/// Serializes like `to_string`, writing the result to `writer`.
+pub fn to_writer<W: Write, T: Serialize + ?Sized>(
+    mut writer: W,
+    value: &T,
+) -> Result<(), Error> {
+    writer.write_all(to_string(value)?.as_bytes())?;
+    Ok(())
+}
+
+fn unsupported(what: &str) -> Error {
+    serde::ser::Error::custom(format!("cannot serialize {what}"))
+}
+
+/// Turns the name of a struct member into a field name, capitalizing
+/// names given in lower case only, e.g. `build_depends` becomes
+/// `Build-Depends`.
+fn field_name(member: &str) -> String {
+    if member.chars().any(|c| c.is_uppercase()) {
+        return member.to_string();
+    }
+    member
+        .split(['-', '_'])
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(c) => c.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Writes a field, turning all but the first line of the value into
+/// continuation lines.
+fn write_field(out: &mut String, name: &str, value: &str) {
+    let mut lines = value.lines();
+    out.push_str(name);
+    out.push(':');
+    match lines.next() {
+        Some(first) if !first.is_empty() => {
+            out.push(' ');
+            out.push_str(first);
+        }
+        _ => (),
+    }
+    for line in lines {
+        out.push('\n');
+        if line.trim().is_empty() {
+            out.push_str(" .");
+        } else {
+            if !line.starts_with([' ', '\t']) {
+                out.push(' ');
+            }
+            out.push_str(line);
+        }
+    }
+    out.push('\n');
+}
+
+/// The formatted value of a field or an element of it.
+struct Text {
+    text: String,
+    /// whether the value is a whitespace separated tuple
+    tuple: bool,
+}
+
+impl Text {
+    fn new<S: ToString>(text: S) -> Option<Text> {
+        Some(Text {
+            text: text.to_string(),
+            tuple: false,
+        })
+    }
+}
+
+/// Serializes paragraphs.
+struct Serializer<'a> {
+    out: &'a mut String,
+}
+
+/// Serializes a single paragraph.
+struct ParagraphSerializer<'a> {
+    out: &'a mut String,
+}
+
+/// Serializes a value, yielding `None` for fields to be omitted.
+struct ValueSerializer;
+
+struct Paragraphs<'a> {
+    out: &'a mut String,
+    first: bool,
+}
+
+struct Fields<'a> {
+    out: &'a mut String,
+    key: Option<String>,
+}
+
+struct List {
+    items: Vec<Text>,
+    tuple: bool,
+}
+
+macro_rules! unsupported {
+    ($what:expr; $($method:ident($($arg:ty),*) -> $ret:ty;)*) => {
+        $(
+            fn $method(self, $(_: $arg),*) -> Result<$ret, Error> {
+                Err(unsupported($what))
+            }
+        )*
+    };
+}
+
+macro_rules! unsupported_scalars {
+    ($what:expr) => {
+        unsupported! { $what;
+            serialize_bool(bool) -> Self::Ok;
+            serialize_i8(i8) -> Self::Ok;
+            serialize_i16(i16) -> Self::Ok;
+            serialize_i32(i32) -> Self::Ok;
+            serialize_i64(i64) -> Self::Ok;
+            serialize_u8(u8) -> Self::Ok;
+            serialize_u16(u16) -> Self::Ok;
+            serialize_u32(u32) -> Self::Ok;
+            serialize_u64(u64) -> Self::Ok;
+            serialize_f32(f32) -> Self::Ok;
+            serialize_f64(f64) -> Self::Ok;
+            serialize_char(char) -> Self::Ok;
+            serialize_str(&str) -> Self::Ok;
+            serialize_bytes(&[u8]) -> Self::Ok;
+            serialize_unit() -> Self::Ok;
+            serialize_unit_struct(&'static str) -> Self::Ok;
+            serialize_unit_variant(&'static str, u32, &'static str)
+                -> Self::Ok;
+            serialize_tuple(usize) -> Self::SerializeTuple;
+            serialize_tuple_struct(&'static str, usize)
+                -> Self::SerializeTupleStruct;
+            serialize_tuple_variant(&'static str, u32, &'static str, usize)
+                -> Self::SerializeTupleVariant;
+            serialize_struct_variant(&'static str, u32, &'static str, usize)
+                -> Self::SerializeStructVariant;
+        }
+
+        fn serialize_newtype_variant<T: Serialize + ?Sized>(
+            self,
+            _name: &'static str,
+            _index: u32,
+            _variant: &'static str,
+            _value: &T,
+        ) -> Result<Self::Ok, Error> {
+            Err(unsupported($what))
+        }
+    };
+}
+
+impl<'a> serde::Serializer for Serializer<'a> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Paragraphs<'a>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Fields<'a>;
+    type SerializeStruct = Fields<'a>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    unsupported_scalars!("a value outside of a paragraph");
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(
+        self,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_seq(
+        self,
+        _len: Option<usize>,
+    ) -> Result<Paragraphs<'a>, Error> {
+        Ok(Paragraphs {
+            out: self.out,
+            first: true,
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Fields<'a>, Error> {
+        ParagraphSerializer { out: self.out }.serialize_map(len)
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Fields<'a>, Error> {
+        ParagraphSerializer { out: self.out }.serialize_struct(name, len)
+    }
+}
+
+impl<'a> SerializeSeq for Paragraphs<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), Error> {
+        if !self.first {
+            self.out.push('\n');
+        }
+        self.first = false;
+        value.serialize(ParagraphSerializer { out: self.out })
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a> serde::Serializer for ParagraphSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Fields<'a>;
+    type SerializeStruct = Fields<'a>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    unsupported_scalars!("a value as a paragraph");
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(
+        self,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_seq(
+        self,
+        _len: Option<usize>,
+    ) -> Result<Self::SerializeSeq, Error> {
+        Err(unsupported("nested paragraphs"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Fields<'a>, Error> {
+        Ok(Fields {
+            out: self.out,
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Fields<'a>, Error> {
+        Ok(Fields {
+            out: self.out,
+            key: None,
+        })
+    }
+}
+
+impl<'a> SerializeStruct for Fields<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        if let Some(value) = value.serialize(ValueSerializer)? {
+            write_field(self.out, &field_name(key), &value.text);
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a> SerializeMap for Fields<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(
+        &mut self,
+        key: &T,
+    ) -> Result<(), Error> {
+        let key = key
+            .serialize(ValueSerializer)?
+            .ok_or_else(|| unsupported("a missing field name"))?;
+        self.key = Some(key.text);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), Error> {
+        let key = self.key.take().unwrap_or_default();
+        if let Some(value) = value.serialize(ValueSerializer)? {
+            write_field(self.out, &key, &value.text);
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl serde::Serializer for ValueSerializer {
+    type Ok = Option<Text>;
+    type Error = Error;
+    type SerializeSeq = List;
+    type SerializeTuple = List;
+    type SerializeTupleStruct = List;
+    type SerializeTupleVariant = Impossible<Option<Text>, Error>;
+    type SerializeMap = Impossible<Option<Text>, Error>;
+    type SerializeStruct = Impossible<Option<Text>, Error>;
+    type SerializeStructVariant = Impossible<Option<Text>, Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<Option<Text>, Error> {
+        Ok(Text::new(if v { "yes" } else { "no" }))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Option<Text>, Error> {
+        Ok(Text::new(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Option<Text>, Error> {
+        Ok(Text::new(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Option<Text>, Error> {
+        Ok(Text::new(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Option<Text>, Error> {
+        Ok(Text::new(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Option<Text>, Error> {
+        Ok(Text::new(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Option<Text>, Error> {
+        Ok(Text::new(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Option<Text>, Error> {
+        Ok(Text::new(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Option<Text>, Error> {
+        Ok(Text::new(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Option<Text>, Error> {
+        Ok(Text::new(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Option<Text>, Error> {
+        Ok(Text::new(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Option<Text>, Error> {
+        Ok(Text::new(v))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Option<Text>, Error> {
+        Ok(Text::new(v))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Option<Text>, Error> {
+        match std::str::from_utf8(v) {
+            Ok(v) => Ok(Text::new(v)),
+            Err(_) => Err(unsupported("bytes that are not valid UTF-8")),
+        }
+    }
+
+    fn serialize_none(self) -> Result<Option<Text>, Error> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(
+        self,
+        value: &T,
+    ) -> Result<Option<Text>, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Option<Text>, Error> {
+        Ok(Text::new(""))
+    }
+
+    fn serialize_unit_struct(
+        self,
+        _name: &'static str,
+    ) -> Result<Option<Text>, Error> {
+        Ok(Text::new(""))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Option<Text>, Error> {
+        Ok(Text::new(variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Option<Text>, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Option<Text>, Error> {
+        Err(unsupported("an enum variant with data as a field"))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<List, Error> {
+        Ok(List {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+            tuple: false,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<List, Error> {
+        Ok(List {
+            items: Vec::with_capacity(len),
+            tuple: true,
+        })
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<List, Error> {
+        self.serialize_tuple(len)
+    }
+
+    unsupported! { "a nested structure as a field";
+        serialize_tuple_variant(&'static str, u32, &'static str, usize)
+            -> Self::SerializeTupleVariant;
+        serialize_map(Option<usize>) -> Self::SerializeMap;
+        serialize_struct(&'static str, usize) -> Self::SerializeStruct;
+        serialize_struct_variant(&'static str, u32, &'static str, usize)
+            -> Self::SerializeStructVariant;
+    }
+}
+
+impl List {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        if let Some(item) = value.serialize(ValueSerializer)? {
+            self.items.push(item);
+        }
+        Ok(())
+    }
+
+    /// Joins the items with whitespace for tuples, on lines of their
+    /// own for sequences of tuples or multiline values, and with commas
+    /// otherwise.
+    fn finish(self) -> Result<Option<Text>, Error> {
+        let texts = self.items.iter().map(|i| i.text.as_str());
+        let text = if self.tuple {
+            texts.collect::<Vec<_>>().join(" ")
+        } else if self.items.iter().any(|i| i.tuple || i.text.contains('\n')) {
+            texts.map(|t| format!("\n{t}")).collect()
+        } else {
+            texts.collect::<Vec<_>>().join(", ")
+        };
+        Ok(Some(Text {
+            text,
+            tuple: self.tuple,
+        }))
+    }
+}
+
+impl SerializeSeq for List {
+    type Ok = Option<Text>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Option<Text>, Error> {
+        self.finish()
+    }
+}
+
+impl SerializeTuple for List {
+    type Ok = Option<Text>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Option<Text>, Error> {
+        self.finish()
+    }
+}
+
+impl SerializeTupleStruct for List {
+    type Ok = Option<Text>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Option<Text>, Error> {
+        self.finish()
+    }
+}
@@ -0,0 +1,419 @@
+//! Comparing source and binary packages
+//!
+//! Like debdiff, compares two versions of a source package, given
+//! their `.dsc` files, or two binary packages. Instead of a textual
+//! diff, the result is a structured report of the control fields
+//! changed, the relations to other packages added, removed or changed,
+//! the files added, removed or modified and the maintainer scripts
+//! changed.
+//!
+//! Source packages are compared by the contents of all tarballs they
+//! consist of, which need to be present next to their `.dsc` files.
+//! Their maintainer scripts are the files like `debian/foo.postinst`.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use debian::debdiff::diff_dscs;
+//! use std::path::Path;
+//!
+//! let diff = diff_dscs(
+//!     Path::new("foo_1.2-1.dsc"),
+//!     Path::new("foo_1.2-2.dsc"),
+//! )
+//! .unwrap();
+//! for change in diff.dependencies {
+//!     println!("{}: {:?} -> {:?}", change.field, change.old, change.new);
+//! }
+//! for file in diff.files {
+//!     println!("{:?} {}", file.kind, file.path);
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+
+use crate::deb::{DataEntry, DebFile};
+use crate::dsc::{read_tarball, TreeEntry};
+use crate::filename::{ArtifactKind, ArtifactName};
+use crate::maintainer_scripts::ScriptKind;
+use crate::md5sums::md5_hex;
+use crate::package::{ControlFile, ControlParagraph};
+
+/// Relation fields of binary packages.
+pub const BINARY_RELATION_FIELDS: &[&str] = &[
+    "Pre-Depends",
+    "Depends",
+    "Recommends",
+    "Suggests",
+    "Enhances",
+    "Breaks",
+    "Conflicts",
+    "Replaces",
+    "Provides",
+    "Built-Using",
+    "Static-Built-Using",
+];
+
+/// Relation fields of source packages.
+pub const SOURCE_RELATION_FIELDS: &[&str] = &[
+    "Build-Depends",
+    "Build-Depends-Arch",
+    "Build-Depends-Indep",
+    "Build-Conflicts",
+    "Build-Conflicts-Arch",
+    "Build-Conflicts-Indep",
+];
+
+/// Fields of `.dsc` files not compared, as they list the files of the
+/// source package, which are compared by contents instead.
+const DSC_FILE_FIELDS: &[&str] =
+    &["Checksums-Sha1", "Checksums-Sha256", "Files"];
+
+/// How something differs between the two packages.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// A control field that differs.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// A relation to another package that differs, like a dependency. A
+/// relation on the same package with a different version or
+/// restriction counts as modified.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DependencyChange {
+    /// the relation field, like `Depends`
+    pub field: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// A file that differs, by path. For binary packages, changes to the
+/// permissions, owner or link target count as modifications.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FileChange {
+    pub path: String,
+    pub kind: ChangeKind,
+}
+
+/// A maintainer script that differs, with its contents.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ScriptChange {
+    /// the name of the script, like `postinst` for binary packages or
+    /// `debian/foo.postinst` for source packages
+    pub name: String,
+    pub kind: ScriptKind,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// The differences between two packages.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct DebDiff {
+    pub fields: Vec<FieldChange>,
+    pub dependencies: Vec<DependencyChange>,
+    /// changed files, sorted by path
+    pub files: Vec<FileChange>,
+    pub scripts: Vec<ScriptChange>,
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Determines the kind of a change from the old and new value.
+fn change_kind<T>(old: &Option<T>, new: &Option<T>) -> ChangeKind {
+    match (old, new) {
+        (None, _) => ChangeKind::Added,
+        (_, None) => ChangeKind::Removed,
+        _ => ChangeKind::Modified,
+    }
+}
+
+impl FieldChange {
+    pub fn kind(&self) -> ChangeKind {
+        change_kind(&self.old, &self.new)
+    }
+}
+
+impl DependencyChange {
+    pub fn kind(&self) -> ChangeKind {
+        change_kind(&self.old, &self.new)
+    }
+}
+
+impl ScriptChange {
+    pub fn kind(&self) -> ChangeKind {
+        change_kind(&self.old, &self.new)
+    }
+}
+
+impl DebDiff {
+    /// Whether the packages do not differ in any aspect compared.
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+            && self.dependencies.is_empty()
+            && self.files.is_empty()
+            && self.scripts.is_empty()
+    }
+}
+
+/// The name of the package a relation refers to, like `libc6` for
+/// `libc6 (>= 2.36) | libc6.1`.
+fn relation_name(relation: &str) -> &str {
+    let end = relation
+        .find(|c: char| c.is_whitespace() || "(|[<:".contains(c))
+        .unwrap_or(relation.len());
+    &relation[..end]
+}
+
+/// Splits a relation field into its normalized relations.
+fn split_relations(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|r| r.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|r| !r.is_empty())
+        .collect()
+}
+
+/// Compares a relation field, pairing relations on the same package.
+fn diff_relations(
+    field: &str,
+    old: Option<&str>,
+    new: Option<&str>,
+    out: &mut Vec<DependencyChange>,
+) {
+    let old = split_relations(old.unwrap_or(""));
+    let new = split_relations(new.unwrap_or(""));
+    let mut added: Vec<&String> =
+        new.iter().filter(|r| !old.contains(r)).collect();
+    for removed in old.iter().filter(|r| !new.contains(r)) {
+        let name = relation_name(removed);
+        let pos = added.iter().position(|a| relation_name(a) == name);
+        out.push(DependencyChange {
+            field: field.to_string(),
+            old: Some(removed.clone()),
+            new: pos.map(|pos| added.remove(pos).clone()),
+        });
+    }
+    out.extend(added.into_iter().map(|a| DependencyChange {
+        field: field.to_string(),
+        old: None,
+        new: Some(a.clone()),
+    }));
+}
+
+/// Compares two control paragraphs, splitting up relation fields and
+/// skipping the ones given.
+fn diff_paragraphs(
+    old: &ControlParagraph,
+    new: &ControlParagraph,
+    relation_fields: &[&str],
+    skip: &[&str],
+    diff: &mut DebDiff,
+) {
+    let mut keys: Vec<&str> = old.entries().map(|e| e.key()).collect();
+    for entry in new.entries() {
+        if !keys.contains(&entry.key()) {
+            keys.push(entry.key());
+        }
+    }
+    for key in keys {
+        let (a, b) = (old.get_entry(key), new.get_entry(key));
+        if skip.contains(&key) || a == b {
+            continue;
+        }
+        if relation_fields.contains(&key) {
+            diff_relations(key, a, b, &mut diff.dependencies);
+        } else {
+            diff.fields.push(FieldChange {
+                field: key.to_string(),
+                old: a.map(|v| v.to_string()),
+                new: b.map(|v| v.to_string()),
+            });
+        }
+    }
+}
+
+/// Compares two sets of files, given as some comparable value per path.
+fn diff_files<T: PartialEq>(
+    old: &BTreeMap<String, T>,
+    new: &BTreeMap<String, T>,
+) -> Vec<FileChange> {
+    let mut paths: Vec<&String> = old.keys().chain(new.keys()).collect();
+    paths.sort();
+    paths.dedup();
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let kind = match (old.get(path), new.get(path)) {
+                (Some(a), Some(b)) if a == b => return None,
+                (Some(_), Some(_)) => ChangeKind::Modified,
+                (None, _) => ChangeKind::Added,
+                (_, None) => ChangeKind::Removed,
+            };
+            Some(FileChange {
+                path: path.clone(),
+                kind,
+            })
+        })
+        .collect()
+}
+
+/// The entries of the data tarball of a binary package, along with the
+/// MD5 digests of regular files.
+fn data_files(
+    deb: &DebFile,
+) -> io::Result<BTreeMap<String, (DataEntry, Option<String>)>> {
+    let mut result = BTreeMap::new();
+    let mut archive = deb.data_tar()?;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let mut info = DataEntry::from_tar(&entry)?;
+        // timestamps change with every build
+        info.mtime = 0;
+        let digest = match entry.header().entry_type().is_file() {
+            true => {
+                let mut contents = vec![];
+                entry.read_to_end(&mut contents)?;
+                Some(md5_hex(&contents))
+            }
+            false => None,
+        };
+        result.insert(info.path.clone(), (info, digest));
+    }
+    Ok(result)
+}
+
+/// Compares two binary packages.
+pub fn diff_debs(old: &DebFile, new: &DebFile) -> io::Result<DebDiff> {
+    let mut diff = DebDiff::default();
+    diff_paragraphs(
+        &old.control()?,
+        &new.control()?,
+        BINARY_RELATION_FIELDS,
+        &[],
+        &mut diff,
+    );
+    diff.files = diff_files(&data_files(old)?, &data_files(new)?);
+    for kind in ScriptKind::ALL {
+        let a = old.maintainer_script(kind).map(|s| s.contents);
+        let b = new.maintainer_script(kind).map(|s| s.contents);
+        if a != b {
+            diff.scripts.push(ScriptChange {
+                name: kind.name().to_string(),
+                kind,
+                old: a,
+                new: b,
+            });
+        }
+    }
+    Ok(diff)
+}
+
+/// A source package read from its `.dsc` and the tarballs it lists.
+struct SourcePackage {
+    para: ControlParagraph,
+    files: BTreeMap<String, TreeEntry>,
+}
+
+impl SourcePackage {
+    fn read(dsc: &Path) -> io::Result<SourcePackage> {
+        let cf = ControlFile::from_file(dsc)?;
+        let para = match cf.get_paragraphs().as_slice() {
+            [para] => para.clone(),
+            _ => return Err(invalid(format!("invalid {}", dsc.display()))),
+        };
+        let dir = dsc.parent().unwrap_or_else(|| Path::new(""));
+        let mut files = BTreeMap::new();
+        let listed = para.get_entry("Files").unwrap_or_default();
+        for name in listed.lines().filter_map(|l| l.split_whitespace().nth(2)) {
+            let kind = ArtifactName::parse(name).map(|n| n.kind);
+            let (strip, base) = match kind {
+                Ok(ArtifactKind::OrigTarball {
+                    component,
+                    signature: false,
+                    ..
+                }) => (true, component.map(|c| format!("{c}/"))),
+                Ok(ArtifactKind::NativeTarball(_)) => (true, None),
+                Ok(ArtifactKind::DebianTarball(_)) => (false, None),
+                Ok(ArtifactKind::Diff) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("unsupported diff {name}"),
+                    ))
+                }
+                // signatures and unknown files
+                _ => continue,
+            };
+            let base = base.unwrap_or_default();
+            files.extend(read_tarball(&dir.join(name), strip, &base)?);
+        }
+        Ok(SourcePackage { para, files })
+    }
+
+    /// The maintainer scripts in the `debian` directory, by path.
+    fn scripts(&self) -> BTreeMap<&str, (ScriptKind, String)> {
+        let mut result = BTreeMap::new();
+        for (path, entry) in &self.files {
+            let name = match path.strip_prefix("debian/") {
+                Some(name) if !name.contains('/') => name,
+                _ => continue,
+            };
+            let suffix = name.rsplit('.').next().unwrap_or(name);
+            let kind = match suffix.parse::<ScriptKind>() {
+                Ok(kind) => kind,
+                Err(_) => continue,
+            };
+            if let TreeEntry::File(ref data, _) = *entry {
+                let contents = String::from_utf8_lossy(data).into_owned();
+                result.insert(path.as_str(), (kind, contents));
+            }
+        }
+        result
+    }
+}
+
+/// Compares two source packages, given their `.dsc` files.
+pub fn diff_dscs(old: &Path, new: &Path) -> io::Result<DebDiff> {
+    let old = SourcePackage::read(old)?;
+    let new = SourcePackage::read(new)?;
+    let mut diff = DebDiff::default();
+    diff_paragraphs(
+        &old.para,
+        &new.para,
+        SOURCE_RELATION_FIELDS,
+        DSC_FILE_FIELDS,
+        &mut diff,
+    );
+    diff.files = diff_files(&old.files, &new.files);
+    let (old_scripts, new_scripts) = (old.scripts(), new.scripts());
+    let mut names: Vec<&&str> =
+        old_scripts.keys().chain(new_scripts.keys()).collect();
+    names.sort();
+    names.dedup();
+    for name in names {
+        let (a, b) = (old_scripts.get(name), new_scripts.get(name));
+        if a == b {
+            continue;
+        }
+        diff.scripts.push(ScriptChange {
+            name: name.to_string(),
+            kind: a.or(b).map(|(kind, _)| *kind).unwrap(),
+            old: a.map(|(_, s)| s.clone()),
+            new: b.map(|(_, s)| s.clone()),
+        });
+    }
+    Ok(diff)
+}
@@ -0,0 +1,312 @@
+//! Parser for the debtags vocabulary and tags database
+//!
+//! Debtags classifies packages by faceted tags like `role::program` or
+//! `implemented-in::rust`, each naming a facet and a tag within it.
+//! The vocabulary, usually found at `/var/lib/debtags/vocabulary`,
+//! describes all facets and tags in paragraphs of `Facet` and `Tag`
+//! fields. The tags of packages are given in the `Tag` field of
+//! Packages indices, or in a database like
+//! `/var/lib/debtags/package-tags` with one line per package:
+//!
+//! ```text
+//! hello: role::program, implemented-in::c, interface::commandline
+//! ```
+//!
+//! Tags sharing a facet may be grouped like `use::{editing,viewing}`.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::debtags::{Tag, TagDatabase};
+//!
+//! let db: TagDatabase = "hello: role::program, implemented-in::{c,perl}\n"
+//!     .parse()
+//!     .unwrap();
+//! let tag: Tag = "implemented-in::perl".parse().unwrap();
+//! assert_eq!(tag.facet, "implemented-in");
+//! assert!(db.has_tag("hello", &tag));
+//! assert_eq!(db.packages_with(&tag), ["hello"]);
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::package::ControlFile;
+
+/// Default location of the debtags vocabulary.
+pub const DEBTAGS_VOCABULARY_PATH: &str = "/var/lib/debtags/vocabulary";
+
+/// Default location of the debtags database.
+pub const DEBTAGS_DATABASE_PATH: &str = "/var/lib/debtags/package-tags";
+
+/// A faceted tag, like `role::program`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+pub struct Tag {
+    /// the facet, like `role`
+    pub facet: String,
+    /// the tag within the facet, like `program`
+    pub name: String,
+}
+
+/// A facet described by the vocabulary.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Facet {
+    pub name: String,
+    /// the description, with the synopsis on the first line
+    pub description: String,
+    /// the status, like `needing-review`, if given
+    pub status: Option<String>,
+}
+
+/// A tag described by the vocabulary.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TagInfo {
+    pub tag: Tag,
+    /// the description, with the synopsis on the first line
+    pub description: String,
+}
+
+/// The debtags vocabulary.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Vocabulary {
+    pub facets: Vec<Facet>,
+    pub tags: Vec<TagInfo>,
+}
+
+/// The tags of packages.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct TagDatabase {
+    tags: BTreeMap<String, Vec<Tag>>,
+}
+
+impl FromStr for Tag {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (facet, name) = s.trim().split_once("::").ok_or("missing facet")?;
+        let valid = |s: &str| {
+            !s.is_empty()
+                && s.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+-._".contains(c))
+        };
+        if !valid(facet) || !valid(name) {
+            return Err("invalid tag");
+        }
+        Ok(Tag {
+            facet: facet.to_string(),
+            name: name.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}::{}", self.facet, self.name)
+    }
+}
+
+/// Parses a comma separated list of tags, like the value of a `Tag`
+/// field, expanding groups like `use::{editing,viewing}`.
+pub fn parse_tags(s: &str) -> Result<Vec<Tag>, &'static str> {
+    let mut result = vec![];
+    let mut rest = s.trim();
+    while !rest.is_empty() {
+        // commas within braces do not separate tags
+        let end = match (rest.find(','), rest.find('{')) {
+            (Some(comma), Some(brace)) if brace < comma => {
+                let close = rest.find('}').ok_or("unterminated group")?;
+                rest[close..].find(',').map(|c| close + c)
+            }
+            (comma, _) => comma,
+        };
+        let (item, next) = match end {
+            Some(end) => (&rest[..end], &rest[end + 1..]),
+            None => (rest, ""),
+        };
+        let item = item.trim();
+        match item.split_once("::{") {
+            Some((facet, group)) => {
+                let group = group.strip_suffix('}').ok_or("invalid group")?;
+                for name in group.split(',') {
+                    result.push(format!("{facet}::{}", name.trim()).parse()?);
+                }
+            }
+            None if item.is_empty() => {}
+            None => result.push(item.parse()?),
+        }
+        rest = next.trim_start();
+    }
+    Ok(result)
+}
+
+/// Formats tags as a comma separated list.
+fn format_tags(tags: &[Tag]) -> String {
+    let tags: Vec<String> = tags.iter().map(Tag::to_string).collect();
+    tags.join(", ")
+}
+
+impl Vocabulary {
+    /// Reads and parses a vocabulary from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<Vocabulary> {
+        Vocabulary::from_reader(io::BufReader::new(fs::File::open(in_file)?))
+    }
+
+    /// Parses a vocabulary from any buffered reader.
+    ///
+    /// Fails with `InvalidData` for paragraphs with neither a `Facet`
+    /// nor a valid `Tag` field.
+    pub fn from_reader<R: BufRead>(buf: R) -> io::Result<Vocabulary> {
+        let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let mut result = Vocabulary::default();
+        for para in ControlFile::from_reader(buf)?.get_paragraphs() {
+            let description =
+                para.get_entry("Description").unwrap_or("").to_string();
+            if let Some(tag) = para.get_entry("Tag") {
+                result.tags.push(TagInfo {
+                    tag: tag.parse().map_err(invalid)?,
+                    description,
+                });
+            } else if let Some(name) = para.get_entry("Facet") {
+                result.facets.push(Facet {
+                    name: name.to_string(),
+                    description,
+                    status: para.get_entry("Status").map(str::to_string),
+                });
+            } else {
+                return Err(invalid("neither a facet nor a tag"));
+            }
+        }
+        Ok(result)
+    }
+
+    /// Look up a facet by its name.
+    pub fn get_facet(&self, name: &str) -> Option<&Facet> {
+        self.facets.iter().find(|f| f.name == name)
+    }
+
+    /// Look up the description of a tag.
+    pub fn get_tag(&self, tag: &Tag) -> Option<&TagInfo> {
+        self.tags.iter().find(|t| t.tag == *tag)
+    }
+
+    /// The tags of a facet.
+    pub fn tags_of_facet<'a>(
+        &'a self,
+        facet: &'a str,
+    ) -> impl Iterator<Item = &'a TagInfo> {
+        self.tags.iter().filter(move |t| t.tag.facet == facet)
+    }
+
+    /// Whether the vocabulary describes a tag.
+    pub fn contains(&self, tag: &Tag) -> bool {
+        self.get_tag(tag).is_some()
+    }
+}
+
+impl TagDatabase {
+    /// Reads and parses a tags database from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<TagDatabase> {
+        fs::read_to_string(in_file)?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Serializes this database to disk.
+    pub fn to_file(&self, out_file: &Path) -> io::Result<()> {
+        fs::write(out_file, self.to_string())
+    }
+
+    /// Collects the tags given in the `Tag` fields of a Packages index.
+    pub fn from_packages(
+        packages: &ControlFile,
+    ) -> Result<TagDatabase, &'static str> {
+        let mut result = TagDatabase::default();
+        for para in packages.get_paragraphs() {
+            if let (Some(package), Some(tags)) =
+                (para.get_entry("Package"), para.get_entry("Tag"))
+            {
+                result.add_tags(package, parse_tags(tags)?);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Adds tags to a package, skipping ones it already has.
+    pub fn add_tags(&mut self, package: &str, tags: Vec<Tag>) {
+        let entry = self.tags.entry(package.to_string()).or_default();
+        for tag in tags {
+            if !entry.contains(&tag) {
+                entry.push(tag);
+            }
+        }
+    }
+
+    /// The tags of a package, in order.
+    pub fn tags_of(&self, package: &str) -> &[Tag] {
+        self.tags.get(package).map_or(&[], Vec::as_slice)
+    }
+
+    /// Whether a package has a tag.
+    pub fn has_tag(&self, package: &str, tag: &Tag) -> bool {
+        self.tags_of(package).contains(tag)
+    }
+
+    /// The packages with a tag, sorted by name.
+    pub fn packages_with(&self, tag: &Tag) -> Vec<&str> {
+        self.tags
+            .iter()
+            .filter(|(_, tags)| tags.contains(tag))
+            .map(|(p, _)| p.as_str())
+            .collect()
+    }
+
+    /// The packages with tags, sorted by name.
+    pub fn packages(&self) -> impl Iterator<Item = &str> {
+        self.tags.keys().map(String::as_str)
+    }
+
+    /// Checks the tags against a vocabulary, returning the packages
+    /// and tags not described by it.
+    pub fn validate(&self, vocabulary: &Vocabulary) -> Vec<(&str, &Tag)> {
+        let mut result = vec![];
+        for (package, tags) in &self.tags {
+            for tag in tags {
+                if !vocabulary.contains(tag) {
+                    result.push((package.as_str(), tag));
+                }
+            }
+        }
+        result
+    }
+}
+
+impl FromStr for TagDatabase {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut result = TagDatabase::default();
+        for line in s.lines().filter(|l| !l.trim().is_empty()) {
+            let (package, tags) = line.split_once(':').ok_or("missing tags")?;
+            let package = package.trim();
+            if package.is_empty() {
+                return Err("missing package name");
+            }
+            result.add_tags(package, parse_tags(tags)?);
+        }
+        Ok(result)
+    }
+}
+
+impl fmt::Display for TagDatabase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (package, tags) in &self.tags {
+            writeln!(f, "{package}: {}", format_tags(tags))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+//! Git branch and tag names following DEP-14
+//!
+//! DEP-14 recommends a layout for git repositories of Debian packages:
+//! packaging branches named after the vendor and the suite targeted,
+//! like `debian/latest` or `debian/bookworm-backports`, `upstream/latest`
+//! for the upstream sources and tags like `debian/1.2-3` for uploads and
+//! `upstream/1.2` for upstream releases.
+//!
+//! Versions are mangled to form valid git reference names: a colon
+//! becomes `%` and a tilde `_`, while `#` is inserted to break up
+//! sequences git does not allow, like `..` or a trailing `.lock`.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::dep14::{parse_version_tag, upstream_tag, version_tag};
+//! use debian::Version;
+//!
+//! let version = Version::parse("1:2.3~rc1-1").unwrap();
+//! assert_eq!(version_tag("debian", &version), "debian/1%2.3_rc1-1");
+//! assert_eq!(upstream_tag(&version), "upstream/2.3_rc1");
+//!
+//! let (vendor, parsed) = parse_version_tag("debian/1%2.3_rc1-1").unwrap();
+//! assert_eq!(vendor, "debian");
+//! assert_eq!(parsed.to_string(), "1:2.3~rc1-1");
+//! ```
+
+use crate::Version;
+
+/// The branch holding the latest upstream sources.
+pub const UPSTREAM_BRANCH: &str = "upstream/latest";
+
+/// The branch holding the data of pristine-tar.
+pub const PRISTINE_TAR_BRANCH: &str = "pristine-tar";
+
+/// The vendor name of upstream tags and branches.
+pub const UPSTREAM_VENDOR: &str = "upstream";
+
+/// Mangles a version for use in a git reference name.
+pub fn mangle_version(version: &str) -> String {
+    let mut result = version.replace(':', "%").replace('~', "_");
+    while result.contains("..") {
+        result = result.replace("..", ".#.");
+    }
+    if result.ends_with('.') {
+        result.push('#');
+    }
+    if let Some(base) = result.strip_suffix(".lock") {
+        result = format!("{base}.#lock");
+    }
+    result
+}
+
+/// Reverts the mangling of a version in a git reference name.
+pub fn unmangle_version(mangled: &str) -> String {
+    mangled.replace('#', "").replace('%', ":").replace('_', "~")
+}
+
+/// The tag of a packaging version for a vendor, like `debian/1.2-3`.
+pub fn version_tag(vendor: &str, version: &Version) -> String {
+    format!("{vendor}/{}", mangle_version(&version.to_string()))
+}
+
+/// The tag of the upstream part of a version, like `upstream/1.2`.
+pub fn upstream_tag(version: &Version) -> String {
+    format!("{UPSTREAM_VENDOR}/{}", mangle_version(&version.upstream()))
+}
+
+/// Parses a version tag into its vendor and version. For `upstream`
+/// tags, the version is the upstream version.
+pub fn parse_version_tag(tag: &str) -> Option<(String, Version)> {
+    let tag = tag.strip_prefix("refs/tags/").unwrap_or(tag);
+    let (vendor, mangled) = tag.split_once('/')?;
+    if vendor.is_empty() || mangled.contains('/') {
+        return None;
+    }
+    let version = Version::parse(&unmangle_version(mangled)).ok()?;
+    Some((vendor.to_string(), version))
+}
+
+/// The packaging branch of a vendor for a suite, like `debian/bookworm`,
+/// or `debian/latest` for the development suite if none is given.
+pub fn packaging_branch(vendor: &str, suite: Option<&str>) -> String {
+    format!("{vendor}/{}", suite.unwrap_or("latest"))
+}
+
+/// Suggests the packaging branch for an upload to a distribution, as
+/// given in the changelog. Uploads to `unstable` belong on the `latest`
+/// branch, while stable updates and security uploads share the branch
+/// of their release, like `debian/bookworm` for `bookworm-security`.
+/// Aliases like `stable` should be resolved to codenames before, as
+/// with `distro::DistroInfo::codename`.
+pub fn suggested_branch(vendor: &str, distribution: &str) -> String {
+    match distribution {
+        "unstable" | "sid" | "UNRELEASED" => packaging_branch(vendor, None),
+        _ => {
+            let suite = ["-security", "-proposed-updates", "-updates"]
+                .iter()
+                .find_map(|s| distribution.strip_suffix(s))
+                .unwrap_or(distribution);
+            packaging_branch(vendor, Some(suite))
+        }
+    }
+}
@@ -0,0 +1,372 @@
+//! Debian releases and suites
+//!
+//! Each Debian release has a codename, like `bookworm`, and over time
+//! moves through the suites: it starts as `testing`, becomes `stable`
+//! when released and then `oldstable` and `oldoldstable`, until its
+//! support ends. After the end of regular security support, some
+//! releases receive long term support (LTS) and extended LTS (ELTS).
+//! `unstable` always is `sid`.
+//!
+//! Which release a suite refers to depends on the date. The data on
+//! releases is built in, but can also be read from the CSV files of
+//! distro-info, like `/usr/share/distro-info/debian.csv`, which are
+//! updated more often.
+//!
+//! # Examples
+//!
+//! ```
+//! use chrono::NaiveDate;
+//! use debian::distro::{DistroInfo, Suite, SupportStatus};
+//!
+//! let info = DistroInfo::builtin();
+//! let date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+//! assert_eq!(info.codename("stable", date), Some("bookworm"));
+//! assert_eq!(info.codename("testing", date), Some("trixie"));
+//! assert_eq!(info.suite("bullseye", date), Some(Suite::OldStable));
+//!
+//! let buster = info.release("buster").unwrap();
+//! assert_eq!(buster.version.as_deref(), Some("10"));
+//! assert_eq!(buster.support_status(date), SupportStatus::Lts);
+//! ```
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+use chrono::NaiveDate;
+
+/// The path of the Debian data of distro-info.
+pub const DISTRO_INFO_DEBIAN: &str = "/usr/share/distro-info/debian.csv";
+
+/// The built-in release data, in the format of distro-info.
+const DEBIAN_CSV: &str = "\
+version,codename,series,created,release,eol,eol-lts,eol-elts
+1.1,Buzz,buzz,1993-08-16,1996-06-17,1997-06-05
+1.2,Rex,rex,1996-06-17,1996-12-12,1998-06-05
+1.3,Bo,bo,1996-12-12,1997-06-05,1999-03-09
+2.0,Hamm,hamm,1997-06-05,1998-07-24,2000-03-09
+2.1,Slink,slink,1998-07-24,1999-03-09,2000-10-30
+2.2,Potato,potato,1999-03-09,2000-08-15,2003-06-30
+3.0,Woody,woody,2000-08-15,2002-07-19,2006-06-30
+3.1,Sarge,sarge,2002-07-19,2005-06-06,2008-03-31
+4.0,Etch,etch,2005-06-06,2007-04-08,2010-02-15
+5.0,Lenny,lenny,2007-04-08,2009-02-14,2012-02-06
+6.0,Squeeze,squeeze,2009-02-14,2011-02-06,2014-05-31,2016-02-29
+7,Wheezy,wheezy,2011-02-06,2013-05-04,2016-04-25,2018-05-31,2020-06-30
+8,Jessie,jessie,2013-05-04,2015-04-26,2018-06-17,2020-06-30,2025-06-30
+9,Stretch,stretch,2015-04-26,2017-06-17,2020-07-18,2022-06-30,2027-06-30
+10,Buster,buster,2017-06-17,2019-07-06,2022-09-10,2024-06-30,2029-06-30
+11,Bullseye,bullseye,2019-07-06,2021-08-14,2024-08-14,2026-08-31,2031-06-30
+12,Bookworm,bookworm,2021-08-14,2023-06-10,2026-07-11,2028-06-30,2033-06-30
+13,Trixie,trixie,2023-06-10,2025-08-09,2028-08-09,2030-06-30,2035-06-30
+14,Forky,forky,2025-08-09
+15,Duke,duke,2027-08-01
+,Sid,sid,1993-08-16
+,Experimental,experimental,1993-08-16
+";
+
+/// The suites releases move through.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub enum Suite {
+    OldOldStable,
+    OldStable,
+    Stable,
+    Testing,
+    Unstable,
+    Experimental,
+}
+
+/// The support a release receives at some date.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum SupportStatus {
+    /// Not released yet, including `sid` and `experimental`.
+    Unreleased,
+    /// Regular security support by the Debian security team.
+    Supported,
+    /// Long term support.
+    Lts,
+    /// Extended long term support.
+    Elts,
+    /// No support anymore.
+    EndOfLife,
+}
+
+/// A Debian release, as listed by distro-info.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DistroRelease {
+    /// the version, like `12`, missing for `sid` and `experimental`
+    pub version: Option<String>,
+    /// the codename for display, like `Bookworm`
+    pub codename: String,
+    /// the codename as used for suites, like `bookworm`
+    pub series: String,
+    pub created: NaiveDate,
+    pub release: Option<NaiveDate>,
+    /// the end of regular security support
+    pub eol: Option<NaiveDate>,
+    pub eol_lts: Option<NaiveDate>,
+    pub eol_elts: Option<NaiveDate>,
+}
+
+/// Data on all Debian releases.
+#[derive(Debug, Clone)]
+pub struct DistroInfo {
+    releases: Vec<DistroRelease>,
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+impl Suite {
+    /// The name of the suite, like `oldstable`.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Suite::OldOldStable => "oldoldstable",
+            Suite::OldStable => "oldstable",
+            Suite::Stable => "stable",
+            Suite::Testing => "testing",
+            Suite::Unstable => "unstable",
+            Suite::Experimental => "experimental",
+        }
+    }
+}
+
+impl fmt::Display for Suite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Suite {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "oldoldstable" => Ok(Suite::OldOldStable),
+            "oldstable" => Ok(Suite::OldStable),
+            "stable" => Ok(Suite::Stable),
+            "testing" => Ok(Suite::Testing),
+            "unstable" => Ok(Suite::Unstable),
+            "experimental" => Ok(Suite::Experimental),
+            _ => Err("unknown suite"),
+        }
+    }
+}
+
+impl DistroRelease {
+    /// Whether the release has been released at the given date.
+    pub fn is_released(&self, date: NaiveDate) -> bool {
+        self.release.map_or(false, |r| r <= date)
+    }
+
+    /// The support the release receives at the given date.
+    pub fn support_status(&self, date: NaiveDate) -> SupportStatus {
+        if !self.is_released(date) {
+            return SupportStatus::Unreleased;
+        }
+        let before = |end: Option<NaiveDate>| end.map(|end| date < end);
+        match before(self.eol) {
+            None | Some(true) => SupportStatus::Supported,
+            Some(false) if before(self.eol_lts) == Some(true) => {
+                SupportStatus::Lts
+            }
+            Some(false) if before(self.eol_elts) == Some(true) => {
+                SupportStatus::Elts
+            }
+            Some(false) => SupportStatus::EndOfLife,
+        }
+    }
+
+    /// The name of the backports suite of the release, like
+    /// `bookworm-backports`.
+    pub fn backports_suite(&self) -> String {
+        format!("{}-backports", self.series)
+    }
+
+    /// The name of the security suite of the release, like
+    /// `bookworm-security`.
+    pub fn security_suite(&self) -> String {
+        format!("{}-security", self.series)
+    }
+}
+
+impl DistroInfo {
+    /// Release data built into the library.
+    pub fn builtin() -> DistroInfo {
+        DistroInfo::from_reader(DEBIAN_CSV.as_bytes())
+            .expect("valid built-in distro data")
+    }
+
+    /// Reads release data from the distro-info data of the system,
+    /// falling back to the built-in data if it is not available.
+    pub fn system() -> DistroInfo {
+        DistroInfo::from_file(Path::new(DISTRO_INFO_DEBIAN))
+            .unwrap_or_else(|_| DistroInfo::builtin())
+    }
+
+    /// Reads release data from a CSV file of distro-info.
+    pub fn from_file(path: &Path) -> io::Result<DistroInfo> {
+        DistroInfo::from_reader(File::open(path)?)
+    }
+
+    /// Reads release data in the CSV format of distro-info. Columns are
+    /// identified by the header line, which has to name at least the
+    /// `version`, `codename`, `series` and `created` columns.
+    pub fn from_reader<R: Read>(reader: R) -> io::Result<DistroInfo> {
+        let mut lines = BufReader::new(reader).lines();
+        let header = lines
+            .next()
+            .ok_or_else(|| invalid_data("missing header".to_string()))??;
+        let columns: Vec<String> =
+            header.split(',').map(|c| c.trim().to_string()).collect();
+        let column = |name: &str| columns.iter().position(|c| c == name);
+        let required = |name: &str| {
+            column(name)
+                .ok_or_else(|| invalid_data(format!("missing column {name}")))
+        };
+        let (version, codename, series, created) = (
+            required("version")?,
+            required("codename")?,
+            required("series")?,
+            required("created")?,
+        );
+        let (release, eol, eol_lts, eol_elts) = (
+            column("release"),
+            column("eol"),
+            column("eol-lts"),
+            column("eol-elts"),
+        );
+
+        let mut releases = vec![];
+        for line in lines {
+            let line = line?;
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+            let field = |i: Option<usize>| {
+                i.and_then(|i| fields.get(i)).filter(|f| !f.is_empty())
+            };
+            let date = |i: Option<usize>| {
+                field(i)
+                    .map(|f| {
+                        NaiveDate::parse_from_str(f, "%Y-%m-%d").map_err(|_| {
+                            invalid_data(format!("invalid date {f}"))
+                        })
+                    })
+                    .transpose()
+            };
+            releases.push(DistroRelease {
+                version: field(Some(version)).map(|f| f.to_string()),
+                codename: field(Some(codename)).unwrap_or(&"").to_string(),
+                series: field(Some(series))
+                    .ok_or_else(|| invalid_data(format!("no series: {line}")))?
+                    .to_string(),
+                created: date(Some(created))?.ok_or_else(|| {
+                    invalid_data(format!("no creation date: {line}"))
+                })?,
+                release: date(release)?,
+                eol: date(eol)?,
+                eol_lts: date(eol_lts)?,
+                eol_elts: date(eol_elts)?,
+            });
+        }
+        Ok(DistroInfo { releases })
+    }
+
+    /// All releases, in the order given, oldest first.
+    pub fn releases(&self) -> &[DistroRelease] {
+        &self.releases
+    }
+
+    /// Looks up a release by its codename, like `bookworm`.
+    pub fn release(&self, codename: &str) -> Option<&DistroRelease> {
+        self.releases.iter().find(|r| {
+            r.series == codename || r.codename.eq_ignore_ascii_case(codename)
+        })
+    }
+
+    /// Looks up a release by its version, like `12`. Point releases,
+    /// like `12.5`, are accepted for releases since Debian 7.
+    pub fn release_by_version(&self, version: &str) -> Option<&DistroRelease> {
+        self.releases.iter().find(|r| {
+            r.version.as_deref().map_or(false, |v| {
+                v == version
+                    || (!v.contains('.')
+                        && version.split('.').next() == Some(v))
+            })
+        })
+    }
+
+    /// Releases released at the given date, newest first.
+    fn released(
+        &self,
+        date: NaiveDate,
+    ) -> impl Iterator<Item = &DistroRelease> {
+        self.releases
+            .iter()
+            .rev()
+            .filter(move |r| r.version.is_some() && r.is_released(date))
+    }
+
+    /// The release a suite refers to at the given date.
+    pub fn resolve(
+        &self,
+        suite: Suite,
+        date: NaiveDate,
+    ) -> Option<&DistroRelease> {
+        match suite {
+            Suite::Stable => self.released(date).next(),
+            Suite::OldStable => self.released(date).nth(1),
+            Suite::OldOldStable => self.released(date).nth(2),
+            Suite::Testing => self.releases.iter().find(|r| {
+                r.version.is_some() && r.created <= date && !r.is_released(date)
+            }),
+            Suite::Unstable => self.release("sid"),
+            Suite::Experimental => self.release("experimental"),
+        }
+    }
+
+    /// The codename of a suite or codename at the given date, like
+    /// `bookworm` for `stable` in 2024. Codenames map to themselves and
+    /// `unstable` to `sid`.
+    pub fn codename(&self, name: &str, date: NaiveDate) -> Option<&str> {
+        let release = match name.parse::<Suite>() {
+            Ok(suite) => self.resolve(suite, date),
+            Err(_) => self.release(name),
+        };
+        release.map(|r| r.series.as_str())
+    }
+
+    /// The suite a release is part of at the given date, if any.
+    pub fn suite(&self, codename: &str, date: NaiveDate) -> Option<Suite> {
+        let release = self.release(codename)?;
+        [
+            Suite::Stable,
+            Suite::OldStable,
+            Suite::OldOldStable,
+            Suite::Testing,
+            Suite::Unstable,
+            Suite::Experimental,
+        ]
+        .into_iter()
+        .find(|&suite| self.resolve(suite, date) == Some(release))
+    }
+
+    /// The releases receiving any kind of support at the given date,
+    /// oldest first.
+    pub fn supported(&self, date: NaiveDate) -> Vec<&DistroRelease> {
+        self.releases
+            .iter()
+            .filter(|r| {
+                !matches!(
+                    r.support_status(date),
+                    SupportStatus::Unreleased | SupportStatus::EndOfLife
+                )
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,148 @@
+//! Parser and writer for the dpkg diversions database
+//!
+//! Diversions make dpkg install a file of a package under another
+//! name, so that another package or the local administrator can
+//! provide the original path. The database, usually found at
+//! `/var/lib/dpkg/diversions`, lists each diversion as three lines:
+//! the path diverted, the path it is diverted to and the package
+//! holding the diversion, or `:` for local diversions:
+//!
+//! ```text
+//! /usr/bin/foo
+//! /usr/bin/foo.distrib
+//! foo-plus
+//! ```
+//!
+//! A diversion applies to the files of all packages except the one
+//! holding it.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::diversions::Diversions;
+//!
+//! let diversions: Diversions =
+//!     "/usr/bin/foo\n/usr/bin/foo.distrib\nfoo-plus\n".parse().unwrap();
+//! let path = diversions.divert("/usr/bin/foo", "foo");
+//! assert_eq!(path, "/usr/bin/foo.distrib");
+//! assert_eq!(diversions.divert("/usr/bin/foo", "foo-plus"), "/usr/bin/foo");
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Default location of the dpkg diversions database.
+pub const DPKG_DIVERSIONS_PATH: &str = "/var/lib/dpkg/diversions";
+
+/// A single diversion.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Diversion {
+    /// the path diverted
+    pub path: String,
+    /// the path files are installed to instead
+    pub divert_to: String,
+    /// the package holding the diversion, `None` for local ones
+    pub package: Option<String>,
+}
+
+/// The complete diversions database.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Diversions {
+    pub diversions: Vec<Diversion>,
+}
+
+impl Diversion {
+    /// Whether the diversion was added by the local administrator.
+    pub fn is_local(&self) -> bool {
+        self.package.is_none()
+    }
+
+    /// Whether the diversion applies to the files of a package, i.e.
+    /// whether the package does not hold it.
+    pub fn applies_to(&self, package: &str) -> bool {
+        self.package.as_deref() != Some(package)
+    }
+}
+
+impl Diversions {
+    /// Reads and parses a diversions database from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<Diversions> {
+        fs::read_to_string(in_file)?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Serializes this database to disk.
+    pub fn to_file(&self, out_file: &Path) -> io::Result<()> {
+        fs::write(out_file, self.to_string())
+    }
+
+    /// Look up the diversion of a path.
+    pub fn get(&self, path: &str) -> Option<&Diversion> {
+        self.diversions.iter().find(|d| d.path == path)
+    }
+
+    /// Look up the diversion of a path to another one, if any.
+    pub fn get_diverted_to(&self, divert_to: &str) -> Option<&Diversion> {
+        self.diversions.iter().find(|d| d.divert_to == divert_to)
+    }
+
+    /// Iterate over the diversions held by a package.
+    pub fn by_package<'a>(
+        &'a self,
+        package: &'a str,
+    ) -> impl Iterator<Item = &'a Diversion> {
+        self.diversions
+            .iter()
+            .filter(move |d| d.package.as_deref() == Some(package))
+    }
+
+    /// The path a file of a package is installed to, taking diversions
+    /// into account.
+    pub fn divert<'a>(&'a self, path: &'a str, package: &str) -> &'a str {
+        match self.get(path) {
+            Some(d) if d.applies_to(package) => &d.divert_to,
+            _ => path,
+        }
+    }
+}
+
+impl FromStr for Diversions {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lines: Vec<&str> = s.lines().collect();
+        if lines.len() % 3 != 0 {
+            return Err("incomplete diversion");
+        }
+        let mut diversions = vec![];
+        for chunk in lines.chunks(3) {
+            if chunk.iter().any(|l| l.is_empty()) {
+                return Err("empty line in diversion");
+            }
+            diversions.push(Diversion {
+                path: chunk[0].to_string(),
+                divert_to: chunk[1].to_string(),
+                package: match chunk[2] {
+                    ":" => None,
+                    package => Some(package.to_string()),
+                },
+            });
+        }
+        Ok(Diversions { diversions })
+    }
+}
+
+impl fmt::Display for Diversions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for d in &self.diversions {
+            writeln!(f, "{}", d.path)?;
+            writeln!(f, "{}", d.divert_to)?;
+            writeln!(f, "{}", d.package.as_deref().unwrap_or(":"))?;
+        }
+        Ok(())
+    }
+}
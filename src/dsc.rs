@@ -0,0 +1,737 @@
+//! Building `3.0 (quilt)` source packages
+//!
+//! Like `dpkg-source -b`, generates the debian tarball and the `.dsc`
+//! file of an unpacked source package, next to its orig tarballs. The
+//! source name and version are taken from `debian/changelog`, the other
+//! fields of the `.dsc` from `debian/control`.
+//!
+//! Before building, the upstream files of the unpacked tree are checked
+//! against the orig tarballs with the patches of `debian/patches`
+//! applied, as far as recorded in `.pc/applied-patches`. Unlike
+//! dpkg-source, changes outside patches are never recorded as a new
+//! patch but always make the build fail, as with the
+//! `abort-on-upstream-changes` option. Upstream files deleted in the
+//! tree are ignored, like dpkg-source does by default.
+//!
+//! The `.dsc` file is written unsigned. With the `openpgp` feature, it
+//! can be signed with [`SecretKey::sign_file`] afterwards.
+//!
+//! [`SecretKey::sign_file`]: ../openpgp/struct.SecretKey.html#method.sign_file
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use debian::dsc::SourceBuilder;
+//! use std::path::Path;
+//!
+//! let builder = SourceBuilder::new(Path::new("foo-1.2")).unwrap();
+//! for path in builder.upstream_changes().unwrap() {
+//!     println!("modified outside patches: {path}");
+//! }
+//! let dsc = builder.build().unwrap();
+//! println!("built {}", dsc.display());
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use regex::Regex;
+
+use crate::buildopts::source_date_epoch;
+use crate::changes::ChangesFile;
+use crate::deb::Compression;
+use crate::filename::{ArtifactKind, ArtifactName};
+use crate::orig::{find_orig_tarballs, OrigTarball};
+use crate::package::{Changelog, ControlFile, ControlParagraph};
+use crate::quilt::{DirTree, MemoryTree, Quilt, Tree, PATCHES_DIR, PC_DIR};
+use crate::source::{SourceFormat, SourceOptions};
+use crate::Version;
+
+/// The files dpkg-source ignores by default when looking for upstream
+/// changes, as a regular expression on their path.
+pub const DEFAULT_DIFF_IGNORE: &str = concat!(
+    r"(?:^|/).*~$|(?:^|/)\.#.*$|(?:^|/)\..*\.sw.$|",
+    r"(?:^|/),,.*(?:$|/.*$)|",
+    r"(?:^|/)(?:DEADJOE|\.arch-inventory|\.(?:bzr|cvs|hg|git|mtn-)ignore)$|",
+    r"(?:^|/)(?:CVS|RCS|\.deps|\{arch\}|\.arch-ids|\.svn|",
+    r"\.hg(?:tags|sigs)?|_darcs|\.git(?:attributes|modules|review)?|",
+    r"\.mailmap|\.shelf|_MTN|\.be|\.bzr(?:\.backup|tags)?)(?:$|/.*$)"
+);
+
+/// Fields of the source paragraph copied to the `.dsc`, in order.
+const DSC_FIELDS: &[&str] = &[
+    "Maintainer",
+    "Uploaders",
+    "Homepage",
+    "Standards-Version",
+    "Vcs-Arch",
+    "Vcs-Browser",
+    "Vcs-Bzr",
+    "Vcs-Cvs",
+    "Vcs-Darcs",
+    "Vcs-Git",
+    "Vcs-Hg",
+    "Vcs-Mtn",
+    "Vcs-Svn",
+    "Testsuite",
+    "Testsuite-Triggers",
+    "Build-Depends",
+    "Build-Depends-Arch",
+    "Build-Depends-Indep",
+    "Build-Conflicts",
+    "Build-Conflicts-Arch",
+    "Build-Conflicts-Indep",
+];
+
+/// Fields whose value may span several lines in `debian/control` but
+/// is folded into a single line in the `.dsc`.
+const FOLDED_FIELDS: &[&str] = &[
+    "Uploaders",
+    "Testsuite-Triggers",
+    "Build-Depends",
+    "Build-Depends-Arch",
+    "Build-Depends-Indep",
+    "Build-Conflicts",
+    "Build-Conflicts-Arch",
+    "Build-Conflicts-Indep",
+];
+
+/// A file, with its permissions, or symlink of a source tree.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(crate) enum TreeEntry {
+    File(Vec<u8>, u32),
+    Symlink(String),
+}
+
+/// Builder for `3.0 (quilt)` source packages.
+#[derive(Debug, Clone)]
+pub struct SourceBuilder {
+    source_dir: PathBuf,
+    out_dir: PathBuf,
+    source: String,
+    version: Version,
+    /// the paragraphs of `debian/control`
+    control: Vec<ControlParagraph>,
+    ignore: Regex,
+    tar_ignore: Vec<Regex>,
+    compression: Compression,
+    mtime: u64,
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn invalid_input(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+/// Converts a shell pattern as used by `tar --exclude` to a regular
+/// expression matching a file name.
+fn glob_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    let mut re = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    Regex::new(&re)
+}
+
+/// Collects the files and symlinks below a directory, by path relative
+/// to the given root, skipping paths for which `skip` returns true.
+pub(crate) fn read_tree<F>(
+    root: &Path,
+    dir: &Path,
+    skip: &F,
+    out: &mut BTreeMap<String, TreeEntry>,
+) -> io::Result<()>
+where
+    F: Fn(&str) -> bool,
+{
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let rel = path
+            .strip_prefix(root)
+            .ok()
+            .and_then(|p| p.to_str())
+            .ok_or_else(|| invalid(format!("invalid path {}", path.display())))?
+            .to_string();
+        if skip(&rel) {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            read_tree(root, &path, skip, out)?;
+        } else if file_type.is_symlink() {
+            let target = fs::read_link(&path)?;
+            let target = target.to_string_lossy().into_owned();
+            out.insert(rel, TreeEntry::Symlink(target));
+        } else {
+            let mode = file_mode(&entry.metadata()?);
+            out.insert(rel, TreeEntry::File(fs::read(&path)?, mode));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn file_mode(meta: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    match meta.permissions().mode() & 0o111 {
+        0 => 0o644,
+        _ => 0o755,
+    }
+}
+
+#[cfg(not(unix))]
+fn file_mode(_meta: &fs::Metadata) -> u32 {
+    0o644
+}
+
+/// Writes files and symlinks below a directory, creating directories
+/// as needed.
+pub(crate) fn write_tree(
+    dir: &Path,
+    entries: &BTreeMap<String, TreeEntry>,
+) -> io::Result<()> {
+    for (path, entry) in entries {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap_or(dir))?;
+        match entry {
+            TreeEntry::File(data, mode) => {
+                fs::write(&path, data)?;
+                set_file_mode(&path, *mode)?;
+            }
+            TreeEntry::Symlink(target) => symlink(target, &path)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_file_mode(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn set_file_mode(_path: &Path, _mode: u32) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink(target: &str, path: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, path)
+}
+
+#[cfg(not(unix))]
+fn symlink(_target: &str, path: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("cannot create symlink {}", path.display()),
+    ))
+}
+
+/// Reads the files and symlinks of a tarball of a source package, by
+/// path prefixed with `base`. With `strip_top`, a single top level
+/// directory is dropped, as when unpacking orig tarballs.
+pub(crate) fn read_tarball(
+    path: &Path,
+    strip_top: bool,
+    base: &str,
+) -> io::Result<BTreeMap<String, TreeEntry>> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let compression = name
+        .rsplit_once(".tar")
+        .and_then(|(_, ext)| Compression::from_extension(ext))
+        .ok_or_else(|| {
+            invalid_input(format!(
+                "unsupported compression of {}",
+                path.display()
+            ))
+        })?;
+    let reader = compression.decoder(File::open(path)?)?;
+    let mut archive = tar::Archive::new(reader);
+    let mut entries = vec![];
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().into_owned();
+        let path = path.trim_start_matches("./").trim_end_matches('/');
+        if path.is_empty() || path.split('/').any(|p| p == "..") {
+            continue;
+        }
+        let path = path.to_string();
+        let tree_entry = match entry.header().entry_type() {
+            tar::EntryType::Regular | tar::EntryType::Continuous => {
+                let mode = match entry.header().mode()? & 0o111 {
+                    0 => 0o644,
+                    _ => 0o755,
+                };
+                let mut data = vec![];
+                entry.read_to_end(&mut data)?;
+                Some(TreeEntry::File(data, mode))
+            }
+            tar::EntryType::Symlink => {
+                let target = entry.link_name()?.unwrap_or_default();
+                Some(TreeEntry::Symlink(target.to_string_lossy().into_owned()))
+            }
+            _ => None,
+        };
+        entries.push((path, tree_entry));
+    }
+    // strip a common top level directory
+    let top = entries
+        .first()
+        .map(|(p, _)| p.split('/').next().unwrap_or(""));
+    let top = top.unwrap_or("").to_string();
+    let prefix = format!("{top}/");
+    let strip = strip_top
+        && entries
+            .iter()
+            .all(|(p, e)| p.starts_with(&prefix) || (*p == top && e.is_none()));
+    let mut result = BTreeMap::new();
+    for (path, entry) in entries {
+        let path = match strip {
+            true => match path.strip_prefix(&prefix) {
+                Some(p) => p.to_string(),
+                None => continue,
+            },
+            false => path,
+        };
+        if let Some(entry) = entry {
+            result.insert(format!("{base}{path}"), entry);
+        }
+    }
+    Ok(result)
+}
+
+/// Normalizes a field value spanning several lines into a single line.
+fn fold(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+impl SourceBuilder {
+    /// Creates a builder for an unpacked source package, given its
+    /// top level directory. The package is built next to it, where the
+    /// orig tarballs are expected. Fails if `SOURCE_DATE_EPOCH` is set
+    /// but not a number of seconds.
+    pub fn new(source_dir: &Path) -> io::Result<SourceBuilder> {
+        let debian_dir = source_dir.join("debian");
+        let format = SourceFormat::from_dir(&debian_dir)?;
+        if format != SourceFormat::Quilt {
+            return Err(invalid_input(format!(
+                "unsupported source format {format}"
+            )));
+        }
+        let changelog = Changelog::from_file(&debian_dir.join("changelog"))?;
+        let entry = changelog
+            .entries()
+            .first()
+            .ok_or_else(|| invalid("empty changelog".to_string()))?;
+        let version = Version::parse(entry.version()).map_err(|_| {
+            invalid(format!("invalid version {}", entry.version()))
+        })?;
+        if version.is_native() {
+            return Err(invalid_input(format!(
+                "native version {version} in a non-native package"
+            )));
+        }
+        let control = ControlFile::from_file(&debian_dir.join("control"))?
+            .get_paragraphs()
+            .clone();
+        let options = SourceOptions::from_dir(&debian_dir)?;
+        let mut ignore = vec![DEFAULT_DIFF_IGNORE];
+        ignore.extend(options.extend_diff_ignore());
+        let ignore = Regex::new(&ignore.join("|")).map_err(|_| {
+            invalid("invalid extend-diff-ignore option".to_string())
+        })?;
+        let tar_ignore = options
+            .tar_ignore()
+            .into_iter()
+            .map(glob_regex)
+            .collect::<Result<Vec<Regex>, _>>()
+            .map_err(|_| invalid("invalid tar-ignore option".to_string()))?;
+        let compression = match options.compression() {
+            None | Some("xz") => Compression::Xz,
+            Some("gzip") => Compression::Gzip,
+            Some(other) => {
+                return Err(invalid_input(format!(
+                    "unsupported compression {other}"
+                )))
+            }
+        };
+        let mtime = match source_date_epoch()? {
+            Some(epoch) => epoch,
+            None => entry.timestamp().timestamp().max(0) as u64,
+        };
+        Ok(SourceBuilder {
+            source_dir: source_dir.to_path_buf(),
+            out_dir: source_dir
+                .parent()
+                .unwrap_or_else(|| Path::new(""))
+                .to_path_buf(),
+            source: entry.package().to_string(),
+            version,
+            control,
+            ignore,
+            tar_ignore,
+            compression,
+            mtime,
+        })
+    }
+
+    /// Sets the directory to find the orig tarballs in and to write
+    /// the source package to, instead of the parent of the source
+    /// directory.
+    pub fn set_out_dir(&mut self, out_dir: &Path) {
+        self.out_dir = out_dir.to_path_buf();
+    }
+
+    /// Sets the compression of the debian tarball, which defaults to
+    /// the one given in `debian/source/options` or xz.
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.compression = compression;
+    }
+
+    /// Sets the modification time of all entries of the debian
+    /// tarball, which defaults to `SOURCE_DATE_EPOCH`, if set, or the
+    /// date of the latest changelog entry.
+    pub fn set_mtime(&mut self, mtime: u64) {
+        self.mtime = mtime;
+    }
+
+    /// The name of the source package.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The version of the source package.
+    pub fn version(&self) -> &Version {
+        &self.version
+    }
+
+    /// Finds the orig tarballs to build from.
+    pub fn orig_tarballs(&self) -> io::Result<Vec<OrigTarball>> {
+        let tarballs =
+            find_orig_tarballs(&self.out_dir, self.source(), &self.version)?;
+        if tarballs.first().map_or(true, |t| t.component().is_some()) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "no orig tarball for {} {}",
+                    self.source(),
+                    self.version.upstream()
+                ),
+            ));
+        }
+        Ok(tarballs)
+    }
+
+    fn is_ignored(&self, path: &str) -> bool {
+        path == PC_DIR || path.starts_with(".pc/") || self.ignore.is_match(path)
+    }
+
+    /// Lists the upstream files of the unpacked tree that differ from
+    /// the orig tarballs with the applied patches. These are files
+    /// changed or added outside of patches, which a source package
+    /// cannot represent.
+    pub fn upstream_changes(&self) -> io::Result<Vec<String>> {
+        let skip = |p: &str| p == "debian" || self.is_ignored(p);
+        let mut tree = BTreeMap::new();
+        read_tree(&self.source_dir, &self.source_dir, &skip, &mut tree)?;
+
+        let mut orig = BTreeMap::new();
+        for tarball in self.orig_tarballs()? {
+            let base = match tarball.component() {
+                Some(component) => format!("{component}/"),
+                None => String::new(),
+            };
+            orig.extend(read_tarball(&tarball.path, true, &base)?);
+        }
+        orig.retain(|p, _| !p.starts_with("debian/") && !self.is_ignored(p));
+
+        // apply the patches applied in the tree to the orig files
+        let applied =
+            Quilt::new(DirTree::new(&self.source_dir))?.applied().len();
+        let mut text_tree = MemoryTree::new();
+        let mut texts = BTreeSet::new();
+        for (path, entry) in &orig {
+            if let TreeEntry::File(data, _) = entry {
+                if let Ok(text) = std::str::from_utf8(data) {
+                    text_tree.insert(path, text);
+                    texts.insert(path.as_str());
+                }
+            }
+        }
+        let patches = DirTree::new(&self.source_dir);
+        for path in patches.list(PATCHES_DIR)? {
+            let path = format!("{PATCHES_DIR}/{path}");
+            if let Some(text) = patches.read(&path)? {
+                text_tree.insert(&path, &text);
+            }
+        }
+        let mut quilt = Quilt::new(text_tree)?;
+        for _ in 0..applied {
+            let report = quilt.push()?;
+            if !report.is_applied() {
+                return Err(invalid(format!(
+                    "patch {} does not apply to the orig tarball:\n{report}",
+                    report.patch.as_deref().unwrap_or_default()
+                )));
+            }
+        }
+        let patched = quilt.into_tree();
+
+        let mut changes = vec![];
+        for (path, entry) in &tree {
+            let unchanged = match (patched.get(path), orig.get(path), entry) {
+                (Some(text), _, TreeEntry::File(data, _)) => {
+                    data == text.as_bytes()
+                }
+                (Some(_), _, TreeEntry::Symlink(_)) => false,
+                // binary files and symlinks cannot be patched
+                (None, Some(TreeEntry::File(a, _)), TreeEntry::File(b, _)) => {
+                    !texts.contains(path.as_str()) && a == b
+                }
+                (None, Some(e), _) => e == entry,
+                (None, None, _) => false,
+            };
+            if !unchanged {
+                changes.push(path.clone());
+            }
+        }
+        for path in orig.keys() {
+            let patched_away =
+                texts.contains(path.as_str()) && patched.get(path).is_none();
+            if !patched_away && !tree.contains_key(path) {
+                warn!("ignoring deletion of file {path}");
+            }
+        }
+        Ok(changes)
+    }
+
+    fn tar_header(&self, entry_type: tar::EntryType, mode: u32) -> tar::Header {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(entry_type);
+        header.set_mode(mode);
+        header.set_mtime(self.mtime);
+        header.set_size(0);
+        header.set_uid(0);
+        header.set_gid(0);
+        // root names always fit into the header
+        let _ = header.set_username("root");
+        let _ = header.set_groupname("root");
+        header
+    }
+
+    /// Generates the uncompressed debian tarball, holding the `debian`
+    /// directory in sorted order, owned by root and with normalized
+    /// permissions.
+    pub fn debian_tarball(&self) -> io::Result<Vec<u8>> {
+        let skip = |p: &str| {
+            self.is_ignored(p)
+                || p.rsplit('/').next().map_or(false, |n| {
+                    self.tar_ignore.iter().any(|r| r.is_match(n))
+                })
+        };
+        let debian_dir = self.source_dir.join("debian");
+        let mut files = BTreeMap::new();
+        read_tree(&self.source_dir, &debian_dir, &skip, &mut files)?;
+        // directories holding files, including the debian directory
+        let mut dirs: Vec<String> = files
+            .keys()
+            .flat_map(|p| {
+                p.match_indices('/').map(move |(i, _)| p[..i].to_string())
+            })
+            .collect();
+        dirs.sort();
+        dirs.dedup();
+
+        let mut entries: Vec<(String, Option<&TreeEntry>)> = dirs
+            .into_iter()
+            .map(|d| (format!("{d}/"), None))
+            .chain(files.iter().map(|(p, e)| (p.clone(), Some(e))))
+            .collect();
+        entries.sort_by(|a, b| {
+            a.0.trim_end_matches('/').cmp(b.0.trim_end_matches('/'))
+        });
+
+        let mut tar = tar::Builder::new(vec![]);
+        for (path, entry) in entries {
+            match entry {
+                None => {
+                    let mut header =
+                        self.tar_header(tar::EntryType::Directory, 0o755);
+                    tar.append_data(&mut header, &path, io::empty())?;
+                }
+                Some(TreeEntry::File(data, mode)) => {
+                    let mut header =
+                        self.tar_header(tar::EntryType::Regular, *mode);
+                    header.set_size(data.len() as u64);
+                    tar.append_data(&mut header, &path, data.as_slice())?;
+                }
+                Some(TreeEntry::Symlink(target)) => {
+                    let mut header =
+                        self.tar_header(tar::EntryType::Symlink, 0o777);
+                    tar.append_link(&mut header, &path, target)?;
+                }
+            }
+        }
+        tar.into_inner()
+    }
+
+    /// The source paragraph of `debian/control`.
+    fn source_paragraph(&self) -> io::Result<&ControlParagraph> {
+        self.control
+            .first()
+            .filter(|p| p.has_entry("Source"))
+            .ok_or_else(|| invalid("missing source paragraph".to_string()))
+    }
+
+    /// The `Package-List` entries for the binary packages, like
+    /// `foo deb utils optional arch=any`.
+    fn package_list(&self, source: &ControlParagraph) -> Vec<String> {
+        self.control
+            .iter()
+            .skip(1)
+            .filter_map(|p| {
+                let name = p.get_entry("Package")?;
+                let get = |field, default| {
+                    p.get_entry(field)
+                        .or_else(|| source.get_entry(field))
+                        .unwrap_or(default)
+                };
+                let arch = p.get_entry("Architecture").unwrap_or("any");
+                let arch: Vec<&str> = arch.split_whitespace().collect();
+                Some(format!(
+                    "{name} {} {} {} arch={}",
+                    p.get_entry("Package-Type").unwrap_or("deb"),
+                    get("Section", "misc"),
+                    get("Priority", "optional"),
+                    arch.join(",")
+                ))
+            })
+            .collect()
+    }
+
+    /// The architectures of the binary packages: `any` if any package
+    /// is architecture dependent, followed by `all` if any is not.
+    fn architectures(&self) -> Vec<&str> {
+        let mut result: Vec<&str> = vec![];
+        for para in self.control.iter().skip(1) {
+            let arch = para.get_entry("Architecture").unwrap_or("any");
+            for a in arch.split_whitespace() {
+                if !result.contains(&a) {
+                    result.push(a);
+                }
+            }
+        }
+        if result.contains(&"any") {
+            result.retain(|a| *a == "any" || *a == "all");
+        }
+        result
+    }
+
+    /// Builds the source package, writing the debian tarball and the
+    /// `.dsc` file, and returns the path of the latter.
+    ///
+    /// Fails if the orig tarball is missing, or if any upstream file
+    /// was modified outside of patches.
+    pub fn build(&self) -> io::Result<PathBuf> {
+        let changes = self.upstream_changes()?;
+        if !changes.is_empty() {
+            return Err(invalid(format!(
+                "upstream files modified outside of patches: {}",
+                changes.join(", ")
+            )));
+        }
+        let source = self.source_paragraph()?;
+        if source.get_entry("Source") != Some(self.source()) {
+            return Err(invalid(format!(
+                "source name in debian/control differs from {}",
+                self.source()
+            )));
+        }
+
+        let mut files = vec![];
+        for tarball in self.orig_tarballs()? {
+            let name = tarball.name.to_string();
+            files.push(ChangesFile::new(
+                &name,
+                &fs::read(&tarball.path)?,
+                "",
+                "",
+            ));
+            if let Some(ref signature) = tarball.signature {
+                let name = tarball.signature_name();
+                files.push(ChangesFile::new(
+                    &name,
+                    &fs::read(signature)?,
+                    "",
+                    "",
+                ));
+            }
+        }
+        let extension = self.compression.extension().trim_start_matches('.');
+        let name = ArtifactName::new(
+            self.source(),
+            &self.version,
+            ArtifactKind::DebianTarball(extension.to_string()),
+        )
+        .to_string();
+        let tarball = self.compression.compress(&self.debian_tarball()?)?;
+        fs::write(self.out_dir.join(&name), &tarball)?;
+        files.push(ChangesFile::new(&name, &tarball, "", ""));
+
+        let dsc_name = ArtifactName::dsc(self.source(), &self.version);
+        let dsc_path = self.out_dir.join(dsc_name.to_string());
+        let mut writer = BufWriter::new(File::create(&dsc_path)?);
+        writeln!(writer, "Format: {}", SourceFormat::Quilt)?;
+        writeln!(writer, "Source: {}", self.source())?;
+        let binaries: Vec<&str> = self
+            .control
+            .iter()
+            .skip(1)
+            .filter_map(|p| p.get_entry("Package"))
+            .collect();
+        writeln!(writer, "Binary: {}", binaries.join(", "))?;
+        writeln!(writer, "Architecture: {}", self.architectures().join(" "))?;
+        writeln!(writer, "Version: {}", self.version)?;
+        for field in DSC_FIELDS {
+            if let Some(value) = source.get_entry(field) {
+                match FOLDED_FIELDS.contains(field) {
+                    true => writeln!(writer, "{field}: {}", fold(value))?,
+                    false => writeln!(writer, "{field}: {value}")?,
+                }
+            }
+        }
+        writeln!(writer, "Package-List:")?;
+        for line in self.package_list(source) {
+            writeln!(writer, " {line}")?;
+        }
+        writeln!(writer, "Checksums-Sha1:")?;
+        for f in &files {
+            writeln!(writer, " {} {} {}", f.sha1, f.size, f.name)?;
+        }
+        writeln!(writer, "Checksums-Sha256:")?;
+        for f in &files {
+            writeln!(writer, " {} {} {}", f.sha256, f.size, f.name)?;
+        }
+        writeln!(writer, "Files:")?;
+        for f in &files {
+            writeln!(writer, " {} {} {}", f.md5, f.size, f.name)?;
+        }
+        writer.flush()?;
+        Ok(dsc_path)
+    }
+}
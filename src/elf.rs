@@ -0,0 +1,173 @@
+//! Dynamic linking information of ELF objects
+//!
+//! Reads what matters for packaging shared libraries and the binaries
+//! using them from ELF executables and shared objects: the SONAME, the
+//! libraries needed, the run path to look for them in and the dynamic
+//! symbols with their versions.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use debian::elf::ElfObject;
+//! use std::path::Path;
+//!
+//! let object = ElfObject::from_file(Path::new("/usr/bin/ls")).unwrap();
+//! for soname in &object.needed {
+//!     println!("needs {soname}");
+//! }
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use object::elf::{DT_NEEDED, DT_RPATH, DT_RUNPATH, DT_SONAME};
+use object::read::elf::{Dyn, FileHeader, Sym};
+use object::{Endianness, FileKind};
+
+/// A symbol of the dynamic symbol table.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ElfSymbol {
+    pub name: String,
+    /// the symbol version, like `GLIBC_2.34`, if any
+    pub version: Option<String>,
+    /// whether the symbol is defined elsewhere, rather than by this
+    /// object
+    pub undefined: bool,
+    pub weak: bool,
+}
+
+/// The dynamic linking information of an ELF object.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ElfObject {
+    /// the SONAME, given for shared libraries
+    pub soname: Option<String>,
+    /// the SONAMEs of the libraries needed, in order
+    pub needed: Vec<String>,
+    /// the directories of the run path, as given, with `$ORIGIN`
+    /// unexpanded
+    pub runpath: Vec<String>,
+    /// the global symbols of the dynamic symbol table
+    pub symbols: Vec<ElfSymbol>,
+}
+
+fn invalid_data<E: ToString>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+impl ElfSymbol {
+    /// The name of the symbol as used in symbols files, like
+    /// `foo@FOO_1.0`, or `foo@Base` for unversioned symbols.
+    pub fn qualified_name(&self) -> String {
+        let version = self.version.as_deref().unwrap_or("Base");
+        format!("{}@{version}", self.name)
+    }
+}
+
+fn parse<Elf: FileHeader<Endian = Endianness>>(
+    data: &[u8],
+) -> object::read::Result<ElfObject> {
+    let header = Elf::parse(data)?;
+    let endian = header.endian()?;
+    let sections = header.sections(endian, data)?;
+    let mut result = ElfObject::default();
+
+    if let Some((entries, link)) = sections.dynamic(endian, data)? {
+        let strings = sections.strings(endian, data, link)?;
+        for entry in entries {
+            let value = || -> object::read::Result<String> {
+                let s = entry.string(endian, strings)?;
+                Ok(String::from_utf8_lossy(s).into_owned())
+            };
+            match entry.tag32(endian) {
+                Some(DT_NEEDED) => result.needed.push(value()?),
+                Some(DT_SONAME) => result.soname = Some(value()?),
+                // RUNPATH takes precedence over RPATH
+                Some(DT_RUNPATH) => {
+                    result.runpath =
+                        value()?.split(':').map(|s| s.to_string()).collect()
+                }
+                Some(DT_RPATH) if result.runpath.is_empty() => {
+                    result.runpath =
+                        value()?.split(':').map(|s| s.to_string()).collect()
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let symbols = sections.symbols(endian, data, object::elf::SHT_DYNSYM)?;
+    let versions = sections.versions(endian, data)?;
+    for (index, symbol) in symbols.enumerate() {
+        if symbol.is_local() || symbol.st_name(endian) == 0 {
+            continue;
+        }
+        let name = symbol.name(endian, symbols.strings())?;
+        let version = match versions {
+            Some(ref versions) => {
+                let index = versions.version_index(endian, index);
+                versions
+                    .version(index)?
+                    .map(|v| String::from_utf8_lossy(v.name()).into_owned())
+            }
+            None => None,
+        };
+        result.symbols.push(ElfSymbol {
+            name: String::from_utf8_lossy(name).into_owned(),
+            version,
+            undefined: symbol.is_undefined(endian),
+            weak: symbol.is_weak(),
+        });
+    }
+    Ok(result)
+}
+
+impl ElfObject {
+    /// Reads an ELF object from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<ElfObject> {
+        ElfObject::parse(&fs::read(in_file)?)
+    }
+
+    /// Parses the contents of an ELF object, failing with
+    /// `InvalidData` if it is none.
+    pub fn parse(data: &[u8]) -> io::Result<ElfObject> {
+        let result = match FileKind::parse(data).map_err(invalid_data)? {
+            FileKind::Elf32 => {
+                parse::<object::elf::FileHeader32<Endianness>>(data)
+            }
+            FileKind::Elf64 => {
+                parse::<object::elf::FileHeader64<Endianness>>(data)
+            }
+            _ => return Err(invalid_data("not an ELF object")),
+        };
+        result.map_err(invalid_data)
+    }
+
+    /// Whether a file starts like an ELF object.
+    pub fn is_elf(data: &[u8]) -> bool {
+        data.starts_with(b"\x7fELF")
+    }
+
+    /// The symbols this object needs from the libraries it uses.
+    pub fn undefined_symbols(&self) -> impl Iterator<Item = &ElfSymbol> {
+        self.symbols.iter().filter(|s| s.undefined)
+    }
+
+    /// The symbols this object provides.
+    pub fn defined_symbols(&self) -> impl Iterator<Item = &ElfSymbol> {
+        self.symbols.iter().filter(|s| !s.undefined)
+    }
+
+    /// The directories of the run path, with `$ORIGIN` expanded to the
+    /// directory of the object, given its path.
+    pub fn runpath_dirs(&self, path: &Path) -> Vec<String> {
+        let origin = path.parent().unwrap_or(Path::new("")).to_string_lossy();
+        self.runpath
+            .iter()
+            .filter(|d| !d.is_empty())
+            .map(|d| {
+                d.replace("${ORIGIN}", &origin).replace("$ORIGIN", &origin)
+            })
+            .collect()
+    }
+}
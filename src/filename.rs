@@ -0,0 +1,425 @@
+//! Names of the files making up packages and uploads
+//!
+//! Binary packages, source packages and uploads follow fixed naming
+//! schemes, combining the package name, its version without the epoch
+//! and, where applicable, the architecture, like
+//! `foo_1.2-1_amd64.deb`, `foo_1.2-1.dsc`, `foo_1.2-1_source.changes`
+//! or `foo_1.2.orig-docs.tar.xz` for an additional orig tarball of the
+//! component `docs`.
+//!
+//! apt keeps the epoch in the names of the packages it downloads,
+//! escaping the colon, like `foo_1%3a1.2-1_amd64.deb`. Parsing accepts
+//! both forms.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::filename::{ArtifactKind, ArtifactName};
+//! use debian::Version;
+//!
+//! let name = ArtifactName::parse("foo_1.2.orig-docs.tar.xz").unwrap();
+//! assert_eq!(name.package, "foo");
+//! assert_eq!(name.version.to_string(), "1.2");
+//! assert_eq!(
+//!     name.kind,
+//!     ArtifactKind::OrigTarball {
+//!         component: Some("docs".to_string()),
+//!         compression: "xz".to_string(),
+//!         signature: false,
+//!     }
+//! );
+//!
+//! let version = Version::parse("1:1.2-1").unwrap();
+//! let deb = ArtifactName::binary("foo", &version, "amd64");
+//! assert_eq!(deb.to_string(), "foo_1.2-1_amd64.deb");
+//! assert_eq!(deb.apt_cache_name(), "foo_1%3a1.2-1_amd64.deb");
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::Version;
+
+/// The compressions allowed for tarballs of source packages.
+pub const TARBALL_COMPRESSIONS: [&str; 4] = ["gz", "bz2", "lzma", "xz"];
+
+/// The kinds of binary packages.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum BinaryType {
+    /// a regular binary package, `.deb`
+    Deb,
+    /// a package for the installer, `.udeb`
+    Udeb,
+    /// a package of debug symbols, `.ddeb`
+    Ddeb,
+}
+
+/// The kind of a file, with the parts of its name besides package name
+/// and version.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum ArtifactKind {
+    /// a binary package for an architecture
+    Binary(BinaryType, String),
+    /// the description of a source package
+    Dsc,
+    /// the description of an upload, for an architecture like `amd64`,
+    /// `source` or `multi`
+    Changes(String),
+    /// the build information of a build, for an architecture
+    Buildinfo(String),
+    /// an upstream tarball, possibly of an additional component, or its
+    /// OpenPGP signature, named after the upstream version
+    OrigTarball {
+        component: Option<String>,
+        compression: String,
+        /// whether this is the detached `.asc` signature
+        signature: bool,
+    },
+    /// the `debian` directory of a `3.0 (quilt)` source package
+    DebianTarball(String),
+    /// the tarball of a native source package
+    NativeTarball(String),
+    /// the Debian changes of a `1.0` source package, `.diff.gz`
+    Diff,
+}
+
+/// The name of a package or upload file, split into its parts.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ArtifactName {
+    pub package: String,
+    /// the version, which for orig tarballs is the upstream version
+    pub version: Version,
+    pub kind: ArtifactKind,
+}
+
+/// Escapes a version for a file name as apt does, replacing colons,
+/// underscores and percent signs by their percent-encoded form.
+pub fn escape_version(version: &str) -> String {
+    let mut result = String::with_capacity(version.len());
+    for c in version.chars() {
+        match c {
+            ':' | '_' | '%' => result.push_str(&format!("%{:02x}", c as u8)),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// Reverts percent-encoding in a file name. Invalid escapes are kept.
+pub fn unescape(name: &str) -> String {
+    let mut result = Vec::with_capacity(name.len());
+    let bytes = name.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let decoded = match bytes[i] {
+            b'%' => name
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match decoded {
+            Some(b) => {
+                result.push(b);
+                i += 3;
+            }
+            None => {
+                result.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&result).into_owned()
+}
+
+fn valid_package(name: &str) -> bool {
+    let bytes = name.as_bytes();
+    bytes.len() >= 2
+        && bytes.iter().enumerate().all(|(i, &b)| {
+            b.is_ascii_lowercase()
+                || b.is_ascii_digit()
+                || (i > 0 && matches!(b, b'+' | b'.' | b'-'))
+        })
+}
+
+/// Splits the base name of an orig tarball, like `1.0.orig-docs`, into
+/// the upstream version and the component, if any.
+fn split_orig(base: &str) -> Option<(&str, Option<&str>)> {
+    base.rmatch_indices(".orig").find_map(|(i, _)| {
+        let component = match &base[i + 5..] {
+            "" => None,
+            rest => Some(rest.strip_prefix('-')?),
+        };
+        let valid = component.map_or(true, |c| {
+            !c.is_empty()
+                && c.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        });
+        match i > 0 && valid {
+            true => Some((&base[..i], component)),
+            false => None,
+        }
+    })
+}
+
+impl BinaryType {
+    /// The extension of files of this type, without the dot.
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            BinaryType::Deb => "deb",
+            BinaryType::Udeb => "udeb",
+            BinaryType::Ddeb => "ddeb",
+        }
+    }
+}
+
+impl ArtifactName {
+    pub fn new(
+        package: &str,
+        version: &Version,
+        kind: ArtifactKind,
+    ) -> ArtifactName {
+        ArtifactName {
+            package: package.to_string(),
+            version: version.clone(),
+            kind,
+        }
+    }
+
+    /// The name of a `.deb` package.
+    pub fn binary(
+        package: &str,
+        version: &Version,
+        arch: &str,
+    ) -> ArtifactName {
+        let kind = ArtifactKind::Binary(BinaryType::Deb, arch.to_string());
+        ArtifactName::new(package, version, kind)
+    }
+
+    /// The name of the `.dsc` file of a source package.
+    pub fn dsc(source: &str, version: &Version) -> ArtifactName {
+        ArtifactName::new(source, version, ArtifactKind::Dsc)
+    }
+
+    /// The name of a `.changes` file, for an architecture like `source`.
+    pub fn changes(
+        source: &str,
+        version: &Version,
+        arch: &str,
+    ) -> ArtifactName {
+        let kind = ArtifactKind::Changes(arch.to_string());
+        ArtifactName::new(source, version, kind)
+    }
+
+    /// The name of a `.buildinfo` file.
+    pub fn buildinfo(
+        source: &str,
+        version: &Version,
+        arch: &str,
+    ) -> ArtifactName {
+        let kind = ArtifactKind::Buildinfo(arch.to_string());
+        ArtifactName::new(source, version, kind)
+    }
+
+    /// The name of an orig tarball, or the one of an additional
+    /// component, for the upstream part of a version.
+    pub fn orig_tarball(
+        source: &str,
+        version: &Version,
+        component: Option<&str>,
+        compression: &str,
+    ) -> ArtifactName {
+        let kind = ArtifactKind::OrigTarball {
+            component: component.map(|c| c.to_string()),
+            compression: compression.to_string(),
+            signature: false,
+        };
+        // shared by all revisions, so only the upstream version matters
+        let upstream = Version::parse(&version.upstream())
+            .unwrap_or_else(|_| version.clone());
+        ArtifactName::new(source, &upstream, kind)
+    }
+
+    /// Parses a file name, which may be percent-encoded.
+    pub fn parse(name: &str) -> Result<ArtifactName, &'static str> {
+        let name = unescape(name);
+        let (package, rest) =
+            name.split_once('_').ok_or("no version in file name")?;
+        if !valid_package(package) {
+            return Err("invalid package name");
+        }
+
+        let (base, ext) = rest.rsplit_once('.').ok_or("unknown file type")?;
+        let binary = match ext {
+            "deb" => Some(BinaryType::Deb),
+            "udeb" => Some(BinaryType::Udeb),
+            "ddeb" => Some(BinaryType::Ddeb),
+            _ => None,
+        };
+        let with_arch = || -> Result<(String, String), &'static str> {
+            let (version, arch) =
+                base.split_once('_').ok_or("no architecture in file name")?;
+            match arch.is_empty() || arch.contains('_') {
+                true => Err("invalid architecture"),
+                false => Ok((version.to_string(), arch.to_string())),
+            }
+        };
+        let (version, kind) = match (binary, ext) {
+            (Some(binary), _) => {
+                let (version, arch) = with_arch()?;
+                (version, ArtifactKind::Binary(binary, arch))
+            }
+            (None, "dsc") => (base.to_string(), ArtifactKind::Dsc),
+            (None, "changes") => {
+                let (version, arch) = with_arch()?;
+                (version, ArtifactKind::Changes(arch))
+            }
+            (None, "buildinfo") => {
+                let (version, arch) = with_arch()?;
+                (version, ArtifactKind::Buildinfo(arch))
+            }
+            _ => ArtifactName::parse_source_file(rest)?,
+        };
+
+        if version.contains('_') {
+            return Err("invalid version");
+        }
+        let version =
+            Version::parse(&version).map_err(|_| "invalid version")?;
+        Ok(ArtifactName {
+            package: package.to_string(),
+            version,
+            kind,
+        })
+    }
+
+    /// Parses the parts of the name of a tarball or diff after the
+    /// package name.
+    fn parse_source_file(
+        rest: &str,
+    ) -> Result<(String, ArtifactKind), &'static str> {
+        if let Some(version) = rest.strip_suffix(".diff.gz") {
+            return Ok((version.to_string(), ArtifactKind::Diff));
+        }
+        let (rest, signature) = match rest.strip_suffix(".asc") {
+            Some(rest) => (rest, true),
+            None => (rest, false),
+        };
+        let (base, compression) =
+            rest.rsplit_once(".tar.").ok_or("unknown file type")?;
+        if !TARBALL_COMPRESSIONS.contains(&compression) {
+            return Err("unknown compression");
+        }
+        let compression = compression.to_string();
+        if let Some((version, component)) = split_orig(base) {
+            let kind = ArtifactKind::OrigTarball {
+                component: component.map(|c| c.to_string()),
+                compression,
+                signature,
+            };
+            return Ok((version.to_string(), kind));
+        }
+        if signature {
+            return Err("signatures are only allowed for orig tarballs");
+        }
+        Ok(match base.strip_suffix(".debian") {
+            Some(version) => (
+                version.to_string(),
+                ArtifactKind::DebianTarball(compression),
+            ),
+            None => {
+                (base.to_string(), ArtifactKind::NativeTarball(compression))
+            }
+        })
+    }
+
+    /// The version as it appears in the file name: the upstream version
+    /// for orig tarballs, the version without epoch otherwise.
+    pub fn file_version(&self) -> String {
+        match self.kind {
+            ArtifactKind::OrigTarball { .. } => self.version.upstream(),
+            _ => self.version.without_epoch(),
+        }
+    }
+
+    /// The part of the file name after the version.
+    fn suffix(&self) -> String {
+        match self.kind {
+            ArtifactKind::Binary(binary, ref arch) => {
+                format!("_{arch}.{}", binary.extension())
+            }
+            ArtifactKind::Dsc => ".dsc".to_string(),
+            ArtifactKind::Changes(ref arch) => format!("_{arch}.changes"),
+            ArtifactKind::Buildinfo(ref arch) => format!("_{arch}.buildinfo"),
+            ArtifactKind::OrigTarball {
+                ref component,
+                ref compression,
+                signature,
+            } => {
+                let mut s = ".orig".to_string();
+                if let Some(component) = component {
+                    s.push('-');
+                    s.push_str(component);
+                }
+                s.push_str(".tar.");
+                s.push_str(compression);
+                if signature {
+                    s.push_str(".asc");
+                }
+                s
+            }
+            ArtifactKind::DebianTarball(ref compression) => {
+                format!(".debian.tar.{compression}")
+            }
+            ArtifactKind::NativeTarball(ref compression) => {
+                format!(".tar.{compression}")
+            }
+            ArtifactKind::Diff => ".diff.gz".to_string(),
+        }
+    }
+
+    /// The name of the file as apt stores downloaded packages, keeping
+    /// the epoch and escaping the version.
+    pub fn apt_cache_name(&self) -> String {
+        let version = match self.kind {
+            ArtifactKind::OrigTarball { .. } => self.version.upstream(),
+            _ => self.version.to_string(),
+        };
+        format!(
+            "{}_{}{}",
+            self.package,
+            escape_version(&version),
+            self.suffix()
+        )
+    }
+
+    /// Whether the file is part of a source package, as listed in its
+    /// `.dsc`.
+    pub fn is_source(&self) -> bool {
+        matches!(
+            self.kind,
+            ArtifactKind::OrigTarball { .. }
+                | ArtifactKind::DebianTarball(_)
+                | ArtifactKind::NativeTarball(_)
+                | ArtifactKind::Diff
+        )
+    }
+}
+
+impl fmt::Display for ArtifactName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}_{}{}",
+            self.package,
+            self.file_version(),
+            self.suffix()
+        )
+    }
+}
+
+impl FromStr for ArtifactName {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ArtifactName::parse(s)
+    }
+}
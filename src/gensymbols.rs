@@ -0,0 +1,308 @@
+//! Checking symbols files against built libraries
+//!
+//! Like dpkg-gensymbols, compares the symbols exported by a shared
+//! library just built with those listed in its symbols file. Symbols
+//! exported but not listed are new and get the version given as their
+//! minimal version, usually the version of the package being built.
+//! Symbols listed but no longer exported are lost, which breaks the
+//! ABI unless they are tagged `optional`.
+//!
+//! Symbols tagged with `arch`, `arch-bits` or `arch-endian` are only
+//! expected on the matching architectures. Patterns tagged `symver`
+//! match all symbols of a version node and those tagged `regex` all
+//! symbols matching the regular expression. Demangling is not
+//! supported, so `c++` patterns never match, but are not reported as
+//! lost either.
+//!
+//! The result can be applied to the symbols file, adding new symbols
+//! and marking lost ones with `#MISSING:`, or turned into a patch to
+//! review.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use debian::arch::DebianArchitecture;
+//! use debian::elf::ElfObject;
+//! use debian::gensymbols::diff_symbols;
+//! use debian::symbols::SymbolsFile;
+//! use std::path::Path;
+//!
+//! let arch = DebianArchitecture::parse("amd64").unwrap();
+//! let path = Path::new("debian/libfoo1.symbols");
+//! let symbols = SymbolsFile::from_file(path).unwrap();
+//! let object = ElfObject::from_file(Path::new(
+//!     "debian/libfoo1/usr/lib/x86_64-linux-gnu/libfoo.so.1",
+//! ))
+//! .unwrap();
+//! let diff = diff_symbols(&symbols, &object, &arch, "1.3").unwrap();
+//! if !diff.is_empty() {
+//!     print!("{}", diff.patch(&symbols, "debian/libfoo1.symbols"));
+//! }
+//! ```
+
+use std::io;
+
+use regex::Regex;
+
+use crate::arch::{DebianArchitecture, Endianness};
+use crate::elf::ElfObject;
+use crate::quilt::FilePatch;
+use crate::symbols::{SymbolEntry, SymbolsFile, SymbolsItem, SymbolsLibrary};
+
+/// Symbols defined by the linker rather than the library itself, which
+/// are never listed in symbols files.
+pub const IGNORED_SYMBOLS: &[&str] = &[
+    "_DYNAMIC",
+    "_GLOBAL_OFFSET_TABLE_",
+    "_PROCEDURE_LINKAGE_TABLE_",
+    "_SDA2_BASE_",
+    "_SDA_BASE_",
+    "__bss_end",
+    "__bss_end__",
+    "__bss_start",
+    "__bss_start__",
+    "__end__",
+    "__exidx_end",
+    "__exidx_start",
+    "__gnu_local_gp",
+    "_bss_end__",
+    "_edata",
+    "_end",
+    "_fbss",
+    "_fdata",
+    "_fini",
+    "_ftext",
+    "_gp_disp",
+    "_init",
+];
+
+/// The dependency template of libraries not yet in the symbols file.
+const DEFAULT_DEPENDENCY: &str = "#PACKAGE# #MINVER#";
+
+/// The differences between the symbols file of a library and the
+/// symbols it exports.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SymbolsDiff {
+    /// the SONAME of the library
+    pub soname: String,
+    /// the version new symbols get as their minimal version and lost
+    /// ones are marked missing in
+    pub version: String,
+    /// symbols exported, but not listed, including those listed as
+    /// missing before
+    pub added: Vec<SymbolEntry>,
+    /// symbols and patterns listed, but no longer exported
+    pub lost: Vec<SymbolEntry>,
+}
+
+/// The qualified names of the symbols a library exports, sorted, like
+/// `foo@FOO_1.0`, leaving out those defined by the linker.
+pub fn exported_symbols(object: &ElfObject) -> Vec<String> {
+    let mut result: Vec<String> = object
+        .defined_symbols()
+        .filter(|s| !IGNORED_SYMBOLS.contains(&s.name.as_str()))
+        .map(|s| s.qualified_name())
+        .collect();
+    result.sort();
+    result.dedup();
+    result
+}
+
+/// Whether a symbol is expected on an architecture, given its tags.
+fn applies_to(entry: &SymbolEntry, arch: &DebianArchitecture) -> bool {
+    if let Some(list) = entry.get_tag("arch") {
+        let wildcards: Vec<&str> = list.split_whitespace().collect();
+        let negated = wildcards.iter().any(|w| w.starts_with('!'));
+        let matched = wildcards
+            .iter()
+            .any(|w| arch.matches(w.trim_start_matches('!')));
+        if matched == negated {
+            return false;
+        }
+    }
+    if let Some(bits) = entry.get_tag("arch-bits") {
+        if bits != arch.bits().to_string() {
+            return false;
+        }
+    }
+    match entry.get_tag("arch-endian") {
+        Some("little") => arch.endianness() == Endianness::Little,
+        Some("big") => arch.endianness() == Endianness::Big,
+        _ => true,
+    }
+}
+
+/// A symbol of a symbols file, prepared for matching.
+enum Matcher<'a> {
+    Name(&'a str),
+    Version(&'a str),
+    Regex(Regex),
+    Unsupported,
+}
+
+impl<'a> Matcher<'a> {
+    fn new(entry: &'a SymbolEntry) -> io::Result<Matcher<'a>> {
+        if entry.has_tag("c++") {
+            Ok(Matcher::Unsupported)
+        } else if entry.has_tag("symver") {
+            Ok(Matcher::Version(&entry.name))
+        } else if entry.has_tag("regex") {
+            Regex::new(&entry.name).map(Matcher::Regex).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid pattern {}: {e}", entry.name),
+                )
+            })
+        } else {
+            Ok(Matcher::Name(&entry.name))
+        }
+    }
+
+    fn matches(&self, symbol: &str) -> bool {
+        match *self {
+            Matcher::Name(name) => symbol == name,
+            Matcher::Version(version) => {
+                symbol.rsplit_once('@').map(|(_, v)| v) == Some(version)
+            }
+            Matcher::Regex(ref regex) => regex.is_match(symbol),
+            Matcher::Unsupported => false,
+        }
+    }
+}
+
+/// Compares the symbols file of a library with the symbols it exports,
+/// for the given architecture. New symbols get the version given as
+/// their minimal version. A library not in the symbols file is compared
+/// with an empty list.
+///
+/// Fails with `InvalidInput` for objects without a SONAME and with
+/// `InvalidData` for invalid regular expressions.
+pub fn diff_symbols(
+    symbols: &SymbolsFile,
+    object: &ElfObject,
+    arch: &DebianArchitecture,
+    version: &str,
+) -> io::Result<SymbolsDiff> {
+    let soname = object.soname.as_deref().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "no shared library")
+    })?;
+    let empty = SymbolsLibrary::default();
+    let library = symbols.get_library(soname).unwrap_or(&empty);
+    let entries: Vec<&SymbolEntry> =
+        library.symbols().filter(|s| applies_to(s, arch)).collect();
+    let matchers = entries
+        .iter()
+        .map(|s| Matcher::new(s))
+        .collect::<io::Result<Vec<Matcher>>>()?;
+    let exported = exported_symbols(object);
+
+    let mut result = SymbolsDiff {
+        soname: soname.to_string(),
+        version: version.to_string(),
+        added: vec![],
+        lost: vec![],
+    };
+    for (entry, matcher) in entries.iter().zip(&matchers) {
+        let found = exported.iter().any(|s| matcher.matches(s));
+        match (found, &entry.missing) {
+            (true, Some(_)) => result.added.push(SymbolEntry {
+                missing: None,
+                ..(*entry).clone()
+            }),
+            (false, None) if !matches!(matcher, Matcher::Unsupported) => {
+                result.lost.push((*entry).clone())
+            }
+            _ => {}
+        }
+    }
+    for symbol in exported {
+        if !matchers.iter().any(|m| m.matches(&symbol)) {
+            result.added.push(SymbolEntry {
+                name: symbol,
+                min_version: version.to_string(),
+                ..SymbolEntry::default()
+            });
+        }
+    }
+    Ok(result)
+}
+
+impl SymbolsDiff {
+    /// Whether the symbols file matches the library.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.lost.is_empty()
+    }
+
+    /// Whether the library still exports all symbols listed, except
+    /// optional ones, so that binaries using it keep working.
+    pub fn is_compatible(&self) -> bool {
+        self.lost.iter().all(|s| s.is_optional())
+    }
+
+    /// Updates a symbols file, adding new symbols next to those
+    /// sorting before them and marking lost ones as missing. A library
+    /// not in the file yet is added.
+    pub fn apply(&self, symbols: &mut SymbolsFile) {
+        let pos = symbols
+            .libraries
+            .iter()
+            .position(|l| l.soname == self.soname);
+        let library = match pos {
+            Some(pos) => &mut symbols.libraries[pos],
+            None => {
+                symbols.libraries.push(SymbolsLibrary {
+                    soname: self.soname.clone(),
+                    dependency: DEFAULT_DEPENDENCY.to_string(),
+                    ..SymbolsLibrary::default()
+                });
+                symbols.libraries.last_mut().unwrap()
+            }
+        };
+        for item in &mut library.items {
+            let entry = match *item {
+                SymbolsItem::Symbol(ref mut entry) => entry,
+                SymbolsItem::Comment(_) => continue,
+            };
+            if self.lost.contains(entry) {
+                entry.missing = Some(self.version.clone());
+            } else if self
+                .added
+                .iter()
+                .any(|a| a.name == entry.name && a.tags == entry.tags)
+            {
+                entry.missing = None;
+            }
+        }
+        for new in &self.added {
+            // symbols listed as missing before are already there
+            if library
+                .symbols()
+                .any(|s| s.name == new.name && s.tags == new.tags)
+            {
+                continue;
+            }
+            let pos = library.items.iter().position(|i| match *i {
+                SymbolsItem::Symbol(ref s) => s.name > new.name,
+                SymbolsItem::Comment(_) => false,
+            });
+            let item = SymbolsItem::Symbol(new.clone());
+            match pos {
+                Some(pos) => library.items.insert(pos, item),
+                None => library.items.push(item),
+            }
+        }
+    }
+
+    /// The changes `apply` makes to a symbols file, as a patch of the
+    /// file at the path given.
+    pub fn patch(&self, symbols: &SymbolsFile, path: &str) -> FilePatch {
+        let mut updated = symbols.clone();
+        self.apply(&mut updated);
+        FilePatch::diff(
+            Some(path),
+            Some(path),
+            &symbols.to_string(),
+            &updated.to_string(),
+        )
+    }
+}
@@ -0,0 +1,244 @@
+//! Parser for the file lists of installed packages
+//!
+//! For each installed package, dpkg keeps the list of paths it
+//! installed in `/var/lib/dpkg/info/<pkg>.list`, including the
+//! directories, and the MD5 digests of its regular files in
+//! `<pkg>.md5sums`. Packages co-installable for several architectures
+//! use names like `<pkg>:<arch>.list` instead.
+//!
+//! Reading the whole directory gives a reverse index from paths to the
+//! packages owning them, answering the same questions as `dpkg -S`.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::installed::{FileList, InstalledFiles};
+//!
+//! let mut installed = InstalledFiles::default();
+//! installed.add_list(FileList::parse(
+//!     "hello",
+//!     None,
+//!     "/.\n/usr\n/usr/bin\n/usr/bin/hello\n",
+//! ));
+//! assert_eq!(installed.owners("/usr/bin/hello"), ["hello"]);
+//! assert_eq!(installed.search("bin/hel").len(), 1);
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::apt::preferences::glob_match;
+use crate::md5sums::Md5Sums;
+
+/// Default location of the directory holding the file lists.
+pub const DPKG_INFO_DIR: &str = "/var/lib/dpkg/info";
+
+/// The paths installed by a package.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FileList {
+    pub package: String,
+    /// the architecture, for packages co-installable for several
+    pub architecture: Option<String>,
+    /// the absolute paths, in the order listed
+    pub paths: Vec<String>,
+}
+
+/// The file lists and digests of all installed packages.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct InstalledFiles {
+    lists: Vec<FileList>,
+    md5sums: HashMap<String, Md5Sums>,
+    by_path: HashMap<String, Vec<usize>>,
+}
+
+/// Splits the base name of a file in the info directory, like
+/// `libc6:amd64`, into package name and architecture.
+fn split_name(name: &str) -> (&str, Option<&str>) {
+    match name.split_once(':') {
+        Some((package, arch)) => (package, Some(arch)),
+        None => (name, None),
+    }
+}
+
+impl FileList {
+    /// Parses the contents of a `.list` file.
+    pub fn parse(
+        package: &str,
+        architecture: Option<&str>,
+        text: &str,
+    ) -> FileList {
+        FileList {
+            package: package.to_string(),
+            architecture: architecture.map(str::to_string),
+            paths: text
+                .lines()
+                .filter(|l| !l.is_empty())
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+
+    /// Reads a `.list` file, taking package name and architecture from
+    /// the file name.
+    pub fn from_file(in_file: &Path) -> io::Result<FileList> {
+        let name = in_file
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_suffix(".list"))
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "not a .list file")
+            })?;
+        let (package, arch) = split_name(name);
+        Ok(FileList::parse(
+            package,
+            arch,
+            &fs::read_to_string(in_file)?,
+        ))
+    }
+
+    /// The name of the package as dpkg prints it, qualified with the
+    /// architecture if given, like `libc6:amd64`.
+    pub fn name(&self) -> String {
+        match self.architecture {
+            Some(ref arch) => format!("{}:{arch}", self.package),
+            None => self.package.clone(),
+        }
+    }
+
+    /// Whether the package installed a path.
+    pub fn contains(&self, path: &str) -> bool {
+        self.paths.iter().any(|p| p == path)
+    }
+}
+
+impl fmt::Display for FileList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for path in &self.paths {
+            writeln!(f, "{path}")?;
+        }
+        Ok(())
+    }
+}
+
+impl InstalledFiles {
+    /// Reads all `.list` and `.md5sums` files of an info directory,
+    /// usually `/var/lib/dpkg/info`.
+    pub fn read_dir(info_dir: &Path) -> io::Result<InstalledFiles> {
+        let mut entries =
+            fs::read_dir(info_dir)?.collect::<io::Result<Vec<_>>>()?;
+        entries.sort_by_key(|e| e.file_name());
+        let mut result = InstalledFiles::default();
+        for entry in entries {
+            let path = entry.path();
+            let name = entry.file_name();
+            let name = match name.to_str() {
+                Some(name) if entry.file_type()?.is_file() => name,
+                _ => continue,
+            };
+            if name.ends_with(".list") {
+                result.add_list(FileList::from_file(&path)?);
+            } else if let Some(name) = name.strip_suffix(".md5sums") {
+                result.add_md5sums(name, Md5Sums::from_file(&path)?);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Adds the file list of a package, replacing any previous one.
+    pub fn add_list(&mut self, list: FileList) {
+        let name = list.name();
+        let i = match self.lists.iter().position(|l| l.name() == name) {
+            Some(i) => {
+                for path in &self.lists[i].paths {
+                    if let Some(indices) = self.by_path.get_mut(path) {
+                        indices.retain(|&j| j != i);
+                    }
+                }
+                self.by_path.retain(|_, indices| !indices.is_empty());
+                self.lists[i] = list;
+                i
+            }
+            None => {
+                self.lists.push(list);
+                self.lists.len() - 1
+            }
+        };
+        for path in &self.lists[i].paths {
+            self.by_path.entry(path.clone()).or_default().push(i);
+        }
+    }
+
+    /// Adds the digests of the files of a package, given its name as
+    /// printed by dpkg, like `libc6:amd64`.
+    pub fn add_md5sums(&mut self, package: &str, md5sums: Md5Sums) {
+        self.md5sums.insert(package.to_string(), md5sums);
+    }
+
+    /// All file lists, in order.
+    pub fn lists(&self) -> &[FileList] {
+        &self.lists
+    }
+
+    /// Look up the file list of a package, by its name alone or
+    /// qualified with the architecture.
+    pub fn get(&self, package: &str) -> Option<&FileList> {
+        let (name, arch) = split_name(package);
+        self.lists.iter().find(|l| {
+            l.package == name
+                && (arch.is_none() || l.architecture.as_deref() == arch)
+        })
+    }
+
+    /// Look up the digests of the files of a package.
+    pub fn get_md5sums(&self, package: &str) -> Option<&Md5Sums> {
+        self.md5sums.get(package).or_else(|| {
+            let list = self.get(package)?;
+            self.md5sums.get(&list.name())
+        })
+    }
+
+    /// The packages owning a path, like `dpkg -S /usr/bin/hello`, in
+    /// order of their file lists. Directories are usually owned by
+    /// several packages.
+    pub fn owners(&self, path: &str) -> Vec<String> {
+        let mut indices = self.by_path.get(path).cloned().unwrap_or_default();
+        indices.sort_unstable();
+        indices.into_iter().map(|i| self.lists[i].name()).collect()
+    }
+
+    /// The MD5 digest recorded for a file, along with the package
+    /// owning it.
+    pub fn md5sum(&self, path: &str) -> Option<(String, &str)> {
+        let relative = path.trim_start_matches('/');
+        self.owners(path).into_iter().find_map(|owner| {
+            let digest = self.get_md5sums(&owner)?.get(relative)?;
+            Some((owner, digest))
+        })
+    }
+
+    /// Searches the installed paths like `dpkg -S`: patterns with
+    /// wildcards are matched against whole paths, absolute paths must
+    /// match exactly, other patterns anywhere within a path. Returns
+    /// pairs of package and path, in order of the packages.
+    pub fn search(&self, pattern: &str) -> Vec<(String, &str)> {
+        let matches = |path: &str| {
+            if pattern.contains(['*', '?', '[']) {
+                glob_match(pattern, path)
+            } else if pattern.starts_with('/') {
+                path == pattern
+            } else {
+                path.contains(pattern)
+            }
+        };
+        let mut result = vec![];
+        for list in &self.lists {
+            for path in list.paths.iter().filter(|p| matches(p)) {
+                result.push((list.name(), path.as_str()));
+            }
+        }
+        result
+    }
+}
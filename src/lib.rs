@@ -11,6 +11,71 @@
     unused_qualifications
 )]
 
+pub mod alternatives;
+pub mod apt;
+pub mod arch;
+pub mod archive;
+pub mod bugs;
+pub mod builddep;
+pub mod buildopts;
+#[cfg(feature = "cargo")]
+pub mod cargo;
+pub mod changes;
+pub mod conffiles;
+pub mod conflicts;
+pub mod copyright;
+#[cfg(feature = "deb")]
+pub mod deb;
+pub mod deb822;
+#[cfg(feature = "deb")]
+pub mod debdiff;
+pub mod debtags;
+pub mod dep14;
+pub mod distro;
+pub mod diversions;
+#[cfg(feature = "deb")]
+pub mod dsc;
+#[cfg(feature = "elf")]
+pub mod elf;
+pub mod filename;
+#[cfg(feature = "elf")]
+pub mod gensymbols;
+pub mod installed;
+pub mod lint;
+pub mod maintainer_scripts;
+pub mod md5sums;
+#[cfg(feature = "openpgp")]
+pub mod openpgp;
+pub mod ordering;
+pub mod orig;
 pub mod package;
+pub mod patches;
+pub mod popcon;
+pub mod quilt;
+pub mod resolver;
+pub mod scaffold;
+#[cfg(feature = "elf")]
+pub mod shlibdeps;
+pub mod shlibs;
+pub mod soname;
+pub mod source;
+pub mod standards;
+pub mod statoverride;
+pub mod status;
+pub mod substvars;
+pub mod symbols;
+pub mod tasks;
+pub mod templates;
+pub mod triggers;
+pub mod universe;
+#[cfg(feature = "upload")]
+pub mod upload;
+#[cfg(feature = "yaml")]
+pub mod upstream;
+#[cfg(feature = "http")]
+pub mod uscan;
+#[cfg(feature = "deb")]
+pub mod uupdate;
 pub mod version;
+pub mod watch;
 pub use self::version::Version;
@@ -0,0 +1,613 @@
+//! A framework for checking packaging files
+//!
+//! Checks implement the `Check` trait and examine one kind of target:
+//! a `debian/changelog`, a `debian/control` file, a `.dsc` file or a
+//! set of binary package paragraphs, like a Packages index. Each check
+//! reports findings, identified by a tag name like the ones of lintian,
+//! with a severity and, for text files, the line they refer to.
+//!
+//! A `Linter` runs a set of checks against targets, collecting their
+//! findings. A starter set of checks is available from
+//! `Linter::with_default_checks`, further checks can be added.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::lint::{LintTarget, Linter, Severity};
+//!
+//! let control = "\
+//! Source: foo
+//! Maintainer: Jane Doe <jane@example.org>
+//!
+//! Package: foo
+//! Architecture: any
+//! Description: foo
+//! ";
+//! let linter = Linter::with_default_checks();
+//! let findings = linter.check(&LintTarget::Control(control));
+//! assert_eq!(findings.len(), 1);
+//! assert_eq!(findings[0].tag, "no-standards-version");
+//! assert_eq!(findings[0].severity, Severity::Error);
+//! assert_eq!(findings[0].line, Some(1));
+//! ```
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+use crate::package::{ControlFile, ControlParagraph};
+use crate::standards::{StandardsStatus, StandardsVersion};
+use crate::Version;
+
+/// The longest synopsis recommended by policy.
+pub const MAX_SYNOPSIS_LENGTH: usize = 80;
+
+/// How severe a finding is.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+/// The kinds of targets checks examine.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum TargetKind {
+    Changelog,
+    Control,
+    Dsc,
+    PackageSet,
+}
+
+/// Something to check, given as text for files or parsed paragraphs
+/// for a set of binary packages.
+#[derive(Debug, Clone, Copy)]
+pub enum LintTarget<'a> {
+    /// the contents of a `debian/changelog` file
+    Changelog(&'a str),
+    /// the contents of a `debian/control` file
+    Control(&'a str),
+    /// the contents of a `.dsc` file, possibly signed
+    Dsc(&'a str),
+    /// the paragraphs of binary packages, like from a Packages index
+    PackageSet(&'a [ControlParagraph]),
+}
+
+/// A single problem found by a check.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Finding {
+    /// the tag identifying the kind of problem, like `synopsis-too-long`
+    pub tag: &'static str,
+    pub severity: Severity,
+    /// details on the problem
+    pub message: String,
+    /// the file checked, if known
+    pub path: Option<PathBuf>,
+    /// the line the finding refers to, counting from 1
+    pub line: Option<usize>,
+}
+
+/// A check of one or more kinds of targets.
+pub trait Check: fmt::Debug {
+    /// Whether the check examines targets of this kind.
+    fn applies_to(&self, kind: TargetKind) -> bool;
+
+    /// Examines a target, adding any findings.
+    fn check(&self, target: &LintTarget, findings: &mut Vec<Finding>);
+}
+
+/// Runs a set of checks against targets.
+#[derive(Debug, Default)]
+pub struct Linter {
+    checks: Vec<Box<dyn Check>>,
+}
+
+/// A field of a paragraph read from text, with its line number.
+#[derive(Debug)]
+struct Field<'a> {
+    name: &'a str,
+    /// the first line of the value, which is all for most fields
+    value: &'a str,
+    line: usize,
+}
+
+/// Splits the text of a control file into paragraphs of fields,
+/// skipping comments and an OpenPGP signature. Continuation lines are
+/// not included in the values.
+fn paragraphs(text: &str) -> Vec<Vec<Field<'_>>> {
+    let mut result = vec![];
+    let mut current = vec![];
+    let mut lines = text.lines().enumerate().peekable();
+    if let Some((_, l)) = lines.peek() {
+        if l.starts_with("-----BEGIN PGP SIGNED MESSAGE-----") {
+            // skip the armor headers
+            lines.find(|(_, l)| l.trim().is_empty());
+        }
+    }
+    for (i, line) in lines {
+        if line.starts_with("-----BEGIN PGP SIGNATURE-----") {
+            break;
+        }
+        if line.trim().is_empty() {
+            if !current.is_empty() {
+                result.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        if line.starts_with('#') || line.starts_with(char::is_whitespace) {
+            continue;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            current.push(Field {
+                name: name.trim(),
+                value: value.trim(),
+                line: i + 1,
+            });
+        }
+    }
+    if !current.is_empty() {
+        result.push(current);
+    }
+    result
+}
+
+fn get<'a>(para: &'a [Field<'a>], name: &str) -> Option<&'a Field<'a>> {
+    para.iter().find(|f| f.name.eq_ignore_ascii_case(name))
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Severity::Info => write!(f, "I"),
+            Severity::Warning => write!(f, "W"),
+            Severity::Error => write!(f, "E"),
+        }
+    }
+}
+
+impl<'a> LintTarget<'a> {
+    /// The kind of this target.
+    pub fn kind(&self) -> TargetKind {
+        match *self {
+            LintTarget::Changelog(_) => TargetKind::Changelog,
+            LintTarget::Control(_) => TargetKind::Control,
+            LintTarget::Dsc(_) => TargetKind::Dsc,
+            LintTarget::PackageSet(_) => TargetKind::PackageSet,
+        }
+    }
+}
+
+impl Finding {
+    /// Creates a finding, optionally referring to a line.
+    pub fn new(
+        tag: &'static str,
+        severity: Severity,
+        line: Option<usize>,
+        message: String,
+    ) -> Finding {
+        Finding {
+            tag,
+            severity,
+            message,
+            path: None,
+            line,
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: ", self.severity)?;
+        if let Some(ref path) = self.path {
+            write!(f, "{}:", path.display())?;
+            if let Some(line) = self.line {
+                write!(f, "{line}:")?;
+            }
+            write!(f, " ")?;
+        } else if let Some(line) = self.line {
+            write!(f, "line {line}: ")?;
+        }
+        write!(f, "{}", self.tag)?;
+        if !self.message.is_empty() {
+            write!(f, " {}", self.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl Linter {
+    /// Creates a linter running the starter set of checks.
+    pub fn with_default_checks() -> Linter {
+        let mut linter = Linter::default();
+        linter.add_check(MissingStandardsVersion);
+        linter.add_check(MissingFields);
+        linter.add_check(DuplicateFields);
+        linter.add_check(SynopsisTooLong);
+        linter.add_check(ChangelogVersionOrder);
+        linter.add_check(DuplicatePackages);
+        linter
+    }
+
+    /// Adds a check to run.
+    pub fn add_check<C: Check + 'static>(&mut self, check: C) {
+        self.checks.push(Box::new(check));
+    }
+
+    /// Runs all checks applying to a target, returning their findings
+    /// ordered by line.
+    pub fn check(&self, target: &LintTarget) -> Vec<Finding> {
+        let mut findings = vec![];
+        for check in &self.checks {
+            if check.applies_to(target.kind()) {
+                check.check(target, &mut findings);
+            }
+        }
+        findings.sort_by_key(|f| f.line);
+        findings
+    }
+
+    /// Reads and checks a file of the given kind, recording its path in
+    /// the findings.
+    pub fn check_file(
+        &self,
+        kind: TargetKind,
+        path: &Path,
+    ) -> io::Result<Vec<Finding>> {
+        let mut findings = match kind {
+            TargetKind::PackageSet => {
+                let cf = ControlFile::from_file(path)?;
+                self.check(&LintTarget::PackageSet(cf.get_paragraphs()))
+            }
+            _ => {
+                let text = fs::read_to_string(path)?;
+                self.check(&match kind {
+                    TargetKind::Changelog => LintTarget::Changelog(&text),
+                    TargetKind::Control => LintTarget::Control(&text),
+                    _ => LintTarget::Dsc(&text),
+                })
+            }
+        };
+        for finding in &mut findings {
+            finding.path = Some(path.to_path_buf());
+        }
+        Ok(findings)
+    }
+}
+
+/// Checks that source packages declare the version of the policy they
+/// comply with.
+#[derive(Debug, Clone, Copy)]
+pub struct MissingStandardsVersion;
+
+impl Check for MissingStandardsVersion {
+    fn applies_to(&self, kind: TargetKind) -> bool {
+        kind == TargetKind::Control || kind == TargetKind::Dsc
+    }
+
+    fn check(&self, target: &LintTarget, findings: &mut Vec<Finding>) {
+        let text = match *target {
+            LintTarget::Control(text) | LintTarget::Dsc(text) => text,
+            _ => return,
+        };
+        if let Some(source) = paragraphs(text).first() {
+            if get(source, "Standards-Version").is_none() {
+                findings.push(Finding::new(
+                    "no-standards-version",
+                    Severity::Error,
+                    source.first().map(|f| f.line),
+                    String::new(),
+                ));
+            }
+        }
+    }
+}
+
+/// Checks that the `Standards-Version` of source packages is valid and
+/// matches the current policy version, ignoring editorial changes. As
+/// the current version changes over time, this check is not part of the
+/// default set.
+#[derive(Debug, Clone, Copy)]
+pub struct OutdatedStandardsVersion {
+    pub current: StandardsVersion,
+}
+
+impl Check for OutdatedStandardsVersion {
+    fn applies_to(&self, kind: TargetKind) -> bool {
+        kind == TargetKind::Control || kind == TargetKind::Dsc
+    }
+
+    fn check(&self, target: &LintTarget, findings: &mut Vec<Finding>) {
+        let text = match *target {
+            LintTarget::Control(text) | LintTarget::Dsc(text) => text,
+            _ => return,
+        };
+        let paragraphs = paragraphs(text);
+        let field =
+            paragraphs.first().and_then(|s| get(s, "Standards-Version"));
+        let field = match field {
+            Some(field) => field,
+            None => return,
+        };
+        let line = Some(field.line);
+        let (tag, severity) = match StandardsVersion::parse(field.value) {
+            Err(_) => ("invalid-standards-version", Severity::Error),
+            Ok(v) => match v.status(&self.current) {
+                StandardsStatus::Current => return,
+                StandardsStatus::Outdated => {
+                    ("out-of-date-standards-version", Severity::Info)
+                }
+                StandardsStatus::Newer => {
+                    ("newer-standards-version", Severity::Warning)
+                }
+            },
+        };
+        findings.push(Finding::new(
+            tag,
+            severity,
+            line,
+            format!("{} (current is {})", field.value, self.current),
+        ));
+    }
+}
+
+/// Checks that required fields are present: `Source` and `Maintainer`
+/// for source packages and `Package`, `Architecture` and `Description`
+/// for binary packages, which additionally need a `Version` outside of
+/// `debian/control`.
+#[derive(Debug, Clone, Copy)]
+pub struct MissingFields;
+
+impl MissingFields {
+    fn check_fields(
+        present: impl Fn(&str) -> bool,
+        required: &[&str],
+        line: Option<usize>,
+        findings: &mut Vec<Finding>,
+    ) {
+        for field in required {
+            if !present(field) {
+                findings.push(Finding::new(
+                    "missing-required-field",
+                    Severity::Error,
+                    line,
+                    field.to_string(),
+                ));
+            }
+        }
+    }
+}
+
+impl Check for MissingFields {
+    fn applies_to(&self, _kind: TargetKind) -> bool {
+        true
+    }
+
+    fn check(&self, target: &LintTarget, findings: &mut Vec<Finding>) {
+        let (text, binary) = match *target {
+            LintTarget::Control(text) => {
+                (text, &["Package", "Architecture", "Description"][..])
+            }
+            LintTarget::Dsc(text) => (text, &[][..]),
+            LintTarget::PackageSet(paragraphs) => {
+                let required =
+                    ["Package", "Version", "Architecture", "Description"];
+                for para in paragraphs {
+                    MissingFields::check_fields(
+                        |f| para.has_entry(f),
+                        &required,
+                        None,
+                        findings,
+                    );
+                }
+                return;
+            }
+            LintTarget::Changelog(_) => return,
+        };
+        let paragraphs = paragraphs(text);
+        let mut paragraphs = paragraphs.iter();
+        if let Some(source) = paragraphs.next() {
+            let mut required = vec!["Source", "Maintainer"];
+            if target.kind() == TargetKind::Dsc {
+                required.extend(["Version", "Format"]);
+            }
+            MissingFields::check_fields(
+                |f| get(source, f).is_some(),
+                &required,
+                source.first().map(|f| f.line),
+                findings,
+            );
+        }
+        for para in paragraphs {
+            MissingFields::check_fields(
+                |f| get(para, f).is_some(),
+                binary,
+                para.first().map(|f| f.line),
+                findings,
+            );
+        }
+    }
+}
+
+/// Checks that no field is given twice in a paragraph.
+#[derive(Debug, Clone, Copy)]
+pub struct DuplicateFields;
+
+impl Check for DuplicateFields {
+    fn applies_to(&self, kind: TargetKind) -> bool {
+        kind == TargetKind::Control || kind == TargetKind::Dsc
+    }
+
+    fn check(&self, target: &LintTarget, findings: &mut Vec<Finding>) {
+        let text = match *target {
+            LintTarget::Control(text) | LintTarget::Dsc(text) => text,
+            _ => return,
+        };
+        for para in paragraphs(text) {
+            let mut seen = HashSet::new();
+            for field in para {
+                if !seen.insert(field.name.to_ascii_lowercase()) {
+                    findings.push(Finding::new(
+                        "duplicate-field",
+                        Severity::Error,
+                        Some(field.line),
+                        field.name.to_string(),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Checks that the synopsis of binary packages, the first line of the
+/// description, is not longer than `MAX_SYNOPSIS_LENGTH` characters.
+#[derive(Debug, Clone, Copy)]
+pub struct SynopsisTooLong;
+
+impl SynopsisTooLong {
+    fn check_synopsis(
+        package: &str,
+        synopsis: &str,
+        line: Option<usize>,
+        findings: &mut Vec<Finding>,
+    ) {
+        let length = synopsis.chars().count();
+        if length > MAX_SYNOPSIS_LENGTH {
+            findings.push(Finding::new(
+                "synopsis-too-long",
+                Severity::Warning,
+                line,
+                format!("{package} ({length} > {MAX_SYNOPSIS_LENGTH})"),
+            ));
+        }
+    }
+}
+
+impl Check for SynopsisTooLong {
+    fn applies_to(&self, kind: TargetKind) -> bool {
+        kind == TargetKind::Control || kind == TargetKind::PackageSet
+    }
+
+    fn check(&self, target: &LintTarget, findings: &mut Vec<Finding>) {
+        match *target {
+            LintTarget::Control(text) => {
+                for para in paragraphs(text).iter().skip(1) {
+                    if let Some(desc) = get(para, "Description") {
+                        let package =
+                            get(para, "Package").map_or("", |f| f.value);
+                        SynopsisTooLong::check_synopsis(
+                            package,
+                            desc.value,
+                            Some(desc.line),
+                            findings,
+                        );
+                    }
+                }
+            }
+            LintTarget::PackageSet(paragraphs) => {
+                for para in paragraphs {
+                    if let Some(desc) = para.get_entry("Description") {
+                        let package = para.get_entry("Package").unwrap_or("");
+                        let synopsis = desc.lines().next().unwrap_or("");
+                        SynopsisTooLong::check_synopsis(
+                            package, synopsis, None, findings,
+                        );
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Checks that the versions in a changelog decrease from each entry to
+/// the next, older one, and that entry headers are well-formed.
+#[derive(Debug, Clone, Copy)]
+pub struct ChangelogVersionOrder;
+
+impl Check for ChangelogVersionOrder {
+    fn applies_to(&self, kind: TargetKind) -> bool {
+        kind == TargetKind::Changelog
+    }
+
+    fn check(&self, target: &LintTarget, findings: &mut Vec<Finding>) {
+        let text = match *target {
+            LintTarget::Changelog(text) => text,
+            _ => return,
+        };
+        let header =
+            Regex::new(r"^[a-z0-9][a-z0-9+.-]* \(([^ ()]+)\) [^;]+;").unwrap();
+        let mut previous: Option<(Version, usize)> = None;
+        for (i, line) in text.lines().enumerate() {
+            let lower = line.to_ascii_lowercase();
+            if lower.starts_with("local variables:")
+                || lower.starts_with("old changelog:")
+            {
+                break;
+            }
+            if line.trim().is_empty() || line.starts_with(char::is_whitespace) {
+                continue;
+            }
+            let version = header
+                .captures(line)
+                .and_then(|c| Version::parse(&c[1]).ok());
+            let version = match version {
+                Some(v) => v,
+                None => {
+                    findings.push(Finding::new(
+                        "malformed-changelog-header",
+                        Severity::Error,
+                        Some(i + 1),
+                        line.to_string(),
+                    ));
+                    continue;
+                }
+            };
+            if let Some((ref newer, newer_line)) = previous {
+                if *newer <= version {
+                    findings.push(Finding::new(
+                        "non-monotonic-changelog",
+                        Severity::Error,
+                        Some(newer_line),
+                        format!("{newer} is not newer than {version}"),
+                    ));
+                }
+            }
+            previous = Some((version, i + 1));
+        }
+    }
+}
+
+/// Checks that a set of packages does not contain the same version of
+/// a package for the same architecture twice.
+#[derive(Debug, Clone, Copy)]
+pub struct DuplicatePackages;
+
+impl Check for DuplicatePackages {
+    fn applies_to(&self, kind: TargetKind) -> bool {
+        kind == TargetKind::PackageSet
+    }
+
+    fn check(&self, target: &LintTarget, findings: &mut Vec<Finding>) {
+        let paragraphs = match *target {
+            LintTarget::PackageSet(paragraphs) => paragraphs,
+            _ => return,
+        };
+        let mut seen = HashSet::new();
+        for para in paragraphs {
+            let key = ["Package", "Version", "Architecture"]
+                .map(|f| para.get_entry(f).unwrap_or(""));
+            if !seen.insert(key) {
+                findings.push(Finding::new(
+                    "duplicate-package",
+                    Severity::Warning,
+                    None,
+                    key.join(" "),
+                ));
+            }
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! Maintainer scripts and simple checks for them
+//!
+//! Binary packages may carry scripts dpkg runs before and after
+//! installation or removal, as well as a debconf `config` script. Most
+//! are shell scripts, which should stop on errors and, if run by
+//! `/bin/sh`, avoid bash-specific syntax.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::maintainer_scripts::{MaintainerScript, ScriptIssue, ScriptKind};
+//!
+//! let script = MaintainerScript::new(
+//!     ScriptKind::Postinst,
+//!     "#!/bin/sh\nif [[ \"$1\" == configure ]]; then\n    true\nfi\n",
+//! );
+//! assert_eq!(script.interpreter.as_deref(), Some("/bin/sh"));
+//! let issues = script.lint();
+//! assert_eq!(issues[0], ScriptIssue::MissingSetE);
+//! assert!(matches!(issues[1], ScriptIssue::Bashism(2, _)));
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use regex::Regex;
+
+/// The kind of a maintainer script, given by its name.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ScriptKind {
+    Preinst,
+    Postinst,
+    Prerm,
+    Postrm,
+    Config,
+}
+
+/// A maintainer script.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MaintainerScript {
+    pub kind: ScriptKind,
+    /// the interpreter given by the `#!` line, like `/bin/sh`
+    pub interpreter: Option<String>,
+    pub contents: String,
+}
+
+/// A problem found in a maintainer script.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ScriptIssue {
+    /// The script lacks a `#!` line.
+    MissingInterpreter,
+    /// A shell script that does not stop on errors.
+    MissingSetE,
+    /// Syntax not supported by POSIX shells in a `/bin/sh` script, with
+    /// its line number, starting at one, and a description.
+    Bashism(usize, String),
+}
+
+/// Constructs commonly used in `/bin/sh` scripts that only work with
+/// bash, with a description each.
+const BASHISMS: &[(&str, &str)] = &[
+    (r"\[\[", "[[ test construct"),
+    (r"^\s*function\s+\w+", "function keyword"),
+    (r"\[\s[^\]]*\s==\s", "== in test"),
+    (r"\$RANDOM\b", "$RANDOM variable"),
+    (r"^\s*source\s", "source instead of ."),
+    (r"&>", "&> redirection"),
+    (r"<<<", "here-string"),
+    (r"\$'", "$'...' quoting"),
+    (r"\becho\s+-e\b", "echo -e"),
+    (r"^\s*\w+=\(", "array assignment"),
+    (r"\$\{\w+:[0-9]", "substring expansion"),
+    (r"\$\{\w+//?[^/}]*/", "pattern substitution"),
+];
+
+impl ScriptKind {
+    /// All kinds of maintainer scripts.
+    pub const ALL: [ScriptKind; 5] = [
+        ScriptKind::Preinst,
+        ScriptKind::Postinst,
+        ScriptKind::Prerm,
+        ScriptKind::Postrm,
+        ScriptKind::Config,
+    ];
+
+    /// The name of the script in the control member, like `postinst`.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ScriptKind::Preinst => "preinst",
+            ScriptKind::Postinst => "postinst",
+            ScriptKind::Prerm => "prerm",
+            ScriptKind::Postrm => "postrm",
+            ScriptKind::Config => "config",
+        }
+    }
+}
+
+impl FromStr for ScriptKind {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ScriptKind::ALL
+            .iter()
+            .find(|k| k.name() == s)
+            .copied()
+            .ok_or("unknown maintainer script")
+    }
+}
+
+impl fmt::Display for ScriptKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl MaintainerScript {
+    /// Creates a script, determining its interpreter.
+    pub fn new(kind: ScriptKind, contents: &str) -> MaintainerScript {
+        let interpreter = contents
+            .lines()
+            .next()
+            .and_then(|l| l.strip_prefix("#!"))
+            .and_then(|l| l.split_whitespace().next())
+            .map(str::to_string);
+        MaintainerScript {
+            kind,
+            interpreter,
+            contents: contents.to_string(),
+        }
+    }
+
+    /// Whether the script is run by a shell, either a POSIX one or
+    /// bash.
+    pub fn is_shell_script(&self) -> bool {
+        self.is_posix_shell_script()
+            || matches!(self.interpreter.as_deref(), Some("/bin/bash"))
+    }
+
+    /// Whether the script is run by `/bin/sh` or dash, which may not
+    /// be bash.
+    pub fn is_posix_shell_script(&self) -> bool {
+        matches!(self.interpreter.as_deref(), Some("/bin/sh" | "/bin/dash"))
+    }
+
+    /// Whether the script stops on errors, either by `set -e` or by
+    /// passing `-e` to the shell on the `#!` line.
+    pub fn has_set_e(&self) -> bool {
+        let shebang_e = self
+            .contents
+            .lines()
+            .next()
+            .filter(|l| l.starts_with("#!"))
+            .map_or(false, |l| {
+                l.split_whitespace()
+                    .skip(1)
+                    .any(|a| a.starts_with('-') && a.contains('e'))
+            });
+        let set_e =
+            Regex::new(r"(?m)^\s*set\s+(-[a-z]*e[a-z]*\b|-o\s+errexit)")
+                .unwrap();
+        shebang_e || set_e.is_match(&self.contents)
+    }
+
+    /// Finds constructs specific to bash, with their line numbers,
+    /// starting at one. Comment lines are skipped.
+    ///
+    /// This is a heuristic like checkbashisms, not a shell parser, so
+    /// it may report constructs within strings or here-documents.
+    pub fn bashisms(&self) -> Vec<(usize, &'static str)> {
+        let patterns: Vec<(Regex, &str)> = BASHISMS
+            .iter()
+            .map(|(p, d)| (Regex::new(p).unwrap(), *d))
+            .collect();
+        let mut result = vec![];
+        for (i, line) in self.contents.lines().enumerate() {
+            if line.trim_start().starts_with('#') {
+                continue;
+            }
+            for (re, description) in &patterns {
+                if re.is_match(line) {
+                    result.push((i + 1, *description));
+                }
+            }
+        }
+        result
+    }
+
+    /// Checks the script for a missing interpreter, missing `set -e`
+    /// in shell scripts and bashisms in `/bin/sh` scripts.
+    pub fn lint(&self) -> Vec<ScriptIssue> {
+        let mut issues = vec![];
+        if self.interpreter.is_none() {
+            issues.push(ScriptIssue::MissingInterpreter);
+        }
+        if self.is_shell_script() && !self.has_set_e() {
+            issues.push(ScriptIssue::MissingSetE);
+        }
+        if self.is_posix_shell_script() {
+            issues.extend(
+                self.bashisms()
+                    .into_iter()
+                    .map(|(line, d)| ScriptIssue::Bashism(line, d.to_string())),
+            );
+        }
+        issues
+    }
+}
@@ -0,0 +1,193 @@
+//! Generation and verification of md5sums control files
+//!
+//! The `md5sums` control file of a binary package lists the MD5 digest
+//! of each regular file the package installs, with paths relative to
+//! the root directory. It is generated when building a package and
+//! used to audit installed files, like debsums does.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::md5sums::{md5_hex, Md5Sums};
+//!
+//! let mut sums = Md5Sums::default();
+//! sums.add_file("usr/share/doc/foo/README", &md5_hex(b"foo\n"));
+//! assert_eq!(
+//!     sums.to_string(),
+//!     "d3b07384d113edec49eaa6238ad5ff00  usr/share/doc/foo/README\n"
+//! );
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use md5::{Digest, Md5};
+
+/// The contents of an md5sums file, as pairs of a path, relative to
+/// the root directory, and its hex digest.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Md5Sums {
+    files: Vec<(String, String)>,
+}
+
+/// A problem found when verifying files against their md5sums.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Md5SumsIssue {
+    /// The file's contents do not match the listed digest.
+    Mismatch(String),
+    /// The file does not exist or is not a regular file.
+    Missing(String),
+}
+
+/// The MD5 digest of some data, as lowercase hex string.
+pub fn md5_hex(data: &[u8]) -> String {
+    format!("{:x}", Md5::digest(data))
+}
+
+/// The MD5 digest of a file, as lowercase hex string.
+pub fn file_md5_hex(path: &Path) -> io::Result<String> {
+    let mut hasher = Md5::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn is_md5_digest(s: &str) -> bool {
+    s.len() == 32 && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Collects the regular files below a directory, recursing into
+/// subdirectories but not following symlinks.
+fn collect_files(
+    root: &Path,
+    dir: &Path,
+    sums: &mut Md5Sums,
+) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if dir == root && entry.file_name() == "DEBIAN" {
+            continue;
+        } else if file_type.is_dir() {
+            collect_files(root, &path, sums)?;
+        } else if file_type.is_file() {
+            let rel = path
+                .strip_prefix(root)
+                .ok()
+                .and_then(|p| p.to_str())
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "file name is not valid UTF-8",
+                    )
+                })?
+                .to_string();
+            sums.files.push((rel, file_md5_hex(&path)?));
+        }
+    }
+    Ok(())
+}
+
+impl Md5Sums {
+    /// Reads and parses an md5sums file from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<Md5Sums> {
+        fs::read_to_string(in_file)?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Computes the digests of all regular files below a directory,
+    /// sorted by path. A top-level `DEBIAN` directory, as used by
+    /// dpkg-deb for control files, is skipped.
+    pub fn from_tree(root: &Path) -> io::Result<Md5Sums> {
+        let mut sums = Md5Sums::default();
+        collect_files(root, root, &mut sums)?;
+        Ok(sums)
+    }
+
+    /// All entries, in order, as pairs of path and digest.
+    pub fn files(&self) -> &Vec<(String, String)> {
+        &self.files
+    }
+
+    /// Append an entry, replacing any previous one for the same path.
+    /// Leading slashes or `./` are removed from the path.
+    pub fn add_file(&mut self, path: &str, digest: &str) {
+        let path = path.trim_start_matches("./").trim_start_matches('/');
+        self.files.retain(|(p, _)| p != path);
+        self.files.push((path.to_string(), digest.to_lowercase()));
+    }
+
+    /// Removes the entry for a path, returning whether there was one.
+    pub fn remove_file(&mut self, path: &str) -> bool {
+        let len = self.files.len();
+        self.files.retain(|(p, _)| p != path);
+        self.files.len() != len
+    }
+
+    /// Look up the digest of a file.
+    pub fn get(&self, path: &str) -> Option<&str> {
+        self.files
+            .iter()
+            .find(|(p, _)| p == path)
+            .map(|(_, d)| d.as_str())
+    }
+
+    /// Verifies the files below a root directory, like an installed
+    /// system or an extracted package, against the listed digests.
+    /// Files not listed are not reported.
+    pub fn verify(&self, root: &Path) -> io::Result<Vec<Md5SumsIssue>> {
+        let mut issues = vec![];
+        for (path, digest) in &self.files {
+            let full_path = root.join(path);
+            match fs::symlink_metadata(&full_path) {
+                Ok(m) if m.is_file() => {
+                    if file_md5_hex(&full_path)? != *digest {
+                        issues.push(Md5SumsIssue::Mismatch(path.clone()));
+                    }
+                }
+                Ok(_) => issues.push(Md5SumsIssue::Missing(path.clone())),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    issues.push(Md5SumsIssue::Missing(path.clone()))
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(issues)
+    }
+}
+
+impl FromStr for Md5Sums {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut result = Md5Sums::default();
+        for line in s.lines().filter(|l| !l.trim().is_empty()) {
+            let (digest, path) = line
+                .split_once(char::is_whitespace)
+                .ok_or("missing path in md5sums")?;
+            if !is_md5_digest(digest) {
+                return Err("invalid md5 digest");
+            }
+            let path = path.trim_start();
+            if path.is_empty() {
+                return Err("missing path in md5sums");
+            }
+            result.add_file(path, digest);
+        }
+        Ok(result)
+    }
+}
+
+impl fmt::Display for Md5Sums {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (path, digest) in &self.files {
+            writeln!(f, "{digest}  {path}")?;
+        }
+        Ok(())
+    }
+}
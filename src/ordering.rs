@@ -0,0 +1,262 @@
+//! Ordering the installation of packages
+//!
+//! When installing a set of packages, dpkg first unpacks each package
+//! and later configures it. A package `Pre-Depends` on others if they
+//! need to be configured before it can even be unpacked, and `Depends`
+//! on those that need to be configured before it is configured itself.
+//! Tools installing packages one by one, like debootstrap, need to
+//! follow an order meeting these constraints.
+//!
+//! Dependencies may form cycles, in which case there is no such order.
+//! dpkg configures packages depending on each other together, but
+//! cannot unpack a package before its pre-dependencies are configured,
+//! so cycles including a `Pre-Depends` need to be broken by other means,
+//! like deferring the configuration or triggers of some package.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::ordering::{installation_order, InstallStep};
+//! use debian::package::{parse_dep_list, ControlFile};
+//! use debian::resolver::Resolver;
+//!
+//! let packages = ControlFile::from_reader(
+//!     "Package: foo\nVersion: 1.0\nPre-Depends: libfoo1\n\n\
+//!      Package: libfoo1\nVersion: 1.2\n"
+//!         .as_bytes(),
+//! )
+//! .unwrap();
+//! let mut resolver = Resolver::default();
+//! resolver.add_index(&packages).unwrap();
+//! let set = resolver.resolve(&parse_dep_list("foo").unwrap()).unwrap();
+//!
+//! let order = installation_order(set.packages());
+//! let steps: Vec<String> =
+//!     order.steps.iter().map(|s| s.to_string()).collect();
+//! assert_eq!(
+//!     steps,
+//!     ["unpack libfoo1", "configure libfoo1", "unpack foo", "configure foo"]
+//! );
+//! assert!(order.cycles.is_empty());
+//! ```
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fmt;
+
+use crate::package::Dependency;
+use crate::resolver::Candidate;
+
+/// A single step of installing a set of packages.
+#[derive(Debug, Clone, Copy)]
+pub enum InstallStep<'a> {
+    Unpack(&'a Candidate),
+    Configure(&'a Candidate),
+}
+
+/// Packages whose dependencies form a cycle, so that they cannot be
+/// installed in an order meeting all of them.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct InstallCycle {
+    /// the packages involved, sorted by name
+    pub packages: Vec<String>,
+    /// whether the cycle includes a `Pre-Depends`, so that a package is
+    /// unpacked before its pre-dependencies are configured
+    pub pre_depends: bool,
+}
+
+/// The order to install a set of packages in.
+#[derive(Debug, Clone)]
+pub struct InstallOrder<'a> {
+    /// the steps to take, in order
+    pub steps: Vec<InstallStep<'a>>,
+    /// the dependency cycles that had to be broken
+    pub cycles: Vec<InstallCycle>,
+}
+
+/// Nodes of the ordering graph, two per package.
+const UNPACK: usize = 0;
+const CONFIGURE: usize = 1;
+
+fn node(package: usize, step: usize) -> usize {
+    package * 2 + step
+}
+
+impl<'a> InstallStep<'a> {
+    /// The package to unpack or configure.
+    pub fn candidate(&self) -> &'a Candidate {
+        match *self {
+            InstallStep::Unpack(c) | InstallStep::Configure(c) => c,
+        }
+    }
+}
+
+impl<'a> fmt::Display for InstallStep<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InstallStep::Unpack(c) => write!(f, "unpack {}", c.package),
+            InstallStep::Configure(c) => write!(f, "configure {}", c.package),
+        }
+    }
+}
+
+/// The first package of the set satisfying a dependency, if any.
+fn satisfier(packages: &[&Candidate], dep: &Dependency) -> Option<usize> {
+    dep.alternatives
+        .iter()
+        .find_map(|alt| packages.iter().position(|c| c.satisfies(alt)))
+}
+
+/// Splits a graph into its strongly connected components, using
+/// Tarjan's algorithm without recursion.
+fn components(edges: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let n = edges.len();
+    let mut index = vec![usize::MAX; n];
+    let mut low = vec![0; n];
+    let mut on_stack = vec![false; n];
+    let mut stack = vec![];
+    let mut calls: Vec<(usize, usize)> = vec![];
+    let mut counter = 0;
+    let mut result = vec![];
+    for start in 0..n {
+        if index[start] != usize::MAX {
+            continue;
+        }
+        calls.push((start, 0));
+        while let Some(&(v, i)) = calls.last() {
+            if index[v] == usize::MAX {
+                index[v] = counter;
+                low[v] = counter;
+                counter += 1;
+                stack.push(v);
+                on_stack[v] = true;
+            }
+            if i < edges[v].len() {
+                calls.last_mut().unwrap().1 += 1;
+                let w = edges[v][i];
+                if index[w] == usize::MAX {
+                    calls.push((w, 0));
+                } else if on_stack[w] {
+                    low[v] = low[v].min(index[w]);
+                }
+                continue;
+            }
+            calls.pop();
+            if let Some(&(u, _)) = calls.last() {
+                low[u] = low[u].min(low[v]);
+            }
+            if low[v] == index[v] {
+                let mut component = vec![];
+                loop {
+                    let w = stack.pop().unwrap();
+                    on_stack[w] = false;
+                    component.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                result.push(component);
+            }
+        }
+    }
+    result
+}
+
+/// Computes the order to unpack and configure a set of packages in,
+/// such as an installation set computed by the resolver.
+///
+/// Dependencies satisfied by packages outside of the set, like those
+/// installed already, do not affect the order. Packages are unpacked as
+/// early as possible and configured as late as possible, by name
+/// otherwise. Packages forming a dependency cycle are unpacked and then
+/// configured together.
+pub fn installation_order<'a, I>(packages: I) -> InstallOrder<'a>
+where
+    I: IntoIterator<Item = &'a Candidate>,
+{
+    let mut packages: Vec<&Candidate> = packages.into_iter().collect();
+    packages.sort_by(|a, b| a.package.cmp(&b.package));
+
+    // edges lead from each step to the steps requiring it
+    let mut edges = vec![vec![]; packages.len() * 2];
+    for (i, candidate) in packages.iter().enumerate() {
+        edges[node(i, UNPACK)].push(node(i, CONFIGURE));
+        let deps = [
+            (&candidate.pre_depends, UNPACK),
+            (&candidate.depends, CONFIGURE),
+        ];
+        for (deps, step) in deps {
+            for dep in deps {
+                match satisfier(&packages, dep) {
+                    Some(j) if j != i => {
+                        edges[node(j, CONFIGURE)].push(node(i, step))
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    let components = components(&edges);
+    let mut component_of = vec![0; edges.len()];
+    for (c, nodes) in components.iter().enumerate() {
+        for &v in nodes {
+            component_of[v] = c;
+        }
+    }
+    let mut in_degree = vec![0; components.len()];
+    for (v, targets) in edges.iter().enumerate() {
+        for &w in targets {
+            if component_of[v] != component_of[w] {
+                in_degree[component_of[w]] += 1;
+            }
+        }
+    }
+
+    // ready components, preferring unpacking over configuring
+    let key = |c: usize| {
+        let v = *components[c].iter().min_by_key(|&&v| (v % 2, v)).unwrap();
+        Reverse((v % 2, v / 2, c))
+    };
+    let mut ready: BinaryHeap<_> = (0..components.len())
+        .filter(|&c| in_degree[c] == 0)
+        .map(key)
+        .collect();
+    let mut order = InstallOrder {
+        steps: vec![],
+        cycles: vec![],
+    };
+    while let Some(Reverse((_, _, c))) = ready.pop() {
+        let mut nodes = components[c].clone();
+        nodes.sort_by_key(|&v| (v % 2, v / 2));
+        if nodes.len() > 1 {
+            let mut names: Vec<String> = nodes
+                .iter()
+                .map(|&v| packages[v / 2].package.clone())
+                .collect();
+            names.sort();
+            names.dedup();
+            order.cycles.push(InstallCycle {
+                packages: names,
+                pre_depends: nodes.iter().any(|&v| v % 2 == UNPACK),
+            });
+        }
+        for &v in &nodes {
+            let candidate = packages[v / 2];
+            order.steps.push(match v % 2 {
+                UNPACK => InstallStep::Unpack(candidate),
+                _ => InstallStep::Configure(candidate),
+            });
+            for &w in &edges[v] {
+                let d = component_of[w];
+                if d != c {
+                    in_degree[d] -= 1;
+                    if in_degree[d] == 0 {
+                        ready.push(key(d));
+                    }
+                }
+            }
+        }
+    }
+    order
+}
@@ -0,0 +1,181 @@
+//! Orig tarballs and their upstream signatures
+//!
+//! Non-native source packages are built from the orig tarball of their
+//! upstream version, like `foo_1.2.orig.tar.xz`, plus possibly
+//! tarballs of additional components, like `foo_1.2.orig-docs.tar.gz`.
+//! Each of them may be accompanied by a detached, ASCII-armored
+//! signature of the upstream author, like `foo_1.2.orig.tar.xz.asc`.
+//! Before building, the tarballs need to be found next to the unpacked
+//! source, in any of the allowed compressions.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use debian::orig::find_orig_tarballs;
+//! use debian::Version;
+//! use std::path::Path;
+//!
+//! let version = Version::parse("1.2-1").unwrap();
+//! for tarball in find_orig_tarballs(Path::new(".."), "foo", &version)? {
+//!     println!("{}", tarball.path.display());
+//!     tarball.check_signature()?;
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::filename::{ArtifactKind, ArtifactName, TARBALL_COMPRESSIONS};
+use crate::Version;
+
+/// An orig tarball found on disk.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct OrigTarball {
+    pub name: ArtifactName,
+    pub path: PathBuf,
+    /// the accompanying upstream signature, if any
+    pub signature: Option<PathBuf>,
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// The possible names of the orig tarball of a version, or the one of
+/// an additional component, one per allowed compression.
+pub fn orig_tarball_names(
+    source: &str,
+    version: &Version,
+    component: Option<&str>,
+) -> Vec<ArtifactName> {
+    TARBALL_COMPRESSIONS
+        .iter()
+        .map(|c| ArtifactName::orig_tarball(source, version, component, c))
+        .collect()
+}
+
+/// Checks that a file holds an ASCII-armored detached OpenPGP
+/// signature, as expected for `.asc` files. The signature itself is not
+/// verified.
+pub fn check_signature_file(path: &Path) -> io::Result<()> {
+    let content = fs::read(path)?;
+    let text = String::from_utf8_lossy(&content);
+    let text = text.trim();
+    let begin = "-----BEGIN PGP SIGNATURE-----";
+    let end = "-----END PGP SIGNATURE-----";
+    if !text.starts_with(begin) {
+        return Err(invalid_data(format!(
+            "{} is not an armored OpenPGP signature",
+            path.display()
+        )));
+    }
+    if !text.ends_with(end) || text.matches(begin).count() != 1 {
+        return Err(invalid_data(format!(
+            "{} does not hold a single complete signature",
+            path.display()
+        )));
+    }
+    Ok(())
+}
+
+impl OrigTarball {
+    /// The component of the tarball, if it is not the main one.
+    pub fn component(&self) -> Option<&str> {
+        match self.name.kind {
+            ArtifactKind::OrigTarball { ref component, .. } => {
+                component.as_deref()
+            }
+            _ => None,
+        }
+    }
+
+    /// The name of the signature file accompanying the tarball.
+    pub fn signature_name(&self) -> String {
+        format!("{}.asc", self.name)
+    }
+
+    /// Checks the signature file, if there is one.
+    pub fn check_signature(&self) -> io::Result<()> {
+        match self.signature {
+            Some(ref path) => check_signature_file(path),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Looks for the orig tarball of a version, or the one of an additional
+/// component, in a directory, together with its signature. Fails if
+/// there are tarballs in more than one compression.
+pub fn find_orig_tarball(
+    dir: &Path,
+    source: &str,
+    version: &Version,
+    component: Option<&str>,
+) -> io::Result<Option<OrigTarball>> {
+    let mut found = vec![];
+    for name in orig_tarball_names(source, version, component) {
+        let path = dir.join(name.to_string());
+        if path.is_file() {
+            found.push((name, path));
+        }
+    }
+    if found.len() > 1 {
+        return Err(invalid_data(format!(
+            "multiple orig tarballs: {}",
+            found
+                .iter()
+                .map(|(name, _)| name.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        )));
+    }
+    Ok(found.pop().map(|(name, path)| {
+        let signature = dir.join(format!("{name}.asc"));
+        OrigTarball {
+            name,
+            path,
+            signature: Some(signature).filter(|p| p.is_file()),
+        }
+    }))
+}
+
+/// Finds the orig tarballs of a version in a directory: the main one
+/// first, if present, followed by those of additional components,
+/// sorted by component name.
+pub fn find_orig_tarballs(
+    dir: &Path,
+    source: &str,
+    version: &Version,
+) -> io::Result<Vec<OrigTarball>> {
+    let mut components = BTreeSet::new();
+    for entry in fs::read_dir(dir)? {
+        let file_name = entry?.file_name();
+        let name = match ArtifactName::parse(&file_name.to_string_lossy()) {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+        if name.package != source
+            || name.version.to_string() != version.upstream()
+        {
+            continue;
+        }
+        if let ArtifactKind::OrigTarball {
+            component,
+            signature: false,
+            ..
+        } = name.kind
+        {
+            components.insert(component);
+        }
+    }
+    let mut result = vec![];
+    for component in components {
+        let tarball =
+            find_orig_tarball(dir, source, version, component.as_deref())?;
+        result.extend(tarball);
+    }
+    Ok(result)
+}
@@ -3,6 +3,7 @@
 //! This module contains a `Changelog` and a `ControlFile` parser for the
 //! Debian changelog and control files usually used for packaging.
 
+use std::cmp::Ordering;
 use std::env;
 use std::fmt;
 
@@ -13,7 +14,9 @@ use std::path::Path;
 
 use chrono::prelude::*;
 use log::*;
+use regex::Regex;
 
+use super::deb822::{self, Deb822Reader, Paragraph};
 use super::Version;
 
 /// Represents a single entry in a debian/changelog file.
@@ -36,7 +39,24 @@ pub struct ChangelogEntry {
     // email of the uploader of the package
     maintainer_email: String,
     // date of the upload
-    ts: DateTime<Local>,
+    ts: DateTime<FixedOffset>,
+}
+
+/// The pattern dpkg uses for the first line of a changelog entry, like
+/// `foo (1.2-1) unstable; urgency=medium`.
+const CHANGELOG_HEADER: &str = concat!(
+    r"^(\w[-+0-9a-z.]*) \(([^()\s]+)\)",
+    r"((?:\s+[-+0-9a-zA-Z.]+)+);(.*)$"
+);
+
+/// The pattern of the last line of a changelog entry, like
+/// ` -- Jane Doe <jane@example.org>  Mon, 02 Oct 2023 10:00:00 +0200`.
+const CHANGELOG_TRAILER: &str = r"^ -- (.*) <(.*)>  ?(.*)$";
+
+/// Formats a date as in changelog trailers and `.changes` files, with
+/// a two-digit day of the month as required by policy.
+pub(crate) fn format_date(date: &DateTime<FixedOffset>) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S %z").to_string()
 }
 
 /// Represents a complete debian/changelog file
@@ -69,13 +89,71 @@ impl ChangelogEntry {
             detail,
             maintainer_name: get_default_maintainer_name(),
             maintainer_email: get_default_maintainer_email(),
-            ts: Local::now(),
+            ts: Local::now().into(),
         }
     }
 
-    fn serialize(&self) -> String {
+    /// The name of the source package.
+    pub fn package(&self) -> &str {
+        &self.pkg
+    }
+
+    /// The version of the source package, as given in the header.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// The distributions targeted by the upload, like `unstable`.
+    pub fn distributions(&self) -> &Vec<String> {
+        &self.distributions
+    }
+
+    /// The urgency of the upload, like `medium`.
+    pub fn urgency(&self) -> &str {
+        &self.urgency
+    }
+
+    /// The lines describing the changes, without the surrounding empty
+    /// lines but keeping their indentation.
+    pub fn detail(&self) -> &str {
+        &self.detail
+    }
+
+    /// The name of whoever prepared the upload.
+    pub fn maintainer_name(&self) -> &str {
+        &self.maintainer_name
+    }
+
+    /// The email address of whoever prepared the upload.
+    pub fn maintainer_email(&self) -> &str {
+        &self.maintainer_email
+    }
+
+    /// The date of the entry.
+    pub fn timestamp(&self) -> &DateTime<FixedOffset> {
+        &self.ts
+    }
+
+    /// Sets whoever prepared the upload, by default taken from the
+    /// environment.
+    pub fn set_maintainer(&mut self, name: &str, email: &str) {
+        self.maintainer_name = name.to_string();
+        self.maintainer_email = email.to_string();
+    }
+
+    /// Sets the distributions targeted, by default `UNRELEASED`.
+    pub fn set_distributions(&mut self, distributions: Vec<String>) {
+        self.distributions = distributions;
+    }
+
+    /// Sets the date of the entry, by default the time of creation.
+    pub fn set_timestamp(&mut self, ts: DateTime<FixedOffset>) {
+        self.ts = ts;
+    }
+
+    pub(crate) fn serialize(&self) -> String {
         format!(
-            "{} ({}) {}; urgency={}\n\n{}\n -- {} <{}>  {}\n\n",
+            "{} ({}) {}; urgency={}\n\n{}\n\n -- {} <{}>  {}\n\n",
             self.pkg,
             self.version,
             self.distributions.join(" "),
@@ -83,7 +161,7 @@ impl ChangelogEntry {
             self.detail,
             self.maintainer_name,
             self.maintainer_email,
-            self.ts.to_rfc2822()
+            format_date(&self.ts)
         )
     }
 }
@@ -124,21 +202,88 @@ impl Changelog {
     /// Reads a Debian changelog file into memory.
     pub fn from_file(in_file: &Path) -> io::Result<Changelog> {
         let file = File::open(in_file)?;
-        let mut buf = io::BufReader::new(file);
-        let entries = vec![];
-        loop {
-            let mut line = String::new();
-            buf.read_line(&mut line)?;
-            let is_eof = line.is_empty();
-
-            // Loop termination condition
-            if is_eof {
+        Changelog::from_reader(io::BufReader::new(file))
+    }
+
+    /// Parses a changelog from any buffered reader.
+    ///
+    /// Parsing stops at a line starting with `Old Changelog:` or at
+    /// Emacs local variables, as with dpkg. Other lines between entries
+    /// are skipped, while an entry without a valid trailer line is an
+    /// error.
+    pub fn from_reader<R: BufRead>(buf: R) -> io::Result<Changelog> {
+        let header = Regex::new(CHANGELOG_HEADER).unwrap();
+        let trailer = Regex::new(CHANGELOG_TRAILER).unwrap();
+        let invalid = |n: usize, msg: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {n}: {msg}"),
+            )
+        };
+        let mut entries = vec![];
+        let mut current: Option<(ChangelogEntry, Vec<String>)> = None;
+        for (i, line) in buf.lines().enumerate() {
+            let line = line?;
+            let n = i + 1;
+            if let Some((mut entry, mut lines)) = current.take() {
+                if let Some(c) = trailer.captures(&line) {
+                    entry.maintainer_name = c[1].to_string();
+                    entry.maintainer_email = c[2].to_string();
+                    entry.ts = DateTime::parse_from_rfc2822(c[3].trim())
+                        .map_err(|_| invalid(n, "invalid date"))?;
+                    while lines.last().map_or(false, |l| l.trim().is_empty()) {
+                        lines.pop();
+                    }
+                    let start = lines
+                        .iter()
+                        .position(|l| !l.trim().is_empty())
+                        .unwrap_or(lines.len());
+                    entry.detail = lines[start..].join("\n");
+                    entries.push(entry);
+                } else if header.is_match(&line) || line.starts_with(" --") {
+                    return Err(invalid(n, "expected trailer line"));
+                } else {
+                    lines.push(line.trim_end().to_string());
+                    current = Some((entry, lines));
+                }
+            } else if line.starts_with("Old Changelog:")
+                || line.starts_with("Local variables:")
+                || line.starts_with("# Local variables:")
+            {
                 break;
+            } else if let Some(c) = header.captures(&line) {
+                let mut entry = ChangelogEntry::new(
+                    c[1].to_string(),
+                    c[2].to_string(),
+                    String::new(),
+                );
+                entry.distributions =
+                    c[3].split_whitespace().map(|d| d.to_string()).collect();
+                for (key, value) in
+                    c[4].split(',').filter_map(|kv| kv.trim().split_once('='))
+                {
+                    if key.eq_ignore_ascii_case("urgency") {
+                        entry.urgency = value.to_lowercase();
+                    }
+                }
+                current = Some((entry, vec![]));
+            } else if !line.trim().is_empty() {
+                debug!("Skipping unrecognized changelog line {}", n);
             }
         }
-
+        if current.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "missing trailer line of last entry",
+            ));
+        }
         Ok(Changelog { entries })
     }
+
+    /// All entries, latest first.
+    pub fn entries(&self) -> &Vec<ChangelogEntry> {
+        &self.entries
+    }
 }
 
 /// A helper routine to determine the default Debian maintainer name
@@ -228,6 +373,15 @@ impl ControlValue {
             }
         }
     }
+
+    /// Returns the raw string value, regardless of its type.
+    pub fn as_str(&self) -> &str {
+        match *self {
+            ControlValue::Simple(ref v)
+            | ControlValue::Folded(ref v)
+            | ControlValue::MultiLine(ref v) => v,
+        }
+    }
 }
 
 impl ControlEntry {
@@ -238,6 +392,16 @@ impl ControlEntry {
             value: ControlValue::new(key, val),
         }
     }
+
+    /// The key or field name of this entry.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// The value of this entry.
+    pub fn value(&self) -> &ControlValue {
+        &self.value
+    }
 }
 
 impl ControlParagraph {
@@ -283,15 +447,57 @@ impl ControlParagraph {
     pub fn get_entry(&self, key: &str) -> Option<&str> {
         for entry in &self.entries {
             if entry.key == key {
-                return Some(match entry.value {
-                    ControlValue::Simple(ref v)
-                    | ControlValue::Folded(ref v)
-                    | ControlValue::MultiLine(ref v) => v,
-                });
+                return Some(entry.value.as_str());
             }
         }
         None
     }
+
+    /// Iterate over all entries of the paragraph, in order.
+    pub fn entries(&self) -> impl Iterator<Item = &ControlEntry> {
+        self.entries.iter()
+    }
+}
+
+impl TryFrom<&Paragraph> for ControlParagraph {
+    type Error = deb822::Error;
+
+    /// Converts a paragraph, failing on the first line neither starting
+    /// nor continuing a field.
+    fn try_from(para: &Paragraph) -> Result<ControlParagraph, deb822::Error> {
+        if let Some(line) = para.invalid.first() {
+            return Err(deb822::Error {
+                msg: format!("invalid line '{}'", line.text),
+                line: Some(line.span.line),
+            });
+        }
+        let mut result = ControlParagraph::default();
+        for field in &para.fields {
+            result.add_entry(&field.name, field.full_value());
+        }
+        Ok(result)
+    }
+}
+
+impl fmt::Display for ControlParagraph {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{}: {}", entry.key, entry.value.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ControlFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, para) in self.paragraphs.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{para}")?;
+        }
+        Ok(())
+    }
 }
 
 impl ControlFile {
@@ -311,85 +517,33 @@ impl ControlFile {
 
     pub fn from_file(in_file: &Path) -> io::Result<ControlFile> {
         let file = File::open(in_file)?;
-        let mut buf = io::BufReader::new(file);
-        let mut paragraphs = Vec::new();
-        let mut cur_entry: Option<String> = None;
-        let mut cur_para = ControlParagraph::default();
-        loop {
-            let mut line = "".to_string();
-
-            buf.read_line(&mut line)?;
-            let is_eof = line.is_empty();
-
-            let (is_end_of_para, is_indented) = {
-                let trimmed_line = line.trim();
-                (
-                    trimmed_line.is_empty(),
-                    line.starts_with(' ') && line.len() > 1,
-                )
-            };
-
-            // Possibly terminate the current entry and append to the
-            // current paragraph.
-            cur_entry = match (cur_entry, is_indented, is_end_of_para) {
-                (Some(v), false, _) => {
-                    // terminate the last entry
-                    let mut v2 = v.splitn(2, ':');
-                    let key = v2.next().unwrap();
-                    match v2.next() {
-                        Some(value) => {
-                            let value = value.trim().to_string();
-                            cur_para.add_entry(key, value);
-                        }
-                        None => {
-                            // FIXME: handle this parser error!
-                            debug!(
-                                "Parser error in line before: '{}', with value '{}'",
-                                line, v
-                            );
-                        }
-                    };
+        ControlFile::from_reader(io::BufReader::new(file))
+    }
 
-                    // begin new entry
-                    if is_end_of_para {
-                        None
-                    } else {
-                        Some(line)
-                    }
-                }
-                (Some(v), true, false) => Some(v + &line),
-                (None, _, false) => Some(line),
-                (_, _, true) => None,
-            };
-
-            // Possibly terminate the current paragraph and append it
-            // to the main structure.
-            if is_end_of_para && !cur_para.entries.is_empty() {
+    /// Parses control data from any buffered reader.
+    ///
+    /// This is the parser behind `from_file`, useful for other files
+    /// using the same paragraph format (like the dpkg status database)
+    /// or for data not stored on disk. Clearsigned data is accepted,
+    /// the signature is not verified. Lines neither starting nor
+    /// continuing a field are an error. See the `deb822` module for a
+    /// representation keeping the source location of every field.
+    pub fn from_reader<R: BufRead>(buf: R) -> io::Result<ControlFile> {
+        let mut paragraphs = Vec::new();
+        for para in Deb822Reader::new(buf) {
+            let cur_para = ControlParagraph::try_from(&para?)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            if !cur_para.entries.is_empty() {
                 paragraphs.push(cur_para);
-                cur_para = ControlParagraph::default();
-            }
-
-            // Loop termination condition
-            if is_eof {
-                break;
             }
         }
-
         Ok(ControlFile { paragraphs })
     }
 
     pub fn serialize(&self, out_file: &Path) -> io::Result<()> {
         let mut file = File::create(out_file)?;
         for para in &self.paragraphs {
-            for entry in &para.entries {
-                let v = match entry.value.clone() {
-                    ControlValue::Simple(v)
-                    | ControlValue::Folded(v)
-                    | ControlValue::MultiLine(v) => v,
-                };
-                let s = entry.key.clone() + ": " + &v + "\n";
-                file.write_all(s.as_bytes())?;
-            }
+            file.write_all(para.to_string().as_bytes())?;
             file.write_all(b"\n")?;
         }
 
@@ -401,6 +555,39 @@ impl ControlFile {
     }
 }
 
+/// Splits the value of a multiline field into its first line and the
+/// following continuation lines.
+///
+/// The leading space of continuation lines is removed and lines
+/// consisting of a single dot are turned into empty lines.
+pub fn split_multiline(value: &str) -> (&str, Vec<&str>) {
+    let mut lines = value.lines();
+    let first = lines.next().unwrap_or("").trim();
+    let rest = lines
+        .map(|l| l.strip_prefix(' ').unwrap_or(l.trim_start()))
+        .map(|l| {
+            if l.trim_end() == "." {
+                ""
+            } else {
+                l.trim_end()
+            }
+        })
+        .collect();
+    (first, rest)
+}
+
+/// Joins a first line and continuation lines into the value of a
+/// multiline field, the inverse of `split_multiline`.
+pub fn join_multiline<S: AsRef<str>>(first: &str, lines: &[S]) -> String {
+    let mut result = first.to_string();
+    for line in lines {
+        let line = line.as_ref();
+        result += if line.is_empty() { "\n ." } else { "\n " };
+        result += line;
+    }
+    result
+}
+
 /// Version relations
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum VRel {
@@ -411,6 +598,30 @@ pub enum VRel {
     Equal,
 }
 
+impl VRel {
+    /// Check whether the result of comparing a version to the one of
+    /// the relation satisfies it.
+    pub fn matches(&self, ord: Ordering) -> bool {
+        match *self {
+            VRel::GreaterOrEqual => ord != Ordering::Less,
+            VRel::Greater => ord == Ordering::Greater,
+            VRel::LesserOrEqual => ord != Ordering::Greater,
+            VRel::Lesser => ord == Ordering::Less,
+            VRel::Equal => ord == Ordering::Equal,
+        }
+    }
+
+    /// Check whether `version` satisfies this relation to `reference`,
+    /// e.g. whether `version >= reference` for `GreaterOrEqual`.
+    pub fn is_satisfied_by(
+        &self,
+        version: &Version,
+        reference: &Version,
+    ) -> bool {
+        self.matches(version.cmp(reference))
+    }
+}
+
 impl fmt::Display for VRel {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -430,6 +641,21 @@ pub struct SingleDependency {
     pub version: Option<(VRel, Version)>,
     pub arch: Option<String>,
     pub condition: Option<String>,
+    /// the build profile restriction lists following the first one,
+    /// like `stage1` in `<!nocheck> <stage1>`
+    pub extra_conditions: Vec<String>,
+}
+
+impl SingleDependency {
+    /// All build profile restriction lists, like `!nocheck` for
+    /// `<!nocheck>`, any of which has to hold.
+    pub fn conditions(&self) -> Vec<&str> {
+        self.condition
+            .iter()
+            .chain(&self.extra_conditions)
+            .map(|c| c.as_str())
+            .collect()
+    }
 }
 
 impl fmt::Display for SingleDependency {
@@ -443,7 +669,11 @@ impl fmt::Display for SingleDependency {
             (&Some((ref vrel, ref ver)), Some(a)) => {
                 write!(f, "{} ({} {}) [{}]", self.package, vrel, ver, a)
             }
+        }?;
+        for condition in self.conditions() {
+            write!(f, " <{condition}>")?;
         }
+        Ok(())
     }
 }
 
@@ -476,7 +706,6 @@ pub fn parse_single_dep(s: &str) -> Result<SingleDependency, &'static str> {
         InArch,
         InDependencyCondition,
         PreDependencyCondition,
-        Done,
     }
     let mut st = ST::PackageName;
     let mut result = SingleDependency {
@@ -484,10 +713,12 @@ pub fn parse_single_dep(s: &str) -> Result<SingleDependency, &'static str> {
         version: None,
         arch: None,
         condition: None,
+        extra_conditions: vec![],
     };
     let mut vrel = "".to_string();
     let mut vdef = "".to_string();
     let mut arch = "".to_string();
+    let mut condition = "".to_string();
     for ch in s.chars() {
         match st {
             ST::PackageName => {
@@ -505,7 +736,6 @@ pub fn parse_single_dep(s: &str) -> Result<SingleDependency, &'static str> {
                     st = ST::InVersionRel;
                 } else if ch == '<' {
                     st = ST::InDependencyCondition;
-                    result.condition = Some("".to_string());
                 } else if ch == '[' {
                     st = ST::InArch;
                 } else {
@@ -551,6 +781,8 @@ pub fn parse_single_dep(s: &str) -> Result<SingleDependency, &'static str> {
                 if ch.is_whitespace() {
                 } else if ch == '[' {
                     st = ST::InArch;
+                } else if ch == '<' {
+                    st = ST::InDependencyCondition;
                 } else {
                     return Err("garbage after version");
                 }
@@ -570,29 +802,28 @@ pub fn parse_single_dep(s: &str) -> Result<SingleDependency, &'static str> {
             }
             ST::InDependencyCondition => {
                 if ch == '>' {
-                    st = ST::Done
-                } else {
+                    let list = condition.trim().to_string();
+                    if list.is_empty() {
+                        return Err("empty restriction list given");
+                    }
                     match result.condition {
-                        Some(ref mut c) => c.push(ch),
-                        _ => unreachable!(),
-                    };
+                        None => result.condition = Some(list),
+                        Some(_) => result.extra_conditions.push(list),
+                    }
+                    condition.clear();
+                    st = ST::PreDependencyCondition;
+                } else {
+                    condition.push(ch);
                 }
             }
             ST::PreDependencyCondition => {
                 if ch.is_whitespace() {
-                    continue;
-                }
-                if ch == '<' {
+                } else if ch == '<' {
                     st = ST::InDependencyCondition;
-                    result.condition = Some("".to_string());
-                } else {
-                    st = ST::Done;
-                }
-            }
-            ST::Done => {
-                if ch.is_whitespace() {
-                } else {
+                } else if result.condition.is_none() {
                     return Err("garbage after arch");
+                } else {
+                    return Err("garbage after restriction list");
                 }
             }
         }
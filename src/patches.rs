@@ -0,0 +1,336 @@
+//! Patch series and DEP-3 patch headers
+//!
+//! Source packages in format `3.0 (quilt)` keep their changes to the
+//! upstream sources in `debian/patches`, applied in the order given by
+//! `debian/patches/series`. Each patch should start with a DEP-3
+//! header describing its origin and status.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::patches::{PatchHeader, PatchSeries};
+//!
+//! let series: PatchSeries = "fix-build.patch\n# disabled.patch\n"
+//!     .parse()
+//!     .unwrap();
+//! assert_eq!(series.patches().collect::<Vec<_>>().len(), 1);
+//!
+//! let header = PatchHeader::parse(
+//!     "Description: Fix the build\n\
+//!      Forwarded: not-needed\n\
+//!      ---\n\
+//!      --- a/Makefile\n",
+//! );
+//! assert_eq!(header.description.as_deref(), Some("Fix the build"));
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// A single line of a series file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SeriesLine {
+    /// A patch to apply, with options like `-p0` and a trailing comment.
+    Patch {
+        name: String,
+        options: Vec<String>,
+        comment: Option<String>,
+    },
+    /// A line holding nothing but a comment, without the leading `#`.
+    Comment(String),
+    /// An empty line.
+    Blank,
+}
+
+/// The contents of a `debian/patches/series` file.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct PatchSeries {
+    lines: Vec<SeriesLine>,
+}
+
+/// The DEP-3 header of a patch.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct PatchHeader {
+    /// short description, from `Description` or `Subject`
+    pub description: Option<String>,
+    /// long description, from continuation lines and free-form text
+    pub long_description: Option<String>,
+    /// authors, from `Author` or `From`
+    pub author: Vec<String>,
+    /// reviewers, from `Reviewed-by` or `Acked-by`
+    pub reviewed_by: Vec<String>,
+    pub origin: Option<String>,
+    /// bug references: `Bug` with no vendor, `Bug-<Vendor>` otherwise
+    pub bugs: Vec<(Option<String>, String)>,
+    pub forwarded: Option<String>,
+    pub applied_upstream: Option<String>,
+    pub last_update: Option<String>,
+    /// any other fields
+    pub extra: Vec<(String, String)>,
+}
+
+/// Checks that a patch name stays within the patches directory, like
+/// dpkg-source does: it has to be relative, without `..` components.
+fn valid_patch_name(name: &str) -> bool {
+    !name.starts_with('/') && !name.split('/').any(|c| c == "..")
+}
+
+impl SeriesLine {
+    fn parse(line: &str) -> Result<SeriesLine, &'static str> {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            return Ok(SeriesLine::Blank);
+        }
+        if let Some(c) = trimmed.strip_prefix('#') {
+            return Ok(SeriesLine::Comment(c.to_string()));
+        }
+        let (content, comment) = match trimmed.split_once(" #") {
+            Some((c, comment)) => (c, Some(comment.trim().to_string())),
+            None => (trimmed, None),
+        };
+        let mut words = content.split_whitespace();
+        let name = words.next().unwrap_or("").to_string();
+        if !valid_patch_name(&name) {
+            return Err("patch name outside of the patches directory");
+        }
+        Ok(SeriesLine::Patch {
+            name,
+            options: words.map(|w| w.to_string()).collect(),
+            comment,
+        })
+    }
+}
+
+impl fmt::Display for SeriesLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SeriesLine::Patch {
+                ref name,
+                ref options,
+                ref comment,
+            } => {
+                write!(f, "{name}")?;
+                for o in options {
+                    write!(f, " {o}")?;
+                }
+                match comment {
+                    Some(c) => write!(f, " # {c}"),
+                    None => Ok(()),
+                }
+            }
+            SeriesLine::Comment(ref c) => write!(f, "#{c}"),
+            SeriesLine::Blank => Ok(()),
+        }
+    }
+}
+
+impl PatchSeries {
+    /// Reads and parses a series file from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<PatchSeries> {
+        PatchSeries::parse(&fs::read_to_string(in_file)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Reads the series of an unpacked source package, given its
+    /// `debian` directory. A missing series file yields an empty series.
+    pub fn from_dir(debian_dir: &Path) -> io::Result<PatchSeries> {
+        let path = debian_dir.join("patches").join("series");
+        match PatchSeries::from_file(&path) {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                Ok(PatchSeries::default())
+            }
+            result => result,
+        }
+    }
+
+    /// Parses the contents of a series file, refusing absolute patch
+    /// names and names with `..` components.
+    pub fn parse(s: &str) -> Result<PatchSeries, &'static str> {
+        Ok(PatchSeries {
+            lines: s
+                .lines()
+                .map(SeriesLine::parse)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// All lines, including comments.
+    pub fn lines(&self) -> &Vec<SeriesLine> {
+        &self.lines
+    }
+
+    /// Names of the patches to apply, in order.
+    pub fn patches(&self) -> impl Iterator<Item = &str> {
+        self.lines.iter().filter_map(|l| match *l {
+            SeriesLine::Patch { ref name, .. } => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Paths of the patches to apply, relative to the given `patches`
+    /// directory.
+    pub fn patch_paths(&self, patches_dir: &Path) -> Vec<PathBuf> {
+        self.patches().map(|p| patches_dir.join(p)).collect()
+    }
+
+    /// The strip level to use for a patch, as given by a `-pN` option,
+    /// defaulting to 1.
+    pub fn strip_level(&self, patch: &str) -> Option<u32> {
+        self.lines.iter().find_map(|l| match *l {
+            SeriesLine::Patch {
+                ref name,
+                ref options,
+                ..
+            } if name == patch => Some(
+                options
+                    .iter()
+                    .find_map(|o| o.strip_prefix("-p")?.parse().ok())
+                    .unwrap_or(1),
+            ),
+            _ => None,
+        })
+    }
+
+    /// Append a patch to the series.
+    pub fn push(&mut self, name: &str) {
+        self.lines.push(SeriesLine::Patch {
+            name: name.to_string(),
+            options: vec![],
+            comment: None,
+        });
+    }
+
+    /// Remove a patch from the series, returning true if it was found.
+    pub fn remove(&mut self, patch: &str) -> bool {
+        let len = self.lines.len();
+        self.lines.retain(
+            |l| !matches!(*l, SeriesLine::Patch { ref name, .. } if name == patch),
+        );
+        self.lines.len() != len
+    }
+}
+
+impl FromStr for PatchSeries {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PatchSeries::parse(s)
+    }
+}
+
+impl fmt::Display for PatchSeries {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{line}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks whether a line marks the end of the header and the start of
+/// the actual diff.
+fn is_diff_start(line: &str) -> bool {
+    line == "---"
+        || line.starts_with("--- ")
+        || line.starts_with("diff ")
+        || line.starts_with("Index: ")
+        || line.starts_with("=== ")
+}
+
+impl PatchHeader {
+    /// Reads the header of a patch file on disk.
+    pub fn from_file(in_file: &Path) -> io::Result<PatchHeader> {
+        let content = fs::read(in_file)?;
+        Ok(PatchHeader::parse(&String::from_utf8_lossy(&content)))
+    }
+
+    /// Parses the header of a patch, stopping at the start of the diff.
+    pub fn parse(patch: &str) -> PatchHeader {
+        let mut fields: Vec<(String, String)> = vec![];
+        let mut free_text: Vec<&str> = vec![];
+        let mut in_field = false;
+        for line in patch.lines() {
+            if is_diff_start(line) {
+                break;
+            }
+            if in_field && line.starts_with([' ', '\t']) {
+                let (_, ref mut v) = fields.last_mut().unwrap();
+                v.push('\n');
+                v.push_str(line.trim());
+                continue;
+            }
+            in_field = false;
+            let field = line.split_once(':').filter(|(k, _)| {
+                !k.is_empty()
+                    && k.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            });
+            match field {
+                Some((k, v)) => {
+                    fields.push((k.to_string(), v.trim().to_string()));
+                    in_field = true;
+                }
+                None => free_text.push(line),
+            }
+        }
+
+        let mut header = PatchHeader::default();
+        let mut long = vec![];
+        for (k, v) in fields {
+            match k.to_lowercase().as_str() {
+                "description" | "subject" => {
+                    let (short, rest) = v.split_once('\n').unwrap_or((&v, ""));
+                    let short = short.trim_start_matches("[PATCH]").trim();
+                    header.description = Some(short.to_string());
+                    if !rest.is_empty() {
+                        long.extend(rest.lines().map(|l| match l {
+                            "." => "".to_string(),
+                            l => l.to_string(),
+                        }));
+                    }
+                }
+                "author" | "from" => header.author.push(v),
+                "reviewed-by" | "acked-by" => header.reviewed_by.push(v),
+                "origin" => header.origin = Some(v),
+                "bug" => header.bugs.push((None, v)),
+                "forwarded" => header.forwarded = Some(v),
+                "applied-upstream" => header.applied_upstream = Some(v),
+                "last-update" => header.last_update = Some(v),
+                lower if lower.starts_with("bug-") => {
+                    header.bugs.push((Some(k[4..].to_string()), v))
+                }
+                _ => header.extra.push((k, v)),
+            }
+        }
+        let free = free_text.join("\n");
+        let free = free.trim();
+        if !free.is_empty() {
+            if !long.is_empty() {
+                long.push("".to_string());
+            }
+            long.push(free.to_string());
+        }
+        if !long.is_empty() {
+            header.long_description = Some(long.join("\n"));
+        }
+        header
+    }
+
+    /// Whether the patch has been or does not need to be forwarded
+    /// upstream.
+    ///
+    /// Per DEP-3, patches with an upstream origin count as forwarded
+    /// even without a `Forwarded` field.
+    pub fn is_forwarded(&self) -> bool {
+        match self.forwarded.as_deref() {
+            Some("no") => false,
+            Some(_) => true,
+            None => self
+                .origin
+                .as_deref()
+                .map_or(false, |o| o.starts_with("upstream")),
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! Parser for popularity-contest results
+//!
+//! The popularity contest collects reports on the packages installed
+//! and used on participating systems. Its summaries are published as
+//! `all-popcon-results.txt`, listing for each package the number of
+//! systems using it regularly, having it installed but not used
+//! recently, having it installed only recently, and having it
+//! installed without any files:
+//!
+//! ```text
+//! Submissions: 230041
+//! Package: hello 1020 3208 12 4
+//! ```
+//!
+//! as well as rankings like `by_inst`, with a line per package giving
+//! its rank, the number of installations and the same counts:
+//!
+//! ```text
+//! 1     dpkg    228910 213530  1280  14083    17 (Dpkg Developers)
+//! ```
+//!
+//! Both formats are understood.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::popcon::PopconResults;
+//!
+//! let results: PopconResults =
+//!     "Submissions: 100\nPackage: hello 10 20 3 0\n".parse().unwrap();
+//! let hello = results.get("hello").unwrap();
+//! assert_eq!(hello.inst, 33);
+//! assert_eq!(hello.vote, 10);
+//! ```
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::package::{ControlFile, ControlParagraph};
+
+/// The popcon counts of a single package.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PopconEntry {
+    pub package: String,
+    /// the number of systems having the package installed
+    pub inst: u64,
+    /// the number of systems using the package regularly
+    pub vote: u64,
+    /// the number of systems not having used the package recently
+    pub old: u64,
+    /// the number of systems having upgraded the package recently
+    pub recent: u64,
+    /// the number of systems without any files of the package
+    pub no_files: u64,
+    /// the maintainer, as given in rankings
+    pub maintainer: Option<String>,
+}
+
+/// Parsed popularity-contest results.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct PopconResults {
+    /// the number of systems reporting, if given
+    pub submissions: Option<u64>,
+    entries: Vec<PopconEntry>,
+    by_package: HashMap<String, usize>,
+}
+
+fn counts(fields: &[&str]) -> Result<Vec<u64>, &'static str> {
+    fields
+        .iter()
+        .map(|f| f.parse().map_err(|_| "invalid count"))
+        .collect()
+}
+
+impl PopconResults {
+    /// Reads and parses popcon results from disk, uncompressed.
+    pub fn from_file(in_file: &Path) -> io::Result<PopconResults> {
+        PopconResults::from_reader(io::BufReader::new(fs::File::open(in_file)?))
+    }
+
+    /// Parses popcon results from any buffered reader.
+    pub fn from_reader<R: BufRead>(buf: R) -> io::Result<PopconResults> {
+        let mut result = PopconResults::default();
+        for line in buf.lines() {
+            let more = result
+                .parse_line(&line?)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            if !more {
+                break;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Parses a single line, returning false at the end of a ranking,
+    /// where totals follow.
+    fn parse_line(&mut self, line: &str) -> Result<bool, &'static str> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields.as_slice() {
+            [] => {}
+            [first, ..] if first.starts_with('#') => {}
+            [first] if first.starts_with("---") => return Ok(false),
+            ["Submissions:", count] => {
+                self.submissions =
+                    Some(count.parse().map_err(|_| "invalid submissions")?);
+            }
+            ["Package:", package, rest @ ..] => match counts(rest)?[..] {
+                [vote, old, recent, no_files] => self.add(PopconEntry {
+                    package: package.to_string(),
+                    inst: vote + old + recent + no_files,
+                    vote,
+                    old,
+                    recent,
+                    no_files,
+                    maintainer: None,
+                }),
+                _ => return Err("invalid package line"),
+            },
+            [rank, package, rest @ ..]
+                if rank.bytes().all(|b| b.is_ascii_digit()) =>
+            {
+                if rest.len() < 5 {
+                    return Err("invalid ranking line");
+                }
+                let (numbers, maintainer) = rest.split_at(5);
+                let maintainer = maintainer.join(" ");
+                let maintainer = maintainer
+                    .strip_prefix('(')
+                    .and_then(|m| m.strip_suffix(')'))
+                    .map(str::to_string);
+                let counts = counts(numbers)?;
+                self.add(PopconEntry {
+                    package: package.to_string(),
+                    inst: counts[0],
+                    vote: counts[1],
+                    old: counts[2],
+                    recent: counts[3],
+                    no_files: counts[4],
+                    maintainer,
+                });
+            }
+            // other summaries, like the ones per architecture
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    /// Adds the counts of a package, replacing any previous ones.
+    pub fn add(&mut self, entry: PopconEntry) {
+        match self.by_package.get(&entry.package) {
+            Some(&i) => self.entries[i] = entry,
+            None => {
+                self.by_package
+                    .insert(entry.package.clone(), self.entries.len());
+                self.entries.push(entry);
+            }
+        }
+    }
+
+    /// All entries, in the order given.
+    pub fn entries(&self) -> &[PopconEntry] {
+        &self.entries
+    }
+
+    /// Look up the counts of a package.
+    pub fn get(&self, package: &str) -> Option<&PopconEntry> {
+        self.by_package.get(package).map(|&i| &self.entries[i])
+    }
+
+    /// The entries sorted by the number of installations, the most
+    /// popular first, then by package name.
+    pub fn by_inst(&self) -> Vec<&PopconEntry> {
+        let mut result: Vec<&PopconEntry> = self.entries.iter().collect();
+        result.sort_by(|a, b| {
+            b.inst.cmp(&a.inst).then_with(|| a.package.cmp(&b.package))
+        });
+        result
+    }
+
+    /// Pairs the entries of a Packages index with their popcon counts,
+    /// in the order of the index.
+    pub fn join<'a>(
+        &'a self,
+        packages: &'a ControlFile,
+    ) -> Vec<(&'a ControlParagraph, Option<&'a PopconEntry>)> {
+        packages
+            .get_paragraphs()
+            .iter()
+            .map(|p| (p, p.get_entry("Package").and_then(|n| self.get(n))))
+            .collect()
+    }
+}
+
+impl FromStr for PopconResults {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut result = PopconResults::default();
+        for line in s.lines() {
+            if !result.parse_line(line)? {
+                break;
+            }
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,1074 @@
+//! Applying patch series like quilt
+//!
+//! Parses unified diffs and applies the patches listed in
+//! `debian/patches/series` to a source tree, one at a time, keeping
+//! track of the applied patches in the `.pc` directory the same way
+//! quilt and dpkg-source do. Before a patch modifies a file, the
+//! original is saved below `.pc/<patch>/`, so popping the patch can
+//! restore it.
+//!
+//! Hunks that do not apply at the position given are searched for
+//! nearby and, failing that, with up to two lines of context ignored,
+//! like GNU patch does with its default fuzz factor. Such hunks are
+//! listed in the resulting report. A patch with hunks that do not apply
+//! at all is not applied and its rejected hunks are reported instead.
+//!
+//! The tree can either be a directory on disk or held in memory.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::quilt::{MemoryTree, Quilt};
+//!
+//! let mut tree = MemoryTree::new();
+//! tree.insert("hello.c", "int main() {\n    return 1;\n}\n");
+//! tree.insert("debian/patches/series", "fix-return.patch\n");
+//! tree.insert(
+//!     "debian/patches/fix-return.patch",
+//!     "--- a/hello.c\n\
+//!      +++ b/hello.c\n\
+//!      @@ -1,3 +1,3 @@\n \
+//!      int main() {\n\
+//!      -    return 1;\n\
+//!      +    return 0;\n \
+//!      }\n",
+//! );
+//!
+//! let mut quilt = Quilt::new(tree).unwrap();
+//! let report = quilt.push().unwrap();
+//! assert!(report.is_clean());
+//! assert_eq!(quilt.top(), Some("fix-return.patch"));
+//! assert_eq!(
+//!     quilt.tree().get("hello.c"),
+//!     Some("int main() {\n    return 0;\n}\n")
+//! );
+//!
+//! quilt.pop().unwrap();
+//! assert_eq!(
+//!     quilt.tree().get("hello.c"),
+//!     Some("int main() {\n    return 1;\n}\n")
+//! );
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::patches::PatchSeries;
+
+/// The directory holding the patches, relative to the tree.
+pub const PATCHES_DIR: &str = "debian/patches";
+
+/// The directory holding the state of applied patches.
+pub const PC_DIR: &str = ".pc";
+
+/// The number of context lines a hunk may ignore to apply, as with the
+/// default fuzz factor of GNU patch.
+pub const MAX_FUZZ: usize = 2;
+
+/// The number of context lines around changes in generated diffs.
+pub const DIFF_CONTEXT: usize = 3;
+
+/// A single line of a hunk, including its line break unless it is the
+/// last line of a file without one.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum HunkLine {
+    Context(String),
+    Remove(String),
+    Add(String),
+}
+
+/// A hunk of a unified diff.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Hunk {
+    /// the first line of the original range, counting from 1
+    pub old_start: usize,
+    pub old_len: usize,
+    /// the first line of the new range, counting from 1
+    pub new_start: usize,
+    pub new_len: usize,
+    /// text following the range, usually the enclosing function
+    pub section: String,
+    pub lines: Vec<HunkLine>,
+}
+
+/// The changes to a single file within a patch.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FilePatch {
+    /// the original file name, `None` for files created
+    pub old_name: Option<String>,
+    /// the new file name, `None` for files deleted
+    pub new_name: Option<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+/// A patch in unified diff format, possibly covering several files.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Patch {
+    files: Vec<FilePatch>,
+}
+
+/// A hunk that applied at a different position or with context lines
+/// ignored.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct HunkOffset {
+    /// the number of the hunk within its file, counting from 1
+    pub hunk: usize,
+    /// lines between the position given and where it applied
+    pub offset: isize,
+    /// context lines ignored at each end
+    pub fuzz: usize,
+}
+
+/// The result of applying a patch to a single file.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct FileReport {
+    /// the path of the file within the tree
+    pub path: String,
+    /// hunks that did not apply exactly as given
+    pub offsets: Vec<HunkOffset>,
+    /// hunks that did not apply, with their numbers
+    pub rejected: Vec<(usize, Hunk)>,
+}
+
+/// The result of applying a patch.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ApplyReport {
+    /// the name of the patch, if applied from a series
+    pub patch: Option<String>,
+    pub files: Vec<FileReport>,
+}
+
+/// A tree of text files that patches apply to, addressed by relative
+/// paths with `/` as separator.
+pub trait Tree: fmt::Debug {
+    /// Reads a file, returning `None` if it does not exist.
+    fn read(&self, path: &str) -> io::Result<Option<String>>;
+    /// Writes a file, creating parent directories as needed.
+    fn write(&mut self, path: &str, contents: &str) -> io::Result<()>;
+    /// Removes a file, if it exists.
+    fn remove(&mut self, path: &str) -> io::Result<()>;
+    /// Lists the paths of all files below a directory, relative to it.
+    fn list(&self, dir: &str) -> io::Result<Vec<String>>;
+}
+
+/// A tree held in memory.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct MemoryTree {
+    files: BTreeMap<String, String>,
+}
+
+/// A tree on disk, below a root directory.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DirTree {
+    root: PathBuf,
+}
+
+/// Applies the patches of a series to a tree.
+#[derive(Debug)]
+pub struct Quilt<T: Tree> {
+    tree: T,
+    series: PatchSeries,
+    applied: Vec<String>,
+}
+
+/// New contents of files, `None` for files to delete.
+type FileChanges = Vec<(String, Option<String>)>;
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Parses a range like `12,3` or `12` of a hunk header.
+fn parse_range(s: &str) -> Option<(usize, usize)> {
+    match s.split_once(',') {
+        Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+        None => Some((s.parse().ok()?, 1)),
+    }
+}
+
+/// Parses a hunk header like `@@ -1,3 +1,4 @@ fn main()`.
+fn parse_hunk_header(line: &str) -> Option<Hunk> {
+    let rest = line.strip_prefix("@@ -")?;
+    let (ranges, section) = rest.split_once(" @@")?;
+    let (old, new) = ranges.split_once(" +")?;
+    let (old_start, old_len) = parse_range(old)?;
+    let (new_start, new_len) = parse_range(new)?;
+    Some(Hunk {
+        old_start,
+        old_len,
+        new_start,
+        new_len,
+        section: section.trim().to_string(),
+        lines: vec![],
+    })
+}
+
+/// Extracts the file name from a `---` or `+++` line, dropping any
+/// timestamp following a tab.
+fn parse_file_name(s: &str) -> Option<String> {
+    let name = s.split('\t').next().unwrap_or("").trim_end();
+    match name {
+        "" | "/dev/null" => None,
+        name => Some(name.to_string()),
+    }
+}
+
+/// Strips the given number of leading components from a path, as with
+/// the `-p` option of patch.
+fn strip_path(name: &str, strip: u32) -> Option<String> {
+    let mut parts = name.split('/').filter(|p| !p.is_empty() && *p != ".");
+    for _ in 0..strip {
+        parts.next()?;
+    }
+    let parts: Vec<&str> = parts.collect();
+    if parts.is_empty() || parts.contains(&"..") {
+        return None;
+    }
+    Some(parts.join("/"))
+}
+
+/// Splits text into lines, keeping their line breaks.
+fn split_lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+/// Computes the shortest edit script turning one list of lines into
+/// another, using the algorithm of Myers.
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<HunkLine> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let offset = n + m + 1;
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    let mut trace = vec![];
+    'search: for d in 0..=n + m {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let i = (k + offset) as usize;
+            let mut x = match k == -d || (k != d && v[i - 1] < v[i + 1]) {
+                true => v[i + 1],
+                false => v[i - 1] + 1,
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[i] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+    // walk back through the furthest reaching paths
+    let mut result = vec![];
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let i = (k + offset) as usize;
+        let prev_k = match k == -d || (k != d && v[i - 1] < v[i + 1]) {
+            true => k + 1,
+            false => k - 1,
+        };
+        let prev_x = v[(prev_k + offset) as usize];
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            result.push(HunkLine::Context(old[x as usize - 1].to_string()));
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            match x == prev_x {
+                true => result.push(HunkLine::Add(new[y as usize - 1].into())),
+                false => {
+                    result.push(HunkLine::Remove(old[x as usize - 1].into()))
+                }
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    result.reverse();
+    result
+}
+
+/// Groups an edit script into hunks with the given number of context
+/// lines.
+fn group_hunks(lines: Vec<HunkLine>, context: usize) -> Vec<Hunk> {
+    let changes: Vec<usize> = (0..lines.len())
+        .filter(|&i| !matches!(lines[i], HunkLine::Context(_)))
+        .collect();
+    // the ranges of lines covered by each hunk
+    let mut ranges: Vec<(usize, usize)> = vec![];
+    for &i in &changes {
+        let start = i.saturating_sub(context);
+        let end = (i + context + 1).min(lines.len());
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+    let mut result = vec![];
+    let (mut old_pos, mut new_pos, mut pos) = (0, 0, 0);
+    for (start, end) in ranges {
+        for line in &lines[pos..start] {
+            if let HunkLine::Context(_) = *line {
+                old_pos += 1;
+                new_pos += 1;
+            }
+        }
+        let mut hunk = Hunk {
+            old_start: old_pos + 1,
+            old_len: 0,
+            new_start: new_pos + 1,
+            new_len: 0,
+            section: String::new(),
+            lines: lines[start..end].to_vec(),
+        };
+        for line in &hunk.lines {
+            match *line {
+                HunkLine::Context(_) => {
+                    hunk.old_len += 1;
+                    hunk.new_len += 1;
+                }
+                HunkLine::Remove(_) => hunk.old_len += 1,
+                HunkLine::Add(_) => hunk.new_len += 1,
+            }
+        }
+        old_pos += hunk.old_len;
+        new_pos += hunk.new_len;
+        // an empty range starts after the line given
+        if hunk.old_len == 0 {
+            hunk.old_start -= 1;
+        }
+        if hunk.new_len == 0 {
+            hunk.new_start -= 1;
+        }
+        result.push(hunk);
+        pos = end;
+    }
+    result
+}
+
+impl Hunk {
+    /// The lines expected in the original file.
+    pub fn old_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|l| match *l {
+                HunkLine::Context(ref s) | HunkLine::Remove(ref s) => {
+                    Some(s.as_str())
+                }
+                HunkLine::Add(_) => None,
+            })
+            .collect()
+    }
+
+    /// The lines replacing them in the new file.
+    pub fn new_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|l| match *l {
+                HunkLine::Context(ref s) | HunkLine::Add(ref s) => {
+                    Some(s.as_str())
+                }
+                HunkLine::Remove(_) => None,
+            })
+            .collect()
+    }
+
+    /// The number of context lines at the start and end of the hunk.
+    fn context_len(&self) -> (usize, usize) {
+        let is_context = |l: &&HunkLine| matches!(l, HunkLine::Context(_));
+        let leading = self.lines.iter().take_while(is_context).count();
+        let trailing = self.lines.iter().rev().take_while(is_context).count();
+        (leading, trailing)
+    }
+
+    /// The hunk with up to `fuzz` context lines dropped at each end,
+    /// along with the number of lines dropped at the start.
+    fn fuzzed(&self, fuzz: usize) -> (Vec<&str>, Vec<&str>, usize) {
+        let (leading, trailing) = self.context_len();
+        let lead = fuzz.min(leading);
+        let trail = fuzz.min(trailing).min(self.lines.len() - lead);
+        let lines = &self.lines[lead..self.lines.len() - trail];
+        let mut old = vec![];
+        let mut new = vec![];
+        for line in lines {
+            match *line {
+                HunkLine::Context(ref s) => {
+                    old.push(s.as_str());
+                    new.push(s.as_str());
+                }
+                HunkLine::Remove(ref s) => old.push(s.as_str()),
+                HunkLine::Add(ref s) => new.push(s.as_str()),
+            }
+        }
+        (old, new, lead)
+    }
+}
+
+impl fmt::Display for Hunk {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "@@ -{},{} +{},{} @@",
+            self.old_start, self.old_len, self.new_start, self.new_len
+        )?;
+        if !self.section.is_empty() {
+            write!(f, " {}", self.section)?;
+        }
+        writeln!(f)?;
+        for line in &self.lines {
+            let (prefix, s) = match *line {
+                HunkLine::Context(ref s) => (' ', s),
+                HunkLine::Remove(ref s) => ('-', s),
+                HunkLine::Add(ref s) => ('+', s),
+            };
+            write!(f, "{prefix}{s}")?;
+            if !s.ends_with('\n') {
+                write!(f, "\n\\ No newline at end of file\n")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for FilePatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let old = self.old_name.as_deref().unwrap_or("/dev/null");
+        let new = self.new_name.as_deref().unwrap_or("/dev/null");
+        writeln!(f, "--- {old}")?;
+        writeln!(f, "+++ {new}")?;
+        for hunk in &self.hunks {
+            write!(f, "{hunk}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FilePatch {
+    /// Computes the changes between two versions of a file, with
+    /// `DIFF_CONTEXT` lines of context around each change. The names
+    /// are `None` for files created or deleted.
+    pub fn diff(
+        old_name: Option<&str>,
+        new_name: Option<&str>,
+        old: &str,
+        new: &str,
+    ) -> FilePatch {
+        let lines = diff_lines(&split_lines(old), &split_lines(new));
+        FilePatch {
+            old_name: old_name.map(|n| n.to_string()),
+            new_name: new_name.map(|n| n.to_string()),
+            hunks: group_hunks(lines, DIFF_CONTEXT),
+        }
+    }
+
+    /// The path of the file to patch within the tree, after stripping
+    /// the given number of leading components from the names in the
+    /// patch. The new name is preferred, unless the file is deleted.
+    pub fn path(&self, strip: u32) -> Option<String> {
+        self.new_name
+            .as_deref()
+            .or(self.old_name.as_deref())
+            .and_then(|name| strip_path(name, strip))
+    }
+
+    /// Whether the patch creates the file.
+    pub fn is_creation(&self) -> bool {
+        self.old_name.is_none()
+    }
+
+    /// Whether the patch deletes the file.
+    pub fn is_deletion(&self) -> bool {
+        self.new_name.is_none()
+    }
+
+    /// Applies the hunks to the contents of a file, returning the new
+    /// contents along with a report of hunks that did not apply
+    /// exactly.
+    pub fn apply_to(&self, path: &str, text: &str) -> (String, FileReport) {
+        let lines = split_lines(text);
+        let mut report = FileReport {
+            path: path.to_string(),
+            ..Default::default()
+        };
+        let mut out: Vec<&str> = vec![];
+        let mut pos = 0;
+        let mut delta: isize = 0;
+        for (i, hunk) in self.hunks.iter().enumerate() {
+            let found = (0..=MAX_FUZZ).find_map(|fuzz| {
+                let (old, new, lead) = hunk.fuzzed(fuzz);
+                // an empty range starts after the line given
+                let base = match hunk.old_len {
+                    0 => hunk.old_start,
+                    _ => hunk.old_start.saturating_sub(1),
+                };
+                let expected = (base + lead) as isize + delta;
+                let last = lines.len().checked_sub(old.len())?;
+                (pos..=last)
+                    .filter(|&p| lines[p..p + old.len()] == old[..])
+                    .min_by_key(|&p| (p as isize - expected).abs())
+                    .map(|p| (p, old.len(), new, fuzz, p as isize - expected))
+            });
+            match found {
+                Some((p, old_len, new, fuzz, offset)) => {
+                    out.extend(&lines[pos..p]);
+                    out.extend(new);
+                    pos = p + old_len;
+                    delta += offset;
+                    if offset != 0 || fuzz != 0 {
+                        report.offsets.push(HunkOffset {
+                            hunk: i + 1,
+                            offset,
+                            fuzz,
+                        });
+                    }
+                }
+                None => report.rejected.push((i + 1, hunk.clone())),
+            }
+        }
+        out.extend(&lines[pos..]);
+        (out.concat(), report)
+    }
+}
+
+impl Patch {
+    /// Reads and parses a patch file from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<Patch> {
+        Patch::parse(&fs::read_to_string(in_file)?)
+    }
+
+    /// Parses a patch, skipping any text outside the diffs, like a
+    /// DEP-3 header.
+    pub fn parse(text: &str) -> io::Result<Patch> {
+        // the same splitter as for the files patched, so line breaks
+        // like `\r\n` are kept in hunks
+        let lines = split_lines(text);
+        let mut files: Vec<FilePatch> = vec![];
+        let mut i = 0;
+        while i < lines.len() {
+            let line = trim_line_break(lines[i]);
+            i += 1;
+            if let Some(old) = line.strip_prefix("--- ") {
+                let new = match lines
+                    .get(i)
+                    .and_then(|l| trim_line_break(l).strip_prefix("+++ "))
+                {
+                    Some(new) => new,
+                    None => continue,
+                };
+                i += 1;
+                files.push(FilePatch {
+                    old_name: parse_file_name(old),
+                    new_name: parse_file_name(new),
+                    hunks: vec![],
+                });
+                continue;
+            }
+            if !line.starts_with("@@ ") {
+                continue;
+            }
+            let file = files.last_mut().ok_or_else(|| {
+                invalid(format!("line {i}: hunk without file names"))
+            })?;
+            let mut hunk = parse_hunk_header(line).ok_or_else(|| {
+                invalid(format!("line {i}: invalid hunk header"))
+            })?;
+            let (mut old, mut new) = (0, 0);
+            while old < hunk.old_len || new < hunk.new_len {
+                let raw = *lines.get(i).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        format!("line {i}: incomplete hunk"),
+                    )
+                })?;
+                i += 1;
+                let line = trim_line_break(raw);
+                let mut s = match line.is_empty() {
+                    true => raw.to_string(),
+                    false => raw.get(1..).unwrap_or("").to_string(),
+                };
+                if !s.ends_with('\n') {
+                    s.push('\n');
+                }
+                match line.chars().next() {
+                    // some tools drop the space of empty context lines
+                    Some(' ') | None => {
+                        hunk.lines.push(HunkLine::Context(s));
+                        old += 1;
+                        new += 1;
+                    }
+                    Some('-') => {
+                        hunk.lines.push(HunkLine::Remove(s));
+                        old += 1;
+                    }
+                    Some('+') => {
+                        hunk.lines.push(HunkLine::Add(s));
+                        new += 1;
+                    }
+                    Some('\\') => no_newline(&mut hunk)?,
+                    _ => {
+                        return Err(invalid(format!(
+                            "line {i}: invalid line in hunk"
+                        )))
+                    }
+                }
+                if old > hunk.old_len || new > hunk.new_len {
+                    return Err(invalid(format!("line {i}: hunk too long")));
+                }
+            }
+            // the marker may follow the last line
+            if lines.get(i).map_or(false, |l| l.starts_with('\\')) {
+                no_newline(&mut hunk)?;
+                i += 1;
+            }
+            file.hunks.push(hunk);
+        }
+        Ok(Patch { files })
+    }
+
+    /// The changes to each file.
+    pub fn files(&self) -> &Vec<FilePatch> {
+        &self.files
+    }
+
+    /// Computes the changes of the patch to a tree, without modifying
+    /// it, as the new contents of each file, or `None` for files to
+    /// delete.
+    fn changes<T: Tree>(
+        &self,
+        tree: &T,
+        strip: u32,
+    ) -> io::Result<(FileChanges, Vec<FileReport>)> {
+        let mut changes: FileChanges = vec![];
+        let mut reports = vec![];
+        for file in &self.files {
+            let path = file.path(strip).ok_or_else(|| {
+                invalid("invalid file name in patch".to_string())
+            })?;
+            // a file may be patched more than once
+            let current = match changes.iter().rposition(|(p, _)| *p == path) {
+                Some(pos) => changes[pos].1.clone(),
+                None => tree.read(&path)?,
+            };
+            let result = match current {
+                Some(ref text) if !file.is_creation() => {
+                    Some(file.apply_to(&path, text))
+                }
+                Some(ref text) if text.is_empty() => {
+                    Some(file.apply_to(&path, text))
+                }
+                None if file.is_creation() => Some(file.apply_to(&path, "")),
+                // a missing file or one that already exists
+                _ => None,
+            };
+            let (text, report) = result.unwrap_or_else(|| {
+                let rejected = file.hunks.iter().cloned();
+                let report = FileReport {
+                    path: path.clone(),
+                    offsets: vec![],
+                    rejected: (1..).zip(rejected).collect(),
+                };
+                (String::new(), report)
+            });
+            let new = match file.is_deletion() && text.is_empty() {
+                true => None,
+                false => Some(text),
+            };
+            changes.push((path, new));
+            if !report.offsets.is_empty() || !report.rejected.is_empty() {
+                reports.push(report);
+            }
+        }
+        Ok((changes, reports))
+    }
+
+    /// Applies the patch to a tree, stripping the given number of
+    /// leading path components from file names. If any hunk is
+    /// rejected, the tree is left unchanged.
+    pub fn apply<T: Tree>(
+        &self,
+        tree: &mut T,
+        strip: u32,
+    ) -> io::Result<ApplyReport> {
+        let (changes, files) = self.changes(tree, strip)?;
+        let report = ApplyReport { patch: None, files };
+        if report.is_applied() {
+            write_changes(tree, changes)?;
+        }
+        Ok(report)
+    }
+}
+
+/// Strips the line break from a line split by `split_lines`.
+fn trim_line_break(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+/// Handles a `\ No newline at end of file` marker, which applies to the
+/// preceding line.
+fn no_newline(hunk: &mut Hunk) -> io::Result<()> {
+    match hunk.lines.last_mut() {
+        Some(HunkLine::Context(s) | HunkLine::Remove(s) | HunkLine::Add(s)) => {
+            s.truncate(trim_line_break(s).len());
+            Ok(())
+        }
+        None => Err(invalid("misplaced newline marker".to_string())),
+    }
+}
+
+fn write_changes<T: Tree>(
+    tree: &mut T,
+    changes: FileChanges,
+) -> io::Result<()> {
+    for (path, contents) in changes {
+        match contents {
+            Some(text) => tree.write(&path, &text)?,
+            None => tree.remove(&path)?,
+        }
+    }
+    Ok(())
+}
+
+impl ApplyReport {
+    /// Whether all hunks applied, so the patch was applied.
+    pub fn is_applied(&self) -> bool {
+        self.files.iter().all(|f| f.rejected.is_empty())
+    }
+
+    /// Whether all hunks applied exactly as given.
+    pub fn is_clean(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// The rejected hunks, with the paths of their files.
+    pub fn rejected(&self) -> Vec<(&str, &Hunk)> {
+        self.files
+            .iter()
+            .flat_map(|f| f.rejected.iter().map(|(_, h)| (f.path.as_str(), h)))
+            .collect()
+    }
+}
+
+impl fmt::Display for ApplyReport {
+    /// Describes the hunks that did not apply cleanly, similar to the
+    /// output of GNU patch, with rejected hunks in full.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for file in &self.files {
+            for o in &file.offsets {
+                write!(f, "{}: hunk #{} succeeded", file.path, o.hunk)?;
+                if o.fuzz > 0 {
+                    write!(f, " with fuzz {}", o.fuzz)?;
+                }
+                if o.offset != 0 {
+                    write!(f, " (offset {} lines)", o.offset)?;
+                }
+                writeln!(f, ".")?;
+            }
+            for (n, hunk) in &file.rejected {
+                writeln!(f, "{}: hunk #{n} FAILED:", file.path)?;
+                write!(f, "{hunk}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl MemoryTree {
+    pub fn new() -> MemoryTree {
+        MemoryTree::default()
+    }
+
+    /// Adds or replaces a file.
+    pub fn insert(&mut self, path: &str, contents: &str) {
+        self.files.insert(path.to_string(), contents.to_string());
+    }
+
+    /// The contents of a file, if present.
+    pub fn get(&self, path: &str) -> Option<&str> {
+        self.files.get(path).map(|s| s.as_str())
+    }
+
+    /// All files, sorted by path.
+    pub fn files(&self) -> &BTreeMap<String, String> {
+        &self.files
+    }
+}
+
+impl Tree for MemoryTree {
+    fn read(&self, path: &str) -> io::Result<Option<String>> {
+        Ok(self.files.get(path).cloned())
+    }
+
+    fn write(&mut self, path: &str, contents: &str) -> io::Result<()> {
+        self.insert(path, contents);
+        Ok(())
+    }
+
+    fn remove(&mut self, path: &str) -> io::Result<()> {
+        self.files.remove(path);
+        Ok(())
+    }
+
+    fn list(&self, dir: &str) -> io::Result<Vec<String>> {
+        let prefix = format!("{}/", dir.trim_end_matches('/'));
+        Ok(self
+            .files
+            .keys()
+            .filter_map(|p| p.strip_prefix(&prefix))
+            .map(|p| p.to_string())
+            .collect())
+    }
+}
+
+impl DirTree {
+    pub fn new(root: &Path) -> DirTree {
+        DirTree {
+            root: root.to_path_buf(),
+        }
+    }
+
+    /// The root directory of the tree.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+fn list_dir(dir: &Path, prefix: &str, out: &mut Vec<String>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let path = format!("{prefix}{name}");
+        if entry.file_type()?.is_dir() {
+            list_dir(&entry.path(), &format!("{path}/"), out)?;
+        } else {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+impl Tree for DirTree {
+    fn read(&self, path: &str) -> io::Result<Option<String>> {
+        match fs::read(self.root.join(path)) {
+            Ok(data) => String::from_utf8(data)
+                .map(Some)
+                .map_err(|_| invalid(format!("{path} is not valid UTF-8"))),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn write(&mut self, path: &str, contents: &str) -> io::Result<()> {
+        let path = self.root.join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, contents)
+    }
+
+    /// Removes a file along with any parent directories left empty.
+    fn remove(&mut self, path: &str) -> io::Result<()> {
+        let path = self.root.join(path);
+        match fs::remove_file(&path) {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            result => result?,
+        }
+        let mut dir = path.parent();
+        while let Some(d) = dir.filter(|d| *d != self.root) {
+            if fs::remove_dir(d).is_err() {
+                break;
+            }
+            dir = d.parent();
+        }
+        Ok(())
+    }
+
+    fn list(&self, dir: &str) -> io::Result<Vec<String>> {
+        let mut result = vec![];
+        match list_dir(&self.root.join(dir), "", &mut result) {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+            r => r?,
+        }
+        result.sort();
+        Ok(result)
+    }
+}
+
+impl<T: Tree> Quilt<T> {
+    /// Opens a tree, reading its series from `debian/patches/series`
+    /// and the patches already applied from `.pc/applied-patches`.
+    pub fn new(tree: T) -> io::Result<Quilt<T>> {
+        let series = tree
+            .read(&format!("{PATCHES_DIR}/series"))?
+            .map(|s| PatchSeries::parse(&s).map_err(|e| invalid(e.to_string())))
+            .transpose()?
+            .unwrap_or_default();
+        let applied: Vec<String> = tree
+            .read(&format!("{PC_DIR}/applied-patches"))?
+            .unwrap_or_default()
+            .lines()
+            .filter(|l| !l.is_empty())
+            .map(|l| l.to_string())
+            .collect();
+        let patches: Vec<&str> = series.patches().collect();
+        if !patches.starts_with(
+            &applied.iter().map(|p| p.as_str()).collect::<Vec<_>>(),
+        ) {
+            return Err(invalid(
+                "applied patches do not match the series".to_string(),
+            ));
+        }
+        Ok(Quilt {
+            tree,
+            series,
+            applied,
+        })
+    }
+
+    /// The tree patches are applied to.
+    pub fn tree(&self) -> &T {
+        &self.tree
+    }
+
+    /// Returns the tree, leaving the patches applied as they are.
+    pub fn into_tree(self) -> T {
+        self.tree
+    }
+
+    /// The series of patches.
+    pub fn series(&self) -> &PatchSeries {
+        &self.series
+    }
+
+    /// The patches applied, in order.
+    pub fn applied(&self) -> &Vec<String> {
+        &self.applied
+    }
+
+    /// The patches not applied yet, in order.
+    pub fn unapplied(&self) -> Vec<&str> {
+        self.series.patches().skip(self.applied.len()).collect()
+    }
+
+    /// The patch applied last.
+    pub fn top(&self) -> Option<&str> {
+        self.applied.last().map(|p| p.as_str())
+    }
+
+    fn save_state(&mut self) -> io::Result<()> {
+        let path = format!("{PC_DIR}/applied-patches");
+        if self.applied.is_empty() {
+            return self.tree.remove(&path);
+        }
+        self.tree.write(&format!("{PC_DIR}/.version"), "2\n")?;
+        self.tree
+            .write(&format!("{PC_DIR}/.quilt_patches"), "debian/patches\n")?;
+        self.tree
+            .write(&format!("{PC_DIR}/.quilt_series"), "series\n")?;
+        let mut applied = self.applied.join("\n");
+        applied.push('\n');
+        self.tree.write(&path, &applied)
+    }
+
+    /// Applies the next patch of the series.
+    ///
+    /// Before any file is modified, its original contents are saved
+    /// below `.pc/<patch>/`, as an empty file if it did not exist. If
+    /// any hunk is rejected, nothing is changed and the returned report
+    /// lists the rejected hunks.
+    pub fn push(&mut self) -> io::Result<ApplyReport> {
+        let name = self.unapplied().first().map(|p| p.to_string()).ok_or_else(
+            || {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "all patches are applied",
+                )
+            },
+        )?;
+        let text = self
+            .tree
+            .read(&format!("{PATCHES_DIR}/{name}"))?
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("patch {name} not found"),
+                )
+            })?;
+        let patch =
+            Patch::parse(&text).map_err(|e| invalid(format!("{name}: {e}")))?;
+        let strip = self.series.strip_level(&name).unwrap_or(1);
+        let (changes, files) = patch.changes(&self.tree, strip)?;
+        let report = ApplyReport {
+            patch: Some(name.clone()),
+            files,
+        };
+        if !report.is_applied() {
+            return Ok(report);
+        }
+        for (path, _) in &changes {
+            let backup = format!("{PC_DIR}/{name}/{path}");
+            if self.tree.read(&backup)?.is_none() {
+                let original = self.tree.read(path)?.unwrap_or_default();
+                self.tree.write(&backup, &original)?;
+            }
+        }
+        write_changes(&mut self.tree, changes)?;
+        self.applied.push(name);
+        self.save_state()?;
+        Ok(report)
+    }
+
+    /// Applies all remaining patches, stopping at the first one that
+    /// does not apply. Returns the reports of all patches tried.
+    pub fn push_all(&mut self) -> io::Result<Vec<ApplyReport>> {
+        let mut reports = vec![];
+        while !self.unapplied().is_empty() {
+            let report = self.push()?;
+            let applied = report.is_applied();
+            reports.push(report);
+            if !applied {
+                break;
+            }
+        }
+        Ok(reports)
+    }
+
+    /// Removes the patch applied last by restoring the files saved
+    /// below `.pc`, returning its name.
+    pub fn pop(&mut self) -> io::Result<String> {
+        let name = self.applied.last().cloned().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no patch applied")
+        })?;
+        let backup_dir = format!("{PC_DIR}/{name}");
+        for path in self.tree.list(&backup_dir)? {
+            let backup = format!("{backup_dir}/{path}");
+            let original = self.tree.read(&backup)?.unwrap_or_default();
+            // an empty backup stands for a file created by the patch
+            if original.is_empty() {
+                self.tree.remove(&path)?;
+            } else {
+                self.tree.write(&path, &original)?;
+            }
+            self.tree.remove(&backup)?;
+        }
+        self.applied.pop();
+        self.save_state()?;
+        Ok(name)
+    }
+
+    /// Removes all applied patches, returning their names in the order
+    /// removed.
+    pub fn pop_all(&mut self) -> io::Result<Vec<String>> {
+        let mut popped = vec![];
+        while !self.applied.is_empty() {
+            popped.push(self.pop()?);
+        }
+        Ok(popped)
+    }
+}
@@ -0,0 +1,600 @@
+//! Dependency resolution for binary packages
+//!
+//! Given the packages available from one or more `Packages` indices
+//! and optionally the packages already installed according to the dpkg
+//! status database, the resolver computes the packages to install to
+//! satisfy some requested dependencies, following `Pre-Depends` and
+//! `Depends` with their version constraints. Recommendations are not
+//! considered.
+//!
+//! Dependencies on virtual packages are satisfied by the packages
+//! providing them. As required by Debian policy, a versioned dependency
+//! is only satisfied by a versioned provides, like `foo (= 1.2)`.
+//!
+//! Like apt, the resolver prefers the first alternative of a dependency,
+//! a real package over a provider and the newest version of a package,
+//! but unlike apt it backtracks to other choices if those turn out to be
+//! unsatisfiable.
+//!
+//! The same way, the resolver computes the essential set, that is the
+//! packages marked `Essential: yes` with their dependencies, as needed
+//! by bootstrapping tools, and the build-essential set, which adds the
+//! `build-essential` metapackage and its dependencies.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::package::{parse_dep_list, ControlFile};
+//! use debian::resolver::Resolver;
+//!
+//! let packages = ControlFile::from_reader(
+//!     "Package: foo\nVersion: 1.0\nDepends: libfoo1 (>= 1.0)\n\n\
+//!      Package: libfoo1\nVersion: 1.2\n"
+//!         .as_bytes(),
+//! )
+//! .unwrap();
+//! let mut resolver = Resolver::default();
+//! resolver.add_index(&packages).unwrap();
+//!
+//! let set = resolver.resolve(&parse_dep_list("foo").unwrap()).unwrap();
+//! let names: Vec<&str> = set.packages().map(|c| c.package.as_str()).collect();
+//! assert_eq!(names, ["foo", "libfoo1"]);
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::package::{
+    parse_dep_list, ControlFile, ControlParagraph, Dependency,
+    SingleDependency, VRel,
+};
+use crate::status::DpkgStatus;
+use crate::universe::PackageUniverse;
+use crate::Version;
+
+/// How many other choices the resolver tries after the preferred ones
+/// failed before giving up.
+const MAX_BACKTRACKS: usize = 10_000;
+
+/// Name of the metapackage depending on the packages needed to build
+/// Debian packages, in addition to the essential ones.
+pub const BUILD_ESSENTIAL: &str = "build-essential";
+
+/// A version of a binary package, available or installed.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub package: String,
+    pub version: Version,
+    pub architecture: Option<String>,
+    pub pre_depends: Vec<Dependency>,
+    pub depends: Vec<Dependency>,
+    /// the virtual packages provided, with their versions if given
+    pub provides: Vec<(String, Option<Version>)>,
+    paragraph: ControlParagraph,
+}
+
+/// Resolver for binary package dependencies.
+#[derive(Debug, Default)]
+pub struct Resolver {
+    /// available candidates by package name, newest version first
+    available: HashMap<String, Vec<Candidate>>,
+    /// names of the available packages providing a virtual package
+    virtuals: HashMap<String, Vec<String>>,
+    installed: HashMap<String, Candidate>,
+    /// names of the installed packages providing a virtual package
+    installed_virtuals: HashMap<String, Vec<String>>,
+}
+
+/// The packages to install to satisfy the requested dependencies.
+#[derive(Debug, Clone)]
+pub struct InstallationSet<'a> {
+    packages: Vec<&'a Candidate>,
+    installed: &'a HashMap<String, Candidate>,
+}
+
+/// Why an alternative of a dependency cannot be satisfied.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum UnsatisfiedReason {
+    /// No package of this name is known.
+    UnknownPackage(String),
+    /// The package is known, but no version available satisfies the
+    /// version constraint. The versions available are given.
+    NoMatchingVersion(String, Vec<Version>),
+    /// The package is only provided by other packages, none of which
+    /// provides a version satisfying the version constraint.
+    NoMatchingProvider(String),
+    /// Another version of the package has been selected already.
+    VersionSelected(String, Version),
+}
+
+/// A dependency that cannot be satisfied.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ResolveError {
+    /// the package declaring the dependency, or `None` for requested
+    /// dependencies
+    pub package: Option<String>,
+    pub dependency: Dependency,
+    /// why each alternative cannot be satisfied, in order
+    pub reasons: Vec<UnsatisfiedReason>,
+}
+
+/// The state of a search for an installation set.
+#[derive(Debug, Clone)]
+struct State<'a, 'r> {
+    selected: HashMap<&'a str, &'a Candidate>,
+    /// dependencies still to satisfy, with the package declaring them
+    todo: Vec<(Option<&'a str>, &'r Dependency)>,
+}
+
+/// The package name of a dependency, without any architecture
+/// qualifier like `:any`.
+fn dep_name(dep: &SingleDependency) -> &str {
+    dep.package.split(':').next().unwrap_or(&dep.package)
+}
+
+/// An unversioned dependency on a single package.
+pub(crate) fn dependency_on(package: &str) -> Dependency {
+    Dependency {
+        alternatives: vec![SingleDependency {
+            package: package.to_string(),
+            version: None,
+            arch: None,
+            condition: None,
+            extra_conditions: vec![],
+        }],
+    }
+}
+
+/// Whether a version satisfies the version constraint of a dependency,
+/// if any.
+fn version_matches(dep: &SingleDependency, version: &Version) -> bool {
+    match dep.version {
+        Some((ref rel, ref reference)) => {
+            rel.is_satisfied_by(version, reference)
+        }
+        None => true,
+    }
+}
+
+/// Parses a `Provides` field, which only allows exact versions.
+fn parse_provides(
+    value: &str,
+) -> Result<Vec<(String, Option<Version>)>, &'static str> {
+    let mut provides = vec![];
+    for dep in parse_dep_list(value)? {
+        for alt in dep.alternatives {
+            let version = match alt.version {
+                Some((VRel::Equal, version)) => Some(version),
+                Some(_) => return Err("only = relations allowed in Provides"),
+                None => None,
+            };
+            provides.push((alt.package, version));
+        }
+    }
+    Ok(provides)
+}
+
+/// Records the name of a package as a provider of its virtual packages.
+fn add_virtuals(
+    virtuals: &mut HashMap<String, Vec<String>>,
+    candidate: &Candidate,
+) {
+    for (name, _) in &candidate.provides {
+        let providers = virtuals.entry(name.clone()).or_default();
+        if !providers.contains(&candidate.package) {
+            providers.push(candidate.package.clone());
+        }
+    }
+}
+
+impl Candidate {
+    /// Creates a candidate from a paragraph of a Packages index or the
+    /// status database.
+    pub fn from_paragraph(
+        paragraph: ControlParagraph,
+    ) -> Result<Candidate, &'static str> {
+        let package = match paragraph.get_entry("Package") {
+            Some(p) => p.to_string(),
+            None => return Err("missing Package field"),
+        };
+        let version = match paragraph.get_entry("Version") {
+            Some(v) => {
+                Version::parse(v).map_err(|_| "error parsing version")?
+            }
+            None => return Err("missing Version field"),
+        };
+        let deps = |field| match paragraph.get_entry(field) {
+            Some(value) => parse_dep_list(value),
+            None => Ok(vec![]),
+        };
+        Ok(Candidate {
+            package,
+            version,
+            architecture: paragraph
+                .get_entry("Architecture")
+                .map(|a| a.to_string()),
+            pre_depends: deps("Pre-Depends")?,
+            depends: deps("Depends")?,
+            provides: match paragraph.get_entry("Provides") {
+                Some(value) => parse_provides(value)?,
+                None => vec![],
+            },
+            paragraph,
+        })
+    }
+
+    /// Whether this package satisfies a dependency, either by itself or
+    /// by one of the virtual packages it provides.
+    pub fn satisfies(&self, dep: &SingleDependency) -> bool {
+        let name = dep_name(dep);
+        if self.package == name && version_matches(dep, &self.version) {
+            return true;
+        }
+        self.provides.iter().any(|(provided, version)| {
+            provided == name
+                && match (&dep.version, version) {
+                    (None, _) => true,
+                    (Some(_), Some(v)) => version_matches(dep, v),
+                    (Some(_), None) => false,
+                }
+        })
+    }
+
+    /// Get the value of any other field of this package.
+    pub fn get_field(&self, key: &str) -> Option<&str> {
+        self.paragraph.get_entry(key)
+    }
+
+    /// The complete underlying paragraph.
+    pub fn paragraph(&self) -> &ControlParagraph {
+        &self.paragraph
+    }
+}
+
+impl fmt::Display for UnsatisfiedReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UnsatisfiedReason::UnknownPackage(ref name) => {
+                write!(f, "{name} is not available")
+            }
+            UnsatisfiedReason::NoMatchingVersion(ref name, ref versions) => {
+                let versions: Vec<String> =
+                    versions.iter().map(|v| v.to_string()).collect();
+                write!(f, "{name} is only available in {}", versions.join(", "))
+            }
+            UnsatisfiedReason::NoMatchingProvider(ref name) => {
+                write!(f, "no package provides a matching version of {name}")
+            }
+            UnsatisfiedReason::VersionSelected(ref name, ref version) => {
+                write!(f, "{name} {version} is to be installed")
+            }
+        }
+    }
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.package {
+            Some(ref p) => write!(f, "{p} depends on {}", self.dependency)?,
+            None => write!(f, "{} is requested", self.dependency)?,
+        }
+        let reasons: Vec<String> =
+            self.reasons.iter().map(|r| r.to_string()).collect();
+        write!(f, ", but {}", reasons.join(" and "))
+    }
+}
+
+impl<'a> InstallationSet<'a> {
+    /// The packages to install, sorted by name.
+    pub fn packages(&self) -> impl Iterator<Item = &'a Candidate> + '_ {
+        self.packages.iter().copied()
+    }
+
+    /// The packages to install that upgrade or downgrade an installed
+    /// version.
+    pub fn upgrades(&self) -> impl Iterator<Item = &'a Candidate> + '_ {
+        self.packages()
+            .filter(|c| self.installed.contains_key(&c.package))
+    }
+
+    /// The number of packages to install.
+    pub fn len(&self) -> usize {
+        self.packages.len()
+    }
+
+    /// Whether nothing needs to be installed.
+    pub fn is_empty(&self) -> bool {
+        self.packages.is_empty()
+    }
+}
+
+impl Resolver {
+    /// Adds the packages of a Packages index. Versions already known
+    /// from another index are skipped.
+    pub fn add_index(
+        &mut self,
+        index: &ControlFile,
+    ) -> Result<(), &'static str> {
+        for para in index.get_paragraphs() {
+            self.add_candidate(Candidate::from_paragraph(para.clone())?);
+        }
+        Ok(())
+    }
+
+    /// Adds the binary packages of all origins of a universe. The
+    /// resolver still prefers newer versions regardless of the priority
+    /// of their origin, but where several origins provide the same
+    /// version, the one preferred by the universe is used.
+    pub fn add_universe(&mut self, universe: &PackageUniverse) {
+        for entry in universe.iter() {
+            self.add_candidate(entry.candidate.clone());
+        }
+    }
+
+    /// Adds a single available package.
+    pub fn add_candidate(&mut self, candidate: Candidate) {
+        add_virtuals(&mut self.virtuals, &candidate);
+        let versions =
+            self.available.entry(candidate.package.clone()).or_default();
+        match versions.binary_search_by(|c| candidate.version.cmp(&c.version)) {
+            Ok(_) => (),
+            Err(pos) => versions.insert(pos, candidate),
+        }
+    }
+
+    /// Considers the packages installed according to a status database,
+    /// which satisfy dependencies without being installed again.
+    pub fn set_status(
+        &mut self,
+        status: &DpkgStatus,
+    ) -> Result<(), &'static str> {
+        self.installed.clear();
+        self.installed_virtuals.clear();
+        for entry in status.installed() {
+            let candidate =
+                Candidate::from_paragraph(entry.paragraph().clone())?;
+            self.add_installed(candidate);
+        }
+        Ok(())
+    }
+
+    /// Adds a single installed package, replacing any other version
+    /// recorded as installed.
+    pub fn add_installed(&mut self, candidate: Candidate) {
+        add_virtuals(&mut self.installed_virtuals, &candidate);
+        self.installed.insert(candidate.package.clone(), candidate);
+    }
+
+    /// The available versions of a package, newest first.
+    pub fn candidates(&self, package: &str) -> &[Candidate] {
+        self.available.get(package).map_or(&[], |v| v.as_slice())
+    }
+
+    /// The available packages satisfying a dependency, real or
+    /// virtual, in order of preference: versions of the package itself,
+    /// newest first, followed by the packages providing it.
+    pub fn providers(&self, dep: &SingleDependency) -> Vec<&Candidate> {
+        let name = dep_name(dep);
+        let providers = self
+            .virtuals
+            .get(name)
+            .into_iter()
+            .flatten()
+            .filter(|p| *p != name)
+            .flat_map(|p| self.candidates(p));
+        self.candidates(name)
+            .iter()
+            .chain(providers)
+            .filter(|c| c.satisfies(dep))
+            .collect()
+    }
+
+    /// The installed version of a package, if any.
+    pub fn installed(&self, package: &str) -> Option<&Candidate> {
+        self.installed.get(package)
+    }
+
+    /// Computes the packages to install to satisfy the given
+    /// dependencies, or reports a dependency that cannot be satisfied.
+    /// After backtracking, that is the one that failed the last choice
+    /// tried.
+    pub fn resolve<'a>(
+        &'a self,
+        requests: &[Dependency],
+    ) -> Result<InstallationSet<'a>, ResolveError> {
+        let state = State {
+            selected: HashMap::new(),
+            todo: requests.iter().rev().map(|d| (None, d)).collect(),
+        };
+        let mut budget = MAX_BACKTRACKS;
+        let mut error = None;
+        match self.solve(state, &mut budget, &mut error) {
+            Some(state) => {
+                let mut packages: Vec<&Candidate> =
+                    state.selected.into_values().collect();
+                packages.sort_by(|a, b| a.package.cmp(&b.package));
+                Ok(InstallationSet {
+                    packages,
+                    installed: &self.installed,
+                })
+            }
+            None => Err(error.expect("failed resolution without an error")),
+        }
+    }
+
+    /// The available packages marked `Essential: yes` in their newest
+    /// version, sorted by name.
+    pub fn essential_packages(&self) -> Vec<&Candidate> {
+        let mut result: Vec<&Candidate> = self
+            .available
+            .values()
+            .filter_map(|versions| versions.first())
+            .filter(|c| c.get_field("Essential") == Some("yes"))
+            .collect();
+        result.sort_by(|a, b| a.package.cmp(&b.package));
+        result
+    }
+
+    /// Computes the essential set: the essential packages along with
+    /// everything they depend on, transitively.
+    pub fn essential_set(&self) -> Result<InstallationSet<'_>, ResolveError> {
+        self.resolve_with_essential(&[])
+    }
+
+    /// Computes the build-essential set: the essential set extended by
+    /// the `build-essential` metapackage and its dependencies, which is
+    /// what any source package may assume to be installed for building.
+    pub fn build_essential_set(
+        &self,
+    ) -> Result<InstallationSet<'_>, ResolveError> {
+        self.resolve_with_essential(&[BUILD_ESSENTIAL])
+    }
+
+    /// Resolves dependencies on the essential packages and some others.
+    fn resolve_with_essential(
+        &self,
+        others: &[&str],
+    ) -> Result<InstallationSet<'_>, ResolveError> {
+        let essential = self.essential_packages();
+        let names = essential.iter().map(|c| c.package.as_str());
+        let requests: Vec<Dependency> = names
+            .chain(others.iter().copied())
+            .map(dependency_on)
+            .collect();
+        self.resolve(&requests)
+    }
+
+    /// Whether an alternative is satisfied by a selected or installed
+    /// package, itself or a provider.
+    fn is_satisfied(&self, state: &State, dep: &SingleDependency) -> bool {
+        let name = dep_name(dep);
+        let providers = self
+            .virtuals
+            .get(name)
+            .into_iter()
+            .chain(self.installed_virtuals.get(name))
+            .flatten()
+            .map(String::as_str);
+        std::iter::once(name).chain(providers).any(|p| {
+            match state.selected.get(p) {
+                Some(c) => c.satisfies(dep),
+                None => {
+                    self.installed.get(p).map_or(false, |c| c.satisfies(dep))
+                }
+            }
+        })
+    }
+
+    /// The candidates that could satisfy a dependency, in order of
+    /// preference, or the reasons why there are none.
+    fn options<'a>(
+        &'a self,
+        state: &State<'a, '_>,
+        dep: &Dependency,
+    ) -> Result<Vec<&'a Candidate>, Vec<UnsatisfiedReason>> {
+        let mut options = vec![];
+        let mut reasons = vec![];
+        for alt in &dep.alternatives {
+            let name = dep_name(alt);
+            let mut selected = None;
+            let mut found = false;
+            for candidate in self.providers(alt) {
+                // any other version selected does not satisfy it
+                match state.selected.get(candidate.package.as_str()) {
+                    Some(c) => {
+                        selected.get_or_insert(*c);
+                    }
+                    None => {
+                        options.push(candidate);
+                        found = true;
+                    }
+                }
+            }
+            if found {
+                continue;
+            }
+            let available = self.candidates(name);
+            reasons.push(match selected {
+                Some(c) => UnsatisfiedReason::VersionSelected(
+                    c.package.clone(),
+                    c.version.clone(),
+                ),
+                None if !available.is_empty() => {
+                    UnsatisfiedReason::NoMatchingVersion(
+                        name.to_string(),
+                        available.iter().map(|c| c.version.clone()).collect(),
+                    )
+                }
+                None if self.virtuals.contains_key(name) => {
+                    UnsatisfiedReason::NoMatchingProvider(name.to_string())
+                }
+                None => UnsatisfiedReason::UnknownPackage(name.to_string()),
+            });
+        }
+        match options.is_empty() {
+            true => Err(reasons),
+            false => Ok(options),
+        }
+    }
+
+    fn select<'a: 'r, 'r>(
+        &self,
+        state: &mut State<'a, 'r>,
+        candidate: &'a Candidate,
+    ) {
+        state.selected.insert(&candidate.package, candidate);
+        let deps = candidate.pre_depends.iter().chain(&candidate.depends);
+        for dep in deps.rev() {
+            state.todo.push((Some(&candidate.package), dep));
+        }
+    }
+
+    /// Works through the dependencies still to satisfy, trying the
+    /// options of each in turn.
+    fn solve<'a: 'r, 'r>(
+        &'a self,
+        mut state: State<'a, 'r>,
+        budget: &mut usize,
+        error: &mut Option<ResolveError>,
+    ) -> Option<State<'a, 'r>> {
+        while let Some((package, dep)) = state.todo.pop() {
+            if dep
+                .alternatives
+                .iter()
+                .any(|a| self.is_satisfied(&state, a))
+            {
+                continue;
+            }
+            let options = match self.options(&state, dep) {
+                Ok(options) => options,
+                Err(reasons) => {
+                    *error = Some(ResolveError {
+                        package: package.map(str::to_string),
+                        dependency: dep.clone(),
+                        reasons,
+                    });
+                    return None;
+                }
+            };
+            if let [candidate] = options[..] {
+                self.select(&mut state, candidate);
+                continue;
+            }
+            for (i, candidate) in options.into_iter().enumerate() {
+                if i > 0 {
+                    if *budget == 0 {
+                        return None;
+                    }
+                    *budget -= 1;
+                }
+                let mut next = state.clone();
+                self.select(&mut next, candidate);
+                if let Some(solved) = self.solve(next, budget, error) {
+                    return Some(solved);
+                }
+            }
+            return None;
+        }
+        Some(state)
+    }
+}
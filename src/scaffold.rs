@@ -0,0 +1,411 @@
+//! Generation of initial debian directories
+//!
+//! Like dh_make, creates the `debian` directory of a new package from a
+//! short description of it: `control`, `changelog`, `rules` using the
+//! dh sequencer, a machine-readable `copyright` stub, `source/format`
+//! and a watch file, plus `.install` files for libraries. Whatever is
+//! not known is left as a placeholder to be filled in.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::scaffold::{PackageDescription, PackageKind};
+//!
+//! let mut desc =
+//!     PackageDescription::new("foo", "1.2", "Jane Doe <jane@example.org>");
+//! desc.kind = PackageKind::Indep;
+//! desc.synopsis = "frobnicates bars".to_string();
+//! desc.license = "GPL-2+".to_string();
+//! desc.watch_url = Some("https://example.org/releases/".to_string());
+//!
+//! let files = desc.files();
+//! assert!(files["debian/control"].contains("Architecture: all\n"));
+//! assert_eq!(files["debian/source/format"], "3.0 (quilt)\n");
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use chrono::{DateTime, Datelike, FixedOffset, Local};
+
+use crate::copyright::{
+    Copyright, FilesParagraph, Header, License, LicenseParagraph, FORMAT_1_0,
+};
+use crate::package::{
+    join_multiline, ChangelogEntry, ControlFile, ControlParagraph,
+};
+use crate::soname::Soname;
+use crate::watch::{WatchEntry, WatchFile};
+
+/// The policy version new packages comply with.
+pub const STANDARDS_VERSION: &str = "4.7.0";
+
+/// The debhelper compatibility level new packages use.
+pub const DEBHELPER_COMPAT: u32 = 13;
+
+/// The matching pattern of generated watch files.
+const WATCH_PATTERN: &str = "@PACKAGE@@ANY_VERSION@@ARCHIVE_EXT@";
+
+/// Licenses whose text is shipped in `/usr/share/common-licenses`.
+const COMMON_LICENSES: &[&str] = &[
+    "Apache-2.0",
+    "Artistic",
+    "BSD",
+    "CC0-1.0",
+    "GFDL-1.2",
+    "GFDL-1.3",
+    "GPL-1",
+    "GPL-2",
+    "GPL-3",
+    "LGPL-2",
+    "LGPL-2.1",
+    "LGPL-3",
+    "MPL-1.1",
+    "MPL-2.0",
+];
+
+/// The kind of binary packages to generate.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum PackageKind {
+    /// a single architecture dependent binary package
+    Single,
+    /// a single architecture independent binary package
+    Indep,
+    /// a shared library with the given SONAME version, with its
+    /// development package
+    Library { soversion: String },
+}
+
+/// Description of a new package.
+#[derive(Debug, Clone)]
+pub struct PackageDescription {
+    pub source: String,
+    pub upstream_version: String,
+    /// the maintainer, like `Jane Doe <jane@example.org>`
+    pub maintainer: String,
+    pub kind: PackageKind,
+    pub section: String,
+    pub homepage: Option<String>,
+    /// the first line of the description
+    pub synopsis: String,
+    /// the extended description, with paragraphs separated by empty
+    /// lines
+    pub description: String,
+    /// build dependencies besides debhelper
+    pub build_depends: Vec<String>,
+    /// the short name of the upstream license, like `GPL-2+`
+    pub license: String,
+    /// the upstream copyright statements, one per line
+    pub copyright: Vec<String>,
+    /// the page to look for new upstream releases at
+    pub watch_url: Option<String>,
+    /// whether the package is native, without an orig tarball
+    pub native: bool,
+    /// the ITP bug closed by the initial upload
+    pub itp_bug: Option<u32>,
+    /// the date of the changelog entry, by default the current time
+    pub date: Option<DateTime<FixedOffset>>,
+}
+
+impl PackageDescription {
+    /// Creates a description of a package with a single architecture
+    /// dependent binary package and placeholders for everything else.
+    pub fn new(
+        source: &str,
+        upstream_version: &str,
+        maintainer: &str,
+    ) -> PackageDescription {
+        PackageDescription {
+            source: source.to_string(),
+            upstream_version: upstream_version.to_string(),
+            maintainer: maintainer.to_string(),
+            kind: PackageKind::Single,
+            section: "misc".to_string(),
+            homepage: None,
+            synopsis: "<insert up to 60 chars description>".to_string(),
+            description: "<insert long description, indented with spaces>"
+                .to_string(),
+            build_depends: vec![],
+            license: "<special license>".to_string(),
+            copyright: vec![
+                "<years> <put author's name and email here>".to_string()
+            ],
+            watch_url: None,
+            native: false,
+            itp_bug: None,
+            date: None,
+        }
+    }
+
+    /// The version of the initial changelog entry.
+    pub fn version(&self) -> String {
+        match self.native {
+            true => self.upstream_version.clone(),
+            false => format!("{}-1", self.upstream_version),
+        }
+    }
+
+    /// The names of the binary packages.
+    pub fn binary_packages(&self) -> Vec<String> {
+        match self.kind {
+            PackageKind::Library { ref soversion } => {
+                let soname = Soname {
+                    name: format!("lib{}", self.library_base()),
+                    version: Some(soversion.clone()),
+                };
+                vec![soname.package_name(), soname.dev_package_name()]
+            }
+            _ => vec![self.source.clone()],
+        }
+    }
+
+    /// The library name without a `lib` prefix.
+    fn library_base(&self) -> &str {
+        self.source.strip_prefix("lib").unwrap_or(&self.source)
+    }
+
+    fn maintainer_address(&self) -> (&str, &str) {
+        match self.maintainer.split_once('<') {
+            Some((name, email)) => (name.trim(), email.trim_end_matches('>')),
+            None => (self.maintainer.trim(), ""),
+        }
+    }
+
+    fn description_value(&self) -> String {
+        let lines: Vec<&str> = self.description.lines().collect();
+        join_multiline(&self.synopsis, &lines)
+    }
+
+    /// Generates `debian/control`.
+    pub fn control(&self) -> ControlFile {
+        let mut cf = ControlFile::default();
+        let mut source = ControlParagraph::default();
+        source.add_entry("Source", self.source.clone());
+        source.add_entry("Section", self.section.clone());
+        source.add_entry("Priority", "optional".to_string());
+        source.add_entry("Maintainer", self.maintainer.clone());
+        let mut build_depends =
+            vec![format!("debhelper-compat (= {DEBHELPER_COMPAT})")];
+        build_depends.extend(self.build_depends.iter().cloned());
+        source.add_entry("Build-Depends", build_depends.join(",\n "));
+        source.add_entry("Standards-Version", STANDARDS_VERSION.to_string());
+        if let Some(ref homepage) = self.homepage {
+            source.add_entry("Homepage", homepage.clone());
+        }
+        source.add_entry("Rules-Requires-Root", "no".to_string());
+        cf.add_paragraph(source);
+
+        let binaries = self.binary_packages();
+        let misc = "${misc:Depends}".to_string();
+        let shlibs = "${shlibs:Depends},\n ${misc:Depends}".to_string();
+        let paragraphs: Vec<Vec<(&str, String)>> = match self.kind {
+            PackageKind::Single => vec![vec![
+                ("Architecture", "any".to_string()),
+                ("Depends", shlibs),
+            ]],
+            PackageKind::Indep => vec![vec![
+                ("Architecture", "all".to_string()),
+                ("Depends", misc),
+            ]],
+            PackageKind::Library { .. } => vec![
+                vec![
+                    ("Section", "libs".to_string()),
+                    ("Architecture", "any".to_string()),
+                    ("Multi-Arch", "same".to_string()),
+                    ("Depends", shlibs),
+                ],
+                vec![
+                    ("Section", "libdevel".to_string()),
+                    ("Architecture", "any".to_string()),
+                    ("Multi-Arch", "same".to_string()),
+                    (
+                        "Depends",
+                        format!(
+                            "{} (= ${{binary:Version}}),\n ${{misc:Depends}}",
+                            binaries[0]
+                        ),
+                    ),
+                ],
+            ],
+        };
+        for (name, fields) in binaries.iter().zip(paragraphs) {
+            let mut para = ControlParagraph::default();
+            para.add_entry("Package", name.clone());
+            for (field, value) in fields {
+                para.add_entry(field, value);
+            }
+            para.add_entry("Description", self.description_value());
+            cf.add_paragraph(para);
+        }
+        cf
+    }
+
+    /// Generates the initial entry of `debian/changelog`.
+    pub fn changelog_entry(&self) -> ChangelogEntry {
+        let detail = match self.itp_bug {
+            Some(bug) => format!("  * Initial release. (Closes: #{bug})"),
+            None => "  * Initial release.".to_string(),
+        };
+        let mut entry =
+            ChangelogEntry::new(self.source.clone(), self.version(), detail);
+        let (name, email) = self.maintainer_address();
+        entry.set_maintainer(name, email);
+        if let Some(date) = self.date {
+            entry.set_timestamp(date);
+        }
+        entry
+    }
+
+    /// Generates `debian/rules`, using the dh sequencer.
+    pub fn rules(&self) -> String {
+        "#!/usr/bin/make -f\n\
+         \n\
+         # see debhelper(7) for overriding single steps of the build\n\
+         %:\n\
+         \tdh $@\n"
+            .to_string()
+    }
+
+    /// Generates `debian/copyright`, with the upstream files and the
+    /// packaging under the same license.
+    pub fn copyright(&self) -> Copyright {
+        let year = self.date.map_or_else(|| Local::now().year(), |d| d.year());
+        let license = License {
+            name: self.license.clone(),
+            text: None,
+        };
+        let common = self.license.trim_end_matches('+');
+        let text = match COMMON_LICENSES.contains(&common) {
+            true => format!(
+                "On Debian systems, the complete text of this license can \
+                 be found in\n\"/usr/share/common-licenses/{common}\"."
+            ),
+            false => "<license text>".to_string(),
+        };
+        Copyright {
+            header: Header {
+                format: FORMAT_1_0.to_string(),
+                upstream_name: Some(self.source.clone()),
+                source: Some(
+                    self.homepage
+                        .clone()
+                        .unwrap_or_else(|| "<url://example.com>".to_string()),
+                ),
+                ..Header::default()
+            },
+            files: vec![
+                FilesParagraph {
+                    files: vec!["*".to_string()],
+                    copyright: self.copyright.clone(),
+                    license: license.clone(),
+                    comment: None,
+                },
+                FilesParagraph {
+                    files: vec!["debian/*".to_string()],
+                    copyright: vec![format!("{year} {}", self.maintainer)],
+                    license: license.clone(),
+                    comment: None,
+                },
+            ],
+            licenses: vec![LicenseParagraph {
+                license: License {
+                    text: Some(text),
+                    ..license
+                },
+                comment: None,
+            }],
+        }
+    }
+
+    /// Generates `debian/watch`, if the page to watch is known and the
+    /// package not native.
+    pub fn watch(&self) -> Option<WatchFile> {
+        let url = self.watch_url.as_ref().filter(|_| !self.native)?;
+        Some(WatchFile {
+            version: 4,
+            entries: vec![WatchEntry {
+                url: url.clone(),
+                matching_pattern: WATCH_PATTERN.to_string(),
+                ..WatchEntry::default()
+            }],
+        })
+    }
+
+    /// All generated files, by path relative to the source tree. Non
+    /// native packages without a page to watch get a commented
+    /// `debian/watch.ex` template instead of a watch file.
+    pub fn files(&self) -> BTreeMap<String, String> {
+        let mut files = BTreeMap::new();
+        let mut add = |path: &str, contents: String| {
+            files.insert(format!("debian/{path}"), contents);
+        };
+        add("control", self.control().to_string());
+        add("changelog", self.changelog_entry().serialize());
+        add("rules", self.rules());
+        add("copyright", self.copyright().to_string());
+        let format = match self.native {
+            true => "3.0 (native)\n",
+            false => "3.0 (quilt)\n",
+        };
+        add("source/format", format.to_string());
+        match self.watch() {
+            Some(watch) => add("watch", watch.to_string()),
+            None if !self.native => add(
+                "watch.ex",
+                format!(
+                    "# See uscan(1) for the format of this file, rename it \
+                     to watch\n\
+                     # and adapt the URL to check for new upstream \
+                     releases.\n\
+                     version=4\n\
+                     #https://example.org/releases/ {WATCH_PATTERN}\n"
+                ),
+            ),
+            None => {}
+        }
+        if let PackageKind::Library { .. } = self.kind {
+            let binaries = self.binary_packages();
+            add(
+                &format!("{}.install", binaries[0]),
+                "usr/lib/*/lib*.so.*\n".to_string(),
+            );
+            add(
+                &format!("{}.install", binaries[1]),
+                "usr/include\nusr/lib/*/lib*.so\nusr/lib/*/pkgconfig\n"
+                    .to_string(),
+            );
+        }
+        files
+    }
+
+    /// Writes the generated files into a source tree, failing if it
+    /// already has a `debian` directory.
+    pub fn write(&self, source_dir: &Path) -> io::Result<()> {
+        let debian_dir = source_dir.join("debian");
+        if debian_dir.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", debian_dir.display()),
+            ));
+        }
+        for (path, contents) in self.files() {
+            let path = source_dir.join(path);
+            fs::create_dir_all(path.parent().unwrap_or(source_dir))?;
+            fs::write(&path, contents)?;
+        }
+        make_executable(&debian_dir.join("rules"))
+    }
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
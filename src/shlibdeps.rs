@@ -0,0 +1,490 @@
+//! Computation of shared library dependencies
+//!
+//! Like dpkg-shlibdeps, determines the dependencies of ELF binaries on
+//! the packages of the shared libraries they use, for the
+//! `shlibs:Depends` substitution variable. For every library needed,
+//! the library is looked up in the run path of the binary, in any
+//! directories given, like the build trees of the packages being built,
+//! and in the library directories of the system. Its dependency
+//! information is taken from:
+//!
+//! - the shlibs files given, like `debian/shlibs.local`, which
+//!   override any other information,
+//! - the symbols files given, usually those of the packages being
+//!   built,
+//! - the symbols file of the installed package shipping the library,
+//!   as recorded in the dpkg database, and
+//! - the shlibs file of that package.
+//!
+//! With a symbols file, the dependency requires the highest version
+//! any symbol used by the binaries was introduced in, so binaries only
+//! using old symbols get looser dependencies than with shlibs files.
+//! Dependencies on the same package are merged, keeping the strongest
+//! version requirement.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use debian::arch::DebianArchitecture;
+//! use debian::shlibdeps::ShlibDeps;
+//! use std::path::Path;
+//!
+//! let arch = DebianArchitecture::parse("amd64").unwrap();
+//! let mut shlibdeps = ShlibDeps::new(&arch);
+//! shlibdeps.add_lib_dir(Path::new("debian/libfoo1/usr/lib"));
+//! shlibdeps.ignore_package("libfoo1");
+//! let depends = shlibdeps
+//!     .compute(&[Path::new("debian/foo/usr/bin/foo")])
+//!     .unwrap();
+//! println!("shlibs:Depends={depends}");
+//! ```
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::arch::DebianArchitecture;
+use crate::elf::ElfObject;
+use crate::package::{parse_dep_list, Dependency, VRel};
+use crate::shlibs::ShlibsFile;
+use crate::soname::Soname;
+use crate::status::DpkgStatus;
+use crate::symbols::{SymbolEntry, SymbolsFile, SymbolsLibrary};
+use crate::version::compare;
+
+/// The default location of the dpkg database, relative to the root.
+const ADMIN_DIR: &str = "var/lib/dpkg";
+
+/// Computes the shared library dependencies of binaries.
+#[derive(Debug, Clone)]
+pub struct ShlibDeps {
+    root: PathBuf,
+    admin_dir: Option<PathBuf>,
+    multiarch: String,
+    lib_dirs: Vec<PathBuf>,
+    symbols: Vec<SymbolsFile>,
+    shlibs: Vec<ShlibsFile>,
+    package_type: Option<String>,
+    ignored: Vec<String>,
+}
+
+/// An installed package and the files it ships.
+#[derive(Debug)]
+struct InstalledPackage {
+    /// the name used for its files in the dpkg database, with the
+    /// architecture for multi-arch same packages
+    info_name: String,
+    files: Vec<String>,
+}
+
+fn not_found(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, msg)
+}
+
+/// Reads a file, returning `None` if it does not exist.
+fn read_optional(path: &Path) -> io::Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(s) => Ok(Some(s)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Resolves `.` and `..` in an absolute path lexically.
+fn normalize(path: &Path) -> PathBuf {
+    let mut result = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                result.pop();
+            }
+            Component::Normal(c) => result.push(c),
+            _ => {}
+        }
+    }
+    result
+}
+
+/// Computes the dependency on a library with a symbols file, given the
+/// symbols used.
+fn symbols_dependency(
+    library: &SymbolsLibrary,
+    package: &str,
+    used: &[String],
+) -> Vec<String> {
+    // the highest version of the symbols used, per dependency template
+    let mut versions: BTreeMap<u32, &str> = BTreeMap::new();
+    for symbol in library.symbols().filter(|s| s.missing.is_none()) {
+        if !used.contains(&symbol.name) {
+            continue;
+        }
+        let version = versions.entry(symbol.dep_id).or_insert("0");
+        if compare(&symbol.min_version, version) == Ok(Ordering::Greater) {
+            *version = &symbol.min_version;
+        }
+    }
+    // without any symbol used, depend on the oldest version
+    if versions.is_empty() {
+        let oldest = library
+            .symbols()
+            .map(|s| s.min_version.as_str())
+            .min_by(|a, b| compare(a, b).unwrap_or(Ordering::Equal));
+        versions.insert(0, oldest.unwrap_or("0"));
+    }
+    versions
+        .into_iter()
+        .filter_map(|(dep_id, version)| {
+            let symbol = SymbolEntry {
+                min_version: version.to_string(),
+                dep_id,
+                ..SymbolEntry::default()
+            };
+            library.dependency_for(&symbol, package)
+        })
+        .collect()
+}
+
+/// Adds dependencies to a list, merging those on the same package
+/// with a lower bound into the strongest one.
+fn merge_dependencies(list: &mut Vec<Dependency>, new: Vec<Dependency>) {
+    for dep in new {
+        if list.contains(&dep) {
+            continue;
+        }
+        let single = match dep.alternatives.as_slice() {
+            [single] if single.arch.is_none() => single,
+            _ => {
+                list.push(dep);
+                continue;
+            }
+        };
+        let existing = list.iter_mut().find(|d| match d.alternatives[..] {
+            [ref other] => {
+                other.package == single.package && other.arch.is_none()
+            }
+            _ => false,
+        });
+        let existing = match existing {
+            Some(existing) => &mut existing.alternatives[0],
+            None => {
+                list.push(dep);
+                continue;
+            }
+        };
+        match (&existing.version, &single.version) {
+            (_, None) => {}
+            (None, Some(_)) => existing.version = single.version.clone(),
+            (
+                Some((VRel::GreaterOrEqual, ref a)),
+                Some((VRel::GreaterOrEqual, ref b)),
+            ) => {
+                if b > a {
+                    existing.version = single.version.clone();
+                }
+            }
+            _ => list.push(dep),
+        }
+    }
+}
+
+impl ShlibDeps {
+    /// Creates a computation for binaries of the given architecture,
+    /// looking for libraries and the dpkg database in the root file
+    /// system.
+    pub fn new(arch: &DebianArchitecture) -> ShlibDeps {
+        ShlibDeps {
+            root: PathBuf::from("/"),
+            admin_dir: None,
+            multiarch: arch.multiarch(),
+            lib_dirs: vec![],
+            symbols: vec![],
+            shlibs: vec![],
+            package_type: None,
+            ignored: vec![],
+        }
+    }
+
+    /// Sets the root directory to look for system libraries and the
+    /// dpkg database in.
+    pub fn set_root(&mut self, root: &Path) {
+        self.root = root.to_path_buf();
+    }
+
+    /// Sets the location of the dpkg database, by default
+    /// `var/lib/dpkg` in the root directory.
+    pub fn set_admin_dir(&mut self, admin_dir: &Path) {
+        self.admin_dir = Some(admin_dir.to_path_buf());
+    }
+
+    /// Adds a directory to look for libraries in before the system
+    /// ones, like a library directory in the build tree of a package.
+    pub fn add_lib_dir(&mut self, dir: &Path) {
+        self.lib_dirs.push(dir.to_path_buf());
+    }
+
+    /// Adds a symbols file to use before those of installed packages,
+    /// like the one of a library package being built.
+    pub fn add_symbols(&mut self, symbols: SymbolsFile) {
+        self.symbols.push(symbols);
+    }
+
+    /// Adds a shlibs file overriding all other dependency information,
+    /// like `debian/shlibs.local`.
+    pub fn add_shlibs(&mut self, shlibs: ShlibsFile) {
+        self.shlibs.push(shlibs);
+    }
+
+    /// Sets the type of the package the binaries are for, like `udeb`,
+    /// to select the matching entries of shlibs files.
+    pub fn set_package_type(&mut self, package_type: &str) {
+        self.package_type = Some(package_type.to_string());
+    }
+
+    /// Leaves out dependencies on a package, like the one containing
+    /// the binaries.
+    pub fn ignore_package(&mut self, package: &str) {
+        self.ignored.push(package.to_string());
+    }
+
+    fn admin_dir(&self) -> PathBuf {
+        match self.admin_dir {
+            Some(ref dir) => dir.clone(),
+            None => self.root.join(ADMIN_DIR),
+        }
+    }
+
+    /// The system library directories, as absolute paths within the
+    /// root directory.
+    fn system_lib_dirs(&self) -> Vec<String> {
+        let multiarch = &self.multiarch;
+        vec![
+            format!("/lib/{multiarch}"),
+            format!("/usr/lib/{multiarch}"),
+            "/lib".to_string(),
+            "/usr/lib".to_string(),
+        ]
+    }
+
+    /// Looks up a library needed by a binary, returning whether it was
+    /// found in a system directory and its path, relative to the root
+    /// for system libraries.
+    fn find_library(
+        &self,
+        soname: &str,
+        binary: &Path,
+        object: &ElfObject,
+    ) -> Option<(bool, PathBuf)> {
+        let local = object
+            .runpath_dirs(binary)
+            .into_iter()
+            .map(PathBuf::from)
+            .chain(self.lib_dirs.iter().cloned());
+        for dir in local {
+            let path = dir.join(soname);
+            if path.exists() {
+                return Some((false, path));
+            }
+        }
+        for dir in self.system_lib_dirs() {
+            let path = Path::new(&dir).join(soname);
+            if self.root.join(path.strip_prefix("/").ok()?).exists() {
+                return Some((true, path));
+            }
+        }
+        None
+    }
+
+    /// Reads the file lists of all installed packages.
+    fn installed_packages(&self) -> io::Result<Vec<InstalledPackage>> {
+        let admin_dir = self.admin_dir();
+        let status = match read_optional(&admin_dir.join("status"))? {
+            Some(s) => DpkgStatus::from_reader(s.as_bytes())?,
+            None => DpkgStatus::default(),
+        };
+        let info = admin_dir.join("info");
+        let mut result = vec![];
+        for entry in status.installed() {
+            let mut names = vec![entry.package.clone()];
+            if let Some(ref arch) = entry.architecture {
+                names.insert(0, format!("{}:{arch}", entry.package));
+            }
+            for name in names {
+                let list = info.join(format!("{name}.list"));
+                if let Some(files) = read_optional(&list)? {
+                    result.push(InstalledPackage {
+                        info_name: name,
+                        files: files.lines().map(|l| l.to_string()).collect(),
+                    });
+                    break;
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Finds the installed package shipping a library, given its path
+    /// and that of the file it links to, if any.
+    fn owner<'a>(
+        &self,
+        installed: &'a [InstalledPackage],
+        path: &Path,
+    ) -> Option<&'a InstalledPackage> {
+        let mut candidates = vec![path.to_path_buf()];
+        let relative = path.strip_prefix("/").ok()?;
+        if let Ok(target) = fs::read_link(self.root.join(relative)) {
+            let target = match target.is_absolute() {
+                true => target,
+                false => path.parent()?.join(target),
+            };
+            candidates.push(normalize(&target));
+        }
+        installed.iter().find(|p| {
+            candidates
+                .iter()
+                .any(|c| p.files.iter().any(|f| Path::new(f) == c.as_path()))
+        })
+    }
+
+    /// Looks up the dependency on a library in the files given.
+    fn local_dependency(
+        &self,
+        soname: &str,
+        used: &[String],
+    ) -> Option<Vec<String>> {
+        if let Ok(parsed) = soname.parse::<Soname>() {
+            let package_type = self.package_type.as_deref();
+            let entry = self
+                .shlibs
+                .iter()
+                .find_map(|s| s.get(&parsed, package_type));
+            if let Some(entry) = entry {
+                return Some(vec![entry.dependency.clone()]);
+            }
+        }
+        let library =
+            self.symbols.iter().find_map(|s| s.get_library(soname))?;
+        let package = library.dependency.split_whitespace().next()?;
+        Some(symbols_dependency(library, package, used))
+    }
+
+    /// Looks up the dependency on a library in the files of the
+    /// installed package shipping it.
+    fn installed_dependency(
+        &self,
+        package: &InstalledPackage,
+        soname: &str,
+        used: &[String],
+    ) -> io::Result<Option<Vec<String>>> {
+        let info = self.admin_dir().join("info");
+        let name = package.info_name.split(':').next().unwrap_or("");
+        let symbols = info.join(format!("{}.symbols", package.info_name));
+        if symbols.exists() {
+            let symbols = SymbolsFile::from_file(&symbols)?;
+            if let Some(library) = symbols.get_library(soname) {
+                return Ok(Some(symbols_dependency(library, name, used)));
+            }
+        }
+        let shlibs = info.join(format!("{}.shlibs", package.info_name));
+        let parsed = soname.parse::<Soname>().ok();
+        if let (true, Some(parsed)) = (shlibs.exists(), parsed) {
+            let shlibs = ShlibsFile::from_file(&shlibs)?;
+            let package_type = self.package_type.as_deref();
+            if let Some(entry) = shlibs.get(&parsed, package_type) {
+                return Ok(Some(vec![entry.dependency.clone()]));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Computes the value of `shlibs:Depends` for the given binaries.
+    /// Files that are no ELF objects are skipped, so all files of a
+    /// package may be passed.
+    ///
+    /// Fails with `NotFound` if a library needed cannot be found, or
+    /// there is no dependency information for it.
+    pub fn compute(&self, binaries: &[&Path]) -> io::Result<String> {
+        let mut installed = None;
+        let mut depends = vec![];
+        let mut verbatim: Vec<String> = vec![];
+        for binary in binaries {
+            let data = fs::read(binary)?;
+            if !ElfObject::is_elf(&data) {
+                continue;
+            }
+            let object = ElfObject::parse(&data)?;
+            let used: Vec<String> = object
+                .undefined_symbols()
+                .map(|s| s.qualified_name())
+                .collect();
+            for soname in &object.needed {
+                let (system, path) = self
+                    .find_library(soname, binary, &object)
+                    .ok_or_else(|| {
+                        not_found(format!(
+                            "cannot find library {soname} needed by {}",
+                            binary.display()
+                        ))
+                    })?;
+                let mut deps = self.local_dependency(soname, &used);
+                if deps.is_none() && system {
+                    if installed.is_none() {
+                        installed = Some(self.installed_packages()?);
+                    }
+                    let installed = installed.as_deref().unwrap_or(&[]);
+                    if let Some(package) = self.owner(installed, &path) {
+                        deps =
+                            self.installed_dependency(package, soname, &used)?;
+                    }
+                }
+                let deps = deps.ok_or_else(|| {
+                    not_found(format!(
+                        "no dependency information found for {} \
+                         (used by {})",
+                        path.display(),
+                        binary.display()
+                    ))
+                })?;
+                for dep in deps.iter().flat_map(|d| d.split(',')) {
+                    let dep = dep.trim();
+                    // relations using substitution variables, like
+                    // `(= ${binary:Version})`, are kept as given
+                    if dep.contains("${") {
+                        if !verbatim.iter().any(|v| v == dep) {
+                            verbatim.push(dep.to_string());
+                        }
+                        continue;
+                    }
+                    if dep.is_empty() {
+                        continue;
+                    }
+                    let parsed = parse_dep_list(dep).map_err(|e| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("invalid dependency {dep}: {e}"),
+                        )
+                    })?;
+                    merge_dependencies(&mut depends, parsed);
+                }
+            }
+        }
+        let ignored = |package: &str| self.ignored.iter().any(|i| i == package);
+        let mut result: Vec<String> = depends
+            .iter()
+            .filter(|d| !d.alternatives.iter().any(|a| ignored(&a.package)))
+            .map(|d| d.to_string())
+            .collect();
+        result.extend(verbatim.into_iter().filter(|v| {
+            !v.split('|').any(|a| {
+                ignored(
+                    a.split(|c: char| c.is_whitespace() || c == '(')
+                        .find(|w| !w.is_empty())
+                        .unwrap_or(""),
+                )
+            })
+        }));
+        result.sort();
+        Ok(result.join(", "))
+    }
+}
@@ -0,0 +1,147 @@
+//! Parser and writer for shlibs files
+//!
+//! Shlibs files map shared libraries to the dependency a package using
+//! them needs. They predate symbols files and are still used as a
+//! fallback for libraries without one. Each line names a library and
+//! the version of its SONAME, split like for package names, so
+//! `libfoo.so.1` and `libfoo-1.so` become `libfoo 1`, followed by the
+//! dependency. An optional package type restricts an entry to
+//! packages of that type, like udebs:
+//!
+//! ```text
+//! libfoo 1 libfoo1 (>= 1.2)
+//! udeb: libfoo 1 libfoo1-udeb (>= 1.2)
+//! ```
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::shlibs::ShlibsFile;
+//! use debian::soname::Soname;
+//!
+//! let shlibs: ShlibsFile = "libfoo 1 libfoo1 (>= 1.2)\n".parse().unwrap();
+//! let soname: Soname = "libfoo.so.1".parse().unwrap();
+//! let entry = shlibs.get(&soname, None).unwrap();
+//! assert_eq!(entry.dependency, "libfoo1 (>= 1.2)");
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::soname::Soname;
+
+/// A single line of a shlibs file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ShlibsEntry {
+    /// the package type the entry is restricted to, like `udeb`
+    pub package_type: Option<String>,
+    /// the name of the library, like `libfoo`
+    pub library: String,
+    /// the version of the SONAME, like `1`
+    pub version: String,
+    pub dependency: String,
+}
+
+/// A complete shlibs file.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ShlibsFile {
+    pub entries: Vec<ShlibsEntry>,
+}
+
+impl ShlibsEntry {
+    /// Whether the entry applies to a library, for packages of the
+    /// given type.
+    pub fn matches(&self, soname: &Soname, package_type: Option<&str>) -> bool {
+        self.library == soname.name
+            && Some(self.version.as_str()) == soname.version.as_deref()
+            && self.package_type.as_deref() == package_type
+    }
+}
+
+impl FromStr for ShlibsEntry {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (package_type, rest) = match s.split_once(char::is_whitespace) {
+            Some((first, rest)) if first.ends_with(':') => {
+                (Some(first.trim_end_matches(':')), rest.trim_start())
+            }
+            _ => (None, s),
+        };
+        let mut parts = rest.splitn(3, char::is_whitespace);
+        let library = parts.next().filter(|l| !l.is_empty());
+        let library = library.ok_or("missing library name")?;
+        let version = parts.next().ok_or("missing library version")?;
+        let dependency = parts.next().map(|d| d.trim()).unwrap_or("");
+        Ok(ShlibsEntry {
+            package_type: package_type.map(|t| t.to_string()),
+            library: library.to_string(),
+            version: version.to_string(),
+            dependency: dependency.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for ShlibsEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref package_type) = self.package_type {
+            write!(f, "{package_type}: ")?;
+        }
+        write!(f, "{} {} {}", self.library, self.version, self.dependency)
+    }
+}
+
+impl ShlibsFile {
+    /// Reads and parses a shlibs file from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<ShlibsFile> {
+        fs::read_to_string(in_file)?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Serializes this shlibs file to disk.
+    pub fn to_file(&self, out_file: &Path) -> io::Result<()> {
+        fs::write(out_file, self.to_string())
+    }
+
+    /// Looks up the entry for a library, for packages of the given
+    /// type. Entries without a type are used for typed packages if
+    /// there is none specific to their type.
+    pub fn get(
+        &self,
+        soname: &Soname,
+        package_type: Option<&str>,
+    ) -> Option<&ShlibsEntry> {
+        self.entries
+            .iter()
+            .find(|e| e.matches(soname, package_type))
+            .or_else(|| self.entries.iter().find(|e| e.matches(soname, None)))
+    }
+}
+
+impl FromStr for ShlibsFile {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let entries = s
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(|l| l.parse())
+            .collect::<Result<Vec<ShlibsEntry>, &'static str>>()?;
+        Ok(ShlibsFile { entries })
+    }
+}
+
+impl fmt::Display for ShlibsFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{entry}")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,201 @@
+//! Naming of shared library packages
+//!
+//! Policy requires the runtime package of a shared library to be named
+//! after its SONAME, so that incompatible versions of the library can
+//! be installed side by side: `libfoo.so.1` is shipped in `libfoo1`,
+//! `libfoo-2.so.0` in `libfoo-2-0`, with a hyphen separating the
+//! version if the library name ends in a digit, and `libbar-1.2.so` in
+//! `libbar1.2`. The development files go into a package named after
+//! the library alone, like `libfoo-dev`.
+//!
+//! Libraries whose ABI changed with the transition to a 64-bit `time_t`
+//! were renamed with a `t64` suffix, like `libfoo1t64`, without a change
+//! of their SONAME, so both names are accepted for such libraries.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::soname::Soname;
+//!
+//! let soname: Soname = "libfoo-2.so.0".parse().unwrap();
+//! assert_eq!(soname.name, "libfoo-2");
+//! assert_eq!(soname.version.as_deref(), Some("0"));
+//! assert_eq!(soname.package_name(), "libfoo-2-0");
+//! assert_eq!(soname.t64_package_name(), "libfoo-2-0t64");
+//! assert_eq!(soname.dev_package_name(), "libfoo-2-dev");
+//! assert!(soname.matches_package("libfoo-2-0t64"));
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::package::ControlFile;
+
+/// The suffix added to the names of packages renamed for the 64-bit
+/// `time_t` transition.
+pub const T64_SUFFIX: &str = "t64";
+
+/// The SONAME of a shared library, split into name and version.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Soname {
+    /// the name of the library, like `libfoo`
+    pub name: String,
+    /// the version, like `1` for `libfoo.so.1` or `1.2` for
+    /// `libfoo-1.2.so`, if any
+    pub version: Option<String>,
+}
+
+/// A mismatch between the binary packages of a control file and the
+/// SONAME of a library built from it.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum LibraryNameIssue {
+    /// No package is named after the SONAME; gives the expected name.
+    MissingPackage(String),
+    /// The package looks like the runtime package of the library, but
+    /// is named after another SONAME; gives the package and the
+    /// expected name.
+    WrongName(String, String),
+}
+
+/// Converts a library name into the form allowed in package names.
+fn package_form(name: &str) -> String {
+    name.to_lowercase().replace('_', "-")
+}
+
+/// Strips the suffix of the 64-bit `time_t` transition from a package
+/// name, like `libfoo1t64`, if present.
+pub fn strip_t64(package: &str) -> &str {
+    match package.strip_suffix(T64_SUFFIX) {
+        Some(base) if base.ends_with(|c: char| c.is_ascii_digit()) => base,
+        _ => package,
+    }
+}
+
+impl Soname {
+    /// The name of the runtime package of the library, like `libfoo1`.
+    pub fn package_name(&self) -> String {
+        let name = package_form(&self.name);
+        match self.version {
+            Some(ref version) => {
+                match name.ends_with(|c: char| c.is_ascii_digit()) {
+                    true => format!("{name}-{}", package_form(version)),
+                    false => format!("{name}{}", package_form(version)),
+                }
+            }
+            None => name,
+        }
+    }
+
+    /// The name of the runtime package after the 64-bit `time_t`
+    /// transition, like `libfoo1t64`.
+    pub fn t64_package_name(&self) -> String {
+        self.package_name() + T64_SUFFIX
+    }
+
+    /// The name of the development package, like `libfoo-dev`.
+    pub fn dev_package_name(&self) -> String {
+        format!("{}-dev", package_form(&self.name))
+    }
+
+    /// Whether a package is named as the runtime package of the
+    /// library, with or without the `t64` suffix.
+    pub fn matches_package(&self, package: &str) -> bool {
+        strip_t64(package) == self.package_name()
+    }
+
+    /// Whether a package is named like the runtime package of any
+    /// version of the library.
+    fn is_runtime_package(&self, package: &str) -> bool {
+        let name = package_form(&self.name);
+        let rest = match strip_t64(package).strip_prefix(&name) {
+            Some(rest) => rest,
+            None => return false,
+        };
+        let rest = match name.ends_with(|c: char| c.is_ascii_digit()) {
+            true => match rest.strip_prefix('-') {
+                Some(rest) => rest,
+                None => return false,
+            },
+            false => rest,
+        };
+        rest.starts_with(|c: char| c.is_ascii_digit())
+            && rest.chars().all(|c| {
+                c.is_ascii_digit() || c.is_ascii_lowercase() || c == '.'
+            })
+    }
+
+    /// Checks that the binary packages of a control file include the
+    /// runtime package of the library, and no package named after
+    /// another version of it.
+    pub fn check_control(
+        &self,
+        control: &ControlFile,
+    ) -> Vec<LibraryNameIssue> {
+        let expected = self.package_name();
+        let mut found = false;
+        let mut result = vec![];
+        for para in control.get_paragraphs() {
+            let package = match para.get_entry("Package") {
+                Some(package) => package,
+                None => continue,
+            };
+            if self.matches_package(package) {
+                found = true;
+            } else if self.is_runtime_package(package) {
+                result.push(LibraryNameIssue::WrongName(
+                    package.to_string(),
+                    expected.clone(),
+                ));
+            }
+        }
+        if !found {
+            result.insert(0, LibraryNameIssue::MissingPackage(expected));
+        }
+        result
+    }
+}
+
+impl FromStr for Soname {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() || s.contains(|c: char| c.is_whitespace() || c == '/') {
+            return Err("invalid SONAME");
+        }
+        let (name, version) = match s.rsplit_once(".so.") {
+            Some((name, version)) => (name, Some(version)),
+            None => {
+                let base = s.strip_suffix(".so").ok_or("no shared library")?;
+                match base.rsplit_once('-') {
+                    Some((name, version))
+                        if version
+                            .starts_with(|c: char| c.is_ascii_digit()) =>
+                    {
+                        (name, Some(version))
+                    }
+                    _ => (base, None),
+                }
+            }
+        };
+        if name.is_empty() || version == Some("") {
+            return Err("invalid SONAME");
+        }
+        Ok(Soname {
+            name: name.to_string(),
+            version: version.map(|v| v.to_string()),
+        })
+    }
+}
+
+impl fmt::Display for LibraryNameIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LibraryNameIssue::MissingPackage(ref expected) => {
+                write!(f, "no package named {expected}")
+            }
+            LibraryNameIssue::WrongName(ref package, ref expected) => {
+                write!(f, "{package} should be named {expected}")
+            }
+        }
+    }
+}
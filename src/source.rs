@@ -0,0 +1,265 @@
+//! Source package format and options
+//!
+//! Parses `debian/source/format`, determining how a source package is
+//! built and unpacked, as well as the dpkg-source options given in
+//! `debian/source/options` and `debian/source/local-options`.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::source::SourceFormat;
+//!
+//! let format: SourceFormat = "3.0 (quilt)\n".parse().unwrap();
+//! assert_eq!(format, SourceFormat::Quilt);
+//! assert!(!format.is_native());
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The format of a source package.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SourceFormat {
+    /// `1.0`, the original format with an optional diff
+    V1,
+    /// `3.0 (quilt)`, upstream tarballs plus a debian tarball
+    Quilt,
+    /// `3.0 (native)`, a single tarball
+    Native,
+    /// `3.0 (git)`, a git bundle
+    Git,
+    /// `3.0 (bzr)`, a bzr repository tarball
+    Bzr,
+    /// `3.0 (custom)`, arbitrary files, only usable for building
+    Custom,
+    /// any other format
+    Other(String),
+}
+
+/// A single dpkg-source option.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SourceOption {
+    /// the long option name without leading dashes, like `compression`
+    pub name: String,
+    pub value: Option<String>,
+}
+
+/// A set of dpkg-source options, as found in `debian/source/options`.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct SourceOptions {
+    options: Vec<SourceOption>,
+}
+
+impl SourceFormat {
+    /// Reads the source format of an unpacked source package, given
+    /// its `debian` directory.
+    ///
+    /// As with dpkg-source, a missing `source/format` file means 1.0.
+    pub fn from_dir(debian_dir: &Path) -> io::Result<SourceFormat> {
+        let path = debian_dir.join("source").join("format");
+        match fs::read_to_string(path) {
+            Ok(s) => s
+                .parse()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                Ok(SourceFormat::V1)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Whether the source package consists of a single tarball without
+    /// separate upstream sources.
+    ///
+    /// Format 1.0 packages may or may not be native, depending on the
+    /// presence of an orig tarball, so this returns false for them.
+    pub fn is_native(&self) -> bool {
+        matches!(self, SourceFormat::Native)
+    }
+
+    /// Whether changes to upstream sources are kept as a quilt series
+    /// in `debian/patches`.
+    pub fn uses_quilt(&self) -> bool {
+        matches!(self, SourceFormat::Quilt)
+    }
+
+    /// Whether this is one of the 3.0 formats.
+    pub fn is_v3(&self) -> bool {
+        !matches!(self, SourceFormat::V1 | SourceFormat::Other(_))
+    }
+}
+
+impl FromStr for SourceFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() || s.contains('\n') {
+            return Err("invalid source format");
+        }
+        Ok(match s {
+            "1.0" => SourceFormat::V1,
+            "3.0 (quilt)" => SourceFormat::Quilt,
+            "3.0 (native)" => SourceFormat::Native,
+            "3.0 (git)" => SourceFormat::Git,
+            "3.0 (bzr)" => SourceFormat::Bzr,
+            "3.0 (custom)" => SourceFormat::Custom,
+            other => SourceFormat::Other(other.to_string()),
+        })
+    }
+}
+
+impl fmt::Display for SourceFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SourceFormat::V1 => write!(f, "1.0"),
+            SourceFormat::Quilt => write!(f, "3.0 (quilt)"),
+            SourceFormat::Native => write!(f, "3.0 (native)"),
+            SourceFormat::Git => write!(f, "3.0 (git)"),
+            SourceFormat::Bzr => write!(f, "3.0 (bzr)"),
+            SourceFormat::Custom => write!(f, "3.0 (custom)"),
+            SourceFormat::Other(ref s) => write!(f, "{s}"),
+        }
+    }
+}
+
+impl SourceOptions {
+    /// Reads a single options file from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<SourceOptions> {
+        fs::read_to_string(in_file)?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Reads the effective options of an unpacked source package, given
+    /// its `debian` directory: `source/options` followed by
+    /// `source/local-options`. Missing files are ignored.
+    pub fn from_dir(debian_dir: &Path) -> io::Result<SourceOptions> {
+        let mut result = SourceOptions::default();
+        for name in ["options", "local-options"] {
+            let path = debian_dir.join("source").join(name);
+            if path.is_file() {
+                result
+                    .options
+                    .extend(SourceOptions::from_file(&path)?.options);
+            }
+        }
+        Ok(result)
+    }
+
+    /// All options, in order.
+    pub fn options(&self) -> &Vec<SourceOption> {
+        &self.options
+    }
+
+    /// Append an option.
+    pub fn add_option(&mut self, name: &str, value: Option<&str>) {
+        self.options.push(SourceOption {
+            name: name.to_string(),
+            value: value.map(|v| v.to_string()),
+        });
+    }
+
+    /// Check whether a flag or option is given.
+    pub fn has(&self, name: &str) -> bool {
+        self.options.iter().any(|o| o.name == name)
+    }
+
+    /// Get the value of an option, the last one taking precedence.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|o| o.name == name)
+            .and_then(|o| o.value.as_deref())
+    }
+
+    /// Get all values of an option that may be given multiple times.
+    pub fn get_all(&self, name: &str) -> Vec<&str> {
+        self.options
+            .iter()
+            .filter(|o| o.name == name)
+            .filter_map(|o| o.value.as_deref())
+            .collect()
+    }
+
+    /// The compression to use for generated tarballs, like `xz`.
+    pub fn compression(&self) -> Option<&str> {
+        self.get("compression")
+    }
+
+    /// The compression level, if given.
+    pub fn compression_level(&self) -> Option<u32> {
+        self.get("compression-level").and_then(|l| l.parse().ok())
+    }
+
+    /// Regular expressions of files to ignore in addition to the
+    /// default ones when generating the diff.
+    pub fn extend_diff_ignore(&self) -> Vec<&str> {
+        self.get_all("extend-diff-ignore")
+    }
+
+    /// Patterns of files to exclude from generated tarballs.
+    pub fn tar_ignore(&self) -> Vec<&str> {
+        self.get_all("tar-ignore")
+    }
+
+    /// Whether all upstream changes go into a single patch.
+    pub fn single_debian_patch(&self) -> bool {
+        self.has("single-debian-patch")
+    }
+
+    /// Whether building fails on upstream changes not in a patch.
+    pub fn abort_on_upstream_changes(&self) -> bool {
+        self.has("abort-on-upstream-changes")
+    }
+
+    /// Whether patches get unapplied after building.
+    pub fn unapply_patches(&self) -> bool {
+        self.has("unapply-patches")
+    }
+
+    /// Whether upstream changes get recorded as a patch automatically.
+    pub fn auto_commit(&self) -> bool {
+        self.has("auto-commit")
+    }
+}
+
+impl FromStr for SourceOptions {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut result = SourceOptions::default();
+        for line in s.lines().map(|l| l.trim()) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line = line.trim_start_matches('-');
+            let (name, value) = match line.split_once('=') {
+                Some((n, v)) => (n.trim(), Some(v.trim())),
+                None => (line, None),
+            };
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return Err("invalid option name");
+            }
+            let value = value.map(|v| v.trim_matches('"'));
+            result.add_option(name, value);
+        }
+        Ok(result)
+    }
+}
+
+impl fmt::Display for SourceOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for o in &self.options {
+            match o.value {
+                Some(ref v) => writeln!(f, "{} = \"{}\"", o.name, v)?,
+                None => writeln!(f, "{}", o.name)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+//! The Standards-Version field
+//!
+//! Source packages declare the version of the Debian policy they comply
+//! with in their `Standards-Version` field, like `4.6.2`. Policy
+//! versions have three components, with an optional fourth one for
+//! editorial changes, which never require changes to packages and are
+//! therefore ignored when checking whether a package is up to date.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::standards::{StandardsStatus, StandardsVersion};
+//!
+//! let current: StandardsVersion = "4.7.0".parse().unwrap();
+//! let declared: StandardsVersion = "4.6.2.1".parse().unwrap();
+//! assert!(declared < current);
+//! assert_eq!(declared.status(&current), StandardsStatus::Outdated);
+//! assert_eq!(
+//!     "4.7.0.1".parse::<StandardsVersion>().unwrap().status(&current),
+//!     StandardsStatus::Current
+//! );
+//! ```
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+/// A version of the Debian policy, as given in `Standards-Version`.
+///
+/// Versions compare by their components, with a missing fourth one
+/// taken as zero, so that `4.6.2` equals `4.6.2.0`.
+#[derive(Debug, Clone, Copy)]
+pub struct StandardsVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    /// the editorial component, if given
+    pub editorial: Option<u32>,
+}
+
+/// How a declared policy version relates to the current one.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum StandardsStatus {
+    /// The version is the current one, apart from editorial changes.
+    Current,
+    /// The package claims compliance with an older policy.
+    Outdated,
+    /// The package claims compliance with a policy not released yet.
+    Newer,
+}
+
+impl StandardsVersion {
+    pub fn new(major: u32, minor: u32, patch: u32) -> StandardsVersion {
+        StandardsVersion {
+            major,
+            minor,
+            patch,
+            editorial: None,
+        }
+    }
+
+    /// Parses a version of three or four numeric components.
+    pub fn parse(s: &str) -> Result<StandardsVersion, &'static str> {
+        let parts = s
+            .trim()
+            .split('.')
+            .map(|p| match p.bytes().all(|b| b.is_ascii_digit()) {
+                true => p.parse::<u32>().ok(),
+                false => None,
+            })
+            .collect::<Option<Vec<u32>>>()
+            .ok_or("invalid standards version")?;
+        match parts[..] {
+            [major, minor, patch] => {
+                Ok(StandardsVersion::new(major, minor, patch))
+            }
+            [major, minor, patch, editorial] => Ok(StandardsVersion {
+                editorial: Some(editorial),
+                ..StandardsVersion::new(major, minor, patch)
+            }),
+            _ => Err("standards version needs three or four components"),
+        }
+    }
+
+    /// The version without its editorial component.
+    pub fn significant(&self) -> StandardsVersion {
+        StandardsVersion::new(self.major, self.minor, self.patch)
+    }
+
+    fn key(&self) -> (u32, u32, u32, u32) {
+        (
+            self.major,
+            self.minor,
+            self.patch,
+            self.editorial.unwrap_or(0),
+        )
+    }
+
+    /// Compares the version with the current policy version, ignoring
+    /// editorial components.
+    pub fn status(&self, current: &StandardsVersion) -> StandardsStatus {
+        match self.significant().cmp(&current.significant()) {
+            Ordering::Less => StandardsStatus::Outdated,
+            Ordering::Equal => StandardsStatus::Current,
+            Ordering::Greater => StandardsStatus::Newer,
+        }
+    }
+}
+
+impl PartialEq for StandardsVersion {
+    fn eq(&self, other: &StandardsVersion) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for StandardsVersion {}
+
+impl Hash for StandardsVersion {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+impl Ord for StandardsVersion {
+    fn cmp(&self, other: &StandardsVersion) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+impl PartialOrd for StandardsVersion {
+    fn partial_cmp(&self, other: &StandardsVersion) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for StandardsVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if let Some(editorial) = self.editorial {
+            write!(f, ".{editorial}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for StandardsVersion {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        StandardsVersion::parse(s)
+    }
+}
@@ -0,0 +1,149 @@
+//! Parser and writer for the dpkg statoverride database
+//!
+//! Stat overrides make dpkg install files with an owner, group and
+//! mode other than the ones given in the package. The database,
+//! usually found at `/var/lib/dpkg/statoverride`, holds one override
+//! per line, giving the owner, the group, the mode in octal and the
+//! path. Owners and groups are given by name, or by id with a leading
+//! `#`:
+//!
+//! ```text
+//! root crontab 2755 /usr/bin/crontab
+//! #1000 #1000 0700 /srv/data
+//! ```
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::statoverride::StatOverrides;
+//!
+//! let overrides: StatOverrides =
+//!     "root crontab 2755 /usr/bin/crontab\n".parse().unwrap();
+//! let o = overrides.get("/usr/bin/crontab").unwrap();
+//! assert_eq!(o.group, "crontab");
+//! assert_eq!(o.mode, 0o2755);
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Default location of the dpkg statoverride database.
+pub const DPKG_STATOVERRIDE_PATH: &str = "/var/lib/dpkg/statoverride";
+
+/// A single stat override.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct StatOverride {
+    /// the user name, or the uid with a leading `#`
+    pub owner: String,
+    /// the group name, or the gid with a leading `#`
+    pub group: String,
+    /// the permission bits, including setuid, setgid and sticky bits
+    pub mode: u32,
+    pub path: String,
+}
+
+/// The complete statoverride database.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct StatOverrides {
+    pub overrides: Vec<StatOverride>,
+}
+
+/// Parses a numeric id given with a leading `#`.
+fn numeric_id(s: &str) -> Option<u32> {
+    s.strip_prefix('#')?.parse().ok()
+}
+
+impl StatOverride {
+    /// The uid of the owner, if given numerically.
+    pub fn uid(&self) -> Option<u32> {
+        numeric_id(&self.owner)
+    }
+
+    /// The gid of the group, if given numerically.
+    pub fn gid(&self) -> Option<u32> {
+        numeric_id(&self.group)
+    }
+}
+
+impl FromStr for StatOverride {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim_end().splitn(4, ' ');
+        let owner = parts.next().filter(|o| !o.is_empty());
+        let owner = owner.ok_or("missing owner")?;
+        let group = parts.next().ok_or("missing group")?;
+        let mode = parts.next().ok_or("missing mode")?;
+        let path = parts.next().filter(|p| p.starts_with('/'));
+        let path = path.ok_or("missing absolute path")?;
+        for id in [owner, group] {
+            if id.starts_with('#') && numeric_id(id).is_none() {
+                return Err("invalid numeric id");
+            }
+        }
+        let mode = match u32::from_str_radix(mode, 8) {
+            Ok(mode) if mode <= 0o7777 => mode,
+            _ => return Err("invalid mode"),
+        };
+        Ok(StatOverride {
+            owner: owner.to_string(),
+            group: group.to_string(),
+            mode,
+            path: path.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for StatOverride {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {:o} {}",
+            self.owner, self.group, self.mode, self.path
+        )
+    }
+}
+
+impl StatOverrides {
+    /// Reads and parses a statoverride database from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<StatOverrides> {
+        fs::read_to_string(in_file)?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Serializes this database to disk.
+    pub fn to_file(&self, out_file: &Path) -> io::Result<()> {
+        fs::write(out_file, self.to_string())
+    }
+
+    /// Look up the override of a path.
+    pub fn get(&self, path: &str) -> Option<&StatOverride> {
+        self.overrides.iter().find(|o| o.path == path)
+    }
+}
+
+impl FromStr for StatOverrides {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let overrides = s
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| l.parse())
+            .collect::<Result<Vec<StatOverride>, &'static str>>()?;
+        Ok(StatOverrides { overrides })
+    }
+}
+
+impl fmt::Display for StatOverrides {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for o in &self.overrides {
+            writeln!(f, "{o}")?;
+        }
+        Ok(())
+    }
+}
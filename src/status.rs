@@ -0,0 +1,356 @@
+//! Parser for the dpkg status database
+//!
+//! The dpkg status database, usually found at `/var/lib/dpkg/status`,
+//! lists all packages known to dpkg together with their selection and
+//! installation state. It uses the same paragraph format as control
+//! files, so parsing builds on `ControlFile`.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use debian::status::{DpkgStatus, PackageState};
+//! use std::path::Path;
+//!
+//! let status = DpkgStatus::from_file(Path::new("/var/lib/dpkg/status"))
+//!     .unwrap();
+//! if let Some(entry) = status.get("dpkg") {
+//!     assert_eq!(entry.status.state, PackageState::Installed);
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::io::BufRead;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::package::{ControlFile, ControlParagraph};
+use super::Version;
+
+/// Default location of the dpkg status database.
+pub const DPKG_STATUS_PATH: &str = "/var/lib/dpkg/status";
+
+/// The selection state of a package, i.e. what the user wants.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Want {
+    Unknown,
+    Install,
+    Hold,
+    Deinstall,
+    Purge,
+}
+
+/// Error flags of a package.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Flag {
+    Ok,
+    /// The package is broken and requires reinstallation.
+    ReinstReq,
+}
+
+/// The installation state of a package.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PackageState {
+    NotInstalled,
+    ConfigFiles,
+    HalfInstalled,
+    Unpacked,
+    HalfConfigured,
+    TriggersAwaited,
+    TriggersPending,
+    Installed,
+}
+
+/// The three-part `Status` field of a package in the status database.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Status {
+    pub want: Want,
+    pub flag: Flag,
+    pub state: PackageState,
+}
+
+/// A configuration file as listed in the `Conffiles` field.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Conffile {
+    /// absolute path of the configuration file
+    pub path: String,
+    /// md5 hash of the file as shipped by the package
+    pub hash: String,
+    /// the conffile is no longer shipped by the package
+    pub obsolete: bool,
+}
+
+/// A single package paragraph of the status database.
+#[derive(Debug, Clone)]
+pub struct StatusEntry {
+    /// name of the package
+    pub package: String,
+    /// architecture of the package, if given
+    pub architecture: Option<String>,
+    /// installed version, if any
+    pub version: Option<Version>,
+    /// selection and installation state
+    pub status: Status,
+    /// configuration files of the package
+    pub conffiles: Vec<Conffile>,
+    paragraph: ControlParagraph,
+}
+
+/// The complete dpkg status database, kept in memory.
+#[derive(Debug, Default)]
+pub struct DpkgStatus {
+    entries: Vec<StatusEntry>,
+    by_name: HashMap<String, Vec<usize>>,
+}
+
+impl FromStr for Want {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unknown" => Ok(Want::Unknown),
+            "install" => Ok(Want::Install),
+            "hold" => Ok(Want::Hold),
+            "deinstall" => Ok(Want::Deinstall),
+            "purge" => Ok(Want::Purge),
+            _ => Err("invalid selection state"),
+        }
+    }
+}
+
+impl fmt::Display for Want {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            Want::Unknown => "unknown",
+            Want::Install => "install",
+            Want::Hold => "hold",
+            Want::Deinstall => "deinstall",
+            Want::Purge => "purge",
+        };
+        write!(f, "{s}")
+    }
+}
+
+impl FromStr for Flag {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ok" => Ok(Flag::Ok),
+            "reinstreq" => Ok(Flag::ReinstReq),
+            _ => Err("invalid error flag"),
+        }
+    }
+}
+
+impl fmt::Display for Flag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Flag::Ok => write!(f, "ok"),
+            Flag::ReinstReq => write!(f, "reinstreq"),
+        }
+    }
+}
+
+impl FromStr for PackageState {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "not-installed" => Ok(PackageState::NotInstalled),
+            "config-files" => Ok(PackageState::ConfigFiles),
+            "half-installed" => Ok(PackageState::HalfInstalled),
+            "unpacked" => Ok(PackageState::Unpacked),
+            "half-configured" => Ok(PackageState::HalfConfigured),
+            "triggers-awaited" => Ok(PackageState::TriggersAwaited),
+            "triggers-pending" => Ok(PackageState::TriggersPending),
+            "installed" => Ok(PackageState::Installed),
+            _ => Err("invalid package state"),
+        }
+    }
+}
+
+impl fmt::Display for PackageState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            PackageState::NotInstalled => "not-installed",
+            PackageState::ConfigFiles => "config-files",
+            PackageState::HalfInstalled => "half-installed",
+            PackageState::Unpacked => "unpacked",
+            PackageState::HalfConfigured => "half-configured",
+            PackageState::TriggersAwaited => "triggers-awaited",
+            PackageState::TriggersPending => "triggers-pending",
+            PackageState::Installed => "installed",
+        };
+        write!(f, "{s}")
+    }
+}
+
+impl FromStr for Status {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split_whitespace();
+        let status = match (parts.next(), parts.next(), parts.next()) {
+            (Some(want), Some(flag), Some(state)) => Status {
+                want: want.parse()?,
+                flag: flag.parse()?,
+                state: state.parse()?,
+            },
+            _ => return Err("status requires three words"),
+        };
+        match parts.next() {
+            Some(_) => Err("garbage after status"),
+            None => Ok(status),
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.want, self.flag, self.state)
+    }
+}
+
+impl Status {
+    /// Whether the package is fully installed and configured.
+    pub fn is_installed(&self) -> bool {
+        self.state == PackageState::Installed
+    }
+}
+
+/// Parse the value of a `Conffiles` field, one conffile per line.
+pub fn parse_conffiles(s: &str) -> Result<Vec<Conffile>, &'static str> {
+    let mut result = vec![];
+    for line in s.lines().map(|x| x.trim()).filter(|x| !x.is_empty()) {
+        let mut parts = line.split_whitespace();
+        let (path, hash) = match (parts.next(), parts.next()) {
+            (Some(p), Some(h)) => (p.to_string(), h.to_string()),
+            _ => return Err("conffile without hash"),
+        };
+        let obsolete = match parts.next() {
+            None => false,
+            Some("obsolete") => true,
+            Some(_) => return Err("unknown conffile flag"),
+        };
+        result.push(Conffile {
+            path,
+            hash,
+            obsolete,
+        });
+    }
+    Ok(result)
+}
+
+impl StatusEntry {
+    /// Creates a `StatusEntry` from a paragraph of the status database.
+    pub fn from_paragraph(
+        paragraph: ControlParagraph,
+    ) -> Result<StatusEntry, &'static str> {
+        let package = match paragraph.get_entry("Package") {
+            Some(p) => p.to_string(),
+            None => return Err("missing Package field"),
+        };
+        let status = match paragraph.get_entry("Status") {
+            Some(s) => s.parse()?,
+            None => return Err("missing Status field"),
+        };
+        let version = match paragraph.get_entry("Version") {
+            Some(v) => match Version::parse(v) {
+                Ok(v) => Some(v),
+                Err(_) => return Err("error parsing version"),
+            },
+            None => None,
+        };
+        let conffiles = match paragraph.get_entry("Conffiles") {
+            Some(c) => parse_conffiles(c)?,
+            None => vec![],
+        };
+        Ok(StatusEntry {
+            package,
+            architecture: paragraph
+                .get_entry("Architecture")
+                .map(|a| a.to_string()),
+            version,
+            status,
+            conffiles,
+            paragraph,
+        })
+    }
+
+    /// Get the value of any other field of this package.
+    pub fn get_field(&self, key: &str) -> Option<&str> {
+        self.paragraph.get_entry(key)
+    }
+
+    /// The complete underlying paragraph.
+    pub fn paragraph(&self) -> &ControlParagraph {
+        &self.paragraph
+    }
+}
+
+impl DpkgStatus {
+    /// Reads and parses a dpkg status database from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<DpkgStatus> {
+        DpkgStatus::from_control(ControlFile::from_file(in_file)?)
+    }
+
+    /// Parses a dpkg status database from any buffered reader.
+    pub fn from_reader<R: BufRead>(buf: R) -> io::Result<DpkgStatus> {
+        DpkgStatus::from_control(ControlFile::from_reader(buf)?)
+    }
+
+    fn from_control(cf: ControlFile) -> io::Result<DpkgStatus> {
+        let mut status = DpkgStatus::default();
+        for para in cf.get_paragraphs() {
+            let entry = StatusEntry::from_paragraph(para.clone())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            status.add_entry(entry);
+        }
+        Ok(status)
+    }
+
+    /// Append an entry to the database.
+    pub fn add_entry(&mut self, entry: StatusEntry) {
+        self.by_name
+            .entry(entry.package.clone())
+            .or_default()
+            .push(self.entries.len());
+        self.entries.push(entry);
+    }
+
+    /// All entries, in the order of the database.
+    pub fn entries(&self) -> &Vec<StatusEntry> {
+        &self.entries
+    }
+
+    /// Look up a package by name, returning the first entry found.
+    pub fn get(&self, package: &str) -> Option<&StatusEntry> {
+        self.get_all(package).next()
+    }
+
+    /// Look up a package by name and architecture.
+    pub fn get_arch(&self, package: &str, arch: &str) -> Option<&StatusEntry> {
+        self.get_all(package)
+            .find(|e| e.architecture.as_deref() == Some(arch))
+    }
+
+    /// Iterate over all entries for a package name, i.e. one per
+    /// architecture for co-installable packages.
+    pub fn get_all<'a>(
+        &'a self,
+        package: &str,
+    ) -> impl Iterator<Item = &'a StatusEntry> {
+        self.by_name
+            .get(package)
+            .into_iter()
+            .flatten()
+            .map(move |&i| &self.entries[i])
+    }
+
+    /// Iterate over all installed packages.
+    pub fn installed(&self) -> impl Iterator<Item = &StatusEntry> {
+        self.entries.iter().filter(|e| e.status.is_installed())
+    }
+}
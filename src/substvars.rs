@@ -0,0 +1,411 @@
+//! Substitution variables of control files
+//!
+//! Like dpkg-gencontrol, expands `${name}` references in the fields of
+//! binary package paragraphs, using variables read from
+//! `debian/substvars` files, like `shlibs:Depends`, and built-in ones
+//! like `binary:Version`. Variables may refer to others; a definition
+//! referring to itself is detected instead of expanding forever.
+//!
+//! Substvars files assign one variable per line. Variables assigned
+//! with `?=` are optional and those assigned with `$=` required:
+//!
+//! ```text
+//! shlibs:Depends=libc6 (>= 2.34)
+//! misc:Recommends?=foo-doc
+//! misc:Pre-Depends$=init-system-helpers (>= 1.54~)
+//! ```
+//!
+//! Variables used, but not defined, expand to nothing with a warning.
+//! Variables defined, but not used, cause a warning as well, unless
+//! they are optional, or an error, if they are required.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::package::ControlParagraph;
+//! use debian::substvars::Substvars;
+//!
+//! let mut substvars: Substvars =
+//!     "shlibs:Depends=libc6 (>= 2.34)\n".parse().unwrap();
+//! substvars.set("misc:Depends", "");
+//!
+//! let mut para = ControlParagraph::default();
+//! para.add_entry("Package", "foo".to_string());
+//! para.add_entry(
+//!     "Depends",
+//!     "${shlibs:Depends}, ${misc:Depends}".to_string(),
+//! );
+//! let para = substvars.substitute_paragraph(&para).unwrap();
+//! assert_eq!(para.get_entry("Depends"), Some("libc6 (>= 2.34)"));
+//! assert!(substvars.check_unused().is_ok());
+//! ```
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use log::warn;
+
+use crate::package::ControlParagraph;
+use crate::version::Version;
+
+/// The number of substitutions in a row, without any progress through
+/// the text, after which a definition is taken as recursive.
+pub const MAX_SUBSTITUTIONS: usize = 50;
+
+/// The built-in variables, besides the `F:` ones for fields, which are
+/// not written to substvars files.
+const BUILTIN_VARS: &[&str] = &[
+    "Newline",
+    "Space",
+    "Tab",
+    "Arch",
+    "source:Version",
+    "source:Upstream-Version",
+    "binary:Version",
+    "Installed-Size",
+];
+
+/// Fields holding lists of relations, which are cleaned up after
+/// substitution.
+const RELATION_FIELDS: &[&str] = &[
+    "Pre-Depends",
+    "Depends",
+    "Recommends",
+    "Suggests",
+    "Enhances",
+    "Breaks",
+    "Conflicts",
+    "Replaces",
+    "Provides",
+    "Built-Using",
+    "Static-Built-Using",
+];
+
+/// How a variable was assigned, determining what happens if it is not
+/// used.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SubstvarKind {
+    /// assigned with `=`, causing a warning if unused
+    Normal,
+    /// assigned with `?=`, may remain unused
+    Optional,
+    /// assigned with `$=`, causing an error if unused
+    Required,
+}
+
+/// A single substitution variable.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Substvar {
+    pub name: String,
+    pub value: String,
+    pub kind: SubstvarKind,
+}
+
+/// A set of substitution variables, keeping track of those used.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Substvars {
+    vars: Vec<Substvar>,
+    used: BTreeSet<String>,
+}
+
+/// Whether a string is a valid variable name, like `shlibs:Depends`.
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .map_or(false, |c| c.is_ascii_alphanumeric() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == ':')
+}
+
+/// Drops empty entries from a comma-separated list of relations, as
+/// left by variables expanding to nothing.
+fn clean_relations(value: &str) -> String {
+    value
+        .split(',')
+        .map(|r| r.split_whitespace().collect::<Vec<&str>>().join(" "))
+        .filter(|r| !r.is_empty())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+impl Default for Substvars {
+    /// Creates a set with only the variables for whitespace defined,
+    /// `Newline`, `Space` and `Tab`.
+    fn default() -> Self {
+        let mut result = Substvars {
+            vars: vec![],
+            used: BTreeSet::new(),
+        };
+        result.set_optional("Newline", "\n");
+        result.set_optional("Space", " ");
+        result.set_optional("Tab", "\t");
+        result
+    }
+}
+
+impl FromStr for Substvar {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = s.split_once('=').ok_or("missing assignment")?;
+        let (name, kind) = match name.strip_suffix('?') {
+            Some(name) => (name, SubstvarKind::Optional),
+            None => match name.strip_suffix('$') {
+                Some(name) => (name, SubstvarKind::Required),
+                None => (name, SubstvarKind::Normal),
+            },
+        };
+        if !is_valid_name(name) {
+            return Err("invalid variable name");
+        }
+        Ok(Substvar {
+            name: name.to_string(),
+            value: value.to_string(),
+            kind,
+        })
+    }
+}
+
+impl fmt::Display for Substvar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = match self.kind {
+            SubstvarKind::Normal => "=",
+            SubstvarKind::Optional => "?=",
+            SubstvarKind::Required => "$=",
+        };
+        write!(f, "{}{op}{}", self.name, self.value)
+    }
+}
+
+impl Substvars {
+    /// Reads a substvars file from disk, adding its variables to the
+    /// built-in ones.
+    pub fn from_file(in_file: &Path) -> io::Result<Substvars> {
+        fs::read_to_string(in_file)?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Serializes all variables but the built-in ones to disk.
+    pub fn to_file(&self, out_file: &Path) -> io::Result<()> {
+        fs::write(out_file, self.to_string())
+    }
+
+    /// Adds the variables of a substvars file, replacing those of the
+    /// same name.
+    pub fn add_file(&mut self, in_file: &Path) -> io::Result<()> {
+        let other = Substvars::from_file(in_file)?;
+        for var in other.vars {
+            self.add(var);
+        }
+        Ok(())
+    }
+
+    /// Adds a variable, replacing any previous one of the same name.
+    pub fn add(&mut self, var: Substvar) {
+        match self.vars.iter_mut().find(|v| v.name == var.name) {
+            Some(existing) => *existing = var,
+            None => self.vars.push(var),
+        }
+    }
+
+    /// Sets a variable, which should be used.
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.add(Substvar {
+            name: name.to_string(),
+            value: value.to_string(),
+            kind: SubstvarKind::Normal,
+        });
+    }
+
+    /// Sets a variable, which may remain unused.
+    pub fn set_optional(&mut self, name: &str, value: &str) {
+        self.add(Substvar {
+            name: name.to_string(),
+            value: value.to_string(),
+            kind: SubstvarKind::Optional,
+        });
+    }
+
+    /// Get the value of a variable.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.vars
+            .iter()
+            .find(|v| v.name == name)
+            .map(|v| v.value.as_str())
+    }
+
+    /// Iterate over all variables, including the built-in ones.
+    pub fn vars(&self) -> impl Iterator<Item = &Substvar> {
+        self.vars.iter()
+    }
+
+    /// Sets the built-in version variables, given the versions of the
+    /// source and binary packages: `source:Version`,
+    /// `source:Upstream-Version`, including any epoch, and
+    /// `binary:Version`.
+    pub fn set_version_vars(&mut self, source: &Version, binary: &Version) {
+        let upstream = match source.epoch {
+            Some(epoch) => format!("{epoch}:{}", source.upstream()),
+            None => source.upstream(),
+        };
+        self.set_optional("source:Version", &source.to_string());
+        self.set_optional("source:Upstream-Version", &upstream);
+        self.set_optional("binary:Version", &binary.to_string());
+    }
+
+    /// Sets the built-in `Arch` variable to the architecture the
+    /// package is built for.
+    pub fn set_arch(&mut self, arch: &str) {
+        self.set_optional("Arch", arch);
+    }
+
+    /// Sets the built-in `F:` variables to the fields of a paragraph,
+    /// like `F:Section`.
+    pub fn set_field_vars(&mut self, para: &ControlParagraph) {
+        for entry in para.entries() {
+            let name = format!("F:{}", entry.key());
+            self.set_optional(&name, entry.value().as_str());
+        }
+    }
+
+    /// Expands all variables in a text. Variables not defined expand
+    /// to nothing with a warning.
+    ///
+    /// Fails with `InvalidData` if a variable is defined recursively.
+    pub fn substitute(&mut self, text: &str) -> io::Result<String> {
+        let mut done = String::new();
+        let mut rest = text.to_string();
+        let mut count = 0;
+        let mut remaining = 0;
+        while let Some(start) = rest.find("${") {
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => break,
+            };
+            let name = rest[start + 2..end].to_string();
+            if !is_valid_name(&name) {
+                done.push_str(&rest[..start + 2]);
+                rest.drain(..start + 2);
+                continue;
+            }
+            // only count substitutions without progress through the
+            // text following them
+            if rest.len() - end - 1 < remaining {
+                count = 0;
+            }
+            remaining = rest.len() - end - 1;
+            if count >= MAX_SUBSTITUTIONS {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("too many substitutions, recursive ${{{name}}}?"),
+                ));
+            }
+            count += 1;
+            let value = match self.get(&name) {
+                Some(value) => value.to_string(),
+                None => {
+                    warn!("substitution variable ${{{name}}} not defined");
+                    String::new()
+                }
+            };
+            self.used.insert(name);
+            done.push_str(&rest[..start]);
+            rest = value + &rest[end + 1..];
+        }
+        done.push_str(&rest);
+        Ok(done)
+    }
+
+    /// Expands all variables in the fields of a paragraph, dropping
+    /// fields left empty and empty entries of relation fields.
+    pub fn substitute_paragraph(
+        &mut self,
+        para: &ControlParagraph,
+    ) -> io::Result<ControlParagraph> {
+        let mut result = ControlParagraph::default();
+        for entry in para.entries() {
+            let key = entry.key();
+            let mut value = self.substitute(entry.value().as_str())?;
+            if RELATION_FIELDS.contains(&key) {
+                value = clean_relations(&value);
+            }
+            if !value.trim().is_empty() {
+                result.add_entry(key, value);
+            }
+        }
+        Ok(result)
+    }
+
+    /// The names of the variables defined, but not used so far, except
+    /// optional ones.
+    pub fn unused(&self) -> Vec<&str> {
+        self.vars
+            .iter()
+            .filter(|v| v.kind != SubstvarKind::Optional)
+            .filter(|v| !self.used.contains(&v.name))
+            .map(|v| v.name.as_str())
+            .collect()
+    }
+
+    /// Warns about the variables not used so far, failing with
+    /// `InvalidData` if any of them is required.
+    pub fn check_unused(&self) -> io::Result<()> {
+        let mut missing = vec![];
+        for var in &self.vars {
+            if self.used.contains(&var.name) {
+                continue;
+            }
+            match var.kind {
+                SubstvarKind::Normal => {
+                    warn!("substitution variable ${{{}}} unused", var.name)
+                }
+                SubstvarKind::Optional => {}
+                SubstvarKind::Required => missing.push(var.name.as_str()),
+            }
+        }
+        match missing.is_empty() {
+            true => Ok(()),
+            false => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "required substitution variables unused: {}",
+                    missing.join(", ")
+                ),
+            )),
+        }
+    }
+}
+
+impl FromStr for Substvars {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut result = Substvars::default();
+        for line in s.lines() {
+            let line = line.trim_end();
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+            result.add(line.parse()?);
+        }
+        Ok(result)
+    }
+}
+
+impl fmt::Display for Substvars {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for var in &self.vars {
+            if !BUILTIN_VARS.contains(&var.name.as_str())
+                && !var.name.starts_with("F:")
+            {
+                writeln!(f, "{var}")?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,342 @@
+//! Parser and writer for dpkg-gensymbols symbols files
+//!
+//! Symbols files list the symbols exported by shared libraries along
+//! with the minimal package version providing each of them, allowing
+//! dpkg-shlibdeps to compute tight dependencies.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::symbols::SymbolsFile;
+//!
+//! let symbols: SymbolsFile = "libfoo.so.1 libfoo1 #MINVER#\n \
+//!                             foo_init@Base 1.0\n"
+//!     .parse()
+//!     .unwrap();
+//! let lib = &symbols.libraries[0];
+//! assert_eq!(lib.soname, "libfoo.so.1");
+//! assert_eq!(lib.symbols().next().unwrap().min_version, "1.0");
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A single symbol of a library.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct SymbolEntry {
+    /// the symbol name including its version, like `foo@Base`
+    pub name: String,
+    /// the minimal version of the package providing this symbol
+    pub min_version: String,
+    /// the index of the alternative dependency template to use, with
+    /// zero being the main dependency
+    pub dep_id: u32,
+    /// tags like `optional` or `arch=amd64`, in order
+    pub tags: Vec<(String, Option<String>)>,
+    /// the version the symbol went missing in, for `#MISSING:` lines
+    pub missing: Option<String>,
+}
+
+/// An item within the symbols of a library.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SymbolsItem {
+    Symbol(SymbolEntry),
+    /// a comment or `#include` line, kept verbatim
+    Comment(String),
+}
+
+/// All information about a single shared library.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct SymbolsLibrary {
+    /// the SONAME of the library, like `libfoo.so.1`
+    pub soname: String,
+    /// the main dependency template, like `libfoo1 #MINVER#`
+    pub dependency: String,
+    /// alternative dependency templates, given by lines starting with
+    /// a pipe
+    pub alternatives: Vec<String>,
+    /// meta-information fields, like `Build-Depends-Package`
+    pub fields: Vec<(String, String)>,
+    pub items: Vec<SymbolsItem>,
+}
+
+/// A complete symbols file.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct SymbolsFile {
+    /// comment and `#include` lines before the first library
+    pub header: Vec<String>,
+    pub libraries: Vec<SymbolsLibrary>,
+}
+
+/// Splits a symbol line (without the leading space) into its tags,
+/// name and the remaining words.
+fn split_symbol_line(line: &str) -> Result<(&str, &str, &str), &'static str> {
+    let (tags, rest) = match line.strip_prefix('(') {
+        Some(r) => match r.split_once(')') {
+            Some(x) => x,
+            None => return Err("unterminated tags"),
+        },
+        None => ("", line),
+    };
+    let (name, rest) = match rest.strip_prefix('"') {
+        Some(r) => match r.split_once('"') {
+            Some(x) => x,
+            None => return Err("unterminated symbol name"),
+        },
+        None => rest.split_once(char::is_whitespace).unwrap_or((rest, "")),
+    };
+    Ok((tags, name, rest.trim()))
+}
+
+impl SymbolEntry {
+    /// Parses a symbol line, without the leading space.
+    pub fn parse(line: &str) -> Result<SymbolEntry, &'static str> {
+        let (tags, name, rest) = split_symbol_line(line.trim())?;
+        if name.is_empty() {
+            return Err("missing symbol name");
+        }
+        let tags = tags
+            .split('|')
+            .filter(|t| !t.is_empty())
+            .map(|t| match t.split_once('=') {
+                Some((k, v)) => (k.to_string(), Some(v.to_string())),
+                None => (t.to_string(), None),
+            })
+            .collect();
+        let mut words = rest.split_whitespace();
+        let min_version = match words.next() {
+            Some(v) => v.to_string(),
+            None => return Err("missing minimal version"),
+        };
+        let dep_id = match words.next() {
+            Some(id) => match u32::from_str(id) {
+                Ok(id) => id,
+                Err(_) => return Err("invalid dependency template id"),
+            },
+            None => 0,
+        };
+        Ok(SymbolEntry {
+            name: name.to_string(),
+            min_version,
+            dep_id,
+            tags,
+            missing: None,
+        })
+    }
+
+    /// Check whether the symbol carries a tag.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|(k, _)| k == tag)
+    }
+
+    /// Get the value of a tag like `arch`.
+    pub fn get_tag(&self, tag: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(k, _)| k == tag)
+            .and_then(|(_, v)| v.as_deref())
+    }
+
+    /// Whether the symbol may disappear without this being an error.
+    pub fn is_optional(&self) -> bool {
+        self.has_tag("optional")
+    }
+}
+
+impl fmt::Display for SymbolEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref m) = self.missing {
+            write!(f, "#MISSING: {m}#")?;
+        }
+        write!(f, " ")?;
+        if !self.tags.is_empty() {
+            let tags = self
+                .tags
+                .iter()
+                .map(|(k, v)| match v {
+                    Some(v) => format!("{k}={v}"),
+                    None => k.clone(),
+                })
+                .collect::<Vec<String>>()
+                .join("|");
+            write!(f, "({tags})")?;
+        }
+        // demangled C++ names may contain spaces and thus get quoted
+        match self.name.contains(char::is_whitespace) || self.has_tag("c++") {
+            true => write!(f, "\"{}\"", self.name)?,
+            false => write!(f, "{}", self.name)?,
+        }
+        write!(f, " {}", self.min_version)?;
+        if self.dep_id != 0 {
+            write!(f, " {}", self.dep_id)?;
+        }
+        Ok(())
+    }
+}
+
+impl SymbolsLibrary {
+    /// Iterate over all symbols, including missing ones.
+    pub fn symbols(&self) -> impl Iterator<Item = &SymbolEntry> {
+        self.items.iter().filter_map(|i| match *i {
+            SymbolsItem::Symbol(ref s) => Some(s),
+            SymbolsItem::Comment(_) => None,
+        })
+    }
+
+    /// Look up a symbol by name.
+    pub fn get_symbol(&self, name: &str) -> Option<&SymbolEntry> {
+        self.symbols().find(|s| s.name == name)
+    }
+
+    /// Get the value of a meta-information field.
+    pub fn get_field(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Computes the dependency for a symbol, replacing `#MINVER#` with
+    /// the version constraint and `#PACKAGE#` with the given package
+    /// name.
+    pub fn dependency_for(
+        &self,
+        symbol: &SymbolEntry,
+        package: &str,
+    ) -> Option<String> {
+        let template = match symbol.dep_id {
+            0 => &self.dependency,
+            n => self.alternatives.get(n as usize - 1)?,
+        };
+        let minver = match symbol.min_version.as_str() {
+            "0" => "".to_string(),
+            v => format!(" (>= {v})"),
+        };
+        let dep = template
+            .replace("#PACKAGE#", package)
+            .replace(" #MINVER#", &minver)
+            .replace("#MINVER#", &minver);
+        Some(dep)
+    }
+}
+
+impl fmt::Display for SymbolsLibrary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} {}", self.soname, self.dependency)?;
+        for alt in &self.alternatives {
+            writeln!(f, "| {alt}")?;
+        }
+        for (k, v) in &self.fields {
+            writeln!(f, "* {k}: {v}")?;
+        }
+        for item in &self.items {
+            match *item {
+                SymbolsItem::Symbol(ref s) => writeln!(f, "{s}")?,
+                SymbolsItem::Comment(ref c) => writeln!(f, "{c}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SymbolsFile {
+    /// Reads and parses a symbols file from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<SymbolsFile> {
+        fs::read_to_string(in_file)?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Serializes this symbols file to disk.
+    pub fn to_file(&self, out_file: &Path) -> io::Result<()> {
+        fs::write(out_file, self.to_string())
+    }
+
+    /// Look up a library by its SONAME.
+    pub fn get_library(&self, soname: &str) -> Option<&SymbolsLibrary> {
+        self.libraries.iter().find(|l| l.soname == soname)
+    }
+}
+
+impl FromStr for SymbolsFile {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut result = SymbolsFile::default();
+        for line in s.lines() {
+            let trimmed = line.trim_end();
+            if trimmed.is_empty() {
+                continue;
+            }
+            let current = result.libraries.last_mut();
+            if let Some(rest) = trimmed.strip_prefix("#MISSING:") {
+                let (version, sym) = match rest.split_once('#') {
+                    Some(x) => x,
+                    None => return Err("invalid #MISSING line"),
+                };
+                let mut entry = SymbolEntry::parse(sym)?;
+                entry.missing = Some(version.trim().to_string());
+                match current {
+                    Some(lib) => lib.items.push(SymbolsItem::Symbol(entry)),
+                    None => return Err("symbol before library line"),
+                }
+            } else if trimmed.starts_with('#') {
+                match current {
+                    Some(lib) => lib
+                        .items
+                        .push(SymbolsItem::Comment(trimmed.to_string())),
+                    None => result.header.push(trimmed.to_string()),
+                }
+            } else if let Some(sym) = trimmed.strip_prefix(' ') {
+                let entry = SymbolEntry::parse(sym)?;
+                match current {
+                    Some(lib) => lib.items.push(SymbolsItem::Symbol(entry)),
+                    None => return Err("symbol before library line"),
+                }
+            } else if let Some(alt) = trimmed.strip_prefix('|') {
+                match current {
+                    Some(lib) => lib.alternatives.push(alt.trim().to_string()),
+                    None => return Err("alternative before library line"),
+                }
+            } else if let Some(field) = trimmed.strip_prefix('*') {
+                let (k, v) = match field.split_once(':') {
+                    Some(x) => x,
+                    None => return Err("invalid meta-information field"),
+                };
+                match current {
+                    Some(lib) => lib
+                        .fields
+                        .push((k.trim().to_string(), v.trim().to_string())),
+                    None => return Err("field before library line"),
+                }
+            } else {
+                let (soname, dep) =
+                    match trimmed.split_once(char::is_whitespace) {
+                        Some(x) => x,
+                        None => return Err("library line without dependency"),
+                    };
+                result.libraries.push(SymbolsLibrary {
+                    soname: soname.to_string(),
+                    dependency: dep.trim().to_string(),
+                    ..Default::default()
+                });
+            }
+        }
+        Ok(result)
+    }
+}
+
+impl fmt::Display for SymbolsFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in &self.header {
+            writeln!(f, "{line}")?;
+        }
+        for lib in &self.libraries {
+            write!(f, "{lib}")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+//! Expansion of tasks and metapackages into package selections
+//!
+//! Packages indices group packages into tasks, like `ssh-server` or
+//! `gnome-desktop`, by listing the tasks a package belongs to in its
+//! `Task` field. Installers offer these tasks for selection, in
+//! addition to metapackages, which pull in other packages by their
+//! dependencies.
+//!
+//! A selection names packages, metapackages included, and tasks marked
+//! by a trailing `^`, the same way as `apt install ssh-server^`. It is
+//! expanded into the concrete packages to install for an architecture:
+//! those of the tasks, along with all their dependencies.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::package::ControlFile;
+//! use debian::tasks::Tasks;
+//!
+//! let packages = ControlFile::from_reader(
+//!     "Package: openssh-server\nVersion: 1:9.2p1-2\nArchitecture: amd64\n\
+//!      Depends: openssh-client\nTask: ssh-server\n\n\
+//!      Package: openssh-client\nVersion: 1:9.2p1-2\nArchitecture: amd64\n"
+//!         .as_bytes(),
+//! )
+//! .unwrap();
+//! let tasks = Tasks::from_packages(&packages, "amd64").unwrap();
+//! assert_eq!(tasks.packages_of("ssh-server"), ["openssh-server"]);
+//!
+//! let set = tasks.expand(&["ssh-server^"]).unwrap();
+//! let names: Vec<&str> = set.packages().map(|c| c.package.as_str()).collect();
+//! assert_eq!(names, ["openssh-client", "openssh-server"]);
+//! ```
+
+use std::collections::BTreeMap;
+
+use crate::package::ControlFile;
+use crate::resolver::{
+    dependency_on, Candidate, InstallationSet, ResolveError, Resolver,
+    UnsatisfiedReason,
+};
+
+/// The tasks and packages of an index for a single architecture.
+#[derive(Debug, Default)]
+pub struct Tasks {
+    /// the architecture packages are selected for
+    pub architecture: String,
+    /// names of the packages of each task, sorted
+    tasks: BTreeMap<String, Vec<String>>,
+    resolver: Resolver,
+}
+
+/// Parses the comma separated list of a `Task` field.
+pub fn parse_task_field(value: &str) -> Vec<&str> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+/// Whether a package of an index is installable on an architecture.
+fn is_for_arch(candidate: &Candidate, arch: &str) -> bool {
+    match candidate.architecture.as_deref() {
+        Some(a) => a == arch || a == "all",
+        None => true,
+    }
+}
+
+impl Tasks {
+    /// Collects the tasks and packages of a Packages index, skipping
+    /// the ones built for other architectures than `arch`.
+    pub fn from_packages(
+        packages: &ControlFile,
+        arch: &str,
+    ) -> Result<Tasks, &'static str> {
+        let mut result = Tasks {
+            architecture: arch.to_string(),
+            ..Default::default()
+        };
+        for para in packages.get_paragraphs() {
+            let candidate = Candidate::from_paragraph(para.clone())?;
+            if !is_for_arch(&candidate, arch) {
+                continue;
+            }
+            let tasks = para.get_entry("Task").unwrap_or("");
+            for task in parse_task_field(tasks) {
+                let members = result.tasks.entry(task.to_string()).or_default();
+                if let Err(pos) = members.binary_search(&candidate.package) {
+                    members.insert(pos, candidate.package.clone());
+                }
+            }
+            result.resolver.add_candidate(candidate);
+        }
+        Ok(result)
+    }
+
+    /// The names of all tasks, sorted.
+    pub fn tasks(&self) -> impl Iterator<Item = &str> {
+        self.tasks.keys().map(String::as_str)
+    }
+
+    /// The packages belonging to a task, sorted by name.
+    pub fn packages_of(&self, task: &str) -> &[String] {
+        self.tasks.get(task).map_or(&[], Vec::as_slice)
+    }
+
+    /// The resolver holding the packages for the architecture.
+    pub fn resolver(&self) -> &Resolver {
+        &self.resolver
+    }
+
+    /// Expands a selection of packages and tasks, the latter given with
+    /// a trailing `^`, into the packages to install, including all
+    /// dependencies. Unknown tasks are reported like unknown packages.
+    pub fn expand<'a>(
+        &'a self,
+        selection: &[&str],
+    ) -> Result<InstallationSet<'a>, ResolveError> {
+        let mut requests = vec![];
+        for name in selection {
+            match name.strip_suffix('^') {
+                Some(task) => match self.tasks.get(task) {
+                    Some(members) => requests
+                        .extend(members.iter().map(|p| dependency_on(p))),
+                    None => {
+                        return Err(ResolveError {
+                            package: None,
+                            dependency: dependency_on(name),
+                            reasons: vec![UnsatisfiedReason::UnknownPackage(
+                                name.to_string(),
+                            )],
+                        })
+                    }
+                },
+                None => requests.push(dependency_on(name)),
+            }
+        }
+        self.resolver.resolve(&requests)
+    }
+}
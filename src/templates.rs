@@ -0,0 +1,402 @@
+//! Parser and writer for debconf templates files
+//!
+//! A templates file holds one paragraph per debconf question, giving
+//! its type, default value, choices and description, optionally along
+//! with translations in fields like `Description-de.UTF-8`.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::templates::{TemplateType, Templates};
+//!
+//! let templates: Templates = "\
+//! Template: foo/enable
+//! Type: boolean
+//! Default: true
+//! Description: Enable foo?
+//!  Foo can be started at boot.
+//! "
+//! .parse()
+//! .unwrap();
+//! let t = templates.get("foo/enable").unwrap();
+//! assert_eq!(t.template_type, TemplateType::Boolean);
+//! assert_eq!(t.description, "Enable foo?");
+//! ```
+
+use std::fmt;
+use std::io;
+use std::io::BufRead;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::package::{
+    join_multiline, split_multiline, ControlFile, ControlParagraph,
+};
+
+/// The type of a debconf question.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum TemplateType {
+    String,
+    Password,
+    Boolean,
+    Select,
+    Multiselect,
+    Note,
+    Text,
+    Title,
+    Error,
+    /// any other type
+    Other(String),
+}
+
+/// The translation of a template into a single language.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct TemplateTranslation {
+    /// the language and possibly encoding, like `de.UTF-8`
+    pub language: String,
+    pub description: Option<String>,
+    pub extended_description: Option<String>,
+    pub choices: Vec<String>,
+    pub default: Option<String>,
+}
+
+/// A single debconf template.
+///
+/// Translatable fields of po-debconf sources, marked by leading
+/// underscores like `_Description`, are read as the plain field.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Template {
+    /// the name of the template, like `foo/enable`
+    pub name: String,
+    pub template_type: TemplateType,
+    pub default: Option<String>,
+    pub choices: Vec<String>,
+    /// the short description, i.e. the first line
+    pub description: String,
+    /// the extended description, with empty lines separating
+    /// paragraphs
+    pub extended_description: Option<String>,
+    pub translations: Vec<TemplateTranslation>,
+    /// any other fields
+    pub extra: Vec<(String, String)>,
+}
+
+/// The contents of a templates file.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Templates {
+    templates: Vec<Template>,
+}
+
+/// Splits a list of choices at commas, honoring `\,` as an escaped
+/// comma within a choice.
+pub fn split_choices(value: &str) -> Vec<String> {
+    let mut result = vec![];
+    let mut cur = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&',') => {
+                cur.push(',');
+                chars.next();
+            }
+            ',' => result.push(std::mem::take(&mut cur)),
+            c => cur.push(c),
+        }
+    }
+    result.push(cur);
+    result
+        .into_iter()
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty())
+        .collect()
+}
+
+/// Joins choices into a list, the inverse of `split_choices`.
+pub fn join_choices<S: AsRef<str>>(choices: &[S]) -> String {
+    choices
+        .iter()
+        .map(|c| c.as_ref().replace(',', "\\,"))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn parse_description(value: &str) -> (String, Option<String>) {
+    let (first, rest) = split_multiline(value);
+    let extended = match rest.is_empty() {
+        true => None,
+        false => Some(rest.join("\n")),
+    };
+    (first.to_string(), extended)
+}
+
+fn description_value(short: &str, extended: Option<&str>) -> String {
+    match extended {
+        Some(e) => join_multiline(short, &e.lines().collect::<Vec<&str>>()),
+        None => short.to_string(),
+    }
+}
+
+impl FromStr for TemplateType {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim() {
+            "" => return Err("empty template type"),
+            "string" => TemplateType::String,
+            "password" => TemplateType::Password,
+            "boolean" => TemplateType::Boolean,
+            "select" => TemplateType::Select,
+            "multiselect" => TemplateType::Multiselect,
+            "note" => TemplateType::Note,
+            "text" => TemplateType::Text,
+            "title" => TemplateType::Title,
+            "error" => TemplateType::Error,
+            other => TemplateType::Other(other.to_string()),
+        })
+    }
+}
+
+impl fmt::Display for TemplateType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            TemplateType::String => "string",
+            TemplateType::Password => "password",
+            TemplateType::Boolean => "boolean",
+            TemplateType::Select => "select",
+            TemplateType::Multiselect => "multiselect",
+            TemplateType::Note => "note",
+            TemplateType::Text => "text",
+            TemplateType::Title => "title",
+            TemplateType::Error => "error",
+            TemplateType::Other(ref s) => s,
+        };
+        write!(f, "{s}")
+    }
+}
+
+impl Template {
+    /// Creates a `Template` from a paragraph of a templates file.
+    pub fn from_paragraph(
+        para: &ControlParagraph,
+    ) -> Result<Template, &'static str> {
+        let mut name = None;
+        let mut template_type = None;
+        let mut description = None;
+        let mut template = Template {
+            name: String::new(),
+            template_type: TemplateType::String,
+            default: None,
+            choices: vec![],
+            description: String::new(),
+            extended_description: None,
+            translations: vec![],
+            extra: vec![],
+        };
+        for e in para.entries() {
+            let v = e.value().as_str();
+            let key = e.key().trim_start_matches('_');
+            let (field, language) = match key.split_once('-') {
+                Some((f, l))
+                    if ["Description", "Choices", "Default"].contains(&f) =>
+                {
+                    (f, Some(l))
+                }
+                _ => (key, None),
+            };
+            if let Some(language) = language {
+                let idx = match template
+                    .translations
+                    .iter()
+                    .position(|t| t.language == language)
+                {
+                    Some(idx) => idx,
+                    None => {
+                        template.translations.push(TemplateTranslation {
+                            language: language.to_string(),
+                            ..Default::default()
+                        });
+                        template.translations.len() - 1
+                    }
+                };
+                let t = &mut template.translations[idx];
+                match field {
+                    "Description" => {
+                        let (short, extended) = parse_description(v);
+                        t.description = Some(short);
+                        t.extended_description = extended;
+                    }
+                    "Choices" => t.choices = split_choices(v),
+                    _ => t.default = Some(v.to_string()),
+                }
+                continue;
+            }
+            match field {
+                "Template" => name = Some(v.to_string()),
+                "Type" => template_type = Some(v.parse()?),
+                "Default" => template.default = Some(v.to_string()),
+                "Choices" => template.choices = split_choices(v),
+                "Description" => description = Some(parse_description(v)),
+                _ => template.extra.push((key.to_string(), v.to_string())),
+            }
+        }
+        template.name = match name {
+            Some(n) if !n.is_empty() => n,
+            _ => return Err("missing Template field"),
+        };
+        template.template_type = match template_type {
+            Some(t) => t,
+            None => return Err("missing Type field"),
+        };
+        match description {
+            Some((short, extended)) => {
+                template.description = short;
+                template.extended_description = extended;
+            }
+            None => return Err("missing Description field"),
+        }
+        Ok(template)
+    }
+
+    /// Converts the template back into a paragraph.
+    pub fn to_paragraph(&self) -> ControlParagraph {
+        let mut para = ControlParagraph::default();
+        para.add_entry("Template", self.name.clone());
+        para.add_entry("Type", self.template_type.to_string());
+        if !self.choices.is_empty() {
+            para.add_entry("Choices", join_choices(&self.choices));
+        }
+        for t in &self.translations {
+            if !t.choices.is_empty() {
+                let key = format!("Choices-{}", t.language);
+                para.add_entry(&key, join_choices(&t.choices));
+            }
+        }
+        if let Some(ref v) = self.default {
+            para.add_entry("Default", v.clone());
+        }
+        for t in &self.translations {
+            if let Some(ref v) = t.default {
+                para.add_entry(&format!("Default-{}", t.language), v.clone());
+            }
+        }
+        for (k, v) in &self.extra {
+            para.add_entry(k, v.clone());
+        }
+        let value = description_value(
+            &self.description,
+            self.extended_description.as_deref(),
+        );
+        para.add_entry("Description", value);
+        for t in &self.translations {
+            if let Some(ref d) = t.description {
+                let value =
+                    description_value(d, t.extended_description.as_deref());
+                para.add_entry(&format!("Description-{}", t.language), value);
+            }
+        }
+        para
+    }
+
+    /// Get the translation into a language, like `de.UTF-8`. A language
+    /// without encoding, like `de`, matches any encoding.
+    pub fn translation(&self, language: &str) -> Option<&TemplateTranslation> {
+        self.translations.iter().find(|t| {
+            t.language == language
+                || t.language.split_once('.').map(|(l, _)| l) == Some(language)
+        })
+    }
+
+    /// Get the short and extended description in a language, falling
+    /// back to the untranslated one.
+    pub fn description_for(&self, language: &str) -> (&str, Option<&str>) {
+        match self.translation(language) {
+            Some(TemplateTranslation {
+                description: Some(ref d),
+                ref extended_description,
+                ..
+            }) => (d, extended_description.as_deref()),
+            _ => (&self.description, self.extended_description.as_deref()),
+        }
+    }
+
+    /// Get the choices in a language, falling back to the untranslated
+    /// ones.
+    pub fn choices_for(&self, language: &str) -> &Vec<String> {
+        match self.translation(language) {
+            Some(t) if !t.choices.is_empty() => &t.choices,
+            _ => &self.choices,
+        }
+    }
+}
+
+impl Templates {
+    /// Reads and parses a templates file from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<Templates> {
+        Templates::from_control(&ControlFile::from_file(in_file)?)
+    }
+
+    /// Parses a templates file from any buffered reader.
+    pub fn from_reader<R: BufRead>(buf: R) -> io::Result<Templates> {
+        Templates::from_control(&ControlFile::from_reader(buf)?)
+    }
+
+    fn from_control(cf: &ControlFile) -> io::Result<Templates> {
+        let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+        let templates = cf
+            .get_paragraphs()
+            .iter()
+            .map(Template::from_paragraph)
+            .collect::<Result<Vec<Template>, &'static str>>()
+            .map_err(invalid)?;
+        Ok(Templates { templates })
+    }
+
+    /// All templates, in order.
+    pub fn templates(&self) -> &Vec<Template> {
+        &self.templates
+    }
+
+    /// Append a template.
+    pub fn add_template(&mut self, template: Template) {
+        self.templates.push(template);
+    }
+
+    /// Look up a template by name.
+    pub fn get(&self, name: &str) -> Option<&Template> {
+        self.templates.iter().find(|t| t.name == name)
+    }
+
+    /// All languages any template is translated into.
+    pub fn languages(&self) -> Vec<&str> {
+        let mut result: Vec<&str> = vec![];
+        for t in self.templates.iter().flat_map(|t| &t.translations) {
+            if !result.contains(&t.language.as_str()) {
+                result.push(&t.language);
+            }
+        }
+        result
+    }
+
+    /// Serializes the templates to disk.
+    pub fn to_file(&self, out_file: &Path) -> io::Result<()> {
+        std::fs::write(out_file, self.to_string())
+    }
+}
+
+impl FromStr for Templates {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Templates::from_reader(s.as_bytes())
+    }
+}
+
+impl fmt::Display for Templates {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut cf = ControlFile::default();
+        for t in &self.templates {
+            cf.add_paragraph(t.to_paragraph());
+        }
+        write!(f, "{cf}")
+    }
+}
@@ -0,0 +1,187 @@
+//! Parser and writer for dpkg triggers control files
+//!
+//! The `triggers` control file of a binary package, generated from
+//! `debian/<pkg>.triggers`, declares the triggers a package is
+//! interested in as well as the ones it activates.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::triggers::{Trigger, Triggers};
+//!
+//! let triggers: Triggers = "interest-noawait /usr/share/icons\n\
+//!                           activate ldconfig\n"
+//!     .parse()
+//!     .unwrap();
+//! assert_eq!(
+//!     triggers.triggers()[0],
+//!     Trigger::InterestNoAwait("/usr/share/icons".to_string())
+//! );
+//! assert!(triggers.triggers()[0].is_file_trigger());
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A single trigger directive.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Trigger {
+    /// `interest` or `interest-await`
+    Interest(String),
+    /// `interest-noawait`
+    InterestNoAwait(String),
+    /// `activate` or `activate-await`
+    Activate(String),
+    /// `activate-noawait`
+    ActivateNoAwait(String),
+}
+
+/// The contents of a triggers control file.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Triggers {
+    triggers: Vec<Trigger>,
+}
+
+/// Checks whether a trigger name is valid, i.e. non-empty and made up
+/// of printable ASCII characters other than space only.
+pub fn validate_trigger_name(name: &str) -> Result<(), &'static str> {
+    if name.is_empty() {
+        return Err("empty trigger name");
+    }
+    if !name.chars().all(|c| c.is_ascii_graphic()) {
+        return Err("invalid character in trigger name");
+    }
+    Ok(())
+}
+
+impl Trigger {
+    /// The name of the trigger, either a path or an explicit name.
+    pub fn name(&self) -> &str {
+        match *self {
+            Trigger::Interest(ref n)
+            | Trigger::InterestNoAwait(ref n)
+            | Trigger::Activate(ref n)
+            | Trigger::ActivateNoAwait(ref n) => n,
+        }
+    }
+
+    /// Whether this is a file trigger, given by an absolute path.
+    pub fn is_file_trigger(&self) -> bool {
+        self.name().starts_with('/')
+    }
+
+    /// Whether this declares an interest rather than an activation.
+    pub fn is_interest(&self) -> bool {
+        matches!(self, Trigger::Interest(_) | Trigger::InterestNoAwait(_))
+    }
+
+    /// Whether the activating package has to wait for the trigger to be
+    /// processed before it counts as installed.
+    pub fn awaits(&self) -> bool {
+        matches!(self, Trigger::Interest(_) | Trigger::Activate(_))
+    }
+
+    fn directive(&self) -> &'static str {
+        match *self {
+            Trigger::Interest(_) => "interest",
+            Trigger::InterestNoAwait(_) => "interest-noawait",
+            Trigger::Activate(_) => "activate",
+            Trigger::ActivateNoAwait(_) => "activate-noawait",
+        }
+    }
+}
+
+impl FromStr for Trigger {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let (directive, name) = match (words.next(), words.next()) {
+            (Some(d), Some(n)) => (d, n.to_string()),
+            _ => return Err("trigger directive without name"),
+        };
+        if words.next().is_some() {
+            return Err("trailing garbage after trigger name");
+        }
+        validate_trigger_name(&name)?;
+        match directive {
+            "interest" | "interest-await" => Ok(Trigger::Interest(name)),
+            "interest-noawait" => Ok(Trigger::InterestNoAwait(name)),
+            "activate" | "activate-await" => Ok(Trigger::Activate(name)),
+            "activate-noawait" => Ok(Trigger::ActivateNoAwait(name)),
+            _ => Err("unknown trigger directive"),
+        }
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.directive(), self.name())
+    }
+}
+
+impl Triggers {
+    /// Reads and parses a triggers file from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<Triggers> {
+        fs::read_to_string(in_file)?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// All trigger directives, in order.
+    pub fn triggers(&self) -> &Vec<Trigger> {
+        &self.triggers
+    }
+
+    /// Append a trigger directive.
+    pub fn add_trigger(&mut self, trigger: Trigger) {
+        self.triggers.push(trigger);
+    }
+
+    /// Names of the triggers the package is interested in.
+    pub fn interests(&self) -> impl Iterator<Item = &str> {
+        self.triggers
+            .iter()
+            .filter(|t| t.is_interest())
+            .map(|t| t.name())
+    }
+
+    /// Names of the triggers the package activates.
+    pub fn activations(&self) -> impl Iterator<Item = &str> {
+        self.triggers
+            .iter()
+            .filter(|t| !t.is_interest())
+            .map(|t| t.name())
+    }
+}
+
+impl FromStr for Triggers {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut result = Triggers::default();
+        for line in s.lines() {
+            let line = match line.split_once('#') {
+                Some((l, _)) => l,
+                None => line,
+            }
+            .trim();
+            if !line.is_empty() {
+                result.add_trigger(line.parse()?);
+            }
+        }
+        Ok(result)
+    }
+}
+
+impl fmt::Display for Triggers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for t in &self.triggers {
+            writeln!(f, "{t}")?;
+        }
+        Ok(())
+    }
+}
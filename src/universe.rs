@@ -0,0 +1,212 @@
+//! Layered view of several package indices
+//!
+//! A system usually pulls packages from more than one place, like a
+//! stable release, its backports and a local repository. A
+//! `PackageUniverse` combines the Packages and Sources indices of all
+//! of these origins, each with a priority, into a single view for
+//! looking up the versions available of a package.
+//!
+//! The priority of an origin defaults to the one apt assigns to its
+//! release if no pin applies, e.g. 100 for backports, which are marked
+//! `NotAutomatic: yes` and `ButAutomaticUpgrades: yes`. Lookups yield
+//! the versions of origins with a higher priority first, newer versions
+//! first within the same priority.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::apt::preferences::ReleaseInfo;
+//! use debian::package::ControlFile;
+//! use debian::universe::{Origin, PackageUniverse};
+//!
+//! let mut universe = PackageUniverse::default();
+//! let stable =
+//!     universe.add_origin(Origin::new("bookworm", ReleaseInfo::default()));
+//! let backports = universe.add_origin(Origin::new(
+//!     "bookworm-backports",
+//!     ReleaseInfo {
+//!         not_automatic: true,
+//!         but_automatic_upgrades: true,
+//!         ..Default::default()
+//!     },
+//! ));
+//! for (origin, version) in [(stable, "1.0"), (backports, "2.0")] {
+//!     let text = format!("Package: foo\nVersion: {version}\n");
+//!     let index = ControlFile::from_reader(text.as_bytes()).unwrap();
+//!     universe.add_packages(origin, &index).unwrap();
+//! }
+//!
+//! let preferred = universe.preferred("foo").unwrap();
+//! assert_eq!(preferred.candidate.version.to_string(), "1.0");
+//! assert_eq!(universe.origin(preferred.origin).name, "bookworm");
+//! assert_eq!(universe.candidates("foo").len(), 2);
+//! ```
+
+use std::collections::BTreeMap;
+
+use crate::apt::preferences::{default_priority, ReleaseInfo};
+use crate::package::ControlFile;
+use crate::resolver::Candidate;
+
+/// A source of packages, like a suite of an archive or a local
+/// repository.
+#[derive(Debug, Clone)]
+pub struct Origin {
+    /// a name to refer to the origin, like `bookworm-backports`
+    pub name: String,
+    /// the properties of the release, as used for pinning
+    pub release: ReleaseInfo,
+    pub priority: i32,
+}
+
+/// A version of a package available from an origin.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// the origin, as returned by `PackageUniverse::add_origin`
+    pub origin: usize,
+    pub candidate: Candidate,
+}
+
+/// The packages available from several origins.
+#[derive(Debug, Clone, Default)]
+pub struct PackageUniverse {
+    origins: Vec<Origin>,
+    /// binary packages by name, in order of preference
+    binaries: BTreeMap<String, Vec<Entry>>,
+    /// source packages by name, in order of preference
+    sources: BTreeMap<String, Vec<Entry>>,
+}
+
+impl Origin {
+    /// Creates an origin with the priority apt assigns to its release
+    /// if no pin applies.
+    pub fn new(name: &str, release: ReleaseInfo) -> Origin {
+        Origin {
+            name: name.to_string(),
+            priority: default_priority(&release),
+            release,
+        }
+    }
+
+    /// Overrides the priority of the origin.
+    pub fn with_priority(mut self, priority: i32) -> Origin {
+        self.priority = priority;
+        self
+    }
+}
+
+/// Adds the packages of an index to a map of entries, keeping the
+/// order of preference: higher priority first, then newer versions,
+/// then origins added earlier.
+fn add_entries(
+    origins: &[Origin],
+    map: &mut BTreeMap<String, Vec<Entry>>,
+    origin: usize,
+    index: &ControlFile,
+) -> Result<(), &'static str> {
+    assert!(origin < origins.len(), "unknown origin {origin}");
+    let priority = |e: &Entry| origins[e.origin].priority;
+    let prefer = |a: &Entry, b: &Entry| {
+        priority(a)
+            .cmp(&priority(b))
+            .then_with(|| a.candidate.version.cmp(&b.candidate.version))
+            .then_with(|| b.origin.cmp(&a.origin))
+    };
+    for para in index.get_paragraphs() {
+        let candidate = Candidate::from_paragraph(para.clone())?;
+        let entries = map.entry(candidate.package.clone()).or_default();
+        let entry = Entry { origin, candidate };
+        let pos = entries
+            .iter()
+            .position(|e| prefer(e, &entry).is_lt())
+            .unwrap_or(entries.len());
+        entries.insert(pos, entry);
+    }
+    Ok(())
+}
+
+impl PackageUniverse {
+    /// Adds an origin, returning the number to refer to it.
+    pub fn add_origin(&mut self, origin: Origin) -> usize {
+        self.origins.push(origin);
+        self.origins.len() - 1
+    }
+
+    /// All origins, in the order added.
+    pub fn origins(&self) -> &[Origin] {
+        &self.origins
+    }
+
+    /// The origin with the given number.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no such origin has been added.
+    pub fn origin(&self, origin: usize) -> &Origin {
+        &self.origins[origin]
+    }
+
+    /// Adds the binary packages of a Packages index of an origin.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no such origin has been added.
+    pub fn add_packages(
+        &mut self,
+        origin: usize,
+        index: &ControlFile,
+    ) -> Result<(), &'static str> {
+        add_entries(&self.origins, &mut self.binaries, origin, index)
+    }
+
+    /// Adds the source packages of a Sources index of an origin.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no such origin has been added.
+    pub fn add_sources(
+        &mut self,
+        origin: usize,
+        index: &ControlFile,
+    ) -> Result<(), &'static str> {
+        add_entries(&self.origins, &mut self.sources, origin, index)
+    }
+
+    /// The versions of a binary package available from all origins, in
+    /// order of preference.
+    pub fn candidates(&self, package: &str) -> &[Entry] {
+        self.binaries.get(package).map_or(&[], |v| v.as_slice())
+    }
+
+    /// The versions of a source package available from all origins, in
+    /// order of preference.
+    pub fn source_candidates(&self, package: &str) -> &[Entry] {
+        self.sources.get(package).map_or(&[], |v| v.as_slice())
+    }
+
+    /// The preferred version of a binary package, if any.
+    pub fn preferred(&self, package: &str) -> Option<&Entry> {
+        self.candidates(package).first()
+    }
+
+    /// The names of all binary packages, sorted.
+    pub fn packages(&self) -> impl Iterator<Item = &str> {
+        self.binaries.keys().map(|k| k.as_str())
+    }
+
+    /// The names of all source packages, sorted.
+    pub fn source_packages(&self) -> impl Iterator<Item = &str> {
+        self.sources.keys().map(|k| k.as_str())
+    }
+
+    /// All versions of all binary packages, sorted by name, then in
+    /// order of preference.
+    pub fn iter(&self) -> impl Iterator<Item = &Entry> {
+        self.binaries.values().flatten()
+    }
+
+    /// The versions of all binary packages available from an origin.
+    pub fn entries_of(&self, origin: usize) -> impl Iterator<Item = &Entry> {
+        self.iter().filter(move |e| e.origin == origin)
+    }
+}
@@ -0,0 +1,306 @@
+//! Client for upload queues
+//!
+//! Like dput, uploads the files of a signed `.changes` file to the
+//! upload queue of an archive, either over HTTP(S), with one `PUT`
+//! request per file, or over SFTP. Before anything is transferred, all
+//! files listed are checked to be present next to the `.changes` file
+//! with matching sizes and checksums. The `.changes` file is uploaded
+//! last, so the queue never sees it before the files it refers to.
+//!
+//! SFTP uploads use the `sftp` program of OpenSSH in batch mode, so
+//! authentication needs to work without interaction, as with an SSH
+//! agent.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use debian::upload::{UploadQueue, Uploader};
+//! use std::path::Path;
+//!
+//! let queue: UploadQueue =
+//!     "sftp://upload.example.org/incoming".parse().unwrap();
+//! let uploader = Uploader::new(queue);
+//! uploader.upload(Path::new("../foo_1.2-1_source.changes"))?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+use crate::changes::Changes;
+
+/// How files are transferred to an upload queue.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum UploadMethod {
+    /// `PUT` requests over plain HTTP
+    Http,
+    /// `PUT` requests over HTTPS
+    Https,
+    /// SFTP, using the `sftp` program
+    Sftp,
+}
+
+/// The location of an upload queue, given as URL like
+/// `https://upload.example.org/queue` or
+/// `sftp://user@upload.example.org/incoming`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct UploadQueue {
+    pub method: UploadMethod,
+    /// the login name, for SFTP
+    pub login: Option<String>,
+    /// the host name, possibly followed by a port
+    pub host: String,
+    /// the directory of the queue, like the `incoming` setting of dput
+    pub incoming: String,
+}
+
+/// Uploads packages to an upload queue.
+#[derive(Debug)]
+pub struct Uploader {
+    queue: UploadQueue,
+    agent: ureq::Agent,
+    require_signature: bool,
+}
+
+fn invalid_input(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+/// Whether a file name is safe to upload under: a single path component
+/// without control characters, which could end a command of an sftp
+/// batch file.
+fn valid_file_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != ".."
+        && !name.contains('/')
+        && !name.chars().any(char::is_control)
+}
+
+/// Percent-encodes a file name for use as a segment of a URL path.
+fn url_encode(name: &str) -> String {
+    let mut result = String::new();
+    for b in name.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => result.push(b as char),
+            b'-' | b'.' | b'_' | b'~' | b'+' => result.push(b as char),
+            _ => result += &format!("%{b:02X}"),
+        }
+    }
+    result
+}
+
+/// Quotes a path for an sftp batch file, which must not contain
+/// control characters.
+fn sftp_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Splits the host of a queue into the host name and the port, if any,
+/// keeping the brackets of an IPv6 address like `[::1]:2222`.
+fn split_port(host: &str) -> (&str, Option<&str>) {
+    let end = match host.starts_with('[') {
+        true => host.find(']').map_or(0, |i| i + 1),
+        false => 0,
+    };
+    match host[end..].rfind(':') {
+        Some(i) => (&host[..end + i], Some(&host[end + i + 1..])),
+        None => (host, None),
+    }
+}
+
+impl UploadMethod {
+    /// The URL scheme of the method.
+    pub fn scheme(&self) -> &'static str {
+        match *self {
+            UploadMethod::Http => "http",
+            UploadMethod::Https => "https",
+            UploadMethod::Sftp => "sftp",
+        }
+    }
+}
+
+impl FromStr for UploadQueue {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (scheme, rest) = s.split_once("://").ok_or("missing scheme")?;
+        let method = match scheme {
+            "http" => UploadMethod::Http,
+            "https" => UploadMethod::Https,
+            "sftp" => UploadMethod::Sftp,
+            _ => return Err("unsupported upload method"),
+        };
+        let (authority, incoming) = match rest.find('/') {
+            Some(pos) => rest.split_at(pos),
+            None => (rest, ""),
+        };
+        let (login, host) = match authority.rsplit_once('@') {
+            Some((login, host)) => (Some(login.to_string()), host),
+            None => (None, authority),
+        };
+        if host.is_empty() {
+            return Err("missing host");
+        }
+        if s.chars().any(char::is_control) {
+            return Err("control characters in URL");
+        }
+        if login.is_some() && method != UploadMethod::Sftp {
+            return Err("login names are only supported for SFTP");
+        }
+        Ok(UploadQueue {
+            method,
+            login,
+            host: host.to_string(),
+            incoming: incoming.trim_end_matches('/').to_string(),
+        })
+    }
+}
+
+impl fmt::Display for UploadQueue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}://", self.method.scheme())?;
+        if let Some(ref login) = self.login {
+            write!(f, "{login}@")?;
+        }
+        write!(f, "{}{}", self.host, self.incoming)
+    }
+}
+
+/// Lists the files to upload for a `.changes` file, in order: the
+/// files it lists, followed by the `.changes` file itself.
+///
+/// Fails if the `.changes` file is not signed while `require_signature`
+/// is set, or if any file listed is missing or does not match its size
+/// or checksums.
+pub fn upload_files(
+    changes_file: &Path,
+    require_signature: bool,
+) -> io::Result<Vec<PathBuf>> {
+    let changes = Changes::from_file(changes_file)?;
+    if require_signature && !changes.is_signed() {
+        return Err(invalid_input(format!(
+            "{} is not signed",
+            changes_file.display()
+        )));
+    }
+    let dir = changes_file.parent().unwrap_or_else(|| Path::new(""));
+    changes.verify_files(dir)?;
+    let mut result: Vec<PathBuf> =
+        changes.files().iter().map(|f| dir.join(&f.name)).collect();
+    result.push(changes_file.to_path_buf());
+    Ok(result)
+}
+
+impl Uploader {
+    /// Creates an uploader for a queue, requiring signed `.changes`
+    /// files.
+    pub fn new(queue: UploadQueue) -> Uploader {
+        Uploader {
+            queue,
+            agent: ureq::Agent::new(),
+            require_signature: true,
+        }
+    }
+
+    /// Sets whether unsigned `.changes` files are refused, which is the
+    /// default. Some queues accept unsigned uploads, like those of
+    /// local repositories.
+    pub fn set_require_signature(&mut self, require_signature: bool) {
+        self.require_signature = require_signature;
+    }
+
+    /// Uploads a `.changes` file and the files it lists, returning the
+    /// names of the files transferred, in order.
+    pub fn upload(&self, changes_file: &Path) -> io::Result<Vec<String>> {
+        let files = upload_files(changes_file, self.require_signature)?;
+        let names = files
+            .iter()
+            .map(|p| {
+                p.file_name()
+                    .and_then(|n| n.to_str())
+                    .filter(|n| valid_file_name(n))
+                    .map(|n| n.to_string())
+                    .ok_or_else(|| {
+                        invalid_input(format!("invalid file {}", p.display()))
+                    })
+            })
+            .collect::<io::Result<Vec<String>>>()?;
+        match self.queue.method {
+            UploadMethod::Http | UploadMethod::Https => {
+                for (path, name) in files.iter().zip(&names) {
+                    self.put(path, name)?;
+                }
+            }
+            UploadMethod::Sftp => self.sftp(&files, &names)?,
+        }
+        Ok(names)
+    }
+
+    fn put(&self, path: &Path, name: &str) -> io::Result<()> {
+        let url = format!("{}/{}", self.queue, url_encode(name));
+        let len = path.metadata()?.len();
+        self.agent
+            .put(&url)
+            .set("Content-Length", &len.to_string())
+            .send(File::open(path)?)
+            .map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("uploading {name} failed: {e}"),
+                )
+            })?;
+        Ok(())
+    }
+
+    fn sftp(&self, files: &[PathBuf], names: &[String]) -> io::Result<()> {
+        let mut batch = String::new();
+        if !self.queue.incoming.is_empty() {
+            batch += &format!("cd {}\n", sftp_quote(&self.queue.incoming));
+        }
+        for (path, name) in files.iter().zip(names) {
+            let path = path
+                .to_str()
+                .filter(|p| !p.chars().any(char::is_control))
+                .ok_or_else(|| {
+                    invalid_input(format!("invalid file {}", path.display()))
+                })?;
+            batch +=
+                &format!("put {} {}\n", sftp_quote(path), sftp_quote(name));
+        }
+        let mut cmd = Command::new("sftp");
+        cmd.args(["-b", "-"]);
+        let (host, port) = split_port(&self.queue.host);
+        if let Some(port) = port {
+            cmd.args(["-P", port]);
+        }
+        // keep a host like `-oProxyCommand=...` from passing for an option
+        cmd.arg("--");
+        cmd.arg(match self.queue.login {
+            Some(ref login) => format!("{login}@{host}"),
+            None => host.to_string(),
+        });
+        let mut child = cmd
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        // if sftp exits early, writing fails, but its error tells why
+        let written = child.stdin.take().unwrap().write_all(batch.as_bytes());
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "sftp upload failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            ));
+        }
+        written
+    }
+}
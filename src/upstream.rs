@@ -0,0 +1,340 @@
+//! Parser for DEP-12 upstream metadata
+//!
+//! The file `debian/upstream/metadata` carries information about the
+//! upstream project, like its bug tracker or repository, in YAML
+//! format. This module requires the `yaml` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::upstream::UpstreamMetadata;
+//!
+//! let metadata: UpstreamMetadata = "\
+//! Bug-Database: https://github.com/foo/foo/issues
+//! Repository: https://github.com/foo/foo.git
+//! "
+//! .parse()
+//! .unwrap();
+//! assert_eq!(
+//!     metadata.repository.as_deref(),
+//!     Some("https://github.com/foo/foo.git")
+//! );
+//! assert!(metadata.validate().is_empty());
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde_yaml::{Mapping, Value};
+
+/// Fields defined by DEP-12 holding a single string.
+const STRING_FIELDS: &[&str] = &[
+    "Archive",
+    "ASCL-Id",
+    "Bug-Database",
+    "Bug-Submit",
+    "Changelog",
+    "CPE",
+    "Cite-As",
+    "Documentation",
+    "Donation",
+    "FAQ",
+    "Funding",
+    "Gallery",
+    "Other-References",
+    "Repository",
+    "Repository-Browse",
+    "Screenshots",
+    "Security-Contact",
+    "Webservice",
+];
+
+/// Fields that used to be allowed, but should now be given in
+/// debian/control or debian/copyright instead.
+const DEPRECATED_FIELDS: &[&str] = &["Contact", "Homepage", "Name"];
+
+/// An entry of the `Registry` field, referring to the project in an
+/// external registry.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RegistryEntry {
+    /// name of the registry, like `PyPI` or `bio.tools`
+    pub name: String,
+    /// identifier of the project within the registry
+    pub entry: String,
+}
+
+/// A problem found when validating upstream metadata.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum MetadataIssue {
+    /// A field not defined by DEP-12.
+    UnknownField(String),
+    /// A field that should no longer be used.
+    DeprecatedField(String),
+    /// A field with a value of unexpected type or format.
+    InvalidValue(String),
+}
+
+/// The contents of a `debian/upstream/metadata` file.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct UpstreamMetadata {
+    pub archive: Option<String>,
+    pub bug_database: Option<String>,
+    pub bug_submit: Option<String>,
+    pub changelog: Option<String>,
+    pub documentation: Option<String>,
+    pub donation: Option<String>,
+    pub faq: Option<String>,
+    pub funding: Option<String>,
+    pub repository: Option<String>,
+    pub repository_browse: Option<String>,
+    pub screenshots: Option<String>,
+    pub security_contact: Option<String>,
+    pub registry: Vec<RegistryEntry>,
+    /// bibliographic references, each a list of key-value pairs
+    pub reference: Vec<Vec<(String, String)>>,
+    /// all other fields with scalar values, in order of appearance
+    pub other: Vec<(String, String)>,
+    issues: Vec<MetadataIssue>,
+}
+
+fn scalar(value: &Value) -> Option<String> {
+    match *value {
+        Value::String(ref s) => Some(s.clone()),
+        Value::Number(ref n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn pairs(map: &Mapping) -> Vec<(String, String)> {
+    map.iter()
+        .filter_map(|(k, v)| Some((scalar(k)?, scalar(v)?)))
+        .collect()
+}
+
+/// Parses a field that may hold a single mapping or a list of them.
+fn mappings(value: &Value) -> Option<Vec<&Mapping>> {
+    match *value {
+        Value::Mapping(ref m) => Some(vec![m]),
+        Value::Sequence(ref s) => s.iter().map(|v| v.as_mapping()).collect(),
+        _ => None,
+    }
+}
+
+impl UpstreamMetadata {
+    /// Reads and parses upstream metadata from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<UpstreamMetadata> {
+        fs::read_to_string(in_file)?.parse()
+    }
+
+    fn set(&mut self, key: &str, value: String) {
+        let field = match key {
+            "Archive" => &mut self.archive,
+            "Bug-Database" => &mut self.bug_database,
+            "Bug-Submit" => &mut self.bug_submit,
+            "Changelog" => &mut self.changelog,
+            "Documentation" => &mut self.documentation,
+            "Donation" => &mut self.donation,
+            "FAQ" => &mut self.faq,
+            "Funding" => &mut self.funding,
+            "Repository" => &mut self.repository,
+            "Repository-Browse" => &mut self.repository_browse,
+            "Screenshots" => &mut self.screenshots,
+            "Security-Contact" => &mut self.security_contact,
+            _ => {
+                self.other.push((key.to_string(), value));
+                return;
+            }
+        };
+        *field = Some(value);
+    }
+
+    /// Get the value of any field with a scalar value.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        let field = match key {
+            "Archive" => &self.archive,
+            "Bug-Database" => &self.bug_database,
+            "Bug-Submit" => &self.bug_submit,
+            "Changelog" => &self.changelog,
+            "Documentation" => &self.documentation,
+            "Donation" => &self.donation,
+            "FAQ" => &self.faq,
+            "Funding" => &self.funding,
+            "Repository" => &self.repository,
+            "Repository-Browse" => &self.repository_browse,
+            "Screenshots" => &self.screenshots,
+            "Security-Contact" => &self.security_contact,
+            _ => {
+                return self
+                    .other
+                    .iter()
+                    .find(|(k, _)| k == key)
+                    .map(|(_, v)| v.as_str())
+            }
+        };
+        field.as_deref()
+    }
+
+    fn from_value(root: &Value) -> Result<UpstreamMetadata, &'static str> {
+        let mut result = UpstreamMetadata::default();
+        let map = match *root {
+            Value::Mapping(ref m) => m,
+            Value::Null => return Ok(result),
+            _ => return Err("upstream metadata must be a mapping"),
+        };
+        for (k, v) in map {
+            let key = match scalar(k) {
+                Some(k) => k,
+                None => return Err("invalid key"),
+            };
+            match key.as_str() {
+                "Registry" => match mappings(v) {
+                    Some(entries) => {
+                        for m in entries {
+                            let p = pairs(m);
+                            let get = |n: &str| {
+                                p.iter().find(|(k, _)| k == n).map(|x| &x.1)
+                            };
+                            match (get("Name"), get("Entry")) {
+                                (Some(n), Some(e)) => {
+                                    result.registry.push(RegistryEntry {
+                                        name: n.clone(),
+                                        entry: e.clone(),
+                                    })
+                                }
+                                _ => result.issues.push(
+                                    MetadataIssue::InvalidValue(key.clone()),
+                                ),
+                            }
+                        }
+                    }
+                    None => {
+                        result.issues.push(MetadataIssue::InvalidValue(key))
+                    }
+                },
+                "Reference" => match mappings(v) {
+                    Some(entries) => {
+                        result.reference =
+                            entries.into_iter().map(pairs).collect();
+                    }
+                    None => {
+                        result.issues.push(MetadataIssue::InvalidValue(key))
+                    }
+                },
+                _ => match scalar(v) {
+                    Some(s) => result.set(&key, s),
+                    None => {
+                        result.issues.push(MetadataIssue::InvalidValue(key))
+                    }
+                },
+            }
+        }
+        Ok(result)
+    }
+
+    /// Check the metadata for unknown or deprecated fields and values
+    /// of unexpected form.
+    pub fn validate(&self) -> Vec<MetadataIssue> {
+        let mut issues = self.issues.clone();
+        for (key, _) in &self.other {
+            if DEPRECATED_FIELDS.contains(&key.as_str()) {
+                issues.push(MetadataIssue::DeprecatedField(key.clone()));
+            } else if !STRING_FIELDS.contains(&key.as_str()) {
+                issues.push(MetadataIssue::UnknownField(key.clone()));
+            }
+        }
+        let urls = [
+            ("Bug-Database", &self.bug_database),
+            ("Repository-Browse", &self.repository_browse),
+            ("Changelog", &self.changelog),
+            ("Documentation", &self.documentation),
+        ];
+        for (key, value) in urls {
+            if let Some(ref v) = value {
+                if !v.starts_with("http://") && !v.starts_with("https://") {
+                    issues.push(MetadataIssue::InvalidValue(key.to_string()));
+                }
+            }
+        }
+        issues
+    }
+
+    fn to_value(&self) -> Value {
+        let mut map = Mapping::new();
+        let fields = [
+            ("Archive", &self.archive),
+            ("Bug-Database", &self.bug_database),
+            ("Bug-Submit", &self.bug_submit),
+            ("Changelog", &self.changelog),
+            ("Documentation", &self.documentation),
+            ("Donation", &self.donation),
+            ("FAQ", &self.faq),
+            ("Funding", &self.funding),
+            ("Repository", &self.repository),
+            ("Repository-Browse", &self.repository_browse),
+            ("Screenshots", &self.screenshots),
+            ("Security-Contact", &self.security_contact),
+        ];
+        for (key, value) in fields {
+            if let Some(ref v) = value {
+                map.insert(key.into(), v.as_str().into());
+            }
+        }
+        for (k, v) in &self.other {
+            map.insert(k.as_str().into(), v.as_str().into());
+        }
+        let to_mapping = |pairs: &[(String, String)]| {
+            let mut m = Mapping::new();
+            for (k, v) in pairs {
+                m.insert(k.as_str().into(), v.as_str().into());
+            }
+            Value::Mapping(m)
+        };
+        if !self.registry.is_empty() {
+            let entries = self
+                .registry
+                .iter()
+                .map(|r| {
+                    to_mapping(&[
+                        ("Name".to_string(), r.name.clone()),
+                        ("Entry".to_string(), r.entry.clone()),
+                    ])
+                })
+                .collect();
+            map.insert("Registry".into(), Value::Sequence(entries));
+        }
+        if !self.reference.is_empty() {
+            let entries = self.reference.iter().map(|r| to_mapping(r));
+            map.insert("Reference".into(), Value::Sequence(entries.collect()));
+        }
+        Value::Mapping(map)
+    }
+
+    /// Serializes the metadata to disk.
+    pub fn to_file(&self, out_file: &Path) -> io::Result<()> {
+        fs::write(out_file, self.to_string())
+    }
+}
+
+impl FromStr for UpstreamMetadata {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+        let value: Value = serde_yaml::from_str(s)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        UpstreamMetadata::from_value(&value).map_err(invalid)
+    }
+}
+
+impl fmt::Display for UpstreamMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match serde_yaml::to_string(&self.to_value()) {
+            Ok(s) => write!(f, "---\n{s}"),
+            Err(_) => Err(fmt::Error),
+        }
+    }
+}
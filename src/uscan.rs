@@ -0,0 +1,515 @@
+//! Evaluation of watch files
+//!
+//! Like uscan, looks for new upstream releases as described by the
+//! entries of a [`WatchFile`](crate::watch::WatchFile): fetches the
+//! listing page, matches its links against the matching pattern and
+//! reports the newest upstream version found, together with the URL to
+//! download it from.
+//!
+//! Besides plain HTML pages, this supports:
+//!
+//! - the substitutions `@PACKAGE@`, `@ANY_VERSION@`, `@ARCHIVE_EXT@`,
+//!   `@SIGNATURE_EXT@` and `@DEB_EXT@`,
+//! - regular expressions in directory names of the URL, with
+//!   `dirversionmangle`,
+//! - the `pagemangle`, `uversionmangle`, `dversionmangle`,
+//!   `downloadurlmangle`, `filenamemangle` and `pgpsigurlmangle`
+//!   options and `searchmode=plain`,
+//! - `mode=git`, which lists the references of a git repository with
+//!   `git ls-remote`; local repositories need to be given by absolute
+//!   path,
+//! - the redirectors for GitHub tag and release pages, which are looked
+//!   up through the GitHub API, and for SourceForge.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use debian::uscan::Scanner;
+//! use debian::watch::WatchFile;
+//! use debian::Version;
+//! use std::path::Path;
+//!
+//! let watch = WatchFile::from_file(Path::new("debian/watch")).unwrap();
+//! let scanner = Scanner::new("foo");
+//! let current = Version::parse("1.0-1").unwrap();
+//! let result = scanner.check(&watch.entries[0], &current).unwrap();
+//! if result.is_newer() {
+//!     let newest = &result.newest;
+//!     println!("new version {} at {}", newest.version, newest.url);
+//! }
+//! ```
+
+use std::cmp::Ordering;
+use std::env;
+use std::io;
+use std::process::Command;
+
+use regex::{Captures, Regex};
+
+use crate::archive::fetch::request_error;
+use crate::version::compare;
+use crate::watch::WatchEntry;
+use crate::Version;
+
+/// The substitutions available in URLs and matching patterns.
+const SUBSTITUTIONS: &[(&str, &str)] = &[
+    ("@ANY_VERSION@", r"[-_]?[Vv]?(\d[\-+\.:\~\da-zA-Z]*)"),
+    (
+        "@ARCHIVE_EXT@",
+        r"(?i:\.(?:tar\.xz|tar\.bz2|tar\.gz|tar\.zstd?|zip|tgz|tbz|txz))",
+    ),
+    (
+        "@SIGNATURE_EXT@",
+        concat!(
+            r"(?i:\.(?:tar\.xz|tar\.bz2|tar\.gz|tar\.zstd?|zip|tgz|tbz|txz))",
+            r"(?i:\.(?:asc|pgp|gpg|sig|sign))",
+        ),
+    ),
+    ("@DEB_EXT@", r"[\+~](debian|dfsg|ds|deb)(\.)?(\d+)?$"),
+];
+
+/// User agent sent unless the watch entry sets one.
+const USER_AGENT: &str = "Debian uscan";
+
+/// Base URL of the GitHub API, used for GitHub tag and release pages.
+const GITHUB_API_URL: &str = "https://api.github.com";
+
+/// A release found by scanning a watch entry.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct UpstreamRelease {
+    /// the version as matched, before `uversionmangle`
+    pub upstream_version: String,
+    /// the version after `uversionmangle`, comparable to the upstream
+    /// part of Debian versions
+    pub version: String,
+    /// the URL to download the release from, or the repository in git
+    /// mode
+    pub url: String,
+    /// the reference of the release, in git mode
+    pub git_ref: Option<String>,
+    /// the URL of the signature, given `pgpsigurlmangle`
+    pub signature_url: Option<String>,
+    /// the name to store the download as
+    pub filename: String,
+}
+
+/// The result of checking a watch entry against the current version
+/// of a package.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ScanResult {
+    /// the newest release found
+    pub newest: UpstreamRelease,
+    /// the upstream part of the current version, after
+    /// `dversionmangle`
+    pub current: String,
+}
+
+/// Scans watch entries of a source package for upstream releases.
+#[derive(Debug)]
+pub struct Scanner {
+    agent: ureq::Agent,
+    package: String,
+    github_api_url: String,
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(
+    e: E,
+) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// Applies the rules of a mangling option to a string.
+fn mangle(entry: &WatchEntry, option: &str, s: &str) -> io::Result<String> {
+    let mut result = s.to_string();
+    for rule in entry.mangle_rules(option).map_err(invalid_data)? {
+        result = rule.apply(&result);
+    }
+    Ok(result)
+}
+
+/// Replaces the substitutions of uscan, like `@ANY_VERSION@`, in a URL
+/// or matching pattern.
+pub fn expand_pattern(pattern: &str, package: &str) -> String {
+    let mut result = pattern.replace("@PACKAGE@", package);
+    for (name, value) in SUBSTITUTIONS {
+        result = result.replace(name, value);
+    }
+    result
+}
+
+/// Compiles a pattern matching whole strings only.
+fn anchored(pattern: &str) -> io::Result<Regex> {
+    Regex::new(&format!("^(?:{pattern})$"))
+        .map_err(|_| invalid_data(format!("invalid pattern {pattern}")))
+}
+
+/// Compares versions as dpkg does, falling back to a plain string
+/// comparison for strings that are no valid versions.
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    compare(a, b).unwrap_or_else(|_| a.cmp(b))
+}
+
+/// The version matched by a pattern, which is made up of all groups
+/// captured, joined by dots.
+fn captured_version(caps: &Captures) -> Option<String> {
+    let groups: Vec<&str> =
+        caps.iter().skip(1).flatten().map(|m| m.as_str()).collect();
+    match groups.is_empty() {
+        true => None,
+        false => Some(groups.join(".")),
+    }
+}
+
+/// Resolves a possibly relative link against the URL of the page it
+/// appears on.
+fn resolve_url(base: &str, href: &str) -> String {
+    let scheme_end = base.find("://").map_or(0, |p| p + 3);
+    if href.contains("://") {
+        href.to_string()
+    } else if let Some(rest) = href.strip_prefix("//") {
+        format!("{}{rest}", &base[..scheme_end])
+    } else if href.starts_with('/') {
+        let host_end = base[scheme_end..]
+            .find('/')
+            .map_or(base.len(), |p| scheme_end + p);
+        format!("{}{href}", &base[..host_end])
+    } else {
+        let dir_end = base.rfind('/').filter(|&p| p >= scheme_end);
+        let dir = dir_end.map_or(base.to_string(), |p| base[..=p].to_string());
+        format!("{dir}{}", href.trim_start_matches("./"))
+    }
+}
+
+/// Extracts the targets of the links of an HTML page.
+fn links(page: &str) -> Vec<String> {
+    let href =
+        Regex::new(r#"(?i)<\s*a\s[^>]*href\s*=\s*["']([^"']+)["']"#).unwrap();
+    href.captures_iter(page)
+        .map(|caps| caps[1].replace("&amp;", "&"))
+        .collect()
+}
+
+/// Matches links found on a page against an anchored pattern,
+/// returning the matched version and absolute URL of each match. The
+/// pattern may match the link as given, the absolute URL or the URL
+/// relative to the directory of the page, with or without a trailing
+/// slash.
+fn match_links(
+    page_url: &str,
+    links: &[String],
+    pattern: &Regex,
+) -> Vec<(String, String)> {
+    let dir = resolve_url(page_url, "");
+    let mut result = vec![];
+    for link in links {
+        let url = resolve_url(page_url, link);
+        let relative = url.strip_prefix(&dir).unwrap_or(link);
+        let version = [link.as_str(), &url, relative]
+            .into_iter()
+            .flat_map(|c| [c, c.trim_end_matches('/')])
+            .find_map(|c| captured_version(&pattern.captures(c)?));
+        if let Some(version) = version {
+            result.push((version, url));
+        }
+    }
+    result
+}
+
+/// Rewrites URLs of sites uscan knows redirectors for.
+fn redirect_url(url: &str) -> String {
+    let sf = Regex::new(r"^https?://sf\.net/(.*)$").unwrap();
+    match sf.captures(url) {
+        Some(caps) => {
+            format!("https://qa.debian.org/watch/sf.php/{}", &caps[1])
+        }
+        None => url.to_string(),
+    }
+}
+
+/// The owner and name of the repository of a GitHub tag or release
+/// page.
+fn github_project(url: &str) -> Option<(String, String)> {
+    let re = Regex::new(
+        r"^https?://github\.com/([^/]+)/([^/]+)/(?:tags|releases)/?$",
+    )
+    .unwrap();
+    let caps = re.captures(url)?;
+    Some((caps[1].to_string(), caps[2].to_string()))
+}
+
+impl Scanner {
+    /// Creates a scanner for the given source package, which is
+    /// substituted for `@PACKAGE@`.
+    pub fn new(package: &str) -> Scanner {
+        Scanner {
+            agent: ureq::Agent::new(),
+            package: package.to_string(),
+            github_api_url: GITHUB_API_URL.to_string(),
+        }
+    }
+
+    /// Sets the base URL of the GitHub API, for GitHub Enterprise
+    /// instances or mirrors.
+    pub fn set_github_api_url(&mut self, url: &str) {
+        self.github_api_url = url.trim_end_matches('/').to_string();
+    }
+
+    /// Fetches a page, applying `pagemangle`.
+    fn fetch_page(&self, entry: &WatchEntry, url: &str) -> io::Result<String> {
+        let user_agent = entry.get_option("user-agent").unwrap_or(USER_AGENT);
+        let page = self
+            .agent
+            .get(url)
+            .set("User-Agent", user_agent)
+            .call()
+            .map_err(request_error)?
+            .into_string()?;
+        mangle(entry, "pagemangle", &page)
+    }
+
+    /// Replaces the regular expressions in directory names of a URL by
+    /// the newest matching directory.
+    fn resolve_dirs(
+        &self,
+        entry: &WatchEntry,
+        url: &str,
+    ) -> io::Result<String> {
+        let mut url = url.to_string();
+        loop {
+            let start = url.find("://").map_or(0, |p| p + 3);
+            let segments: Vec<&str> = url[start..].split('/').collect();
+            let i = match segments.iter().skip(1).position(|s| s.contains('('))
+            {
+                Some(i) => i + 1,
+                None => return Ok(url),
+            };
+            let parent =
+                format!("{}{}/", &url[..start], segments[..i].join("/"));
+            let page = self.fetch_page(entry, &parent)?;
+            let pattern = anchored(segments[i])?;
+            let mut newest: Option<(String, String)> = None;
+            for (version, dir_url) in
+                match_links(&parent, &links(&page), &pattern)
+            {
+                let version = mangle(entry, "dirversionmangle", &version)?;
+                let newer = newest.as_ref().map_or(true, |(v, _)| {
+                    compare_versions(&version, v) == Ordering::Greater
+                });
+                if newer {
+                    newest = Some((version, dir_url));
+                }
+            }
+            let (_, dir_url) = newest.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!(
+                        "no directory matching {} in {parent}",
+                        segments[i]
+                    ),
+                )
+            })?;
+            let mut rest = segments[i + 1..].join("/");
+            if i + 1 < segments.len() {
+                rest.insert(0, '/');
+            }
+            url = format!("{}{rest}", dir_url.trim_end_matches('/'));
+        }
+    }
+
+    /// Creates the release for a matched version and URL.
+    fn release(
+        &self,
+        entry: &WatchEntry,
+        upstream_version: String,
+        url: String,
+        git_ref: Option<String>,
+    ) -> io::Result<UpstreamRelease> {
+        let version = entry
+            .mangle_upstream_version(&upstream_version)
+            .map_err(invalid_data)?;
+        let (url, signature_url, filename) = match git_ref {
+            Some(_) => {
+                let filename = format!("{}-{version}.tar.xz", self.package);
+                (url, None, filename)
+            }
+            None => {
+                let url = mangle(entry, "downloadurlmangle", &url)?;
+                let signature_url = match entry.has_option("pgpsigurlmangle") {
+                    true => Some(mangle(entry, "pgpsigurlmangle", &url)?),
+                    false => None,
+                };
+                let filename = match entry.has_option("filenamemangle") {
+                    true => {
+                        entry.mangle_filename(&url).map_err(invalid_data)?
+                    }
+                    false => {
+                        let path = url.split(['?', '#']).next().unwrap_or("");
+                        path.rsplit('/').next().unwrap_or("").to_string()
+                    }
+                };
+                (url, signature_url, filename)
+            }
+        };
+        Ok(UpstreamRelease {
+            upstream_version,
+            version,
+            url,
+            git_ref,
+            signature_url,
+            filename,
+        })
+    }
+
+    /// Lists the tags or branches of a git repository matching the
+    /// pattern, as `(version, reference)`.
+    fn scan_git(
+        &self,
+        entry: &WatchEntry,
+    ) -> io::Result<Vec<(String, String)>> {
+        let pattern = expand_pattern(&entry.matching_pattern, &self.package);
+        if pattern == "HEAD" {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "scanning HEAD in git mode is not supported",
+            ));
+        }
+        let pattern = anchored(&pattern)?;
+        // listing needs no repository, so keep git from picking up the
+        // configuration of one the caller may be in
+        let output = Command::new("git")
+            .arg("ls-remote")
+            .arg(&entry.url)
+            .current_dir(env::temp_dir())
+            .output()?;
+        if !output.status.success() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "git ls-remote failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            ));
+        }
+        let mut result: Vec<(String, String)> = vec![];
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let git_ref = match line.split_once('\t') {
+                Some((_, r)) => r.trim_end_matches("^{}"),
+                None => continue,
+            };
+            if result.iter().any(|(_, r)| r == git_ref) {
+                continue;
+            }
+            if let Some(caps) = pattern.captures(git_ref) {
+                if let Some(version) = captured_version(&caps) {
+                    result.push((version, git_ref.to_string()));
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Looks for the releases of a watch entry, returning all of them,
+    /// newest first.
+    pub fn scan(&self, entry: &WatchEntry) -> io::Result<Vec<UpstreamRelease>> {
+        let mut releases = vec![];
+        if entry.get_option("mode") == Some("git") {
+            for (version, git_ref) in self.scan_git(entry)? {
+                let url = entry.url.clone();
+                releases.push(self.release(
+                    entry,
+                    version,
+                    url,
+                    Some(git_ref),
+                )?);
+            }
+        } else {
+            let url = expand_pattern(&entry.url, &self.package);
+            let pattern =
+                expand_pattern(&entry.matching_pattern, &self.package);
+            let matches = match github_project(&url) {
+                Some((owner, repo)) => {
+                    let api_url = format!(
+                        "{}/repos/{owner}/{repo}/tags?per_page=100",
+                        self.github_api_url
+                    );
+                    let name = Regex::new(r#""name"\s*:\s*"([^"]+)""#).unwrap();
+                    let page = self.fetch_page(entry, &api_url)?;
+                    let links: Vec<String> = name
+                        .captures_iter(&page)
+                        .map(|caps| {
+                            format!(
+                                "https://github.com/{owner}/{repo}/archive/\
+                                 refs/tags/{}.tar.gz",
+                                &caps[1]
+                            )
+                        })
+                        .collect();
+                    match_links(&url, &links, &anchored(&pattern)?)
+                }
+                None => {
+                    let url = self.resolve_dirs(entry, &redirect_url(&url))?;
+                    let page = self.fetch_page(entry, &url)?;
+                    match entry.get_option("searchmode") {
+                        Some("plain") => {
+                            let pattern =
+                                Regex::new(&pattern).map_err(|_| {
+                                    invalid_data(format!(
+                                        "invalid pattern {pattern}"
+                                    ))
+                                })?;
+                            pattern
+                                .captures_iter(&page)
+                                .filter_map(|caps| {
+                                    let version = captured_version(&caps)?;
+                                    Some((version, resolve_url(&url, &caps[0])))
+                                })
+                                .collect()
+                        }
+                        _ => match_links(
+                            &url,
+                            &links(&page),
+                            &anchored(&pattern)?,
+                        ),
+                    }
+                }
+            };
+            for (version, url) in matches {
+                releases.push(self.release(entry, version, url, None)?);
+            }
+        }
+        releases.sort_by(|a, b| compare_versions(&b.version, &a.version));
+        Ok(releases)
+    }
+
+    /// Looks for the newest release of a watch entry.
+    pub fn newest(&self, entry: &WatchEntry) -> io::Result<UpstreamRelease> {
+        self.scan(entry)?.into_iter().next().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no release matching {}", entry.matching_pattern),
+            )
+        })
+    }
+
+    /// Looks for the newest release of a watch entry and compares it to
+    /// the current version of the package, after `dversionmangle`.
+    pub fn check(
+        &self,
+        entry: &WatchEntry,
+        current: &Version,
+    ) -> io::Result<ScanResult> {
+        let newest = self.newest(entry)?;
+        let current = entry
+            .mangle_debian_version(&current.upstream())
+            .map_err(invalid_data)?;
+        Ok(ScanResult { newest, current })
+    }
+}
+
+impl ScanResult {
+    /// Whether the newest release is newer than the current version.
+    pub fn is_newer(&self) -> bool {
+        compare_versions(&self.newest.version, &self.current)
+            == Ordering::Greater
+    }
+}
@@ -0,0 +1,293 @@
+//! Updating source packages to new upstream versions
+//!
+//! Like uupdate, creates the source tree of a new upstream version from
+//! its tarball and the `debian` directory of the current tree, next to
+//! the current one: the tarball is copied to the orig tarball of the
+//! new version, unpacked with any upstream `debian` directory replaced,
+//! and a changelog entry for the new version is added.
+//!
+//! The patches of the series are tried on the new upstream sources, so
+//! those that no longer apply, usually because they were merged
+//! upstream or conflict with upstream changes, can be refreshed or
+//! dropped. The new tree is left with no patches applied.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use debian::uupdate::UpstreamUpdate;
+//! use std::path::Path;
+//!
+//! let update = UpstreamUpdate::new(
+//!     Path::new("foo-1.2"),
+//!     Path::new("../foo-1.3.tar.gz"),
+//!     "1.3",
+//! )
+//! .unwrap();
+//! for report in update.check_patches().unwrap() {
+//!     if !report.is_applied() {
+//!         println!("{} needs to be refreshed", report.patch.unwrap());
+//!     }
+//! }
+//! let result = update.update().unwrap();
+//! println!("updated tree in {}", result.source_dir.display());
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::dsc::{read_tarball, read_tree, write_tree, TreeEntry};
+use crate::filename::{ArtifactName, TARBALL_COMPRESSIONS};
+use crate::package::{Changelog, ChangelogEntry};
+use crate::patches::PatchSeries;
+use crate::quilt::{ApplyReport, MemoryTree, Patch, PATCHES_DIR};
+use crate::version::compare;
+use crate::Version;
+
+/// The changelog entry added by default.
+const DEFAULT_MESSAGE: &str = "New upstream release.";
+
+/// Creates the source tree of a new upstream version.
+#[derive(Debug, Clone)]
+pub struct UpstreamUpdate {
+    source_dir: PathBuf,
+    tarball: PathBuf,
+    compression: String,
+    out_dir: PathBuf,
+    source: String,
+    version: Version,
+    message: String,
+}
+
+/// The outcome of an update.
+#[derive(Debug)]
+pub struct UpdateResult {
+    /// the new source tree
+    pub source_dir: PathBuf,
+    /// the orig tarball of the new version
+    pub orig_tarball: PathBuf,
+    /// the version of the new changelog entry
+    pub version: Version,
+    /// the results of trying the patches of the series, in order
+    pub patches: Vec<ApplyReport>,
+}
+
+fn invalid_input(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+impl UpstreamUpdate {
+    /// Prepares the update of the source tree in `source_dir` to the
+    /// upstream version in a tarball, like `foo-1.3.tar.gz`. The new
+    /// version keeps the epoch of the current one, with revision `1`.
+    ///
+    /// Fails if the current version is native or not older than the new
+    /// one, or the tarball is not compressed in a way allowed for orig
+    /// tarballs.
+    pub fn new(
+        source_dir: &Path,
+        tarball: &Path,
+        upstream_version: &str,
+    ) -> io::Result<UpstreamUpdate> {
+        let changelog =
+            Changelog::from_file(&source_dir.join("debian/changelog"))?;
+        let latest = changelog.entries().first().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "empty changelog")
+        })?;
+        let current = Version::parse(latest.version())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if current.is_native() {
+            return Err(invalid_input(format!(
+                "{current} is a native version"
+            )));
+        }
+        let epoch = match current.has_epoch() {
+            true => format!("{}:", current.effective_epoch()),
+            false => String::new(),
+        };
+        let version = Version::parse(&format!("{epoch}{upstream_version}-1"))
+            .map_err(|_| {
+            invalid_input(format!("invalid version {upstream_version}"))
+        })?;
+        if compare(&version.upstream(), &current.upstream())
+            != Ok(std::cmp::Ordering::Greater)
+        {
+            return Err(invalid_input(format!(
+                "{upstream_version} is not newer than {}",
+                current.upstream()
+            )));
+        }
+
+        let name = tarball.file_name().unwrap_or_default().to_string_lossy();
+        let compression = name
+            .rsplit_once(".tar.")
+            .map(|(_, ext)| ext)
+            .filter(|ext| TARBALL_COMPRESSIONS.contains(ext))
+            .ok_or_else(|| {
+                invalid_input(format!("{name} is no compressed tarball"))
+            })?;
+        let source_dir = source_dir.to_path_buf();
+        let out_dir = match source_dir.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+            _ => PathBuf::from(".."),
+        };
+        Ok(UpstreamUpdate {
+            source_dir,
+            tarball: tarball.to_path_buf(),
+            compression: compression.to_string(),
+            out_dir,
+            source: latest.package().to_string(),
+            version,
+            message: DEFAULT_MESSAGE.to_string(),
+        })
+    }
+
+    /// Sets the directory to create the new tree and orig tarball in,
+    /// by default the parent directory of the current tree.
+    pub fn set_out_dir(&mut self, out_dir: &Path) {
+        self.out_dir = out_dir.to_path_buf();
+    }
+
+    /// Sets the text of the new changelog entry, by default
+    /// `New upstream release.`.
+    pub fn set_message(&mut self, message: &str) {
+        self.message = message.to_string();
+    }
+
+    /// The name of the source package.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The version of the new changelog entry.
+    pub fn version(&self) -> &Version {
+        &self.version
+    }
+
+    /// The path of the new source tree, like `../foo-1.3`.
+    pub fn new_source_dir(&self) -> PathBuf {
+        let dir = format!("{}-{}", self.source, self.version.upstream());
+        self.out_dir.join(dir)
+    }
+
+    /// The path of the orig tarball of the new version.
+    pub fn orig_tarball(&self) -> PathBuf {
+        let name = ArtifactName::orig_tarball(
+            &self.source,
+            &self.version,
+            None,
+            &self.compression,
+        );
+        self.out_dir.join(name.to_string())
+    }
+
+    /// Reads the new upstream sources, without any `debian` directory.
+    fn upstream_files(&self) -> io::Result<BTreeMap<String, TreeEntry>> {
+        let mut files = read_tarball(&self.tarball, true, "")?;
+        files.retain(|p, _| p != "debian" && !p.starts_with("debian/"));
+        Ok(files)
+    }
+
+    /// Tries the patches of the series on the new upstream sources,
+    /// returning a report per patch. Patches are tried in order, each
+    /// on top of those before it that applied.
+    pub fn check_patches(&self) -> io::Result<Vec<ApplyReport>> {
+        self.try_patches(&self.upstream_files()?)
+    }
+
+    fn try_patches(
+        &self,
+        upstream: &BTreeMap<String, TreeEntry>,
+    ) -> io::Result<Vec<ApplyReport>> {
+        let series = PatchSeries::from_dir(&self.source_dir.join("debian"))?;
+        let mut tree = MemoryTree::new();
+        for (path, entry) in upstream {
+            if let TreeEntry::File(data, _) = entry {
+                if let Ok(text) = std::str::from_utf8(data) {
+                    tree.insert(path, text);
+                }
+            }
+        }
+        let patches_dir = self.source_dir.join(PATCHES_DIR);
+        let mut reports = vec![];
+        for name in series.patches() {
+            let patch = Patch::from_file(&patches_dir.join(name))?;
+            let strip = series.strip_level(name).unwrap_or(1);
+            let mut report = patch.apply(&mut tree, strip)?;
+            report.patch = Some(name.to_string());
+            reports.push(report);
+        }
+        Ok(reports)
+    }
+
+    /// Creates the new source tree and orig tarball and adds the
+    /// changelog entry, failing if the tree already exists. The tarball
+    /// is copied unless the orig tarball already exists with the same
+    /// contents.
+    pub fn update(&self) -> io::Result<UpdateResult> {
+        let new_dir = self.new_source_dir();
+        if new_dir.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", new_dir.display()),
+            ));
+        }
+        let upstream = self.upstream_files()?;
+        let patches = self.try_patches(&upstream)?;
+
+        let orig = self.orig_tarball();
+        match fs::read(&orig) {
+            Ok(data) if data == fs::read(&self.tarball)? => {}
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} already exists", orig.display()),
+                ))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                fs::create_dir_all(&self.out_dir)?;
+                fs::copy(&self.tarball, &orig)?;
+            }
+            Err(e) => return Err(e),
+        }
+
+        let mut debian = BTreeMap::new();
+        read_tree(
+            &self.source_dir,
+            &self.source_dir.join("debian"),
+            &|_| false,
+            &mut debian,
+        )?;
+        fs::create_dir_all(&new_dir)?;
+        write_tree(&new_dir, &upstream)?;
+        write_tree(&new_dir, &debian)?;
+
+        let entry = ChangelogEntry::new(
+            self.source.clone(),
+            self.version.to_string(),
+            format!("  * {}", self.message),
+        );
+        let changelog = new_dir.join("debian/changelog");
+        let old = fs::read_to_string(&changelog)?;
+        fs::write(&changelog, entry.serialize() + &old)?;
+
+        Ok(UpdateResult {
+            source_dir: new_dir,
+            orig_tarball: orig,
+            version: self.version.clone(),
+            patches,
+        })
+    }
+}
+
+impl UpdateResult {
+    /// The patches that no longer apply.
+    pub fn failed_patches(&self) -> Vec<&str> {
+        self.patches
+            .iter()
+            .filter(|r| !r.is_applied())
+            .filter_map(|r| r.patch.as_deref())
+            .collect()
+    }
+}
@@ -2,16 +2,47 @@ use std::cmp::Ordering;
 use std::fmt;
 use std::str::FromStr;
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+use crate::distro::DistroInfo;
+use crate::package::VRel;
+
+#[derive(Debug, Clone)]
 pub struct VersionElement {
     pub alpha: String,
+    /// the value of the numeric run, saturating at `u64::MAX`; it is
+    /// not used for comparisons, so even longer runs compare correctly
     pub numeric: u64,
+    /// the numeric run as written, possibly with leading zeros, or
+    /// empty if there was none
+    pub digits: String,
+}
+
+impl VersionElement {
+    /// The non-numeric run, possibly empty.
+    pub fn alpha_run(&self) -> &str {
+        &self.alpha
+    }
+
+    /// The numeric run as written, if there is one.
+    pub fn numeric_run(&self) -> Option<&str> {
+        match self.digits.is_empty() {
+            true => None,
+            false => Some(&self.digits),
+        }
+    }
+
+    /// The value of the numeric run, if there is one and it fits into
+    /// a `u64`.
+    pub fn numeric_value(&self) -> Option<u64> {
+        self.numeric_run()?.parse().ok()
+    }
 }
 
 impl Ord for VersionElement {
+    /// Compares the non-numeric runs first, following the dpkg rules,
+    /// then the numeric ones.
     fn cmp(&self, other: &VersionElement) -> Ordering {
-        match self.numeric.cmp(&other.numeric) {
-            Ordering::Equal => self.alpha.cmp(&other.alpha),
+        match compare_alpha(&self.alpha, &other.alpha) {
+            Ordering::Equal => compare_digits(&self.digits, &other.digits),
             x => x,
         }
     }
@@ -23,9 +54,17 @@ impl PartialOrd for VersionElement {
     }
 }
 
+impl PartialEq for VersionElement {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for VersionElement {}
+
 impl fmt::Display for VersionElement {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}{}", self.alpha, self.numeric)
+        write!(f, "{}{}", self.alpha, self.digits)
     }
 }
 
@@ -35,22 +74,608 @@ impl serde::Serialize for VersionElement {
     where
         S: serde::Serializer,
     {
-        let b = format!("{}{}", self.alpha, self.numeric);
+        let b = self.to_string();
         serializer.serialize_str(b.as_str())
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord)]
+/// Looks up the major version number of a Debian release given by its
+/// codename in the built-in distro data, like 12 for `bookworm`.
+/// Numbers are passed through.
+pub fn debian_release_number(release: &str) -> Option<u32> {
+    match release.parse() {
+        Ok(n) => Some(n),
+        Err(_) => DistroInfo::builtin()
+            .release(release)?
+            .version
+            .as_deref()?
+            .split('.')
+            .next()?
+            .parse()
+            .ok(),
+    }
+}
+
+/// Increments a run of decimal digits by one, keeping its width unless
+/// it overflows, like `09` to `10` or `99` to `100`.
+fn increment_digits(digits: &str) -> String {
+    let mut result = digits.as_bytes().to_vec();
+    for c in result.iter_mut().rev() {
+        if *c == b'9' {
+            *c = b'0';
+        } else {
+            *c += 1;
+            return String::from_utf8(result).unwrap();
+        }
+    }
+    result.insert(0, b'1');
+    String::from_utf8(result).unwrap()
+}
+
+/// Keywords of pre-release suffixes, like `~rc1`.
+const PRERELEASE_KEYWORDS: &[&str] = &["alpha", "beta", "rc", "pre", "dev"];
+
+/// Keywords of repack suffixes, like `+dfsg1`.
+const REPACK_KEYWORDS: &[&str] = &["dfsg", "ds", "repack"];
+
+/// Keywords of VCS snapshot suffixes, like `+git20240101`.
+const SNAPSHOT_KEYWORDS: &[&str] =
+    &["git", "svn", "bzr", "hg", "darcs", "cvs", "snapshot"];
+
+/// Strips any of the keywords from the start of `s`.
+fn strip_keyword<'a>(s: &'a str, keywords: &[&str]) -> Option<&'a str> {
+    keywords.iter().find_map(|k| s.strip_prefix(k))
+}
+
+/// Whether `s` is a run of digits, optionally preceded by a dot, like
+/// after `~rc` in `~rc.1`.
+fn is_counter(s: &str) -> bool {
+    s.strip_prefix('.')
+        .unwrap_or(s)
+        .bytes()
+        .all(|b| b.is_ascii_digit())
+}
+
+/// Whether `s` starts with a date of eight digits, like `20240101`,
+/// not followed by another digit.
+fn starts_with_date(s: &str) -> bool {
+    let bytes = s.as_bytes();
+    bytes.len() >= 8
+        && bytes[..8].iter().all(|b| b.is_ascii_digit())
+        && !bytes.get(8).map_or(false, |b| b.is_ascii_digit())
+}
+
+/// Whether `s` is one of the upstream suffixes `Version::base_upstream`
+/// drops, starting with its separator.
+fn is_upstream_suffix(s: &str) -> bool {
+    let snapshot = |after: &str| {
+        after
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'.')
+    };
+    let rest = s.get(1..).unwrap_or_default();
+    match s.as_bytes().first() {
+        Some(b'.') => {
+            strip_keyword(rest, REPACK_KEYWORDS).map_or(false, is_counter)
+        }
+        Some(b'+' | b'~') => {
+            strip_keyword(rest, REPACK_KEYWORDS).map_or(false, is_counter)
+                || (s.starts_with('~')
+                    && strip_keyword(rest, PRERELEASE_KEYWORDS)
+                        .map_or(false, is_counter))
+                || strip_keyword(rest, SNAPSHOT_KEYWORDS)
+                    .map_or(false, snapshot)
+                || (rest.len() >= 8
+                    && rest.as_bytes()[..8].iter().all(|b| b.is_ascii_digit())
+                    && snapshot(&rest[8..]))
+        }
+        _ => false,
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for VersionElement {
+    fn deserialize<D>(deserializer: D) -> Result<VersionElement, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        let mut part = parse_elements(&s);
+        match part.elements.len() {
+            1 => Ok(part.elements.remove(0)),
+            _ => Err(serde::de::Error::custom(
+                "expected a single version element",
+            )),
+        }
+    }
+}
+
+/// Weight of a byte within the non-numeric run of a version, like
+/// `char_order`, with digits and the end of the string weighing zero.
+fn byte_order(c: Option<u8>) -> i32 {
+    match c {
+        None => 0,
+        Some(b'~') => -1,
+        Some(c) if c.is_ascii_digit() => 0,
+        Some(c) if c.is_ascii_alphabetic() => c as i32,
+        Some(c) => c as i32 + 256,
+    }
+}
+
+/// Compares two upstream versions or revisions, following the
+/// algorithm of dpkg.
+fn compare_part(a: &[u8], b: &[u8]) -> Ordering {
+    let is_digit =
+        |s: &[u8], i: usize| s.get(i).map_or(false, u8::is_ascii_digit);
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        while (i < a.len() && !is_digit(a, i))
+            || (j < b.len() && !is_digit(b, j))
+        {
+            match byte_order(a.get(i).copied())
+                .cmp(&byte_order(b.get(j).copied()))
+            {
+                Ordering::Equal => {}
+                ord => return ord,
+            }
+            i += 1;
+            j += 1;
+        }
+        while a.get(i) == Some(&b'0') {
+            i += 1;
+        }
+        while b.get(j) == Some(&b'0') {
+            j += 1;
+        }
+        let mut first_diff = Ordering::Equal;
+        while is_digit(a, i) && is_digit(b, j) {
+            if first_diff == Ordering::Equal {
+                first_diff = a[i].cmp(&b[j]);
+            }
+            i += 1;
+            j += 1;
+        }
+        if is_digit(a, i) {
+            return Ordering::Greater;
+        }
+        if is_digit(b, j) {
+            return Ordering::Less;
+        }
+        if first_diff != Ordering::Equal {
+            return first_diff;
+        }
+    }
+    Ordering::Equal
+}
+
+/// Splits a version string into its epoch, upstream version and
+/// revision, without allocating.
+fn split_version(s: &str) -> Result<(u32, &str, &str), ParseError> {
+    let (epoch, rest) = match s.split_once(':') {
+        Some((e, rest)) => match u32::from_str(e) {
+            Ok(e) => (e, rest),
+            Err(_) => {
+                return Err(ParseError {
+                    pos: 0,
+                    msg: "Expected a numeric epoch.".to_string(),
+                })
+            }
+        },
+        None => (0, s),
+    };
+    Ok(match rest.rsplit_once('-') {
+        Some((upstream, revision)) => (epoch, upstream, revision),
+        None => (epoch, rest, ""),
+    })
+}
+
+/// Compares two version strings the way dpkg does, without building
+/// `Version` structs or allocating, which makes it suitable for sorting
+/// large package indices.
+///
+/// ```
+/// use debian::version::compare;
+/// use std::cmp::Ordering;
+///
+/// assert_eq!(compare("1.0~rc1-1", "1.0-1").unwrap(), Ordering::Less);
+/// assert_eq!(compare("0:1.04", "1.4").unwrap(), Ordering::Equal);
+/// ```
+pub fn compare(a: &str, b: &str) -> Result<Ordering, ParseError> {
+    let (a_epoch, a_upstream, a_revision) = split_version(a)?;
+    let (b_epoch, b_upstream, b_revision) = split_version(b)?;
+    Ok(a_epoch
+        .cmp(&b_epoch)
+        .then_with(|| {
+            compare_part(a_upstream.as_bytes(), b_upstream.as_bytes())
+        })
+        .then_with(|| {
+            compare_part(a_revision.as_bytes(), b_revision.as_bytes())
+        }))
+}
+
+/// A precomputed sort key for a version, ordering like the version
+/// itself but comparable by a single slice comparison.
+///
+/// Building the key once per version pays off when sorting large
+/// lists, like whole archive indices.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Default)]
+pub struct VersionKey(Vec<u32>);
+
+// Weights of the key: a tilde sorts before the end of a part, which
+// sorts before the end of a non-numeric run, which sorts before any
+// other character, letters first.
+const KEY_TILDE: u32 = 1;
+const KEY_PART_END: u32 = 2;
+const KEY_RUN_END: u32 = 3;
+
+impl VersionKey {
+    /// Computes the key of a version string.
+    pub fn new(s: &str) -> Result<VersionKey, ParseError> {
+        let (epoch, upstream, revision) = split_version(s)?;
+        let mut key = vec![epoch];
+        VersionKey::push_part(&mut key, upstream.as_bytes());
+        VersionKey::push_part(&mut key, revision.as_bytes());
+        Ok(VersionKey(key))
+    }
+
+    /// Appends the alternating non-numeric and numeric runs of a part,
+    /// the former weighted by character, the latter as the number of
+    /// significant digits followed by the digits.
+    fn push_part(key: &mut Vec<u32>, mut s: &[u8]) {
+        loop {
+            let n = s.iter().take_while(|c| !c.is_ascii_digit()).count();
+            key.extend(s[..n].iter().map(|&c| match c {
+                b'~' => KEY_TILDE,
+                c if c.is_ascii_alphabetic() => c as u32 + 4,
+                c => c as u32 + 260,
+            }));
+            key.push(KEY_RUN_END);
+            s = &s[n..];
+            let n = s.iter().take_while(|c| c.is_ascii_digit()).count();
+            let digits =
+                &s[s[..n].iter().take_while(|&&c| c == b'0').count()..n];
+            key.push(digits.len() as u32);
+            key.extend(digits.iter().map(|&c| c as u32));
+            s = &s[n..];
+            if s.is_empty() {
+                break;
+            }
+        }
+        key.push(KEY_PART_END);
+    }
+}
+
+impl From<&Version> for VersionKey {
+    fn from(version: &Version) -> VersionKey {
+        let mut key = vec![version.effective_epoch()];
+        VersionKey::push_part(&mut key, version.upstream().as_bytes());
+        VersionKey::push_part(
+            &mut key,
+            version.debian_revision.to_string().as_bytes(),
+        );
+        VersionKey(key)
+    }
+}
+
+/// Sorts versions in ascending order, computing each version's key
+/// only once.
+pub fn sort_versions(versions: &mut [Version]) {
+    versions.sort_by_cached_key(|v| VersionKey::from(v));
+}
+
+/// Sorts pairs of a name and a version string, like package names and
+/// their versions, by ascending version, computing each version's key
+/// only once. The sort is stable, so entries with equal versions keep
+/// their order.
+///
+/// The slice is left untouched if any of the versions is invalid.
+pub fn sort_by_cached_version_key<N, V: AsRef<str>>(
+    items: &mut [(N, V)],
+) -> Result<(), ParseError> {
+    for (_, v) in items.iter() {
+        split_version(v.as_ref())?;
+    }
+    items.sort_by_cached_key(|(_, v)| VersionKey::new(v.as_ref()).ok());
+    Ok(())
+}
+
+/// A comparison operator of `dpkg --compare-versions`.
+///
+/// The `-nl` variants treat an empty version as later rather than
+/// earlier than any other one.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CompareOp {
+    Lt,
+    Le,
+    Eq,
+    Ne,
+    Ge,
+    Gt,
+    LtNl,
+    LeNl,
+    GeNl,
+    GtNl,
+}
+
+impl CompareOp {
+    /// Check whether the result of comparing two versions satisfies
+    /// the operator.
+    pub fn matches(&self, ord: Ordering) -> bool {
+        match *self {
+            CompareOp::Lt | CompareOp::LtNl => ord == Ordering::Less,
+            CompareOp::Le | CompareOp::LeNl => ord != Ordering::Greater,
+            CompareOp::Eq => ord == Ordering::Equal,
+            CompareOp::Ne => ord != Ordering::Equal,
+            CompareOp::Ge | CompareOp::GeNl => ord != Ordering::Less,
+            CompareOp::Gt | CompareOp::GtNl => ord == Ordering::Greater,
+        }
+    }
+
+    fn empty_is_later(&self) -> bool {
+        matches!(
+            *self,
+            CompareOp::LtNl
+                | CompareOp::LeNl
+                | CompareOp::GeNl
+                | CompareOp::GtNl
+        )
+    }
+}
+
+/// Parses the operator spellings dpkg accepts, including the
+/// deprecated `<` and `>`, which mean `<=` and `>=`.
+impl FromStr for CompareOp {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "lt" | "<<" => CompareOp::Lt,
+            "le" | "<=" | "<" => CompareOp::Le,
+            "eq" | "=" => CompareOp::Eq,
+            "ne" => CompareOp::Ne,
+            "ge" | ">=" | ">" => CompareOp::Ge,
+            "gt" | ">>" => CompareOp::Gt,
+            "lt-nl" => CompareOp::LtNl,
+            "le-nl" => CompareOp::LeNl,
+            "ge-nl" => CompareOp::GeNl,
+            "gt-nl" => CompareOp::GtNl,
+            _ => return Err("unknown comparison operator"),
+        })
+    }
+}
+
+impl fmt::Display for CompareOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            CompareOp::Lt => "lt",
+            CompareOp::Le => "le",
+            CompareOp::Eq => "eq",
+            CompareOp::Ne => "ne",
+            CompareOp::Ge => "ge",
+            CompareOp::Gt => "gt",
+            CompareOp::LtNl => "lt-nl",
+            CompareOp::LeNl => "le-nl",
+            CompareOp::GeNl => "ge-nl",
+            CompareOp::GtNl => "gt-nl",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Checks the syntax errors dpkg refuses versions for, as opposed to
+/// the ones it merely warns about.
+fn check_dpkg_syntax(s: &str) -> Result<(), ParseError> {
+    let error = |pos: usize, msg: &str| ParseError {
+        pos: pos as i32,
+        msg: msg.to_string(),
+    };
+    if let Some(pos) = s.find(char::is_whitespace) {
+        return Err(error(pos, "Version string has embedded spaces."));
+    }
+    let (_, upstream, revision) = split_version(s)?;
+    let upstream_pos = s.len() - upstream.len() - revision.len();
+    if upstream.is_empty() && s.contains(':') {
+        return Err(error(upstream_pos, "Nothing after colon in version."));
+    }
+    if revision.is_empty() && s.ends_with('-') {
+        return Err(error(s.len(), "Revision number is empty."));
+    }
+    Ok(())
+}
+
+/// Evaluates a version comparison the way `dpkg --compare-versions`
+/// does, so the conditions of maintainer scripts can be checked
+/// without calling dpkg.
+///
+/// Surrounding whitespace is ignored. An empty version counts as
+/// earlier than any other version, or as later for the `-nl`
+/// operators.
+///
+/// ```
+/// use debian::version::{dpkg_compare_versions, CompareOp};
+///
+/// assert!(dpkg_compare_versions("1.0-1", CompareOp::Lt, "1.0-2").unwrap());
+/// assert!(dpkg_compare_versions("", CompareOp::GtNl, "1.0").unwrap());
+/// ```
+pub fn dpkg_compare_versions(
+    a: &str,
+    op: CompareOp,
+    b: &str,
+) -> Result<bool, ParseError> {
+    let (a, b) = (a.trim(), b.trim());
+    let empty = match op.empty_is_later() {
+        true => Ordering::Greater,
+        false => Ordering::Less,
+    };
+    let ord = match (a.is_empty(), b.is_empty()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => empty,
+        (false, true) => empty.reverse(),
+        (false, false) => {
+            check_dpkg_syntax(a)?;
+            check_dpkg_syntax(b)?;
+            compare(a, b)?
+        }
+    };
+    Ok(op.matches(ord))
+}
+
+/// Weight of a character within the non-numeric run of a version, as
+/// used by dpkg. A tilde sorts before anything, even the end of the
+/// run, and letters sort before all other characters.
+fn char_order(c: Option<char>) -> i32 {
+    match c {
+        None => 0,
+        Some('~') => -1,
+        Some(c) if c.is_ascii_alphabetic() => c as i32,
+        Some(c) => c as i32 + 256,
+    }
+}
+
+/// Compares two runs of digits by their numeric value, regardless of
+/// their length.
+fn compare_digits(a: &str, b: &str) -> Ordering {
+    let a = a.trim_start_matches('0');
+    let b = b.trim_start_matches('0');
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+/// Compares two non-numeric runs of a version the way dpkg does.
+fn compare_alpha(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars();
+    let mut b = b.chars();
+    loop {
+        match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (x, y) => match char_order(x).cmp(&char_order(y)) {
+                Ordering::Equal => {}
+                ord => return ord,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct VersionPart {
     pub elements: Vec<VersionElement>,
 }
 
 impl VersionPart {
-    fn count_elements(&self) -> usize {
+    /// Iterates over the elements, each a non-numeric run followed by
+    /// a numeric one.
+    pub fn iter(&self) -> std::slice::Iter<'_, VersionElement> {
+        self.elements.iter()
+    }
+
+    /// The number of elements.
+    pub fn len(&self) -> usize {
         self.elements.len()
     }
+
+    /// Whether the part has no elements, like the revision of a native
+    /// version.
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Get the element at the given index.
+    pub fn get(&self, index: usize) -> Option<&VersionElement> {
+        self.elements.get(index)
+    }
+
+    /// Get the last element.
+    pub fn last(&self) -> Option<&VersionElement> {
+        self.elements.last()
+    }
+
+    /// Whether the part starts with a digit, as Debian Policy requires
+    /// for upstream versions.
+    pub fn starts_with_digit(&self) -> bool {
+        match self.elements.first() {
+            Some(e) => e.alpha.is_empty() && !e.digits.is_empty(),
+            None => false,
+        }
+    }
+
+    /// Strips leading zeros from all numeric runs.
+    fn strip_leading_zeros(&mut self) {
+        for e in &mut self.elements {
+            let trimmed = e.digits.trim_start_matches('0');
+            if trimmed.len() != e.digits.len() {
+                e.digits = match trimmed.is_empty() {
+                    true => "0".to_string(),
+                    false => trimmed.to_string(),
+                };
+            }
+        }
+    }
+
+    /// Increments the trailing numeric run, appending a `1` if the part
+    /// ends in a non-numeric run.
+    fn increment_last(&mut self) {
+        match self.elements.last_mut() {
+            Some(last) if !last.digits.is_empty() => {
+                last.digits = increment_digits(&last.digits);
+                last.numeric = last.numeric.saturating_add(1);
+            }
+            Some(last) => {
+                last.digits = "1".to_string();
+                last.numeric = 1;
+            }
+            None => self.elements.push(VersionElement {
+                alpha: "".to_string(),
+                numeric: 1,
+                digits: "1".to_string(),
+            }),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a VersionPart {
+    type Item = &'a VersionElement;
+    type IntoIter = std::slice::Iter<'a, VersionElement>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
 }
 
+impl Ord for VersionPart {
+    fn cmp(&self, other: &VersionPart) -> Ordering {
+        // Missing elements compare like empty ones, so that a trailing
+        // tilde sorts before the end of the other part.
+        let empty = VersionElement {
+            alpha: "".to_string(),
+            numeric: 0,
+            digits: "".to_string(),
+        };
+        let len = self.elements.len().max(other.elements.len());
+        for i in 0..len {
+            let a = self.elements.get(i).unwrap_or(&empty);
+            let b = other.elements.get(i).unwrap_or(&empty);
+            match a.cmp(b) {
+                Ordering::Equal => {}
+                ord => return ord,
+            }
+        }
+        Ordering::Equal
+    }
+}
+
+impl PartialOrd for VersionPart {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for VersionPart {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for VersionPart {}
+
 impl fmt::Display for VersionPart {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let s = self
@@ -73,51 +698,80 @@ impl serde::Serialize for VersionPart {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for VersionPart {
+    fn deserialize<D>(deserializer: D) -> Result<VersionPart, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Ok(parse_elements(&String::deserialize(deserializer)?))
+    }
+}
+
+/// Splits a version part into its elements, which cannot fail.
+fn parse_elements(s: &str) -> VersionPart {
+    if s.is_empty() {
+        return VersionPart { elements: vec![] };
+    }
+    let mut elements: Vec<VersionElement> = vec![];
+    let mut in_numeric_part = false;
+    let mut cur = VersionElement {
+        alpha: "".to_string(),
+        numeric: 0,
+        digits: "".to_string(),
+    };
+    for c in s.chars() {
+        match (in_numeric_part, c.is_ascii_digit()) {
+            (false, false) => cur.alpha.push(c),
+            (_, true) => {
+                in_numeric_part = true;
+                cur.numeric = cur
+                    .numeric
+                    .saturating_mul(10)
+                    .saturating_add((c as u64) - ('0' as u64));
+                cur.digits.push(c);
+            }
+            (true, false) => {
+                elements.push(cur);
+                in_numeric_part = false;
+                cur = VersionElement {
+                    alpha: "".to_string(),
+                    numeric: 0,
+                    digits: "".to_string(),
+                };
+                cur.alpha.push(c);
+            }
+        }
+    }
+    elements.push(cur);
+    VersionPart { elements }
+}
+
+#[derive(Debug, Clone)]
 pub struct Version {
-    pub epoch: u32,
+    /// the epoch, if given explicitly, even if zero
+    pub epoch: Option<u32>,
     pub upstream_version: VersionPart,
     pub debian_revision: VersionPart,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ParseError {
     pub pos: i32,
     pub msg: String,
 }
 
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid version at position {}: {}", self.pos, self.msg)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
 impl Version {
     pub fn parse_part(s: &str) -> Result<VersionPart, ParseError> {
-        if s.is_empty() {
-            return Ok(VersionPart { elements: vec![] });
-        }
-        let mut elements: Vec<VersionElement> = vec![];
-        let mut in_numeric_part = false;
-        let mut cur = VersionElement {
-            alpha: "".to_string(),
-            numeric: 0,
-        };
-        for c in s.chars() {
-            match (in_numeric_part, c.is_ascii_digit()) {
-                (false, false) => cur.alpha.push(c),
-                (_, true) => {
-                    in_numeric_part = true;
-                    cur.numeric *= 10;
-                    cur.numeric += (c as u64) - ('0' as u64);
-                }
-                (true, false) => {
-                    elements.push(cur);
-                    in_numeric_part = false;
-                    cur = VersionElement {
-                        alpha: "".to_string(),
-                        numeric: 0,
-                    };
-                    cur.alpha.push(c);
-                }
-            }
-        }
-        elements.push(cur);
-        Ok(VersionPart { elements })
+        Ok(parse_elements(s))
     }
 
     pub fn parse(s: &str) -> Result<Version, ParseError> {
@@ -142,22 +796,22 @@ impl Version {
 
         Ok(match (epoch, last_dash) {
             (Some((l, epoch)), Some(r)) => Version {
-                epoch,
+                epoch: Some(epoch),
                 upstream_version: Version::parse_part(&s[l + 1..r])?,
                 debian_revision: Version::parse_part(&s[r + 1..])?,
             },
             (Some((l, epoch)), None) => Version {
-                epoch,
+                epoch: Some(epoch),
                 upstream_version: Version::parse_part(&s[l + 1..])?,
                 debian_revision: VersionPart { elements: vec![] },
             },
             (None, Some(r)) => Version {
-                epoch: 0,
+                epoch: None,
                 upstream_version: Version::parse_part(&s[..r])?,
                 debian_revision: Version::parse_part(&s[r + 1..])?,
             },
             (None, None) => Version {
-                epoch: 0,
+                epoch: None,
                 upstream_version: Version::parse_part(s)?,
                 debian_revision: VersionPart { elements: vec![] },
             },
@@ -165,6 +819,761 @@ impl Version {
     }
 }
 
+impl Version {
+    /// Creates a version from its components, validating them as
+    /// `parse_strict` does. Error positions refer to the assembled
+    /// version string, i.e. `epoch:upstream-revision`.
+    pub fn new(
+        epoch: Option<u32>,
+        upstream: &str,
+        revision: Option<&str>,
+    ) -> Result<Version, ParseError> {
+        let mut s = match epoch {
+            Some(e) => format!("{e}:{upstream}"),
+            None => upstream.to_string(),
+        };
+        if let Some(r) = revision {
+            s.push('-');
+            s.push_str(r);
+        }
+        let version = Version::parse_strict(&s)?;
+        if version.upstream() != upstream
+            || version.revision().as_deref() != revision
+        {
+            // a hyphen ended up in the wrong part
+            let pos = s.len() - revision.map_or(0, |r| r.len() + 1);
+            return Err(ParseError {
+                pos: pos as i32,
+                msg: "Hyphen in Debian revision or in a native version."
+                    .to_string(),
+            });
+        }
+        Ok(version)
+    }
+
+    /// The epoch, defaulting to zero if not given explicitly.
+    pub fn effective_epoch(&self) -> u32 {
+        self.epoch.unwrap_or(0)
+    }
+
+    /// Whether the version carries an explicit zero epoch, like `0:1.2`,
+    /// which is equivalent to giving no epoch at all.
+    pub fn has_redundant_epoch(&self) -> bool {
+        self.epoch == Some(0)
+    }
+
+    /// Returns the canonical form of this version, which compares equal
+    /// to it: without a redundant `0:` epoch, without a revision
+    /// equivalent to none, like `0`, and without leading zeros in
+    /// numeric runs.
+    ///
+    /// Unlike the lossless original form given by `to_string`, all
+    /// versions comparing equal in this way share a canonical form.
+    pub fn canonicalize(&self) -> Version {
+        let mut result = self.clone();
+        if result.has_redundant_epoch() {
+            result.epoch = None;
+        }
+        result.upstream_version.strip_leading_zeros();
+        result.debian_revision.strip_leading_zeros();
+        let empty = VersionPart { elements: vec![] };
+        if result.debian_revision == empty {
+            result.debian_revision = empty;
+        }
+        result
+    }
+
+    /// The canonical form of this version as a string, suitable as a
+    /// key for deduplication.
+    pub fn canonical_string(&self) -> String {
+        self.canonicalize().to_string()
+    }
+
+    /// The fully explicit form of the version, `epoch:upstream-revision`,
+    /// like `{:#}` formats it.
+    pub fn explicit_string(&self) -> String {
+        format!("{self:#}")
+    }
+
+    /// The version without its epoch, as used in file names, like
+    /// `2.0-1` for `1:2.0-1`.
+    pub fn without_epoch(&self) -> String {
+        let mut result = self.clone();
+        result.epoch = None;
+        result.to_string()
+    }
+
+    /// Whether the epoch is non-zero.
+    pub fn has_epoch(&self) -> bool {
+        self.effective_epoch() != 0
+    }
+
+    /// Whether this is the version of a native package, i.e. one
+    /// without a Debian revision.
+    pub fn is_native(&self) -> bool {
+        self.debian_revision.is_empty()
+    }
+
+    /// The upstream version as a string.
+    pub fn upstream(&self) -> String {
+        self.upstream_version.to_string()
+    }
+
+    /// The Debian revision as a string, if any.
+    pub fn revision(&self) -> Option<String> {
+        match self.is_native() {
+            true => None,
+            false => Some(self.debian_revision.to_string()),
+        }
+    }
+
+    /// Returns the version for the next upload of the same upstream
+    /// version, as `dch -i` does: the trailing number of the revision
+    /// gets incremented, like `1ubuntu3` to `1ubuntu4`, or `0.1` to
+    /// `0.2`. A native version gets a revision of `1` appended,
+    /// turning it into a non-native one.
+    pub fn increment_revision(&self) -> Version {
+        let mut result = self.clone();
+        result.debian_revision.increment_last();
+        result
+    }
+
+    /// Derives the version of a backport to the given Debian release,
+    /// given by codename or number, like `1.2-3~bpo12+1`.
+    ///
+    /// If the previous backport of the same version is given, its
+    /// counter gets incremented, yielding `1.2-3~bpo12+2`. Returns
+    /// `None` for unknown release codenames.
+    pub fn backport(
+        &self,
+        release: &str,
+        previous: Option<&Version>,
+    ) -> Option<Version> {
+        let number = debian_release_number(release)?;
+        let prefix = format!("{self}~bpo{number}+");
+        let counter = previous
+            .map(|p| p.to_string())
+            .and_then(|p| {
+                p.strip_prefix(&prefix)?.parse::<u64>().ok()?.checked_add(1)
+            })
+            .unwrap_or(1);
+        Version::parse(&format!("{prefix}{counter}")).ok()
+    }
+
+    /// The part carrying suffixes like `+bN`: the revision, or the
+    /// upstream version of native packages.
+    fn last_part(&self) -> &VersionPart {
+        match self.is_native() {
+            true => &self.upstream_version,
+            false => &self.debian_revision,
+        }
+    }
+
+    fn last_part_mut(&mut self) -> &mut VersionPart {
+        match self.is_native() {
+            true => &mut self.upstream_version,
+            false => &mut self.debian_revision,
+        }
+    }
+
+    /// Returns the version for a non-maintainer upload.
+    ///
+    /// For non-native packages, the revision gets a `.1` appended, or
+    /// its NMU counter incremented, like `3` to `3.1` and `3.1` to `3.2`.
+    /// Native versions get a `+nmu1` suffix, or an incremented one.
+    pub fn nmu(&self) -> Version {
+        let mut result = self.clone();
+        let alpha = match self.is_native() {
+            true => "+nmu",
+            false => ".",
+        };
+        let part = result.last_part_mut();
+        match part.elements.last() {
+            Some(last) if last.alpha == alpha && !last.digits.is_empty() => {
+                part.increment_last()
+            }
+            _ => part.elements.push(VersionElement {
+                alpha: alpha.to_string(),
+                numeric: 1,
+                digits: "1".to_string(),
+            }),
+        }
+        result
+    }
+
+    /// Returns the version of the given binary-only NMU, replacing an
+    /// existing `+bN` suffix, like `1.0-2+b3`.
+    pub fn binnmu(&self, n: u32) -> Version {
+        let mut result = self.clone();
+        let is_binnmu = self.is_binnmu();
+        let part = result.last_part_mut();
+        if is_binnmu {
+            part.elements.pop();
+        }
+        part.elements.push(VersionElement {
+            alpha: "+b".to_string(),
+            numeric: n as u64,
+            digits: n.to_string(),
+        });
+        result
+    }
+
+    /// Whether this is the version of a binary-only NMU, ending in a
+    /// `+bN` suffix.
+    pub fn is_binnmu(&self) -> bool {
+        self.binnmu_number().is_some()
+    }
+
+    /// The number of the binary-only NMU, if this is one.
+    pub fn binnmu_number(&self) -> Option<u32> {
+        match self.last_part().elements.last() {
+            Some(last) if last.alpha == "+b" && !last.digits.is_empty() => {
+                last.digits.parse().ok()
+            }
+            _ => None,
+        }
+    }
+
+    /// The Debian release and update number of a `+debXuY` suffix, as
+    /// used for stable point releases and security updates.
+    pub fn stable_update_suffix(&self) -> Option<(u32, u32)> {
+        match self.last_part().elements.as_slice() {
+            [.., deb, u]
+                if deb.alpha == "+deb"
+                    && u.alpha == "u"
+                    && !deb.digits.is_empty()
+                    && !u.digits.is_empty() =>
+            {
+                Some((deb.digits.parse().ok()?, u.digits.parse().ok()?))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the version for an update to the given stable Debian
+    /// release, like `1.2-3+deb12u1`, with the update number `n`. If
+    /// `n` is None and the version already carries a suffix for that
+    /// release, its counter gets incremented, otherwise it starts at 1.
+    /// A suffix for another release gets replaced.
+    pub fn stable_update(&self, debian_major: u32, n: Option<u32>) -> Version {
+        let mut result = self.clone();
+        let existing = self.stable_update_suffix();
+        let n = match (n, existing) {
+            (Some(n), _) => n,
+            (None, Some((major, u))) if major == debian_major => {
+                u.saturating_add(1)
+            }
+            (None, _) => 1,
+        };
+        let part = result.last_part_mut();
+        if existing.is_some() {
+            part.elements.truncate(part.elements.len() - 2);
+        }
+        part.elements.push(VersionElement {
+            alpha: "+deb".to_string(),
+            numeric: debian_major as u64,
+            digits: debian_major.to_string(),
+        });
+        part.elements.push(VersionElement {
+            alpha: "u".to_string(),
+            numeric: n as u64,
+            digits: n.to_string(),
+        });
+        result
+    }
+
+    /// The upstream version without the suffixes Debian commonly adds
+    /// to it: repacks like `+dfsg1` or `+ds`, pre-releases like `~rc1`
+    /// or `~beta2` and VCS snapshots like `+git20240101.abc123`. For
+    /// versions reverting to an older upstream release, like
+    /// `2.0+really1.9`, this is the release actually packaged.
+    pub fn base_upstream(&self) -> String {
+        let upstream = self.upstream();
+        let mut base = match upstream.rsplit_once("+really") {
+            Some((_, really)) => really,
+            None => &upstream,
+        };
+        // strip the longest suffix first, as it may contain others
+        while let Some(start) = base
+            .char_indices()
+            .find(|&(i, _)| is_upstream_suffix(&base[i..]))
+            .map(|(i, _)| i)
+        {
+            if start == 0 {
+                break;
+            }
+            base = &base[..start];
+        }
+        base.to_string()
+    }
+
+    /// Whether the upstream version is a pre-release, marked by a
+    /// suffix like `~rc1`, `~beta2` or `~alpha`.
+    pub fn is_prerelease(&self) -> bool {
+        self.upstream().split('~').skip(1).any(|s| {
+            strip_keyword(s, PRERELEASE_KEYWORDS).map_or(false, |after| {
+                after.is_empty()
+                    || after.starts_with(['.', '+'])
+                    || after.starts_with(|c: char| c.is_ascii_digit())
+            })
+        })
+    }
+
+    /// Whether the upstream version is a VCS snapshot, like
+    /// `1.0+git20240101.abc123` or `1.0~20240101`.
+    pub fn is_snapshot(&self) -> bool {
+        self.upstream().split(['+', '~']).skip(1).any(|s| {
+            strip_keyword(s, SNAPSHOT_KEYWORDS).is_some() || starts_with_date(s)
+        })
+    }
+
+    /// Guesses upstream versions likely to follow this one, for
+    /// packages without a watch file, in ascending order.
+    ///
+    /// For a pre-release like `2.0~rc1` these are the next pre-release
+    /// `2.0~rc2` and the final release `2.0`. Otherwise, each numeric
+    /// component of the base upstream version gets bumped in turn, with
+    /// the following ones reset to zero, like `1.2.4`, `1.3.0` and
+    /// `2.0.0` for `1.2.3`, with the base itself leading for snapshots
+    /// preceding it, like `1.0~git20240101`. The epoch is kept, the
+    /// revision dropped.
+    pub fn next_upstream_candidates(&self) -> Vec<Version> {
+        let upstream = |s: &str| Version {
+            epoch: self.epoch,
+            upstream_version: parse_elements(s),
+            debian_revision: VersionPart { elements: vec![] },
+        };
+        let current = upstream(&self.upstream());
+        let base = upstream(&self.base_upstream());
+        let mut result = vec![base.clone()];
+        if self.is_prerelease() {
+            let mut next = current.clone();
+            let prerelease = |alpha: &str| {
+                alpha
+                    .strip_prefix('~')
+                    .and_then(|s| strip_keyword(s, PRERELEASE_KEYWORDS))
+                    .map_or(false, |after| after.is_empty() || after == ".")
+            };
+            match next.upstream_version.elements.last() {
+                Some(last)
+                    if prerelease(&last.alpha) && !last.digits.is_empty() =>
+                {
+                    next.upstream_version.increment_last();
+                    result.push(next);
+                }
+                _ => {}
+            }
+        } else {
+            let elements = &base.upstream_version.elements;
+            for (i, e) in elements.iter().enumerate().rev() {
+                if e.digits.is_empty() {
+                    continue;
+                }
+                let mut next = base.clone();
+                let part = &mut next.upstream_version;
+                part.elements.truncate(i + 1);
+                part.increment_last();
+                part.elements.extend(
+                    elements[i + 1..]
+                        .iter()
+                        .take_while(|e| e.alpha == "." && !e.digits.is_empty())
+                        .map(|e| VersionElement {
+                            alpha: e.alpha.clone(),
+                            numeric: 0,
+                            digits: "0".to_string(),
+                        }),
+                );
+                result.push(next);
+            }
+        }
+        result.retain(|v| v.upstream_version > current.upstream_version);
+        result.sort();
+        result.dedup();
+        result
+    }
+
+    /// The number of the repack of the upstream sources, like `2` for
+    /// `1.0+dfsg2` or `1.0+ds.2`. An unnumbered `+dfsg` or `+ds` yields
+    /// `0`, a version without such a suffix `None`.
+    pub fn dfsg_revision(&self) -> Option<u32> {
+        let upstream = self.upstream();
+        // the digits of a suffix, if it is followed by the end or a
+        // separator
+        let digits = |s: &str| {
+            let len = s.bytes().take_while(|b| b.is_ascii_digit()).count();
+            match s[len..].starts_with(|c: char| c.is_ascii_alphanumeric()) {
+                true => None,
+                false => Some(s[..len].to_string()),
+            }
+        };
+        let revision = upstream
+            .match_indices(['+', '~', '.'])
+            .filter_map(|(i, _)| {
+                let after = strip_keyword(&upstream[i + 1..], &["dfsg", "ds"])?;
+                match after.strip_prefix('.') {
+                    Some(counter) => digits(counter).or_else(|| digits(after)),
+                    None => digits(after),
+                }
+            })
+            .next_back()?;
+        match revision.as_str() {
+            "" => Some(0),
+            n => n.parse().ok(),
+        }
+    }
+
+    /// Check whether this version satisfies the relation to another
+    /// one, like an installed version meeting a dependency's constraint.
+    pub fn satisfies(&self, rel: VRel, other: &Version) -> bool {
+        rel.is_satisfied_by(self, other)
+    }
+
+    /// Parses a version, strictly validating its syntax as defined by
+    /// Debian Policy §5.6.12.
+    ///
+    /// The epoch has to be numeric, the upstream version has to start
+    /// with a digit and may only contain alphanumerics and `.+-~`, the
+    /// revision only alphanumerics and `.+~`. A colon is only allowed
+    /// to separate the epoch. Errors carry the byte position of the
+    /// offending character.
+    pub fn parse_strict(s: &str) -> Result<Version, ParseError> {
+        let err = |pos: usize, msg: &str| ParseError {
+            pos: pos as i32,
+            msg: msg.to_string(),
+        };
+        if s.is_empty() {
+            return Err(err(0, "Empty version string."));
+        }
+        let upstream_start = match s.find(':') {
+            Some(0) => return Err(err(0, "Empty epoch.")),
+            Some(l) => l + 1,
+            None => 0,
+        };
+        let epoch = &s[..upstream_start.saturating_sub(1)];
+        if let Some(pos) = epoch.find(|c: char| !c.is_ascii_digit()) {
+            return Err(err(pos, "Expected a numeric epoch."));
+        }
+        if epoch.len() > 1 && epoch.starts_with('0') {
+            return Err(err(0, "Epoch with leading zeros."));
+        }
+        // the epoch is numeric, so the last hyphen follows it
+        let upstream_end = s.rfind('-').unwrap_or(s.len());
+        let upstream = &s[upstream_start..upstream_end];
+        match upstream.chars().next() {
+            None => return Err(err(upstream_start, "Empty upstream version.")),
+            Some(c) if !c.is_ascii_digit() => {
+                return Err(err(
+                    upstream_start,
+                    "Upstream version must start with a digit.",
+                ))
+            }
+            Some(_) => {}
+        }
+        let allowed = |c: char| c.is_ascii_alphanumeric() || ".+~".contains(c);
+        for (i, c) in upstream.char_indices() {
+            if c == ':' {
+                return Err(err(
+                    upstream_start + i,
+                    "Colon is only allowed after the epoch.",
+                ));
+            }
+            if !allowed(c) && c != '-' {
+                return Err(err(
+                    upstream_start + i,
+                    "Invalid character in upstream version.",
+                ));
+            }
+        }
+        if upstream_end < s.len() {
+            let revision = &s[upstream_end + 1..];
+            if revision.is_empty() {
+                return Err(err(upstream_end, "Empty Debian revision."));
+            }
+            for (i, c) in revision.char_indices() {
+                if !allowed(c) {
+                    return Err(err(
+                        upstream_end + 1 + i,
+                        "Invalid character in Debian revision.",
+                    ));
+                }
+            }
+        }
+        Version::parse(s)
+    }
+}
+
+/// Checks whether a string is a valid version, as `parse_strict` would
+/// accept it. Being a `const fn`, it can validate versions at compile
+/// time, as the `version!` macro does.
+pub const fn is_valid_version(s: &str) -> bool {
+    let b = s.as_bytes();
+    let (mut colon, mut hyphen) = (b.len(), b.len());
+    let mut i = 0;
+    while i < b.len() {
+        if b[i] == b':' && colon == b.len() {
+            colon = i;
+        }
+        if b[i] == b'-' {
+            hyphen = i;
+        }
+        i += 1;
+    }
+    let mut upstream_start = 0;
+    if colon < b.len() {
+        if colon == 0 || (colon > 1 && b[0] == b'0') {
+            return false;
+        }
+        let mut epoch: u64 = 0;
+        while upstream_start < colon {
+            let c = b[upstream_start];
+            if !c.is_ascii_digit() {
+                return false;
+            }
+            epoch = epoch * 10 + (c - b'0') as u64;
+            if epoch > u32::MAX as u64 {
+                return false;
+            }
+            upstream_start += 1;
+        }
+        upstream_start += 1;
+    }
+    if upstream_start >= hyphen || !b[upstream_start].is_ascii_digit() {
+        return false;
+    }
+    if hyphen + 1 == b.len() {
+        return false;
+    }
+    let mut i = upstream_start;
+    while i < b.len() {
+        let c = b[i];
+        // any hyphen belongs to the upstream version or separates it
+        if !(c.is_ascii_alphanumeric()
+            || c == b'.'
+            || c == b'+'
+            || c == b'~'
+            || c == b'-')
+        {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Parses a version literal, checking its syntax at compile time
+/// instead of failing at runtime.
+///
+/// ```
+/// use debian::version;
+///
+/// let v = version!("1:2.3-4");
+/// assert_eq!(v.upstream(), "2.3");
+/// ```
+///
+/// ```compile_fail
+/// let v = debian::version!("1.0:2");
+/// ```
+#[macro_export]
+macro_rules! version {
+    ($s:expr) => {{
+        const _: () = assert!(
+            $crate::version::is_valid_version($s),
+            "invalid Debian version"
+        );
+        $crate::version::Version::parse_strict($s)
+            .expect("version checked at compile time")
+    }};
+}
+
+/// Converts a semver version into a Debian upstream version following
+/// the debcargo conventions: a pre-release gets separated by a tilde,
+/// so it sorts before the release, with hyphens in it replaced by dots.
+/// Build metadata is dropped, as it does not affect precedence.
+#[cfg(feature = "semver")]
+impl From<semver::Version> for Version {
+    fn from(v: semver::Version) -> Version {
+        let mut s = format!("{}.{}.{}", v.major, v.minor, v.patch);
+        if !v.pre.is_empty() {
+            s.push('~');
+            s.push_str(&v.pre.as_str().replace('-', "."));
+        }
+        Version {
+            epoch: None,
+            upstream_version: parse_elements(&s),
+            debian_revision: VersionPart { elements: vec![] },
+        }
+    }
+}
+
+/// Converts the upstream part of a Debian version back into a semver
+/// version, ignoring epoch and revision. A tilde introduces the
+/// pre-release and a plus sign the build metadata, like for
+/// `1.2.3~rc.1+dfsg`.
+#[cfg(feature = "semver")]
+impl TryFrom<&Version> for semver::Version {
+    type Error = ParseError;
+
+    fn try_from(v: &Version) -> Result<semver::Version, ParseError> {
+        let upstream = v.upstream();
+        let (rest, build) = match upstream.split_once('+') {
+            Some((r, b)) => (r, Some(b)),
+            None => (upstream.as_str(), None),
+        };
+        let mut s = rest.replacen('~', "-", 1);
+        if let Some(b) = build {
+            s.push('+');
+            s.push_str(b);
+        }
+        semver::Version::parse(&s).map_err(|e| ParseError {
+            pos: 0,
+            msg: e.to_string(),
+        })
+    }
+}
+
+/// A bound of a `VersionConstraint`, flagged as inclusive or not.
+type Bound = Option<(Version, bool)>;
+
+/// A set of versions defined by a lower and an upper bound, resulting
+/// from one or more version relations, like `>= 1.0, << 2.0`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct VersionConstraint {
+    lower: Bound,
+    upper: Bound,
+}
+
+impl VersionConstraint {
+    /// The constraint satisfied by any version.
+    pub fn any() -> VersionConstraint {
+        VersionConstraint::default()
+    }
+
+    /// The constraint given by a single relation.
+    pub fn from_relation(rel: VRel, version: &Version) -> VersionConstraint {
+        let mut result = VersionConstraint::any();
+        result.add(rel, version);
+        result
+    }
+
+    /// Narrow the constraint by another relation.
+    pub fn add(&mut self, rel: VRel, version: &Version) {
+        let v = Some((version.clone(), true));
+        let exclusive = Some((version.clone(), false));
+        match rel {
+            VRel::GreaterOrEqual => self.tighten_lower(v),
+            VRel::Greater => self.tighten_lower(exclusive),
+            VRel::LesserOrEqual => self.tighten_upper(v),
+            VRel::Lesser => self.tighten_upper(exclusive),
+            VRel::Equal => {
+                self.tighten_lower(v.clone());
+                self.tighten_upper(v);
+            }
+        }
+    }
+
+    fn tighten_lower(&mut self, bound: Bound) {
+        self.lower = match (self.lower.take(), bound) {
+            (Some((a, ai)), Some((b, bi))) => Some(match a.cmp(&b) {
+                Ordering::Greater => (a, ai),
+                Ordering::Less => (b, bi),
+                Ordering::Equal => (a, ai && bi),
+            }),
+            (a, b) => a.or(b),
+        };
+    }
+
+    fn tighten_upper(&mut self, bound: Bound) {
+        self.upper = match (self.upper.take(), bound) {
+            (Some((a, ai)), Some((b, bi))) => Some(match a.cmp(&b) {
+                Ordering::Less => (a, ai),
+                Ordering::Greater => (b, bi),
+                Ordering::Equal => (a, ai && bi),
+            }),
+            (a, b) => a.or(b),
+        };
+    }
+
+    /// Check whether a version satisfies the constraint.
+    pub fn contains(&self, version: &Version) -> bool {
+        let above = match self.lower {
+            Some((ref l, true)) => version >= l,
+            Some((ref l, false)) => version > l,
+            None => true,
+        };
+        let below = match self.upper {
+            Some((ref u, true)) => version <= u,
+            Some((ref u, false)) => version < u,
+            None => true,
+        };
+        above && below
+    }
+
+    /// The constraint satisfied by versions satisfying both.
+    pub fn intersection(&self, other: &VersionConstraint) -> VersionConstraint {
+        let mut result = self.clone();
+        result.tighten_lower(other.lower.clone());
+        result.tighten_upper(other.upper.clone());
+        result
+    }
+
+    /// Whether no version can satisfy the constraint, like for
+    /// `>> 2.0, << 1.0` or `>= 1.0, << 1.0`.
+    pub fn is_empty(&self) -> bool {
+        match (&self.lower, &self.upper) {
+            (Some((l, li)), Some((u, ui))) => match l.cmp(u) {
+                Ordering::Greater => true,
+                Ordering::Equal => !(*li && *ui),
+                Ordering::Less => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Whether the constraint is satisfied by any version.
+    pub fn is_any(&self) -> bool {
+        self.lower.is_none() && self.upper.is_none()
+    }
+
+    /// The relations making up the constraint, from which it can be
+    /// rebuilt.
+    pub fn relations(&self) -> Vec<(VRel, Version)> {
+        match (&self.lower, &self.upper) {
+            (Some((l, true)), Some((u, true))) if l == u => {
+                return vec![(VRel::Equal, l.clone())]
+            }
+            _ => {}
+        }
+        let mut result = vec![];
+        match self.lower {
+            Some((ref l, true)) => {
+                result.push((VRel::GreaterOrEqual, l.clone()))
+            }
+            Some((ref l, false)) => result.push((VRel::Greater, l.clone())),
+            None => {}
+        }
+        match self.upper {
+            Some((ref u, true)) => {
+                result.push((VRel::LesserOrEqual, u.clone()))
+            }
+            Some((ref u, false)) => result.push((VRel::Lesser, u.clone())),
+            None => {}
+        }
+        result
+    }
+}
+
+impl fmt::Display for VersionConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = self
+            .relations()
+            .iter()
+            .map(|(rel, v)| format!("{rel} {v}"))
+            .collect::<Vec<String>>()
+            .join(", ");
+        write!(f, "{s}")
+    }
+}
+
 impl FromStr for Version {
     type Err = ParseError;
 
@@ -175,7 +1584,7 @@ impl FromStr for Version {
 
 impl Ord for Version {
     fn cmp(&self, other: &Version) -> Ordering {
-        match self.epoch.cmp(&other.epoch) {
+        match self.effective_epoch().cmp(&other.effective_epoch()) {
             Ordering::Equal => {}
             ord => return ord,
         }
@@ -193,20 +1602,68 @@ impl PartialOrd for Version {
     }
 }
 
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+/// Compares against a version string, which is never equal to an
+/// invalid one.
+impl PartialEq<str> for Version {
+    fn eq(&self, other: &str) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialEq<&str> for Version {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+/// Compares against a version string, yielding `None` if that is not
+/// a valid version.
+impl PartialOrd<str> for Version {
+    fn partial_cmp(&self, other: &str) -> Option<Ordering> {
+        Version::parse_strict(other).ok().map(|v| self.cmp(&v))
+    }
+}
+
+impl PartialOrd<&str> for Version {
+    fn partial_cmp(&self, other: &&str) -> Option<Ordering> {
+        self.partial_cmp(*other)
+    }
+}
+
+/// The alternate form `{:#}` always includes the epoch and the
+/// revision separator, like `0:1.0-` for `1.0`, as stored by archive
+/// databases. It parses back to an equal version.
 impl fmt::Display for Version {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match (self.epoch, self.debian_revision.count_elements()) {
-            (0, 0) => write!(f, "{}", &self.upstream_version),
-            (0, _) => write!(
+        if f.alternate() {
+            return write!(
+                f,
+                "{}:{}-{}",
+                self.effective_epoch(),
+                &self.upstream_version,
+                &self.debian_revision
+            );
+        }
+        match (self.epoch, self.debian_revision.len()) {
+            (None, 0) => write!(f, "{}", &self.upstream_version),
+            (None, _) => write!(
                 f,
                 "{}-{}",
                 &self.upstream_version, &self.debian_revision
             ),
-            (_, 0) => write!(f, "{}:{}", self.epoch, &self.upstream_version),
-            (_, _) => write!(
+            (Some(e), 0) => write!(f, "{}:{}", e, &self.upstream_version),
+            (Some(e), _) => write!(
                 f,
                 "{}:{}-{}",
-                self.epoch, &self.upstream_version, &self.debian_revision
+                e, &self.upstream_version, &self.debian_revision
             ),
         }
     }
@@ -0,0 +1,619 @@
+//! Parser and writer for debian/watch files
+//!
+//! Watch files tell uscan where to look for new upstream releases.
+//! This module supports the line-based format of version 4 as well as
+//! the deb822 based format of version 5, including the options used to
+//! mangle upstream versions and file names.
+//!
+//! # Examples
+//!
+//! ```
+//! use debian::watch::WatchFile;
+//!
+//! let watch: WatchFile = "version=4\n\
+//!     opts=uversionmangle=s/-rc/~rc/ \\\n\
+//!     https://example.org/files/ foo-(\\d\\S+)\\.tar\\.gz\n"
+//!     .parse()
+//!     .unwrap();
+//! let entry = &watch.entries[0];
+//! assert_eq!(entry.matching_pattern, r"foo-(\d\S+)\.tar\.gz");
+//! assert_eq!(entry.mangle_upstream_version("1.0-rc1").unwrap(), "1.0~rc1");
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use regex::Regex;
+
+use crate::package::{ControlFile, ControlParagraph};
+
+/// Option names in their version 4 spelling and the corresponding
+/// version 5 field names.
+const OPTION_FIELDS: &[(&str, &str)] = &[
+    ("component", "Component"),
+    ("compression", "Compression"),
+    ("ctype", "Ctype"),
+    ("date", "Date"),
+    ("decompress", "Decompress"),
+    ("bare", "Bare"),
+    ("dirversionmangle", "Dirversion-Mangle"),
+    ("downloadurlmangle", "Downloadurl-Mangle"),
+    ("dversionmangle", "Dversion-Mangle"),
+    ("filenamemangle", "Filename-Mangle"),
+    ("gitexport", "Git-Export"),
+    ("gitmode", "Git-Mode"),
+    ("mode", "Mode"),
+    ("oversionmangle", "Oversion-Mangle"),
+    ("pagemangle", "Page-Mangle"),
+    ("passive", "Passive"),
+    ("pgpmode", "Pgp-Mode"),
+    ("pgpsigurlmangle", "Pgpsigurl-Mangle"),
+    ("pretty", "Pretty"),
+    ("repack", "Repack"),
+    ("repacksuffix", "Repack-Suffix"),
+    ("searchmode", "Search-Mode"),
+    ("unzipopt", "Unzip-Opt"),
+    ("user-agent", "User-Agent"),
+    ("uversionmangle", "Uversion-Mangle"),
+    ("versionmangle", "Version-Mangle"),
+];
+
+/// A single rule of a mangling option, like `s/-rc/~rc/g`.
+#[derive(Debug, Clone)]
+pub enum MangleRule {
+    /// A regular expression substitution, `s/regex/replacement/flags`.
+    Substitute {
+        regex: Regex,
+        replacement: String,
+        global: bool,
+    },
+    /// A character transliteration, `tr/from/to/` or `y/from/to/`.
+    Transliterate { from: Vec<char>, to: Vec<char> },
+}
+
+/// A single entry of a watch file.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct WatchEntry {
+    /// options in their version 4 spelling, like `uversionmangle`,
+    /// with `None` for flags without a value
+    pub options: Vec<(String, Option<String>)>,
+    /// URL of the page or directory listing to check
+    pub url: String,
+    /// regular expression matching the upstream files
+    pub matching_pattern: String,
+    /// the version policy, like `debian` or `same`, if given
+    pub version_policy: Option<String>,
+    /// the script to run after downloading, if given
+    pub script: Option<String>,
+    /// the matching pattern is part of the URL, as in
+    /// `https://example.org/foo-(\d+)\.tar\.gz` (version 4 only)
+    pub pattern_in_url: bool,
+}
+
+/// A complete watch file.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct WatchFile {
+    /// version of the watch file format
+    pub version: u32,
+    pub entries: Vec<WatchEntry>,
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(
+    e: E,
+) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// Converts a version 5 field name to the version 4 option name.
+fn option_from_field(field: &str) -> String {
+    for (name, f) in OPTION_FIELDS {
+        if f.eq_ignore_ascii_case(field) {
+            return name.to_string();
+        }
+    }
+    field.to_lowercase().replace('-', "")
+}
+
+/// Converts a version 4 option name to the version 5 field name.
+fn option_to_field(name: &str) -> String {
+    for (n, f) in OPTION_FIELDS {
+        if *n == name {
+            return f.to_string();
+        }
+    }
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) => c.to_uppercase().chain(chars).collect(),
+        None => "".to_string(),
+    }
+}
+
+/// Splits a Perl style expression like `s/a/b/g` into its parts,
+/// honouring backslash escapes of the delimiter.
+fn split_perl_expr(s: &str) -> Result<(&str, Vec<String>), &'static str> {
+    let (op, rest) = if let Some(r) = s.strip_prefix("tr") {
+        ("tr", r)
+    } else if let Some(r) = s.strip_prefix('s') {
+        ("s", r)
+    } else if let Some(r) = s.strip_prefix('y') {
+        ("y", r)
+    } else {
+        return Err("unknown mangle rule");
+    };
+    let mut chars = rest.chars();
+    let delim = match chars.next() {
+        Some(d) => d,
+        None => return Err("incomplete mangle rule"),
+    };
+    let mut parts = vec![String::new()];
+    let mut escaped = false;
+    for c in chars {
+        if escaped {
+            if c != delim {
+                parts.last_mut().unwrap().push('\\');
+            }
+            parts.last_mut().unwrap().push(c);
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == delim {
+            parts.push(String::new());
+        } else {
+            parts.last_mut().unwrap().push(c);
+        }
+    }
+    if parts.len() != 3 {
+        return Err("incomplete mangle rule");
+    }
+    Ok((op, parts))
+}
+
+/// Converts a Perl replacement string (using `$1` or `\1`) to the
+/// syntax of the regex crate.
+fn convert_replacement(s: &str) -> String {
+    let mut result = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '$' | '\\'
+                if chars.peek().map_or(false, |n| n.is_ascii_digit()) =>
+            {
+                let mut n = String::new();
+                while let Some(d) = chars.peek().filter(|d| d.is_ascii_digit())
+                {
+                    n.push(*d);
+                    chars.next();
+                }
+                result += &format!("${{{n}}}");
+            }
+            '$' if chars.peek() == Some(&'{') => result.push('$'),
+            '$' => result += "$$",
+            '\\' => {
+                if let Some(n) = chars.next() {
+                    result.push(n);
+                }
+            }
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// Expands ranges like `a-z` in a transliteration list.
+fn expand_tr(s: &str) -> Vec<char> {
+    let chars = s.chars().collect::<Vec<char>>();
+    let mut result = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        if i + 2 < chars.len() && chars[i + 1] == '-' {
+            result.extend(chars[i]..=chars[i + 2]);
+            i += 3;
+        } else {
+            result.push(chars[i]);
+            i += 1;
+        }
+    }
+    result
+}
+
+impl MangleRule {
+    /// Parses a list of rules, separated by semicolons.
+    pub fn parse_list(s: &str) -> Result<Vec<MangleRule>, &'static str> {
+        let mut rules = vec![];
+        let mut current = String::new();
+        let mut escaped = false;
+        for c in s.chars() {
+            if c == ';' && !escaped {
+                rules.push(current.trim().parse()?);
+                current = String::new();
+                continue;
+            }
+            escaped = c == '\\' && !escaped;
+            current.push(c);
+        }
+        if !current.trim().is_empty() {
+            rules.push(current.trim().parse()?);
+        }
+        Ok(rules)
+    }
+
+    /// Applies this rule to a string.
+    pub fn apply(&self, s: &str) -> String {
+        match *self {
+            MangleRule::Substitute {
+                ref regex,
+                ref replacement,
+                global,
+            } => match global {
+                true => regex.replace_all(s, replacement.as_str()),
+                false => regex.replace(s, replacement.as_str()),
+            }
+            .to_string(),
+            MangleRule::Transliterate { ref from, ref to } => s
+                .chars()
+                .map(|c| match from.iter().position(|&f| f == c) {
+                    Some(i) => *to.get(i).or(to.last()).unwrap_or(&c),
+                    None => c,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl FromStr for MangleRule {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (op, parts) = split_perl_expr(s)?;
+        match op {
+            "s" => {
+                let mut pattern = String::new();
+                for flag in parts[2].chars() {
+                    match flag {
+                        'g' => {}
+                        'i' | 'x' | 's' | 'm' => {
+                            pattern += &format!("(?{flag})");
+                        }
+                        _ => return Err("unsupported regex flag"),
+                    }
+                }
+                pattern += &parts[0];
+                match Regex::new(&pattern) {
+                    Ok(regex) => Ok(MangleRule::Substitute {
+                        regex,
+                        replacement: convert_replacement(&parts[1]),
+                        global: parts[2].contains('g'),
+                    }),
+                    Err(_) => Err("invalid regular expression"),
+                }
+            }
+            _ => Ok(MangleRule::Transliterate {
+                from: expand_tr(&parts[0]),
+                to: expand_tr(&parts[1]),
+            }),
+        }
+    }
+}
+
+/// Splits the value of an `opts=` field into single options.
+///
+/// Commas separate options, but may also appear within mangle rules,
+/// so a piece only starts a new option if it looks like one.
+fn split_options(s: &str) -> Vec<(String, Option<String>)> {
+    let mut result: Vec<(String, Option<String>)> = vec![];
+    for piece in s.split(',') {
+        let trimmed = piece.trim();
+        let name = trimmed.split('=').next().unwrap_or("");
+        let looks_like_option = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        match result.last_mut() {
+            Some((_, Some(ref mut v))) if !looks_like_option => {
+                v.push(',');
+                v.push_str(piece);
+            }
+            _ => match trimmed.split_once('=') {
+                Some((k, v)) => {
+                    result.push((k.to_string(), Some(v.to_string())))
+                }
+                None if !trimmed.is_empty() => {
+                    result.push((trimmed.to_string(), None))
+                }
+                None => {}
+            },
+        }
+    }
+    result
+}
+
+impl WatchEntry {
+    /// Get the value of an option, given its version 4 name.
+    pub fn get_option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_deref().unwrap_or(""))
+    }
+
+    /// Check whether an option or flag is present.
+    pub fn has_option(&self, name: &str) -> bool {
+        self.options.iter().any(|(k, _)| k == name)
+    }
+
+    /// The parsed rules of a mangling option, like `uversionmangle`.
+    pub fn mangle_rules(
+        &self,
+        name: &str,
+    ) -> Result<Vec<MangleRule>, &'static str> {
+        match self.get_option(name) {
+            Some(v) => MangleRule::parse_list(v),
+            None => Ok(vec![]),
+        }
+    }
+
+    fn mangle(&self, name: &str, s: &str) -> Result<String, &'static str> {
+        let mut result = s.to_string();
+        for rule in self.mangle_rules(name)? {
+            result = rule.apply(&result);
+        }
+        Ok(result)
+    }
+
+    /// Applies `uversionmangle` to an upstream version, falling back to
+    /// the generic `versionmangle`.
+    pub fn mangle_upstream_version(
+        &self,
+        version: &str,
+    ) -> Result<String, &'static str> {
+        match self.has_option("uversionmangle") {
+            true => self.mangle("uversionmangle", version),
+            false => self.mangle("versionmangle", version),
+        }
+    }
+
+    /// Applies `dversionmangle` to a Debian upstream version, falling
+    /// back to the generic `versionmangle`.
+    pub fn mangle_debian_version(
+        &self,
+        version: &str,
+    ) -> Result<String, &'static str> {
+        match self.has_option("dversionmangle") {
+            true => self.mangle("dversionmangle", version),
+            false => self.mangle("versionmangle", version),
+        }
+    }
+
+    /// Applies `filenamemangle` to the URL of a downloaded file.
+    pub fn mangle_filename(&self, url: &str) -> Result<String, &'static str> {
+        self.mangle("filenamemangle", url)
+    }
+
+    /// The component name, for additional upstream tarballs.
+    pub fn component(&self) -> Option<&str> {
+        self.get_option("component")
+    }
+
+    /// Parses a single logical line of a version 4 watch file.
+    pub fn parse_line(line: &str) -> Result<WatchEntry, &'static str> {
+        let mut entry = WatchEntry::default();
+        let mut rest = line.trim();
+        if let Some(r) = rest.strip_prefix("opts=") {
+            let (opts, r) = match r.strip_prefix('"') {
+                Some(r) => match r.split_once('"') {
+                    Some(x) => x,
+                    None => return Err("unterminated opts"),
+                },
+                None => r.split_once(char::is_whitespace).unwrap_or((r, "")),
+            };
+            entry.options = split_options(opts);
+            rest = r.trim_start();
+        }
+
+        let mut words = rest.split_whitespace();
+        let url = match words.next() {
+            Some(u) => u,
+            None => return Err("missing URL"),
+        };
+        let (dir, last) = url.rsplit_once('/').unwrap_or(("", url));
+        if last.contains('(') {
+            entry.url = dir.to_string() + "/";
+            entry.matching_pattern = last.to_string();
+            entry.pattern_in_url = true;
+        } else {
+            entry.url = url.to_string();
+            match words.next() {
+                Some(p) => entry.matching_pattern = p.to_string(),
+                None => return Err("missing matching pattern"),
+            }
+        }
+        entry.version_policy = words.next().map(|x| x.to_string());
+        entry.script = words.next().map(|x| x.to_string());
+        match words.next() {
+            Some(_) => Err("garbage after script"),
+            None => Ok(entry),
+        }
+    }
+
+    /// Creates a `WatchEntry` from a paragraph of a version 5 watch
+    /// file.
+    pub fn from_paragraph(
+        para: &ControlParagraph,
+    ) -> Result<WatchEntry, &'static str> {
+        let mut entry = WatchEntry::default();
+        for e in para.entries() {
+            let v = e.value().as_str().to_string();
+            match e.key() {
+                "Source" => entry.url = v,
+                "Matching-Pattern" => entry.matching_pattern = v,
+                "Version-Policy" | "Version-Schema" => {
+                    entry.version_policy = Some(v)
+                }
+                "Update-Script" | "Script" => entry.script = Some(v),
+                key => {
+                    let value = if v == "yes" { None } else { Some(v) };
+                    entry.options.push((option_from_field(key), value));
+                }
+            }
+        }
+        if entry.url.is_empty() {
+            Err("missing Source field")
+        } else if entry.matching_pattern.is_empty() {
+            Err("missing Matching-Pattern field")
+        } else {
+            Ok(entry)
+        }
+    }
+
+    /// Converts this entry to a paragraph of a version 5 watch file.
+    pub fn to_paragraph(&self) -> ControlParagraph {
+        let mut para = ControlParagraph::default();
+        para.add_entry("Source", self.url.clone());
+        para.add_entry("Matching-Pattern", self.matching_pattern.clone());
+        if let Some(ref v) = self.version_policy {
+            para.add_entry("Version-Policy", v.clone());
+        }
+        if let Some(ref s) = self.script {
+            para.add_entry("Update-Script", s.clone());
+        }
+        for (k, v) in &self.options {
+            let value = v.clone().unwrap_or_else(|| "yes".to_string());
+            para.add_entry(&option_to_field(k), value);
+        }
+        para
+    }
+
+    /// Converts this entry to a line of a version 4 watch file.
+    pub fn to_line(&self) -> String {
+        let mut line = String::new();
+        if !self.options.is_empty() {
+            let opts = self
+                .options
+                .iter()
+                .map(|(k, v)| match v {
+                    Some(v) => format!("{k}={v}"),
+                    None => k.clone(),
+                })
+                .collect::<Vec<String>>()
+                .join(",");
+            line += &format!("opts=\"{opts}\" \\\n  ");
+        }
+        match self.pattern_in_url {
+            true => line += &format!("{}{}", self.url, self.matching_pattern),
+            false => line += &format!("{} {}", self.url, self.matching_pattern),
+        }
+        for extra in [&self.version_policy, &self.script].into_iter().flatten()
+        {
+            line += " ";
+            line += extra;
+        }
+        line
+    }
+}
+
+impl WatchFile {
+    /// Reads and parses a watch file from disk.
+    pub fn from_file(in_file: &Path) -> io::Result<WatchFile> {
+        fs::read_to_string(in_file)?.parse()
+    }
+
+    /// Parses a version 4 (or older) line based watch file.
+    fn parse_lines(s: &str) -> Result<WatchFile, &'static str> {
+        let mut result = WatchFile::default();
+        let mut logical = String::new();
+        for line in s.lines() {
+            let trimmed = line.trim();
+            if trimmed.starts_with('#')
+                || (trimmed.is_empty() && logical.is_empty())
+            {
+                continue;
+            }
+            match trimmed.strip_suffix('\\') {
+                Some(l) => {
+                    logical += l.trim_start();
+                    continue;
+                }
+                None => logical += trimmed,
+            }
+            let current = std::mem::take(&mut logical);
+            if current.is_empty() {
+                continue;
+            }
+            if result.version == 0 {
+                match current.strip_prefix("version=") {
+                    Some(v) => match u32::from_str(v.trim()) {
+                        Ok(v) => result.version = v,
+                        Err(_) => return Err("invalid version line"),
+                    },
+                    None => return Err("missing version line"),
+                }
+                continue;
+            }
+            result.entries.push(WatchEntry::parse_line(&current)?);
+        }
+        Ok(result)
+    }
+
+    /// Parses a version 5 deb822 based watch file.
+    fn parse_deb822(s: &str) -> io::Result<WatchFile> {
+        let cf = ControlFile::from_reader(s.as_bytes())?;
+        let mut paragraphs = cf.get_paragraphs().iter();
+        let version =
+            match paragraphs.next().and_then(|p| p.get_entry("Version")) {
+                Some(v) => u32::from_str(v).map_err(invalid_data)?,
+                None => return Err(invalid_data("missing Version field")),
+            };
+        let mut entries = vec![];
+        for para in paragraphs {
+            entries
+                .push(WatchEntry::from_paragraph(para).map_err(invalid_data)?);
+        }
+        Ok(WatchFile { version, entries })
+    }
+
+    /// Serializes this watch file to disk.
+    pub fn to_file(&self, out_file: &Path) -> io::Result<()> {
+        fs::write(out_file, self.to_string())
+    }
+}
+
+impl FromStr for WatchFile {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let first = s
+            .lines()
+            .map(|l| l.trim())
+            .find(|l| !l.is_empty() && !l.starts_with('#'))
+            .unwrap_or("");
+        if first.starts_with("Version:") {
+            WatchFile::parse_deb822(s)
+        } else {
+            WatchFile::parse_lines(s).map_err(invalid_data)
+        }
+    }
+}
+
+impl fmt::Display for WatchFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.version >= 5 {
+            let mut cf = ControlFile::default();
+            let mut header = ControlParagraph::default();
+            header.add_entry("Version", self.version.to_string());
+            cf.add_paragraph(header);
+            for entry in &self.entries {
+                cf.add_paragraph(entry.to_paragraph());
+            }
+            write!(f, "{cf}")
+        } else {
+            writeln!(f, "version={}", self.version)?;
+            for entry in &self.entries {
+                writeln!(f, "{}", entry.to_line())?;
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,36 @@
+// These tests set SOURCE_DATE_EPOCH for the whole process, so they are
+// kept apart from the other tests, which run concurrently and read it.
+extern crate debian;
+
+use debian::archive::release::ReleaseBuilder;
+use debian::buildopts::source_date_epoch;
+#[cfg(feature = "deb")]
+use debian::deb::DebBuilder;
+#[cfg(feature = "deb")]
+use debian::package::ControlParagraph;
+
+#[test]
+fn source_date_epoch_invalid() {
+    std::env::set_var("SOURCE_DATE_EPOCH", "yesterday");
+    let err = source_date_epoch().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(err.to_string(), "invalid SOURCE_DATE_EPOCH 'yesterday'");
+    assert!(ReleaseBuilder::new().is_err());
+    #[cfg(feature = "deb")]
+    assert!(DebBuilder::new(ControlParagraph::default()).is_err());
+
+    std::env::set_var("SOURCE_DATE_EPOCH", "1700000000");
+    assert_eq!(source_date_epoch().unwrap(), Some(1700000000));
+    let mut release = ReleaseBuilder::new().unwrap();
+    release.set_suite("stable");
+    let mut text = vec![];
+    release.write(&mut text).unwrap();
+    assert!(String::from_utf8(text)
+        .unwrap()
+        .contains("Date: Tue, 14 Nov 2023 22:13:20 UTC\n"));
+    #[cfg(feature = "deb")]
+    assert!(DebBuilder::new(ControlParagraph::default()).is_ok());
+
+    std::env::remove_var("SOURCE_DATE_EPOCH");
+    assert_eq!(source_date_epoch().unwrap(), None);
+}
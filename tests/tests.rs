@@ -1,16 +1,141 @@
+// some older tests use `get(0)` to access the first element
+#![allow(clippy::get_first)]
+
 extern crate debian;
 #[macro_use]
 extern crate log;
 extern crate tempfile;
 
+use std::cmp::Ordering;
 use std::env;
 use std::path::PathBuf;
 use std::str::FromStr;
 
+use chrono::{NaiveDate, TimeZone};
+use sha2::{Digest, Sha256};
 use tempfile::TempDir;
 
-use debian::package::{parse_dep_list, ControlFile, VRel};
-use debian::version::{Version, VersionElement, VersionPart};
+use debian::alternatives::{read_admin_dir, AlternativeMode, LinkGroup};
+use debian::apt::edsp::{
+    write_progress, EdspActionKind, EdspAnswer, EdspRequest, EdspScenario,
+};
+use debian::apt::extended_states::ExtendedStates;
+use debian::apt::policy::Policy;
+use debian::apt::preferences::{glob_match, Preferences, ReleaseInfo};
+use debian::apt::sources::{SourceEntry, SourceType, SourcesList};
+use debian::arch::{arch_matches, DebianArchitecture, Endianness};
+use debian::archive::cache::IndexCache;
+use debian::archive::contents::{Contents, ContentsEntry, ContentsReader};
+#[cfg(feature = "yaml")]
+use debian::archive::dep11::Dep11File;
+#[cfg(feature = "http")]
+use debian::archive::dget::{source_files, SourceDownloader};
+#[cfg(feature = "gpgv")]
+use debian::archive::fetch::Gpgv;
+#[cfg(feature = "http")]
+use debian::archive::fetch::{RepositoryFetcher, Verifier};
+use debian::archive::mirror::MirrorPlanner;
+use debian::archive::overrides::{
+    MaintainerOverride, OverrideEntry, Overrides,
+};
+#[cfg(feature = "deb")]
+use debian::archive::packages::PackagesIndex;
+use debian::archive::pdiff::{apply_ed_script, PdiffIndex, PdiffPatch};
+use debian::archive::release::{Release, ReleaseBuilder};
+use debian::archive::translation::{description_md5, Translation};
+use debian::bugs::{
+    format_bug_field, format_closes, format_launchpad, parse_bug_field,
+    parse_bug_refs, parse_closes, parse_launchpad, BugRef,
+};
+use debian::builddep::{
+    arch_restriction_matches, builddep_check, profile_restriction_matches,
+};
+use debian::buildopts::{BuildOptions, BuildProfiles};
+#[cfg(feature = "cargo")]
+use debian::cargo::CrateMetadata;
+use debian::changes::{Changes, ChangesBuilder, ChangesFile};
+use debian::conffiles::{Conffile, Conffiles};
+use debian::conflicts::{ConflictAnalyzer, ConflictIssue};
+use debian::copyright::{files_pattern_match, Copyright};
+#[cfg(feature = "deb")]
+use debian::deb::{Compression, DataEntryType, DebBuilder, DebFile};
+use debian::deb822::{
+    parse_str, Deb822Reader, Decoder, Encoding, EncodingWarning, Span,
+};
+#[cfg(feature = "deb")]
+use debian::debdiff::{
+    diff_debs, diff_dscs, ChangeKind, FieldChange, FileChange,
+};
+use debian::debtags::{parse_tags, Tag, TagDatabase, Vocabulary};
+use debian::dep14::{
+    mangle_version, packaging_branch, parse_version_tag, suggested_branch,
+    unmangle_version, upstream_tag, version_tag, UPSTREAM_BRANCH,
+};
+use debian::distro::{DistroInfo, Suite, SupportStatus};
+use debian::diversions::Diversions;
+#[cfg(feature = "deb")]
+use debian::dsc::SourceBuilder;
+#[cfg(feature = "elf")]
+use debian::elf::ElfObject;
+use debian::filename::{
+    escape_version, unescape, ArtifactKind, ArtifactName, BinaryType,
+};
+#[cfg(feature = "elf")]
+use debian::gensymbols::{diff_symbols, exported_symbols};
+use debian::installed::{FileList, InstalledFiles};
+use debian::lint::{
+    Check, Finding, LintTarget, Linter, OutdatedStandardsVersion, Severity,
+    TargetKind,
+};
+use debian::maintainer_scripts::{MaintainerScript, ScriptIssue, ScriptKind};
+use debian::md5sums::{md5_hex, Md5Sums, Md5SumsIssue};
+#[cfg(feature = "openpgp")]
+use debian::openpgp::{strip_signature, Keyring, SecretKey};
+use debian::ordering::{installation_order, InstallCycle, InstallStep};
+use debian::orig::{
+    check_signature_file, find_orig_tarball, find_orig_tarballs,
+    orig_tarball_names,
+};
+use debian::package::{
+    parse_dep_list, Changelog, ControlFile, ControlParagraph, VRel,
+};
+use debian::patches::{PatchHeader, PatchSeries, SeriesLine};
+use debian::popcon::PopconResults;
+use debian::quilt::{
+    DirTree, FilePatch, HunkOffset, MemoryTree, Patch, Quilt, Tree,
+};
+use debian::resolver::{Candidate, Resolver, UnsatisfiedReason};
+use debian::scaffold::{PackageDescription, PackageKind};
+#[cfg(feature = "elf")]
+use debian::shlibdeps::ShlibDeps;
+use debian::shlibs::ShlibsFile;
+use debian::soname::{strip_t64, LibraryNameIssue, Soname};
+use debian::source::{SourceFormat, SourceOptions};
+use debian::standards::{StandardsStatus, StandardsVersion};
+use debian::statoverride::StatOverrides;
+use debian::status::{DpkgStatus, Flag, PackageState, Status, Want};
+use debian::substvars::{SubstvarKind, Substvars};
+use debian::symbols::{SymbolEntry, SymbolsFile};
+use debian::tasks::{parse_task_field, Tasks};
+use debian::templates::{split_choices, TemplateType, Templates};
+use debian::triggers::{Trigger, Triggers};
+use debian::universe::{Entry, Origin, PackageUniverse};
+#[cfg(feature = "upload")]
+use debian::upload::{upload_files, UploadMethod, UploadQueue, Uploader};
+#[cfg(feature = "yaml")]
+use debian::upstream::{MetadataIssue, UpstreamMetadata};
+#[cfg(feature = "http")]
+use debian::uscan::Scanner;
+#[cfg(feature = "deb")]
+use debian::uupdate::UpstreamUpdate;
+use debian::version::{
+    compare, debian_release_number, dpkg_compare_versions, is_valid_version,
+    sort_by_cached_version_key, sort_versions, CompareOp, Version,
+    VersionConstraint, VersionElement, VersionKey, VersionPart,
+};
+#[cfg(feature = "http")]
+use debian::watch::WatchEntry;
+use debian::watch::{MangleRule, WatchFile};
 
 fn data_path() -> PathBuf {
     // Not sure what the best way is - this works when invoked from cargo.
@@ -71,32 +196,223 @@ fn control_file_postgis() {
     assert_eq!(gp.get_entry("Source").unwrap(), "postgis");
 }
 
+#[test]
+fn deb822_spans() {
+    let text = "# comment\nSource: foo\nBuild-Depends: a,\n\tb\n\n\
+                Package: foo\r\nbroken line\r\nDescription: short\r\n long\r\n";
+    let paragraphs = parse_str(text);
+    assert_eq!(paragraphs.len(), 2);
+
+    let source = &paragraphs[0];
+    assert_eq!(source.span.line, 2);
+    assert_eq!(source.span.end_line, 4);
+    let bd = source.get("build-depends").unwrap();
+    assert_eq!(bd.name_span.start, 22);
+    assert_eq!(&text[bd.name_span.start..bd.name_span.end], "Build-Depends");
+    assert_eq!(&text[bd.value_span.start..bd.value_span.end], "a,");
+    assert_eq!(bd.continuations[0].text, "\tb");
+    assert_eq!(bd.span.end_line, 4);
+    assert_eq!(bd.full_value(), "a,\n\tb");
+
+    let binary = &paragraphs[1];
+    assert_eq!(binary.invalid.len(), 1);
+    assert_eq!(binary.invalid[0].span.line, 7);
+    let desc = binary.get("Description").unwrap();
+    assert_eq!(
+        desc.continuations[0].span,
+        Span {
+            start: text.find(" long").unwrap(),
+            end: text.find(" long").unwrap() + 5,
+            line: 9,
+            end_line: 9,
+        }
+    );
+
+    assert!(ControlParagraph::try_from(source).is_ok());
+    let err = ControlParagraph::try_from(binary).unwrap_err();
+    assert_eq!(err.to_string(), "line 7: invalid line 'broken line'");
+    let err = ControlFile::from_reader(text.as_bytes()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn deb822_clearsigned() {
+    let text = "-----BEGIN PGP SIGNED MESSAGE-----\nHash: SHA256\n\n\
+                Format: 1.8\nSource: foo\n\
+                -----BEGIN PGP SIGNATURE-----\n\nabc\n\
+                -----END PGP SIGNATURE-----\n";
+    let mut reader = Deb822Reader::new(text.as_bytes());
+    let para = reader.next().unwrap().unwrap();
+    assert!(reader.next().is_none());
+    assert!(reader.is_signed());
+    assert_eq!(para.fields.len(), 2);
+    assert_eq!(para.get("Source").unwrap().span.line, 5);
+
+    let cf = ControlFile::from_reader(text.as_bytes()).unwrap();
+    assert_eq!(cf.get_paragraphs().len(), 1);
+    assert_eq!(cf.get_paragraphs()[0].get_entry("Format"), Some("1.8"));
+    assert_eq!(cf.get_paragraphs()[0].get_entry("Hash"), None);
+}
+
+#[test]
+fn deb822_invalid_utf8() {
+    let mut reader =
+        Deb822Reader::new(&b"Package: foo\nMaintainer: \xff\n"[..]);
+    let err = reader.next().unwrap().unwrap_err();
+    assert!(err.to_string().contains("line 2"));
+    assert!(reader.next().is_none());
+
+    let data = b"Package: foo\r\nMaintainer: J\xf6rg\r\n\nPackage: bar\n";
+    let mut reader =
+        Deb822Reader::new(&data[..]).with_encoding(Encoding::Lossy);
+    let para = reader.next().unwrap().unwrap();
+    assert_eq!(para.get("Maintainer").unwrap().value, "J\u{fffd}rg");
+    assert_eq!(reader.next().unwrap().unwrap().fields[0].value, "bar");
+    assert_eq!(
+        reader.warnings(),
+        &[EncodingWarning {
+            span: Span {
+                start: 14,
+                end: 30,
+                line: 2,
+                end_line: 2,
+            },
+            offset: 27,
+        }]
+    );
+    let mut reader =
+        Deb822Reader::new(&data[..]).with_encoding(Encoding::Latin1Fallback);
+    let para = reader.next().unwrap().unwrap();
+    assert_eq!(para.get("Maintainer").unwrap().value, "Jörg");
+
+    // other parsers read through a decoder
+    let changelog = b"foo (1.0-1) unstable; urgency=low\n\n  * Initial.\n\n \
+                      -- J\xf6rg Doe <jd@example.org>  \
+                      Mon, 02 Oct 2023 10:00:00 +0200\n";
+    let err =
+        Changelog::from_reader(Decoder::new(&changelog[..], Encoding::Strict))
+            .unwrap_err();
+    assert_eq!(err.to_string(), "invalid UTF-8 in line 5 at byte 5");
+    let mut decoder = Decoder::new(&changelog[..], Encoding::Latin1Fallback);
+    let parsed = Changelog::from_reader(&mut decoder).unwrap();
+    assert_eq!(parsed.entries()[0].maintainer_name(), "Jörg Doe");
+    assert_eq!(decoder.warnings().len(), 1);
+    assert_eq!(decoder.warnings()[0].span.line, 5);
+    assert_eq!(decoder.warnings()[0].offset, 54);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn deb822_serde() {
+    use std::collections::BTreeMap;
+
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    enum Priority {
+        #[serde(rename = "optional")]
+        Optional,
+        #[serde(rename = "required")]
+        Required,
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    struct Dsc {
+        format: String,
+        source: String,
+        version: Version,
+        build_depends: Option<Vec<String>>,
+        #[serde(rename = "Testsuite")]
+        test_suite: Option<String>,
+        priority: Priority,
+        files: Vec<(String, u64, String)>,
+        description: String,
+    }
+
+    let text = "Format: 3.0 (quilt)\nSource: foo\nversion: 1.0-1\n\
+                BUILD-DEPENDS: debhelper-compat (= 13),\n libfoo-dev\n\
+                Priority: optional\nFiles:\n \
+                abc 123 foo_1.0.orig.tar.xz\n \
+                def 45 foo_1.0-1.debian.tar.xz\n\
+                Description: short\n long\n .\n more\n";
+    let dsc: Dsc = debian::deb822::from_str(text).unwrap();
+    assert_eq!(dsc.version, Version::parse("1.0-1").unwrap());
+    assert_eq!(
+        dsc.build_depends.as_deref(),
+        Some(&["debhelper-compat (= 13)".to_string(), "libfoo-dev".into()][..])
+    );
+    assert_eq!(dsc.test_suite, None);
+    assert_eq!(dsc.priority, Priority::Optional);
+    assert_eq!(dsc.files[1].1, 45);
+    assert_eq!(dsc.files[1].2, "foo_1.0-1.debian.tar.xz");
+    assert_eq!(dsc.description, "short\n long\n .\n more");
+
+    let written = debian::deb822::to_string(&dsc).unwrap();
+    assert_eq!(
+        written,
+        "Format: 3.0 (quilt)\nSource: foo\nVersion: 1.0-1\n\
+         Build-Depends: debhelper-compat (= 13), libfoo-dev\n\
+         Priority: optional\nFiles:\n \
+         abc 123 foo_1.0.orig.tar.xz\n \
+         def 45 foo_1.0-1.debian.tar.xz\n\
+         Description: short\n long\n .\n more\n"
+    );
+    assert_eq!(debian::deb822::from_str::<Dsc>(&written).unwrap(), dsc);
+
+    // multiple paragraphs as a sequence of maps
+    let paragraphs: Vec<BTreeMap<String, String>> =
+        debian::deb822::from_str("A: 1\nB: 2\n\nC: 3\n").unwrap();
+    assert_eq!(paragraphs.len(), 2);
+    assert_eq!(paragraphs[1]["C"], "3");
+    assert_eq!(
+        debian::deb822::to_string(&paragraphs).unwrap(),
+        "A: 1\nB: 2\n\nC: 3\n"
+    );
+
+    // errors point at the offending line
+    let err = debian::deb822::from_str::<Vec<Dsc>>(
+        "Source: foo\n\nFormat: 1.0\nSource: bar\nVersion: 1\n\
+         Priority: high\n",
+    )
+    .unwrap_err();
+    assert_eq!(err.line, Some(1));
+    assert!(err.msg.contains("missing field `format`"));
+    let err = debian::deb822::from_str::<Dsc>(
+        "Format: 1.0\nSource: bar\nVersion: 1\nPriority: high\n",
+    )
+    .unwrap_err();
+    assert_eq!(err.line, Some(4));
+    let err = debian::deb822::from_str::<Dsc>("A: 1\n\nB: 2\n").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "line 3: expected a single paragraph, found 2"
+    );
+}
+
 #[test]
 fn version_basics() {
     let v = Version::parse("7:2.1.4-0~bpo2").unwrap();
-    assert_eq!(v.epoch, 7);
+    assert_eq!(v.epoch, Some(7));
     assert_eq!(&v.upstream_version.to_string(), "2.1.4");
     assert_eq!(&v.upstream_version.to_string(), "2.1.4");
     assert_eq!(&v.debian_revision.to_string(), "0~bpo2");
     assert_eq!(Version::from_str("7:2.1.4-0~bpo2").unwrap(), v);
 
     let v = Version::parse("2.1.4-0~bpo2").unwrap();
-    assert_eq!(v.epoch, 0);
+    assert_eq!(v.epoch, None);
     assert_eq!(&v.upstream_version.to_string(), "2.1.4");
     assert_eq!(&v.debian_revision.to_string(), "0~bpo2");
 
     let v = Version::parse("7:2.1.4").unwrap();
-    assert_eq!(v.epoch, 7);
+    assert_eq!(v.epoch, Some(7));
     assert_eq!(v.upstream_version.to_string(), "2.1.4");
     assert_eq!(v.debian_revision.to_string(), "");
 
     let v = Version::parse("2.1.4").unwrap();
-    assert_eq!(v.epoch, 0);
+    assert_eq!(v.epoch, None);
     assert_eq!(v.upstream_version.to_string(), "2.1.4");
     assert_eq!(v.debian_revision.to_string(), "");
 
     let v = Version::parse("1:1:1-8-8").unwrap();
-    assert_eq!(v.epoch, 1);
+    assert_eq!(v.epoch, Some(1));
     assert_eq!(v.upstream_version.to_string(), "1:1-8");
     assert_eq!(v.debian_revision.to_string(), "8");
 }
@@ -153,6 +469,668 @@ fn version_comparisons() {
     );
 }
 
+#[test]
+fn version_strict_parsing() {
+    for v in ["1.0", "1:2.3.4-1", "2.0~rc1+dfsg-1~bpo12+1", "1.0-2-3"] {
+        assert!(Version::parse_strict(v).is_ok(), "{v}");
+    }
+    let pos = |v: &str| Version::parse_strict(v).unwrap_err().pos;
+    assert_eq!(pos(""), 0);
+    assert_eq!(pos("A:1.0"), 0);
+    assert_eq!(pos(":1.0"), 0);
+    assert_eq!(pos("1:a1.0"), 2);
+    assert_eq!(pos("1.0 beta"), 3);
+    assert_eq!(pos("1.0!-1"), 3);
+    assert_eq!(pos("1:1:1-8"), 3);
+    assert_eq!(pos("1.0-1_2"), 5);
+    assert_eq!(pos("1.0-"), 3);
+    assert_eq!(pos("1-2:3"), 1);
+    assert!(Version::parse("1.0 beta").is_ok());
+}
+
+#[test]
+fn version_round_trip() {
+    for v in [
+        "1.04",
+        "0:1.2",
+        "1:007-0",
+        "1.0~",
+        "2.0a",
+        "1.0-1ubuntu0.1",
+        "1:1:1-8-8",
+        "20240101.0+git00ab",
+    ] {
+        assert_eq!(Version::parse(v).unwrap().to_string(), v);
+    }
+    assert_eq!(
+        Version::parse("1.04").unwrap(),
+        Version::parse("1.4").unwrap()
+    );
+}
+
+#[test]
+fn version_explicit_zero_epoch() {
+    let explicit = Version::parse("0:1.2-1").unwrap();
+    let implicit = Version::parse("1.2-1").unwrap();
+    assert_eq!(explicit, implicit);
+    assert_eq!(explicit.epoch, Some(0));
+    assert_eq!(implicit.epoch, None);
+    assert_eq!(explicit.effective_epoch(), implicit.effective_epoch());
+    assert!(explicit.has_redundant_epoch());
+    assert!(!implicit.has_redundant_epoch());
+    assert!(!Version::parse("1:1.2").unwrap().has_redundant_epoch());
+    assert_ne!(explicit.to_string(), implicit.to_string());
+}
+
+#[test]
+fn version_predicates() {
+    let v = Version::parse("1:2.0~rc1-3").unwrap();
+    assert!(v.has_epoch());
+    assert!(!v.is_native());
+    assert_eq!(v.upstream(), "2.0~rc1");
+    assert_eq!(v.revision().as_deref(), Some("3"));
+
+    let v = Version::parse("0:2.04").unwrap();
+    assert!(!v.has_epoch());
+    assert!(v.is_native());
+    assert_eq!(v.upstream(), "2.04");
+    assert_eq!(v.revision(), None);
+}
+
+#[test]
+fn version_increment_revision() {
+    let inc =
+        |v: &str| Version::parse(v).unwrap().increment_revision().to_string();
+    assert_eq!(inc("1.0-1"), "1.0-2");
+    assert_eq!(inc("1:1.0-9"), "1:1.0-10");
+    assert_eq!(inc("1.0-1ubuntu3"), "1.0-1ubuntu4");
+    assert_eq!(inc("1.0-0.1"), "1.0-0.2");
+    assert_eq!(inc("1.0-1build"), "1.0-1build1");
+    assert_eq!(inc("1.0"), "1.0-1");
+    assert_eq!(inc("1.0-099"), "1.0-100");
+    let v = Version::parse("2.3-4").unwrap();
+    assert!(v.increment_revision() > v);
+}
+
+#[test]
+fn version_backport() {
+    let v = Version::parse("1.2-3").unwrap();
+    let bpo = v.backport("bookworm", None).unwrap();
+    assert_eq!(bpo.to_string(), "1.2-3~bpo12+1");
+    assert!(bpo < v);
+    assert!(bpo > Version::parse("1.2-2").unwrap());
+    assert_eq!(v.backport("12", None), Some(bpo.clone()));
+
+    let next = v.backport("bookworm", Some(&bpo)).unwrap();
+    assert_eq!(next.to_string(), "1.2-3~bpo12+2");
+    assert!(next > bpo);
+    let other = Version::parse("1.2-2~bpo12+4").unwrap();
+    assert_eq!(
+        v.backport("bookworm", Some(&other)).unwrap().to_string(),
+        "1.2-3~bpo12+1"
+    );
+
+    let native = Version::parse("2.0").unwrap();
+    assert_eq!(
+        native.backport("trixie", None).unwrap().to_string(),
+        "2.0~bpo13+1"
+    );
+    assert_eq!(v.backport("hurd", None), None);
+    assert_eq!(debian_release_number("bullseye"), Some(11));
+    for (release, number) in [("rex", 1), ("bo", 1), ("slink", 2)] {
+        assert_eq!(debian_release_number(release), Some(number));
+    }
+    assert_eq!(
+        native.backport("slink", None).unwrap().to_string(),
+        "2.0~bpo2+1"
+    );
+}
+
+#[test]
+fn version_nmu_and_binnmu() {
+    let nmu = |v: &str| Version::parse(v).unwrap().nmu().to_string();
+    assert_eq!(nmu("1.0-3"), "1.0-3.1");
+    assert_eq!(nmu("1.0-3.1"), "1.0-3.2");
+    assert_eq!(nmu("1:2.0"), "1:2.0+nmu1");
+    assert_eq!(nmu("2.0+nmu1"), "2.0+nmu2");
+
+    let v = Version::parse("1.0-3").unwrap();
+    assert!(!v.is_binnmu());
+    let b1 = v.binnmu(1);
+    assert_eq!(b1.to_string(), "1.0-3+b1");
+    assert!(b1.is_binnmu());
+    assert_eq!(b1.binnmu_number(), Some(1));
+    assert!(b1 > v && b1 < v.nmu());
+    assert_eq!(b1.binnmu(2).to_string(), "1.0-3+b2");
+    assert_eq!(
+        Version::parse("2.0").unwrap().binnmu(1).to_string(),
+        "2.0+b1"
+    );
+    assert_eq!(Version::parse("2.0+b7").unwrap().binnmu_number(), Some(7));
+}
+
+#[test]
+fn version_stable_update() {
+    let v = Version::parse("1.2-3").unwrap();
+    assert_eq!(v.stable_update_suffix(), None);
+    let u1 = v.stable_update(12, None);
+    assert_eq!(u1.to_string(), "1.2-3+deb12u1");
+    assert_eq!(u1.stable_update_suffix(), Some((12, 1)));
+    assert!(u1 > v && u1 < Version::parse("1.2-4").unwrap());
+
+    let u2 = u1.stable_update(12, None);
+    assert_eq!(u2.to_string(), "1.2-3+deb12u2");
+    assert!(u2 > u1);
+    assert_eq!(u1.stable_update(12, Some(5)).to_string(), "1.2-3+deb12u5");
+    assert_eq!(
+        Version::parse("3.0")
+            .unwrap()
+            .stable_update(11, None)
+            .to_string(),
+        "3.0+deb11u1"
+    );
+    assert_eq!(v.stable_update(11, Some(3)).to_string(), "1.2-3+deb11u3");
+
+    // a suffix for another release gets replaced
+    let old = Version::parse("1.0-1+deb11u2").unwrap();
+    assert_eq!(old.stable_update(12, None).to_string(), "1.0-1+deb12u1");
+    assert_eq!(old.stable_update(12, Some(2)).to_string(), "1.0-1+deb12u2");
+}
+
+#[test]
+fn version_upstream_suffixes() {
+    let v = |s: &str| Version::parse(s).unwrap();
+    assert_eq!(v("1.2.3+dfsg1-2").base_upstream(), "1.2.3");
+    assert_eq!(v("1.2.3+ds-1").base_upstream(), "1.2.3");
+    assert_eq!(v("2.0~rc1+dfsg-1").base_upstream(), "2.0");
+    assert_eq!(v("2.0+really1.9-1").base_upstream(), "1.9");
+    assert_eq!(v("1.0+git20240101.abc123-1").base_upstream(), "1.0");
+    assert_eq!(v("1.0~20240101-1").base_upstream(), "1.0");
+    assert_eq!(v("3:1.0-1").base_upstream(), "1.0");
+    assert_eq!(v("git20240101").base_upstream(), "git20240101");
+
+    assert!(v("2.0~rc1-1").is_prerelease());
+    assert!(v("2.0~beta2+dfsg-1").is_prerelease());
+    assert!(!v("2.0-1~bpo12+1").is_prerelease());
+    assert!(!v("2.0~release-1").is_prerelease());
+
+    assert!(v("1.0+git20240101.abc123-1").is_snapshot());
+    assert!(v("1.0~20240101-1").is_snapshot());
+    assert!(!v("1.0+dfsg-1").is_snapshot());
+
+    assert_eq!(v("1.0+dfsg2-1").dfsg_revision(), Some(2));
+    assert_eq!(v("1.0+ds.3-1").dfsg_revision(), Some(3));
+    assert_eq!(v("1.0+dfsg-1").dfsg_revision(), Some(0));
+    assert_eq!(v("1.0~rc1+dfsg1~git2024-1").dfsg_revision(), Some(1));
+    assert_eq!(v("1.0-1").dfsg_revision(), None);
+    assert_eq!(v("1.0+dsfoo-1").dfsg_revision(), None);
+}
+
+#[test]
+fn version_compare_with_str() {
+    let v = Version::parse("1:2.1.4-1").unwrap();
+    assert!(v == "1:2.1.4-1");
+    assert!(v == "1:2.1.4-01");
+    assert!(v != "2.1.4-1");
+    assert!(v >= "1:2.1.4-1");
+    assert!(v > "1:2.1.4~rc1-1");
+    assert!(v < "1:2.1.5");
+    assert!(v != "1:2.1.4-1:");
+    assert_eq!(v.partial_cmp(""), None);
+    assert_eq!(v.partial_cmp("1:x"), None);
+}
+
+#[test]
+fn version_dpkg_compare_versions() {
+    let check = |a: &str, op: &str, b: &str| {
+        dpkg_compare_versions(a, op.parse().unwrap(), b).unwrap()
+    };
+    assert!(check("1.0-1", "lt", "1.0-2"));
+    assert!(check("1.0-1", "<<", "1.0-2"));
+    assert!(check("1.0-1", "le", "1.0-1"));
+    assert!(check("1.0-1", "<", "1.0-1"));
+    assert!(check("0:1.0", "eq", "1.0"));
+    assert!(check("1.0", "ne", "1.0-1"));
+    assert!(check("1:0.9", "ge", "1.0"));
+    assert!(check("1.0", ">", "1.0"));
+    assert!(check("1.0+b1", "gt", "1.0"));
+    assert!(!check("1.0~rc1", "gt", "1.0"));
+
+    assert!(check("", "lt", "1.0"));
+    assert!(!check("", "lt-nl", "1.0"));
+    assert!(check("", "gt-nl", "1.0"));
+    assert!(check("1.0", "lt-nl", ""));
+    assert!(check("", "eq", ""));
+    assert!(check(" 1.0 ", "eq", "1.0"));
+
+    assert_eq!("lt-nl".parse::<CompareOp>(), Ok(CompareOp::LtNl));
+    assert_eq!(CompareOp::GeNl.to_string(), "ge-nl");
+    assert!("lessthan".parse::<CompareOp>().is_err());
+    assert!(dpkg_compare_versions("1.0 1", CompareOp::Eq, "1.0").is_err());
+    assert!(dpkg_compare_versions("a:1.0", CompareOp::Eq, "1.0").is_err());
+    assert!(dpkg_compare_versions("1:", CompareOp::Eq, "1.0").is_err());
+    assert!(dpkg_compare_versions("1.0-", CompareOp::Eq, "1.0").is_err());
+}
+
+#[test]
+fn version_sorting() {
+    let strings = [
+        "1.0",
+        "1.0-0",
+        "1.0-1",
+        "1.0~rc1",
+        "1.0~~",
+        "1.0~",
+        "1.0+b1",
+        "1.0.1",
+        "1.0a",
+        "1.00",
+        "1.0.",
+        "1:0.9",
+        "0:1.0-1",
+        "2",
+        "10",
+        "1.0-1~bpo1",
+        "1.0-1.1",
+        "1.0-1+deb12u1",
+        "1.0-0~",
+        "0",
+        "",
+        "1.0-a",
+        "1.0-A",
+        "123456789012345678901234567890",
+        "123456789012345678901234567891",
+    ];
+    for a in strings {
+        for b in strings {
+            let (ka, kb) = (VersionKey::new(a).unwrap(), VersionKey::new(b));
+            assert_eq!(
+                ka.cmp(&kb.unwrap()),
+                compare(a, b).unwrap(),
+                "{a} vs {b}"
+            );
+        }
+    }
+
+    let mut versions: Vec<Version> =
+        ["1:0.9", "1.0-1", "1.0~rc1-1", "1.0-1+b1"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+    sort_versions(&mut versions);
+    let sorted: Vec<String> = versions.iter().map(|v| v.to_string()).collect();
+    assert_eq!(sorted, ["1.0~rc1-1", "1.0-1", "1.0-1+b1", "1:0.9"]);
+
+    let mut pairs = vec![
+        ("foo", "2.0-1"),
+        ("bar", "1.0-1"),
+        ("baz", "2.0~rc1-1"),
+        ("qux", "0:1.0-1"),
+    ];
+    sort_by_cached_version_key(&mut pairs).unwrap();
+    let names: Vec<&str> = pairs.iter().map(|p| p.0).collect();
+    assert_eq!(names, ["bar", "qux", "baz", "foo"]);
+
+    let mut invalid = vec![("foo", "2.0-1"), ("bar", "x:1.0")];
+    assert!(sort_by_cached_version_key(&mut invalid).is_err());
+    assert_eq!(invalid[0].0, "foo");
+}
+
+#[test]
+fn version_next_upstream_candidates() {
+    let candidates = |s: &str| -> Vec<String> {
+        Version::parse(s)
+            .unwrap()
+            .next_upstream_candidates()
+            .iter()
+            .map(|v| v.to_string())
+            .collect()
+    };
+    assert_eq!(candidates("1.2.3-1"), ["1.2.4", "1.3.0", "2.0.0"]);
+    assert_eq!(candidates("1:0.9+dfsg-2"), ["1:0.10", "1:1.0"]);
+    assert_eq!(candidates("2.0~rc1-1"), ["2.0~rc2", "2.0"]);
+    assert_eq!(candidates("2.0~beta-1"), ["2.0"]);
+    assert_eq!(candidates("1.0~git20240101-1"), ["1.0", "1.1", "2.0"]);
+    assert_eq!(candidates("1.0+git20240101-1"), ["1.1", "2.0"]);
+    assert_eq!(candidates("1.2a-1"), ["1.3", "2.0"]);
+    assert!(candidates("foo").is_empty());
+}
+
+#[test]
+fn version_part_accessors() {
+    let v = Version::parse("1.02a~rc-0ubuntu1").unwrap();
+    let upstream = &v.upstream_version;
+    assert_eq!(upstream.len(), 3);
+    assert!(upstream.starts_with_digit());
+    let runs: Vec<(&str, Option<&str>)> = upstream
+        .iter()
+        .map(|e| (e.alpha_run(), e.numeric_run()))
+        .collect();
+    assert_eq!(runs, [("", Some("1")), (".", Some("02")), ("a~rc", None)]);
+    assert_eq!(upstream.get(1).unwrap().numeric_value(), Some(2));
+    assert_eq!(upstream.last().unwrap().numeric_value(), None);
+    assert_eq!((&v.debian_revision).into_iter().count(), 2);
+    assert!(v.debian_revision.starts_with_digit());
+    assert!(!Version::parse("a1")
+        .unwrap()
+        .upstream_version
+        .starts_with_digit());
+    assert!(Version::parse("1.0").unwrap().debian_revision.is_empty());
+
+    let long = Version::parse("123456789012345678901234567890").unwrap();
+    assert_eq!(long.upstream_version.get(0).unwrap().numeric_value(), None);
+}
+
+#[test]
+fn version_explicit_format() {
+    for (s, explicit) in [
+        ("1.0", "0:1.0-"),
+        ("1.0-1", "0:1.0-1"),
+        ("0:1.0", "0:1.0-"),
+        ("2:1.0-1ubuntu1", "2:1.0-1ubuntu1"),
+        ("1.0-rc1-1", "0:1.0-rc1-1"),
+    ] {
+        let v = Version::parse(s).unwrap();
+        assert_eq!(format!("{v:#}"), explicit);
+        assert_eq!(v.explicit_string(), explicit);
+        assert_eq!(v.to_string(), s);
+        let back = Version::parse(explicit).unwrap();
+        assert_eq!(back, v);
+        assert_eq!(back.is_native(), v.is_native());
+        assert_eq!(back.upstream(), v.upstream());
+    }
+}
+
+#[test]
+fn version_macro() {
+    const MIN: &str = "2.1.4-1";
+    let v = debian::version!("1:2.3-4");
+    assert_eq!(v, Version::parse("1:2.3-4").unwrap());
+    assert_eq!(debian::version!(MIN).upstream(), "2.1.4");
+
+    for s in [
+        "1.0",
+        "1:1.0-1",
+        "1.0-rc1-1",
+        "0:1.0~rc1+dfsg",
+        "4294967295:1",
+    ] {
+        assert!(is_valid_version(s), "{s}");
+        assert!(Version::parse_strict(s).is_ok(), "{s}");
+    }
+    for s in [
+        "",
+        "a1.0",
+        "1.0-",
+        "01:1.0",
+        "4294967296:1",
+        "1:1:1",
+        "1.0 1",
+    ] {
+        assert!(!is_valid_version(s), "{s}");
+        assert!(Version::parse_strict(s).is_err(), "{s}");
+    }
+}
+
+#[test]
+fn version_satisfies() {
+    let v = |s: &str| Version::parse(s).unwrap();
+    let installed = v("1.2-3");
+    assert!(installed.satisfies(VRel::GreaterOrEqual, &v("1.2-3")));
+    assert!(installed.satisfies(VRel::GreaterOrEqual, &v("1.2~rc1")));
+    assert!(!installed.satisfies(VRel::Greater, &v("1.2-3")));
+    assert!(installed.satisfies(VRel::LesserOrEqual, &v("1.2-3")));
+    assert!(installed.satisfies(VRel::Lesser, &v("1.2-3+b1")));
+    assert!(installed.satisfies(VRel::Equal, &v("0:1.2-3")));
+    assert!(!installed.satisfies(VRel::Equal, &v("1.2-4")));
+    assert!(VRel::Greater.is_satisfied_by(&v("2.0"), &v("1.9")));
+    assert!(VRel::Lesser.matches(Ordering::Less));
+    assert!(!VRel::GreaterOrEqual.matches(Ordering::Less));
+}
+
+#[test]
+fn version_constraints() {
+    let v = |s: &str| Version::parse(s).unwrap();
+    let mut c =
+        VersionConstraint::from_relation(VRel::GreaterOrEqual, &v("1.0"));
+    c.add(VRel::Lesser, &v("2.0~"));
+    assert!(c.contains(&v("1.0")));
+    assert!(c.contains(&v("1.9-3")));
+    assert!(!c.contains(&v("2.0~rc1")));
+    assert!(!c.contains(&v("0.9")));
+    assert!(!c.is_empty());
+    assert_eq!(c.to_string(), ">= 1.0, << 2.0~");
+
+    // tighter bounds win
+    c.add(VRel::Greater, &v("1.0"));
+    assert!(!c.contains(&v("1.0")));
+    assert!(c.contains(&v("1.0-1")));
+
+    let breaks = VersionConstraint::from_relation(VRel::Lesser, &v("1.0"));
+    assert!(c.intersection(&breaks).is_empty());
+    let exact = VersionConstraint::from_relation(VRel::Equal, &v("1.5"));
+    assert_eq!(c.intersection(&exact), exact);
+    assert_eq!(exact.relations(), vec![(VRel::Equal, v("1.5"))]);
+    assert!(VersionConstraint::from_relation(VRel::Greater, &v("1.5"))
+        .intersection(&exact)
+        .is_empty());
+    assert!(VersionConstraint::any().is_any());
+    assert!(VersionConstraint::any().contains(&v("0")));
+}
+
+#[cfg(feature = "semver")]
+#[test]
+fn version_semver_conversion() {
+    let deb = |s: &str| Version::from(semver::Version::parse(s).unwrap());
+    assert_eq!(deb("1.2.3").to_string(), "1.2.3");
+    assert_eq!(deb("1.2.3-rc.1").to_string(), "1.2.3~rc.1");
+    assert_eq!(deb("1.2.3-beta-2").to_string(), "1.2.3~beta.2");
+    assert_eq!(deb("1.2.3+build.5").to_string(), "1.2.3");
+    assert!(deb("1.0.0-alpha") < deb("1.0.0"));
+
+    let sv = |s: &str| semver::Version::try_from(&Version::parse(s).unwrap());
+    assert_eq!(sv("1:1.2.3-4").unwrap(), semver::Version::new(1, 2, 3));
+    assert_eq!(
+        sv("1.2.3~rc.1+dfsg-1").unwrap(),
+        semver::Version::parse("1.2.3-rc.1+dfsg").unwrap()
+    );
+    assert!(sv("1.2").is_err());
+}
+
+#[test]
+fn version_from_components() {
+    let v = Version::new(Some(1), "2.3", Some("4")).unwrap();
+    assert_eq!(v, Version::parse("1:2.3-4").unwrap());
+    assert_eq!(v.to_string(), "1:2.3-4");
+    assert_eq!(Version::new(None, "2.3", None).unwrap().to_string(), "2.3");
+    assert_eq!(
+        Version::new(None, "2.3-rc1", Some("1")).unwrap().upstream(),
+        "2.3-rc1"
+    );
+    assert_eq!(Version::new(Some(0), "1.0", None).unwrap().epoch, Some(0));
+
+    assert!(Version::new(None, "2.3-rc1", None).is_err());
+    assert!(Version::new(None, "2.3", Some("1-2")).is_err());
+    assert!(Version::new(None, "v2.3", None).is_err());
+    assert!(Version::new(None, "2.3", Some("")).is_err());
+    assert_eq!(
+        Version::new(Some(2), "2.3", Some("1_1")).unwrap_err().pos,
+        7
+    );
+}
+
+#[test]
+fn version_parse_error() {
+    let err = Version::parse_strict("1.0 beta").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid version at position 3: \
+         Invalid character in upstream version."
+    );
+    let parse = |s: &str| -> Result<Version, Box<dyn std::error::Error>> {
+        Ok(Version::parse_strict(s)?)
+    };
+    assert!(parse("1.0").is_ok());
+    assert!(parse("x:1.0").is_err());
+}
+
+#[test]
+fn version_string_compare() {
+    let versions = [
+        "1.0~~",
+        "1.0~",
+        "1.0~rc1",
+        "1.0",
+        "1.0-0.1",
+        "1.0-1~bpo1",
+        "1.0-1",
+        "1.0-1+b1",
+        "1.0a",
+        "1.0+dfsg",
+        "1.0.1",
+        "1.00.2",
+        "1.10",
+        "1:0.1",
+        "1:1.0-1",
+    ];
+    for a in versions {
+        for b in versions {
+            let expected =
+                Version::parse(a).unwrap().cmp(&Version::parse(b).unwrap());
+            assert_eq!(compare(a, b).unwrap(), expected, "{a} vs {b}");
+        }
+    }
+    assert_eq!(
+        compare("1.000000000000000000000001", "1.1").unwrap(),
+        Ordering::Equal
+    );
+    assert_eq!(
+        compare("99999999999999999999999", "99999999999999999999998").unwrap(),
+        Ordering::Greater
+    );
+    assert!(compare("x:1.0", "1.0").is_err());
+}
+
+#[test]
+fn version_long_digit_runs() {
+    let long = Version::parse("20240101000000000000001-1").unwrap();
+    let longer = Version::parse("20240101000000000000002-1").unwrap();
+    assert_eq!(long.to_string(), "20240101000000000000001-1");
+    assert_eq!(long.upstream_version.elements[0].numeric, u64::MAX);
+    assert!(long < longer);
+    assert!(
+        Version::parse("18446744073709551616").unwrap()
+            > Version::parse("18446744073709551615").unwrap()
+    );
+    assert_eq!(
+        Version::parse("1.000000000000000000000000002").unwrap(),
+        Version::parse("1.2").unwrap()
+    );
+}
+
+#[test]
+fn version_parsers_do_not_panic() {
+    // regressions found by the fuzz targets in fuzz/
+    let v = Version::parse("1.0").unwrap();
+    let p = Version::parse("1.0~bpo12+18446744073709551615").unwrap();
+    assert_eq!(
+        v.backport("12", Some(&p)).unwrap().to_string(),
+        "1.0~bpo12+1"
+    );
+    assert!(Version::parse_strict("03:2").is_err());
+    assert!(Version::parse_strict("3:2").is_ok());
+
+    for s in ["", "-", ":", "1:", "1:-", "--", "1-2:3", "\u{e4}:1"] {
+        if let Ok(v) = Version::parse(s) {
+            let _ = (
+                v.to_string(),
+                v.nmu(),
+                v.binnmu(1),
+                v.stable_update(1, None),
+            );
+        }
+        let _ = compare(s, "1.0");
+        let _ = parse_dep_list(s);
+        let _ = ControlFile::from_reader(s.as_bytes());
+    }
+}
+
+#[test]
+fn version_canonicalization() {
+    let canon = |s: &str| Version::parse(s).unwrap().canonical_string();
+    assert_eq!(canon("0:1.2-1"), "1.2-1");
+    assert_eq!(canon("1:1.2-1"), "1:1.2-1");
+    assert_eq!(canon("1.04-01"), "1.4-1");
+    assert_eq!(canon("1.0-0"), "1.0");
+    assert_eq!(canon("1.0-00"), "1.0");
+    assert_eq!(canon("1.00"), "1.0");
+    assert_eq!(canon("2.0~rc1+dfsg-3"), "2.0~rc1+dfsg-3");
+
+    let v = Version::parse("0:01.2-0").unwrap();
+    assert_eq!(v.to_string(), "0:01.2-0");
+    assert_eq!(v.canonicalize(), v);
+    assert_eq!(canon("0:01.2-0"), canon("1.2"));
+}
+
+#[test]
+fn version_element_ordering() {
+    let elem = |alpha: &str, numeric: u64| VersionElement {
+        alpha: alpha.to_string(),
+        numeric,
+        digits: numeric.to_string(),
+    };
+    assert!(elem("~rc", 1) < elem("", 0));
+    assert!(elem("a", 9) < elem(".", 1));
+    assert!(elem(".", 1) < elem(".", 2));
+    assert!(elem("+b", 1) < elem(".", 0));
+
+    let mut elements = vec![elem(".", 2), elem("~", 5), elem("a", 0)];
+    elements.sort();
+    assert_eq!(elements, vec![elem("~", 5), elem("a", 0), elem(".", 2)]);
+
+    let mut map = std::collections::BTreeMap::new();
+    for v in ["1.0", "1.0~rc1", "1.0+b1", "1.0a"] {
+        map.insert(Version::parse(v).unwrap(), v);
+    }
+    assert_eq!(
+        map.values().copied().collect::<Vec<_>>(),
+        vec!["1.0~rc1", "1.0", "1.0a", "1.0+b1"]
+    );
+}
+
+#[test]
+fn version_tilde_ordering() {
+    let ordered = [
+        "1.0~~",
+        "1.0~~a",
+        "1.0~",
+        "1.0~rc1",
+        "1.0~rc2",
+        "1.0",
+        "1.0-1~bpo1",
+        "1.0-1",
+        "1.0a",
+        "1.0+",
+        "1.0+b1",
+        "1.0.1",
+    ];
+    for pair in ordered.windows(2) {
+        let a = Version::parse(pair[0]).unwrap();
+        let b = Version::parse(pair[1]).unwrap();
+        assert!(a < b, "{} < {}", pair[0], pair[1]);
+        assert!(b > a, "{} > {}", pair[1], pair[0]);
+    }
+    assert_eq!(
+        Version::parse("1.0-0").unwrap(),
+        Version::parse("1.0").unwrap()
+    );
+    assert!(
+        Version::parse("1.0+b1").unwrap() > Version::parse("1.0a").unwrap()
+    );
+    assert!(Version::parse("1.0a").unwrap() < Version::parse("1.0+").unwrap());
+    assert!(Version::parse("1.0Z").unwrap() < Version::parse("1.0a").unwrap());
+}
+
 #[test]
 fn dependency_basics() {
     let deps = parse_dep_list("foo (>= 3.2) | bar, baz (>= 1)").unwrap();
@@ -166,16 +1144,18 @@ fn dependency_basics() {
         Some((
             VRel::GreaterOrEqual,
             Version {
-                epoch: 0,
+                epoch: None,
                 upstream_version: VersionPart {
                     elements: vec![
                         VersionElement {
                             alpha: "".to_string(),
-                            numeric: 3
+                            numeric: 3,
+                            digits: "3".to_string(),
                         },
                         VersionElement {
                             alpha: ".".to_string(),
-                            numeric: 2
+                            numeric: 2,
+                            digits: "2".to_string(),
                         }
                     ]
                 },
@@ -193,11 +1173,12 @@ fn dependency_basics() {
         Some((
             VRel::GreaterOrEqual,
             Version {
-                epoch: 0,
+                epoch: None,
                 upstream_version: VersionPart {
                     elements: vec![VersionElement {
                         alpha: "".to_string(),
-                        numeric: 1
+                        numeric: 1,
+                        digits: "1".to_string(),
                     }]
                 },
                 debian_revision: VersionPart { elements: vec![] }
@@ -224,3 +1205,5804 @@ fn serde_tests() {
     let ser = serde_json::to_string(&versions).unwrap();
     assert_eq!(ser, r#"["8:1.8-0~bpo2","1.8-0","1:1:1-8-8"]"#);
 }
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_version_parts() {
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    struct Embedding {
+        version: Version,
+        upstream: VersionPart,
+        element: VersionElement,
+    }
+    let data =
+        r#"{"version":"1:2.04-1","upstream":"2.0~rc1","element":"~rc1"}"#;
+    let e: Embedding = serde_json::from_str(data).unwrap();
+    assert_eq!(e.version, Version::parse("1:2.4-1").unwrap());
+    assert_eq!(e.upstream.to_string(), "2.0~rc1");
+    assert_eq!(e.element.alpha, "~rc");
+    assert_eq!(e.element.numeric, 1);
+    assert_eq!(serde_json::to_string(&e).unwrap(), data);
+    assert!(serde_json::from_str::<VersionElement>(r#""1.2""#).is_err());
+}
+
+#[test]
+fn dpkg_status() {
+    let path = data_path().join("dpkg-status");
+    let status = DpkgStatus::from_file(&path).unwrap();
+    assert_eq!(status.entries().len(), 4);
+
+    let bf = status.get("base-files").unwrap();
+    assert_eq!(bf.status.want, Want::Install);
+    assert_eq!(bf.status.flag, Flag::Ok);
+    assert_eq!(bf.status.state, PackageState::Installed);
+    assert_eq!(bf.version, Some(Version::parse("12.4+deb12u5").unwrap()));
+    assert_eq!(bf.get_field("Essential"), Some("yes"));
+    assert_eq!(bf.conffiles.len(), 3);
+    assert_eq!(bf.conffiles[0].path, "/etc/debian_version");
+    assert!(!bf.conffiles[1].obsolete);
+    assert!(bf.conffiles[2].obsolete);
+
+    assert_eq!(status.get_all("libc6").count(), 2);
+    let libc = status.get_arch("libc6", "i386").unwrap();
+    assert_eq!(libc.get_field("Installed-Size"), Some("12577"));
+    assert!(status.get_arch("libc6", "arm64").is_none());
+
+    let old = status.get("oldpkg").unwrap();
+    assert_eq!(old.status.to_string(), "deinstall ok config-files");
+    assert_eq!(status.installed().count(), 3);
+    assert!(status.get("missing").is_none());
+
+    assert!("install ok".parse::<Status>().is_err());
+    assert!("install bad installed".parse::<Status>().is_err());
+}
+
+#[test]
+fn apt_extended_states() {
+    let path = data_path().join("extended-states");
+    let states = ExtendedStates::from_file(&path).unwrap();
+    assert_eq!(states.iter().count(), 4);
+    assert!(states.is_auto_installed("libc6", "amd64"));
+    assert!(states.is_auto_installed("libc6", "i386"));
+    assert!(!states.is_auto_installed("libc6", "arm64"));
+    assert!(!states.is_auto_installed("oldpkg", "all"));
+    assert!(states.get("oldpkg", "all").is_some());
+
+    let status = DpkgStatus::from_file(&data_path().join("dpkg-status"));
+    let status = status.unwrap();
+    let removable = states.auto_removable(&status);
+    let mut names = removable
+        .iter()
+        .map(|e| e.architecture.clone().unwrap())
+        .collect::<Vec<String>>();
+    names.sort();
+    assert_eq!(names, vec!["amd64", "i386"]);
+    assert!(removable.iter().all(|e| e.package == "libc6"));
+}
+
+#[test]
+fn apt_preferences() {
+    assert!(glob_match("linux-image-*", "linux-image-amd64"));
+    assert!(glob_match("lib?[0-9]", "libc6"));
+    assert!(!glob_match("lib?[!0-9]", "libc6"));
+    assert!(!glob_match("perl*", "libperl5"));
+
+    let prefs = Preferences::from_dir(&data_path().join("apt")).unwrap();
+    assert_eq!(prefs.get_stanzas().len(), 4);
+
+    let v = Version::parse("6.5.10-1~bpo12+1").unwrap();
+    let bpo = ReleaseInfo {
+        origin: Some("Debian".to_string()),
+        archive: Some("stable-backports".to_string()),
+        codename: Some("bookworm-backports".to_string()),
+        not_automatic: true,
+        but_automatic_upgrades: true,
+        ..Default::default()
+    };
+    assert_eq!(prefs.pin_priority("linux-image-amd64", &v, &bpo), 600);
+    assert_eq!(prefs.pin_priority("firmware-misc-nonfree", &v, &bpo), 600);
+    assert_eq!(prefs.pin_priority("bash", &v, &bpo), 100);
+
+    let sid = ReleaseInfo {
+        origin: Some("Debian".to_string()),
+        archive: Some("unstable".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(prefs.pin_priority("bash", &v, &sid), 50);
+    let mesa = Version::parse("23.2.1-1").unwrap();
+    let p = prefs.pin_priority_with_source("libgl1", Some("mesa"), &mesa, &sid);
+    assert_eq!(p, 1001);
+    assert_eq!(prefs.pin_priority("libgl1", &mesa, &sid), 50);
+
+    let local = ReleaseInfo {
+        site: Some("repo.example.org".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(prefs.pin_priority("bash", &v, &local), -1);
+    let target = ReleaseInfo {
+        is_target_release: true,
+        ..Default::default()
+    };
+    assert_eq!(prefs.pin_priority("bash", &v, &target), 990);
+
+    // round trip
+    let again: Preferences = prefs.to_string().parse().unwrap();
+    assert_eq!(again.to_string(), prefs.to_string());
+    assert!("Package: foo\nPin: bogus\nPin-Priority: 1\n"
+        .parse::<Preferences>()
+        .is_err());
+}
+
+#[test]
+fn apt_sources() {
+    let list = SourcesList::from_dir(&data_path().join("apt")).unwrap();
+    assert_eq!(list.entries().len(), 5);
+
+    let sec = &list.entries()[2];
+    assert_eq!(sec.types, vec![SourceType::Deb]);
+    assert_eq!(sec.suites, vec!["bookworm-security"]);
+    assert_eq!(sec.get_option("Architectures"), Some("amd64 i386"));
+    assert_eq!(
+        sec.get_option("signed-by"),
+        Some("/usr/share/keyrings/debian.gpg")
+    );
+
+    let local = &list.entries()[3];
+    assert_eq!(local.types, vec![SourceType::Deb, SourceType::DebSrc]);
+    assert_eq!(local.suites.len(), 2);
+    assert!(local
+        .get_option("Signed-By")
+        .unwrap()
+        .starts_with("-----BEGIN PGP PUBLIC KEY BLOCK-----"));
+    assert!(local.to_lines().is_err());
+
+    let flat = &list.entries()[4];
+    assert!(!flat.enabled);
+    assert!(flat.components.is_empty());
+    assert_eq!(flat.to_lines().unwrap(), vec!["# deb file:/srv/repo ./"]);
+
+    // one-line to deb822 and back
+    let one_line =
+        SourcesList::from_file(&data_path().join("apt/sources.list"));
+    let one_line = one_line.unwrap();
+    let deb822 = SourcesList::parse_deb822(&one_line.to_deb822()).unwrap();
+    assert_eq!(deb822.entries(), one_line.entries());
+    assert_eq!(
+        deb822.to_list().unwrap().lines().nth(2).unwrap(),
+        "deb [arch=amd64,i386 signed-by=/usr/share/keyrings/debian.gpg] \
+         http://security.debian.org/debian-security bookworm-security main"
+    );
+
+    // multiple suites expand to multiple lines
+    let mut e = SourceEntry::parse_line("deb http://a/ sid main").unwrap();
+    e.suites.push("experimental".to_string());
+    assert_eq!(e.to_lines().unwrap().len(), 2);
+
+    assert!(SourceEntry::parse_line("deb http://a/ sid").is_err());
+    assert!(SourceEntry::parse_line("rpm http://a/ sid main").is_err());
+    assert!(SourceEntry::parse_line("deb [arch=amd64 http://a/").is_err());
+}
+
+#[test]
+fn copyright_dep5() {
+    assert!(files_pattern_match("*", "src/a/b.c"));
+    assert!(files_pattern_match("src/*.c", "src/a/b.c"));
+    assert!(files_pattern_match("src/?.c", "./src/b.c"));
+    assert!(!files_pattern_match("src/\\*.c", "src/b.c"));
+    assert!(files_pattern_match("src/\\*.c", "src/*.c"));
+
+    let path = data_path().join("copyright-foo");
+    let c = Copyright::from_file(&path).unwrap();
+    assert_eq!(c.header.upstream_name.as_deref(), Some("foo"));
+    assert_eq!(c.header.files_excluded, vec!["doc/*.pdf", "vendor/*"]);
+    assert_eq!(c.files.len(), 4);
+    assert_eq!(c.licenses.len(), 2);
+    assert_eq!(c.files[0].copyright.len(), 2);
+    assert_eq!(
+        c.files[1].comment.as_deref(),
+        Some("Taken from the goofy project.\n\nModified slightly.")
+    );
+
+    let name = |p| c.license_for_path(p).map(|l| l.name.as_str());
+    assert_eq!(name("README"), Some("GPL-2+"));
+    assert_eq!(name("src/contrib/x/y.c"), Some("BSD-3-clause"));
+    assert_eq!(name("src/extra.c"), Some("BSD-3-clause"));
+    assert_eq!(name("src/contrib/legacy*.c"), Some("public-domain"));
+    assert_eq!(name("debian/rules"), Some("GPL-2+"));
+
+    let gpl = c.license_text("GPL-2+").unwrap();
+    assert!(gpl.starts_with("This program is free software"));
+    assert!(gpl.contains("any later version.\n\nOn Debian systems"));
+    assert_eq!(
+        c.license_text("public-domain"),
+        Some("This file is in the public domain.")
+    );
+    assert_eq!(c.license_text("MIT"), None);
+
+    // round trip
+    let again: Copyright = c.to_string().parse().unwrap();
+    assert_eq!(again, c);
+
+    assert!("Files: *\nCopyright: x\nLicense: y\n"
+        .parse::<Copyright>()
+        .is_err());
+}
+
+#[test]
+fn watch_file() {
+    let w = WatchFile::from_file(&data_path().join("watch-v4")).unwrap();
+    assert_eq!(w.version, 4);
+    assert_eq!(w.entries.len(), 2);
+
+    let e = &w.entries[0];
+    assert_eq!(e.url, "https://github.com/foo/foo/tags");
+    assert_eq!(e.matching_pattern, r".*/v?(\d\S+)\.tar\.gz");
+    assert_eq!(e.version_policy.as_deref(), Some("debian"));
+    assert_eq!(e.script.as_deref(), Some("uupdate"));
+    assert_eq!(e.options.len(), 2);
+    assert_eq!(e.mangle_upstream_version("2.0RC1").unwrap(), "2.0~rc1");
+    assert_eq!(e.mangle_upstream_version("2.0-beta").unwrap(), "2.0~beta");
+    assert_eq!(
+        e.mangle_filename("https://github.com/foo/foo/v1.2.tar.gz")
+            .unwrap(),
+        "foo-1.2.tar.gz"
+    );
+
+    let docs = &w.entries[1];
+    assert!(docs.pattern_in_url);
+    assert_eq!(docs.url, "https://example.org/files/");
+    assert_eq!(docs.component(), Some("docs"));
+    assert!(docs.has_option("repack"));
+    assert_eq!(docs.version_policy.as_deref(), Some("same"));
+
+    // round trips, including conversion to version 5
+    let again: WatchFile = w.to_string().parse().unwrap();
+    assert_eq!(again, w);
+    let mut v5 = w.clone();
+    v5.version = 5;
+    let again: WatchFile = v5.to_string().parse().unwrap();
+    assert_eq!(again.entries[1].get_option("component"), Some("docs"));
+    assert!(again.entries[1].has_option("repack"));
+
+    let w = WatchFile::from_file(&data_path().join("watch-v5")).unwrap();
+    assert_eq!(w.version, 5);
+    let e = &w.entries[0];
+    assert_eq!(e.get_option("pgpmode"), Some("none"));
+    assert_eq!(e.mangle_upstream_version("1.0-rc2").unwrap(), "1.0~rc2");
+    assert_eq!(w.to_string().parse::<WatchFile>().unwrap(), w);
+
+    let rule: MangleRule = "s/\\+ds\\d*$//".parse().unwrap();
+    assert_eq!(rule.apply("1.2+ds1"), "1.2");
+    assert!("x/a/b/".parse::<MangleRule>().is_err());
+    assert!("version=4\nhttps://example.org/\n"
+        .parse::<WatchFile>()
+        .is_err());
+}
+
+#[cfg(feature = "yaml")]
+#[test]
+fn upstream_metadata() {
+    let path = data_path().join("upstream-metadata");
+    let m = UpstreamMetadata::from_file(&path).unwrap();
+    assert_eq!(m.archive.as_deref(), Some("GitHub"));
+    assert_eq!(
+        m.bug_database.as_deref(),
+        Some("https://github.com/foo/foo/issues")
+    );
+    assert_eq!(m.get("Security-Contact"), Some("security@foo.example.org"));
+    assert_eq!(m.registry.len(), 2);
+    assert_eq!(m.registry[0].name, "PyPI");
+    assert_eq!(m.reference.len(), 1);
+    assert!(m.reference[0].contains(&("Year".to_string(), "2020".to_string())));
+
+    let issues = m.validate();
+    assert_eq!(issues.len(), 2);
+    assert!(issues.contains(&MetadataIssue::DeprecatedField("Name".into())));
+    assert!(issues.contains(&MetadataIssue::UnknownField("X-Custom".into())));
+
+    let again: UpstreamMetadata = m.to_string().parse().unwrap();
+    assert_eq!(again, m);
+
+    let bad: UpstreamMetadata =
+        "Registry: foo\nChangelog: NEWS\n".parse().unwrap();
+    assert_eq!(
+        bad.validate(),
+        vec![
+            MetadataIssue::InvalidValue("Registry".into()),
+            MetadataIssue::InvalidValue("Changelog".into())
+        ]
+    );
+    assert!("- a list".parse::<UpstreamMetadata>().is_err());
+}
+
+#[test]
+fn source_format_and_options() {
+    let debian_dir = data_path().join("srcpkg").join("debian");
+    let format = SourceFormat::from_dir(&debian_dir).unwrap();
+    assert_eq!(format, SourceFormat::Quilt);
+    assert!(format.uses_quilt() && format.is_v3());
+    assert_eq!(format.to_string(), "3.0 (quilt)");
+    let missing = SourceFormat::from_dir(&data_path()).unwrap();
+    assert_eq!(missing, SourceFormat::V1);
+    assert_eq!(
+        "3.0 (foo)".parse::<SourceFormat>().unwrap(),
+        SourceFormat::Other("3.0 (foo)".to_string())
+    );
+
+    let opts = SourceOptions::from_dir(&debian_dir).unwrap();
+    assert_eq!(opts.options().len(), 7);
+    assert_eq!(opts.compression(), Some("xz"));
+    assert_eq!(opts.compression_level(), Some(9));
+    assert_eq!(
+        opts.extend_diff_ignore(),
+        vec!["^\\.gitignore$", "(^|/)\\.pc/"]
+    );
+    assert!(opts.single_debian_patch());
+    assert!(opts.unapply_patches());
+    assert!(opts.abort_on_upstream_changes());
+    assert!(!opts.auto_commit());
+
+    let again: SourceOptions = opts.to_string().parse().unwrap();
+    assert_eq!(again, opts);
+    assert!("foo bar = 1".parse::<SourceOptions>().is_err());
+}
+
+#[test]
+fn patch_series_and_headers() {
+    let debian_dir = data_path().join("srcpkg").join("debian");
+    let series = PatchSeries::from_dir(&debian_dir).unwrap();
+    assert_eq!(
+        series.patches().collect::<Vec<&str>>(),
+        vec!["fix-build.patch", "legacy.diff"]
+    );
+    assert_eq!(series.strip_level("fix-build.patch"), Some(1));
+    assert_eq!(series.strip_level("legacy.diff"), Some(0));
+    assert_eq!(series.strip_level("disabled.patch"), None);
+    assert_eq!(
+        series.lines()[2],
+        SeriesLine::Patch {
+            name: "legacy.diff".to_string(),
+            options: vec!["-p0".to_string()],
+            comment: Some("from the old packaging".to_string()),
+        }
+    );
+    assert_eq!(series.to_string().parse::<PatchSeries>().unwrap(), series);
+    assert!("ok.patch\n../evil.patch -p0\n"
+        .parse::<PatchSeries>()
+        .is_err());
+    assert!(PatchSeries::parse("sub/dir..patch\n").is_ok());
+
+    let mut edited = series.clone();
+    assert!(edited.remove("legacy.diff"));
+    assert!(!edited.remove("legacy.diff"));
+    edited.push("new.patch");
+    assert_eq!(edited.patches().last(), Some("new.patch"));
+
+    let paths = series.patch_paths(&debian_dir.join("patches"));
+    let h = PatchHeader::from_file(&paths[0]).unwrap();
+    assert_eq!(h.description.as_deref(), Some("Fix the build with GCC 14"));
+    assert_eq!(
+        h.long_description.as_deref(),
+        Some(
+            "The build failed due to implicit function declarations.\n\n\
+             This adds the missing includes."
+        )
+    );
+    assert_eq!(h.author, vec!["Mickey Mouse <mouse@disney.com>"]);
+    assert_eq!(h.reviewed_by.len(), 1);
+    assert_eq!(h.bugs.len(), 2);
+    assert_eq!(h.bugs[1].0.as_deref(), Some("Debian"));
+    assert_eq!(h.last_update.as_deref(), Some("2024-03-01"));
+    assert!(h.is_forwarded());
+
+    let h = PatchHeader::from_file(&paths[1]).unwrap();
+    assert_eq!(h.description.as_deref(), Some("Use the legacy API"));
+    assert_eq!(h.author, vec!["Goofy <goofy@disney.com>"]);
+    assert_eq!(
+        h.long_description.as_deref(),
+        Some("Some more explanation, in free form.")
+    );
+    assert_eq!(h.extra[0].0, "Date");
+    assert!(!h.is_forwarded());
+}
+
+#[test]
+fn symbols_file() {
+    let path = data_path().join("symbols-libfoo");
+    let symbols = SymbolsFile::from_file(&path).unwrap();
+    assert_eq!(symbols.header, vec!["#include \"libfoo1.symbols.common\""]);
+    assert_eq!(symbols.libraries.len(), 2);
+
+    let lib = symbols.get_library("libfoo.so.1").unwrap();
+    assert_eq!(lib.dependency, "libfoo1 #MINVER#");
+    assert_eq!(lib.alternatives.len(), 1);
+    assert_eq!(lib.get_field("Build-Depends-Package"), Some("libfoo-dev"));
+    assert_eq!(lib.symbols().count(), 4);
+
+    let bar = lib.get_symbol("foo_bar@Base").unwrap();
+    assert!(bar.is_optional());
+    assert_eq!(bar.dep_id, 1);
+    assert_eq!(
+        lib.dependency_for(bar, "libfoo1").unwrap(),
+        "libfoo1-extra (>= 1.1), libfoo1 (>= 1.2)"
+    );
+    let cxx = lib.get_symbol("foo::bar()@Base").unwrap();
+    assert_eq!(cxx.get_tag("arch"), Some("amd64 i386"));
+    assert!(cxx.has_tag("c++"));
+    let old = lib.get_symbol("foo_old@Base").unwrap();
+    assert_eq!(old.missing.as_deref(), Some("1.3"));
+
+    let util = symbols.get_library("libfoo-util.so.0").unwrap();
+    let run = util.get_symbol("util_run@Base").unwrap();
+    assert_eq!(util.dependency_for(run, "x").unwrap(), "libfoo-util0");
+
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(symbols.to_string(), text);
+    assert!(SymbolEntry::parse("foo@Base").is_err());
+    assert!(" foo@Base 1.0\n".parse::<SymbolsFile>().is_err());
+}
+
+#[test]
+fn triggers_file() {
+    let triggers = Triggers::from_file(&data_path().join("triggers")).unwrap();
+    assert_eq!(triggers.triggers().len(), 4);
+    assert_eq!(
+        triggers.interests().collect::<Vec<_>>(),
+        vec!["/usr/share/icons/hicolor", "update-foo-cache"]
+    );
+    assert_eq!(
+        triggers.activations().collect::<Vec<_>>(),
+        vec!["ldconfig", "foo-registry"]
+    );
+    let first = &triggers.triggers()[0];
+    assert!(first.is_file_trigger());
+    assert!(!first.awaits());
+    assert_eq!(
+        triggers.triggers()[1],
+        Trigger::Interest("update-foo-cache".to_string())
+    );
+    assert_eq!(
+        triggers.to_string(),
+        "interest-noawait /usr/share/icons/hicolor\n\
+         interest update-foo-cache\n\
+         activate-noawait ldconfig\n\
+         activate foo-registry\n"
+    );
+
+    assert!("interest".parse::<Trigger>().is_err());
+    assert!("interested foo".parse::<Trigger>().is_err());
+    assert!("activate foo bar".parse::<Trigger>().is_err());
+    assert!("activate f\u{e4}h".parse::<Trigger>().is_err());
+}
+
+#[test]
+fn conffiles() {
+    let path = data_path().join("conffiles");
+    let old = Conffiles::from_file(&path).unwrap();
+    assert_eq!(old.conffiles().len(), 3);
+    assert_eq!(old.conffiles()[0], Conffile::new("/etc/foo/foo.conf"));
+    assert!(old.get("/etc/foo/legacy.conf").unwrap().remove_on_upgrade);
+    assert!(!old.ships("/etc/foo/legacy.conf"));
+    assert!(old.ships("/etc/foo/with space.conf"));
+    assert_eq!(old.to_string(), std::fs::read_to_string(&path).unwrap());
+
+    let mut new = old.clone();
+    new.add_conffile("remove-on-upgrade /etc/foo/foo.conf".parse().unwrap());
+    new.add_conffile(Conffile::new("/etc/foo/new.conf"));
+    new.add_conffile(Conffile::new("/etc/foo/legacy.conf"));
+    let mut newer = Conffiles::default();
+    newer.add_conffile(Conffile::new("/etc/foo/new.conf"));
+
+    let diff = old.diff(&new);
+    assert_eq!(diff.added, ["/etc/foo/new.conf", "/etc/foo/legacy.conf"]);
+    assert!(diff.dropped.is_empty());
+    assert_eq!(diff.removed_on_upgrade, ["/etc/foo/foo.conf"]);
+    let diff = new.diff(&newer);
+    assert!(diff.added.is_empty());
+    assert_eq!(
+        diff.dropped,
+        ["/etc/foo/with space.conf", "/etc/foo/legacy.conf"]
+    );
+    assert!(old.diff(&old).added.is_empty());
+
+    assert!("etc/foo.conf".parse::<Conffiles>().is_err());
+    assert!("obsolete /etc/foo.conf".parse::<Conffiles>().is_err());
+    assert!("remove-on-upgrade foo.conf".parse::<Conffiles>().is_err());
+}
+
+#[test]
+fn debconf_templates() {
+    let path = data_path().join("templates-foo");
+    let templates = Templates::from_file(&path).unwrap();
+    assert_eq!(templates.templates().len(), 2);
+    assert_eq!(templates.languages(), vec!["de.UTF-8"]);
+
+    let enable = templates.get("foo/enable").unwrap();
+    assert_eq!(enable.template_type, TemplateType::Boolean);
+    assert_eq!(enable.default.as_deref(), Some("true"));
+    assert_eq!(enable.description, "Enable the foo daemon?");
+    assert_eq!(
+        enable.extended_description.as_deref(),
+        Some(
+            "The foo daemon can be started automatically at boot.\n\
+             \n\
+             You can change this later by running dpkg-reconfigure."
+        )
+    );
+    assert_eq!(enable.description_for("de").0, "Den foo-Daemon aktivieren?");
+    assert_eq!(enable.description_for("fr").0, "Enable the foo daemon?");
+
+    let backend = templates.get("foo/backend").unwrap();
+    assert_eq!(backend.template_type, TemplateType::Select);
+    assert_eq!(
+        backend.choices,
+        vec!["sqlite", "postgresql", "none, really"]
+    );
+    assert_eq!(backend.choices_for("de.UTF-8")[2], "keines");
+    assert_eq!(backend.description, "Database backend:");
+    assert_eq!(backend.extended_description, None);
+
+    let reparsed: Templates = templates.to_string().parse().unwrap();
+    assert_eq!(reparsed, templates);
+    assert_eq!(split_choices("a\\, b, c"), vec!["a, b", "c"]);
+    assert!("Template: foo/bar\nType: note\n"
+        .parse::<Templates>()
+        .is_err());
+}
+
+#[cfg(feature = "deb")]
+#[test]
+fn deb_reader() {
+    for (name, compression) in [
+        ("foo-xz.deb", Compression::Xz),
+        ("foo-gz.deb", Compression::Gzip),
+        ("foo-zst.deb", Compression::Zstd),
+    ] {
+        let deb =
+            DebFile::from_file(&data_path().join("deb").join(name)).unwrap();
+        assert_eq!(deb.format_version(), "2.0");
+        assert_eq!(deb.control_compression(), compression);
+        assert_eq!(deb.data_compression(), compression);
+
+        let control = deb.control().unwrap();
+        assert_eq!(control.get_entry("Package"), Some("foo"));
+        assert_eq!(control.get_entry("Version"), Some("1.0-1"));
+        let conffiles = deb.conffiles().unwrap();
+        assert_eq!(
+            conffiles.shipped().collect::<Vec<_>>(),
+            ["/etc/foo/foo.conf"]
+        );
+        let md5sums = deb.md5sums().unwrap();
+        assert_eq!(md5sums.files().len(), 3);
+        assert_eq!(
+            md5sums.get("etc/foo/foo.conf"),
+            Some("d5ca556a6ebec84e6b64090c4180571d")
+        );
+        let postinst = deb.maintainer_script(ScriptKind::Postinst).unwrap();
+        assert!(postinst.contents.contains("set -e"));
+        assert!(deb.maintainer_script(ScriptKind::Preinst).is_none());
+        assert!(deb.control_file_names().any(|n| n == "prerm"));
+
+        let mut data = deb.data_tar().unwrap();
+        let paths: Vec<String> = data
+            .entries()
+            .unwrap()
+            .map(|e| e.unwrap().path().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert!(paths.contains(&"./usr/bin/foo".to_string()));
+    }
+
+    assert!(DebFile::from_reader(&b"!<arch>\n"[..]).is_err());
+    assert!(DebFile::from_file(&data_path().join("control-foo")).is_err());
+}
+
+#[cfg(feature = "deb")]
+#[test]
+fn deb_builder() {
+    let deb = DebFile::from_file(&data_path().join("deb").join("foo-xz.deb"))
+        .unwrap();
+    let mut control = deb.control().unwrap();
+    control.update_entry("Version", "1.0-2".to_string());
+    let mut builder = DebBuilder::new(control.clone()).unwrap();
+    builder.set_compression(Compression::Zstd);
+    builder.set_mtime(1700000000);
+    builder.add_maintainer_script(ScriptKind::Postinst, "#!/bin/sh\nset -e\n");
+    builder
+        .add_control_file("conffiles", b"/etc/foo/foo.conf\n".to_vec(), 0o644)
+        .unwrap();
+    builder
+        .add_file("/etc/foo/foo.conf", b"enabled = true\n".to_vec(), 0o644)
+        .unwrap();
+    builder
+        .add_file("usr/bin/foo", b"#!/bin/sh\necho foo\n".to_vec(), 0o755)
+        .unwrap();
+    builder.add_symlink("/usr/bin/bar", "foo").unwrap();
+    builder.add_dir("/var/lib/foo", 0o750).unwrap();
+    assert!(builder.set_owner("/var/lib/foo", "foo", 100, "foo", 101));
+    assert!(!builder.set_owner("/var/lib/bar", "foo", 100, "foo", 101));
+    assert!(builder.add_file("../etc/passwd", vec![], 0o644).is_err());
+    assert!(builder.add_file("/usr/bin/foo/x", vec![], 0o644).is_err());
+    assert_eq!(builder.installed_size(), 10);
+
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("foo.deb");
+    builder.to_file(&path).unwrap();
+    let mut again = vec![];
+    builder.write(&mut again).unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), again);
+
+    let built = DebFile::from_file(&path).unwrap();
+    assert_eq!(built.data_compression(), Compression::Zstd);
+    let built_control = built.control().unwrap();
+    assert_eq!(built_control.get_entry("Version"), Some("1.0-2"));
+    assert_eq!(built_control.get_entry("Installed-Size"), Some("12"));
+    assert!(built.conffiles().unwrap().ships("/etc/foo/foo.conf"));
+    let md5sums = built.md5sums().unwrap();
+    assert_eq!(md5sums, builder.md5sums().unwrap());
+    assert_eq!(
+        md5sums.to_string(),
+        "8e74b6cfdf9ef1dd17f6bdedd95016a5  usr/bin/foo\n"
+    );
+    assert_eq!(
+        built
+            .maintainer_script(ScriptKind::Postinst)
+            .unwrap()
+            .contents,
+        "#!/bin/sh\nset -e\n"
+    );
+
+    let mut data = built.data_tar().unwrap();
+    let entries: Vec<(String, u32, u64, u64)> = data
+        .entries()
+        .unwrap()
+        .map(|e| {
+            let e = e.unwrap();
+            let h = e.header();
+            (
+                e.path().unwrap().to_string_lossy().to_string(),
+                h.mode().unwrap(),
+                h.uid().unwrap(),
+                h.mtime().unwrap(),
+            )
+        })
+        .collect();
+    let paths: Vec<&str> = entries.iter().map(|e| e.0.as_str()).collect();
+    assert_eq!(
+        paths,
+        [
+            "./",
+            "./etc/",
+            "./etc/foo/",
+            "./etc/foo/foo.conf",
+            "./usr/",
+            "./usr/bin/",
+            "./usr/bin/bar",
+            "./usr/bin/foo",
+            "./var/",
+            "./var/lib/",
+            "./var/lib/foo/",
+        ]
+    );
+    assert!(entries.contains(&(
+        "./usr/bin/foo".to_string(),
+        0o755,
+        0,
+        1700000000
+    )));
+    assert!(entries.contains(&(
+        "./var/lib/foo/".to_string(),
+        0o750,
+        100,
+        1700000000
+    )));
+
+    let mut incomplete = debian::package::ControlParagraph::default();
+    incomplete.add_entry("Package", "foo".to_string());
+    assert!(DebBuilder::new(incomplete).unwrap().write(vec![]).is_err());
+}
+
+#[cfg(feature = "deb")]
+#[test]
+fn deb_maintainer_scripts() {
+    let deb = DebFile::from_file(&data_path().join("deb").join("foo-gz.deb"))
+        .unwrap();
+    let scripts = deb.maintainer_scripts();
+    let kinds: Vec<ScriptKind> = scripts.iter().map(|s| s.kind).collect();
+    assert_eq!(kinds, [ScriptKind::Postinst, ScriptKind::Prerm]);
+    assert!(scripts[0].lint().is_empty());
+    assert_eq!(scripts[1].interpreter.as_deref(), Some("/bin/bash"));
+    assert_eq!(scripts[1].lint(), [ScriptIssue::MissingSetE]);
+}
+
+#[cfg(feature = "deb")]
+#[test]
+fn deb_data_entries() {
+    let deb = DebFile::from_file(&data_path().join("deb").join("foo-xz.deb"))
+        .unwrap();
+    let entries = deb.data_entries().unwrap();
+    let paths: Vec<&str> = entries.iter().map(|e| e.path.as_str()).collect();
+    assert_eq!(
+        paths,
+        [
+            "/",
+            "/etc",
+            "/etc/foo",
+            "/etc/foo/foo.conf",
+            "/usr",
+            "/usr/bin",
+            "/usr/bin/foo",
+            "/usr/share",
+            "/usr/share/doc",
+            "/usr/share/doc/foo",
+            "/usr/share/doc/foo/copyright",
+            "/usr/bin/bar",
+        ]
+    );
+    let foo = &entries[6];
+    assert_eq!(foo.entry_type, DataEntryType::File);
+    assert_eq!(foo.mode, 0o755);
+    assert_eq!((foo.user.as_str(), foo.uid), ("root", 0));
+    assert_eq!((foo.group.as_str(), foo.gid), ("root", 0));
+    assert_eq!(foo.size, 19);
+    assert_eq!(foo.mtime, 1700000000);
+    let bar = &entries[11];
+    assert_eq!(bar.entry_type, DataEntryType::Symlink);
+    assert_eq!(bar.link_target.as_deref(), Some("foo"));
+    assert_eq!(entries[1].entry_type, DataEntryType::Dir);
+
+    assert_eq!(
+        deb.read_data_file("/etc/foo/foo.conf").unwrap().unwrap(),
+        b"enabled = true\n"
+    );
+    assert_eq!(
+        deb.read_data_file("usr/bin/foo").unwrap().unwrap(),
+        b"#!/bin/sh\necho foo\n"
+    );
+    assert_eq!(deb.read_data_file("/usr/bin/bar").unwrap(), None);
+    assert_eq!(deb.read_data_file("/usr/bin/baz").unwrap(), None);
+
+    let dir = TempDir::new().unwrap();
+    let count = deb
+        .extract_data(dir.path(), |e| e.path.starts_with("/etc"))
+        .unwrap();
+    assert_eq!(count, 3);
+    let conf = dir.path().join("etc").join("foo").join("foo.conf");
+    assert_eq!(std::fs::read(conf).unwrap(), b"enabled = true\n");
+    assert!(!dir.path().join("usr").exists());
+}
+
+#[test]
+fn maintainer_script_checks() {
+    let script = |s: &str| MaintainerScript::new(ScriptKind::Postinst, s);
+    let good = script(
+        "#!/bin/sh\nset -e\n\n# [[ in a comment ]]\n\
+         if [ \"$1\" = configure ]; then\n    echo \"${FOO:-bar}\"\nfi\n",
+    );
+    assert!(good.is_posix_shell_script());
+    assert!(good.has_set_e());
+    assert!(good.lint().is_empty());
+    assert!(script("#!/bin/sh -e\ntrue\n").has_set_e());
+    assert!(script("#!/bin/sh\nset -eu\n").has_set_e());
+    assert!(script("#!/bin/sh\nset -o errexit\n").has_set_e());
+    assert!(!script("#!/bin/sh\nset -u\n").has_set_e());
+
+    let bad = script(
+        "#!/bin/sh\nfunction foo {\n    source /etc/default/foo\n}\n\
+         if [ \"$1\" == configure ]; then\n    echo -e \"${FOO:1}\" &> /dev/null\nfi\n",
+    );
+    assert_eq!(
+        bad.lint(),
+        vec![
+            ScriptIssue::MissingSetE,
+            ScriptIssue::Bashism(2, "function keyword".to_string()),
+            ScriptIssue::Bashism(3, "source instead of .".to_string()),
+            ScriptIssue::Bashism(5, "== in test".to_string()),
+            ScriptIssue::Bashism(6, "&> redirection".to_string()),
+            ScriptIssue::Bashism(6, "echo -e".to_string()),
+            ScriptIssue::Bashism(6, "substring expansion".to_string()),
+        ]
+    );
+
+    let bash = script("#!/bin/bash\nset -e\nif [[ -e /foo ]]; then :; fi\n");
+    assert!(bash.is_shell_script());
+    assert!(bash.lint().is_empty());
+    let perl = script("#!/usr/bin/perl\nprint 1;\n");
+    assert!(!perl.is_shell_script());
+    assert!(perl.lint().is_empty());
+    assert_eq!(
+        script("echo foo\n").lint(),
+        [ScriptIssue::MissingInterpreter]
+    );
+    assert_eq!("config".parse::<ScriptKind>(), Ok(ScriptKind::Config));
+    assert!("postinstall".parse::<ScriptKind>().is_err());
+}
+
+#[test]
+fn md5sums() {
+    let sums = Md5Sums::from_file(&data_path().join("md5sums")).unwrap();
+    assert_eq!(sums.files().len(), 3);
+    assert_eq!(
+        sums.get("usr/share/doc/foo/copyright"),
+        Some("d3b07384d113edec49eaa6238ad5ff00")
+    );
+    assert_eq!(
+        sums.to_string(),
+        std::fs::read_to_string(data_path().join("md5sums")).unwrap()
+    );
+    assert!("d3b07384  usr/bin/foo".parse::<Md5Sums>().is_err());
+    assert!("d3b07384d113edec49eaa6238ad5ff00"
+        .parse::<Md5Sums>()
+        .is_err());
+
+    let dir = TempDir::new().unwrap();
+    let root = dir.path();
+    std::fs::create_dir_all(root.join("DEBIAN")).unwrap();
+    std::fs::create_dir_all(root.join("usr/bin")).unwrap();
+    std::fs::write(root.join("DEBIAN/control"), "Package: foo\n").unwrap();
+    std::fs::write(root.join("usr/bin/foo"), "foo\n").unwrap();
+    std::fs::write(root.join("usr/bin/bar"), "bar\n").unwrap();
+    let mut generated = Md5Sums::from_tree(root).unwrap();
+    assert_eq!(
+        generated.to_string(),
+        format!(
+            "{}  usr/bin/bar\n{}  usr/bin/foo\n",
+            md5_hex(b"bar\n"),
+            md5_hex(b"foo\n")
+        )
+    );
+    assert!(generated.verify(root).unwrap().is_empty());
+
+    generated.add_file("/usr/bin/baz", &md5_hex(b"baz\n"));
+    std::fs::write(root.join("usr/bin/foo"), "changed\n").unwrap();
+    assert_eq!(
+        generated.verify(root).unwrap(),
+        [
+            Md5SumsIssue::Mismatch("usr/bin/foo".to_string()),
+            Md5SumsIssue::Missing("usr/bin/baz".to_string()),
+        ]
+    );
+    assert!(generated.remove_file("usr/bin/baz"));
+    assert!(!generated.remove_file("usr/bin/baz"));
+}
+
+#[test]
+fn archive_contents() {
+    let contents =
+        Contents::from_file(&data_path().join("Contents-amd64")).unwrap();
+    assert_eq!(contents.len(), 4);
+    assert_eq!(
+        contents.packages("/usr/bin/foo"),
+        Some(&vec![
+            "contrib/net/bar".to_string(),
+            "utils/foo".to_string()
+        ])
+    );
+    assert_eq!(
+        contents.packages("usr/share/fonts/My Font.ttf"),
+        Some(&vec!["non-free/fonts/fonts-mine".to_string()])
+    );
+    assert_eq!(contents.packages("usr/bin/bar"), None);
+    assert_eq!(
+        contents.paths_of("bash").collect::<Vec<_>>(),
+        ["bin/bash", "usr/share/doc/bash/README"]
+    );
+    let found: Vec<ContentsEntry> = contents.search("bin/").collect();
+    assert_eq!(found.len(), 2);
+    assert_eq!(found[1].package_names().collect::<Vec<_>>(), ["bar", "foo"]);
+
+    // the streaming parser yields lines as they are
+    let text = "usr/bin/foo  utils/foo\n\nusr/bin/bar\n";
+    let mut reader = ContentsReader::new(text.as_bytes());
+    let entry = reader.next().unwrap().unwrap();
+    assert_eq!(entry.path, "usr/bin/foo");
+    assert_eq!(entry.packages, ["utils/foo"]);
+    assert!(reader.next().unwrap().is_err());
+    assert!(reader.next().is_none());
+    assert!(text.parse::<Contents>().is_err());
+
+    let written = contents.to_string();
+    assert!(written
+        .starts_with(&format!("bin/bash{}shells/bash\n", " ".repeat(48))));
+    assert_eq!(written.parse::<Contents>().unwrap(), contents);
+}
+
+#[cfg(feature = "deb")]
+#[test]
+fn archive_contents_from_deb() {
+    let deb = DebFile::from_file(&data_path().join("deb").join("foo-xz.deb"))
+        .unwrap();
+    let mut contents = Contents::default();
+    contents.add_deb(&deb).unwrap();
+    contents.add_package("admin/baz", ["/usr/bin/bar"]);
+    let entries: Vec<String> =
+        contents.entries().map(|e| e.to_string()).collect();
+    assert_eq!(
+        entries
+            .iter()
+            .map(|e| e.split_whitespace().collect::<Vec<_>>())
+            .collect::<Vec<_>>(),
+        [
+            ["etc/foo/foo.conf", "utils/foo"],
+            ["usr/bin/bar", "admin/baz,utils/foo"],
+            ["usr/bin/foo", "utils/foo"],
+            ["usr/share/doc/foo/copyright", "utils/foo"],
+        ]
+    );
+}
+
+#[cfg(feature = "deb")]
+#[test]
+fn archive_packages_index() {
+    let dir = TempDir::new().unwrap();
+    let root = dir.path();
+    let pool = root.join("pool").join("main");
+    std::fs::create_dir_all(pool.join("f").join("foo")).unwrap();
+    std::fs::copy(
+        data_path().join("deb").join("foo-gz.deb"),
+        pool.join("f/foo/foo_1.0-1_all.deb"),
+    )
+    .unwrap();
+    std::fs::write(pool.join("f/foo/README"), "not a package").unwrap();
+
+    let deb = DebFile::from_file(&data_path().join("deb").join("foo-xz.deb"))
+        .unwrap();
+    let mut control = deb.control().unwrap();
+    control.update_entry("Version", "1.0~rc1-1".to_string());
+    let mut builder = DebBuilder::new(control.clone()).unwrap();
+    builder
+        .add_file("/usr/bin/foo", b"rc\n".to_vec(), 0o755)
+        .unwrap();
+    builder
+        .to_file(&pool.join("f/foo/foo_1.0~rc1-1_all.deb"))
+        .unwrap();
+    control.update_entry("Package", "bar".to_string());
+    builder = DebBuilder::new(control).unwrap();
+    builder
+        .to_file(&pool.join("bar_1.0~rc1-1_all.deb"))
+        .unwrap();
+
+    let index = PackagesIndex::scan(root, &pool).unwrap();
+    let summary: Vec<(&str, &str, &str)> = index
+        .paragraphs()
+        .iter()
+        .map(|p| {
+            (
+                p.get_entry("Package").unwrap(),
+                p.get_entry("Version").unwrap(),
+                p.get_entry("Filename").unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("bar", "1.0~rc1-1", "pool/main/bar_1.0~rc1-1_all.deb"),
+            ("foo", "1.0~rc1-1", "pool/main/f/foo/foo_1.0~rc1-1_all.deb"),
+            ("foo", "1.0-1", "pool/main/f/foo/foo_1.0-1_all.deb"),
+        ]
+    );
+    let foo = &index.paragraphs()[2];
+    assert_eq!(foo.get_entry("Size"), Some("1112"));
+    assert_eq!(
+        foo.get_entry("MD5sum"),
+        Some("edb7c213b9550926e9536a6cf3946d24")
+    );
+    assert_eq!(
+        foo.get_entry("SHA256"),
+        Some(
+            "4039f24c42641d639922ced2b57b3809b90cc8b5d674f71f13286ef0c93ad5d1"
+        )
+    );
+    assert!(PackagesIndex::scan(&root.join("dists"), &pool).is_err());
+
+    let out = root.join("dists/stable/main/binary-all");
+    let paths = index
+        .to_dir(&out, &[Compression::None, Compression::Gzip])
+        .unwrap();
+    assert_eq!(paths, [out.join("Packages"), out.join("Packages.gz")]);
+    let plain = std::fs::read(out.join("Packages")).unwrap();
+    assert_eq!(plain, index.to_string().into_bytes());
+    let mut unpacked = vec![];
+    let gz = std::fs::File::open(out.join("Packages.gz")).unwrap();
+    let mut decoder = Compression::Gzip.decoder(gz).unwrap();
+    std::io::Read::read_to_end(&mut decoder, &mut unpacked).unwrap();
+    assert_eq!(unpacked, plain);
+    let parsed = ControlFile::from_file(&out.join("Packages")).unwrap();
+    assert_eq!(parsed.get_paragraphs().len(), 3);
+    assert_eq!(
+        parsed.get_paragraphs()[2].get_entry("Description"),
+        foo.get_entry("Description")
+    );
+}
+
+#[test]
+fn archive_release() {
+    let dir = TempDir::new().unwrap();
+    let dist = dir.path().join("dists").join("stable");
+    std::fs::create_dir_all(dist.join("main/binary-amd64")).unwrap();
+    std::fs::write(dist.join("main/binary-amd64/Packages"), "").unwrap();
+    std::fs::write(dist.join("main/Contents-amd64"), "foo\n").unwrap();
+    std::fs::write(dist.join("InRelease"), "signed").unwrap();
+
+    let mut release = ReleaseBuilder::new().unwrap();
+    assert!(release.write(vec![]).is_err());
+    release.set_codename("bookworm");
+    release.set_suite("stable");
+    release.set_origin("Example");
+    release.set_label("Example");
+    release.set_description("Example packages");
+    release.add_architecture("amd64");
+    release.add_architecture("all");
+    release.add_component("main");
+    release.add_component("main");
+    let date = chrono::Utc.timestamp_opt(1700000000, 0).unwrap();
+    release.set_date(date);
+    release.set_valid_until(Some(date + chrono::Duration::days(7)));
+    release.add_index_files(&dist).unwrap();
+    let paths: Vec<&str> =
+        release.indices().iter().map(|i| i.path.as_str()).collect();
+    assert_eq!(paths, ["main/Contents-amd64", "main/binary-amd64/Packages"]);
+
+    release.to_file(&dist.join("Release")).unwrap();
+    let text = std::fs::read_to_string(dist.join("Release")).unwrap();
+    assert_eq!(
+        text,
+        "Origin: Example
+Label: Example
+Suite: stable
+Codename: bookworm
+Date: Tue, 14 Nov 2023 22:13:20 UTC
+Valid-Until: Tue, 21 Nov 2023 22:13:20 UTC
+Architectures: amd64 all
+Components: main
+Description: Example packages
+MD5Sum:
+ d3b07384d113edec49eaa6238ad5ff00                4 main/Contents-amd64
+ d41d8cd98f00b204e9800998ecf8427e                0 main/binary-amd64/Packages
+SHA256:
+ b5bb9d8014a0f9b1d61e21e796d78dccdf1352f23cd32812f4850b878ae4944c                4 main/Contents-amd64
+ e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855                0 main/binary-amd64/Packages
+"
+    );
+    // the Release file itself is not listed when regenerating
+    release.add_index_files(&dist).unwrap();
+    assert_eq!(release.indices().len(), 2);
+}
+
+#[test]
+fn archive_release_by_hash() {
+    let dir = TempDir::new().unwrap();
+    let dist = dir.path().join("dists").join("stable");
+    let binary = dist.join("main").join("binary-amd64");
+    std::fs::create_dir_all(&binary).unwrap();
+
+    let mut digests = vec![];
+    for generation in ["one", "two", "three"] {
+        // replace the index rather than writing through the link
+        std::fs::write(binary.join("Packages.new"), generation).unwrap();
+        std::fs::rename(binary.join("Packages.new"), binary.join("Packages"))
+            .unwrap();
+        let mut release = ReleaseBuilder::new().unwrap();
+        release.set_suite("stable");
+        release.set_acquire_by_hash(true);
+        release.add_index_files(&dist).unwrap();
+        assert_eq!(release.indices().len(), 1);
+        release.write_by_hash(&dist).unwrap();
+        release.to_file(&dist.join("Release")).unwrap();
+        digests.push(release.indices()[0].sha256.clone());
+        let removed = release.prune_by_hash(&dist, 1).unwrap();
+        match generation {
+            "three" => assert_eq!(
+                removed,
+                [binary.join("by-hash/SHA256").join(&digests[0])]
+            ),
+            _ => assert!(removed.is_empty()),
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    let by_hash = binary.join("by-hash").join("SHA256");
+    assert_eq!(std::fs::read_dir(&by_hash).unwrap().count(), 2);
+    assert_eq!(
+        std::fs::read_to_string(by_hash.join(&digests[1])).unwrap(),
+        "two"
+    );
+    assert_eq!(
+        std::fs::read_to_string(binary.join("Packages")).unwrap(),
+        "three"
+    );
+    #[cfg(unix)]
+    assert_eq!(
+        std::fs::read_link(binary.join("Packages")).unwrap(),
+        std::path::Path::new("by-hash/SHA256").join(&digests[2])
+    );
+
+    let release = Release::from_file(&dist.join("Release")).unwrap();
+    assert!(release.acquire_by_hash());
+    assert_eq!(release.suite(), Some("stable"));
+    let index = release.index("main/binary-amd64/Packages").unwrap();
+    assert_eq!(index.size, 5);
+    assert_eq!(
+        index.md5.as_deref(),
+        Some("35d6d33467aae9a2e3dccb4b6b027878")
+    );
+    assert_eq!(
+        release.fetch_path("main/binary-amd64/Packages"),
+        Some(format!("main/binary-amd64/by-hash/SHA256/{}", digests[2]))
+    );
+    assert_eq!(release.fetch_path("main/binary-i386/Packages"), None);
+}
+
+#[test]
+fn archive_release_parse() {
+    let release = Release::from_reader(
+        "Origin: Debian
+Suite: stable
+Codename: bookworm
+Date: Sat, 10 Feb 2024 09:47:39 UTC
+Valid-Until: Sat, 17 Feb 2024 09:47:39 UTC
+Architectures: all amd64 arm64
+Components: main contrib
+SHA256:
+ e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855        0 main/binary-all/Packages
+ b5bb9d8014a0f9b1d61e21e796d78dccdf1352f23cd32812f4850b878ae4944c 4 main/i18n/Translation-en
+"
+        .as_bytes(),
+    )
+    .unwrap();
+    assert_eq!(release.codename(), Some("bookworm"));
+    assert_eq!(release.components(), ["main", "contrib"]);
+    assert_eq!(release.architectures(), ["all", "amd64", "arm64"]);
+    assert_eq!(
+        release.date(),
+        chrono::Utc
+            .with_ymd_and_hms(2024, 2, 10, 9, 47, 39)
+            .single()
+    );
+    assert_eq!(
+        release.valid_until(),
+        chrono::Utc
+            .with_ymd_and_hms(2024, 2, 17, 9, 47, 39)
+            .single()
+    );
+    assert!(!release.acquire_by_hash());
+    assert_eq!(release.indices().len(), 2);
+    assert_eq!(release.indices()[1].md5, None);
+    assert_eq!(
+        release.fetch_path("main/i18n/Translation-en").as_deref(),
+        Some("main/i18n/Translation-en")
+    );
+    assert!(Release::from_reader("SHA256:\n abc x y\n".as_bytes()).is_err());
+}
+
+/// Serves files below a directory over HTTP for the given number of
+/// requests, returning the server's URL and a handle yielding the
+/// requested paths.
+#[test]
+fn resolver() {
+    let index =
+        ControlFile::from_file(&data_path().join("Packages-resolver")).unwrap();
+    let status =
+        DpkgStatus::from_file(&data_path().join("dpkg-status")).unwrap();
+    let mut resolver = Resolver::default();
+    resolver.add_index(&index).unwrap();
+    resolver.set_status(&status).unwrap();
+    assert_eq!(resolver.candidates("app").len(), 2);
+    assert_eq!(resolver.candidates("app")[0].version.to_string(), "2.0-1");
+    assert!(resolver.installed("libc6").is_some());
+
+    let resolve = |deps: &str| {
+        let deps = parse_dep_list(deps).unwrap();
+        resolver.resolve(&deps).map(|set| {
+            set.packages()
+                .map(|c| format!("{} {}", c.package, c.version))
+                .collect::<Vec<_>>()
+        })
+    };
+
+    // app 2.0 needs a libfoo1 requiring a newer libc6, and apache2 a
+    // version of apache2-bin that is not available
+    assert_eq!(
+        resolve("app").unwrap(),
+        ["app 1.0-1", "libfoo1 1.5-1", "nginx 1.22.1-9"]
+    );
+    // installed packages satisfy dependencies, but can be upgraded
+    assert_eq!(resolve("libc6").unwrap(), Vec::<String>::new());
+    let deps = parse_dep_list("libc6 (>> 2.36-9+deb12u4)").unwrap();
+    let set = resolver.resolve(&deps).unwrap();
+    assert_eq!(set.len(), 2);
+    let upgrades: Vec<&str> =
+        set.upgrades().map(|c| c.package.as_str()).collect();
+    assert_eq!(upgrades, ["libc6"]);
+    // a version selected earlier is kept
+    assert_eq!(
+        resolve("nginx, app (<< 2)").unwrap(),
+        ["app 1.0-1", "libfoo1 1.5-1", "nginx 1.22.1-9"]
+    );
+
+    // virtual packages, with versioned provides only satisfying
+    // versioned dependencies
+    assert_eq!(resolve("base").unwrap(), Vec::<String>::new());
+    assert_eq!(
+        resolve("mailer").unwrap(),
+        ["mailer 0.9-1", "postfix 3.7.10-0+deb12u1"]
+    );
+    assert_eq!(
+        resolve("plugin").unwrap(),
+        ["libbar1 3.0-1", "plugin 1.0-1"]
+    );
+    assert_eq!(resolve("libfoo-api").unwrap(), ["libbar1 3.0-1"]);
+    assert_eq!(
+        resolve("libfoo-ng, plugin").unwrap(),
+        ["libbar1 3.0-1", "libfoo-ng 1.0-1", "plugin 1.0-1"]
+    );
+    let dep = &parse_dep_list("libfoo-api").unwrap()[0].alternatives[0];
+    let providers: Vec<&str> = resolver
+        .providers(dep)
+        .iter()
+        .map(|c| c.package.as_str())
+        .collect();
+    assert_eq!(providers, ["libbar1", "libfoo-ng"]);
+    let libbar = &resolver.candidates("libbar1")[0];
+    assert_eq!(
+        libbar.provides,
+        [(
+            "libfoo-api".to_string(),
+            Some(Version::parse("1.8").unwrap())
+        )]
+    );
+    assert!(libbar.satisfies(dep));
+    let dep = &parse_dep_list("libfoo-api (>= 2)").unwrap()[0];
+    let err = resolver.resolve(std::slice::from_ref(dep)).unwrap_err();
+    assert_eq!(
+        err.reasons,
+        [UnsatisfiedReason::NoMatchingProvider(
+            "libfoo-api".to_string()
+        )]
+    );
+
+    let deps = parse_dep_list("libfoo1 (>= 2.0)").unwrap();
+    let err = resolver.resolve(&deps).unwrap_err();
+    assert_eq!(err.package.as_deref(), Some("libfoo1"));
+    assert_eq!(err.dependency.to_string(), "libc6 (>= 2.40)");
+    assert_eq!(
+        err.reasons,
+        [UnsatisfiedReason::NoMatchingVersion(
+            "libc6".to_string(),
+            vec![
+                Version::parse("2.36-9+deb12u7").unwrap(),
+                Version::parse("2.36-9+deb12u4").unwrap()
+            ]
+        )]
+    );
+    assert_eq!(
+        err.to_string(),
+        "libfoo1 depends on libc6 (>= 2.40), but libc6 is only available \
+         in 2.36-9+deb12u7, 2.36-9+deb12u4"
+    );
+
+    let deps = parse_dep_list("app (>= 3) | missing").unwrap();
+    let err = resolver.resolve(&deps).unwrap_err();
+    assert_eq!(err.package, None);
+    assert_eq!(
+        err.to_string(),
+        "app (>= 3) | missing is requested, but app is only available in \
+         2.0-1, 1.0-1 and missing is not available"
+    );
+}
+
+#[test]
+fn resolver_essential() {
+    let index = ControlFile::from_reader(
+        "Package: base-files\nVersion: 12.4\nEssential: yes\n\n\
+         Package: coreutils\nVersion: 9.1-1\nEssential: yes\n\
+         Pre-Depends: libc6 (>= 2.34)\n\n\
+         Package: dpkg\nVersion: 1.21.22\nEssential: yes\n\
+         Pre-Depends: libc6, tar\n\n\
+         Package: dpkg\nVersion: 1.21.1\n\n\
+         Package: tar\nVersion: 1.34\nEssential: no\nDepends: libc6\n\n\
+         Package: libc6\nVersion: 2.36-9\n\n\
+         Package: oldutils\nVersion: 2.0\n\n\
+         Package: oldutils\nVersion: 1.0\nEssential: yes\n\n\
+         Package: build-essential\nVersion: 12.9\n\
+         Depends: gcc, make, dpkg-dev\n\n\
+         Package: gcc\nVersion: 4:12.2.0-3\nDepends: cpp\n\n\
+         Package: cpp\nVersion: 4:12.2.0-3\n\n\
+         Package: make\nVersion: 4.3-4.1\n\n\
+         Package: dpkg-dev\nVersion: 1.21.22\nDepends: tar, make\n\n\
+         Package: hello\nVersion: 2.10-3\nDepends: libc6\n"
+            .as_bytes(),
+    )
+    .unwrap();
+    let mut resolver = Resolver::default();
+    resolver.add_index(&index).unwrap();
+
+    // only the newest version of a package counts
+    let essential: Vec<&str> = resolver
+        .essential_packages()
+        .iter()
+        .map(|c| c.package.as_str())
+        .collect();
+    assert_eq!(essential, ["base-files", "coreutils", "dpkg"]);
+
+    let set = resolver.essential_set().unwrap();
+    let names: Vec<&str> = set.packages().map(|c| c.package.as_str()).collect();
+    assert_eq!(names, ["base-files", "coreutils", "dpkg", "libc6", "tar"]);
+    let dpkg = set.packages().find(|c| c.package == "dpkg").unwrap();
+    assert_eq!(dpkg.version.to_string(), "1.21.22");
+
+    let set = resolver.build_essential_set().unwrap();
+    let names: Vec<&str> = set.packages().map(|c| c.package.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "base-files",
+            "build-essential",
+            "coreutils",
+            "cpp",
+            "dpkg",
+            "dpkg-dev",
+            "gcc",
+            "libc6",
+            "make",
+            "tar"
+        ]
+    );
+
+    // the build-essential set needs the metapackage
+    let mut resolver = Resolver::default();
+    let index = ControlFile::from_reader(
+        "Package: dpkg\nVersion: 1.21.22\nEssential: yes\n".as_bytes(),
+    )
+    .unwrap();
+    resolver.add_index(&index).unwrap();
+    assert_eq!(resolver.essential_set().unwrap().len(), 1);
+    let err = resolver.build_essential_set().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "build-essential is requested, but \
+         build-essential is not available"
+    );
+}
+
+#[test]
+fn package_universe() {
+    let parse = |text: &str| ControlFile::from_reader(text.as_bytes()).unwrap();
+    let mut universe = PackageUniverse::default();
+    let stable = universe.add_origin(Origin::new(
+        "bookworm",
+        ReleaseInfo {
+            archive: Some("stable".to_string()),
+            ..Default::default()
+        },
+    ));
+    let backports = universe.add_origin(Origin::new(
+        "bookworm-backports",
+        ReleaseInfo {
+            not_automatic: true,
+            but_automatic_upgrades: true,
+            ..Default::default()
+        },
+    ));
+    let local = universe.add_origin(
+        Origin::new("local", ReleaseInfo::default()).with_priority(1000),
+    );
+    assert_eq!(universe.origin(stable).priority, 500);
+    assert_eq!(universe.origin(backports).priority, 100);
+    assert_eq!(universe.origins().len(), 3);
+
+    universe
+        .add_packages(
+            stable,
+            &parse(
+                "Package: foo\nVersion: 1.0-1\nDepends: libfoo1\n\n\
+                 Package: libfoo1\nVersion: 1.0-1\n\n\
+                 Package: bar\nVersion: 3.0-1\n",
+            ),
+        )
+        .unwrap();
+    universe
+        .add_packages(
+            backports,
+            &parse(
+                "Package: foo\nVersion: 2.0-1~bpo12+1\n\n\
+                 Package: libfoo1\nVersion: 1.0-1\n",
+            ),
+        )
+        .unwrap();
+    universe
+        .add_packages(local, &parse("Package: bar\nVersion: 2.0-0local1\n"))
+        .unwrap();
+    universe
+        .add_sources(stable, &parse("Package: foo\nVersion: 1.0-1\n"))
+        .unwrap();
+    assert!(universe
+        .add_packages(stable, &parse("Package: broken\n"))
+        .is_err());
+
+    let describe = |entries: &[Entry]| -> Vec<String> {
+        entries
+            .iter()
+            .map(|e| {
+                let origin = &universe.origin(e.origin).name;
+                format!("{} {}", e.candidate.version, origin)
+            })
+            .collect()
+    };
+    assert_eq!(
+        describe(universe.candidates("foo")),
+        ["1.0-1 bookworm", "2.0-1~bpo12+1 bookworm-backports"]
+    );
+    assert_eq!(
+        describe(universe.candidates("bar")),
+        ["2.0-0local1 local", "3.0-1 bookworm"]
+    );
+    assert_eq!(
+        describe(universe.candidates("libfoo1")),
+        ["1.0-1 bookworm", "1.0-1 bookworm-backports"]
+    );
+    assert!(universe.candidates("baz").is_empty());
+    assert_eq!(universe.preferred("bar").unwrap().origin, local);
+    assert_eq!(
+        describe(universe.source_candidates("foo")),
+        ["1.0-1 bookworm"]
+    );
+    assert_eq!(
+        universe.packages().collect::<Vec<_>>(),
+        ["bar", "foo", "libfoo1"]
+    );
+    assert_eq!(universe.source_packages().collect::<Vec<_>>(), ["foo"]);
+    assert_eq!(universe.iter().count(), 6);
+    assert_eq!(universe.entries_of(backports).count(), 2);
+
+    let mut resolver = Resolver::default();
+    resolver.add_universe(&universe);
+    assert_eq!(resolver.candidates("foo").len(), 2);
+    assert_eq!(resolver.candidates("libfoo1").len(), 1);
+    let set = resolver
+        .resolve(&parse_dep_list("foo (<< 2)").unwrap())
+        .unwrap();
+    let names: Vec<&str> = set.packages().map(|c| c.package.as_str()).collect();
+    assert_eq!(names, ["foo", "libfoo1"]);
+}
+
+#[test]
+fn apt_policy() {
+    let parse = |text: &str| ControlFile::from_reader(text.as_bytes()).unwrap();
+    let release = Release::from_reader(
+        "Origin: Debian Backports\nLabel: Debian Backports\n\
+         Suite: stable-backports\nCodename: bookworm-backports\n\
+         NotAutomatic: yes\nButAutomaticUpgrades: yes\n"
+            .as_bytes(),
+    )
+    .unwrap();
+    let info = ReleaseInfo::from_release(&release);
+    assert_eq!(info.archive.as_deref(), Some("stable-backports"));
+    assert_eq!(info.codename.as_deref(), Some("bookworm-backports"));
+    assert_eq!(info.origin.as_deref(), Some("Debian Backports"));
+    assert!(info.not_automatic && info.but_automatic_upgrades);
+    assert_eq!(info.component, None);
+
+    let mut universe = PackageUniverse::default();
+    let stable = universe.add_origin(Origin::new(
+        "bookworm",
+        ReleaseInfo {
+            archive: Some("stable".to_string()),
+            ..Default::default()
+        },
+    ));
+    let backports =
+        universe.add_origin(Origin::new("bookworm-backports", info));
+    let experimental = universe.add_origin(Origin::new(
+        "experimental",
+        ReleaseInfo {
+            archive: Some("experimental".to_string()),
+            not_automatic: true,
+            ..Default::default()
+        },
+    ));
+    universe
+        .add_packages(
+            stable,
+            &parse(
+                "Package: foo\nSource: foo-src (1.0-1)\nVersion: 1.0-1\n\n\
+                 Package: bar\nVersion: 1.0\n",
+            ),
+        )
+        .unwrap();
+    universe
+        .add_packages(
+            backports,
+            &parse("Package: foo\nVersion: 2.0-1~bpo12+1\n"),
+        )
+        .unwrap();
+    universe
+        .add_packages(experimental, &parse("Package: foo\nVersion: 3.0-1\n"))
+        .unwrap();
+
+    let prefs = Preferences::default();
+    let mut policy = Policy::new(&universe, &prefs);
+    let summary = |policy: &Policy, package: &str| -> Vec<String> {
+        policy
+            .versions(package)
+            .iter()
+            .map(|v| {
+                let mark = if v.installed { " *" } else { "" };
+                format!("{} {}{}", v.version, v.priority, mark)
+            })
+            .collect()
+    };
+    assert_eq!(
+        summary(&policy, "foo"),
+        ["3.0-1 1", "2.0-1~bpo12+1 100", "1.0-1 500"]
+    );
+    assert_eq!(
+        policy.candidate_version("foo").unwrap().to_string(),
+        "1.0-1"
+    );
+    assert!(policy.candidate_version("baz").is_none());
+
+    // upgrades within backports, but no downgrade to stable
+    policy.add_installed("foo", Version::parse("1.9-1~bpo12+1").unwrap());
+    let candidate = policy.candidate("foo").unwrap();
+    assert_eq!(candidate.version.to_string(), "2.0-1~bpo12+1");
+    assert_eq!(candidate.entries.len(), 1);
+    assert_eq!(candidate.entries[0].0.origin, backports);
+    assert!(policy.is_upgradable("foo"));
+    policy.add_installed("foo", Version::parse("2.0-1~bpo12+1").unwrap());
+    assert_eq!(
+        summary(&policy, "foo"),
+        ["3.0-1 1", "2.0-1~bpo12+1 100 *", "1.0-1 500"]
+    );
+    assert_eq!(
+        policy.candidate_version("foo").unwrap().to_string(),
+        "2.0-1~bpo12+1"
+    );
+    assert!(!policy.is_upgradable("foo"));
+
+    // an installed version no longer available remains the candidate
+    policy.add_installed("qux", Version::parse("0.1").unwrap());
+    let candidate = policy.candidate("qux").unwrap();
+    assert!(candidate.installed && candidate.entries.is_empty());
+
+    // pins on source packages, releases and downgrades
+    let prefs: Preferences = "Package: src:foo-src\n\
+                              Pin: version 1.0*\n\
+                              Pin-Priority: 1001\n\n\
+                              Package: *\n\
+                              Pin: release a=experimental\n\
+                              Pin-Priority: 600\n\n\
+                              Package: bar\n\
+                              Pin: release a=stable\n\
+                              Pin-Priority: -1\n"
+        .parse()
+        .unwrap();
+    let mut policy = Policy::new(&universe, &prefs);
+    assert_eq!(
+        policy.candidate_version("foo").unwrap().to_string(),
+        "1.0-1"
+    );
+    assert!(policy.candidate_version("bar").is_none());
+    let status = DpkgStatus::from_reader(
+        "Package: foo\nStatus: install ok installed\nVersion: 3.0-1\n\n\
+         Package: bar\nStatus: install ok installed\nVersion: 1.0\n"
+            .as_bytes(),
+    )
+    .unwrap();
+    policy.set_status(&status);
+    assert_eq!(policy.installed("foo").unwrap().to_string(), "3.0-1");
+    assert_eq!(
+        summary(&policy, "foo"),
+        ["3.0-1 600 *", "2.0-1~bpo12+1 100", "1.0-1 1001"]
+    );
+    assert_eq!(
+        policy.candidate_version("foo").unwrap().to_string(),
+        "1.0-1"
+    );
+    assert_eq!(policy.candidate_version("bar").unwrap().to_string(), "1.0");
+    assert_eq!(summary(&policy, "bar"), ["1.0 100 *"]);
+}
+
+#[test]
+fn conflicts() {
+    let status =
+        DpkgStatus::from_file(&data_path().join("dpkg-status")).unwrap();
+    let planned = ControlFile::from_reader(
+        "Package: initscripts\nVersion: 2.88dsf-13\n\n\
+         Package: sendfile\nVersion: 2.1b.20080616-6\n\n\
+         Package: exim4\nVersion: 4.96-15\n\
+         Provides: mail-transport-agent\nConflicts: mail-transport-agent\n\n\
+         Package: postfix\nVersion: 3.7.10-0+deb12u1\n\
+         Provides: mail-transport-agent\nConflicts: mail-transport-agent\n\n\
+         Package: foo\nVersion: 1.0\n\n\
+         Package: bar\nVersion: 1.0\nReplaces: foo (<< 2)\n\n\
+         Package: baz\nVersion: 1.0\n"
+            .as_bytes(),
+    )
+    .unwrap();
+    let mut analyzer = ConflictAnalyzer::default();
+    analyzer.add_status(&status).unwrap();
+    assert!(analyzer.check().is_empty());
+    analyzer.add_index(&planned).unwrap();
+    assert_eq!(analyzer.packages().len(), 10);
+    analyzer.add_files("foo", ["/usr/bin/tool", "/usr/bin/foo"]);
+    analyzer.add_files("bar", ["./usr/bin/tool"]);
+    analyzer.add_files("baz", ["usr/bin/tool"]);
+    analyzer.add_files("libc6", ["/usr/share/doc/libc6/copyright"]);
+    analyzer.add_files("missing", ["/usr/bin/foo"]);
+
+    let issues = analyzer.check();
+    let messages: Vec<String> = issues.iter().map(|i| i.to_string()).collect();
+    assert_eq!(
+        messages,
+        [
+            "base-files breaks initscripts (initscripts (<< 2.88dsf-13.3))",
+            "exim4 conflicts with postfix (mail-transport-agent)",
+            "postfix conflicts with exim4 (mail-transport-agent)",
+            "/usr/bin/tool is shipped by both bar and baz",
+            "/usr/bin/tool is shipped by both baz and foo",
+        ]
+    );
+    assert_eq!(
+        issues[4],
+        ConflictIssue::FileOverwrite(
+            "usr/bin/tool".to_string(),
+            "baz".to_string(),
+            "foo".to_string()
+        )
+    );
+
+    // upgrading a package replaces the installed version
+    let upgrade = ControlFile::from_reader(
+        "Package: initscripts\nVersion: 3.0\n\n\
+         Package: baz\nVersion: 1.1\nReplaces: bar, foo\n"
+            .as_bytes(),
+    )
+    .unwrap();
+    analyzer.add_index(&upgrade).unwrap();
+    assert_eq!(analyzer.packages().len(), 10);
+    assert_eq!(analyzer.check().len(), 2);
+}
+
+#[test]
+fn builddep() {
+    assert!(arch_restriction_matches("amd64 i386", "i386"));
+    assert!(arch_restriction_matches("linux-any", "amd64"));
+    assert!(!arch_restriction_matches("linux-any", "hurd-i386"));
+    assert!(arch_restriction_matches("any-i386", "hurd-i386"));
+    assert!(!arch_restriction_matches("!hurd-any !amd64", "amd64"));
+    assert!(arch_restriction_matches("!hurd-any !amd64", "arm64"));
+    assert!(profile_restriction_matches(
+        &["!nocheck !cross"],
+        &["nodoc"]
+    ));
+    assert!(!profile_restriction_matches(
+        &["!nocheck !cross"],
+        &["cross"]
+    ));
+    assert!(profile_restriction_matches(
+        &["stage1"],
+        &["stage1", "cross"]
+    ));
+    assert!(profile_restriction_matches(
+        &["!nocheck", "cross"],
+        &["cross"]
+    ));
+    assert!(!profile_restriction_matches(&["stage1", "cross"], &[]));
+    assert!(profile_restriction_matches::<&str>(&[], &[]));
+
+    let dep = &parse_dep_list("foo (>= 1) <!nocheck>").unwrap()[0];
+    let foo = &dep.alternatives[0];
+    assert_eq!(foo.version.as_ref().unwrap().1.to_string(), "1");
+    assert_eq!(foo.condition.as_deref(), Some("!nocheck"));
+    assert!(foo.extra_conditions.is_empty());
+    assert_eq!(foo.to_string(), "foo (>= 1) <!nocheck>");
+    let text = "foo [amd64] <!nocheck> <stage1 cross>";
+    let dep = &parse_dep_list(text).unwrap();
+    let foo = &dep[0].alternatives[0];
+    assert_eq!(foo.arch.as_deref(), Some("amd64"));
+    assert_eq!(foo.condition.as_deref(), Some("!nocheck"));
+    assert_eq!(foo.extra_conditions, ["stage1 cross"]);
+    assert_eq!(foo.conditions(), ["!nocheck", "stage1 cross"]);
+    assert_eq!(foo.to_string(), text);
+    assert_eq!(parse_dep_list(&foo.to_string()).unwrap(), *dep);
+    assert!(parse_dep_list("foo <!nocheck> bar").is_err());
+    assert!(parse_dep_list("foo <>").is_err());
+
+    let index =
+        ControlFile::from_file(&data_path().join("Packages-resolver")).unwrap();
+    let mut resolver = Resolver::default();
+    resolver.add_index(&index).unwrap();
+    let source = ControlFile::from_reader(
+        "Source: app\n\
+         Build-Depends: libfoo1 (>= 2.0) | libfoo1 (<< 2), nginx [!hurd-any],\n \
+          missing-tool <!nocheck>, app [any-i386],\n\
+         Build-Depends-Indep: mailer\n"
+            .as_bytes(),
+    )
+    .unwrap();
+    let source = &source.get_paragraphs()[0];
+
+    let report = builddep_check(source, &resolver, "amd64", &[]).unwrap();
+    assert!(!report.is_installable());
+    assert_eq!(report.dependencies.len(), 4);
+    let alternatives = &report.dependencies[0].alternatives;
+    assert!(alternatives[0].1.is_some());
+    assert!(alternatives[1].1.is_none());
+    let unsatisfiable: Vec<String> = report
+        .unsatisfiable()
+        .map(|d| d.dependency.to_string())
+        .collect();
+    assert_eq!(unsatisfiable, ["missing-tool <!nocheck>"]);
+    let err = report.result.unwrap_err();
+    assert_eq!(err.package, None);
+    assert_eq!(err.dependency.to_string(), "missing-tool <!nocheck>");
+
+    let names = |arch: &str| {
+        let report =
+            builddep_check(source, &resolver, arch, &["nocheck"]).unwrap();
+        report
+            .result
+            .unwrap()
+            .packages()
+            .map(|c| c.package.clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        names("amd64"),
+        [
+            "libc6",
+            "libfoo1",
+            "libgcc-s1",
+            "mailer",
+            "nginx",
+            "postfix"
+        ]
+    );
+    assert_eq!(
+        names("hurd-i386"),
+        [
+            "app",
+            "libc6",
+            "libfoo1",
+            "libgcc-s1",
+            "mailer",
+            "nginx",
+            "postfix"
+        ]
+    );
+
+    let source = ControlFile::from_reader(
+        "Source: app\n\
+         Build-Depends: libfoo1 (<< 2) <!nocheck>,\n \
+          missing-tool <stage1> <cross>\n"
+            .as_bytes(),
+    )
+    .unwrap();
+    let source = &source.get_paragraphs()[0];
+    let report = builddep_check(source, &resolver, "amd64", &[]).unwrap();
+    assert!(report.is_installable());
+    assert_eq!(
+        report.dependencies[0].dependency.to_string(),
+        "libfoo1 (<< 2) <!nocheck>"
+    );
+    let report =
+        builddep_check(source, &resolver, "amd64", &["cross"]).unwrap();
+    assert!(!report.is_installable());
+    assert_eq!(report.dependencies.len(), 2);
+}
+
+#[test]
+fn apt_edsp() {
+    let path = data_path().join("edsp-scenario");
+    let scenario = EdspScenario::from_file(&path).unwrap();
+    let request = &scenario.request;
+    assert_eq!(request.version, "0.5");
+    assert_eq!(request.architecture.as_deref(), Some("amd64"));
+    assert_eq!(request.architectures, ["amd64", "i386"]);
+    assert_eq!(request.install, ["app:amd64"]);
+    assert!(request.remove.is_empty());
+    assert!(!request.upgrade_all);
+    assert!(request.autoremove);
+    assert!(request.strict_pinning);
+    assert_eq!(scenario.packages.len(), 4);
+    assert_eq!(scenario.installed().count(), 1);
+    assert_eq!(
+        scenario.package("2").unwrap().get_entry("APT-Release"),
+        Some("a=stable-security,n=bookworm-security,l=Debian-Security,c=main,b=amd64")
+    );
+    let written = scenario.to_string();
+    assert!(written.starts_with(
+        "Request: EDSP 0.5\nArchitecture: amd64\nArchitectures: amd64 i386\n\
+         Install: app:amd64\nAutoremove: yes\nSolver: internal\n\n\
+         Package: libc6\n"
+    ));
+    let parsed = EdspScenario::from_reader(written.as_bytes()).unwrap();
+    assert_eq!(parsed.request, scenario.request);
+    assert_eq!(parsed.to_string(), written);
+
+    // answering with the resolver, upgrading libc6
+    let resolver = scenario.resolver().unwrap();
+    let set = resolver
+        .resolve(&scenario.request.install_dependencies())
+        .unwrap();
+    let answer = EdspAnswer::from_installation_set(&set).unwrap();
+    let text = answer.to_string();
+    assert_eq!(
+        text,
+        "Install: 3\nPackage: app\nVersion: 1.0-1\nArchitecture: amd64\n\n\
+         Install: 2\nPackage: libc6\nVersion: 2.36-9+deb12u7\n\
+         Architecture: amd64\n\n\
+         Install: 4\nPackage: postfix\nVersion: 3.7.10-0+deb12u1\n\
+         Architecture: amd64\n"
+    );
+
+    let mut output = vec![];
+    write_progress(&mut output, 50, "Resolving").unwrap();
+    output.extend(text.as_bytes());
+    output.extend(b"\nAutoremove: 7\n");
+    match EdspAnswer::from_reader(output.as_slice()).unwrap() {
+        EdspAnswer::Solution(actions) => {
+            assert_eq!(actions.len(), 4);
+            assert_eq!(actions[1].kind, EdspActionKind::Install);
+            assert_eq!(actions[1].id, "2");
+            assert_eq!(actions[1].package.as_deref(), Some("libc6"));
+            assert_eq!(actions[3].kind, EdspActionKind::Autoremove);
+            assert_eq!(actions[3].package, None);
+        }
+        EdspAnswer::Error(..) => panic!("unexpected error"),
+    }
+
+    let error = EdspAnswer::Error(
+        "solver".to_string(),
+        "no solution\n\nfoo is not available".to_string(),
+    );
+    let text = error.to_string();
+    assert_eq!(
+        text,
+        "Error: solver\nMessage: no solution\n .\n foo is not available\n"
+    );
+    assert_eq!(EdspAnswer::from_reader(text.as_bytes()).unwrap(), error);
+
+    assert!(EdspScenario::from_reader("Package: foo\n".as_bytes()).is_err());
+    let request = "Request: EDSP 0.4\nUpgrade: yes\nStrict-Pinning: no\n";
+    let scenario = EdspScenario::from_reader(request.as_bytes()).unwrap();
+    assert_eq!(
+        scenario.request,
+        EdspRequest {
+            version: "0.4".to_string(),
+            upgrade_all: true,
+            strict_pinning: false,
+            forbid_new_install: true,
+            forbid_remove: true,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn ordering() {
+    let packages = ControlFile::from_reader(
+        "Package: a\nVersion: 1\nDepends: b\n\n\
+         Package: b\nVersion: 1\nDepends: a\n\n\
+         Package: c\nVersion: 1\nPre-Depends: d\n\n\
+         Package: d\nVersion: 1\nDepends: c\n\n\
+         Package: e\nVersion: 1\nPre-Depends: libx | liby\n\n\
+         Package: libx\nVersion: 1\nDepends: base-files\n"
+            .as_bytes(),
+    )
+    .unwrap();
+    let candidates: Vec<Candidate> = packages
+        .get_paragraphs()
+        .iter()
+        .map(|p| Candidate::from_paragraph(p.clone()).unwrap())
+        .collect();
+    let order = installation_order(candidates.iter().rev());
+    let steps: Vec<String> =
+        order.steps.iter().map(|s| s.to_string()).collect();
+    assert_eq!(
+        steps,
+        [
+            "unpack a",
+            "unpack b",
+            "unpack d",
+            "unpack c",
+            "configure c",
+            "configure d",
+            "unpack libx",
+            "configure a",
+            "configure b",
+            "configure libx",
+            "unpack e",
+            "configure e"
+        ]
+    );
+    assert!(matches!(order.steps[0], InstallStep::Unpack(_)));
+    assert_eq!(order.steps[4].candidate().package, "c");
+    assert_eq!(
+        order.cycles,
+        [
+            InstallCycle {
+                packages: vec!["c".to_string(), "d".to_string()],
+                pre_depends: true
+            },
+            InstallCycle {
+                packages: vec!["a".to_string(), "b".to_string()],
+                pre_depends: false
+            }
+        ]
+    );
+
+    // with the resolver, installed packages do not need to be ordered
+    let index =
+        ControlFile::from_file(&data_path().join("Packages-resolver")).unwrap();
+    let status =
+        DpkgStatus::from_file(&data_path().join("dpkg-status")).unwrap();
+    let mut resolver = Resolver::default();
+    resolver.add_index(&index).unwrap();
+    resolver.set_status(&status).unwrap();
+    let set = resolver.resolve(&parse_dep_list("app").unwrap()).unwrap();
+    let order = installation_order(set.packages());
+    let steps: Vec<String> =
+        order.steps.iter().map(|s| s.to_string()).collect();
+    assert_eq!(
+        steps,
+        [
+            "unpack app",
+            "unpack libfoo1",
+            "unpack nginx",
+            "configure libfoo1",
+            "configure nginx",
+            "configure app"
+        ]
+    );
+}
+
+#[test]
+fn lint() {
+    let linter = Linter::with_default_checks();
+    let path = data_path().join("control-foo");
+    assert!(linter
+        .check_file(TargetKind::Control, &path)
+        .unwrap()
+        .is_empty());
+
+    let control = "\
+Source: foo
+Maintainer: Jane Doe <jane@example.org>
+Maintainer: John Doe <john@example.org>
+
+Package: foo
+Architecture: any
+Description: an exceedingly verbose synopsis which goes on and on and on well beyond the limit
+ Long description.
+
+Package: foo-doc
+Description: documentation for foo
+";
+    let findings = linter.check(&LintTarget::Control(control));
+    let tags: Vec<(&str, Option<usize>)> =
+        findings.iter().map(|f| (f.tag, f.line)).collect();
+    assert_eq!(
+        tags,
+        [
+            ("no-standards-version", Some(1)),
+            ("duplicate-field", Some(3)),
+            ("synopsis-too-long", Some(7)),
+            ("missing-required-field", Some(10)),
+        ]
+    );
+    assert_eq!(findings[3].message, "Architecture");
+    assert_eq!(
+        findings[1].to_string(),
+        "E: line 3: duplicate-field Maintainer"
+    );
+    assert_eq!(findings[2].severity, Severity::Warning);
+
+    let dsc = "\
+-----BEGIN PGP SIGNED MESSAGE-----
+Hash: SHA256
+
+Format: 3.0 (quilt)
+Source: foo
+Version: 1.0-1
+Maintainer: Jane Doe <jane@example.org>
+Standards-Version: 4.6.2
+-----BEGIN PGP SIGNATURE-----
+
+iQIzBAEBCAAdFiEE
+-----END PGP SIGNATURE-----
+";
+    assert!(linter.check(&LintTarget::Dsc(dsc)).is_empty());
+    let findings = linter
+        .check(&LintTarget::Dsc(&dsc.replace("Format: 3.0 (quilt)\n", "")));
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].tag, "missing-required-field");
+    assert_eq!(findings[0].message, "Format");
+
+    let path = data_path().join("changelog-foo");
+    let findings = linter.check_file(TargetKind::Changelog, &path).unwrap();
+    assert_eq!(findings.len(), 2);
+    assert_eq!(findings[0].tag, "non-monotonic-changelog");
+    assert_eq!(findings[0].line, Some(7));
+    assert_eq!(findings[0].message, "1.0-2 is not newer than 1.1-1");
+    assert_eq!(findings[1].tag, "malformed-changelog-header");
+    assert_eq!(
+        findings[1].to_string(),
+        format!(
+            "E: {}:19: malformed-changelog-header this is not a header",
+            path.display()
+        )
+    );
+
+    let packages = ControlFile::from_reader(
+        "Package: foo\nVersion: 1.0\nArchitecture: amd64\nDescription: foo\n\n\
+         Package: foo\nVersion: 1.0\nArchitecture: amd64\nDescription: foo\n\n\
+         Package: bar\nArchitecture: all\nDescription: bar\n"
+            .as_bytes(),
+    )
+    .unwrap();
+    let findings =
+        linter.check(&LintTarget::PackageSet(packages.get_paragraphs()));
+    let tags: Vec<&str> = findings.iter().map(|f| f.tag).collect();
+    assert_eq!(tags, ["missing-required-field", "duplicate-package"]);
+    assert_eq!(findings[1].message, "foo 1.0 amd64");
+
+    #[derive(Debug)]
+    struct NoHomepage;
+    impl Check for NoHomepage {
+        fn applies_to(&self, kind: TargetKind) -> bool {
+            kind == TargetKind::Control
+        }
+        fn check(&self, target: &LintTarget, findings: &mut Vec<Finding>) {
+            if let LintTarget::Control(text) = target {
+                if !text.contains("\nHomepage:") {
+                    findings.push(Finding::new(
+                        "no-homepage",
+                        Severity::Info,
+                        None,
+                        String::new(),
+                    ));
+                }
+            }
+        }
+    }
+    let mut linter = Linter::default();
+    linter.add_check(NoHomepage);
+    assert!(linter.check(&LintTarget::Control(control)).len() == 1);
+    assert!(linter.check(&LintTarget::Dsc(control)).is_empty());
+}
+
+#[test]
+fn debian_architecture() {
+    let cases = [
+        ("amd64", "base-gnu-linux-amd64", "x86_64-linux-gnu", 64),
+        ("i386", "base-gnu-linux-i386", "i686-linux-gnu", 32),
+        ("armhf", "eabihf-gnu-linux-arm", "arm-linux-gnueabihf", 32),
+        ("armel", "eabi-gnu-linux-arm", "arm-linux-gnueabi", 32),
+        ("arm64", "base-gnu-linux-arm64", "aarch64-linux-gnu", 64),
+        ("x32", "x32-gnu-linux-amd64", "x86_64-linux-gnux32", 32),
+        (
+            "mips64el",
+            "abi64-gnu-linux-mips64el",
+            "mips64el-linux-gnuabi64",
+            64,
+        ),
+        (
+            "ppc64el",
+            "base-gnu-linux-ppc64el",
+            "powerpc64le-linux-gnu",
+            64,
+        ),
+        (
+            "musl-linux-armhf",
+            "eabihf-musl-linux-arm",
+            "arm-linux-musleabihf",
+            32,
+        ),
+        (
+            "musl-linux-amd64",
+            "base-musl-linux-amd64",
+            "x86_64-linux-musl",
+            64,
+        ),
+        ("hurd-i386", "base-gnu-hurd-i386", "i686-gnu", 32),
+        ("hurd-amd64", "base-gnu-hurd-amd64", "x86_64-gnu", 64),
+        (
+            "kfreebsd-amd64",
+            "base-gnu-kfreebsd-amd64",
+            "x86_64-kfreebsd-gnu",
+            64,
+        ),
+        (
+            "freebsd-arm64",
+            "base-bsd-freebsd-arm64",
+            "aarch64-freebsd",
+            64,
+        ),
+    ];
+    for (name, tuple, triplet, bits) in cases {
+        let arch = DebianArchitecture::parse(name).unwrap();
+        let (abi, libc, os, cpu) = arch.tuple();
+        assert_eq!([abi, libc, os, cpu].join("-"), tuple);
+        assert_eq!(arch.to_string(), name);
+        assert_eq!(arch.gnu_triplet(), triplet);
+        assert_eq!(arch.bits(), bits);
+        assert_eq!(DebianArchitecture::from_tuple(tuple), Ok(arch));
+        assert_eq!(DebianArchitecture::from_gnu_triplet(triplet), Ok(arch));
+    }
+    assert!(DebianArchitecture::parse("all").is_err());
+    assert!(DebianArchitecture::parse("linux-any").is_err());
+    assert!(DebianArchitecture::parse("vax").is_err());
+    assert!(DebianArchitecture::from_gnu_triplet("vax-linux-gnu").is_err());
+    for (triplet, name) in [
+        ("alphaev67-linux-gnu", "alpha"),
+        ("armv7b-linux-gnu", "armeb"),
+        ("armv7l-linux-gnueabihf", "armhf"),
+        ("ppc64-linux-gnu", "ppc64"),
+        ("mipseb-linux-gnu", "mips"),
+    ] {
+        let arch = DebianArchitecture::from_gnu_triplet(triplet).unwrap();
+        assert_eq!(arch.to_string(), name);
+    }
+
+    let arch: DebianArchitecture = "amd64".parse().unwrap();
+    assert_eq!(
+        DebianArchitecture::from_gnu_triplet("x86_64-pc-linux-gnu"),
+        Ok(arch)
+    );
+    assert_eq!(
+        DebianArchitecture::from_gnu_triplet("i586-linux-gnu")
+            .unwrap()
+            .cpu(),
+        "i386"
+    );
+    let i386 = DebianArchitecture::parse("i386").unwrap();
+    assert_eq!(i386.multiarch(), "i386-linux-gnu");
+    assert_eq!(arch.multiarch(), "x86_64-linux-gnu");
+    assert_eq!(
+        DebianArchitecture::parse("s390x").unwrap().endianness(),
+        Endianness::Big
+    );
+
+    for wildcard in ["any", "amd64", "linux-any", "any-amd64", "gnu-any-any"] {
+        assert!(arch.matches(wildcard), "{wildcard}");
+    }
+    for wildcard in ["i386", "hurd-any", "any-i386", "musl-any-any", "all"] {
+        assert!(!arch.matches(wildcard), "{wildcard}");
+    }
+    let armhf = DebianArchitecture::parse("armhf").unwrap();
+    assert!(armhf.matches("any-arm"));
+    assert!(armhf.matches("eabihf-any-any-any"));
+    assert!(!armhf.matches("any-armhf"));
+    assert!(arch_matches("hurd-i386", "hurd-any"));
+    assert!(arch_matches("unknown", "unknown"));
+    assert!(!arch_matches("unknown", "linux-any"));
+
+    let all = DebianArchitecture::all();
+    assert!(all.contains(&armhf));
+    assert!(all.contains(&arch));
+    for arch in &all {
+        assert_eq!(DebianArchitecture::parse(&arch.name()).as_ref(), Ok(arch));
+    }
+}
+
+#[test]
+fn build_options_and_profiles() {
+    let mut options = BuildOptions::parse(
+        "nocheck  parallel=8 nostrip hardening=+all,-pie,-format \
+         reproducible=-fixfilepath parallel=12 terse",
+    );
+    assert!(options.nocheck() && options.nostrip() && options.terse());
+    assert!(!options.nodoc() && !options.noopt());
+    assert_eq!(options.parallel(), Some(12));
+    assert_eq!(
+        options.features("hardening"),
+        [("all", true), ("pie", false), ("format", false)]
+    );
+    assert_eq!(options.feature("hardening", "bindnow"), Some(true));
+    assert_eq!(options.feature("hardening", "format"), Some(false));
+    assert_eq!(options.feature("reproducible", "fixfilepath"), Some(false));
+    assert_eq!(options.feature("reproducible", "timeless"), None);
+    assert_eq!(options.feature("optimize", "lto"), None);
+    assert_eq!(
+        options.to_string(),
+        "nocheck parallel=12 nostrip hardening=+all,-pie,-format \
+         reproducible=-fixfilepath terse"
+    );
+    options.unset("nostrip");
+    options.set("parallel", Some("auto"));
+    options.set("nodoc", None);
+    assert!(!options.nostrip() && options.nodoc());
+    assert_eq!(options.parallel(), None);
+    assert_eq!(options.get("parallel"), Some("auto"));
+    assert_eq!(options.iter().count(), 6);
+    assert!(BuildOptions::parse(" ").is_empty());
+
+    let mut profiles: BuildProfiles = "nocheck  cross nocheck".parse().unwrap();
+    assert_eq!(profiles.names(), ["nocheck", "cross"]);
+    assert!(profiles.matches(""));
+    assert!(profiles.matches("<cross>"));
+    assert!(profiles.matches("<!nocheck> <cross !stage1>"));
+    assert!(!profiles.matches("<!nocheck> <stage1>"));
+    assert!(!profiles.matches("<nocheck !cross>"));
+    profiles.remove("nocheck");
+    profiles.add("stage1");
+    assert_eq!(profiles.to_string(), "cross stage1");
+    assert!(profiles.matches("<!nocheck>"));
+
+    env::set_var("DEB_BUILD_PROFILES", "nodoc");
+    assert!(BuildProfiles::from_env().contains("nodoc"));
+    env::remove_var("DEB_BUILD_PROFILES");
+    assert!(BuildProfiles::from_env().is_empty());
+}
+
+#[test]
+fn standards_version() {
+    let v = StandardsVersion::parse("4.6.2").unwrap();
+    assert_eq!(v, StandardsVersion::new(4, 6, 2));
+    assert_eq!(v.editorial, None);
+    let e: StandardsVersion = "4.6.2.1".parse().unwrap();
+    assert_eq!(e.editorial, Some(1));
+    assert_eq!(e.to_string(), "4.6.2.1");
+    assert_eq!(e.significant(), v);
+    assert!(v < e);
+    assert_eq!(v, "4.6.2.0".parse().unwrap());
+    assert!("3.9.8".parse::<StandardsVersion>().unwrap() < v);
+    assert!("4.10.0".parse::<StandardsVersion>().unwrap() > v);
+    for invalid in ["4.6", "4.6.2.1.0", "4.6.x", "4..2", "", "+4.6.2"] {
+        assert!(StandardsVersion::parse(invalid).is_err(), "{invalid}");
+    }
+
+    let current = StandardsVersion::parse("4.7.0").unwrap();
+    assert_eq!(v.status(&current), StandardsStatus::Outdated);
+    assert_eq!(current.status(&current), StandardsStatus::Current);
+    assert_eq!(
+        StandardsVersion::parse("4.7.0.2").unwrap().status(&current),
+        StandardsStatus::Current
+    );
+    assert_eq!(
+        StandardsVersion::parse("4.7.1").unwrap().status(&current),
+        StandardsStatus::Newer
+    );
+
+    let mut linter = Linter::default();
+    linter.add_check(OutdatedStandardsVersion { current });
+    let path = data_path().join("control-foo");
+    let findings = linter.check_file(TargetKind::Control, &path).unwrap();
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].tag, "out-of-date-standards-version");
+    assert_eq!(findings[0].line, Some(6));
+    assert_eq!(findings[0].message, "3.9.6 (current is 4.7.0)");
+    let control = "Source: foo\nStandards-Version: 4.7\n";
+    let findings = linter.check(&LintTarget::Control(control));
+    assert_eq!(findings[0].tag, "invalid-standards-version");
+    let control = "Source: foo\nStandards-Version: 4.7.0.1\n";
+    assert!(linter.check(&LintTarget::Control(control)).is_empty());
+}
+
+#[test]
+fn distro_info() {
+    let info = DistroInfo::builtin();
+    let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+    let today = date(2026, 10, 16);
+    let suites = [
+        ("oldoldstable", "bullseye"),
+        ("oldstable", "bookworm"),
+        ("stable", "trixie"),
+        ("testing", "forky"),
+        ("unstable", "sid"),
+        ("experimental", "experimental"),
+        ("trixie", "trixie"),
+        ("Bookworm", "bookworm"),
+    ];
+    for (suite, codename) in suites {
+        assert_eq!(info.codename(suite, today), Some(codename), "{suite}");
+    }
+    assert_eq!(info.codename("hardy", today), None);
+    assert_eq!(info.suite("trixie", today), Some(Suite::Stable));
+    assert_eq!(info.suite("sid", today), Some(Suite::Unstable));
+    assert_eq!(info.suite("buster", today), None);
+    assert_eq!(info.suite("trixie", date(2025, 8, 8)), Some(Suite::Testing));
+    assert_eq!(info.suite("duke", date(2027, 8, 1)), None);
+    assert_eq!("oldstable".parse::<Suite>(), Ok(Suite::OldStable));
+    assert_eq!(Suite::OldOldStable.to_string(), "oldoldstable");
+    assert!("sid".parse::<Suite>().is_err());
+
+    let bookworm = info.release_by_version("12.7").unwrap();
+    assert_eq!(bookworm.series, "bookworm");
+    assert_eq!(bookworm.codename, "Bookworm");
+    assert_eq!(bookworm.backports_suite(), "bookworm-backports");
+    assert_eq!(bookworm.security_suite(), "bookworm-security");
+    assert_eq!(bookworm.support_status(today), SupportStatus::Lts);
+    assert_eq!(info.release_by_version("3.1").unwrap().series, "sarge");
+    assert!(info.release_by_version("3.2").is_none());
+
+    let status =
+        |codename, date| info.release(codename).unwrap().support_status(date);
+    assert_eq!(status("buster", today), SupportStatus::Elts);
+    assert_eq!(status("trixie", today), SupportStatus::Supported);
+    assert_eq!(status("forky", today), SupportStatus::Unreleased);
+    assert_eq!(status("sid", today), SupportStatus::Unreleased);
+    assert_eq!(status("woody", today), SupportStatus::EndOfLife);
+    assert_eq!(status("squeeze", date(2015, 1, 1)), SupportStatus::Lts);
+    let supported: Vec<&str> = info
+        .supported(today)
+        .iter()
+        .map(|r| r.series.as_str())
+        .collect();
+    assert_eq!(
+        supported,
+        ["stretch", "buster", "bullseye", "bookworm", "trixie"]
+    );
+
+    let info =
+        DistroInfo::from_file(&data_path().join("distro-info.csv")).unwrap();
+    assert_eq!(info.releases().len(), 4);
+    assert_eq!(info.codename("stable", today), Some("bullseye"));
+    assert_eq!(info.codename("testing", today), Some("bookworm"));
+    assert_eq!(info.release("bookworm").unwrap().eol_lts, None);
+    assert_eq!(
+        info.release("bullseye").unwrap().support_status(today),
+        SupportStatus::EndOfLife
+    );
+    assert!(DistroInfo::from_reader("version,codename\n".as_bytes()).is_err());
+    let bad = "version,codename,series,created\n1,Foo,foo,yesterday\n";
+    assert!(DistroInfo::from_reader(bad.as_bytes()).is_err());
+}
+
+#[test]
+fn artifact_file_names() {
+    let cases = [
+        "foo_1.2-1_amd64.deb",
+        "foo-udeb_1.2-1_arm64.udeb",
+        "foo-dbgsym_1.2-1+b1_i386.ddeb",
+        "foo_1.2-1.dsc",
+        "foo_1.2-1_source.changes",
+        "foo_1.2-1_amd64.buildinfo",
+        "foo_1.2.orig.tar.gz",
+        "foo_1.2.orig.tar.gz.asc",
+        "foo_1.2~rc1+dfsg.orig-docs.tar.xz",
+        "foo_1.2.orig-docs.tar.xz.asc",
+        "foo_1.2-1.debian.tar.xz",
+        "foo_1.2.tar.bz2",
+        "foo_1.2-1.diff.gz",
+    ];
+    for name in cases {
+        let parsed = ArtifactName::parse(name).unwrap();
+        assert_eq!(parsed.to_string(), name);
+        assert_eq!(parsed.apt_cache_name(), name);
+    }
+    let parsed = ArtifactName::parse("foo-dbgsym_1.2-1+b1_i386.ddeb").unwrap();
+    assert_eq!(parsed.package, "foo-dbgsym");
+    assert_eq!(parsed.version.to_string(), "1.2-1+b1");
+    assert_eq!(
+        parsed.kind,
+        ArtifactKind::Binary(BinaryType::Ddeb, "i386".to_string())
+    );
+    assert!(!parsed.is_source());
+    let parsed = ArtifactName::parse("foo_1.2-1.debian.tar.xz").unwrap();
+    assert_eq!(parsed.kind, ArtifactKind::DebianTarball("xz".to_string()));
+    assert!(parsed.is_source());
+    let parsed: ArtifactName = "foo_1.2.tar.bz2".parse().unwrap();
+    assert_eq!(parsed.kind, ArtifactKind::NativeTarball("bz2".to_string()));
+    let parsed = ArtifactName::parse("foo_1.2.orig.tar.gz.asc").unwrap();
+    assert_eq!(
+        parsed.kind,
+        ArtifactKind::OrigTarball {
+            component: None,
+            compression: "gz".to_string(),
+            signature: true,
+        }
+    );
+
+    let parsed = ArtifactName::parse("foo_2%3a1.2-1_all.deb").unwrap();
+    assert_eq!(parsed.version.to_string(), "2:1.2-1");
+    assert_eq!(parsed.to_string(), "foo_1.2-1_all.deb");
+    assert_eq!(parsed.apt_cache_name(), "foo_2%3a1.2-1_all.deb");
+
+    for invalid in [
+        "foo.deb",
+        "foo_1.2-1.deb",
+        "Foo_1.2-1_amd64.deb",
+        "foo_1.2-1_amd64.rpm",
+        "foo_1.2.tar.zip",
+        "foo_1.2-1.debian.tar.xz.asc",
+        "foo_1.2-1_amd64_extra.deb",
+        "foo_:1.2-1.dsc",
+        "f_1.2-1.dsc",
+        "+foo_1.2-1.dsc",
+        "foo_1.2.orig-.tar.gz.asc",
+    ] {
+        assert!(ArtifactName::parse(invalid).is_err(), "{invalid}");
+    }
+
+    let version = Version::parse("1:2.0~rc1-3").unwrap();
+    assert_eq!(version.without_epoch(), "2.0~rc1-3");
+    assert_eq!(
+        ArtifactName::dsc("foo", &version).to_string(),
+        "foo_2.0~rc1-3.dsc"
+    );
+    assert_eq!(
+        ArtifactName::changes("foo", &version, "source").to_string(),
+        "foo_2.0~rc1-3_source.changes"
+    );
+    assert_eq!(
+        ArtifactName::buildinfo("foo", &version, "amd64").to_string(),
+        "foo_2.0~rc1-3_amd64.buildinfo"
+    );
+    let orig = ArtifactName::orig_tarball("foo", &version, Some("docs"), "xz");
+    assert_eq!(orig.to_string(), "foo_2.0~rc1.orig-docs.tar.xz");
+    assert_eq!(orig, ArtifactName::parse(&orig.to_string()).unwrap());
+    let udeb = ArtifactName::new(
+        "foo-udeb",
+        &version,
+        ArtifactKind::Binary(BinaryType::Udeb, "amd64".to_string()),
+    );
+    assert_eq!(udeb.to_string(), "foo-udeb_2.0~rc1-3_amd64.udeb");
+
+    assert_eq!(escape_version("1:2.0_3%"), "1%3a2.0%5f3%25");
+    assert_eq!(unescape("1%3a2.0%5f3%25"), "1:2.0_3%");
+    assert_eq!(unescape("100%"), "100%");
+    assert_eq!(unescape("%zz%4"), "%zz%4");
+}
+
+#[test]
+fn orig_tarballs() {
+    let dir = TempDir::new().unwrap();
+    let version = Version::parse("1:1.2-3").unwrap();
+    let names: Vec<String> = orig_tarball_names("foo", &version, None)
+        .iter()
+        .map(|n| n.to_string())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "foo_1.2.orig.tar.gz",
+            "foo_1.2.orig.tar.bz2",
+            "foo_1.2.orig.tar.lzma",
+            "foo_1.2.orig.tar.xz",
+        ]
+    );
+    assert!(find_orig_tarballs(dir.path(), "foo", &version)
+        .unwrap()
+        .is_empty());
+
+    let signature = "-----BEGIN PGP SIGNATURE-----\n\niQIz\n=abcd\n\
+                     -----END PGP SIGNATURE-----\n";
+    for (name, content) in [
+        ("foo_1.2.orig.tar.xz", "main"),
+        ("foo_1.2.orig.tar.xz.asc", signature),
+        ("foo_1.2.orig-docs.tar.gz", "docs"),
+        ("foo_1.2.orig-docs.tar.gz.asc", "not a signature"),
+        ("foo_1.2.orig-assets.tar.bz2", "assets"),
+        ("foo_1.1.orig.tar.gz", "old"),
+        ("bar_1.2.orig.tar.gz", "other"),
+        ("foo_1.2-3.debian.tar.xz", "debian"),
+    ] {
+        std::fs::write(dir.path().join(name), content).unwrap();
+    }
+    let tarballs = find_orig_tarballs(dir.path(), "foo", &version).unwrap();
+    let found: Vec<(Option<&str>, String, bool)> = tarballs
+        .iter()
+        .map(|t| {
+            let name = t.path.file_name().unwrap().to_string_lossy();
+            (t.component(), name.to_string(), t.signature.is_some())
+        })
+        .collect();
+    assert_eq!(
+        found,
+        [
+            (None, "foo_1.2.orig.tar.xz".to_string(), true),
+            (
+                Some("assets"),
+                "foo_1.2.orig-assets.tar.bz2".to_string(),
+                false
+            ),
+            (Some("docs"), "foo_1.2.orig-docs.tar.gz".to_string(), true),
+        ]
+    );
+    assert_eq!(tarballs[0].signature_name(), "foo_1.2.orig.tar.xz.asc");
+    assert!(tarballs[0].check_signature().is_ok());
+    assert!(tarballs[1].check_signature().is_ok());
+    let err = tarballs[2].check_signature().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    let main = find_orig_tarball(dir.path(), "foo", &version, None).unwrap();
+    assert_eq!(main, Some(tarballs[0].clone()));
+    let missing =
+        find_orig_tarball(dir.path(), "foo", &version, Some("x")).unwrap();
+    assert_eq!(missing, None);
+
+    let truncated = dir.path().join("truncated.asc");
+    std::fs::write(&truncated, "-----BEGIN PGP SIGNATURE-----\niQIz\n")
+        .unwrap();
+    assert!(check_signature_file(&truncated).is_err());
+
+    std::fs::write(dir.path().join("foo_1.2.orig.tar.gz"), "again").unwrap();
+    assert!(find_orig_tarballs(dir.path(), "foo", &version).is_err());
+}
+
+#[test]
+fn dep14_names() {
+    for (version, mangled) in [
+        ("1.2-3", "1.2-3"),
+        ("1:2.3-1", "1%2.3-1"),
+        ("2.0~rc1-1~bpo12+1", "2.0_rc1-1_bpo12+1"),
+        ("1.2..3", "1.2.#.3"),
+        ("1...2", "1.#.#.2"),
+        ("1.2.", "1.2.#"),
+        ("1.2.lock", "1.2.#lock"),
+    ] {
+        assert_eq!(mangle_version(version), mangled, "{version}");
+        assert_eq!(unmangle_version(mangled), version, "{mangled}");
+    }
+
+    let version = Version::parse("1:1.2~beta-3").unwrap();
+    assert_eq!(version_tag("debian", &version), "debian/1%1.2_beta-3");
+    assert_eq!(version_tag("ubuntu", &version), "ubuntu/1%1.2_beta-3");
+    assert_eq!(upstream_tag(&version), "upstream/1.2_beta");
+
+    let (vendor, parsed) =
+        parse_version_tag("refs/tags/upstream/1.2_beta").unwrap();
+    assert_eq!(vendor, "upstream");
+    assert_eq!(parsed.to_string(), "1.2~beta");
+    let (vendor, parsed) = parse_version_tag("ubuntu/1%1.2_beta-3").unwrap();
+    assert_eq!(vendor, "ubuntu");
+    assert_eq!(parsed, version);
+    assert!(parse_version_tag("1.2-3").is_none());
+    assert!(parse_version_tag("debian/latest/1.2").is_none());
+    assert!(parse_version_tag("/1.2").is_none());
+
+    assert_eq!(packaging_branch("debian", None), "debian/latest");
+    assert_eq!(packaging_branch("debian", Some("trixie")), "debian/trixie");
+    for (distribution, branch) in [
+        ("unstable", "debian/latest"),
+        ("UNRELEASED", "debian/latest"),
+        ("experimental", "debian/experimental"),
+        ("bookworm", "debian/bookworm"),
+        ("bookworm-security", "debian/bookworm"),
+        ("bookworm-proposed-updates", "debian/bookworm"),
+        ("bookworm-backports", "debian/bookworm-backports"),
+    ] {
+        assert_eq!(suggested_branch("debian", distribution), branch);
+    }
+    assert_eq!(UPSTREAM_BRANCH, "upstream/latest");
+}
+
+#[test]
+fn bug_references() {
+    let text = "\
+  * New upstream release. Closes: #1000, bug#1001,#1002
+  * Fix FTBFS (closes: # 1003; LP: #2000, #2001).
+  * Update the Closes: #1000 handling; not a bug #1004.
+  * Drop patch applied upstream (LP:#2002).
+";
+    assert_eq!(parse_closes(text), [1000, 1001, 1002, 1003]);
+    assert_eq!(parse_launchpad(text), [2000, 2001]);
+    assert_eq!(
+        parse_bug_refs(text),
+        [
+            BugRef::Debian(1000),
+            BugRef::Debian(1001),
+            BugRef::Debian(1002),
+            BugRef::Debian(1003),
+            BugRef::Launchpad(2000),
+            BugRef::Launchpad(2001),
+        ]
+    );
+    assert!(parse_closes("no bugs closed").is_empty());
+
+    assert_eq!(format_closes(&[1000, 1001]), "Closes: #1000, #1001");
+    assert_eq!(format_launchpad(&[2000]), "LP: #2000");
+    assert_eq!(parse_closes(&format_closes(&[7, 8])), [7, 8]);
+    assert_eq!(
+        parse_bug_field(" 1002 1000\n 1001"),
+        Ok(vec![1002, 1000, 1001])
+    );
+    assert!(parse_bug_field("1000 #1001").is_err());
+    assert_eq!(format_bug_field(&[1002, 1000, 1002]), "1000 1002");
+
+    assert_eq!("#123".parse(), Ok(BugRef::Debian(123)));
+    assert_eq!("123".parse(), Ok(BugRef::Debian(123)));
+    assert_eq!("LP: #456".parse(), Ok(BugRef::Launchpad(456)));
+    assert_eq!("lp:456".parse(), Ok(BugRef::Launchpad(456)));
+    assert!("bug".parse::<BugRef>().is_err());
+    assert_eq!(BugRef::Debian(123).to_string(), "#123");
+    assert_eq!(BugRef::Launchpad(456).to_string(), "LP: #456");
+    assert_eq!(BugRef::Launchpad(456).number(), 456);
+    assert_eq!(BugRef::Debian(123).url(), "https://bugs.debian.org/123");
+}
+
+#[test]
+fn changelog_and_changes() {
+    let changelog =
+        Changelog::from_file(&data_path().join("changelog-foo")).unwrap();
+    let entries = changelog.entries();
+    assert_eq!(entries.len(), 3);
+    let entry = &entries[0];
+    assert_eq!(entry.package(), "foo");
+    assert_eq!(entry.version(), "1.2-1");
+    assert_eq!(entry.distributions(), &["unstable"]);
+    assert_eq!(entry.urgency(), "medium");
+    assert_eq!(entry.detail(), "  * New upstream release.");
+    assert_eq!(entry.maintainer_email(), "jane@example.org");
+    assert_eq!(entry.timestamp().to_rfc3339(), "2023-10-02T10:00:00+02:00");
+    assert_eq!(entries[2].urgency(), "low");
+    let truncated = "foo (1.0) unstable; urgency=low\n\n  * Oops.\n";
+    assert!(Changelog::from_reader(truncated.as_bytes()).is_err());
+
+    let unreleased = "foo (1.3-1) UNRELEASED; urgency=medium\n\n  \
+                      * Work in progress.\n\n \
+                      -- Jane Doe <jane@example.org>  \
+                      Tue, 03 Oct 2023 10:00:00 +0200\n\n";
+    let changelog = Changelog::from_reader(unreleased.as_bytes()).unwrap();
+    assert_eq!(changelog.entries()[0].distributions(), &["UNRELEASED"]);
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("changelog");
+    changelog.to_file(&path).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), unreleased);
+    let reparsed = Changelog::from_file(&path).unwrap();
+    assert_eq!(reparsed.entries()[0].detail(), "  * Work in progress.");
+    assert_eq!(reparsed.entries()[0].distributions(), &["UNRELEASED"]);
+
+    let text = "foo (1.2-1) unstable; urgency=medium\n\n  \
+                * Fix typo. Closes: #1234\n\n  \
+                * Fix crash. (Closes: #999)\n\n \
+                -- Jane Doe <jane@example.org>  \
+                Mon, 02 Oct 2023 10:00:00 +0200\n";
+    let changelog = Changelog::from_reader(text.as_bytes()).unwrap();
+    let control =
+        ControlFile::from_file(&data_path().join("control-foo")).unwrap();
+
+    let dir = TempDir::new().unwrap();
+    let orig: &[u8] = b"upstream sources";
+    let debian: &[u8] = b"packaging";
+    std::fs::write(dir.path().join("foo_1.2.orig.tar.gz"), orig).unwrap();
+    std::fs::write(dir.path().join("foo_1.2-1.debian.tar.xz"), debian).unwrap();
+    let dsc = format!(
+        "-----BEGIN PGP SIGNED MESSAGE-----\nHash: SHA256\n\n\
+         Format: 3.0 (quilt)\nSource: foo\nVersion: 1.2-1\nFiles:\n \
+         {} {} foo_1.2.orig.tar.gz\n {} {} foo_1.2-1.debian.tar.xz\n\n\
+         -----BEGIN PGP SIGNATURE-----\n\nabc\n\
+         -----END PGP SIGNATURE-----\n",
+        md5_hex(orig),
+        orig.len(),
+        md5_hex(debian),
+        debian.len()
+    );
+    let dsc_path = dir.path().join("foo_1.2-1.dsc");
+    std::fs::write(&dsc_path, &dsc).unwrap();
+
+    let mut changes =
+        ChangesBuilder::new(&changelog.entries()[0], &control).unwrap();
+    let mut out = vec![];
+    assert!(changes.write(&mut out).is_err());
+    changes.add_dsc(&dsc_path, false).unwrap();
+    let names: Vec<&str> =
+        changes.files().iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, ["foo_1.2-1.dsc", "foo_1.2-1.debian.tar.xz"]);
+    changes.add_dsc(&dsc_path, true).unwrap();
+    assert_eq!(changes.files().len(), 3);
+    let file = &changes.files()[2];
+    assert_eq!(file.name, "foo_1.2.orig.tar.gz");
+    assert_eq!(file.size, 16);
+    assert_eq!(
+        (file.section.as_str(), file.priority.as_str()),
+        ("unknown", "extra")
+    );
+    assert_eq!(file.sha1, "d8e22e794720c96daa6b122467bc59dab4da58f1");
+
+    changes.write(&mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert!(text.starts_with(
+        "Format: 1.8\nDate: Mon, 02 Oct 2023 10:00:00 +0200\nSource: foo\n\
+         Binary: foo\nArchitecture: source\nVersion: 1.2-1\n\
+         Distribution: unstable\nUrgency: medium\n\
+         Maintainer: Mickey Mouse <mouse@disney.com>\n\
+         Changed-By: Jane Doe <jane@example.org>\nDescription:\n \
+         foo        - Disney Foo - pseudo package\nCloses: 999 1234\n\
+         Changes:\n foo (1.2-1) unstable; urgency=medium\n .\n   \
+         * Fix typo. Closes: #1234\n .\n   * Fix crash. (Closes: #999)\n\
+         Checksums-Sha1:\n"
+    ));
+    assert!(text.contains(&format!(
+        "Files:\n {} {} unknown extra foo_1.2-1.dsc\n",
+        md5_hex(dsc.as_bytes()),
+        dsc.len()
+    )));
+
+    std::fs::write(
+        dir.path().join("foo.dsc"),
+        "Source: foo\nFiles:\n \
+         0123 1 foo_1.2.orig.tar.gz\n",
+    )
+    .unwrap();
+    assert!(changes.add_dsc(&dir.path().join("foo.dsc"), true).is_err());
+
+    #[cfg(feature = "deb")]
+    {
+        let deb = dir.path().join("foo_1.0-1_all.deb");
+        std::fs::copy(data_path().join("deb").join("foo-gz.deb"), &deb)
+            .unwrap();
+        changes.add_deb(&deb).unwrap();
+        let file = changes.files().last().unwrap();
+        assert_eq!(file.section, "utils");
+        assert_eq!(file.priority, "optional");
+        let mut out = vec![];
+        changes.write(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains(
+            "Architecture: source all\n\
+             Version: 1.2-1\n"
+        ));
+        assert!(text.contains("Description:\n foo        - example package\n"));
+    }
+}
+
+#[cfg(feature = "openpgp")]
+#[test]
+fn openpgp_clearsign() {
+    let repo = data_path().join("repo");
+    let key = SecretKey::from_file(&repo.join("secret.gpg")).unwrap();
+    assert_eq!(key.user_ids(), &["Example Archive <archive@example.org>"]);
+    assert_eq!(
+        key.fingerprint(),
+        "41C454AA95373DED41721E378A96071DCFCF6E6F"
+    );
+    assert_eq!(key.key_id(), "8A96071DCFCF6E6F");
+    assert!(SecretKey::from_bytes(b"not a key").is_err());
+
+    let text = "Format: 1.8\nSource: foo  \nChanges:\n foo (1.2-1)\n---\n";
+    let time = chrono::Utc.timestamp_opt(1792177000, 0).unwrap();
+    let signed = key.clearsign_at(text, &time);
+    assert!(signed.starts_with(
+        "-----BEGIN PGP SIGNED MESSAGE-----\nHash: SHA256\n\n\
+         Format: 1.8\nSource: foo\nChanges:\n foo (1.2-1)\n- ---\n\
+         -----BEGIN PGP SIGNATURE-----\n\n"
+    ));
+    assert!(signed.ends_with("\n-----END PGP SIGNATURE-----\n"));
+    assert_eq!(signed, key.clearsign_at(text, &time));
+    let stripped = strip_signature(&signed);
+    assert_eq!(
+        stripped,
+        "Format: 1.8\nSource: foo\nChanges:\n foo (1.2-1)\n---\n"
+    );
+    assert_eq!(strip_signature(text), text);
+
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("foo_1.2-1.dsc");
+    std::fs::write(&path, &signed).unwrap();
+    key.sign_file(&path).unwrap();
+    let resigned = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(strip_signature(&resigned), stripped);
+
+    let keyring = Keyring::from_file(&repo.join("keyring.gpg")).unwrap();
+    assert_eq!(keyring.fingerprints(), [key.fingerprint()]);
+    assert_eq!(keyring.verify_clearsigned(&signed).unwrap(), stripped);
+    let tampered = signed.replace("Source: foo", "Source: bar");
+    assert!(keyring.verify_clearsigned(&tampered).is_err());
+    assert!(keyring.verify_clearsigned(text).is_err());
+    assert!(Keyring::from_bytes(b"not a key").is_err());
+
+    #[cfg(feature = "gpgv")]
+    {
+        let gpgv = Gpgv::new(&repo.join("keyring.gpg"));
+        let data = gpgv.verify(signed.as_bytes()).unwrap();
+        assert_eq!(data, stripped.as_bytes());
+        assert!(gpgv.verify(tampered.as_bytes()).is_err());
+    }
+}
+
+#[cfg(feature = "openpgp")]
+#[test]
+fn openpgp_verify_subkey() {
+    let repo = data_path().join("repo");
+    let key = SecretKey::from_file(&repo.join("subkey-secret.gpg")).unwrap();
+    assert_eq!(key.key_id(), "B0669300FA378FFF");
+    let signed = key.clearsign("Suite: stable\n");
+
+    let binary = std::fs::read(repo.join("subkey-keyring.gpg")).unwrap();
+    let keyring = Keyring::from_bytes(&binary).unwrap();
+    assert_eq!(keyring.fingerprints().len(), 2);
+    assert_eq!(keyring.fingerprints()[1], key.fingerprint());
+    assert_eq!(
+        keyring.verify_clearsigned(&signed).unwrap(),
+        "Suite: stable\n"
+    );
+    let armored = Keyring::from_file(&repo.join("subkey-keyring.asc")).unwrap();
+    assert_eq!(armored.fingerprints(), keyring.fingerprints());
+
+    // a subkey with a broken binding signature is not trusted
+    let mut broken = binary.clone();
+    *broken.last_mut().unwrap() ^= 1;
+    let keyring = Keyring::from_bytes(&broken).unwrap();
+    assert_eq!(keyring.fingerprints().len(), 1);
+    assert!(keyring.verify_clearsigned(&signed).is_err());
+
+    // neither is a signature by a key not in the keyring
+    let other = Keyring::from_file(&repo.join("keyring.gpg")).unwrap();
+    assert!(other.verify_clearsigned(&signed).is_err());
+}
+
+#[test]
+fn changes_file() {
+    let dir = TempDir::new().unwrap();
+    let tarball: &[u8] = b"native sources";
+    let path = dir.path().join("foo_1.2.tar.xz");
+    std::fs::write(&path, tarball).unwrap();
+    let file = ChangesFile::new("foo_1.2.tar.xz", tarball, "utils", "optional");
+    let text = format!(
+        "Format: 1.8\nSource: foo\nArchitecture: source\nVersion: 1.2\n\
+         Distribution: unstable\nChecksums-Sha256:\n {} 14 foo_1.2.tar.xz\n\
+         Files:\n {} 14 utils optional foo_1.2.tar.xz\n",
+        file.sha256, file.md5
+    );
+    let changes = Changes::from_reader(text.as_bytes()).unwrap();
+    assert!(!changes.is_signed());
+    assert_eq!(changes.source(), Some("foo"));
+    assert_eq!(changes.version(), Some("1.2"));
+    assert_eq!(changes.distribution(), Some("unstable"));
+    assert_eq!(changes.architectures(), ["source"]);
+    assert_eq!(changes.files()[0].sha1, "");
+    assert_eq!(changes.files()[0].sha256, file.sha256);
+    assert_eq!(changes.files()[0].section, "utils");
+    changes.verify_files(dir.path()).unwrap();
+    std::fs::write(&path, b"other sources!").unwrap();
+    assert!(changes.verify_files(dir.path()).is_err());
+    std::fs::remove_file(&path).unwrap();
+    assert!(changes.verify_files(dir.path()).is_err());
+
+    let signed = format!(
+        "-----BEGIN PGP SIGNED MESSAGE-----\nHash: SHA256\n\n{text}\
+         -----BEGIN PGP SIGNATURE-----\n\nabc\n-----END PGP SIGNATURE-----\n"
+    );
+    let changes = Changes::from_reader(signed.as_bytes()).unwrap();
+    assert!(changes.is_signed());
+    assert_eq!(changes.files().len(), 1);
+    let unknown = text.replace(" 14 foo_1.2.tar.xz\nFiles", " 1 bar\nFiles");
+    assert!(Changes::from_reader(unknown.as_bytes()).is_err());
+}
+
+/// Paths and bodies of PUT requests received.
+#[cfg(feature = "upload")]
+type Uploads = Vec<(String, Vec<u8>)>;
+
+/// Accepts PUT requests on a local port, returning the queue URL and
+/// a handle yielding the uploads received.
+#[cfg(feature = "upload")]
+fn receive_uploads(
+    requests: usize,
+) -> (String, std::thread::JoinHandle<Uploads>) {
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/queue", listener.local_addr().unwrap());
+    let handle = std::thread::spawn(move || {
+        let mut uploads = vec![];
+        for stream in listener.incoming().take(requests) {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut lines = vec![];
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                lines.push(line.trim().to_string());
+            }
+            assert!(lines[0].starts_with("PUT "));
+            let path = lines[0].split(' ').nth(1).unwrap().to_string();
+            let len: usize = lines
+                .iter()
+                .find_map(|l| {
+                    l.to_lowercase()
+                        .strip_prefix("content-length: ")
+                        .map(|n| n.parse().unwrap())
+                })
+                .unwrap();
+            let mut body = vec![0; len];
+            reader.read_exact(&mut body).unwrap();
+            stream
+                .write_all(
+                    concat!(
+                        "HTTP/1.1 201 Created\r\n",
+                        "Content-Length: 0\r\n",
+                        "Connection: close\r\n\r\n"
+                    )
+                    .as_bytes(),
+                )
+                .unwrap();
+            uploads.push((path, body));
+        }
+        uploads
+    });
+    (url, handle)
+}
+
+#[cfg(feature = "upload")]
+#[test]
+fn upload_queue() {
+    let queue: UploadQueue = "sftp://jane@upload.example.org:2222/incoming/"
+        .parse()
+        .unwrap();
+    assert_eq!(queue.method, UploadMethod::Sftp);
+    assert_eq!(queue.login.as_deref(), Some("jane"));
+    assert_eq!(queue.host, "upload.example.org:2222");
+    assert_eq!(queue.incoming, "/incoming");
+    assert_eq!(
+        queue.to_string(),
+        "sftp://jane@upload.example.org:2222/incoming"
+    );
+    assert!("ftp://example.org/".parse::<UploadQueue>().is_err());
+    assert!("https://jane@example.org/".parse::<UploadQueue>().is_err());
+
+    let dir = TempDir::new().unwrap();
+    let tarball: &[u8] = b"native sources";
+    std::fs::write(dir.path().join("foo_1.2.tar.xz"), tarball).unwrap();
+    let dsc: &[u8] = b"Source: foo\n";
+    std::fs::write(dir.path().join("foo_1.2.dsc"), dsc).unwrap();
+    let text = format!(
+        "-----BEGIN PGP SIGNED MESSAGE-----\nHash: SHA256\n\n\
+         Format: 1.8\nSource: foo\nVersion: 1.2\nFiles:\n \
+         {} 12 devel optional foo_1.2.dsc\n \
+         {} 14 devel optional foo_1.2.tar.xz\n\
+         -----BEGIN PGP SIGNATURE-----\n\nabc\n\
+         -----END PGP SIGNATURE-----\n",
+        md5_hex(dsc),
+        md5_hex(tarball)
+    );
+    let changes = dir.path().join("foo_1.2_source.changes");
+    std::fs::write(&changes, &text).unwrap();
+    let files = upload_files(&changes, true).unwrap();
+    assert_eq!(files.last(), Some(&changes));
+
+    let (url, handle) = receive_uploads(3);
+    let uploader = Uploader::new(url.parse().unwrap());
+    let names = uploader.upload(&changes).unwrap();
+    assert_eq!(
+        names,
+        ["foo_1.2.dsc", "foo_1.2.tar.xz", "foo_1.2_source.changes"]
+    );
+    let uploads = handle.join().unwrap();
+    let paths: Vec<&str> = uploads.iter().map(|(p, _)| p.as_str()).collect();
+    assert_eq!(
+        paths,
+        [
+            "/queue/foo_1.2.dsc",
+            "/queue/foo_1.2.tar.xz",
+            "/queue/foo_1.2_source.changes"
+        ]
+    );
+    assert_eq!(uploads[1].1, tarball);
+    assert_eq!(uploads[2].1, text.as_bytes());
+
+    // nothing is uploaded with files missing or unsigned changes
+    std::fs::remove_file(dir.path().join("foo_1.2.tar.xz")).unwrap();
+    assert!(uploader.upload(&changes).is_err());
+    let unsigned = dir.path().join("foo_1.2_amd64.changes");
+    std::fs::write(&unsigned, "Source: foo\nFiles:\n").unwrap();
+    assert_eq!(
+        upload_files(&unsigned, true).unwrap_err().kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+    assert_eq!(upload_files(&unsigned, false).unwrap(), [unsigned]);
+
+    // file names are encoded in URLs, control characters are refused
+    let signed = |name: &str| {
+        format!(
+            "-----BEGIN PGP SIGNED MESSAGE-----\nHash: SHA256\n\n\
+             Format: 1.8\nSource: bar\nVersion: 1\nFiles:\n \
+             {} 12 devel optional {name}\n\
+             -----BEGIN PGP SIGNATURE-----\n\nabc\n\
+             -----END PGP SIGNATURE-----\n",
+            md5_hex(dsc)
+        )
+    };
+    std::fs::write(dir.path().join("bar#1?.dsc"), dsc).unwrap();
+    let changes = dir.path().join("bar_1_source.changes");
+    std::fs::write(&changes, signed("bar#1?.dsc")).unwrap();
+    let (url, handle) = receive_uploads(2);
+    let uploader = Uploader::new(url.parse().unwrap());
+    uploader.upload(&changes).unwrap();
+    let uploads = handle.join().unwrap();
+    assert_eq!(uploads[0].0, "/queue/bar%231%3F.dsc");
+    assert_eq!(uploads[1].0, "/queue/bar_1_source.changes");
+    std::fs::write(dir.path().join("bar\x071.dsc"), dsc).unwrap();
+    std::fs::write(&changes, signed("bar\x071.dsc")).unwrap();
+    assert_eq!(
+        uploader.upload(&changes).unwrap_err().kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+    assert!("sftp://example.org/in\ncoming"
+        .parse::<UploadQueue>()
+        .is_err());
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn archive_dget() {
+    let root = TempDir::new().unwrap();
+    let pool = root.path().join("pool/main/f/foo");
+    std::fs::create_dir_all(&pool).unwrap();
+    let mut checksums = (String::new(), String::new(), String::new());
+    for (name, contents) in [
+        ("foo_1.2.orig.tar.gz", "upstream sources"),
+        ("foo_1.2-1.debian.tar.xz", "packaging"),
+    ] {
+        std::fs::write(pool.join(name), contents).unwrap();
+        let f = ChangesFile::new(name, contents.as_bytes(), "-", "-");
+        checksums.0 += &format!("\n {} {} {name}", f.md5, f.size);
+        checksums.1 += &format!("\n {} {} {name}", f.sha1, f.size);
+        checksums.2 += &format!("\n {} {} {name}", f.sha256, f.size);
+    }
+    let dsc = format!(
+        "Format: 3.0 (quilt)\nSource: foo\nVersion: 1.2-1\n\
+         Checksums-Sha1:{}\nChecksums-Sha256:{}\nFiles:{}\n",
+        checksums.1, checksums.2, checksums.0
+    );
+    std::fs::write(pool.join("foo_1.2-1.dsc"), &dsc).unwrap();
+    let bad = dsc
+        .replace("Version: 1.2-1", "Version: 1.2-2")
+        .replace(&checksums.2[2..18], "0000000000000000");
+    std::fs::write(pool.join("bad.dsc"), bad).unwrap();
+
+    let para = &ControlFile::from_reader(dsc.as_bytes())
+        .unwrap()
+        .get_paragraphs()[0]
+        .clone();
+    let files = source_files(para).unwrap();
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0].name, "foo_1.2.orig.tar.gz");
+    assert_eq!(files[0].size, 16);
+    assert!(files[0].md5.is_some() && files[0].sha256.is_some());
+    let mut evil = para.clone();
+    evil.update_entry("Files", "\n 00 1 ../evil".to_string());
+    assert!(source_files(&evil).is_err());
+
+    let (url, server) = serve_files(root.path().to_path_buf(), 6);
+    let dest = TempDir::new().unwrap();
+    // a partial download to resume
+    std::fs::write(dest.path().join("foo_1.2.orig.tar.gz"), "upst").unwrap();
+    let dget = SourceDownloader::new(dest.path());
+    let dsc_url = format!("{url}/pool/main/f/foo/foo_1.2-1.dsc");
+    let paths = dget.download_dsc(&dsc_url).unwrap();
+    assert_eq!(paths.len(), 3);
+    assert_eq!(paths[0], dest.path().join("foo_1.2-1.dsc"));
+    for path in &paths {
+        let name = path.file_name().unwrap();
+        assert_eq!(
+            std::fs::read(path).unwrap(),
+            std::fs::read(pool.join(name)).unwrap()
+        );
+    }
+    // files present are not fetched again
+    assert_eq!(dget.download_dsc(&dsc_url).unwrap(), paths);
+    let err = dget
+        .download_dsc(&format!("{url}/pool/main/f/foo/bad.dsc"))
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(dget.download_dsc(&format!("{url}/foo.tar.gz")).is_err());
+    assert_eq!(
+        server.join().unwrap(),
+        [
+            "/pool/main/f/foo/foo_1.2-1.dsc",
+            "/pool/main/f/foo/foo_1.2.orig.tar.gz",
+            "/pool/main/f/foo/foo_1.2-1.debian.tar.xz",
+            "/pool/main/f/foo/foo_1.2-1.dsc",
+            "/pool/main/f/foo/bad.dsc",
+            "/pool/main/f/foo/foo_1.2.orig.tar.gz",
+        ]
+    );
+
+    // a repository listing the package in its Sources index
+    #[cfg(feature = "openpgp")]
+    {
+        let dsc_file =
+            ChangesFile::new("foo_1.2-1.dsc", dsc.as_bytes(), "", "");
+        let sources = format!(
+            "Package: foo\nVersion: 1.0-1\nDirectory: pool/main/f/foo\n\
+             Files:\n 00 1 foo_1.0-1.dsc\n\n\
+             Package: foo\nVersion: 1.2-1\nDirectory: pool/main/f/foo\n\
+             Files:{}\n {} {} foo_1.2-1.dsc\n",
+            checksums.0, dsc_file.md5, dsc_file.size
+        );
+        let dist = root.path().join("dists/stable");
+        std::fs::create_dir_all(dist.join("main/source")).unwrap();
+        std::fs::write(dist.join("main/source/Sources"), &sources).unwrap();
+        let mut release = ReleaseBuilder::new().unwrap();
+        release.set_suite("stable");
+        release.set_codename("bookworm");
+        release.add_index("main/source/Sources", sources.as_bytes());
+        let mut text = vec![];
+        release.write(&mut text).unwrap();
+        let key_path = data_path().join("repo").join("secret.gpg");
+        let key = SecretKey::from_file(&key_path).unwrap();
+        let signed = key.clearsign(&String::from_utf8(text).unwrap());
+        std::fs::write(dist.join("InRelease"), signed).unwrap();
+
+        let (url, server) = serve_files(root.path().to_path_buf(), 6);
+        let keyring = data_path().join("repo").join("keyring.gpg");
+        let keyring = Keyring::from_file(&keyring).unwrap();
+        let mut fetcher = RepositoryFetcher::new(&url, "stable", keyring);
+        fetcher.update().unwrap();
+        let dest = TempDir::new().unwrap();
+        let dget = SourceDownloader::new(&dest.path().join("new"));
+        let paths =
+            dget.download_source(&fetcher, "main", "foo", None).unwrap();
+        assert_eq!(paths.len(), 3);
+        assert_eq!(paths[0], dest.path().join("new/foo_1.2-1.dsc"));
+        let missing = dget.download_source(&fetcher, "main", "bar", None);
+        assert_eq!(missing.unwrap_err().kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(
+            server.join().unwrap()[2..5],
+            [
+                "/pool/main/f/foo/foo_1.2-1.dsc",
+                "/pool/main/f/foo/foo_1.2.orig.tar.gz",
+                "/pool/main/f/foo/foo_1.2-1.debian.tar.xz",
+            ]
+        );
+    }
+}
+
+#[test]
+fn quilt_series() {
+    let lines: Vec<String> = (1..=20).map(|i| format!("line {i}\n")).collect();
+    let mut tree = MemoryTree::new();
+    // two lines more at the start than the patch expects
+    tree.insert("src/a.txt", &format!("new 1\nnew 2\n{}", lines.concat()));
+    tree.insert("old.txt", "obsolete\n");
+    tree.insert("eof.txt", "first\nlast");
+    tree.insert(
+        "debian/patches/series",
+        "change-a.patch\nfiles.patch -p0\nbroken.patch\n",
+    );
+    tree.insert(
+        "debian/patches/change-a.patch",
+        "Description: Change a\n\
+         ---\n\
+         --- a/src/a.txt\n\
+         +++ b/src/a.txt\n\
+         @@ -2,3 +2,3 @@\n \
+         line 2\n\
+         -line 3\n\
+         +LINE 3\n \
+         line 4\n\
+         @@ -16,5 +16,4 @@ section\n \
+         changed context\n \
+         line 17\n\
+         -line 18\n \
+         line 19\n \
+         line 20\n",
+    );
+    tree.insert(
+        "debian/patches/files.patch",
+        "--- /dev/null\n\
+         +++ created/new.txt\n\
+         @@ -0,0 +1,2 @@\n\
+         +hello\n\
+         +world\n\
+         --- old.txt\n\
+         +++ /dev/null\n\
+         @@ -1 +0,0 @@\n\
+         -obsolete\n\
+         --- eof.txt\t2024-01-01 00:00:00\n\
+         +++ eof.txt\n\
+         @@ -1,2 +1,2 @@\n \
+         first\n\
+         -last\n\
+         \\ No newline at end of file\n\
+         +last\n",
+    );
+    tree.insert(
+        "debian/patches/broken.patch",
+        "--- a/src/a.txt\n\
+         +++ b/src/a.txt\n\
+         @@ -10,3 +10,3 @@\n \
+         line 10\n\
+         -line 11\n\
+         +LINE 11\n \
+         line 12\n\
+         @@ -12,3 +12,3 @@\n \
+         line 12\n\
+         -no such line\n\
+         +line 13\n \
+         line 14\n\
+         --- a/missing.txt\n\
+         +++ b/missing.txt\n\
+         @@ -1 +1 @@\n\
+         -a\n\
+         +b\n",
+    );
+    let original = tree.clone();
+
+    let patch =
+        Patch::parse(tree.get("debian/patches/files.patch").unwrap()).unwrap();
+    assert_eq!(patch.files().len(), 3);
+    assert!(patch.files()[0].is_creation());
+    assert!(patch.files()[1].is_deletion());
+    assert_eq!(patch.files()[0].path(0).as_deref(), Some("created/new.txt"));
+    assert_eq!(patch.files()[2].hunks[0].old_lines(), ["first\n", "last"]);
+    assert_eq!(patch.files()[2].hunks[0].new_lines(), ["first\n", "last\n"]);
+    assert_eq!(
+        patch.files()[2].hunks[0].to_string(),
+        "@@ -1,2 +1,2 @@\n first\n-last\n\\ No newline at end of file\n+last\n"
+    );
+    let truncated = "--- a/x\n+++ b/x\n@@ -1,3 +1,3 @@\n a\n-b\n";
+    assert_eq!(
+        Patch::parse(truncated).unwrap_err().kind(),
+        std::io::ErrorKind::UnexpectedEof
+    );
+
+    let mut quilt = Quilt::new(tree).unwrap();
+    assert_eq!(
+        quilt.unapplied(),
+        ["change-a.patch", "files.patch", "broken.patch"]
+    );
+    let reports = quilt.push_all().unwrap();
+    assert_eq!(reports.len(), 3);
+    assert_eq!(reports[0].patch.as_deref(), Some("change-a.patch"));
+    assert!(reports[0].is_applied());
+    assert_eq!(
+        reports[0].files[0].offsets,
+        [
+            HunkOffset {
+                hunk: 1,
+                offset: 2,
+                fuzz: 0
+            },
+            HunkOffset {
+                hunk: 2,
+                offset: 0,
+                fuzz: 1
+            },
+        ]
+    );
+    assert_eq!(
+        reports[0].to_string(),
+        "src/a.txt: hunk #1 succeeded (offset 2 lines).\n\
+         src/a.txt: hunk #2 succeeded with fuzz 1.\n"
+    );
+    assert!(reports[1].is_clean());
+    assert!(!reports[2].is_applied());
+    let rejected = reports[2].rejected();
+    assert_eq!(rejected.len(), 2);
+    assert_eq!(rejected[0].0, "src/a.txt");
+    assert_eq!(rejected[0].1.old_start, 12);
+    assert_eq!(rejected[1].0, "missing.txt");
+    assert!(reports[2]
+        .to_string()
+        .contains("src/a.txt: hunk #2 FAILED:\n@@ -12,3 +12,3 @@\n line 12\n"));
+
+    assert_eq!(quilt.applied(), &["change-a.patch", "files.patch"]);
+    assert_eq!(quilt.top(), Some("files.patch"));
+    let tree = quilt.tree();
+    let a = tree.get("src/a.txt").unwrap();
+    assert!(a.contains("line 2\nLINE 3\nline 4\n"));
+    assert!(a.contains("line 17\nline 19\n"));
+    // the broken patch left no traces
+    assert!(a.contains("line 11\n"));
+    assert_eq!(tree.get("created/new.txt"), Some("hello\nworld\n"));
+    assert_eq!(tree.get("old.txt"), None);
+    assert_eq!(tree.get("eof.txt"), Some("first\nlast\n"));
+    assert_eq!(
+        tree.get(".pc/applied-patches"),
+        Some("change-a.patch\nfiles.patch\n")
+    );
+    assert_eq!(tree.get(".pc/files.patch/created/new.txt"), Some(""));
+    assert_eq!(tree.get(".pc/files.patch/old.txt"), Some("obsolete\n"));
+
+    // reopening picks up the state
+    let mut quilt = Quilt::new(quilt.into_tree()).unwrap();
+    assert_eq!(quilt.unapplied(), ["broken.patch"]);
+    assert_eq!(quilt.pop_all().unwrap(), ["files.patch", "change-a.patch"]);
+    assert!(quilt.pop().is_err());
+    let mut expected = original.files().clone();
+    expected.insert(".pc/.version".to_string(), "2\n".to_string());
+    expected.insert(
+        ".pc/.quilt_patches".to_string(),
+        "debian/patches\n".to_string(),
+    );
+    expected.insert(".pc/.quilt_series".to_string(), "series\n".to_string());
+    assert_eq!(quilt.tree().files(), &expected);
+
+    // the same on disk
+    let dir = TempDir::new().unwrap();
+    let mut disk = DirTree::new(dir.path());
+    for (path, contents) in original.files() {
+        disk.write(path, contents).unwrap();
+    }
+    let mut quilt = Quilt::new(disk).unwrap();
+    quilt.push().unwrap();
+    quilt.push().unwrap();
+    assert!(dir.path().join("created/new.txt").exists());
+    assert!(!dir.path().join("old.txt").exists());
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join(".pc/applied-patches"))
+            .unwrap(),
+        "change-a.patch\nfiles.patch\n"
+    );
+    let pushed = quilt.push().unwrap();
+    assert!(!pushed.is_applied());
+    quilt.pop_all().unwrap();
+    assert!(!dir.path().join("created").exists());
+    assert!(!dir.path().join(".pc/applied-patches").exists());
+    for (path, contents) in original.files() {
+        let on_disk = std::fs::read_to_string(dir.path().join(path)).unwrap();
+        assert_eq!(&on_disk, contents);
+    }
+
+    // patches outside of debian/patches are refused
+    for name in ["../../../etc/x", "/etc/x", "sub/../../x"] {
+        let mut tree = MemoryTree::new();
+        tree.insert("debian/patches/series", &format!("{name}\n"));
+        assert_eq!(
+            Quilt::new(tree).unwrap_err().kind(),
+            std::io::ErrorKind::InvalidData
+        );
+    }
+}
+
+#[test]
+fn quilt_file_diff() {
+    let old: String = (1..=20).map(|i| format!("line {i}\n")).collect();
+    let new = old
+        .replace("line 2\n", "line two\n")
+        .replace("line 15\n", "")
+        .replace("line 20\n", "line 20\nline 21\n");
+    let patch = FilePatch::diff(Some("a/f"), Some("b/f"), &old, &new);
+    assert_eq!(patch.hunks.len(), 2);
+    assert_eq!(
+        patch.hunks[0].to_string(),
+        "@@ -1,5 +1,5 @@\n line 1\n-line 2\n+line two\n line 3\n line 4\n \
+         line 5\n"
+    );
+    assert_eq!((patch.hunks[1].old_start, patch.hunks[1].old_len), (12, 9));
+    assert_eq!((patch.hunks[1].new_start, patch.hunks[1].new_len), (12, 9));
+    let (patched, report) = patch.apply_to("f", &old);
+    assert!(report.offsets.is_empty() && report.rejected.is_empty());
+    assert_eq!(patched, new);
+    let parsed = Patch::parse(&patch.to_string()).unwrap();
+    assert_eq!(parsed.files(), &vec![patch]);
+
+    let created = FilePatch::diff(None, Some("b/f"), "", "new\n");
+    assert!(created.is_creation());
+    assert_eq!(
+        created.to_string(),
+        "--- /dev/null\n+++ b/f\n@@ -0,0 +1,1 @@\n+new\n"
+    );
+    assert!(FilePatch::diff(None, None, &old, &old).hunks.is_empty());
+}
+
+#[test]
+fn quilt_patch_crlf() {
+    let old = "one\r\ntwo\r\nthree\r\n";
+    let new = "one\r\n2\r\nthree\r\n";
+    let patch = Patch::parse(
+        "--- a/f.txt\r\n+++ b/f.txt\r\n@@ -1,3 +1,3 @@\r\n one\r\n-two\r\n\
+         +2\r\n three\r\n",
+    )
+    .unwrap();
+    let file = &patch.files()[0];
+    assert_eq!(file.path(1).as_deref(), Some("f.txt"));
+    assert_eq!(
+        file.hunks[0].old_lines(),
+        ["one\r\n", "two\r\n", "three\r\n"]
+    );
+    let (patched, report) = file.apply_to("f.txt", old);
+    assert!(report.offsets.is_empty() && report.rejected.is_empty());
+    assert_eq!(patched, new);
+    let parsed = Patch::parse(
+        &FilePatch::diff(Some("a/f.txt"), Some("b/f.txt"), old, new)
+            .to_string(),
+    )
+    .unwrap();
+    assert_eq!(parsed.files()[0].apply_to("f.txt", old).0, new);
+}
+
+#[cfg(feature = "deb")]
+#[test]
+fn dsc_build() {
+    let tmp = TempDir::new().unwrap();
+    let src = tmp.path().join("foo-1.2");
+    let main_c = "#include <stdio.h>\nint main() {\n    return 1;\n}\n";
+    let upstream: [(&str, &[u8]); 3] = [
+        ("README", b"hello\n"),
+        ("src/main.c", main_c.as_bytes()),
+        ("logo.png", &[0x89, 0x50, 0xff, 0x00]),
+    ];
+    let mut tar = tar::Builder::new(vec![]);
+    for (path, data) in upstream {
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(0o644);
+        header.set_size(data.len() as u64);
+        let path = format!("foo-1.2/{path}");
+        tar.append_data(&mut header, &path, data).unwrap();
+        let path = tmp.path().join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, data).unwrap();
+    }
+    let orig = Compression::Gzip
+        .compress(&tar.into_inner().unwrap())
+        .unwrap();
+    std::fs::write(tmp.path().join("foo_1.2.orig.tar.gz"), orig).unwrap();
+
+    let mut tree = DirTree::new(&src);
+    let changelog = std::fs::read_to_string(data_path().join("changelog-foo"));
+    tree.write("debian/changelog", &changelog.unwrap()).unwrap();
+    let control = std::fs::read_to_string(data_path().join("control-foo"));
+    tree.write("debian/control", &control.unwrap()).unwrap();
+    tree.write("debian/source/format", "3.0 (quilt)\n").unwrap();
+    tree.write("debian/rules", "#!/usr/bin/make -f\n").unwrap();
+    tree.write("debian/rules~", "backup\n").unwrap();
+    tree.write(".git/HEAD", "ref: refs/heads/main\n").unwrap();
+    tree.write("debian/patches/series", "fix-return.patch\n")
+        .unwrap();
+    tree.write(
+        "debian/patches/fix-return.patch",
+        "--- a/src/main.c\n\
+         +++ b/src/main.c\n\
+         @@ -2,3 +2,3 @@\n \
+         int main() {\n\
+         -    return 1;\n\
+         +    return 0;\n \
+         }\n",
+    )
+    .unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let perms = std::fs::Permissions::from_mode(0o700);
+        std::fs::set_permissions(src.join("debian/rules"), perms).unwrap();
+    }
+    Quilt::new(tree).unwrap().push_all().unwrap();
+
+    let mut builder = SourceBuilder::new(&src).unwrap();
+    builder.set_mtime(1700000000);
+    assert_eq!(builder.source(), "foo");
+    assert_eq!(builder.version().to_string(), "1.2-1");
+    assert!(builder.upstream_changes().unwrap().is_empty());
+    let dsc_path = builder.build().unwrap();
+    assert_eq!(dsc_path, tmp.path().join("foo_1.2-1.dsc"));
+    let dsc = std::fs::read_to_string(&dsc_path).unwrap();
+    let cf = ControlFile::from_reader(dsc.as_bytes()).unwrap();
+    let para = &cf.get_paragraphs()[0];
+    assert_eq!(para.get_entry("Format"), Some("3.0 (quilt)"));
+    assert_eq!(para.get_entry("Source"), Some("foo"));
+    assert_eq!(para.get_entry("Binary"), Some("foo"));
+    assert_eq!(para.get_entry("Architecture"), Some("any"));
+    assert_eq!(para.get_entry("Version"), Some("1.2-1"));
+    assert_eq!(para.get_entry("Standards-Version"), Some("3.9.6"));
+    assert_eq!(
+        para.get_entry("Package-List"),
+        Some("foo deb unknown extra arch=any")
+    );
+    let files = source_paragraph_files(para);
+    assert_eq!(files, ["foo_1.2.orig.tar.gz", "foo_1.2-1.debian.tar.xz"]);
+
+    let tarball = std::fs::read(tmp.path().join(files[1])).unwrap();
+    let reader = Compression::Xz.decoder(tarball.as_slice()).unwrap();
+    let mut archive = tar::Archive::new(reader);
+    let mut entries = vec![];
+    for entry in archive.entries().unwrap() {
+        let header = entry.unwrap().header().clone();
+        assert_eq!(header.mtime().unwrap(), 1700000000);
+        assert_eq!(header.username().unwrap(), Some("root"));
+        let path = String::from_utf8(header.path_bytes().to_vec()).unwrap();
+        entries.push((path, header.mode().unwrap()));
+    }
+    assert_eq!(
+        entries,
+        [
+            ("debian/".to_string(), 0o755),
+            ("debian/changelog".to_string(), 0o644),
+            ("debian/control".to_string(), 0o644),
+            ("debian/patches/".to_string(), 0o755),
+            ("debian/patches/fix-return.patch".to_string(), 0o644),
+            ("debian/patches/series".to_string(), 0o644),
+            ("debian/rules".to_string(), 0o755),
+            ("debian/source/".to_string(), 0o755),
+            ("debian/source/format".to_string(), 0o644),
+        ]
+    );
+
+    // building again yields the same files
+    builder.build().unwrap();
+    assert_eq!(std::fs::read_to_string(&dsc_path).unwrap(), dsc);
+
+    // changes outside patches
+    std::fs::write(src.join("src/main.c"), "int main() { return 2; }\n")
+        .unwrap();
+    std::fs::write(src.join("NEW"), "new\n").unwrap();
+    std::fs::remove_file(src.join("README")).unwrap();
+    assert_eq!(builder.upstream_changes().unwrap(), ["NEW", "src/main.c"]);
+    let err = builder.build().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    // a missing orig tarball
+    std::fs::remove_file(tmp.path().join("foo_1.2.orig.tar.gz")).unwrap();
+    let err = builder.build().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+}
+
+#[cfg(feature = "deb")]
+fn source_paragraph_files(
+    para: &debian::package::ControlParagraph,
+) -> Vec<&str> {
+    para.get_entry("Files")
+        .unwrap()
+        .lines()
+        .filter_map(|l| l.split_whitespace().nth(2))
+        .collect()
+}
+
+#[cfg(feature = "deb")]
+#[test]
+fn debdiff() {
+    let deb = |version: &str, depends: &str, files: &[(&str, &str)]| {
+        let mut control = debian::package::ControlParagraph::default();
+        control.add_entry("Package", "foo".to_string());
+        control.add_entry("Version", version.to_string());
+        control.add_entry("Architecture", "all".to_string());
+        control.add_entry("Maintainer", "Jane Doe <jane@example.org>".into());
+        control.add_entry("Depends", depends.to_string());
+        control.add_entry("Description", "example package".to_string());
+        let mut builder = DebBuilder::new(control).unwrap();
+        for (path, contents) in files {
+            let contents = contents.as_bytes().to_vec();
+            builder.add_file(path, contents, 0o644).unwrap();
+        }
+        builder.add_maintainer_script(ScriptKind::Postinst, version);
+        let mut data = vec![];
+        builder.write(&mut data).unwrap();
+        DebFile::from_reader(data.as_slice()).unwrap()
+    };
+    let old = deb(
+        "1.0-1",
+        "libc6 (>= 2.34), foo-data,\n libfoo1 | libfoo2",
+        &[("/usr/bin/foo", "old"), ("/usr/share/foo/a", "a")],
+    );
+    let new = deb(
+        "1.0-2",
+        "libc6 (>= 2.36), libfoo1 | libfoo2, bar",
+        &[("/usr/bin/foo", "new"), ("/usr/share/foo/b", "b")],
+    );
+    assert!(diff_debs(&old, &old).unwrap().is_empty());
+    let diff = diff_debs(&old, &new).unwrap();
+    assert_eq!(
+        diff.fields,
+        [FieldChange {
+            field: "Version".to_string(),
+            old: Some("1.0-1".to_string()),
+            new: Some("1.0-2".to_string()),
+        }]
+    );
+    let deps: Vec<(ChangeKind, Option<&str>, Option<&str>)> = diff
+        .dependencies
+        .iter()
+        .map(|d| (d.kind(), d.old.as_deref(), d.new.as_deref()))
+        .collect();
+    assert_eq!(
+        deps,
+        [
+            (
+                ChangeKind::Modified,
+                Some("libc6 (>= 2.34)"),
+                Some("libc6 (>= 2.36)")
+            ),
+            (ChangeKind::Removed, Some("foo-data"), None),
+            (ChangeKind::Added, None, Some("bar")),
+        ]
+    );
+    assert!(diff.dependencies.iter().all(|d| d.field == "Depends"));
+    assert_eq!(
+        diff.files,
+        [
+            FileChange {
+                path: "/usr/bin/foo".to_string(),
+                kind: ChangeKind::Modified
+            },
+            FileChange {
+                path: "/usr/share/foo/a".to_string(),
+                kind: ChangeKind::Removed
+            },
+            FileChange {
+                path: "/usr/share/foo/b".to_string(),
+                kind: ChangeKind::Added
+            },
+        ]
+    );
+    assert_eq!(diff.scripts.len(), 1);
+    assert_eq!(diff.scripts[0].name, "postinst");
+    assert_eq!(diff.scripts[0].kind(), ChangeKind::Modified);
+    assert_eq!(diff.scripts[0].new.as_deref(), Some("1.0-2"));
+
+    // source packages
+    let tmp = TempDir::new().unwrap();
+    let tarball = |name: &str,
+                   compression: Compression,
+                   files: &[(&str, &str)]| {
+        let mut tar = tar::Builder::new(vec![]);
+        for (path, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_mode(0o644);
+            header.set_size(contents.len() as u64);
+            tar.append_data(&mut header, path, contents.as_bytes())
+                .unwrap();
+        }
+        let data = compression.compress(&tar.into_inner().unwrap()).unwrap();
+        std::fs::write(tmp.path().join(name), data).unwrap();
+    };
+    let dsc = |version: &str, build_depends: &str, files: &[&str]| {
+        let path = tmp.path().join(format!("foo_{version}.dsc"));
+        let mut text = format!(
+            "Format: 3.0 (quilt)\nSource: foo\nVersion: {version}\n\
+             Build-Depends: {build_depends}\nFiles:\n"
+        );
+        for name in files {
+            text += &format!(" 00000000000000000000000000000000 1 {name}\n");
+        }
+        std::fs::write(&path, text).unwrap();
+        path
+    };
+    tarball(
+        "foo_1.2.orig.tar.gz",
+        Compression::Gzip,
+        &[
+            ("foo-1.2/README", "hello\n"),
+            ("foo-1.2/src/main.c", "main\n"),
+        ],
+    );
+    tarball(
+        "foo_1.2-1.debian.tar.xz",
+        Compression::Xz,
+        &[
+            ("debian/control", "Source: foo\n"),
+            ("debian/foo.postinst", "#!/bin/sh\n"),
+            ("debian/rules", "#!/usr/bin/make -f\n"),
+        ],
+    );
+    tarball(
+        "foo_1.2-2.debian.tar.xz",
+        Compression::Xz,
+        &[
+            ("debian/control", "Source: foo\n"),
+            ("debian/foo.prerm", "#!/bin/sh\n"),
+            ("debian/rules", "#!/usr/bin/make -f\n%:\n"),
+        ],
+    );
+    let old = dsc(
+        "1.2-1",
+        "debhelper-compat (= 12)",
+        &["foo_1.2.orig.tar.gz", "foo_1.2-1.debian.tar.xz"],
+    );
+    let new = dsc(
+        "1.2-2",
+        "debhelper-compat (= 13), pkg-config",
+        &["foo_1.2.orig.tar.gz", "foo_1.2-2.debian.tar.xz"],
+    );
+    let diff = diff_dscs(&old, &new).unwrap();
+    let fields: Vec<&str> =
+        diff.fields.iter().map(|f| f.field.as_str()).collect();
+    assert_eq!(fields, ["Version"]);
+    let deps: Vec<(ChangeKind, Option<&str>)> = diff
+        .dependencies
+        .iter()
+        .map(|d| (d.kind(), d.new.as_deref()))
+        .collect();
+    assert_eq!(
+        deps,
+        [
+            (ChangeKind::Modified, Some("debhelper-compat (= 13)")),
+            (ChangeKind::Added, Some("pkg-config")),
+        ]
+    );
+    let files: Vec<(&str, ChangeKind)> = diff
+        .files
+        .iter()
+        .map(|f| (f.path.as_str(), f.kind))
+        .collect();
+    assert_eq!(
+        files,
+        [
+            ("debian/foo.postinst", ChangeKind::Removed),
+            ("debian/foo.prerm", ChangeKind::Added),
+            ("debian/rules", ChangeKind::Modified),
+        ]
+    );
+    let scripts: Vec<(&str, ScriptKind, ChangeKind)> = diff
+        .scripts
+        .iter()
+        .map(|s| (s.name.as_str(), s.kind, s.kind()))
+        .collect();
+    assert_eq!(
+        scripts,
+        [
+            (
+                "debian/foo.postinst",
+                ScriptKind::Postinst,
+                ChangeKind::Removed
+            ),
+            ("debian/foo.prerm", ScriptKind::Prerm, ChangeKind::Added),
+        ]
+    );
+
+    std::fs::remove_file(tmp.path().join("foo_1.2-2.debian.tar.xz")).unwrap();
+    let err = diff_dscs(&old, &new).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+}
+
+#[test]
+fn archive_overrides() {
+    let mut overrides =
+        Overrides::from_file(&data_path().join("override.stable.main"))
+            .unwrap();
+    overrides
+        .load_extra_file(&data_path().join("override.stable.extra.main"))
+        .unwrap();
+    let names: Vec<&str> =
+        overrides.entries().map(|e| e.package.as_str()).collect();
+    assert_eq!(names, ["bar", "baz", "foo", "libfoo1"]);
+    assert_eq!(
+        overrides.get("libfoo1"),
+        Some(&OverrideEntry {
+            package: "libfoo1".to_string(),
+            priority: "optional".to_string(),
+            section: "libs".to_string(),
+            maintainer: None,
+        })
+    );
+    assert_eq!(
+        overrides.get("bar").unwrap().maintainer,
+        Some(MaintainerOverride::Set(
+            "Jane Doe <jane@example.org>".to_string()
+        ))
+    );
+    let replace = overrides.get("baz").unwrap().maintainer.clone().unwrap();
+    assert_eq!(
+        replace,
+        MaintainerOverride::Replace {
+            old: vec![
+                "Old One <old@example.org>".to_string(),
+                "Older <older@example.org>".to_string()
+            ],
+            new: "New <new@example.org>".to_string(),
+        }
+    );
+    assert_eq!(
+        replace.apply(Some("Older <older@example.org>")),
+        Some("New <new@example.org>")
+    );
+    assert_eq!(replace.apply(Some("Other <other@example.org>")), None);
+    assert_eq!(
+        overrides.extra("bar"),
+        [("Task".into(), "development".into())]
+    );
+    assert!(overrides.extra("baz").is_empty());
+
+    let para = |package: &str, maintainer: &str| {
+        let mut para = debian::package::ControlParagraph::default();
+        para.add_entry("Package", package.to_string());
+        para.add_entry("Version", "1.0-1".to_string());
+        para.add_entry("Architecture", "all".to_string());
+        para.add_entry("Maintainer", maintainer.to_string());
+        para.add_entry("Priority", "important".to_string());
+        para
+    };
+    let mut foo = para("foo", "Foo <foo@example.org>");
+    assert!(overrides.apply(&mut foo));
+    assert_eq!(
+        foo.to_string(),
+        "Package: foo\nVersion: 1.0-1\nArchitecture: all\n\
+         Maintainer: Foo <foo@example.org>\nPriority: optional\n\
+         Task: minimal, standard\nTag: role::program\nSection: utils\n"
+    );
+    let mut baz = para("baz", "Old One <old@example.org>");
+    assert!(overrides.apply(&mut baz));
+    assert_eq!(baz.get_entry("Maintainer"), Some("New <new@example.org>"));
+    let mut qux = para("qux", "Qux <qux@example.org>");
+    assert!(!overrides.apply(&mut qux));
+    assert_eq!(qux.get_entry("Priority"), Some("important"));
+
+    #[cfg(feature = "deb")]
+    {
+        let mut index = PackagesIndex::default();
+        index.add_paragraph(para("qux", "Qux <qux@example.org>"));
+        index.add_paragraph(para("bar", "Bar <bar@example.org>"));
+        index.add_paragraph(para("foo", "Foo <foo@example.org>"));
+        assert_eq!(index.apply_overrides(&overrides), ["qux"]);
+        let bar = &index.paragraphs()[0];
+        assert_eq!(bar.get_entry("Section"), Some("devel"));
+        assert_eq!(
+            bar.get_entry("Maintainer"),
+            Some("Jane Doe <jane@example.org>")
+        );
+    }
+
+    assert!("foo optional\n".parse::<Overrides>().is_err());
+    assert!(Overrides::default().parse_extra("foo Task\n").is_err());
+}
+
+#[test]
+fn archive_index_cache() {
+    let dir = TempDir::new().unwrap();
+    let cache = IndexCache::new(&dir.path().join("cache"));
+    let data = std::fs::read(data_path().join("Packages-resolver")).unwrap();
+    let sha256 = format!("{:x}", Sha256::digest(&data));
+    assert!(cache.get(&sha256).unwrap().is_none());
+    assert!(cache.get("../foo").is_err());
+
+    let index = cache.load(&data).unwrap();
+    let cached = cache.get(&sha256).unwrap().unwrap();
+    assert_eq!(cached.to_string(), index.to_string());
+    assert_eq!(
+        cached.get_paragraphs().len(),
+        ControlFile::from_reader(data.as_slice())
+            .unwrap()
+            .get_paragraphs()
+            .len()
+    );
+
+    // corrupt entries are ignored and replaced
+    let path = cache.dir().join(&sha256);
+    let entry = std::fs::read(&path).unwrap();
+    std::fs::write(&path, &entry[..entry.len() - 1]).unwrap();
+    assert!(cache.get(&sha256).unwrap().is_none());
+    let parsed = cache
+        .get_or_parse(&sha256, || ControlFile::from_reader(data.as_slice()))
+        .unwrap();
+    assert_eq!(parsed.to_string(), index.to_string());
+    assert_eq!(std::fs::read(&path).unwrap(), entry);
+
+    // entries not listed in a Release file are pruned
+    let other = format!("{:x}", Sha256::digest(b"Package: bar\n"));
+    cache.load(b"Package: bar\n").unwrap();
+    let release = Release::from_reader(
+        format!(
+            "SHA256:\n {sha256} {} main/binary-all/Packages\n",
+            data.len()
+        )
+        .as_bytes(),
+    )
+    .unwrap();
+    assert_eq!(cache.prune(&[&release]).unwrap(), 1);
+    assert!(cache.get(&sha256).unwrap().is_some());
+    assert!(cache.get(&other).unwrap().is_none());
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn archive_fetch_index_cache() {
+    let keyring = data_path().join("repo").join("keyring.gpg");
+    let keyring = Keyring::from_file(&keyring).unwrap();
+    let (url, server) = serve_files(data_path().join("repo"), 3);
+    let dir = TempDir::new().unwrap();
+
+    let mut fetcher = RepositoryFetcher::new(&url, "stable", keyring.clone());
+    fetcher.set_index_cache(IndexCache::new(dir.path()));
+    fetcher.update().unwrap();
+    let packages = fetcher.fetch_packages("main", "all").unwrap();
+    let sha256 = &fetcher
+        .release()
+        .unwrap()
+        .index("main/binary-all/Packages.gz")
+        .unwrap()
+        .sha256;
+    assert!(dir.path().join(sha256).exists());
+
+    // the second fetcher finds the parsed index in the cache
+    let mut fetcher = RepositoryFetcher::new(&url, "stable", keyring.clone());
+    fetcher.set_index_cache(IndexCache::new(dir.path()));
+    fetcher.update().unwrap();
+    let cached = fetcher.fetch_packages("main", "all").unwrap();
+    assert_eq!(cached.to_string(), packages.to_string());
+
+    let in_release = "/dists/stable/InRelease";
+    let packages_gz = "/dists/stable/main/binary-all/by-hash/SHA256/\
+                       bb20afdc0d8930e35d9c8b13e8a518a76571c26cf8b270fd499edca9ee813e6a";
+    assert_eq!(
+        server.join().unwrap(),
+        [in_release, packages_gz, in_release]
+    );
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn uscan_scan() {
+    let (url, server) = serve_files(data_path().join("uscan"), 9);
+    let mut scanner = Scanner::new("foo");
+    scanner.set_github_api_url(&url);
+
+    // a directory listing, with substitutions
+    let entry = WatchEntry::parse_line(&format!(
+        "opts=uversionmangle=s/rc/~rc/,pgpsigurlmangle=s/$/.asc/ \
+         {url}/files/ @PACKAGE@@ANY_VERSION@@ARCHIVE_EXT@"
+    ))
+    .unwrap();
+    let releases = scanner.scan(&entry).unwrap();
+    let versions: Vec<&str> =
+        releases.iter().map(|r| r.version.as_str()).collect();
+    assert_eq!(versions, ["1.10~rc1", "1.2", "1.0"]);
+    let newest = &releases[0];
+    assert_eq!(newest.upstream_version, "1.10rc1");
+    assert_eq!(newest.url, format!("{url}/files/foo-1.10rc1.tar.gz"));
+    assert_eq!(newest.filename, "foo-1.10rc1.tar.gz");
+    assert_eq!(
+        newest.signature_url,
+        Some(format!("{url}/files/foo-1.10rc1.tar.gz.asc"))
+    );
+    assert_eq!(releases[1].url, format!("{url}/files/foo-1.2.tar.gz"));
+    let current = Version::parse("1.2-1").unwrap();
+    assert!(scanner.check(&entry, &current).unwrap().is_newer());
+
+    // a version in the directory name
+    let entry = WatchEntry::parse_line(&format!(
+        "{url}/releases/(\\d[\\d.]*)/ bar-([\\d.]+)\\.tar\\.xz"
+    ))
+    .unwrap();
+    let newest = scanner.newest(&entry).unwrap();
+    assert_eq!(newest.version, "2.0.1");
+    assert_eq!(newest.url, format!("{url}/releases/2.0/bar-2.0.1.tar.xz"));
+
+    // searching the whole page
+    let entry = WatchEntry::parse_line(&format!(
+        "opts=searchmode=plain {url}/plain/list.json /dl/baz-([\\d.]+)\\.tar\\.gz"
+    ))
+    .unwrap();
+    let newest = scanner.newest(&entry).unwrap();
+    assert_eq!(newest.version, "3.1");
+    assert_eq!(newest.url, format!("{url}/dl/baz-3.1.tar.gz"));
+
+    // GitHub tags, through the API
+    let watch = WatchFile::from_file(&data_path().join("watch-v4")).unwrap();
+    let result = scanner
+        .check(&watch.entries[0], &Version::parse("1.10-1").unwrap())
+        .unwrap();
+    assert!(!result.is_newer());
+    assert_eq!(result.current, "1.10");
+    assert_eq!(result.newest.version, "1.10");
+    assert_eq!(
+        result.newest.url,
+        "https://github.com/foo/foo/archive/refs/tags/v1.10.tar.gz"
+    );
+    assert_eq!(result.newest.filename, "foo-1.10.tar.gz");
+
+    let entry = WatchEntry::parse_line(&format!(
+        "{url}/missing/ foo-(\\d+)\\.tar\\.gz"
+    ))
+    .unwrap();
+    let err = scanner.scan(&entry).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    let entry =
+        WatchEntry::parse_line(&format!("{url}/files/ bar-(\\d+)\\.tar\\.gz"))
+            .unwrap();
+    assert!(scanner.scan(&entry).unwrap().is_empty());
+    let err = scanner.newest(&entry).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+
+    assert_eq!(
+        server.join().unwrap(),
+        [
+            "/files/",
+            "/files/",
+            "/releases/",
+            "/releases/2.0/",
+            "/plain/list.json",
+            "/repos/foo/foo/tags?per_page=100",
+            "/missing/",
+            "/files/",
+            "/files/",
+        ]
+    );
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn uscan_git() {
+    let dir = TempDir::new().unwrap();
+    let git = |args: &[&str]| {
+        let status = std::process::Command::new("git")
+            .args(["-c", "user.name=Jane", "-c", "user.email=jane@example.org"])
+            .arg("-C")
+            .arg(dir.path())
+            .args(args)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success());
+    };
+    git(&["init", "-q"]);
+    git(&["commit", "-q", "--allow-empty", "-m", "initial"]);
+    git(&["tag", "v1.0"]);
+    git(&["tag", "-a", "-m", "release", "v2.0"]);
+    git(&["tag", "debian/1.0-1"]);
+
+    let scanner = Scanner::new("foo");
+    let entry = WatchEntry::parse_line(&format!(
+        "opts=mode=git {} refs/tags/v([\\d.]+)",
+        dir.path().display()
+    ))
+    .unwrap();
+    let releases = scanner.scan(&entry).unwrap();
+    assert_eq!(releases.len(), 2);
+    assert_eq!(releases[0].version, "2.0");
+    assert_eq!(releases[0].git_ref.as_deref(), Some("refs/tags/v2.0"));
+    assert_eq!(releases[0].url, dir.path().display().to_string());
+    assert_eq!(releases[0].filename, "foo-2.0.tar.xz");
+
+    let entry = WatchEntry::parse_line(&format!(
+        "opts=mode=git {} HEAD",
+        dir.path().display()
+    ))
+    .unwrap();
+    assert!(scanner.scan(&entry).is_err());
+}
+
+#[cfg(feature = "deb")]
+#[test]
+fn uupdate_new_upstream() {
+    let tmp = TempDir::new().unwrap();
+    let src = tmp.path().join("foo-1.2");
+    let mut tree = DirTree::new(&src);
+    tree.write("README", "hello\n").unwrap();
+    tree.write("src/main.c", "int main() {\n    return 1;\n}\n")
+        .unwrap();
+    let changelog = std::fs::read_to_string(data_path().join("changelog-foo"));
+    tree.write("debian/changelog", &changelog.unwrap()).unwrap();
+    tree.write("debian/source/format", "3.0 (quilt)\n").unwrap();
+    tree.write("debian/patches/series", "fix-return.patch\nreadme.patch\n")
+        .unwrap();
+    tree.write(
+        "debian/patches/fix-return.patch",
+        "--- a/src/main.c\n\
+         +++ b/src/main.c\n\
+         @@ -1,3 +1,3 @@\n \
+         int main() {\n\
+         -    return 1;\n\
+         +    return 0;\n \
+         }\n",
+    )
+    .unwrap();
+    tree.write(
+        "debian/patches/readme.patch",
+        "--- a/README\n+++ b/README\n@@ -1 +1 @@\n-hello\n+hello world\n",
+    )
+    .unwrap();
+
+    // the new upstream version changed the README and brings its own
+    // debian directory
+    let upstream: [(&str, &[u8]); 4] = [
+        ("README", b"hello, world\n"),
+        ("src/main.c", b"int main() {\n    return 1;\n}\n"),
+        ("NEWS", b"1.3: greet the world\n"),
+        ("debian/control", b"Source: upstream\n"),
+    ];
+    let mut tar = tar::Builder::new(vec![]);
+    for (path, data) in upstream {
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(0o644);
+        header.set_size(data.len() as u64);
+        tar.append_data(&mut header, format!("foo-1.3/{path}"), data)
+            .unwrap();
+    }
+    let tarball = tmp.path().join("foo-1.3.tar.gz");
+    let data = Compression::Gzip
+        .compress(&tar.into_inner().unwrap())
+        .unwrap();
+    std::fs::write(&tarball, data).unwrap();
+
+    assert!(UpstreamUpdate::new(&src, &tarball, "1.1").is_err());
+    let zip = tmp.path().join("foo-1.3.zip");
+    assert!(UpstreamUpdate::new(&src, &zip, "1.3").is_err());
+
+    let mut update = UpstreamUpdate::new(&src, &tarball, "1.3").unwrap();
+    update.set_message("New upstream release 1.3.");
+    assert_eq!(update.source(), "foo");
+    assert_eq!(update.version().to_string(), "1.3-1");
+    assert_eq!(
+        update.orig_tarball(),
+        tmp.path().join("foo_1.3.orig.tar.gz")
+    );
+    let reports = update.check_patches().unwrap();
+    assert_eq!(reports.len(), 2);
+    assert!(reports[0].is_clean());
+    assert!(!reports[1].is_applied());
+
+    let result = update.update().unwrap();
+    assert_eq!(result.source_dir, tmp.path().join("foo-1.3"));
+    assert_eq!(result.failed_patches(), ["readme.patch"]);
+    assert_eq!(
+        std::fs::read(&result.orig_tarball).unwrap(),
+        std::fs::read(&tarball).unwrap()
+    );
+    let new = DirTree::new(&result.source_dir);
+    assert_eq!(new.read("NEWS").unwrap().unwrap(), "1.3: greet the world\n");
+    assert!(new.read("debian/control").unwrap().is_none());
+    // patches are not applied
+    assert_eq!(
+        new.read("src/main.c").unwrap().unwrap(),
+        "int main() {\n    return 1;\n}\n"
+    );
+    assert!(new.read(".pc/applied-patches").unwrap().is_none());
+    assert!(new.read("debian/patches/readme.patch").unwrap().is_some());
+    let changelog =
+        Changelog::from_file(&result.source_dir.join("debian/changelog"))
+            .unwrap();
+    let entries = changelog.entries();
+    assert_eq!(entries.len(), 4);
+    assert_eq!(entries[0].version(), "1.3-1");
+    assert_eq!(entries[0].distributions(), &["UNRELEASED"]);
+    assert_eq!(entries[0].detail(), "  * New upstream release 1.3.");
+    assert_eq!(entries[1].version(), "1.2-1");
+
+    // the tree is not created twice
+    let err = update.update().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+}
+
+#[test]
+fn scaffold_debian_dir() {
+    let tmp = TempDir::new().unwrap();
+    let mut desc =
+        PackageDescription::new("foo", "1.2", "Jane Doe <jane@example.org>");
+    desc.synopsis = "frobnicates bars".to_string();
+    desc.description = "Foo frobnicates bars.\n\nIt is fast.".to_string();
+    desc.build_depends = vec!["pkgconf".to_string()];
+    desc.homepage = Some("https://example.org/foo".to_string());
+    desc.license = "GPL-2+".to_string();
+    desc.copyright = vec!["2023 Upstream Author <upstream@example.org>".into()];
+    desc.watch_url = Some("https://example.org/releases/".to_string());
+    desc.itp_bug = Some(1234);
+    desc.date = Some(
+        chrono::DateTime::parse_from_rfc2822("Mon, 02 Oct 2023 10:00:00 +0200")
+            .unwrap(),
+    );
+    desc.write(tmp.path()).unwrap();
+    let debian = tmp.path().join("debian");
+
+    let control = ControlFile::from_file(&debian.join("control")).unwrap();
+    let paras = control.get_paragraphs();
+    assert_eq!(paras.len(), 2);
+    assert_eq!(
+        paras[0].get_entry("Build-Depends"),
+        Some("debhelper-compat (= 13),\n pkgconf")
+    );
+    assert_eq!(
+        paras[0].get_entry("Homepage"),
+        Some("https://example.org/foo")
+    );
+    assert_eq!(paras[1].get_entry("Package"), Some("foo"));
+    assert_eq!(paras[1].get_entry("Architecture"), Some("any"));
+    assert_eq!(
+        paras[1].get_entry("Description"),
+        Some("frobnicates bars\n Foo frobnicates bars.\n .\n It is fast.")
+    );
+
+    let changelog = Changelog::from_file(&debian.join("changelog")).unwrap();
+    let entry = &changelog.entries()[0];
+    assert_eq!(entry.version(), "1.2-1");
+    assert_eq!(entry.distributions(), &["UNRELEASED"]);
+    assert_eq!(entry.detail(), "  * Initial release. (Closes: #1234)");
+    assert_eq!(entry.maintainer_name(), "Jane Doe");
+    assert_eq!(entry.maintainer_email(), "jane@example.org");
+
+    let copyright = Copyright::from_file(&debian.join("copyright")).unwrap();
+    assert_eq!(
+        copyright.license_for_path("src/foo.c").unwrap().name,
+        "GPL-2+"
+    );
+    assert_eq!(
+        copyright.files[1].copyright,
+        ["2023 Jane Doe <jane@example.org>"]
+    );
+    assert!(copyright
+        .license_text("GPL-2+")
+        .unwrap()
+        .contains("/usr/share/common-licenses/GPL-2"));
+
+    let watch = WatchFile::from_file(&debian.join("watch")).unwrap();
+    assert_eq!(watch.entries[0].url, "https://example.org/releases/");
+    let format = SourceFormat::from_dir(&debian).unwrap();
+    assert_eq!(format, SourceFormat::Quilt);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let meta = std::fs::metadata(debian.join("rules")).unwrap();
+        assert_eq!(meta.permissions().mode() & 0o777, 0o755);
+    }
+    let err = desc.write(tmp.path()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+
+    // libraries get a runtime and a development package
+    let mut desc = PackageDescription::new("libbar2", "0.9", "Jane Doe");
+    desc.kind = PackageKind::Library {
+        soversion: "0".to_string(),
+    };
+    assert_eq!(desc.binary_packages(), ["libbar2-0", "libbar2-dev"]);
+    let files = desc.files();
+    assert!(files.contains_key("debian/libbar2-0.install"));
+    assert!(files.contains_key("debian/watch.ex"));
+    assert!(!files.contains_key("debian/watch"));
+    let control: ControlFile =
+        ControlFile::from_reader(files["debian/control"].as_bytes()).unwrap();
+    assert_eq!(
+        control.get_paragraphs()[2].get_entry("Depends"),
+        Some("libbar2-0 (= ${binary:Version}),\n ${misc:Depends}")
+    );
+
+    desc.native = true;
+    let files = desc.files();
+    assert_eq!(files["debian/source/format"], "3.0 (native)\n");
+    assert!(!files.contains_key("debian/watch.ex"));
+    assert!(files["debian/changelog"].starts_with("libbar2 (0.9) UNRELEASED"));
+}
+
+#[cfg(feature = "cargo")]
+#[test]
+fn cargo_control() {
+    let metadata =
+        CrateMetadata::from_file(&data_path().join("cargo-foo.toml")).unwrap();
+    assert_eq!(metadata.source_name(), "rust-foo-bar");
+    assert_eq!(metadata.upstream_version().to_string(), "0.3.1~rc.1");
+    assert_eq!(metadata.dependencies.len(), 7);
+    let json = &metadata.dependencies[0];
+    assert_eq!(
+        (json.name.as_str(), json.package.as_str()),
+        ("json", "serde_json")
+    );
+    assert_eq!(json.compat_version().as_deref(), Some("1"));
+    assert_eq!(
+        json.relations(&["std"]),
+        [
+            "librust-serde-json-1+default-dev (>= 1.0.80-~~)",
+            "librust-serde-json-1+default-dev (<< 1.1-~~)",
+            "librust-serde-json-1+std-dev (>= 1.0.80-~~)",
+            "librust-serde-json-1+std-dev (<< 1.1-~~)",
+        ]
+    );
+    assert_eq!(
+        metadata.provided_features().into_iter().collect::<Vec<_>>(),
+        ["default", "std"]
+    );
+
+    let control = metadata.control("Jane Doe <jane@example.org>");
+    let paras = control.get_paragraphs();
+    assert_eq!(paras.len(), 6);
+    assert_eq!(
+        paras[0].get_entry("Build-Depends"),
+        Some(concat!(
+            "debhelper-compat (= 13),\n",
+            " dh-sequence-cargo,\n",
+            " cargo:native <!nocheck>,\n",
+            " rustc:native <!nocheck>,\n",
+            " libstd-rust-dev <!nocheck>,\n",
+            " librust-libc-0.2-dev (>= 0.2.150-~~) <!nocheck>,\n",
+            " librust-libc-0.2-dev (<< 0.2.151-~~) <!nocheck>,\n",
+            " librust-log-0.4+default-dev (>= 0.4.17-~~) <!nocheck>,\n",
+            " librust-cc-1+default-dev <!nocheck>,\n",
+            " librust-nix-0.27+fs-dev <!nocheck>"
+        ))
+    );
+    assert_eq!(
+        paras[0].get_entry("Homepage"),
+        Some("https://example.org/foo_bar")
+    );
+    assert_eq!(paras[0].get_entry("X-Cargo-Crate"), Some("foo_bar"));
+
+    let main = &paras[1];
+    assert_eq!(main.get_entry("Package"), Some("librust-foo-bar-dev"));
+    let provides = main.get_entry("Provides").unwrap();
+    assert!(provides.starts_with(concat!(
+        "librust-foo-bar-0-dev (= ${binary:Version}),\n",
+        " librust-foo-bar-0.3-dev (= ${binary:Version}),\n",
+        " librust-foo-bar-0.3.1-dev (= ${binary:Version}),\n",
+        " librust-foo-bar+default-dev (= ${binary:Version}),\n",
+    )));
+    assert!(provides
+        .ends_with(" librust-foo-bar-0.3.1+std-dev (= ${binary:Version})"));
+    assert_eq!(
+        main.get_entry("Description"),
+        Some(concat!(
+            "Frobnicates bars - Rust source code\n",
+            " Source code for Debianized Rust crate \"foo_bar\""
+        ))
+    );
+
+    let packages: Vec<&str> = paras[2..]
+        .iter()
+        .map(|p| p.get_entry("Package").unwrap())
+        .collect();
+    assert_eq!(
+        packages,
+        [
+            "librust-foo-bar+json-dev",
+            "librust-foo-bar+rand-dev",
+            "librust-foo-bar+random-dev",
+            "librust-foo-bar+serialize-dev",
+        ]
+    );
+    assert_eq!(
+        paras[3].get_entry("Depends"),
+        Some(concat!(
+            "librust-foo-bar-dev (= ${binary:Version}),\n",
+            " librust-rand+default-dev (>= 0.7-~~),\n",
+            " librust-rand+default-dev (<< 0.9-~~),\n",
+            " ${misc:Depends}"
+        ))
+    );
+    // optional dependencies are enabled by their implicit features
+    assert_eq!(
+        paras[4].get_entry("Depends"),
+        Some(concat!(
+            "librust-foo-bar-dev (= ${binary:Version}),\n",
+            " librust-foo-bar+rand-dev (= ${binary:Version}),\n",
+            " ${misc:Depends}"
+        ))
+    );
+    assert_eq!(
+        paras[5].get_entry("Depends"),
+        Some(concat!(
+            "librust-foo-bar-dev (= ${binary:Version}),\n",
+            " librust-serde-1+default-dev (>= 1.0.100-~~),\n",
+            " librust-serde-1+derive-dev (>= 1.0.100-~~),\n",
+            " librust-foo-bar+json-dev (= ${binary:Version}),\n",
+            " librust-log-0.4+default-dev (>= 0.4.17-~~),\n",
+            " librust-log-0.4+std-dev (>= 0.4.17-~~),\n",
+            " ${misc:Depends}"
+        ))
+    );
+
+    // features must refer to features or dependencies
+    let err = CrateMetadata::from_manifest(
+        "[package]\nname = \"foo\"\nversion = \"1.0.0\"\n\
+         [features]\nbar = [\"baz\"]\n",
+    )
+    .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn soname_package_names() {
+    let cases = [
+        ("libfoo.so.1", "libfoo", Some("1"), "libfoo1", "libfoo-dev"),
+        (
+            "libfoo-2.so.0",
+            "libfoo-2",
+            Some("0"),
+            "libfoo-2-0",
+            "libfoo-2-dev",
+        ),
+        (
+            "libbar-1.2.so",
+            "libbar",
+            Some("1.2"),
+            "libbar1.2",
+            "libbar-dev",
+        ),
+        (
+            "libBaz_x.so.3.1",
+            "libBaz_x",
+            Some("3.1"),
+            "libbaz-x3.1",
+            "libbaz-x-dev",
+        ),
+        (
+            "libplugin.so",
+            "libplugin",
+            None,
+            "libplugin",
+            "libplugin-dev",
+        ),
+    ];
+    for (s, name, version, package, dev) in cases {
+        let soname: Soname = s.parse().unwrap();
+        assert_eq!(soname.name, name);
+        assert_eq!(soname.version.as_deref(), version);
+        assert_eq!(soname.package_name(), package);
+        assert_eq!(soname.dev_package_name(), dev);
+    }
+    assert!("libfoo.a".parse::<Soname>().is_err());
+    assert!("libfoo.so.".parse::<Soname>().is_err());
+    assert!("/usr/lib/libfoo.so.1".parse::<Soname>().is_err());
+
+    assert_eq!(strip_t64("libfoo1t64"), "libfoo1");
+    assert_eq!(strip_t64("libbart64"), "libbart64");
+    let soname: Soname = "libfoo.so.1".parse().unwrap();
+    assert_eq!(soname.t64_package_name(), "libfoo1t64");
+    assert!(soname.matches_package("libfoo1"));
+    assert!(soname.matches_package("libfoo1t64"));
+    assert!(!soname.matches_package("libfoo2"));
+
+    let control: ControlFile = ControlFile::from_reader(
+        &b"Source: foo
+
+Package: libfoo1t64
+Architecture: any
+
+Package: libfoo-dev
+Architecture: any
+
+Package: libfoo-bin
+Architecture: any
+"[..],
+    )
+    .unwrap();
+    assert_eq!(soname.check_control(&control), []);
+    let soname: Soname = "libfoo.so.2".parse().unwrap();
+    let issues = soname.check_control(&control);
+    assert_eq!(
+        issues,
+        [
+            LibraryNameIssue::MissingPackage("libfoo2".to_string()),
+            LibraryNameIssue::WrongName(
+                "libfoo1t64".to_string(),
+                "libfoo2".to_string()
+            ),
+        ]
+    );
+    assert_eq!(issues[1].to_string(), "libfoo1t64 should be named libfoo2");
+}
+
+#[test]
+fn shlibs_file() {
+    let text = "\
+# comment
+libfoo 1 libfoo1 (>= 1.2)
+udeb: libfoo 1 libfoo1-udeb (>= 1.2)
+libbar 2.1 libbar2.1
+";
+    let shlibs: ShlibsFile = text.parse().unwrap();
+    assert_eq!(shlibs.entries.len(), 3);
+    assert_eq!(shlibs.entries[1].package_type.as_deref(), Some("udeb"));
+    assert_eq!(shlibs.entries[1].dependency, "libfoo1-udeb (>= 1.2)");
+    let foo: Soname = "libfoo.so.1".parse().unwrap();
+    assert_eq!(
+        shlibs.get(&foo, None).unwrap().dependency,
+        "libfoo1 (>= 1.2)"
+    );
+    assert_eq!(
+        shlibs.get(&foo, Some("udeb")).unwrap().dependency,
+        "libfoo1-udeb (>= 1.2)"
+    );
+    let bar: Soname = "libbar-2.1.so".parse().unwrap();
+    assert_eq!(
+        shlibs.get(&bar, Some("udeb")).unwrap().dependency,
+        "libbar2.1"
+    );
+    assert!(shlibs.get(&"libfoo.so.2".parse().unwrap(), None).is_none());
+    assert_eq!(shlibs.to_string(), text.replace("# comment\n", ""));
+    assert!("libfoo\n".parse::<ShlibsFile>().is_err());
+}
+
+#[cfg(feature = "elf")]
+#[test]
+fn elf_dynamic_info() {
+    let path = data_path().join("shlibdeps/prog");
+    let object = ElfObject::from_file(&path).unwrap();
+    assert_eq!(object.soname, None);
+    assert_eq!(object.needed, ["libfoo.so.1", "libbar.so.2", "libbaz-3.so"]);
+    assert_eq!(object.runpath, ["$ORIGIN/../lib/prog"]);
+    assert_eq!(
+        object.runpath_dirs(std::path::Path::new("/usr/bin/prog")),
+        ["/usr/bin/../lib/prog"]
+    );
+    let mut used: Vec<String> = object
+        .undefined_symbols()
+        .map(|s| s.qualified_name())
+        .collect();
+    used.sort();
+    assert_eq!(used, ["bar_run@Base", "baz@Base", "foo_new@FOO_1.2"]);
+    assert_eq!(object.defined_symbols().count(), 0);
+
+    let err = ElfObject::parse(b"#!/bin/sh\n").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[cfg(all(feature = "elf", unix))]
+#[test]
+fn shlibdeps_compute() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path().join("root");
+    let lib_dir = root.join("usr/lib/x86_64-linux-gnu");
+    let info = root.join("var/lib/dpkg/info");
+    std::fs::create_dir_all(&lib_dir).unwrap();
+    std::fs::create_dir_all(&info).unwrap();
+    std::fs::write(lib_dir.join("libfoo.so.1.2.0"), "").unwrap();
+    std::os::unix::fs::symlink("libfoo.so.1.2.0", lib_dir.join("libfoo.so.1"))
+        .unwrap();
+    std::fs::write(lib_dir.join("libbar.so.2"), "").unwrap();
+    std::fs::write(
+        root.join("var/lib/dpkg/status"),
+        "Package: libfoo1
+Status: install ok installed
+Architecture: amd64
+Multi-Arch: same
+Version: 1.2-1
+
+Package: libbar2
+Status: install ok installed
+Architecture: amd64
+Version: 2.3-1
+",
+    )
+    .unwrap();
+    std::fs::write(
+        info.join("libfoo1:amd64.list"),
+        "/usr\n/usr/lib/x86_64-linux-gnu/libfoo.so.1.2.0\n",
+    )
+    .unwrap();
+    std::fs::write(
+        info.join("libfoo1:amd64.symbols"),
+        "libfoo.so.1 libfoo1 #MINVER#
+ FOO_1.0@FOO_1.0 1.0
+ FOO_1.2@FOO_1.2 1.2~beta
+ foo_init@FOO_1.0 1.0
+ foo_new@FOO_1.2 1.2~beta
+",
+    )
+    .unwrap();
+    std::fs::write(
+        info.join("libbar2.list"),
+        "/usr/lib/x86_64-linux-gnu/libbar.so.2\n",
+    )
+    .unwrap();
+    std::fs::write(info.join("libbar2.shlibs"), "libbar 2 libbar2 (>= 2.0)\n")
+        .unwrap();
+
+    // libbaz is built along with the binary
+    let build_lib = tmp.path().join("debian/libbaz3/usr/lib");
+    std::fs::create_dir_all(&build_lib).unwrap();
+    std::fs::write(build_lib.join("libbaz-3.so"), "").unwrap();
+
+    let arch = DebianArchitecture::parse("amd64").unwrap();
+    let mut shlibdeps = ShlibDeps::new(&arch);
+    shlibdeps.set_root(&root);
+    shlibdeps.add_lib_dir(&build_lib);
+    let prog = data_path().join("shlibdeps/prog");
+    // files that are no ELF objects are skipped
+    let changelog = data_path().join("changelog-foo");
+    let err = shlibdeps.compute(&[&prog]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    assert!(err.to_string().contains("libbaz-3.so"));
+
+    shlibdeps
+        .add_shlibs("libbaz 3 libbaz3 (= ${binary:Version})".parse().unwrap());
+    assert_eq!(
+        shlibdeps.compute(&[&prog, &changelog]).unwrap(),
+        "libbar2 (>= 2.0), libbaz3 (= ${binary:Version}), libfoo1 (>= 1.2~beta)"
+    );
+
+    // a symbols file given takes precedence over the installed one, and
+    // dependencies on the same package are merged
+    let symbols: SymbolsFile = "libfoo.so.1 libfoo1 #MINVER#
+ foo_new@FOO_1.2 1.3
+"
+    .parse()
+    .unwrap();
+    shlibdeps.add_symbols(symbols);
+    shlibdeps.add_shlibs("libbar 2 libfoo1 (>= 1.5)".parse().unwrap());
+    shlibdeps.ignore_package("libbaz3");
+    assert_eq!(shlibdeps.compute(&[&prog]).unwrap(), "libfoo1 (>= 1.5)");
+}
+
+#[cfg(feature = "elf")]
+#[test]
+fn gensymbols_diff() {
+    let arch = DebianArchitecture::parse("amd64").unwrap();
+    let path = data_path().join("gensymbols/libfoo.so.1");
+    let object = ElfObject::from_file(&path).unwrap();
+    assert_eq!(
+        exported_symbols(&object),
+        [
+            "FOO_1.0@FOO_1.0",
+            "FOO_1.2@FOO_1.2",
+            "foo_init@FOO_1.0",
+            "foo_new@FOO_1.2"
+        ]
+    );
+
+    let text = concat!(
+        "libfoo.so.1 libfoo1 #MINVER#\n",
+        "* Build-Depends-Package: libfoo-dev\n",
+        " FOO_1.0@FOO_1.0 1.0\n",
+        " foo_init@FOO_1.0 1.0\n",
+        " (optional)foo_internal@FOO_1.0 1.0\n",
+        " foo_old@FOO_1.0 1.1\n",
+        " (arch=i386)foo_x86@FOO_1.0 1.0\n",
+    );
+    let symbols: SymbolsFile = text.parse().unwrap();
+    let diff = diff_symbols(&symbols, &object, &arch, "1.2").unwrap();
+    assert_eq!(diff.soname, "libfoo.so.1");
+    let added: Vec<&str> = diff.added.iter().map(|s| &s.name[..]).collect();
+    assert_eq!(added, ["FOO_1.2@FOO_1.2", "foo_new@FOO_1.2"]);
+    assert!(diff.added.iter().all(|s| s.min_version == "1.2"));
+    let lost: Vec<&str> = diff.lost.iter().map(|s| &s.name[..]).collect();
+    assert_eq!(lost, ["foo_internal@FOO_1.0", "foo_old@FOO_1.0"]);
+    assert!(!diff.is_compatible());
+
+    let patch = diff.patch(&symbols, "debian/libfoo1.symbols");
+    assert_eq!(
+        patch.to_string(),
+        concat!(
+            "--- debian/libfoo1.symbols\n",
+            "+++ debian/libfoo1.symbols\n",
+            "@@ -1,7 +1,9 @@\n",
+            " libfoo.so.1 libfoo1 #MINVER#\n",
+            " * Build-Depends-Package: libfoo-dev\n",
+            "  FOO_1.0@FOO_1.0 1.0\n",
+            "+ FOO_1.2@FOO_1.2 1.2\n",
+            "  foo_init@FOO_1.0 1.0\n",
+            "- (optional)foo_internal@FOO_1.0 1.0\n",
+            "- foo_old@FOO_1.0 1.1\n",
+            "+#MISSING: 1.2# (optional)foo_internal@FOO_1.0 1.0\n",
+            "+ foo_new@FOO_1.2 1.2\n",
+            "+#MISSING: 1.2# foo_old@FOO_1.0 1.1\n",
+            "  (arch=i386)foo_x86@FOO_1.0 1.0\n",
+        )
+    );
+    let mut updated = symbols.clone();
+    diff.apply(&mut updated);
+    let (patched, report) = patch.apply_to("libfoo1.symbols", text);
+    assert!(report.rejected.is_empty());
+    assert_eq!(patched, updated.to_string());
+
+    // the lost symbols reappearing, with a pattern for the new ones
+    let updated: SymbolsFile = updated
+        .to_string()
+        .replace(" foo_new@FOO_1.2 1.2", " (symver)FOO_1.2 1.2")
+        .replace("#MISSING: 1.2# (optional)", " (optional)")
+        .parse()
+        .unwrap();
+    let diff = diff_symbols(&updated, &object, &arch, "1.3").unwrap();
+    assert!(diff.added.is_empty());
+    assert_eq!(diff.lost.len(), 1);
+    assert!(diff.is_compatible());
+
+    let diff =
+        diff_symbols(&SymbolsFile::default(), &object, &arch, "1.0").unwrap();
+    let mut created = SymbolsFile::default();
+    diff.apply(&mut created);
+    assert_eq!(created.libraries[0].dependency, "#PACKAGE# #MINVER#");
+    assert_eq!(created.libraries[0].symbols().count(), 4);
+}
+
+#[test]
+fn substvars_expand() {
+    let text = "\
+# generated by dh_shlibdeps
+shlibs:Depends=libc6 (>= 2.34), libfoo1 (>= 1.2)
+misc:Depends=
+misc:Recommends?=foo-doc
+misc:Pre-Depends$=dpkg (>= 1.22)
+foo:Loop=x ${foo:Loop}
+foo:Unused=1
+";
+    let mut substvars: Substvars = text.parse().unwrap();
+    assert_eq!(substvars.get("misc:Recommends"), Some("foo-doc"));
+    let pre_depends = substvars
+        .vars()
+        .find(|v| v.name == "misc:Pre-Depends")
+        .unwrap();
+    assert_eq!(pre_depends.kind, SubstvarKind::Required);
+    assert!("foo bar=1".parse::<Substvars>().is_err());
+
+    let source = Version::parse("1:1.2-3").unwrap();
+    let binary = Version::parse("1:1.2-3+b1").unwrap();
+    substvars.set_version_vars(&source, &binary);
+    substvars.set_arch("amd64");
+    assert_eq!(substvars.get("source:Upstream-Version"), Some("1:1.2"));
+    assert_eq!(substvars.to_string(), &text[text.find('\n').unwrap() + 1..]);
+
+    let mut para = ControlParagraph::default();
+    para.add_entry("Package", "foo".to_string());
+    para.add_entry("Architecture", "${Arch}".to_string());
+    para.add_entry(
+        "Depends",
+        "${shlibs:Depends}, ${misc:Depends},\n bar (= ${binary:Version})"
+            .to_string(),
+    );
+    para.add_entry("Recommends", "${misc:Recommends}".to_string());
+    para.add_entry("Suggests", "${foo:Undefined}".to_string());
+    let para = substvars.substitute_paragraph(&para).unwrap();
+    assert_eq!(para.get_entry("Architecture"), Some("amd64"));
+    assert_eq!(
+        para.get_entry("Depends"),
+        Some("libc6 (>= 2.34), libfoo1 (>= 1.2), bar (= 1:1.2-3+b1)")
+    );
+    assert_eq!(para.get_entry("Recommends"), Some("foo-doc"));
+    assert!(!para.has_entry("Suggests"));
+
+    assert_eq!(
+        substvars.unused(),
+        ["misc:Pre-Depends", "foo:Loop", "foo:Unused"]
+    );
+    let err = substvars.check_unused().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(
+        substvars.substitute("${misc:Pre-Depends}").unwrap(),
+        "dpkg (>= 1.22)"
+    );
+    assert!(substvars.check_unused().is_ok());
+    assert_eq!(
+        substvars.substitute("${not valid}${Space}${Tab}x").unwrap(),
+        "${not valid} \tx"
+    );
+    let err = substvars.substitute("a ${foo:Loop} b").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("foo.substvars");
+    substvars.to_file(&path).unwrap();
+    let mut reread = Substvars::default();
+    reread.set("misc:Depends", "foo");
+    reread.add_file(&path).unwrap();
+    assert_eq!(reread.get("misc:Depends"), Some(""));
+    assert_eq!(reread.get("Newline"), Some("\n"));
+}
+
+#[cfg(feature = "deb")]
+#[test]
+fn substvars_deb_builder() {
+    let mut control = ControlParagraph::default();
+    control.add_entry("Package", "foo".to_string());
+    control.add_entry("Version", "${binary:Version}".to_string());
+    control.add_entry("Architecture", "all".to_string());
+    control.add_entry("Maintainer", "Jane Doe <jane@example.org>".to_string());
+    control.add_entry("Installed-Size", "${Installed-Size}".to_string());
+    control.add_entry("Depends", "${misc:Depends}".to_string());
+    control.add_entry("Description", "example package".to_string());
+    let mut builder = DebBuilder::new(control).unwrap();
+    builder
+        .add_file("/usr/bin/foo", vec![b'x'; 2000], 0o755)
+        .unwrap();
+
+    let mut substvars: Substvars = "misc:Depends=\n".parse().unwrap();
+    let version = Version::parse("1.0-1").unwrap();
+    substvars.set_version_vars(&version, &version);
+    builder.substitute(&mut substvars).unwrap();
+    assert!(substvars.check_unused().is_ok());
+
+    let mut deb = vec![];
+    builder.write(&mut deb).unwrap();
+    let control = DebFile::from_reader(deb.as_slice())
+        .unwrap()
+        .control()
+        .unwrap();
+    assert_eq!(control.get_entry("Version"), Some("1.0-1"));
+    assert_eq!(control.get_entry("Installed-Size"), Some("4"));
+    assert!(!control.has_entry("Depends"));
+}
+
+#[test]
+fn dpkg_diversions() {
+    let path = data_path().join("dpkg-admin/diversions");
+    let diversions = Diversions::from_file(&path).unwrap();
+    assert_eq!(diversions.diversions.len(), 3);
+    let issue = diversions.get("/etc/issue").unwrap();
+    assert!(issue.is_local());
+    assert!(issue.applies_to("base-files"));
+    assert_eq!(diversions.by_package("foo-plus").count(), 2);
+    assert_eq!(
+        diversions
+            .get_diverted_to("/usr/bin/foo.distrib")
+            .unwrap()
+            .path,
+        "/usr/bin/foo"
+    );
+    assert_eq!(
+        diversions.divert("/usr/bin/foo", "foo"),
+        "/usr/bin/foo.distrib"
+    );
+    assert_eq!(
+        diversions.divert("/usr/bin/foo", "foo-plus"),
+        "/usr/bin/foo"
+    );
+    assert_eq!(diversions.divert("/usr/bin/bar", "foo"), "/usr/bin/bar");
+
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(diversions.to_string(), text);
+    assert!("/usr/bin/foo\n/usr/bin/foo.distrib\n"
+        .parse::<Diversions>()
+        .is_err());
+}
+
+#[test]
+fn dpkg_statoverride() {
+    let path = data_path().join("dpkg-admin/statoverride");
+    let overrides = StatOverrides::from_file(&path).unwrap();
+    assert_eq!(overrides.overrides.len(), 4);
+    let crontab = overrides.get("/usr/bin/crontab").unwrap();
+    assert_eq!(crontab.owner, "root");
+    assert_eq!(crontab.group, "crontab");
+    assert_eq!(crontab.mode, 0o2755);
+    assert_eq!(crontab.uid(), None);
+    let data = overrides.get("/srv/data").unwrap();
+    assert_eq!((data.uid(), data.gid()), (Some(1000), Some(1000)));
+    assert_eq!(data.mode, 0o700);
+    assert!(overrides.get("/var/www/my site").is_some());
+
+    let dir = TempDir::new().unwrap();
+    let out = dir.path().join("statoverride");
+    overrides.to_file(&out).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&out).unwrap(),
+        std::fs::read_to_string(&path).unwrap()
+    );
+    for invalid in [
+        "root root 0755\n",
+        "root root 0758 /usr/bin/foo\n",
+        "#root root 0755 /usr/bin/foo\n",
+        "root root 17777 /usr/bin/foo\n",
+    ] {
+        assert!(invalid.parse::<StatOverrides>().is_err());
+    }
+}
+
+#[test]
+fn alternatives_admin_dir() {
+    let dir = data_path().join("alternatives");
+    let groups = read_admin_dir(&dir).unwrap();
+    let names: Vec<&str> = groups.iter().map(|g| &g.name[..]).collect();
+    assert_eq!(names, ["editor", "pager"]);
+
+    let editor = &groups[0];
+    assert_eq!(editor.mode, AlternativeMode::Auto);
+    assert_eq!(editor.link, "/usr/bin/editor");
+    assert_eq!(editor.slaves.len(), 2);
+    assert_eq!(editor.slaves[1].name, "editor.fr.1.gz");
+    assert_eq!(editor.alternatives.len(), 3);
+    let nano = editor.get("/bin/nano").unwrap();
+    assert_eq!(nano.priority, 40);
+    assert_eq!(
+        nano.get_slave("editor.1.gz"),
+        Some("/usr/share/man/man1/nano.1.gz")
+    );
+    assert_eq!(nano.get_slave("editor.fr.1.gz"), None);
+    assert_eq!(editor.get("/bin/ed").unwrap().priority, -100);
+    assert_eq!(editor.best(), Some(nano));
+    assert!(!editor.needs_update(Some("/bin/nano")));
+    assert!(editor.needs_update(Some("/usr/bin/vim.basic")));
+    assert!(editor.needs_update(None));
+
+    let pager = &groups[1];
+    assert_eq!(pager.mode, AlternativeMode::Manual);
+    assert!(pager.slaves.is_empty());
+    assert!(!pager.needs_update(Some("/bin/more")));
+    assert!(pager.needs_update(Some("/usr/bin/w3m")));
+    assert!(!pager.needs_update(Some("/bin/less")));
+
+    let tmp = TempDir::new().unwrap();
+    for group in &groups {
+        group.to_dir(tmp.path()).unwrap();
+        let name = &group.name;
+        assert_eq!(
+            std::fs::read_to_string(tmp.path().join(name)).unwrap(),
+            std::fs::read_to_string(dir.join(name)).unwrap()
+        );
+    }
+    let links = tmp.path().join("etc-alternatives");
+    std::fs::create_dir(&links).unwrap();
+    assert_eq!(editor.current(&links).unwrap(), None);
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink("/bin/nano", links.join("editor")).unwrap();
+        assert_eq!(
+            editor.current(&links).unwrap().as_deref(),
+            Some("/bin/nano")
+        );
+    }
+
+    assert!(LinkGroup::parse("x", "auto\n/usr/bin/x\n\n/bin/x\n").is_err());
+    assert!(LinkGroup::parse("x", "semi\n/usr/bin/x\n\n\n").is_err());
+    assert!(
+        LinkGroup::parse("x", "auto\n/usr/bin/x\n\n/bin/x\nhigh\n\n").is_err()
+    );
+}
+
+fn pdiff_versions() -> [&'static str; 3] {
+    [
+        "Package: foo\nVersion: 1.0\n\nPackage: bar\nVersion: 2.0\n",
+        "Package: foo\nVersion: 1.1\n\nPackage: bar\nVersion: 2.0\n",
+        "Package: baz\nVersion: 0.1\n\nPackage: foo\nVersion: 1.1\n",
+    ]
+}
+
+/// Builds an index of patches between versions of an index file, with
+/// the compressed patches, if given, listed for download.
+fn pdiff_index(
+    versions: &[&str],
+    patches: &[&str],
+    downloads: &[Vec<u8>],
+    merged: bool,
+) -> String {
+    let names = ["2024-01-01-0800.00", "2024-01-02-0800.00"];
+    let table = |field: &str, files: &[&[u8]], suffix: &str| {
+        let mut text = format!("{field}:\n");
+        for (name, data) in names.iter().zip(files) {
+            let sha256 = Sha256::digest(data);
+            text += &format!(" {sha256:x} {} {name}{suffix}\n", data.len());
+        }
+        text
+    };
+    let current = versions.last().unwrap();
+    let sha256 = Sha256::digest(current.as_bytes());
+    let mut text = format!("SHA256-Current: {sha256:x} {}\n", current.len());
+    if merged {
+        text += "X-Patch-Precedence: merged\n";
+    }
+    let versions: Vec<&[u8]> = versions.iter().map(|v| v.as_bytes()).collect();
+    let patches: Vec<&[u8]> = patches.iter().map(|p| p.as_bytes()).collect();
+    let downloads: Vec<&[u8]> = downloads.iter().map(Vec::as_slice).collect();
+    text += &table("SHA256-History", &versions, "");
+    text += &table("SHA256-Patches", &patches, "");
+    if !downloads.is_empty() {
+        text += &table("SHA256-Download", &downloads, ".gz");
+    }
+    text
+}
+
+#[test]
+fn archive_pdiff() {
+    assert_eq!(
+        apply_ed_script("a\nb\nc\n", "3c\nC\n.\n1d\n").unwrap(),
+        "b\nC\n"
+    );
+    // lines consisting of a dot, appending without address
+    assert_eq!(
+        apply_ed_script("a\nb\n", "2d\n1c\n..\n.\ns/.//\na\nx\n.\n").unwrap(),
+        ".\nx\n"
+    );
+    assert_eq!(apply_ed_script("a\n", "0a\nz\n.\n").unwrap(), "z\na\n");
+    let err = apply_ed_script("a\n", "3d\n").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(apply_ed_script("a\n", "1w\n").is_err());
+
+    let versions = pdiff_versions();
+    let patches = [
+        "2c\nVersion: 1.1\n.\n",
+        "3,5d\n0a\nPackage: baz\nVersion: 0.1\n\n.\n",
+    ];
+    let text = pdiff_index(&versions, &patches, &[], false);
+    let index = PdiffIndex::from_reader(text.as_bytes()).unwrap();
+    assert!(!index.merged);
+    assert_eq!(index.current_size, versions[2].len() as u64);
+    assert_eq!(index.patches.len(), 2);
+    assert_eq!(index.patches[1].from_size, versions[1].len() as u64);
+    assert_eq!(index.patches[1].size, patches[1].len() as u64);
+    assert_eq!(index.patches[1].download_sha256, None);
+    assert!(index.is_current(versions[2].as_bytes()));
+
+    let names = |v: &str| -> Option<Vec<String>> {
+        let patches = index.patches_for(v.as_bytes())?;
+        Some(patches.iter().map(|p| p.name.clone()).collect())
+    };
+    assert_eq!(
+        names(versions[0]).unwrap(),
+        ["2024-01-01-0800.00", "2024-01-02-0800.00"]
+    );
+    assert_eq!(names(versions[1]).unwrap(), ["2024-01-02-0800.00"]);
+    assert!(names(versions[2]).unwrap().is_empty());
+    assert!(names("Package: foo\n").is_none());
+
+    let to_apply: Vec<(&PdiffPatch, &[u8])> = index
+        .patches_for(versions[0].as_bytes())
+        .unwrap()
+        .into_iter()
+        .zip(patches.iter().map(|p| p.as_bytes()))
+        .collect();
+    let result = index.apply(versions[0].as_bytes(), &to_apply).unwrap();
+    assert_eq!(String::from_utf8(result).unwrap(), versions[2]);
+    // a patch not matching its digest, and a result not matching
+    let tampered = [(to_apply[0].0, "2c\nVersion: 1.2\n.\n".as_bytes())];
+    let err = index.apply(versions[0].as_bytes(), &tampered).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    let err = index
+        .apply(versions[0].as_bytes(), &to_apply[..1])
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    // with merged patches, each one leads to the current version
+    let merged = [
+        "3,5d\n2c\nVersion: 1.1\n.\n0a\nPackage: baz\nVersion: 0.1\n\n.\n",
+        patches[1],
+    ];
+    let text = pdiff_index(&versions, &merged, &[], true);
+    let index = PdiffIndex::from_reader(text.as_bytes()).unwrap();
+    assert!(index.merged);
+    let to_apply = index.patches_for(versions[0].as_bytes()).unwrap();
+    assert_eq!(to_apply.len(), 1);
+    let result = index
+        .apply(
+            versions[0].as_bytes(),
+            &[(to_apply[0], merged[0].as_bytes())],
+        )
+        .unwrap();
+    assert_eq!(String::from_utf8(result).unwrap(), versions[2]);
+
+    // patches need a history entry
+    let text = pdiff_index(&versions[..1], &merged, &[], true);
+    assert!(PdiffIndex::from_reader(text.as_bytes()).is_err());
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn archive_fetch_pdiff() {
+    #[derive(Debug)]
+    struct Unsigned;
+    impl Verifier for Unsigned {
+        fn verify(&self, signed: &[u8]) -> std::io::Result<Vec<u8>> {
+            Ok(signed.to_vec())
+        }
+    }
+
+    let versions = pdiff_versions();
+    let patches = [
+        "2c\nVersion: 1.1\n.\n",
+        "3,5d\n0a\nPackage: baz\nVersion: 0.1\n\n.\n",
+    ];
+    let root = TempDir::new().unwrap();
+    let dist = root.path().join("dists/stable");
+    let diff_dir = dist.join("main/binary-all/Packages.diff");
+    std::fs::create_dir_all(&diff_dir).unwrap();
+    let downloads: Vec<Vec<u8>> = patches
+        .iter()
+        .map(|p| Compression::Gzip.compress(p.as_bytes()).unwrap())
+        .collect();
+    let index = pdiff_index(&versions, &patches, &downloads, false);
+    std::fs::write(diff_dir.join("Index"), &index).unwrap();
+    let names = ["2024-01-01-0800.00", "2024-01-02-0800.00"];
+    for (name, gz) in names.iter().zip(&downloads) {
+        std::fs::write(diff_dir.join(format!("{name}.gz")), gz).unwrap();
+    }
+    let mut release = ReleaseBuilder::new().unwrap();
+    release.set_suite("stable");
+    release.add_index("main/binary-all/Packages", versions[2].as_bytes());
+    release.add_index("main/binary-all/Packages.diff/Index", index.as_bytes());
+    release.to_file(&dist.join("InRelease")).unwrap();
+
+    let (url, server) = serve_files(root.path().to_path_buf(), 5);
+    let mut fetcher = RepositoryFetcher::new(&url, "stable", Unsigned);
+    fetcher.update().unwrap();
+    let path = "main/binary-all/Packages";
+    let data = fetcher.fetch_pdiff(path, versions[0].as_bytes()).unwrap();
+    assert_eq!(data, versions[2].as_bytes());
+    let err = fetcher.fetch_pdiff(path, b"Package: foo\n").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+
+    let index = "/dists/stable/main/binary-all/Packages.diff/Index";
+    assert_eq!(
+        server.join().unwrap(),
+        [
+            "/dists/stable/InRelease",
+            index,
+            "/dists/stable/main/binary-all/Packages.diff/2024-01-01-0800.00.gz",
+            "/dists/stable/main/binary-all/Packages.diff/2024-01-02-0800.00.gz",
+            index,
+        ]
+    );
+}
+
+#[test]
+fn archive_translation() {
+    let path = data_path().join("translation").join("Translation-de");
+    let translation = Translation::from_file(&path).unwrap();
+    assert_eq!(translation.lang, "de");
+    assert_eq!(translation.field(), "Description-de");
+    assert_eq!(translation.descriptions().len(), 2);
+    let sl = translation.get("07f1f2d70367f347a04b26e9726c697d").unwrap();
+    assert_eq!(sl.package, "sl");
+    assert_eq!(sl.description, "Korrigiert den häufigen Tippfehler ls");
+    assert_eq!(
+        translation.to_string(),
+        std::fs::read_to_string(&path).unwrap()
+    );
+
+    // entries refer to their description by digest, or by content
+    let packages = ControlFile::from_reader(
+        "Package: hello\nVersion: 2.10-3\n\
+         Description: example package based on GNU hello\n \
+         The GNU hello program produces a familiar, friendly greeting.\n \
+         .\n Seriously, though: this is an example.\n\n\
+         Package: sl\nVersion: 5.02-1\nDescription: Corrects common typo ls\n\
+         Description-md5: 07f1f2d70367f347a04b26e9726c697d\n\n\
+         Package: bar\nVersion: 1.0\nDescription: something else\n"
+            .as_bytes(),
+    )
+    .unwrap();
+    let paragraphs = packages.get_paragraphs();
+    assert_eq!(
+        description_md5(paragraphs[0].get_entry("Description").unwrap()),
+        "dc8b03a8fd14765acfc4713fc7f61a41"
+    );
+    let hello = translation.translate(&paragraphs[0]).unwrap();
+    assert_eq!(hello.package, "hello");
+    assert!(translation.translate(&paragraphs[2]).is_none());
+
+    let merged = translation.merge(&packages);
+    let merged = merged.get_paragraphs();
+    assert_eq!(merged.len(), 3);
+    assert!(merged[0]
+        .get_entry("Description-de")
+        .unwrap()
+        .starts_with("Beispielpaket basierend auf GNU hello\n Das Programm"));
+    assert_eq!(
+        merged[1].get_entry("Description-de"),
+        Some("Korrigiert den häufigen Tippfehler ls")
+    );
+    assert_eq!(merged[1].get_entry("Version"), Some("5.02-1"));
+    assert!(!merged[2].has_entry("Description-de"));
+
+    // the language is needed, and all fields
+    let err =
+        Translation::from_file(&data_path().join("translation")).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    let text = "Package: foo\nDescription-md5: 00\nDescription-fr: outil\n";
+    let err = Translation::from_reader("de", text.as_bytes()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(Translation::from_reader("fr", text.as_bytes()).is_ok());
+}
+
+#[cfg(feature = "yaml")]
+#[test]
+fn archive_dep11() {
+    let path = data_path().join("dep11").join("Components-amd64.yml");
+    let file = Dep11File::from_file(&path).unwrap();
+    assert_eq!(file.header.version, "0.12");
+    assert_eq!(file.header.origin, "debian-bookworm-main");
+    assert_eq!(
+        file.header.media_base_url.as_deref(),
+        Some("https://appstream.debian.org/media/pool")
+    );
+    assert_eq!(file.header.priority, Some(0));
+    assert_eq!(file.components.len(), 3);
+
+    let gedit = file.get("org.gnome.gedit.desktop").unwrap();
+    assert_eq!(gedit.kind, "desktop-application");
+    assert_eq!(gedit.name("de"), Some("gedit"));
+    assert_eq!(gedit.summary("pt_BR.UTF-8"), Some("Editor de texto"));
+    assert_eq!(gedit.summary("de_CH"), Some("Texteditor"));
+    assert_eq!(gedit.summary("fr"), Some("Text editor"));
+    assert_eq!(
+        gedit.description("de"),
+        Some("<p>gedit is an easy-to-use and general-purpose text editor.</p>")
+    );
+    assert_eq!(gedit.categories, ["GNOME", "GTK", "Utility", "TextEditor"]);
+    assert_eq!(gedit.keywords, ["text", "editor"]);
+    assert_eq!(gedit.project_license.as_deref(), Some("GPL-2.0+"));
+    assert_eq!(
+        gedit.url("bugtracker"),
+        Some("https://gitlab.gnome.org/GNOME/gedit/issues")
+    );
+    assert_eq!(gedit.icons.len(), 3);
+    assert_eq!(gedit.icons[1].kind, "cached");
+    assert_eq!(gedit.icons[1].width, Some(128));
+    assert_eq!(gedit.icons[2].kind, "stock");
+    assert_eq!(gedit.icons[2].name, "org.gnome.gedit");
+    assert_eq!(
+        gedit.provided("mediatypes"),
+        ["text/plain", "application/x-shellscript"]
+    );
+    assert_eq!(gedit.provided("binaries"), ["gedit"]);
+
+    let font = &file.components[1];
+    assert_eq!(font.kind, "font");
+    assert!(font.description("C").is_none());
+    assert_eq!(file.by_package("fonts-dejavu-core").count(), 1);
+    assert_eq!(file.by_package("gedit").count(), 1);
+
+    // components are linked to the newest entry of their package
+    let packages = ControlFile::from_reader(
+        "Package: gedit\nVersion: 43.2-2\n\n\
+         Package: gedit\nVersion: 44.2-1\n\n\
+         Package: fonts-dejavu-core\nVersion: 2.37-6\n"
+            .as_bytes(),
+    )
+    .unwrap();
+    let linked = file.link(&packages);
+    assert_eq!(linked.len(), 3);
+    assert_eq!(linked[0].0.id, "org.gnome.gedit.desktop");
+    assert_eq!(linked[0].1.unwrap().get_entry("Version"), Some("44.2-1"));
+    assert_eq!(
+        linked[1].1.unwrap().get_entry("Package"),
+        Some("fonts-dejavu-core")
+    );
+    assert!(linked[2].1.is_none());
+
+    // the header is required, and the ID of components
+    let err = "Type: font\nID: foo\n".parse::<Dep11File>().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    let text = "File: DEP-11\nVersion: '0.12'\nOrigin: test\n---\nType: font\n";
+    assert!(text.parse::<Dep11File>().is_err());
+    let text = "File: DEP-11\nVersion: '0.12'\nOrigin: test\n";
+    assert!(text.parse::<Dep11File>().unwrap().components.is_empty());
+}
+
+#[test]
+fn archive_mirror_plan() {
+    let sha256 = |d: &[u8]| format!("{:x}", Sha256::digest(d));
+    let foo_deb = b"foo 1.0 deb";
+    let bar_deb = b"bar 1.0 deb";
+    let dsc = b"foo 1.0 dsc";
+    let packages = format!(
+        "Package: foo\nVersion: 1.0\n\
+         Filename: pool/main/f/foo/foo_1.0_amd64.deb\n\
+         Size: {}\nSHA256: {}\n\n\
+         Package: bar\nVersion: 1.0\n\
+         Filename: pool/main/b/bar/bar_1.0_amd64.deb\n\
+         Size: {}\nSHA256: {}\n",
+        foo_deb.len(),
+        sha256(foo_deb),
+        bar_deb.len(),
+        sha256(bar_deb)
+    );
+    let sources = format!(
+        "Package: foo\nVersion: 1.0\nDirectory: pool/main/f/foo\n\
+         Checksums-Sha256:\n {} {} foo_1.0.dsc\n",
+        sha256(dsc),
+        dsc.len()
+    );
+    let mut builder = ReleaseBuilder::new().unwrap();
+    builder.set_suite("stable");
+    builder.set_acquire_by_hash(true);
+    builder.add_index("main/binary-amd64/Packages", packages.as_bytes());
+    builder.add_index("main/source/Sources", sources.as_bytes());
+    builder.add_index("contrib/binary-amd64/Packages", b"");
+    let mut text = vec![];
+    builder.write(&mut text).unwrap();
+    let release = Release::from_reader(text.as_slice()).unwrap();
+
+    let root = TempDir::new().unwrap();
+    let write = |path: &str, data: &[u8]| {
+        let path = root.path().join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, data).unwrap();
+    };
+    let by_hash = "dists/stable/main/binary-amd64/by-hash/SHA256";
+    write("dists/stable/InRelease", b"signed");
+    write("dists/stable/main/binary-amd64/Packages", b"Package: old\n");
+    write(&format!("{by_hash}/{}", sha256(b"old")), b"old");
+    write("dists/stable/main/source/Sources", sources.as_bytes());
+    write("dists/stable/contrib/binary-amd64/Packages", b"");
+    write("pool/main/f/foo/foo_1.0_amd64.deb", foo_deb);
+    write("pool/main/f/foo/foo_0.9_amd64.deb", b"foo 0.9 deb");
+
+    let mut planner = MirrorPlanner::new(root.path());
+    planner.add_release("stable", &release, |p| p.starts_with("main/"));
+    let index = ControlFile::from_reader(packages.as_bytes()).unwrap();
+    planner.add_packages(&index).unwrap();
+    let sources = ControlFile::from_reader(sources.as_bytes()).unwrap();
+    planner.add_sources(&sources).unwrap();
+    let plan = planner.plan().unwrap();
+
+    // index files come from their by-hash location, fetched once
+    let packages_sha256 =
+        &release.index("main/binary-amd64/Packages").unwrap().sha256;
+    let sources_index = release.index("main/source/Sources").unwrap();
+    let sources_by_hash = format!(
+        "dists/stable/main/source/by-hash/SHA256/{}",
+        sources_index.sha256
+    );
+    let summary: Vec<(&str, Vec<&str>)> = plan
+        .fetch
+        .iter()
+        .map(|f| {
+            let targets = f.targets.iter().map(String::as_str).collect();
+            (f.source.as_str(), targets)
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (
+                format!("{by_hash}/{packages_sha256}").as_str(),
+                vec![
+                    "dists/stable/main/binary-amd64/Packages",
+                    format!("{by_hash}/{packages_sha256}").as_str(),
+                ]
+            ),
+            (sources_by_hash.as_str(), vec![sources_by_hash.as_str()]),
+            (
+                "pool/main/b/bar/bar_1.0_amd64.deb",
+                vec!["pool/main/b/bar/bar_1.0_amd64.deb"]
+            ),
+            (
+                "pool/main/f/foo/foo_1.0.dsc",
+                vec!["pool/main/f/foo/foo_1.0.dsc"]
+            ),
+        ]
+    );
+    assert_eq!(
+        plan.fetch_size,
+        packages.len() as u64
+            + sources_index.size
+            + (bar_deb.len() + dsc.len()) as u64
+    );
+
+    // older by-hash files and the Release files are kept
+    assert_eq!(
+        plan.delete,
+        [
+            "dists/stable/contrib/binary-amd64/Packages",
+            "pool/main/f/foo/foo_0.9_amd64.deb",
+        ]
+    );
+    assert_eq!(plan.delete_size, 11);
+    assert!(!plan.is_empty());
+
+    // entries need their digests
+    let err = planner
+        .add_packages(
+            &ControlFile::from_reader(
+                &b"Package: foo\nFilename: foo.deb\n"[..],
+            )
+            .unwrap(),
+        )
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn debtags() {
+    let dir = data_path().join("debtags");
+    let vocabulary = Vocabulary::from_file(&dir.join("vocabulary")).unwrap();
+    assert_eq!(vocabulary.facets.len(), 2);
+    assert_eq!(vocabulary.tags.len(), 4);
+    let role = vocabulary.get_facet("role").unwrap();
+    assert_eq!(role.description, "Role\n Role performed by the package");
+    assert_eq!(role.status.as_deref(), Some("needing-review"));
+    let tag: Tag = "role::program".parse().unwrap();
+    let info = vocabulary.get_tag(&tag).unwrap();
+    assert_eq!(info.description, "Program\n Executable program.");
+    let names: Vec<String> = vocabulary
+        .tags_of_facet("implemented-in")
+        .map(|t| t.tag.to_string())
+        .collect();
+    assert_eq!(names, ["implemented-in::c", "implemented-in::rust"]);
+
+    let path = dir.join("package-tags");
+    let db = TagDatabase::from_file(&path).unwrap();
+    assert_eq!(
+        db.packages().collect::<Vec<&str>>(),
+        ["hello", "libfoo1", "ripgrep"]
+    );
+    let rust: Tag = "implemented-in::rust".parse().unwrap();
+    assert_eq!(db.packages_with(&rust), ["libfoo1", "ripgrep"]);
+    assert!(db.has_tag("hello", &tag));
+    assert_eq!(db.tags_of("libfoo1").len(), 3);
+    assert!(db.tags_of("bar").is_empty());
+    // groups are expanded when writing
+    assert_eq!(
+        db.to_string().lines().nth(1),
+        Some(
+            "libfoo1: implemented-in::c, implemented-in::rust, \
+             role::shared-lib"
+        )
+    );
+
+    // tags not in the vocabulary
+    let unknown: Vec<(&str, String)> = db
+        .validate(&vocabulary)
+        .into_iter()
+        .map(|(p, t)| (p, t.to_string()))
+        .collect();
+    assert_eq!(unknown, [("ripgrep", "use::searching".to_string())]);
+
+    // tags from a Packages index
+    let packages = ControlFile::from_reader(
+        &b"Package: hello\nTag: implemented-in::c, role::program\n\n\
+           Package: bar\nVersion: 1.0\n"[..],
+    )
+    .unwrap();
+    let from_packages = TagDatabase::from_packages(&packages).unwrap();
+    assert_eq!(from_packages.tags_of("hello"), db.tags_of("hello"));
+    assert_eq!(from_packages.packages().count(), 1);
+
+    assert_eq!(
+        parse_tags(" role::program,,use::{editing, viewing} ").unwrap(),
+        [
+            tag.clone(),
+            "use::editing".parse().unwrap(),
+            "use::viewing".parse().unwrap()
+        ]
+    );
+    assert!(parse_tags("role::program, use::{editing").is_err());
+    assert!("program".parse::<Tag>().is_err());
+    assert!("role::pro gram".parse::<Tag>().is_err());
+    assert!("hello role::program\n".parse::<TagDatabase>().is_err());
+}
+
+#[test]
+fn popcon_results() {
+    let dir = data_path().join("popcon");
+    let all =
+        PopconResults::from_file(&dir.join("all-popcon-results.txt")).unwrap();
+    assert_eq!(all.submissions, Some(230041));
+    assert_eq!(all.entries().len(), 3);
+    let hello = all.get("hello").unwrap();
+    assert_eq!(
+        (
+            hello.inst,
+            hello.vote,
+            hello.old,
+            hello.recent,
+            hello.no_files
+        ),
+        (4244, 1020, 3208, 12, 4)
+    );
+    assert_eq!(hello.maintainer, None);
+
+    // rankings give the same counts, but no totals
+    let ranking = PopconResults::from_file(&dir.join("by_inst")).unwrap();
+    assert_eq!(ranking.submissions, None);
+    assert_eq!(ranking.entries().len(), 3);
+    assert!(ranking.get("Total").is_none());
+    let hello_ranked = ranking.get("hello").unwrap();
+    assert_eq!(hello_ranked.maintainer.as_deref(), Some("Santiago Vila"));
+    assert_eq!(hello_ranked.inst, hello.inst);
+    assert_eq!(hello_ranked.recent, hello.recent);
+    let names: Vec<&str> =
+        all.by_inst().iter().map(|e| e.package.as_str()).collect();
+    assert_eq!(names, ["dpkg", "hello", "sl"]);
+
+    let packages = ControlFile::from_reader(
+        &b"Package: sl\nVersion: 5.02-1\n\nPackage: foo\nVersion: 1.0\n"[..],
+    )
+    .unwrap();
+    let joined = all.join(&packages);
+    assert_eq!(joined.len(), 2);
+    assert_eq!(joined[0].1.unwrap().inst, 2979);
+    assert!(joined[1].1.is_none());
+
+    assert!("Package: foo 1 2 3\n".parse::<PopconResults>().is_err());
+    assert!("1 foo 12 x 1 1 1\n".parse::<PopconResults>().is_err());
+}
+
+#[test]
+fn installed_files() {
+    let dir = TempDir::new().unwrap();
+    let write = |name: &str, text: &str| {
+        std::fs::write(dir.path().join(name), text).unwrap();
+    };
+    write(
+        "hello.list",
+        "/.\n/usr\n/usr/bin\n/usr/bin/hello\n/usr/share/doc/hello\n",
+    );
+    write(
+        "hello.md5sums",
+        "9dc58b2a0a7ff8bf57ee2a6f9ee9f7b6  usr/bin/hello\n",
+    );
+    write(
+        "libc6:amd64.list",
+        "/.\n/usr\n/usr/lib/x86_64-linux-gnu/libc.so.6\n",
+    );
+    write(
+        "libc6:amd64.md5sums",
+        "0c65bb0b0c0a4eb3a9b2f5e7d8e6a7f1  \
+         usr/lib/x86_64-linux-gnu/libc.so.6\n",
+    );
+    write("libc6:amd64.postinst", "#!/bin/sh\n");
+    write("format", "1\n");
+
+    let installed = InstalledFiles::read_dir(dir.path()).unwrap();
+    assert_eq!(installed.lists().len(), 2);
+    let libc = installed.get("libc6").unwrap();
+    assert_eq!(libc.architecture.as_deref(), Some("amd64"));
+    assert_eq!(libc.name(), "libc6:amd64");
+    assert!(installed.get("libc6:i386").is_none());
+    assert!(installed.get("hello").unwrap().contains("/usr/bin/hello"));
+
+    // the equivalent of dpkg -S
+    assert_eq!(installed.owners("/usr"), ["hello", "libc6:amd64"]);
+    assert_eq!(installed.owners("/usr/bin/hello"), ["hello"]);
+    assert!(installed.owners("/usr/bin/other").is_empty());
+    assert_eq!(
+        installed.search("libc.so"),
+        [(
+            "libc6:amd64".to_string(),
+            "/usr/lib/x86_64-linux-gnu/libc.so.6"
+        )]
+    );
+    assert_eq!(
+        installed.search("/usr/*/hello"),
+        [
+            ("hello".to_string(), "/usr/bin/hello"),
+            ("hello".to_string(), "/usr/share/doc/hello"),
+        ]
+    );
+    assert!(installed.search("/usr/bin/hell").is_empty());
+
+    assert_eq!(
+        installed.md5sum("/usr/lib/x86_64-linux-gnu/libc.so.6"),
+        Some((
+            "libc6:amd64".to_string(),
+            "0c65bb0b0c0a4eb3a9b2f5e7d8e6a7f1"
+        ))
+    );
+    assert!(installed.md5sum("/usr/bin").is_none());
+    assert_eq!(installed.get_md5sums("libc6").unwrap().files().len(), 1);
+
+    // lists replaced on upgrades
+    let mut installed = installed;
+    installed.add_list(FileList::parse("hello", None, "/.\n/usr/bin/hi\n"));
+    assert_eq!(installed.lists().len(), 2);
+    assert!(installed.owners("/usr/bin/hello").is_empty());
+    assert_eq!(installed.owners("/usr/bin/hi"), ["hello"]);
+    assert_eq!(installed.owners("/."), ["hello", "libc6:amd64"]);
+    assert_eq!(
+        installed.get("hello").unwrap().to_string(),
+        "/.\n/usr/bin/hi\n"
+    );
+}
+
+#[test]
+fn tasks() {
+    let index = ControlFile::from_reader(
+        "Package: task-ssh-server\nVersion: 3.73\nArchitecture: all\n\
+         Depends: tasksel, openssh-server\nTask: ssh-server\n\n\
+         Package: openssh-server\nVersion: 1:9.2p1-2\nArchitecture: amd64\n\
+         Depends: openssh-client (= 1:9.2p1-2)\nTask: ssh-server, server\n\n\
+         Package: openssh-server\nVersion: 1:9.2p1-2\nArchitecture: i386\n\
+         Depends: openssh-client (= 1:9.2p1-2)\nTask: ssh-server, server\n\n\
+         Package: openssh-client\nVersion: 1:9.2p1-2\nArchitecture: amd64\n\n\
+         Package: tasksel\nVersion: 3.73\nArchitecture: all\n\n\
+         Package: wine64\nVersion: 8.0~repack-4\nArchitecture: amd64\n\
+         Task: desktop\n\n\
+         Package: wine32\nVersion: 8.0~repack-4\nArchitecture: i386\n\
+         Task: desktop\n\n\
+         Package: gnome\nVersion: 1:43+1\nArchitecture: amd64\n\
+         Depends: gnome-core\n\n\
+         Package: gnome-core\nVersion: 1:43+1\nArchitecture: amd64\n"
+            .as_bytes(),
+    )
+    .unwrap();
+    assert_eq!(
+        parse_task_field(" ssh-server,server ,"),
+        ["ssh-server", "server"]
+    );
+
+    let tasks = Tasks::from_packages(&index, "amd64").unwrap();
+    assert_eq!(tasks.architecture, "amd64");
+    let names: Vec<&str> = tasks.tasks().collect();
+    assert_eq!(names, ["desktop", "server", "ssh-server"]);
+    assert_eq!(
+        tasks.packages_of("ssh-server"),
+        ["openssh-server", "task-ssh-server"]
+    );
+    assert_eq!(tasks.packages_of("desktop"), ["wine64"]);
+    assert!(tasks.packages_of("missing").is_empty());
+    assert_eq!(tasks.resolver().candidates("openssh-server").len(), 1);
+
+    let names = |selection: &[&str]| -> Vec<String> {
+        let set = tasks.expand(selection).unwrap();
+        set.packages().map(|c| c.package.clone()).collect()
+    };
+    assert_eq!(
+        names(&["ssh-server^"]),
+        [
+            "openssh-client",
+            "openssh-server",
+            "task-ssh-server",
+            "tasksel"
+        ]
+    );
+    // metapackages expand to their dependencies
+    assert_eq!(
+        names(&["gnome", "desktop^"]),
+        ["gnome", "gnome-core", "wine64"]
+    );
+
+    let err = tasks.expand(&["web-server^"]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "web-server^ is requested, but web-server^ \
+         is not available"
+    );
+
+    // the i386 task lacks openssh-client
+    let tasks = Tasks::from_packages(&index, "i386").unwrap();
+    assert_eq!(tasks.packages_of("desktop"), ["wine32"]);
+    let err = tasks.expand(&["server^"]).unwrap_err();
+    assert_eq!(err.package.as_deref(), Some("openssh-server"));
+}
+
+#[cfg(feature = "http")]
+fn serve_files(
+    root: PathBuf,
+    requests: usize,
+) -> (String, std::thread::JoinHandle<Vec<String>>) {
+    use std::io::{BufRead, BufReader, Write};
+
+    const LAST_MODIFIED: &str = "Tue, 14 Nov 2023 22:13:20 GMT";
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let handle = std::thread::spawn(move || {
+        let mut paths = vec![];
+        for stream in listener.incoming().take(requests) {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut lines = vec![];
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                lines.push(line.trim().to_string());
+            }
+            let path = lines[0].split(' ').nth(1).unwrap().to_string();
+            let mut file = path.split('?').next().unwrap().to_string();
+            if file.ends_with('/') {
+                file += "index.html";
+            }
+            let unmodified = lines.iter().any(|l| {
+                l.to_lowercase()
+                    == format!("if-modified-since: {LAST_MODIFIED}")
+                        .to_lowercase()
+            });
+            let range: Option<usize> = lines.iter().find_map(|l| {
+                l.to_lowercase()
+                    .strip_prefix("range: bytes=")
+                    .and_then(|r| r.strip_suffix('-'))
+                    .map(|r| r.parse().unwrap())
+            });
+            let response = match std::fs::read(root.join(&file[1..])) {
+                Ok(_) if unmodified => {
+                    b"HTTP/1.1 304 Not Modified\r\nContent-Length: 0\r\n\r\n"
+                        .to_vec()
+                }
+                Ok(body) if range.is_some() => {
+                    let body = &body[range.unwrap()..];
+                    let mut r = format!(
+                        "HTTP/1.1 206 Partial Content\r\n\
+                         Content-Length: {}\r\n\r\n",
+                        body.len()
+                    )
+                    .into_bytes();
+                    r.extend(body);
+                    r
+                }
+                Ok(body) => {
+                    let mut r = format!(
+                        "HTTP/1.1 200 OK\r\nLast-Modified: {LAST_MODIFIED}\r\n\
+                         Content-Length: {}\r\n\r\n",
+                        body.len()
+                    )
+                    .into_bytes();
+                    r.extend(body);
+                    r
+                }
+                Err(_) => {
+                    b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n"
+                        .to_vec()
+                }
+            };
+            stream.write_all(&response).unwrap();
+            paths.push(path);
+        }
+        paths
+    });
+    (url, handle)
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn archive_fetch_wrong_dist() {
+    let keyring = data_path().join("repo").join("keyring.gpg");
+    let keyring = Keyring::from_file(&keyring).unwrap();
+    let in_release = data_path().join("repo/dists/stable/InRelease");
+    let root = TempDir::new().unwrap();
+    for dist in ["oldstable", "bookworm"] {
+        let dir = root.path().join("dists").join(dist);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::copy(&in_release, dir.join("InRelease")).unwrap();
+    }
+    let (url, server) = serve_files(root.path().to_path_buf(), 2);
+
+    // a validly signed Release file of another suite is rejected
+    let mut fetcher =
+        RepositoryFetcher::new(&url, "oldstable", keyring.clone());
+    let err = fetcher.update().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(fetcher.release().is_none());
+    // while the codename names the distribution as well
+    let mut fetcher = RepositoryFetcher::new(&url, "bookworm", keyring);
+    assert!(fetcher.update().unwrap());
+    server.join().unwrap();
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn archive_fetch() {
+    let keyring = data_path().join("repo").join("keyring.gpg");
+    let keyring = Keyring::from_file(&keyring).unwrap();
+    let (url, server) = serve_files(data_path().join("repo"), 8);
+    let cache = TempDir::new().unwrap();
+
+    let mut fetcher = RepositoryFetcher::new(&url, "stable", keyring.clone());
+    fetcher.set_cache_dir(cache.path());
+    assert!(fetcher.fetch_packages("main", "all").is_err());
+    assert!(fetcher.update().unwrap());
+    let release = fetcher.release().unwrap();
+    assert_eq!(release.codename(), Some("bookworm"));
+    let packages = fetcher.fetch_packages("main", "all").unwrap();
+    let para = &packages.get_paragraphs()[0];
+    assert_eq!(para.get_entry("Package"), Some("foo"));
+    assert_eq!(
+        para.get_entry("Filename"),
+        Some("pool/main/f/foo/foo_1.0-1_all.deb")
+    );
+    let err = fetcher.fetch_sources("main").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+
+    // a second fetcher sharing the cache directory
+    let mut fetcher = RepositoryFetcher::new(&url, "stable", keyring.clone());
+    fetcher.set_cache_dir(cache.path());
+    assert!(!fetcher.update().unwrap());
+    assert_eq!(
+        fetcher
+            .fetch_packages("main", "all")
+            .unwrap()
+            .get_paragraphs()
+            .len(),
+        1
+    );
+    // without cache
+    let mut fetcher = RepositoryFetcher::new(&url, "stable", keyring.clone());
+    assert!(fetcher.update().unwrap());
+    assert_eq!(
+        fetcher
+            .fetch_index("main/binary-all/Packages")
+            .unwrap()
+            .len(),
+        408
+    );
+
+    // InRelease signed by a key not trusted
+    let other = data_path().join("repo").join("subkey-keyring.gpg");
+    let mut fetcher = RepositoryFetcher::new(
+        &url,
+        "stable",
+        Keyring::from_file(&other).unwrap(),
+    );
+    assert!(fetcher.update().is_err());
+
+    // a mismatching index is rejected, and a mismatching cached copy
+    // not used
+    #[derive(Debug)]
+    struct Tampered;
+    impl Verifier for Tampered {
+        fn verify(&self, _signed: &[u8]) -> std::io::Result<Vec<u8>> {
+            Ok(b"Suite: stable\nAcquire-By-Hash: yes\nSHA256:\n \
+                 157701f399753623bc12b1619fc8c3e9762ba8cff478a141e72f8d8a6fb2176b \
+                 407 main/binary-all/Packages\n"
+                .to_vec())
+        }
+    }
+    let cache = TempDir::new().unwrap();
+    std::fs::write(cache.path().join("main_binary-all_Packages"), "foo")
+        .unwrap();
+    let mut fetcher = RepositoryFetcher::new(&url, "stable", Tampered);
+    fetcher.set_cache_dir(cache.path());
+    assert!(fetcher.update().unwrap());
+    let err = fetcher.fetch_index("main/binary-all/Packages").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    let packages = "/dists/stable/main/binary-all/by-hash/SHA256/\
+                    157701f399753623bc12b1619fc8c3e9762ba8cff478a141e72f8d8a6fb2176b";
+    let packages_gz = "/dists/stable/main/binary-all/by-hash/SHA256/\
+                       bb20afdc0d8930e35d9c8b13e8a518a76571c26cf8b270fd499edca9ee813e6a";
+    let in_release = "/dists/stable/InRelease";
+    assert_eq!(
+        server.join().unwrap(),
+        [
+            in_release,
+            packages_gz,
+            in_release,
+            in_release,
+            packages,
+            in_release,
+            in_release,
+            packages,
+        ]
+    );
+}